@@ -0,0 +1,206 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use strum::{AsRefStr, EnumString};
+use suite_223b::{
+    protocol::SocketData,
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::home_dir,
+    },
+    watson_err,
+};
+
+const DAEMON_BINARY: &str = "watson-daemon";
+/// Where `deployment/` installs the daemon
+const DAEMON_FALLBACK: &str = "/usr/local/bin/watson-daemon";
+
+/// How the session starts Watson
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, AsRefStr)]
+#[strum(serialize_all = "kebab-case")]
+enum Autostart {
+    /// User units started with `graphical-session.target`, restarted by systemd on crashes
+    Systemd,
+    /// XDG autostart entries, for sessions without systemd
+    Desktop,
+}
+impl Autostart {
+    fn detect() -> Self {
+        match Path::new("/run/systemd/system").exists() {
+            true => Self::Systemd,
+            false => Self::Desktop,
+        }
+    }
+}
+
+/// Handles `watson install-autostart [systemd|desktop]`, picking systemd if it runs the system.
+/// Returns false for any other command.
+pub fn autostart_request(args: std::env::Args) -> Result<bool, WatsonError> {
+    let mut args = args.skip(1);
+    if args.next().as_deref() != Some("install-autostart") {
+        return Ok(false);
+    }
+    let kind = match args.next() {
+        Some(kind) => Autostart::from_str(&kind).map_err(|_| {
+            watson_err!(
+                WatsonErrorKind::ConfigError,
+                format!("Unknown autostart {kind}, expected systemd or desktop")
+            )
+        })?,
+        None => Autostart::detect(),
+    };
+
+    let client = std::env::current_exe()
+        .map_err(|e| watson_err!(WatsonErrorKind::FileRead, e.to_string()))?;
+    let daemon = client.with_file_name(DAEMON_BINARY);
+    let daemon = match daemon.exists() {
+        true => daemon,
+        false => PathBuf::from(DAEMON_FALLBACK),
+    };
+
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir()?.join(".config"),
+    };
+    let (dir, files) = match kind {
+        Autostart::Systemd => (
+            config.join("systemd/user"),
+            vec![
+                ("watson-daemon.service", daemon_unit(&daemon)),
+                (
+                    "watson-daemon.socket",
+                    socket_unit(&SocketData::display_name()),
+                ),
+                ("watson.service", client_unit(&client)),
+            ],
+        ),
+        Autostart::Desktop => (
+            config.join("autostart"),
+            vec![
+                (
+                    "watson-daemon.desktop",
+                    desktop_entry("Watson Daemon", &daemon),
+                ),
+                ("watson.desktop", desktop_entry("Watson", &client)),
+            ],
+        ),
+    };
+
+    fs::create_dir_all(&dir).map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))?;
+    for (name, content) in files {
+        let path = dir.join(name);
+        fs::write(&path, content)
+            .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))?;
+        println!("Wrote {}", path.display());
+    }
+    if kind == Autostart::Systemd {
+        println!("Enable them with:");
+        println!("  systemctl --user daemon-reload");
+        println!("  systemctl --user enable --now watson-daemon.socket watson.service");
+    }
+    Ok(true)
+}
+
+/// Listens where clients on `display` look for the daemon, see `SocketData::socket_path`
+fn socket_unit(display: &str) -> String {
+    format!(
+        "\
+[Unit]
+Description=Watson Daemon Socket
+
+[Socket]
+ListenStream=%t/watson/{display}.sock
+SocketMode=0600
+DirectoryMode=0700
+RemoveOnStop=true
+
+[Install]
+WantedBy=sockets.target
+"
+    )
+}
+
+fn daemon_unit(daemon: &Path) -> String {
+    format!(
+        "\
+[Unit]
+Description=Watson Daemon
+Requires=watson-daemon.socket
+After=watson-daemon.socket
+
+[Service]
+Type=notify
+BusName=org.freedesktop.Notifications
+ExecStart={} --replace
+Restart=on-failure
+RestartSec=5s
+
+[Install]
+WantedBy=graphical-session.target
+Also=watson-daemon.socket
+",
+        daemon.display()
+    )
+}
+
+/// Tied to the graphical session, so it stops with it instead of being restarted without a
+/// compositor
+fn client_unit(client: &Path) -> String {
+    format!(
+        "\
+[Unit]
+Description=Watson
+PartOf=graphical-session.target
+Wants=watson-daemon.service
+After=graphical-session.target watson-daemon.service
+
+[Service]
+ExecStart={}
+Restart=on-failure
+RestartSec=2s
+
+[Install]
+WantedBy=graphical-session.target
+",
+        client.display()
+    )
+}
+
+fn desktop_entry(name: &str, exec: &Path) -> String {
+    format!(
+        "\
+[Desktop Entry]
+Type=Application
+Name={name}
+Exec={}
+NoDisplay=true
+X-GNOME-Autostart-enabled=true
+",
+        exec.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units_point_at_the_binaries() {
+        let client = client_unit(Path::new("/opt/watson/watson"));
+        assert!(client.contains("\nExecStart=/opt/watson/watson\n"));
+        assert!(client.contains("Restart=on-failure"));
+
+        let socket = socket_unit("wayland-1");
+        assert!(socket.contains("\nListenStream=%t/watson/wayland-1.sock\n"));
+
+        let daemon = daemon_unit(Path::new("/opt/watson/watson-daemon"));
+        assert!(daemon.contains("\nExecStart=/opt/watson/watson-daemon --replace\n"));
+
+        let entry = desktop_entry("Watson", Path::new("/opt/watson/watson"));
+        assert!(entry.starts_with("[Desktop Entry]\n"));
+        assert!(entry.contains("\nExec=/opt/watson/watson\n"));
+    }
+}
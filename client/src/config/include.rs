@@ -0,0 +1,140 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+use suite_223b::{
+    utils::{
+        errors::{ResultExt, WatsonError, WatsonErrorKind},
+        paths::{expand_path, home_dir},
+    },
+    watson_err,
+};
+
+/// Replaces `{ "include": ["widgets/calendar.json", ...] }` entries of any widget list with the
+/// widgets of the named files. A file holds a single widget or a list of them and may include
+/// further files. Relative paths are resolved against the directory of the including file,
+/// `dir` for the top level.
+pub fn resolve_includes(value: Value, dir: &Path) -> Result<Value, WatsonError> {
+    expand(value, dir, &mut Vec::new())
+}
+
+fn expand(value: Value, dir: &Path, stack: &mut Vec<PathBuf>) -> Result<Value, WatsonError> {
+    match value {
+        Value::Array(items) => {
+            let mut expanded = Vec::with_capacity(items.len());
+            for item in items {
+                match include_paths(&item) {
+                    Some(paths) => {
+                        for path in paths {
+                            match include(path, dir, stack)? {
+                                Value::Array(widgets) => expanded.extend(widgets),
+                                widget => expanded.push(widget),
+                            }
+                        }
+                    }
+                    None => expanded.push(expand(item, dir, stack)?),
+                }
+            }
+            Ok(Value::Array(expanded))
+        }
+        Value::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| Ok((key, expand(value, dir, stack)?)))
+            .collect::<Result<_, WatsonError>>()
+            .map(Value::Object),
+        other => Ok(other),
+    }
+}
+
+/// The paths of an entry that consists of nothing but `include`, a path or a list of them
+fn include_paths(item: &Value) -> Option<Vec<&str>> {
+    let map = item.as_object()?;
+    if map.len() != 1 {
+        return None;
+    }
+    match map.get("include")? {
+        Value::String(path) => Some(vec![path.as_str()]),
+        Value::Array(paths) => paths.iter().map(Value::as_str).collect(),
+        _ => None,
+    }
+}
+
+fn include(path: &str, dir: &Path, stack: &mut Vec<PathBuf>) -> Result<Value, WatsonError> {
+    let path = dir.join(expand_path(path, &home_dir()?));
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("Could not include {}", path.display()))?;
+    if stack.contains(&path) {
+        let chain = stack
+            .iter()
+            .chain(std::iter::once(&path))
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        return Err(watson_err!(
+            WatsonErrorKind::ConfigError,
+            format!("Include cycle: {chain}")
+        ));
+    }
+
+    let file = File::open(&path).with_context(|| format!("Could not open {}", path.display()))?;
+    let value: Value = serde_json::from_reader(BufReader::new(file)).map_err(|e| {
+        watson_err!(
+            WatsonErrorKind::ConfigError,
+            format!("{}: {e}", path.display())
+        )
+    })?;
+
+    let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    stack.push(path);
+    let value = expand(value, &parent, stack);
+    stack.pop();
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    fn layout_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("watson-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_self_include_is_a_cycle() {
+        let dir = layout_dir("include-self");
+        fs::write(dir.join("a.json"), r#"[{ "include": "a.json" }]"#).unwrap();
+
+        let err = resolve_includes(json!([{ "include": "a.json" }]), &dir).unwrap_err();
+        assert_eq!(err.kind, WatsonErrorKind::ConfigError);
+        assert!(err.message.starts_with("Include cycle:"), "{}", err.message);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_mutual_includes_are_a_cycle() {
+        let dir = layout_dir("include-mutual");
+        fs::write(
+            dir.join("a.json"),
+            r#"{ "type": "row", "children": [{ "include": "b.json" }] }"#,
+        )
+        .unwrap();
+        fs::write(dir.join("b.json"), r#"[{ "include": "a.json" }]"#).unwrap();
+
+        let err = resolve_includes(json!([{ "include": "a.json" }]), &dir).unwrap_err();
+        assert_eq!(err.kind, WatsonErrorKind::ConfigError);
+        // a.json -> b.json -> a.json
+        assert_eq!(err.message.matches("a.json").count(), 2, "{}", err.message);
+        assert_eq!(err.message.matches("b.json").count(), 1, "{}", err.message);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,2 +1,11 @@
+mod include;
+mod presets;
+mod schema;
 mod structs;
-pub use structs::{WidgetBase, WidgetOrientation, WidgetSpec, load_config};
+pub use presets::{first_run, init_request};
+pub use schema::schema_request;
+pub use structs::{
+    AstronomyConfig, AutohideConfig, LocaleConfig, PowerConfig, ThemeConfig, WidgetBase,
+    WidgetOrientation, WidgetSpec, WindowConfig, load_astronomy_config, load_config, load_layout,
+    load_layout_file, load_locale_config, load_power_config, load_theme_config, load_window_config,
+};
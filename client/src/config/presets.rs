@@ -0,0 +1,91 @@
+use std::{fs, path::PathBuf, str::FromStr};
+
+use suite_223b::{
+    config::profile::load_merged,
+    utils::{
+        errors::{ResultExt, WatsonError, WatsonErrorKind},
+        paths::get_config_dir,
+    },
+    watson_err,
+};
+
+/// Starter layouts written by `watson --init`
+#[derive(Debug, Clone, Copy, Default, strum::EnumString, strum::AsRefStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum Preset {
+    /// Clock and quick settings
+    Minimal,
+    /// Clock, calendar, quick settings with battery and notifications
+    #[default]
+    Dashboard,
+    /// A single row of small widgets
+    Bar,
+}
+impl Preset {
+    fn layout(self) -> &'static str {
+        match self {
+            Self::Minimal => include_str!("../../resources/presets/minimal.json"),
+            Self::Dashboard => include_str!("../../resources/presets/dashboard.json"),
+            Self::Bar => include_str!("../../resources/presets/bar.json"),
+        }
+    }
+    /// Writes the preset as the default layout, never replacing an existing one
+    pub fn write(self) -> Result<PathBuf, WatsonError> {
+        let path = get_config_dir()?.join("fallback.json");
+        if path.exists() {
+            return Err(watson_err!(
+                WatsonErrorKind::FileExist,
+                format!("{} already exists", path.display())
+            ));
+        }
+        fs::write(&path, self.layout())
+            .with_context(|| format!("Could not write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Handles `watson --init [minimal|dashboard|bar]`: writes a starter layout without starting
+/// the UI. Returns false for any other command.
+pub fn init_request(args: std::env::Args) -> Result<bool, WatsonError> {
+    let mut args = args.skip(1);
+    if args.next().as_deref() != Some("--init") {
+        return Ok(false);
+    }
+
+    let preset = match args.next() {
+        Some(name) => Preset::from_str(&name).map_err(|_| {
+            watson_err!(
+                WatsonErrorKind::ConfigError,
+                format!("Unknown preset {name}, expected minimal, dashboard or bar")
+            )
+        })?,
+        None => Preset::default(),
+    };
+    let path = preset.write()?;
+    println!("Wrote the {} layout to {}", preset.as_ref(), path.display());
+    Ok(true)
+}
+
+/// Writes the default preset if there is no layout yet. Returns its path if it did.
+pub fn first_run() -> Result<Option<PathBuf>, WatsonError> {
+    if load_merged("fallback")?.is_some() {
+        return Ok(None);
+    }
+    Preset::default().write().map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WidgetSpec;
+
+    #[test]
+    fn test_presets_deserialize() {
+        for preset in [Preset::Minimal, Preset::Dashboard, Preset::Bar] {
+            // The `//` notes are ignored like any unknown key
+            let widgets = serde_json::from_str::<Vec<WidgetSpec>>(preset.layout())
+                .unwrap_or_else(|e| panic!("{}.json: {e}", preset.as_ref()));
+            assert!(!widgets.is_empty(), "{}.json is empty", preset.as_ref());
+        }
+    }
+}
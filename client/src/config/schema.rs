@@ -0,0 +1,19 @@
+use schemars::schema_for;
+
+use crate::config::WidgetSpec;
+
+/// Handles `watson --dump-config-schema`: prints the JSON schema of layout files like
+/// `fallback.json`, including defaults and accepted values, so editors can validate them.
+/// Returns false for any other command.
+pub fn schema_request(mut args: std::env::Args) -> bool {
+    if args.nth(1).as_deref() != Some("--dump-config-schema") {
+        return false;
+    }
+
+    let schema = schema_for!(Vec<WidgetSpec>);
+    match serde_json::to_string_pretty(&schema) {
+        Ok(schema) => println!("{schema}"),
+        Err(e) => eprintln!("{:?}", e),
+    }
+    true
+}
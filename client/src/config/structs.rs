@@ -1,17 +1,26 @@
+use gtk4::{Widget, prelude::IsA, prelude::WidgetExt};
+use schemars::JsonSchema;
 use std::fs::File;
-use std::{io::BufReader, path::PathBuf};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
-use suite_223b::utils::errors::{WatsonError, WatsonErrorKind};
+use suite_223b::config::profile::{load_config_file, load_merged};
+use suite_223b::utils::astronomy::Location;
+use suite_223b::utils::errors::{ResultExt, WatsonError, WatsonErrorKind};
+use suite_223b::utils::paths::get_config_dir;
 use suite_223b::watson_err;
 
+use crate::config::include::resolve_includes;
 use crate::ui::widgets::BackendFuncType;
+use crate::ui::widgets::locale::ClockFormat;
 use crate::ui::widgets::{
-    BackendFunc, HandStyle, SliderRange,
+    BackendFunc, ClockComplication, HandStyle, LauncherCommand, SecondHand, SecondaryStyle,
+    SliderRange,
     calendar::types::{CalendarConfig, CalendarHMFormat, CalendarRule},
 };
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 pub struct WidgetBase {
     #[serde(default)]
     pub id: Option<String>,
@@ -23,8 +32,86 @@ pub struct WidgetBase {
     pub valign: Option<AlignmentWrapper>,
     #[serde(default)]
     pub halign: Option<AlignmentWrapper>,
+    /// Only shown while the window is on the first of these monitors that is connected, see
+    /// `WindowConfig::monitor`
+    #[serde(default)]
+    pub monitor: Option<Vec<String>>,
+    /// Seconds between periodic refreshes, replaces the widget's own interval. Stretched while
+    /// saving power, see `PowerConfig`
+    #[serde(default)]
+    pub update_interval: Option<u32>,
+    /// Pixels around the widget, one number for every side or e.g. `{ "top": 8, "start": 4 }`
+    #[serde(default)]
+    pub margin: Option<Margin>,
+    #[serde(default)]
+    pub min_width: Option<i32>,
+    #[serde(default)]
+    pub min_height: Option<i32>,
+    /// Take up the space left in the row or column, widgets pick their own default
+    #[serde(default)]
+    pub hexpand: Option<bool>,
+    #[serde(default)]
+    pub vexpand: Option<bool>,
+    /// Let clicks fall through to whatever is below the window, for decorative widgets like a
+    /// clock on the desktop. Interactive widgets next to it keep receiving them.
+    #[serde(default)]
+    pub click_through: bool,
+}
+impl WidgetBase {
+    /// Margin, minimum size and expansion from the config, set on the outermost widget once it
+    /// was built. What isn't set keeps the widget's own default.
+    pub fn apply_geometry(&self, widget: &impl IsA<Widget>) {
+        if let Some(margin) = self.margin {
+            let (top, end, bottom, start) = margin.sides();
+            widget.set_margin_top(top);
+            widget.set_margin_end(end);
+            widget.set_margin_bottom(bottom);
+            widget.set_margin_start(start);
+        }
+        if let Some(width) = self.min_width {
+            widget.set_width_request(width);
+        }
+        if let Some(height) = self.min_height {
+            widget.set_height_request(height);
+        }
+        if let Some(hexpand) = self.hexpand {
+            widget.set_hexpand(hexpand);
+        }
+        if let Some(vexpand) = self.vexpand {
+            widget.set_vexpand(vexpand);
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Margin {
+    All(i32),
+    Sides {
+        #[serde(default)]
+        top: i32,
+        #[serde(default)]
+        end: i32,
+        #[serde(default)]
+        bottom: i32,
+        #[serde(default)]
+        start: i32,
+    },
+}
+impl Margin {
+    /// Top, end, bottom and start
+    pub fn sides(self) -> (i32, i32, i32, i32) {
+        match self {
+            Self::All(m) => (m, m, m, m),
+            Self::Sides {
+                top,
+                end,
+                bottom,
+                start,
+            } => (top, end, bottom, start),
+        }
+    }
 }
-#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, JsonSchema)]
 pub enum AlignmentWrapper {
     Start,
     End,
@@ -42,7 +129,7 @@ impl From<AlignmentWrapper> for gtk4::Align {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum WidgetSpec {
     Battery {
@@ -77,6 +164,18 @@ pub enum WidgetSpec {
 
         #[serde(default)]
         hm_format: CalendarHMFormat,
+
+        /// Days of events kept before and after today
+        #[serde(default = "default_calendar_prefetch_days")]
+        prefetch_days: u16,
+
+        /// Marks sunrise, sunset and the golden hours on the timeline, see `astronomy.json`
+        #[serde(default)]
+        sun: bool,
+
+        /// Leave out invitations you declined instead of showing them struck through
+        #[serde(default)]
+        hide_declined: bool,
     },
     Clock {
         #[serde(flatten)]
@@ -85,19 +184,56 @@ pub enum WidgetSpec {
         #[serde(default)]
         time_zone: Option<String>,
 
+        /// Shown small next to the main time, e.g. `UTC`
+        #[serde(default)]
+        secondary_time_zone: Option<String>,
+
+        #[serde(default)]
+        secondary_style: SecondaryStyle,
+
         #[serde(default)]
         hand_style: HandStyle,
 
+        #[serde(default)]
+        second_hand: SecondHand,
+
+        /// strftime format of the secondary time and the tooltip, by `clock_format` when unset
+        #[serde(default)]
+        time_format: Option<String>,
+
+        /// Extras on the face, e.g. `["daylight", "moon"]`
+        #[serde(default)]
+        complications: Vec<ClockComplication>,
+
         #[serde(default = "default_accent")]
         accent_color: String,
 
         #[serde(default = "default_font")]
         font: String,
     },
+    Launcher {
+        #[serde(flatten)]
+        base: WidgetBase,
+
+        /// Extra entries next to the installed applications
+        #[serde(default)]
+        commands: Vec<LauncherCommand>,
+
+        #[serde(default = "default_true")]
+        recent_files: bool,
+
+        #[serde(default = "default_launcher_max_results")]
+        max_results: usize,
+    },
     Notifications {
         #[serde(flatten)]
         base: WidgetBase,
     },
+    /// Named scratchpads with text and ink, toggled with `watson toggle notes`
+    Notes {
+        #[serde(flatten)]
+        base: WidgetBase,
+    },
     Button {
         #[serde(flatten)]
         base: WidgetBase,
@@ -108,6 +244,42 @@ pub enum WidgetSpec {
         #[serde(default)]
         icon: Option<String>,
     },
+    /// Wifi, Bluetooth, DND and power mode toggles, volume and brightness sliders and the
+    /// battery in one panel
+    QuickSettings {
+        #[serde(flatten)]
+        base: WidgetBase,
+
+        #[serde(default = "default_quick_settings_spacing")]
+        spacing: i32,
+
+        /// Appended to the toggle grid, e.g. `custom` buttons
+        #[serde(default)]
+        buttons: Vec<WidgetSpec>,
+
+        #[serde(default = "default_true")]
+        battery: bool,
+    },
+    /// Active keyboard layout, cycles layouts on click
+    Keyboard {
+        #[serde(flatten)]
+        base: WidgetBase,
+    },
+    /// Unread mail of the accounts in `mail.json`
+    Mail {
+        #[serde(flatten)]
+        base: WidgetBase,
+    },
+    /// Only shown while an application uses the camera or the microphone
+    Privacy {
+        #[serde(flatten)]
+        base: WidgetBase,
+    },
+    /// Only shown while a screen recording is running
+    Recording {
+        #[serde(flatten)]
+        base: WidgetBase,
+    },
     Row {
         #[serde(flatten)]
         base: WidgetBase,
@@ -141,6 +313,39 @@ pub enum WidgetSpec {
         #[serde(flatten)]
         base: WidgetBase,
     },
+    /// The profiles of the daemon's `focus.json`, to start or end one
+    Focus {
+        #[serde(flatten)]
+        base: WidgetBase,
+    },
+    /// Today's most used applications, needs the daemon's `ScreenTime` service
+    ScreenTime {
+        #[serde(flatten)]
+        base: WidgetBase,
+
+        /// Rows shown at most
+        #[serde(default = "default_screen_time_apps")]
+        apps: usize,
+    },
+    /// Quotes of the symbols in `finance.json`
+    Ticker {
+        #[serde(flatten)]
+        base: WidgetBase,
+
+        /// Only these symbols, all of them when empty
+        #[serde(default)]
+        symbols: Vec<String>,
+    },
+    /// Entities of the Home Assistant instance from the daemon's `homeassistant.json`
+    HomeAssistant {
+        #[serde(flatten)]
+        base: WidgetBase,
+
+        /// Entity ids such as `light.kitchen` or `sensor.outside`, the lights and switches when
+        /// empty
+        #[serde(default)]
+        entities: Vec<String>,
+    },
     Separator {
         #[serde(flatten)]
         base: WidgetBase,
@@ -170,11 +375,22 @@ impl WidgetSpec {
             Calendar,
             Clock,
             Column,
+            Focus,
+            HomeAssistant,
+            Keyboard,
+            Launcher,
+            Mail,
+            Notes,
             Notifications,
+            QuickSettings,
+            Privacy,
+            Recording,
             Row,
+            ScreenTime,
             Separator,
             Slider,
-            Spacer
+            Spacer,
+            Ticker
         ], base => base)
     }
     pub fn id(&self) -> Option<&String> {
@@ -198,6 +414,10 @@ impl WidgetSpec {
                         _ => 0
                     }
                 }
+                Self::QuickSettings { buttons, battery, .. } => {
+                    let battery = if *battery { 1 << 0 } else { 0 };
+                    buttons.iter().map(|c| c.required_services()).fold(battery | 1 << 1, |acc, b| acc | b)
+                }
                 Self::Row { children, .. } => {
                     children.iter().map(|c| c.required_services()).reduce(|acc, b| acc | b).unwrap_or(0)
                 }
@@ -210,9 +430,19 @@ impl WidgetSpec {
                 Button,
                 Calendar,
                 Clock,
+                Focus,
+                HomeAssistant,
+                Keyboard,
+                Launcher,
+                Mail,
+                Notes,
                 Notifications,
+                Privacy,
+                Recording,
+                ScreenTime,
                 Separator,
                 Spacer,
+                Ticker,
             ]
         )
     }
@@ -226,6 +456,7 @@ impl WidgetSpec {
                 hours_past,
                 hours_future,
                 hm_format,
+                sun,
                 ..
             } => CalendarConfig {
                 accent_color,
@@ -233,6 +464,7 @@ impl WidgetSpec {
                 hm_format: Some(hm_format),
                 hours_past: *hours_past,
                 hours_future: *hours_future,
+                sun: *sun,
             },
             _ => CalendarConfig {
                 accent_color: "#e9a949",
@@ -240,8 +472,88 @@ impl WidgetSpec {
                 hm_format: None,
                 hours_past: 2,
                 hours_future: 6,
+                sun: false,
+            },
+        }
+    }
+    /// Expands a `QuickSettings` panel into the column of rows it is made of
+    pub fn into_quick_settings(self) -> Option<WidgetSpec> {
+        const COLUMNS: usize = 4;
+
+        let Self::QuickSettings {
+            base,
+            spacing,
+            buttons,
+            battery,
+        } = self
+        else {
+            return None;
+        };
+
+        let row = |ratio: f32, children: Vec<WidgetSpec>| WidgetSpec::Row {
+            base: WidgetBase {
+                ratio: Some(ratio),
+                ..Default::default()
             },
+            spacing,
+            children,
+        };
+        let button = |func: BackendFunc| WidgetSpec::Button {
+            base: WidgetBase::default(),
+            func,
+            icon: None,
+        };
+        let slider = |func: BackendFunc| WidgetSpec::Slider {
+            base: WidgetBase::default(),
+            func,
+            range: SliderRange::default(),
+            orientation: WidgetOrientation::Horizontal,
+        };
+
+        let mut toggles = vec![
+            button(BackendFunc::Wifi),
+            button(BackendFunc::Bluetooth),
+            button(BackendFunc::Dnd),
+            button(BackendFunc::Powermode),
+        ];
+        toggles.extend(buttons);
+
+        // Pad the last row so every cell keeps the same size
+        while toggles.len() % COLUMNS != 0 {
+            toggles.push(WidgetSpec::Spacer {
+                base: WidgetBase::default(),
+            });
         }
+
+        let mut children = Vec::new();
+        let mut toggles = toggles.into_iter().peekable();
+        while toggles.peek().is_some() {
+            children.push(row(
+                COLUMNS as f32,
+                toggles.by_ref().take(COLUMNS).collect(),
+            ));
+        }
+        children.push(row(6.0, vec![slider(BackendFunc::Volume)]));
+        children.push(row(6.0, vec![slider(BackendFunc::Brightness)]));
+        if battery {
+            children.push(row(
+                6.0,
+                vec![WidgetSpec::Battery {
+                    base: WidgetBase::default(),
+                    colors: default_battery_gradient(),
+                    threshold: default_battery_threshold(),
+                }],
+            ));
+        }
+
+        Some(WidgetSpec::Column {
+            base: WidgetBase {
+                class: base.class.or_else(|| Some("quick-settings".into())),
+                ..base
+            },
+            spacing,
+            children,
+        })
     }
     pub fn as_button(self) -> Option<(WidgetBase, BackendFunc, Option<String>)> {
         if let Self::Button { base, func, icon } = self {
@@ -265,19 +577,147 @@ impl WidgetSpec {
     }
 }
 
+/// `$XDG_CONFIG_HOME/watson/window.json`, how the main window behaves on the desktop
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// Slide off the screen edge while the pointer is elsewhere
+    pub autohide: Option<AutohideConfig>,
+    /// Monitors to put the window on, the first connected one wins. Connector names like
+    /// `DP-1`, part of a monitor's description like `Dell`, or `any`, e.g.
+    /// `["DP-1", "eDP-1", "any"]`. The window follows as monitors come and go.
+    pub monitor: Option<Vec<String>>,
+    /// Leave the window where the compositor puts it, instead of restoring the monitor, size
+    /// and visibility of the last session
+    pub pin: bool,
+    /// Opacity of the whole window from 0 to 1
+    pub opacity: Option<f64>,
+    /// Ask the compositor to blur what is behind the window. Layer-shell has no request for it,
+    /// so the window uses the `watson-blur` namespace for compositor rules to match, e.g.
+    /// Hyprland's `layerrule = blur, watson-blur`.
+    pub blur: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AutohideConfig {
+    /// Milliseconds after the pointer left the window before it slides away
+    #[serde(default = "default_autohide_delay")]
+    pub delay: u64,
+    /// Length of the slide in milliseconds
+    #[serde(default = "default_autohide_duration")]
+    pub duration: u64,
+    /// Pixels left on screen for the pointer to hit
+    #[serde(default = "default_autohide_peek")]
+    pub peek: i32,
+}
+
+/// `$XDG_CONFIG_HOME/watson/theme.json`, colors picked from the wallpaper
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// Replace `--accent` and fill `--palette-<n>` with colors of the wallpaper
+    pub wallpaper_colors: bool,
+    /// Image to sample, GNOME's background from the desktop portal when unset
+    pub wallpaper: Option<PathBuf>,
+    /// Colors in the palette
+    pub palette_size: usize,
+}
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            wallpaper_colors: false,
+            wallpaper: None,
+            palette_size: 5,
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/watson/power.json`, how widgets refresh while running on battery
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PowerConfig {
+    /// Stretch refresh intervals and pause animations while the battery discharges
+    pub power_saving: bool,
+    /// Factor refresh intervals get stretched by
+    pub stretch: u32,
+}
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            power_saving: true,
+            stretch: 4,
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/watson/locale.json`, how dates and times are written
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LocaleConfig {
+    /// 12 or 24 hours for times without an explicit format, the locale's on `auto`
+    pub clock_format: ClockFormat,
+}
+
+/// `$XDG_CONFIG_HOME/watson/astronomy.json`, where to compute the sun and the moon for
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AstronomyConfig {
+    /// e.g. `{ "latitude": 52.5, "longitude": 13.4 }`, asks GeoClue when unset
+    pub location: Option<Location>,
+}
+
 pub fn load_config() -> Result<Vec<WidgetSpec>, WatsonError> {
-    let home = std::env::var("HOME").unwrap();
-    let loc = PathBuf::from(home).join(".config/watson/fallback.json");
+    load_layout(None)
+}
 
-    let file =
-        File::open(loc).map_err(|e| watson_err!(WatsonErrorKind::FileOpen, e.to_string()))?;
+/// Widgets of the layout `name`, read from `$XDG_CONFIG_HOME/watson/<name>.json` or the
+/// active profile, with includes resolved. `None` is the default layout.
+pub fn load_layout(name: Option<&str>) -> Result<Vec<WidgetSpec>, WatsonError> {
+    let name = name.unwrap_or("fallback");
+    let value = load_merged(name)?.ok_or_else(|| {
+        watson_err!(
+            WatsonErrorKind::FileOpen,
+            format!("Layout {name}.json not found")
+        )
+    })?;
+    let value = resolve_includes(value, &get_config_dir()?)?;
 
-    let reader = BufReader::new(file);
+    serde_json::from_value::<Vec<WidgetSpec>>(value)
+        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))
+}
+
+/// Widgets of the layout at `path`, without profile overrides. Includes are resolved relative
+/// to the file.
+pub fn load_layout_file(path: &Path) -> Result<Vec<WidgetSpec>, WatsonError> {
+    let file = File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+    let value = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+    let value = resolve_includes(value, path.parent().unwrap_or(Path::new(".")))?;
 
-    serde_json::from_reader::<_, Vec<WidgetSpec>>(reader)
+    serde_json::from_value::<Vec<WidgetSpec>>(value)
         .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))
 }
 
+pub fn load_window_config() -> Result<WindowConfig, WatsonError> {
+    load_config_file("window")
+}
+
+pub fn load_theme_config() -> Result<ThemeConfig, WatsonError> {
+    load_config_file("theme")
+}
+
+pub fn load_power_config() -> Result<PowerConfig, WatsonError> {
+    load_config_file("power")
+}
+
+pub fn load_locale_config() -> Result<LocaleConfig, WatsonError> {
+    load_config_file("locale")
+}
+
+pub fn load_astronomy_config() -> Result<AstronomyConfig, WatsonError> {
+    load_config_file("astronomy")
+}
+
 fn default_font() -> String {
     "Arial".into()
 }
@@ -290,6 +730,18 @@ fn default_calendar_hours_past() -> u8 {
 fn default_calendar_hours_fut() -> u8 {
     8
 }
+fn default_calendar_prefetch_days() -> u16 {
+    7
+}
+fn default_quick_settings_spacing() -> i32 {
+    10
+}
+fn default_true() -> bool {
+    true
+}
+fn default_launcher_max_results() -> usize {
+    8
+}
 fn default_battery_gradient() -> [String; 3] {
     [
         "#68A357".to_string(),
@@ -300,8 +752,22 @@ fn default_battery_gradient() -> [String; 3] {
 fn default_battery_threshold() -> u8 {
     40
 }
+fn default_autohide_delay() -> u64 {
+    800
+}
+fn default_autohide_duration() -> u64 {
+    200
+}
+fn default_autohide_peek() -> i32 {
+    2
+}
+fn default_screen_time_apps() -> usize {
+    5
+}
 
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq, strum::Display)]
+#[derive(
+    Debug, Clone, Copy, Deserialize, Serialize, JsonSchema, Default, PartialEq, Eq, strum::Display,
+)]
 #[serde(rename_all = "lowercase")]
 #[strum(serialize_all = "lowercase")]
 pub enum WidgetOrientation {
@@ -1,30 +1,20 @@
-use bincode::config;
-use std::mem::discriminant;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant};
-use suite_223b::protocol::{AtomicSystemState, Request, Response, SocketData, UpdateField};
-use suite_223b::tokio::{AsyncSizedMessage, SizedMessageObj};
-use suite_223b::utils::errors::{WatsonError, WatsonErrorKind};
-use suite_223b::watson_err;
-use tokio::net::UnixStream;
-use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use suite_223b::protocol::{AtomicSystemState, Request, Response, UpdateField};
+use suite_223b::utils::errors::WatsonError;
 use tokio::sync::{Notify, broadcast, mpsc};
+use watson_client::Engine;
 
 pub struct ClientConnection {
-    writer: OwnedWriteHalf,
-    reader: OwnedReadHalf,
+    engine: Engine,
 }
 
-#[allow(dead_code)]
 impl ClientConnection {
     pub async fn new() -> Result<Self, WatsonError> {
-        let stream = UnixStream::connect(SocketData::SOCKET_ADDR)
-            .await
-            .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
-        let (reader, writer) = stream.into_split();
-
-        Ok(Self { reader, writer })
+        Ok(Self {
+            engine: Engine::connect().await?,
+        })
     }
     pub async fn spawn_engine(
         self,
@@ -32,76 +22,80 @@ impl ClientConnection {
         state: Arc<AtomicSystemState>,
         notify: Arc<Notify>,
     ) -> Result<mpsc::UnboundedSender<Request>, WatsonError> {
-        let (mut reader, mut writer) = (self.reader, self.writer);
-        let (request_tx, mut request_rx) = mpsc::unbounded_channel::<Request>();
+        let handle = self.engine.spawn();
+        let mut responses = handle.subscribe();
 
-        // 1. Task for WRITING to the Daemon
+        // Keeps the atomics current and forwards everything else to the widgets
         tokio::spawn(async move {
-            while let Some(req) = request_rx.recv().await {
-                let mut latest_msg = req;
+            let mut throttle = Throttle::new(60);
+            loop {
+                let v = match responses.recv().await {
+                    Ok(v) => v,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                match v {
+                    Response::VolumeState { percentage } => {
+                        state.volume.store(percentage, Ordering::Relaxed);
+                        state
+                            .updated
+                            .fetch_or(1 << UpdateField::Volume as u8, Ordering::Relaxed);
 
-                // Drain the channel
-                while let Ok(next_msg) = request_rx.try_recv() {
-                    if discriminant(&next_msg) == discriminant(&latest_msg) {
-                        latest_msg = next_msg;
-                    } else {
-                        if let Ok(buf) = SizedMessageObj::from_struct(&latest_msg) {
-                            let _ = writer.write_sized(buf).await;
+                        if throttle.can_notify() {
+                            notify.notify_one();
                         }
-                        latest_msg = next_msg;
                     }
-                }
+                    Response::NightLightState { enabled, intensity } => {
+                        state.night_light.store(enabled, Ordering::Relaxed);
+                        state
+                            .night_light_intensity
+                            .store(intensity, Ordering::Relaxed);
+                        state
+                            .updated
+                            .fetch_or(1 << UpdateField::NightLight as u8, Ordering::Relaxed);
+                        notify.notify_one();
+                    }
+                    Response::Connectivity(info) => {
+                        state.set_connectivity(info.clone());
+                        // The wifi button swaps its icon for wired connections
+                        let _result = response_tx.send(Response::Connectivity(info));
+                    }
+                    Response::PowerProfiles(profiles) => {
+                        state.set_power_profiles(profiles.clone());
+                        notify.notify_one();
+                        // The powermode button explains degraded performance
+                        let _result = response_tx.send(Response::PowerProfiles(profiles));
+                    }
+                    Response::SystemState(s) => {
+                        state.update_from_state(s);
 
-                if let Ok(buf) = SizedMessageObj::from_struct(&latest_msg) {
-                    if let Err(e) = writer.write_sized(buf).await {
-                        eprintln!("Failed to write to daemon: {:?}", e);
-                        break;
+                        if throttle.can_notify() {
+                            notify.notify_one();
+                        }
+                    }
+                    _ => {
+                        let _result = response_tx.send(v);
                     }
                 }
             }
         });
-        // 2. Task for READING from the Daemon
-        tokio::spawn(async move {
-            let mut throttle = Throttle::new(60);
-            let config = config::standard();
 
-            loop {
-                match reader.read_sized().await {
-                    Ok(buf) => {
-                        if let Ok((v, _)) =
-                            bincode::serde::decode_from_slice::<Response, _>(&buf, config)
-                        {
-                            match v {
-                                Response::VolumeState { percentage } => {
-                                    state.volume.store(percentage, Ordering::Relaxed);
-                                    state.updated.fetch_or(
-                                        1 << UpdateField::Volume as u8,
-                                        Ordering::Relaxed,
-                                    );
-
-                                    if throttle.can_notify() {
-                                        notify.notify_one();
-                                    }
-                                }
-                                Response::SystemState(s) => {
-                                    state.update_from_state(s);
-
-                                    if throttle.can_notify() {
-                                        notify.notify_one();
-                                    }
-                                }
-                                _ => {
-                                    let _result = response_tx.send(v);
-                                }
-                            }
-                        }
+        // A restarted daemon doesn't broadcast what changed while it was gone
+        tokio::spawn({
+            let handle = handle.clone();
+            async move {
+                let mut connected = handle.connected();
+                while connected.changed().await.is_ok() {
+                    if *connected.borrow_and_update() {
+                        handle.send(Request::SystemState);
+                        handle.send(Request::DockState);
+                        handle.send(Request::Focus);
                     }
-                    Err(_) => break,
                 }
             }
         });
 
-        Ok(request_tx) // Return this so the UI can send Pings, etc.
+        Ok(handle.sender()) // Return this so the UI can send Pings, etc.
     }
 }
 
@@ -0,0 +1,323 @@
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+use suite_223b::{
+    protocol::{
+        DataKind, FocusState, Request, Response, SocketData, Surface, SurfaceAction, SystemStateRaw,
+    },
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{UnixListener, UnixStream},
+    sync::mpsc::UnboundedSender,
+};
+use watson_client::Connection;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceMode {
+    /// Raise the running instance if there is one
+    #[default]
+    Default,
+    /// Quit the running instance and take its place
+    Replace,
+    /// Hide or show the running instance
+    Toggle,
+    /// Switch the running instance in or out of edit mode
+    Edit,
+}
+impl InstanceMode {
+    pub fn from_args(args: std::env::Args) -> Self {
+        let mut mode = Self::default();
+        for arg in args.skip(1) {
+            match arg.as_str() {
+                "--replace" => mode = Self::Replace,
+                "--toggle" => mode = Self::Toggle,
+                "--edit" => mode = Self::Edit,
+                _ => {}
+            }
+        }
+        mode
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceCommand {
+    Show = 0,
+    Toggle = 1,
+    Quit = 2,
+    Hide = 3,
+    Edit = 4,
+}
+impl TryFrom<u8> for InstanceCommand {
+    type Error = ();
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Show),
+            1 => Ok(Self::Toggle),
+            2 => Ok(Self::Quit),
+            3 => Ok(Self::Hide),
+            4 => Ok(Self::Edit),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<SurfaceAction> for InstanceCommand {
+    fn from(action: SurfaceAction) -> Self {
+        match action {
+            SurfaceAction::Show => Self::Show,
+            SurfaceAction::Hide => Self::Hide,
+            SurfaceAction::Toggle => Self::Toggle,
+        }
+    }
+}
+
+/// Parses `watson <show|hide|toggle> <surface>` into the request relayed by the daemon.
+pub fn surface_request(args: std::env::Args) -> Option<Request> {
+    let mut args = args.skip(1);
+    let action = SurfaceAction::from_str(&args.next()?).ok()?;
+    let surface = args
+        .next()
+        .and_then(|s| Surface::from_str(&s).ok())
+        .unwrap_or(Surface::Window);
+
+    Some(match action {
+        SurfaceAction::Show => Request::ShowSurface(surface),
+        SurfaceAction::Hide => Request::HideSurface(surface),
+        SurfaceAction::Toggle => Request::ToggleSurface(surface),
+    })
+}
+
+/// Parses `watson refresh [calendar|mail|quotes|all]`, refreshing everything without a kind.
+pub fn refresh_request(args: std::env::Args) -> Option<Request> {
+    let mut args = args.skip(1);
+    if args.next()? != "refresh" {
+        return None;
+    }
+    let kind = match args.next() {
+        Some(kind) => DataKind::from_str(&kind).ok()?,
+        None => DataKind::All,
+    };
+    Some(Request::ForceRefresh(kind))
+}
+
+/// Parses `watson notify <summary> [body]`, stored by the daemon with the other notifications.
+pub fn notify_request(args: std::env::Args) -> Option<Request> {
+    let mut args = args.skip(1);
+    if args.next()? != "notify" {
+        return None;
+    }
+    Some(Request::Notify {
+        summary: args.next()?,
+        body: args.next().unwrap_or_default(),
+        icon: String::new(),
+        timeout: -1,
+        actions: Vec::new(),
+    })
+}
+
+/// Sends a single request to the daemon without starting the UI.
+pub async fn send_oneshot(req: &Request) -> Result<(), WatsonError> {
+    Connection::connect().await?.send(req).await
+}
+
+/// Handles `watson status`: prints the daemon's system state without starting the UI.
+/// Returns false for any other command.
+pub async fn status_request(mut args: std::env::Args) -> Result<bool, WatsonError> {
+    if args.nth(1).as_deref() != Some("status") {
+        return Ok(false);
+    }
+
+    let state = Connection::connect().await?.system_state().await?;
+    print_status(&state);
+    Ok(true)
+}
+
+/// Handles `watson dump-state [file]`, writing the daemon's state as JSON to the file or stdout.
+/// A daemon started with `--load-state <file>` takes it up again.
+pub async fn dump_state_request(mut args: std::env::Args) -> Result<bool, WatsonError> {
+    if args.nth(1).as_deref() != Some("dump-state") {
+        return Ok(false);
+    }
+    let path = args.next();
+
+    let snapshot = Connection::connect().await?.dump_state().await?;
+    let json = serde_json::to_string_pretty(&snapshot)
+        .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+    match path {
+        Some(path) => {
+            std::fs::write(&path, json)
+                .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))?;
+            println!("Wrote {}", path);
+        }
+        None => println!("{}", json),
+    }
+    Ok(true)
+}
+
+/// Handles `watson focus [<profile>|off]`: starts or ends a focus profile, or lists the profiles
+/// without an argument. Returns false for any other command.
+pub async fn focus_request(mut args: std::env::Args) -> Result<bool, WatsonError> {
+    if args.nth(1).as_deref() != Some("focus") {
+        return Ok(false);
+    }
+    let request = match args.next() {
+        None => Request::Focus,
+        Some(name) if name == "off" => Request::SetFocus(None),
+        Some(name) => Request::SetFocus(Some(name)),
+    };
+
+    // Broadcasts may arrive before the answer, including the state of other focus changes
+    let listing = matches!(request, Request::Focus);
+    let state = Connection::connect()
+        .await?
+        .ask(&request, |r| match r {
+            Response::Focus(state) if listing => Some(Some(state)),
+            Response::Ok => Some(None),
+            _ => None,
+        })
+        .await?;
+    if let Some(state) = state {
+        print_focus(&state);
+    }
+    Ok(true)
+}
+
+/// Handles `watson trace`: prints what the other clients and the daemon send each other until
+/// interrupted. Returns false for any other command.
+pub async fn trace_request(mut args: std::env::Args) -> Result<bool, WatsonError> {
+    if args.nth(1).as_deref() != Some("trace") {
+        return Ok(false);
+    }
+
+    let mut daemon = Connection::connect().await?;
+    daemon.send(&Request::TraceProtocol).await?;
+
+    // Broadcasts reach this connection as usual too, only the traces are printed
+    loop {
+        if let Response::Trace(event) = daemon.recv().await? {
+            println!("{}", event);
+        }
+    }
+}
+
+fn print_focus(state: &FocusState) {
+    if state.profiles.is_empty() {
+        println!("No focus profiles, add them to focus.json");
+    }
+    for profile in &state.profiles {
+        let marker = if state.active.as_ref() == Some(profile) {
+            "*"
+        } else {
+            " "
+        };
+        println!("{} {}", marker, profile);
+    }
+}
+
+fn print_status(state: &SystemStateRaw) {
+    let on_off = |v: bool| if v { "on" } else { "off" };
+    println!("wifi:        {}", on_off(state.wifi));
+    println!("bluetooth:   {}", on_off(state.bluetooth));
+    println!("brightness:  {}%", state.brightness);
+    println!("volume:      {}%", state.volume);
+    println!(
+        "night light: {} ({}%)",
+        on_off(state.night_light),
+        state.night_light_intensity
+    );
+    match &state.connectivity.primary {
+        Some(info) => {
+            println!("connection:  {}", info.id);
+            if let Some(ssid) = &info.ssid {
+                println!("ssid:        {}", ssid);
+            }
+            if info.speed > 0 {
+                println!("speed:       {} Mb/s", info.speed);
+            }
+            for address in info.ipv4.iter().chain(&info.ipv6) {
+                println!("address:     {}", address);
+            }
+        }
+        None => println!("connection:  disconnected"),
+    }
+    for device in &state.connectivity.wired {
+        match device.carrier {
+            true => println!("{}: {} Mb/s", device.interface, device.speed),
+            false => println!("{}: unplugged", device.interface),
+        }
+    }
+}
+
+/// Single-instance guard backed by a control socket.
+///
+/// Holding the lock means owning `SocketData::client_socket_path`. Other instances connect to it
+/// to forward their command instead of opening a second window.
+pub struct InstanceLock {
+    listener: UnixListener,
+    path: PathBuf,
+}
+impl InstanceLock {
+    /// Returns `None` if the command was forwarded to a running instance and this process should
+    /// exit.
+    pub async fn acquire(mode: InstanceMode) -> Result<Option<Self>, WatsonError> {
+        let path = SocketData::client_socket_path()?;
+        if let Ok(mut stream) = UnixStream::connect(&path).await {
+            let cmd = match mode {
+                InstanceMode::Default => InstanceCommand::Show,
+                InstanceMode::Toggle => InstanceCommand::Toggle,
+                InstanceMode::Replace => InstanceCommand::Quit,
+                InstanceMode::Edit => InstanceCommand::Edit,
+            };
+            stream
+                .write_u8(cmd as u8)
+                .await
+                .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))?;
+
+            if mode != InstanceMode::Replace {
+                return Ok(None);
+            }
+
+            // Wait for the old instance to hang up before taking over
+            let mut buf = [0u8; 1];
+            let _ = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut buf)).await;
+        }
+
+        // Either nobody is listening or the old instance is gone; the socket file is stale
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamBind, e.to_string()))?;
+
+        Ok(Some(Self { listener, path }))
+    }
+
+    /// Forwards commands from other instances to `tx`.
+    pub fn listen(self, tx: UnboundedSender<InstanceCommand>) {
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = self.listener.accept().await {
+                let Ok(byte) = stream.read_u8().await else {
+                    continue;
+                };
+                let Ok(cmd) = InstanceCommand::try_from(byte) else {
+                    continue;
+                };
+                if tx.send(cmd).is_err() {
+                    break;
+                }
+                if cmd == InstanceCommand::Quit {
+                    // Release the socket before hanging up so the new instance can bind it
+                    drop(self);
+                    drop(stream);
+                    break;
+                }
+            }
+        });
+    }
+}
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use suite_223b::{
+    auth::CredentialManager,
+    calendar::{
+        invite::Invitation,
+        protocol::CalendarProvider,
+        utils::{CalendarInfo, structs::Partstat},
+    },
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::home_dir,
+    },
+    watson_err,
+};
+
+use crate::ui::widgets::utils::locale::{self, tr};
+
+const HANDLER_FILE: &str = "watson-invite.desktop";
+
+/// Handles `watson install-invite-handler`, offering `watson invite` to open `.ics` files.
+/// Returns false for any other command.
+pub fn invite_handler_request(args: std::env::Args) -> Result<bool, WatsonError> {
+    if args.skip(1).next().as_deref() != Some("install-invite-handler") {
+        return Ok(false);
+    }
+    let client = std::env::current_exe()
+        .map_err(|e| watson_err!(WatsonErrorKind::FileRead, e.to_string()))?;
+    let dir = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir()?.join(".local/share"),
+    }
+    .join("applications");
+
+    fs::create_dir_all(&dir).map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))?;
+    let path = dir.join(HANDLER_FILE);
+    fs::write(&path, handler_entry(&client))
+        .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))?;
+    println!("Wrote {}", path.display());
+    println!("Make it the default for invitations with:");
+    println!("  xdg-mime default {HANDLER_FILE} text/calendar");
+    Ok(true)
+}
+
+fn handler_entry(client: &Path) -> String {
+    format!(
+        "\
+[Desktop Entry]
+Type=Application
+Name=Watson Invitation
+Exec={} invite %f
+MimeType=text/calendar;application/ics;
+NoDisplay=true
+",
+        client.display()
+    )
+}
+
+/// An account the invitation was sent to
+struct Invitee {
+    provider: Box<dyn CalendarProvider>,
+    calendar: CalendarInfo,
+    address: String,
+}
+
+/// Handles `watson invite <file.ics>`: asks whether to accept the invitation and answers it
+/// through the account it was sent to. Returns false for any other command.
+pub async fn invite_request(args: std::env::Args) -> Result<bool, WatsonError> {
+    let mut args = args.skip(1);
+    if args.next().as_deref() != Some("invite") {
+        return Ok(false);
+    }
+    let path = args.next().ok_or_else(|| {
+        watson_err!(
+            WatsonErrorKind::InvalidAttribute,
+            "Usage: watson invite <file.ics>"
+        )
+    })?;
+    let invitation = Invitation::load(Path::new(&path))?;
+    let Invitee {
+        mut provider,
+        calendar,
+        address,
+    } = invitee(&invitation).await?;
+
+    let Some(answer) = ask(&invitation, &address) else {
+        return Ok(true);
+    };
+    provider
+        .respond(&calendar, invitation, &address, answer)
+        .await?;
+    println!("Answered as {address} in {}", calendar.name);
+    Ok(true)
+}
+
+/// The first account whose address is among the attendees, with the calendar to add the event
+/// to
+async fn invitee(invitation: &Invitation) -> Result<Invitee, WatsonError> {
+    let mut credential_manager = CredentialManager::new()?;
+    credential_manager.unlock()?;
+
+    for mut provider in credential_manager
+        .credentials
+        .into_iter()
+        .filter_map(|account| account.provider())
+    {
+        let calendars = match provider.init().await {
+            Ok(()) => provider.get_calendars().await,
+            Err(e) => Err(e),
+        };
+        let calendars = match calendars {
+            Ok(calendars) => calendars,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                continue;
+            }
+        };
+        let invited = calendars.into_iter().find_map(|calendar| {
+            let address = invitation
+                .attendee(calendar.owner.as_deref())?
+                .email
+                .clone()?;
+            Some((calendar, address))
+        });
+        if let Some((calendar, address)) = invited {
+            return Ok(Invitee {
+                provider,
+                calendar,
+                address,
+            });
+        }
+    }
+    Err(watson_err!(
+        WatsonErrorKind::InvalidData,
+        "None of the accounts is among the attendees"
+    ))
+}
+
+/// The user's answer, `None` if the dialog was dismissed
+fn ask(invitation: &Invitation, address: &str) -> Option<Partstat> {
+    let event = &invitation.event;
+    let mut detail = Vec::new();
+    if let Some(start) = &event.start {
+        let fmt = format!("{} {}", locale::day_format(), locale::time_format());
+        detail.push(locale::format_datetime(&start.local(), &fmt));
+    }
+    if let Some(location) = &event.location {
+        detail.push(location.clone());
+    }
+    if let Some(organizer) = &event.organizer {
+        detail.push(format!(
+            "{}: {}",
+            tr("Organizer"),
+            organizer.trim_start_matches("mailto:")
+        ));
+    }
+    detail.push(format!(
+        "{}: {}",
+        tr("Invited as"),
+        address.trim_start_matches("mailto:")
+    ));
+
+    let dialog = gtk4::AlertDialog::builder()
+        .modal(true)
+        .message(event.title.as_str())
+        .detail(detail.join("\n"))
+        .buttons([tr("Decline"), tr("Maybe"), tr("Accept")])
+        .default_button(2)
+        .build();
+    // The dialog needs the GTK main loop, which only runs once the window exists
+    let choice = gtk4::glib::MainContext::default()
+        .block_on(dialog.choose_future(None::<&gtk4::Window>))
+        .ok()?;
+    match choice {
+        0 => Some(Partstat::Declined),
+        1 => Some(Partstat::Tentative),
+        2 => Some(Partstat::Accepted),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handler_opens_calendar_files() {
+        let entry = handler_entry(Path::new("/opt/watson/watson"));
+        assert!(entry.contains("\nExec=/opt/watson/watson invite %f\n"));
+        assert!(entry.contains("\nMimeType=text/calendar;"));
+    }
+}
@@ -8,30 +8,61 @@ use std::{
 };
 
 use crate::{
-    config::{WidgetSpec, load_config},
+    autostart::autostart_request,
+    config::{
+        WidgetSpec, first_run, init_request, load_astronomy_config, load_config, load_layout,
+        load_layout_file, load_locale_config, load_power_config, load_theme_config, schema_request,
+    },
     connection::ClientConnection,
+    instance::{
+        InstanceCommand, InstanceLock, InstanceMode, dump_state_request, focus_request,
+        notify_request, refresh_request, send_oneshot, status_request, surface_request,
+        trace_request,
+    },
+    invite::{invite_handler_request, invite_request},
     ui::{
         WatsonUi,
+        edit::{LayoutEditor, LayoutSource},
+        g_templates::{main_window::Toast, snapshot_area::SnapshotArea},
+        location,
+        output::{connect_monitor_changed, current_monitor, pick_monitor},
+        preview::{outline_widgets, preview_request, watch_layout},
+        theme::{Appearance, Theming},
         utils::icon_loader::{CustomIconTheme, IconThemeGuard},
-        widgets::{BackendFuncType, Battery, NotificationCentre, WatsonWidget, create_widgets},
+        widgets::{
+            BackendFuncType, Battery, NOTIFICATION_PAGE, NotificationCentre, StateClass,
+            WatsonWidget, create_widgets, locale, pending, power, set_suspended,
+        },
+        window::connect_input_region,
     },
 };
 use gtk4::{
     CssProvider, DrawingArea,
-    gdk::Display,
-    glib::{WeakRef, object::ObjectExt, subclass::types::ObjectSubclassIsExt},
-    prelude::{GtkWindowExt, WidgetExt},
+    gdk::{Display, Monitor},
+    glib::{
+        WeakRef,
+        object::{Cast, ObjectExt},
+        subclass::types::ObjectSubclassIsExt,
+    },
+    prelude::{BoxExt, GtkWindowExt, WidgetExt},
 };
 use suite_223b::{
     config::flags::ArgParse,
     notification::Notification,
-    protocol::{AtomicSystemState, Request, Response, UpdateField},
-    utils::errors::WatsonError,
+    protocol::{
+        AtomicSystemState, DataKind, DisplayBrightness, DockState, FocusState, Request, Response,
+        SessionState, Surface, SurfaceAction, UpdateField,
+    },
+    utils::{crash, errors::WatsonError},
 };
-use tokio::sync::{Notify, broadcast, mpsc::UnboundedSender};
+use tokio::sync::{Notify, broadcast, mpsc, mpsc::UnboundedSender};
 
+mod autostart;
 mod config;
 mod connection;
+mod instance;
+mod invite;
+mod startup;
 mod ui;
 
 static DAEMON_TX: OnceLock<UnboundedSender<Request>> = OnceLock::new();
@@ -39,16 +70,58 @@ static ICONS: OnceCell<RwLock<CustomIconTheme>> = OnceCell::new();
 
 #[tokio::main]
 async fn main() -> Result<(), WatsonError> {
+    crash::install("watson", env!("CARGO_PKG_VERSION"));
+    // Don't need a display
+    if schema_request(std::env::args())
+        || init_request(std::env::args())?
+        || autostart_request(std::env::args())?
+        || invite_handler_request(std::env::args())?
+    {
+        return Ok(());
+    }
+    startup::trace_from_args(std::env::args());
+    let gtk = startup::span("gtk init");
     gtk4::init().expect("Failed to init GTK");
+    drop(gtk);
+    if invite_request(std::env::args()).await? {
+        return Ok(());
+    }
     let main_loop = gtk4::glib::MainLoop::new(None, false);
 
     let (tx, rx) = broadcast::channel::<Response>(64);
 
     let _ = ArgParse::parse(std::env::args()).await;
+
+    if let Some(req) = surface_request(std::env::args())
+        .or_else(|| refresh_request(std::env::args()))
+        .or_else(|| notify_request(std::env::args()))
+    {
+        return send_oneshot(&req).await;
+    }
+    if status_request(std::env::args()).await?
+        || dump_state_request(std::env::args()).await?
+        || focus_request(std::env::args()).await?
+        || trace_request(std::env::args()).await?
+    {
+        return Ok(());
+    }
+
+    // Previews run next to the real instance
+    let preview = preview_request(std::env::args());
+    let mode = InstanceMode::from_args(std::env::args());
+    let instance = match preview {
+        Some(_) => None,
+        None => match InstanceLock::acquire(mode).await? {
+            Some(instance) => Some(instance),
+            // Another instance handled the request
+            None => return Ok(()),
+        },
+    };
     let state = Rc::new(RefCell::new(WatsonState::new()));
 
     let notify = Arc::new(Notify::new());
 
+    let connect = startup::span("daemon connection");
     let client = ClientConnection::new().await?;
     DAEMON_TX
         .set(
@@ -61,10 +134,22 @@ async fn main() -> Result<(), WatsonError> {
                 .await?,
         )
         .expect("DAEMON_TX already set");
+    drop(connect);
 
+    let config_span = startup::span("config");
     let notification_store = Rc::new(RefCell::new(NotificationStore::new()));
 
-    let config = load_config()?;
+    let (config, created) = match &preview {
+        Some(path) => (load_layout_file(path)?, None),
+        None => {
+            // Start from a preset on first launch
+            let created = first_run().unwrap_or_else(|e| {
+                eprintln!("{:?}", e);
+                None
+            });
+            (load_config()?, created)
+        }
+    };
 
     gtk4::gio::resources_register_include!("/resources.gresources")
         .expect("Failed to find resources injo OUT_DIR");
@@ -87,11 +172,40 @@ async fn main() -> Result<(), WatsonError> {
         // Return ControlFlow::Break so it only runs once
         gtk4::glib::ControlFlow::Break
     });
+    Appearance::start();
+    match load_theme_config() {
+        Ok(config) => Theming::start(config),
+        Err(e) => eprintln!("{:?}", e),
+    }
+    match load_power_config() {
+        Ok(config) => power::configure(&config),
+        Err(e) => eprintln!("{:?}", e),
+    }
+    match load_locale_config() {
+        Ok(config) => locale::configure(&config),
+        Err(e) => eprintln!("{:?}", e),
+    }
+    match load_astronomy_config() {
+        Ok(config) => location::configure(&config),
+        Err(e) => eprintln!("{:?}", e),
+    }
+    drop(config_span);
+
+    // Commands from other instances
+    let (instance_tx, mut instance_rx) = mpsc::unbounded_channel::<InstanceCommand>();
+    // Failed requests, shown once the window exists
+    let (toast_tx, mut toast_rx) = mpsc::unbounded_channel::<Toast>();
+    // Layouts the dock config asks for
+    let (layout_tx, mut layout_rx) = mpsc::unbounded_channel::<Option<String>>();
 
     // Listen async for server responses/notifications
     let ui_ready = Rc::new(Notify::new());
     gtk4::glib::spawn_future_local({
         let mut rx = rx.resubscribe();
+        let instance_tx = instance_tx.clone();
+        let layout_tx = layout_tx.clone();
+        let toast_tx = toast_tx.clone();
+        let previewing = preview.is_some();
         let state = Rc::clone(&state);
         let store = Rc::clone(&notification_store);
         let ui_ready = Rc::clone(&ui_ready);
@@ -105,6 +219,7 @@ async fn main() -> Result<(), WatsonError> {
                         let mask = state_ref.system_state.updated.swap(0, std::sync::atomic::Ordering::Relaxed);
                         if mask & (1 << UpdateField::Init as u8) != 0 {
                             ui_ready.notify_one();
+                            state_ref.refresh_controls();
                         }
                         if mask & (1 << UpdateField::Wifi as u8) != 0 {
                             state_ref.notify_update(BackendFuncType::Wifi);
@@ -125,6 +240,11 @@ async fn main() -> Result<(), WatsonError> {
                         if mask & (1 << UpdateField::Volume as u8) != 0 {
                             state_ref.notify_update(BackendFuncType::Volume);
                         }
+
+                        if mask & (1 << UpdateField::NightLight as u8) != 0 {
+                            state_ref.notify_update(BackendFuncType::NightLight);
+                            state_ref.notify_update(BackendFuncType::NightLightIntensity);
+                        }
                     }
                     Ok(msg) = rx.recv() => {
                         match msg {
@@ -136,20 +256,263 @@ async fn main() -> Result<(), WatsonError> {
                                     bat.update_state(s, p);
                                     bat.queue_draw();
                                 });
+                                // Catch up on what the stretched timers skipped
+                                if power::battery_changed(s) && !power::is_saving_power() {
+                                    state.borrow().redraw_all();
+                                }
                             }
                             Response::Notification(Some(notification)) => {
                                 let rc = Rc::new(notification);
                                 state.borrow().notification_centres().for_each(|c| {
                                     c.insert(rc.clone());
                                 });
-                                store.borrow_mut().notifications.push(rc);
+                                store.borrow_mut().insert(rc);
+                            }
+                            Response::NotificationClosed { id, .. } => {
+                                state.borrow().notification_centres().for_each(|c| {
+                                    c.remove(id);
+                                });
+                                store.borrow_mut().notifications.retain(|n| n.id != id);
+                            }
+                            Response::MissedNotifications(missed) => {
+                                let state_ref = state.borrow();
+                                state_ref.system_state.missed.store(missed, std::sync::atomic::Ordering::Relaxed);
+                                state_ref.refresh_buttons(BackendFuncType::Dnd);
+                            }
+                            Response::DndEnded { missed } => {
+                                let state_ref = state.borrow();
+                                state_ref.system_state.missed.store(0, std::sync::atomic::Ordering::Relaxed);
+                                state_ref.refresh_buttons(BackendFuncType::Dnd);
+                                let text = match missed {
+                                    1 => locale::tr("notification while you were away"),
+                                    _ => locale::tr("notifications while you were away"),
+                                };
+                                let _result = toast_tx.send(Toast::new(format!("{missed} {text}")));
                             }
                             Response::Notifications(s) => {
-                                store
-                                    .borrow_mut()
-                                    .notifications
-                                    .extend(s.into_iter().map(|v| Rc::new(v)));
+                                // Also requested after resume, only add what we missed
+                                let mut store = store.borrow_mut();
+                                for notification in s {
+                                    if store.notifications.iter().any(|n| n.id == notification.id) {
+                                        continue;
+                                    }
+                                    let rc = Rc::new(notification);
+                                    state.borrow().notification_centres().for_each(|c| {
+                                        c.insert(rc.clone());
+                                    });
+                                    store.insert(rc);
+                                }
+                            }
+                            Response::ColorPicked(color) => {
+                                let picker = state.borrow().widgets.iter().find_map(|w| match w {
+                                    WatsonWidget::Button(b) => b.color_picker().cloned(),
+                                    _ => None,
+                                });
+                                if let Some(picker) = picker {
+                                    let copied = picker.picked(color);
+                                    let _result = toast_tx.send(Toast::new(format!(
+                                        "{} {}",
+                                        locale::tr("Copied"),
+                                        copied
+                                    )));
+                                }
+                            }
+                            Response::RecordingState(active) => {
+                                let state_ref = state.borrow();
+                                state_ref
+                                    .system_state
+                                    .recording
+                                    .store(active, std::sync::atomic::Ordering::Relaxed);
+                                state_ref.widgets.iter().for_each(|w| {
+                                    if let WatsonWidget::RecordingIndicator(r) = w {
+                                        r.set_active(active);
+                                    }
+                                });
+                                state_ref.notify_update(BackendFuncType::ScreenRecord);
+                            }
+                            Response::Error(e) => {
+                                eprintln!("{:?}", e);
+                                let _result = toast_tx.send(e.into());
+                                // Buttons and sliders change before the daemon answers
+                                let _result = DAEMON_TX.get().map(|d| d.send(Request::SystemState));
+                            }
+                            Response::Ack { id, error } => {
+                                let state_ref = state.borrow();
+                                if pending::resolve(id, error.is_some(), &state_ref.system_state) {
+                                    state_ref.refresh_controls();
+                                    // The daemon may have applied part of the change
+                                    let _result = DAEMON_TX.get().map(|d| d.send(Request::SystemState));
+                                }
+                                if let Some(e) = error {
+                                    eprintln!("{:?}", e);
+                                    let _result = toast_tx.send(e.into());
+                                }
+                            }
+                            Response::Connectivity(_) => {
+                                let state_ref = state.borrow();
+                                state_ref.widgets.iter().for_each(|w| {
+                                    if let WatsonWidget::Button(b) = w {
+                                        b.refresh(&state_ref.system_state);
+                                    }
+                                });
+                            }
+                            Response::PowerProfiles(profiles) => {
+                                let state_ref = state.borrow();
+                                state_ref.widgets.iter().for_each(|w| {
+                                    if let WatsonWidget::Button(b) = w
+                                        && b.func.func() == BackendFuncType::Powermode
+                                    {
+                                        b.refresh(&state_ref.system_state);
+                                        b.show_power_profiles(&profiles);
+                                    }
+                                });
+                            }
+                            Response::KeyboardLayout { name, short } => {
+                                state.borrow().widgets.iter().for_each(|w| {
+                                    if let WatsonWidget::KeyboardLayout(k) = w {
+                                        k.set_layout(&name, &short);
+                                    }
+                                });
+                            }
+                            Response::Mail(accounts) => {
+                                state.borrow().widgets.iter().for_each(|w| {
+                                    if let WatsonWidget::Mail(m) = w {
+                                        m.set_accounts(&accounts);
+                                    }
+                                });
+                            }
+                            Response::DeviceUse(used) => {
+                                state.borrow().widgets.iter().for_each(|w| {
+                                    if let WatsonWidget::PrivacyIndicator(p) = w {
+                                        p.set_state(&used);
+                                    }
+                                });
+                            }
+                            Response::Quotes(quotes) => {
+                                state.borrow().widgets.iter().for_each(|w| {
+                                    if let WatsonWidget::Ticker(t) = w {
+                                        t.set_quotes(&quotes);
+                                    }
+                                });
+                            }
+                            Response::HomeAssistant(entities) => {
+                                state.borrow().widgets.iter().for_each(|w| {
+                                    if let WatsonWidget::HomeAssistant(h) = w {
+                                        h.set_entities(&entities);
+                                    }
+                                });
+                            }
+                            Response::HomeAssistantEntity(entity) => {
+                                state.borrow().widgets.iter().for_each(|w| {
+                                    if let WatsonWidget::HomeAssistant(h) = w {
+                                        h.set_entity(&entity);
+                                    }
+                                });
+                            }
+                            Response::Focus(focus) => {
+                                let mut state_ref = state.borrow_mut();
+                                if !previewing {
+                                    let layout = focus.layout.clone().or_else(|| state_ref.dock.layout.clone());
+                                    let _result = layout_tx.send(layout);
+                                }
+                                state_ref.widgets.iter().for_each(|w| {
+                                    if let WatsonWidget::Focus(f) = w {
+                                        f.set_state(&focus);
+                                    }
+                                });
+                                state_ref.focus = focus;
+                            }
+                            Response::ScreenTime(usage) => {
+                                state.borrow().widgets.iter().for_each(|w| {
+                                    if let WatsonWidget::ScreenTime(s) = w {
+                                        s.set_usage(&usage);
+                                    }
+                                });
+                            }
+                            Response::Refreshing { kind, active } => {
+                                state.borrow().widgets.iter().for_each(|w| match w {
+                                    // The calendar fetches its events itself
+                                    WatsonWidget::Calendar(c)
+                                        if active && kind.includes(DataKind::Calendar) =>
+                                    {
+                                        c.reload()
+                                    }
+                                    WatsonWidget::Mail(m) if kind.includes(DataKind::Mail) => {
+                                        m.set_refreshing(active)
+                                    }
+                                    WatsonWidget::Ticker(t) if kind.includes(DataKind::Quotes) => {
+                                        t.set_refreshing(active)
+                                    }
+                                    _ => {}
+                                });
+                            }
+                            Response::CalendarChanged { calendar } => {
+                                state.borrow().widgets.iter().for_each(|w| {
+                                    if let WatsonWidget::Calendar(c) = w {
+                                        c.calendar_changed(calendar.clone());
+                                    }
+                                });
+                            }
+                            Response::Session(session) => {
+                                let suspended =
+                                    matches!(session, SessionState::Sleeping | SessionState::Idle);
+                                set_suspended(suspended);
+                                for window in gtk4::Window::list_toplevels() {
+                                    if suspended {
+                                        window.add_css_class("suspended");
+                                    } else {
+                                        window.remove_css_class("suspended");
+                                    }
+                                }
+                                if !suspended {
+                                    state.borrow().redraw_all();
+                                }
+                                if session == SessionState::Resumed {
+                                    // Toggles may have changed while asleep, e.g. a dock was unplugged
+                                    let loaded = store.borrow().notifications.len();
+                                    let _result = DAEMON_TX.get().map(|d| {
+                                        d.send(Request::SystemState)?;
+                                        d.send(Request::PendingNotifications {
+                                            offset: 0,
+                                            limit: loaded.max(NOTIFICATION_PAGE),
+                                        })
+                                    });
                                 }
+                            }
+                            Response::Dock(dock) => {
+                                let mut state_ref = state.borrow_mut();
+                                if !previewing {
+                                    let layout = state_ref.focus.layout.clone().or_else(|| dock.layout.clone());
+                                    let _result = layout_tx.send(layout);
+                                }
+                                state_ref.dock = dock;
+                                state_ref.apply_sensitivity();
+                            }
+                            Response::Displays(displays) => {
+                                state.borrow().update_displays(&displays);
+                            }
+                            Response::AudioAvailable(available) => {
+                                let mut state_ref = state.borrow_mut();
+                                state_ref.audio_unavailable = !available;
+                                state_ref.apply_sensitivity();
+                            }
+                            Response::Surface { surface: Surface::Window, action } => {
+                                let _ = instance_tx.send(action.into());
+                            }
+                            Response::Surface { surface, action } => {
+                                state.borrow().surfaces(surface).for_each(|w| {
+                                    let visible = match action {
+                                        SurfaceAction::Show => true,
+                                        SurfaceAction::Hide => false,
+                                        SurfaceAction::Toggle => !w.is_visible(),
+                                    };
+                                    w.set_visible(visible);
+                                    // Revealing a widget inside a hidden window is pointless
+                                    if visible {
+                                        let _ = instance_tx.send(InstanceCommand::Show);
+                                    }
+                                });
+                            }
                             _ => {
                                 println!("{:?}", msg);
                             }
@@ -161,18 +524,23 @@ async fn main() -> Result<(), WatsonError> {
     });
 
     // Make initial requests
-    let required_services = config
-        .iter()
-        .map(WidgetSpec::required_services)
-        .reduce(|a, b| a | b)
-        .unwrap_or(0);
-
     if let Some(daemon) = DAEMON_TX.get() {
-        let _result = daemon.send(Request::RegisterServices(required_services));
+        let _result = daemon.send(Request::RegisterServices(required_services(&config)));
+        let _result = daemon.send(Request::DockState);
+        let _result = daemon.send(Request::Focus);
+        let _result = daemon.send(Request::PendingNotifications {
+            offset: 0,
+            limit: NOTIFICATION_PAGE,
+        });
     }
 
+    let window_span = startup::span("window");
     let mut ui = WatsonUi::default();
-    let win = ui.window();
+    let win = match preview {
+        Some(_) => ui.preview_window(),
+        None => ui.window(),
+    };
+    startup::connect_first_paint(&win);
 
     win.connect_close_request({
         let main_loop = main_loop.clone();
@@ -182,18 +550,159 @@ async fn main() -> Result<(), WatsonError> {
         }
     });
 
-    win.present();
+    connect_monitor_changed(&win, {
+        let state = Rc::clone(&state);
+        move |win| state.borrow().place_widgets(current_monitor(win).as_ref())
+    });
+
+    let source = match &preview {
+        Some(path) => LayoutSource::File(path.clone()),
+        None => LayoutSource::Named(None),
+    };
+    let editor = LayoutEditor::new(&win, source, Rc::clone(&state), toast_tx);
+
+    // Click-through widgets stay grabbable while editing
+    connect_input_region(&win, {
+        let state = Rc::clone(&state);
+        let editor = Rc::downgrade(&editor);
+        move || match editor.upgrade() {
+            Some(editor) if !editor.is_active() => state.borrow().passthrough(),
+            _ => Vec::new(),
+        }
+    });
+
+    drop(window_span);
+    if !ui.hidden {
+        win.present();
+    }
+    if let Some(path) = created {
+        win.show_first_run(&path);
+    }
+
+    if let Some(instance) = instance {
+        instance.listen(instance_tx);
+    }
+    // Reload the previewed layout on save
+    let _monitor = match &preview {
+        Some(path) => Some(watch_layout(path, layout_tx.clone())?),
+        None => None,
+    };
+    gtk4::glib::spawn_future_local({
+        let win = win.downgrade();
+        let main_loop = main_loop.clone();
+        let editor = Rc::clone(&editor);
+        async move {
+            while let Some(cmd) = instance_rx.recv().await {
+                let Some(win) = win.upgrade() else {
+                    break;
+                };
+                match cmd {
+                    InstanceCommand::Show => win.present(),
+                    InstanceCommand::Hide => win.set_visible(false),
+                    InstanceCommand::Toggle => {
+                        if win.is_visible() {
+                            win.set_visible(false);
+                        } else {
+                            win.present();
+                        }
+                    }
+                    InstanceCommand::Quit => {
+                        main_loop.quit();
+                        break;
+                    }
+                    InstanceCommand::Edit => {
+                        win.present();
+                        editor.toggle();
+                    }
+                }
+            }
+        }
+    });
+    gtk4::glib::spawn_future_local({
+        let win = win.downgrade();
+        async move {
+            while let Some(toast) = toast_rx.recv().await {
+                let Some(win) = win.upgrade() else {
+                    break;
+                };
+                win.show_toast(&toast.message, toast.hint.as_deref());
+            }
+        }
+    });
     gtk4::glib::spawn_future_local({
         let state = Rc::clone(&state);
+        let store = Rc::clone(&notification_store);
         let win = win.downgrade();
         async move {
+            let daemon_ready = startup::span("daemon ready");
             ui_ready.notified().await;
+            drop(daemon_ready);
             // async wait for a notify signal
             if let Some(win) = win.upgrade() {
+                let _span = startup::span("widgets");
                 let imp = win.imp();
                 for spec in config {
                     create_widgets(&imp.viewport.get(), spec, Rc::clone(&state), false);
                 }
+                state.borrow().apply_sensitivity();
+                state.borrow().request_displays();
+                state.borrow().place_widgets(current_monitor(&win).as_ref());
+                if preview.is_some() {
+                    outline_widgets(&win);
+                }
+                if mode == InstanceMode::Edit {
+                    editor.toggle();
+                }
+            }
+
+            let mut current = None;
+            while let Some(layout) = layout_rx.recv().await {
+                if preview.is_none() && layout == current {
+                    continue;
+                }
+                let Some(win) = win.upgrade() else {
+                    break;
+                };
+                let loaded = match &preview {
+                    Some(path) => load_layout_file(path),
+                    None => load_layout(layout.as_deref()),
+                };
+                let config = match loaded {
+                    Ok(config) => config,
+                    Err(e) => {
+                        eprintln!("{:?}", e);
+                        continue;
+                    }
+                };
+                if preview.is_none() {
+                    editor.set_source(LayoutSource::Named(layout.clone()));
+                }
+                current = layout;
+
+                let viewport = win.imp().viewport.get();
+                while let Some(child) = viewport.first_child() {
+                    viewport.remove(&child);
+                }
+                state.borrow_mut().clear_widgets();
+                let _result = DAEMON_TX
+                    .get()
+                    .map(|d| d.send(Request::RegisterServices(required_services(&config))));
+                for spec in config {
+                    create_widgets(&viewport, spec, Rc::clone(&state), false);
+                }
+                editor.refresh();
+
+                let state_ref = state.borrow();
+                state_ref.apply_sensitivity();
+                state_ref.request_displays();
+                state_ref.place_widgets(current_monitor(&win).as_ref());
+                state_ref.notification_centres().for_each(|c| {
+                    store
+                        .borrow()
+                        .notifications
+                        .iter()
+                        .for_each(|n| c.insert(Rc::clone(n)));
+                });
             }
         }
     });
@@ -203,21 +712,46 @@ async fn main() -> Result<(), WatsonError> {
     Ok(())
 }
 
+fn required_services(config: &[WidgetSpec]) -> u8 {
+    config
+        .iter()
+        .map(WidgetSpec::required_services)
+        .reduce(|a, b| a | b)
+        .unwrap_or(0)
+}
+
 #[derive(Default)]
 pub struct WatsonState {
     system_state: Arc<AtomicSystemState>,
+    dock: DockState,
+    /// The layout of the active focus profile wins over the dock's
+    focus: FocusState,
+    /// The daemon lost its audio server
+    audio_unavailable: bool,
 
     widgets: Vec<WatsonWidget>,
     subscribers: HashMap<BackendFuncType, Vec<WeakRef<gtk4::Widget>>>,
+    /// Rows and Columns of the layout
+    containers: Vec<WeakRef<gtk4::Box>>,
+    /// Widgets only shown on some monitors, with their `monitor` targets
+    placed: Vec<(WeakRef<gtk4::Widget>, Vec<String>)>,
+    /// Widgets configured with `click_through`
+    passthrough: Vec<WeakRef<gtk4::Widget>>,
 }
 #[allow(dead_code)]
 impl WatsonState {
     pub fn new() -> Self {
         Self {
             system_state: Arc::new(AtomicSystemState::default()),
+            dock: DockState::default(),
+            focus: FocusState::default(),
+            audio_unavailable: false,
 
             widgets: Vec::new(),
             subscribers: HashMap::new(),
+            containers: Vec::new(),
+            placed: Vec::new(),
+            passthrough: Vec::new(),
         }
     }
     pub fn register_widget(&mut self, widget: WatsonWidget) {
@@ -255,7 +789,7 @@ impl WatsonState {
             }
         })
     }
-    pub fn clocks(&self) -> impl Iterator<Item = &WeakRef<DrawingArea>> {
+    pub fn clocks(&self) -> impl Iterator<Item = &WeakRef<SnapshotArea>> {
         self.widgets.iter().filter_map(|w| {
             if let WatsonWidget::Clock(c) = w {
                 Some(c)
@@ -273,6 +807,107 @@ impl WatsonState {
             }
         })
     }
+    pub fn surfaces(&self, surface: Surface) -> impl Iterator<Item = gtk4::Widget> {
+        self.widgets.iter().filter_map(move |w| match (surface, w) {
+            (Surface::Calendar, WatsonWidget::Calendar(c)) => c.stack.upgrade().map(|s| s.upcast()),
+            (Surface::NotificationCentre, WatsonWidget::NotificationCentre(c)) => c.root(),
+            (Surface::Notes, WatsonWidget::Notes(n)) => n.root(),
+            _ => None,
+        })
+    }
+    /// Redraws buttons and sliders from `system_state`, dropping optimistic changes
+    pub fn refresh_controls(&self) {
+        self.widgets.iter().for_each(|w| match w {
+            WatsonWidget::Button(b) => b.refresh(&self.system_state),
+            WatsonWidget::Slider(s) => s.queue_draw(),
+            _ => {}
+        });
+    }
+    /// Shows the current state on the buttons of `func`
+    pub fn refresh_buttons(&self, func: BackendFuncType) {
+        self.widgets.iter().for_each(|w| {
+            if let WatsonWidget::Button(b) = w
+                && b.func.func() == func
+            {
+                b.refresh(&self.system_state);
+            }
+        });
+    }
+    pub fn clear_widgets(&mut self) {
+        self.widgets.clear();
+        self.subscribers.clear();
+        self.containers.clear();
+        self.placed.clear();
+        self.passthrough.clear();
+    }
+    /// Click-through widgets currently on screen
+    pub fn passthrough(&self) -> Vec<gtk4::Widget> {
+        self.passthrough
+            .iter()
+            .filter_map(WeakRef::upgrade)
+            .filter(|w| w.is_mapped())
+            .collect()
+    }
+    /// Shows the widgets meant for `monitor`, the window's current one. All of them until the
+    /// window is on one.
+    pub fn place_widgets(&self, monitor: Option<&Monitor>) {
+        for (widget, targets) in &self.placed {
+            if let Some(widget) = widget.upgrade() {
+                let shown = monitor.is_none_or(|m| pick_monitor(targets).as_ref() == Some(m));
+                widget.set_visible(shown);
+            }
+        }
+    }
+    /// Greys out sliders the dock config disables or whose backend is gone
+    pub fn apply_sensitivity(&self) {
+        self.widgets.iter().for_each(|w| {
+            if let WatsonWidget::Slider(s) = w
+                && let Some(slider) = s.weak.upgrade()
+            {
+                let sensitive = match s.func.func() {
+                    BackendFuncType::Brightness => self.dock.brightness_slider,
+                    BackendFuncType::Volume => !self.audio_unavailable,
+                    _ => return,
+                };
+                slider.set_sensitive(sensitive);
+                StateClass::Disabled.set(&slider, !sensitive);
+            }
+        });
+    }
+    /// Moves the sliders of external displays, unless the user is dragging them
+    pub fn update_displays(&self, displays: &[DisplayBrightness]) {
+        self.widgets.iter().for_each(|w| {
+            if let WatsonWidget::Slider(s) = w
+                && !s.edit_lock.get()
+                && let Some(device) = s.func.device()
+                && let Some(display) = displays.iter().find(|d| d.device == device)
+            {
+                s.func.set_percentage(&self.system_state, display.percent);
+                s.queue_draw();
+            }
+        });
+    }
+    /// Asks the daemon for display brightness if any slider needs it, detection is slow
+    pub fn request_displays(&self) {
+        let wanted = self
+            .widgets
+            .iter()
+            .any(|w| matches!(w, WatsonWidget::Slider(s) if s.func.device().is_some()));
+        if wanted {
+            let _result = DAEMON_TX.get().map(|d| d.send(Request::Displays));
+        }
+    }
+    /// Catches up on the periodic redraws skipped while suspended
+    pub fn redraw_all(&self) {
+        self.refresh_controls();
+        self.batteries().for_each(|b| b.queue_draw());
+        self.clocks()
+            .filter_map(|c| c.upgrade())
+            .for_each(|c| c.queue_draw());
+        self.calendars()
+            .filter_map(|c| c.upgrade())
+            .for_each(|c| c.queue_draw());
+    }
     pub fn notify_update(&self, func: BackendFuncType) {
         if let Some(subs) = self.subscribers.get(&func) {
             subs.iter()
@@ -291,4 +926,15 @@ impl NotificationStore {
             notifications: Vec::new(),
         }
     }
+    /// Replaces a notification with the same id, otherwise appends it
+    pub fn insert(&mut self, notification: Rc<Notification>) {
+        match self
+            .notifications
+            .iter_mut()
+            .find(|n| n.id == notification.id)
+        {
+            Some(existing) => *existing = notification,
+            None => self.notifications.push(notification),
+        }
+    }
 }
@@ -0,0 +1,134 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use gtk4::{
+    Widget,
+    glib::{self, object::ObjectExt},
+    prelude::{IsA, WidgetExt},
+};
+
+/// Work that can wait until the window shows something, in the order it was deferred
+type Deferred = (&'static str, Box<dyn FnOnce()>);
+
+/// Time spent in each phase since the process started
+struct Trace {
+    start: Instant,
+    /// Name, start offset and duration
+    spans: Vec<(&'static str, Duration, Duration)>,
+}
+
+thread_local! {
+    static TRACE: RefCell<Option<Trace>> = const { RefCell::new(None) };
+    static PAINTED: Cell<bool> = const { Cell::new(false) };
+    static DEFERRED: RefCell<Vec<Deferred>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Records the startup phases if `--trace-startup` was passed. They are printed whenever a batch
+/// of deferred work finished.
+pub fn trace_from_args(mut args: std::env::Args) {
+    if args.any(|a| a == "--trace-startup") {
+        TRACE.with_borrow_mut(|trace| {
+            *trace = Some(Trace {
+                start: Instant::now(),
+                spans: Vec::new(),
+            })
+        });
+    }
+}
+
+/// A phase of starting up, recorded when dropped
+#[must_use]
+pub struct Span {
+    name: &'static str,
+    start: Instant,
+}
+impl Drop for Span {
+    fn drop(&mut self) {
+        let end = Instant::now();
+        TRACE.with_borrow_mut(|trace| {
+            if let Some(trace) = trace {
+                let offset = self.start.saturating_duration_since(trace.start);
+                trace.spans.push((self.name, offset, end - self.start));
+            }
+        });
+    }
+}
+
+pub fn span(name: &'static str) -> Span {
+    Span {
+        name,
+        start: Instant::now(),
+    }
+}
+
+/// Runs `f` once the window painted its first frame, or when idle if it already did. Meant for
+/// disk and network work the first frame doesn't need.
+pub fn defer(name: &'static str, f: impl FnOnce() + 'static) {
+    if PAINTED.get() {
+        run_when_idle(vec![(name, Box::new(f))]);
+    } else {
+        DEFERRED.with_borrow_mut(|deferred| deferred.push((name, Box::new(f))));
+    }
+}
+
+/// Starts the deferred work after the first frame of `win`. A window that starts hidden defers
+/// it until it is shown.
+pub fn connect_first_paint(win: &impl IsA<Widget>) {
+    let first_frame = Rc::new(RefCell::new(Some(span("first frame"))));
+    win.connect_realize(move |win| {
+        let Some(clock) = win.frame_clock() else {
+            return;
+        };
+        let handler = Rc::new(RefCell::new(None));
+        let id = clock.connect_after_paint({
+            let handler = Rc::clone(&handler);
+            let first_frame = Rc::clone(&first_frame);
+            move |clock| {
+                if PAINTED.replace(true) {
+                    return;
+                }
+                drop(first_frame.take());
+                if let Some(id) = handler.take() {
+                    clock.disconnect(id);
+                }
+                run_when_idle(DEFERRED.take());
+            }
+        });
+        handler.replace(Some(id));
+    });
+}
+
+/// After the pending redraws, one idle callback per piece of work so input isn't blocked
+fn run_when_idle(work: Vec<Deferred>) {
+    let mut work = work.into_iter();
+    glib::idle_add_local_full(glib::Priority::LOW, move || {
+        let Some((name, f)) = work.next() else {
+            report();
+            return glib::ControlFlow::Break;
+        };
+        let _span = span(name);
+        f();
+        glib::ControlFlow::Continue
+    });
+}
+
+/// Prints the phases recorded since the last report, if tracing
+fn report() {
+    let spans = TRACE.with_borrow_mut(|trace| {
+        trace
+            .as_mut()
+            .map(|t| std::mem::take(&mut t.spans))
+            .unwrap_or_default()
+    });
+    for (name, offset, duration) in spans {
+        eprintln!(
+            "startup: {:>8.1}ms +{:>7.1}ms  {}",
+            offset.as_secs_f64() * 1000.0,
+            duration.as_secs_f64() * 1000.0,
+            name
+        );
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    cell::{Cell, RefCell},
+    fs::{self, File},
+    io::BufReader,
+    path::PathBuf,
+    rc::Rc,
+};
+
+use gtk4::{
+    Box, DragSource, DropTarget, EventController, EventControllerKey, Orientation, PickFlags,
+    PropagationPhase, Widget, WidgetPaintable,
+    gdk::{ContentProvider, DragAction, Key, ModifierType},
+    glib::{
+        self, WeakRef,
+        object::{Cast, CastNone, ObjectExt},
+        subclass::types::ObjectSubclassIsExt,
+        types::StaticType,
+        value::ToValue,
+    },
+    prelude::{BoxExt, EventControllerExt, OrientableExt, WidgetExt},
+};
+use serde_json::Value;
+use suite_223b::{
+    utils::{
+        errors::{ResultExt, WatsonError, WatsonErrorKind},
+        paths::get_config_dir,
+    },
+    watson_err,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    WatsonState,
+    ui::g_templates::main_window::{MainWindow, Toast},
+};
+
+/// Where the shown layout was read from
+#[derive(Debug, Clone)]
+pub enum LayoutSource {
+    /// `$XDG_CONFIG_HOME/watson/<name>.json`, `None` is the default layout
+    Named(Option<String>),
+    /// `watson --preview <file>`
+    File(PathBuf),
+}
+impl LayoutSource {
+    fn path(&self) -> Result<PathBuf, WatsonError> {
+        match self {
+            Self::Named(name) => {
+                let name = name.as_deref().unwrap_or("fallback");
+                Ok(get_config_dir()?.join(format!("{name}.json")))
+            }
+            Self::File(path) => Ok(path.clone()),
+        }
+    }
+}
+
+/// Edit mode, toggled with `watson --edit` or Ctrl+E. Widgets can be dragged to another place
+/// in their Row, Column or the window, and the new order is written back to the layout file.
+/// Profile overrides of the layout still win over the file.
+pub struct LayoutEditor {
+    active: Cell<bool>,
+    source: RefCell<LayoutSource>,
+    viewport: WeakRef<Box>,
+    state: Rc<RefCell<WatsonState>>,
+    toast_tx: UnboundedSender<Toast>,
+    /// Added to the widgets while editing, removed when done
+    controllers: RefCell<Vec<(WeakRef<Widget>, EventController)>>,
+}
+impl LayoutEditor {
+    pub fn new(
+        win: &MainWindow,
+        source: LayoutSource,
+        state: Rc<RefCell<WatsonState>>,
+        toast_tx: UnboundedSender<Toast>,
+    ) -> Rc<Self> {
+        let viewport = win.imp().viewport.get();
+        let editor = Rc::new(Self {
+            active: Cell::new(false),
+            source: RefCell::new(source),
+            viewport: viewport.downgrade(),
+            state,
+            toast_tx,
+            controllers: RefCell::new(Vec::new()),
+        });
+
+        let shortcut = EventControllerKey::new();
+        shortcut.connect_key_pressed({
+            let editor = Rc::downgrade(&editor);
+            move |_, key, _, modifiers| {
+                if key == Key::e
+                    && modifiers.contains(ModifierType::CONTROL_MASK)
+                    && let Some(editor) = editor.upgrade()
+                {
+                    editor.toggle();
+                    return glib::Propagation::Stop;
+                }
+                glib::Propagation::Proceed
+            }
+        });
+        win.add_controller(shortcut);
+        editor
+    }
+
+    pub fn toggle(self: &Rc<Self>) {
+        let active = !self.active.get();
+        self.active.set(active);
+        if active {
+            self.attach();
+        } else {
+            self.detach();
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.get()
+    }
+
+    /// Follows a layout switch, the widgets of the new one are rebuilt right after
+    pub fn set_source(&self, source: LayoutSource) {
+        self.detach();
+        self.source.replace(source);
+    }
+
+    /// Makes freshly built widgets draggable again
+    pub fn refresh(self: &Rc<Self>) {
+        self.detach();
+        if self.active.get() {
+            self.attach();
+        }
+    }
+
+    /// The viewport and every Row and Column in it
+    fn containers(&self) -> Vec<Box> {
+        let state = self.state.borrow();
+        self.viewport
+            .upgrade()
+            .into_iter()
+            .chain(state.containers.iter().filter_map(WeakRef::upgrade))
+            .collect()
+    }
+
+    fn attach(self: &Rc<Self>) {
+        let mut controllers = self.controllers.borrow_mut();
+        for container in self.containers() {
+            let mut child = container.first_child();
+            while let Some(widget) = child {
+                child = widget.next_sibling();
+                widget.add_css_class("editable");
+                widget.set_cursor_from_name(Some("grab"));
+
+                // Wins over the widget's own gestures, e.g. a slider's drag
+                let source = DragSource::builder()
+                    .actions(DragAction::MOVE)
+                    .propagation_phase(PropagationPhase::Capture)
+                    .build();
+                source.connect_prepare({
+                    let editor = Rc::downgrade(self);
+                    let widget = widget.downgrade();
+                    move |_, x, y| {
+                        let (editor, widget) = (editor.upgrade()?, widget.upgrade()?);
+                        // Rows see the drag first, leave it to the child it started on
+                        let picked = widget.pick(x, y, PickFlags::DEFAULT)?;
+                        let innermost = std::iter::successors(Some(picked), Widget::parent)
+                            .find(|w| w.has_css_class("editable"))?;
+                        if innermost != widget {
+                            return None;
+                        }
+                        let path = editor.path_of(&widget)?;
+                        Some(ContentProvider::for_value(&encode_path(&path).to_value()))
+                    }
+                });
+                source.connect_drag_begin({
+                    let widget = widget.downgrade();
+                    move |source, _| {
+                        let paintable = WidgetPaintable::new(widget.upgrade().as_ref());
+                        source.set_icon(Some(&paintable), 0, 0);
+                    }
+                });
+
+                let target = DropTarget::new(String::static_type(), DragAction::MOVE);
+                target.connect_drop({
+                    let editor = Rc::downgrade(self);
+                    let widget = widget.downgrade();
+                    move |_, value, x, y| {
+                        let (Some(editor), Some(widget)) = (editor.upgrade(), widget.upgrade())
+                        else {
+                            return false;
+                        };
+                        let Some(from) = value.get::<String>().ok().and_then(|p| decode_path(&p))
+                        else {
+                            return false;
+                        };
+                        editor.drop_onto(&from, &widget, x, y)
+                    }
+                });
+
+                for controller in [source.upcast::<EventController>(), target.upcast()] {
+                    widget.add_controller(controller.clone());
+                    controllers.push((widget.downgrade(), controller));
+                }
+            }
+        }
+    }
+
+    fn detach(&self) {
+        for (widget, controller) in self.controllers.take() {
+            if let Some(widget) = widget.upgrade() {
+                widget.remove_controller(&controller);
+                widget.remove_css_class("editable");
+                widget.set_cursor(None);
+            }
+        }
+    }
+
+    /// Indices from the top of the layout down to `widget`, which matches the widget lists of
+    /// the layout file as long as no includes are involved
+    fn path_of(&self, widget: &Widget) -> Option<Vec<usize>> {
+        let viewport = self.viewport.upgrade()?.upcast::<Widget>();
+        let containers: Vec<Widget> = self.containers().into_iter().map(Cast::upcast).collect();
+
+        let mut path = Vec::new();
+        let mut current = widget.clone();
+        while current != viewport {
+            let parent = current.parent()?;
+            if containers.contains(&parent) {
+                path.push(index_of(&parent, &current));
+            }
+            current = parent;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Moves the widget at `from` before or after `target`, whichever half it was dropped on
+    fn drop_onto(&self, from: &[usize], target: &Widget, x: f64, y: f64) -> bool {
+        let Some(to) = self.path_of(target) else {
+            return false;
+        };
+        // Only within the same Row or Column
+        let (Some((&from_index, parent)), Some((&target_index, target_parent))) =
+            (from.split_last(), to.split_last())
+        else {
+            return false;
+        };
+        let Some(container) = target.parent().and_downcast::<Box>() else {
+            return false;
+        };
+        if parent != target_parent || from_index == target_index {
+            return false;
+        }
+
+        let after = match container.orientation() {
+            Orientation::Horizontal => x > target.width() as f64 / 2.0,
+            _ => y > target.height() as f64 / 2.0,
+        };
+        // The index once the widget was taken out
+        let mut to_index = target_index + after as usize;
+        if from_index < to_index {
+            to_index -= 1;
+        }
+        if to_index == from_index {
+            return false;
+        }
+
+        let written = self
+            .source
+            .borrow()
+            .path()
+            .and_then(|path| move_in_file(&path, parent, from_index, to_index));
+        if let Err(e) = written {
+            let _result = self.toast_tx.send(e.into());
+            return false;
+        }
+
+        let Some(moved) = nth_child(&container, from_index) else {
+            return false;
+        };
+        let sibling = if after {
+            Some(target.clone())
+        } else {
+            target.prev_sibling()
+        };
+        container.reorder_child_after(&moved, sibling.as_ref());
+        true
+    }
+}
+
+fn index_of(parent: &Widget, child: &Widget) -> usize {
+    std::iter::successors(parent.first_child(), |c| c.next_sibling())
+        .position(|c| c == *child)
+        .unwrap_or_default()
+}
+
+fn nth_child(container: &Box, n: usize) -> Option<Widget> {
+    std::iter::successors(container.first_child(), |c| c.next_sibling()).nth(n)
+}
+
+fn encode_path(path: &[usize]) -> String {
+    path.iter()
+        .map(usize::to_string)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn decode_path(path: &str) -> Option<Vec<usize>> {
+    path.split('/').map(|i| i.parse().ok()).collect()
+}
+
+/// Moves a widget of the list at `parent` in the layout file, leaving everything else as it was
+fn move_in_file(
+    path: &std::path::Path,
+    parent: &[usize],
+    from: usize,
+    to: usize,
+) -> Result<(), WatsonError> {
+    let file = File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+    let mut layout: Value = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+
+    let list = siblings(&mut layout, parent)
+        .filter(|list| from < list.len() && to < list.len())
+        .ok_or_else(|| {
+            watson_err!(
+                WatsonErrorKind::ConfigError,
+                format!(
+                    "Only widgets listed in {} itself can be moved, not included ones",
+                    path.display()
+                )
+            )
+        })?;
+    let widget = list.remove(from);
+    list.insert(to, widget);
+
+    let mut json = serde_json::to_string_pretty(&layout)
+        .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+    json.push('\n');
+    fs::write(path, json).with_context(|| format!("Could not write {}", path.display()))
+}
+
+/// The widget list at `parent`, `None` if includes shift the indices on the way
+fn siblings<'a>(layout: &'a mut Value, parent: &[usize]) -> Option<&'a mut Vec<Value>> {
+    let is_include = |w: &Value| w.as_object().is_some_and(|o| o.contains_key("include"));
+    let mut list = layout.as_array_mut()?;
+    for &i in parent {
+        if list.iter().any(is_include) {
+            return None;
+        }
+        list = list.get_mut(i)?.get_mut("children")?.as_array_mut()?;
+    }
+    if list.iter().any(is_include) {
+        return None;
+    }
+    Some(list)
+}
@@ -9,9 +9,15 @@ mod imp {
         #[template_child]
         pub event_title: TemplateChild<gtk4::Label>,
 
+        #[template_child]
+        pub start_label: TemplateChild<gtk4::Label>,
+
         #[template_child]
         pub event_start: TemplateChild<gtk4::Label>,
 
+        #[template_child]
+        pub end_label: TemplateChild<gtk4::Label>,
+
         #[template_child]
         pub event_end: TemplateChild<gtk4::Label>,
 
@@ -21,6 +27,15 @@ mod imp {
         #[template_child]
         pub location_icon: TemplateChild<gtk4::Image>,
 
+        #[template_child]
+        pub attendees: TemplateChild<gtk4::Box>,
+
+        #[template_child]
+        pub attendees_label: TemplateChild<gtk4::Label>,
+
+        #[template_child]
+        pub attendee_list: TemplateChild<gtk4::FlowBox>,
+
         #[template_child]
         pub event_description: TemplateChild<gtk4::Label>,
 
@@ -61,7 +76,10 @@ use gtk4::glib::Object;
 use gtk4::glib::subclass::types::ObjectSubclassIsExt;
 use gtk4::prelude::*;
 use suite_223b::calendar::utils::CalDavEvent;
-use suite_223b::calendar::utils::structs::DateTimeSpec;
+use suite_223b::calendar::utils::structs::{Attendee, DateTimeSpec};
+use suite_223b::contacts::{email_address, initials};
+
+use crate::ui::widgets::locale::{self, tr};
 
 gtk4::glib::wrapper! {
     pub struct EventDetails(ObjectSubclass<imp::EventDetails>)
@@ -81,6 +99,11 @@ impl EventDetails {
         obj.set_can_target(true);
 
         obj.set_css_classes(&["inner-widget", "calendar-details"]);
+
+        let imp = obj.imp();
+        imp.start_label.set_label(tr("Starts"));
+        imp.end_label.set_label(tr("Ends"));
+        imp.attendees_label.set_label(tr("Attendees"));
         obj
     }
     pub fn set_event(&self, event: &CalDavEvent) {
@@ -93,8 +116,8 @@ impl EventDetails {
         // 2. Date/Time Formatting
         let format_time = |ts: &Option<DateTimeSpec>| {
             ts.as_ref()
-                .map(|t| format!("{}", t.local().format("%H:%M"))) // Replace with your actual formatting logic
-                .unwrap_or_else(|| "N/A".to_string())
+                .map(|t| locale::format_datetime(&t.local(), locale::time_format()))
+                .unwrap_or_else(|| tr("N/A").to_string())
         };
         imp.event_start.set_label(&format_time(&event.start));
         imp.event_end.set_label(&format_time(&event.end));
@@ -119,15 +142,60 @@ impl EventDetails {
             imp.recurrence_icon.set_visible(false);
         }
 
-        // 5. Description
+        // 5. Attendees, named from the address book where the invite only had an address
+        imp.attendee_list.remove_all();
+        for attendee in &event.attendees {
+            imp.attendee_list.append(&attendee_row(attendee));
+        }
+        imp.attendees.set_visible(!event.attendees.is_empty());
+
+        // 6. Description
         imp.event_description.set_label(
             event
                 .description
                 .as_deref()
-                .unwrap_or("No additional details."),
+                .unwrap_or(tr("No additional details.")),
         );
     }
 }
+fn attendee_row(attendee: &Attendee) -> gtk4::Box {
+    let email = attendee
+        .email
+        .as_deref()
+        .map(email_address)
+        .unwrap_or_default();
+    let name = attendee
+        .display_name
+        .clone()
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(|| email.clone());
+
+    let row = gtk4::Box::builder()
+        .spacing(8)
+        .tooltip_text(email.as_str())
+        .build();
+    let avatar: gtk4::Widget = match attendee.avatar.as_ref().filter(|p| p.exists()) {
+        Some(path) => {
+            let image = gtk4::Image::from_file(path);
+            image.set_pixel_size(24);
+            image.set_overflow(gtk4::Overflow::Hidden);
+            image.upcast()
+        }
+        None => gtk4::Label::new(Some(&initials(&name))).upcast(),
+    };
+    avatar.add_css_class("attendee-avatar");
+    avatar.set_valign(gtk4::Align::Center);
+    row.append(&avatar);
+    row.append(
+        &gtk4::Label::builder()
+            .label(name.as_str())
+            .halign(gtk4::Align::Start)
+            .ellipsize(gtk4::pango::EllipsizeMode::End)
+            .build(),
+    );
+    row
+}
+
 impl Default for EventDetails {
     fn default() -> Self {
         Self::new()
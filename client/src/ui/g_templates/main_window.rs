@@ -3,7 +3,7 @@ mod imp {
     use std::rc::Rc;
 
     use gtk4::subclass::prelude::*;
-    use gtk4::{Box as GtkBox, Window};
+    use gtk4::{Box as GtkBox, Label, Overlay, Revealer, Window};
     use gtk4::{CompositeTemplate, ScrolledWindow, glib};
 
     use crate::WatsonState;
@@ -11,12 +11,23 @@ mod imp {
     #[derive(CompositeTemplate, Default)]
     #[template(resource = "/dev/skxxtz/watson/ui/window.ui")]
     pub struct MainWindow {
+        #[template_child(id = "window-overlay")]
+        pub overlay: TemplateChild<Overlay>,
+
         #[template_child(id = "viewport")]
         pub viewport: TemplateChild<GtkBox>,
 
         #[template_child(id = "viewport-scroll")]
         pub viewport_scroll: TemplateChild<ScrolledWindow>,
 
+        #[template_child(id = "toast-revealer")]
+        pub toast_revealer: TemplateChild<Revealer>,
+
+        #[template_child(id = "toast-label")]
+        pub toast_label: TemplateChild<Label>,
+
+        pub toast_timeout: RefCell<Option<glib::SourceId>>,
+
         pub state: Rc<RefCell<WatsonState>>,
     }
 
@@ -40,8 +51,43 @@ mod imp {
     impl WindowImpl for MainWindow {}
 }
 
+use std::{path::Path, time::Duration};
+
 use gtk4::glib::Object;
 use gtk4::prelude::*;
+use gtk4::subclass::prelude::ObjectSubclassIsExt;
+use suite_223b::utils::errors::{WatsonError, WatsonErrorDto};
+
+/// How long a toast stays visible
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+
+/// A short message at the bottom of the window, mostly failed requests
+#[derive(Debug, Clone)]
+pub struct Toast {
+    pub message: String,
+    pub hint: Option<String>,
+}
+impl Toast {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            hint: None,
+        }
+    }
+}
+impl From<WatsonErrorDto> for Toast {
+    fn from(e: WatsonErrorDto) -> Self {
+        Self {
+            message: e.message,
+            hint: e.hint,
+        }
+    }
+}
+impl From<WatsonError> for Toast {
+    fn from(e: WatsonError) -> Self {
+        WatsonErrorDto::from(e).into()
+    }
+}
 
 gtk4::glib::wrapper! {
     pub struct MainWindow(ObjectSubclass<imp::MainWindow>)
@@ -57,4 +103,42 @@ impl MainWindow {
 
         obj
     }
+
+    /// Shows `message` at the bottom of the window until it times out or the next toast replaces it
+    pub fn show_toast(&self, message: &str, hint: Option<&str>) {
+        let imp = self.imp();
+        let text = match hint {
+            Some(hint) => format!("{message}\n{hint}"),
+            None => message.to_string(),
+        };
+        imp.toast_label.set_label(&text);
+        imp.toast_revealer.set_reveal_child(true);
+
+        if let Some(previous) = imp.toast_timeout.take() {
+            previous.remove();
+        }
+        let weak = self.downgrade();
+        let id = gtk4::glib::timeout_add_local_once(TOAST_DURATION, move || {
+            if let Some(win) = weak.upgrade() {
+                win.imp().toast_timeout.take();
+                win.imp().toast_revealer.set_reveal_child(false);
+            }
+        });
+        imp.toast_timeout.replace(Some(id));
+    }
+
+    /// Tells the user where the starter layout written on first launch lives
+    pub fn show_first_run(&self, path: &Path) {
+        let dialog = gtk4::AlertDialog::builder()
+            .modal(true)
+            .message("Welcome to Watson")
+            .detail(format!(
+                "A starter layout was written to {}.\n\nEdit it to arrange your widgets, \
+                 `watson --dump-config-schema` lists every option.",
+                path.display()
+            ))
+            .buttons(["OK"])
+            .build();
+        dialog.show(Some(self));
+    }
 }
@@ -3,3 +3,4 @@ pub mod main_window;
 pub mod notification;
 pub mod notification_centre;
 pub mod notification_obj;
+pub mod snapshot_area;
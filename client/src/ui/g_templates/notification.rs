@@ -1,5 +1,8 @@
 mod imp {
+    use std::cell::Cell;
+
     use gtk4::Box as GtkBox;
+    use gtk4::Button;
     use gtk4::Image;
     use gtk4::Label;
     use gtk4::glib;
@@ -16,6 +19,11 @@ mod imp {
 
         #[template_child(id = "app_icon")]
         pub app_icon: TemplateChild<Image>,
+
+        #[template_child(id = "snooze")]
+        pub snooze: TemplateChild<Button>,
+
+        pub id: Cell<u32>,
     }
 
     #[glib::object_subclass]
@@ -42,9 +50,19 @@ use std::rc::Rc;
 
 use gtk4::gio::{ActionGroup, ActionMap};
 use gtk4::glib::Object;
+use gtk4::glib::object::ObjectExt;
 use gtk4::glib::subclass::types::ObjectSubclassIsExt;
-use gtk4::prelude::WidgetExt;
+use gtk4::prelude::{BoxExt, ButtonExt, GestureSingleExt, PopoverExt, WidgetExt};
+use gtk4::{Box as GtkBox, Button, GestureClick, Label, Popover, SpinButton};
 use suite_223b::notification::Notification;
+use suite_223b::protocol::Request;
+
+use crate::DAEMON_TX;
+use crate::ui::widgets::attach_popover;
+use crate::ui::widgets::locale::tr;
+
+/// Minutes the custom snooze starts at
+const CUSTOM_SNOOZE_MINUTES: f64 = 30.0;
 
 gtk4::glib::wrapper! {
     pub struct NotificationWidget(ObjectSubclass<imp::NotificationWidget>)
@@ -65,6 +83,7 @@ impl NotificationWidget {
 
         // Notification
         let imp = obj.imp();
+        imp.id.set(notification.id);
 
         // Handle visibility
         imp.title.set_visible(!&notification.summary.is_empty());
@@ -78,6 +97,85 @@ impl NotificationWidget {
 
         obj.add_css_class(notification.urgency.css_class());
 
+        // Right click dismisses
+        let gesture = GestureClick::builder().button(3).build();
+        gesture.connect_released({
+            let id = notification.id;
+            move |_, _, _, _| {
+                DAEMON_TX
+                    .get()
+                    .map(|d| d.send(Request::DismissNotification(id)));
+            }
+        });
+        obj.add_controller(gesture);
+
+        Self::attach_snooze(&imp.snooze, notification.id);
+
         obj
     }
+
+    /// Snooze presets and a custom number of minutes, in a popover on the snooze button
+    fn attach_snooze(button: &Button, id: u32) {
+        let popover = Popover::builder()
+            .css_classes(["snooze-popover"])
+            .has_arrow(true)
+            .build();
+        attach_popover(&popover, button);
+
+        let snooze = {
+            let popover = popover.downgrade();
+            move |seconds: u64| {
+                DAEMON_TX
+                    .get()
+                    .map(|d| d.send(Request::SnoozeNotification { id, seconds }));
+                if let Some(popover) = popover.upgrade() {
+                    popover.popdown();
+                }
+            }
+        };
+
+        let holder = GtkBox::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(4)
+            .build();
+        for (label, seconds) in [("15 minutes", 15 * 60), ("1 hour", 60 * 60)] {
+            let preset = Button::with_label(tr(label));
+            preset.connect_clicked({
+                let snooze = snooze.clone();
+                move |_| snooze(seconds)
+            });
+            holder.append(&preset);
+        }
+
+        let custom = GtkBox::builder().spacing(6).build();
+        let minutes = SpinButton::with_range(1.0, 24.0 * 60.0, 5.0);
+        minutes.set_value(CUSTOM_SNOOZE_MINUTES);
+        custom.append(&minutes);
+        custom.append(&Label::new(Some(tr("minutes"))));
+        let confirm = Button::with_label(tr("Snooze"));
+        confirm.connect_clicked({
+            let minutes = minutes.downgrade();
+            move |_| {
+                if let Some(minutes) = minutes.upgrade() {
+                    snooze(minutes.value_as_int() as u64 * 60);
+                }
+            }
+        });
+        custom.append(&confirm);
+        holder.append(&custom);
+        popover.set_child(Some(&holder));
+
+        button.set_tooltip_text(Some(tr("Snooze")));
+        button.connect_clicked({
+            let popover = popover.downgrade();
+            move |_| {
+                if let Some(popover) = popover.upgrade() {
+                    popover.popup();
+                }
+            }
+        });
+    }
+    pub fn id(&self) -> u32 {
+        self.imp().id.get()
+    }
 }
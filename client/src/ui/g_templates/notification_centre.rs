@@ -30,12 +30,10 @@ mod imp {
 use gtk4::gio::{ActionGroup, ActionMap, ListStore};
 use gtk4::glib::object::{Cast, CastNone, ObjectExt};
 use gtk4::glib::{Object, WeakRef};
-use gtk4::prelude::{BoxExt, ListItemExt, WidgetExt};
-use gtk4::{
-    Box, CustomFilter, FilterListModel, Image, Label, ListBox, ListItem, SignalListItemFactory,
-    SingleSelection,
-};
+use gtk4::prelude::{ListItemExt, WidgetExt};
+use gtk4::{CustomFilter, FilterListModel, ListItem, NoSelection, SignalListItemFactory};
 
+use crate::ui::g_templates::notification::NotificationWidget;
 use crate::ui::g_templates::notification_obj::NotificationObj;
 
 gtk4::glib::wrapper! {
@@ -49,13 +47,6 @@ impl NotificationCollection {
     pub fn new() -> Self {
         let obj: Self = Object::new();
 
-        let list = ListBox::builder()
-            .selection_mode(gtk4::SelectionMode::None)
-            .hexpand(true)
-            .valign(gtk4::Align::Start)
-            .build();
-
-        obj.append(&list);
         obj.set_css_classes(&["widget", "notification_centre"]);
         obj.set_vexpand(false);
         obj.set_hexpand(true);
@@ -64,7 +55,9 @@ impl NotificationCollection {
 
         obj
     }
-    pub fn construct_liststore() -> (SingleSelection, SignalListItemFactory, WeakRef<ListStore>) {
+    /// The model and factory of a notification list. Rows are only built for the notifications
+    /// in view and dropped once they scroll out.
+    pub fn construct_liststore() -> (NoSelection, SignalListItemFactory, WeakRef<ListStore>) {
         let store = ListStore::new::<NotificationObj>();
         let store_weak = store.downgrade();
 
@@ -75,7 +68,7 @@ impl NotificationCollection {
             notification_obj.notification().is_some()
         });
         let filter_model = FilterListModel::new(Some(store), Some(filter));
-        let selection_model = SingleSelection::new(Some(filter_model));
+        let selection_model = NoSelection::new(Some(filter_model));
 
         let factory = SignalListItemFactory::new();
 
@@ -83,79 +76,8 @@ impl NotificationCollection {
             let list_item = list_item
                 .downcast_ref::<ListItem>()
                 .expect("Expected a ListItem");
-
-            let container = Box::builder()
-                .spacing(4)
-                .hexpand(true)
-                .halign(gtk4::Align::Fill)
-                .css_classes(["notification"])
-                .build();
-
-            let status_icon = Image::builder().css_classes(["status-icon"]).build();
-
-            // Construcs the content box
-            let content = Box::builder()
-                .orientation(gtk4::Orientation::Vertical)
-                .spacing(4)
-                .hexpand(true)
-                .halign(gtk4::Align::Fill)
-                .build();
-
-            // Title box
-            let title_box = Box::builder().spacing(10).hexpand(true).build();
-            let app_icon = Image::builder()
-                .css_classes(["app-icon"])
-                .halign(gtk4::Align::Start)
-                .valign(gtk4::Align::Center)
-                .build();
-            let title = Label::builder()
-                .css_classes(["notification-title"])
-                .wrap(true)
-                .wrap_mode(gtk4::pango::WrapMode::Word)
-                .halign(gtk4::Align::Start)
-                .valign(gtk4::Align::Center)
-                .build();
-            title_box.append(&app_icon);
-            title_box.append(&title);
-            content.append(&title_box);
-
-            // Body
-            let body_box = Box::builder()
-                .hexpand(true)
-                .halign(gtk4::Align::Fill)
-                .build();
-
-            let body = Label::builder()
-                .css_classes(["notification-body", "text-body"])
-                .wrap(true)
-                .wrap_mode(gtk4::pango::WrapMode::Word)
-                .valign(gtk4::Align::End)
-                .halign(gtk4::Align::Start)
-                .xalign(0.0)
-                .build();
-            body_box.append(&body);
-            content.append(&body_box);
-
-            // Actions
-            let actions = Box::builder()
-                .spacing(4)
-                .valign(gtk4::Align::End)
-                .css_classes(["notification-actions"])
-                .build();
-            content.append(&actions);
-
-            let close_icon = Image::builder()
-                .css_classes(["close-icon"])
-                .valign(gtk4::Align::Start)
-                .icon_name("close")
-                .build();
-
-            // Populate
-            container.append(&status_icon);
-            container.append(&content);
-            container.append(&close_icon);
-
-            list_item.set_child(Some(&container));
+            list_item.set_activatable(false);
+            list_item.set_selectable(false);
         });
 
         factory.connect_bind(|_, list_item| {
@@ -163,52 +85,21 @@ impl NotificationCollection {
                 .downcast_ref::<ListItem>()
                 .expect("Expected a ListItem");
 
-            let container = list_item
-                .child()
-                .and_downcast::<gtk4::Box>()
-                .expect("Expected a GtkBox");
-
-            let _status_icon = container.first_child().and_downcast::<Image>().unwrap();
-            let content = container
-                .first_child()
-                .and_then(|s| s.next_sibling())
-                .and_downcast::<Box>()
-                .unwrap();
-            let _close_icon = container.last_child().and_downcast::<Image>().unwrap();
-
-            let title_box = content.first_child().and_downcast::<Box>().unwrap();
-            let body_box = title_box.next_sibling().and_downcast::<Box>().unwrap();
-            let body = body_box.first_child().and_downcast::<Label>().unwrap();
-            let actions = content.last_child().and_downcast::<Box>().unwrap();
-
-            let app_icon = title_box.first_child().and_downcast::<Image>().unwrap();
-            let title = title_box.last_child().and_downcast::<Label>().unwrap();
-
-            while let Some(action) = actions.last_child() {
-                actions.remove(&action);
-            }
-
-            let notification_obj = list_item
+            // Should never be None due to filter
+            let notification = list_item
                 .item()
                 .and_downcast::<NotificationObj>()
-                .expect("Expected item.");
-
-            // Should never panic due to filter
-            let notification = notification_obj.notification().unwrap();
-
-            container.add_css_class(notification.urgency.css_class());
-
-            title.set_visible(!&notification.summary.is_empty());
-            title.set_text(&notification.summary);
-
-            body.set_visible(!&notification.body.is_empty());
-            body.set_text(&notification.body);
-
-            app_icon.set_visible(!notification.app_icon.is_empty());
-            app_icon.set_icon_name(Some(&notification.app_icon));
+                .and_then(|n| n.notification());
+            if let Some(notification) = notification {
+                list_item.set_child(Some(&NotificationWidget::new(notification)));
+            }
+        });
 
-            body_box.set_size_request(1, -1);
-            content.set_size_request(1, -1);
+        factory.connect_unbind(|_, list_item| {
+            let list_item = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Expected a ListItem");
+            list_item.set_child(None::<&gtk4::Widget>);
         });
 
         (selection_model, factory, store_weak)
@@ -1,6 +1,6 @@
 // src/ui/templates/notification_obj.rs
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use gtk4::glib::subclass::prelude::*;
@@ -15,6 +15,8 @@ mod imp {
     #[derive(Default)]
     pub struct NotificationObjImp {
         pub notification: RefCell<Weak<Notification>>,
+        /// Kept for ordering once the notification is gone
+        pub id: Cell<u32>,
     }
 
     #[glib::object_subclass]
@@ -37,11 +39,16 @@ impl NotificationObj {
         let obj: Self = Object::new();
         let imp = obj.imp();
 
+        imp.id.set(notification.id);
         imp.notification.replace(Rc::downgrade(&notification));
 
         obj
     }
 
+    pub fn id(&self) -> u32 {
+        self.imp().id.get()
+    }
+
     pub fn notification(&self) -> Option<Rc<Notification>> {
         self.imp().notification.borrow().upgrade()
     }
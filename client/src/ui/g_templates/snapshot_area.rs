@@ -0,0 +1,59 @@
+mod imp {
+    use std::cell::RefCell;
+
+    use gtk4::glib;
+    use gtk4::prelude::WidgetExt;
+    use gtk4::subclass::prelude::*;
+
+    pub type SnapshotFunc = Box<dyn Fn(&super::SnapshotArea, &gtk4::Snapshot, f64, f64)>;
+
+    #[derive(Default)]
+    pub struct SnapshotArea {
+        pub func: RefCell<Option<SnapshotFunc>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SnapshotArea {
+        const NAME: &'static str = "SnapshotArea";
+        type Type = super::SnapshotArea;
+        type ParentType = gtk4::Widget;
+    }
+
+    impl ObjectImpl for SnapshotArea {}
+    impl WidgetImpl for SnapshotArea {
+        fn snapshot(&self, snapshot: &gtk4::Snapshot) {
+            let obj = self.obj();
+            if let Some(func) = self.func.borrow().as_ref() {
+                func(&obj, snapshot, obj.width() as f64, obj.height() as f64);
+            }
+        }
+    }
+}
+
+use gtk4::glib::Object;
+
+gtk4::glib::wrapper! {
+    /// Counterpart to `DrawingArea` that hands out the widget's `Snapshot` instead of a Cairo
+    /// context.
+    ///
+    /// Content appended as render nodes is composited by the GPU renderer and cached between
+    /// frames, so animating a small part of the widget doesn't rerasterize the whole surface.
+    pub struct SnapshotArea(ObjectSubclass<imp::SnapshotArea>)
+        @extends gtk4::Widget,
+        @implements gtk4::Accessible, gtk4::Buildable, gtk4::ConstraintTarget;
+}
+
+impl SnapshotArea {
+    pub fn new() -> Self {
+        Object::new()
+    }
+    pub fn set_snapshot_func<F>(&self, func: F)
+    where
+        F: Fn(&SnapshotArea, &gtk4::Snapshot, f64, f64) + 'static,
+    {
+        use gtk4::{prelude::WidgetExt, subclass::prelude::ObjectSubclassIsExt};
+
+        *self.imp().func.borrow_mut() = Some(Box::new(func));
+        self.queue_draw();
+    }
+}
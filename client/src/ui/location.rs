@@ -0,0 +1,133 @@
+use std::cell::{Cell, RefCell};
+
+use gtk4::{
+    gio::{BusType, DBusCallFlags, DBusProxy, DBusProxyFlags},
+    glib::{self, variant::ToVariant},
+    prelude::DBusProxyExt,
+};
+use suite_223b::{
+    utils::{
+        astronomy::Location,
+        errors::{WatsonError, WatsonErrorKind},
+    },
+    watson_err,
+};
+
+use crate::config::AstronomyConfig;
+
+const GEOCLUE_NAME: &str = "org.freedesktop.GeoClue2";
+const MANAGER_PATH: &str = "/org/freedesktop/GeoClue2/Manager";
+const MANAGER_INTERFACE: &str = "org.freedesktop.GeoClue2.Manager";
+const CLIENT_INTERFACE: &str = "org.freedesktop.GeoClue2.Client";
+const LOCATION_INTERFACE: &str = "org.freedesktop.GeoClue2.Location";
+/// `GCLUE_ACCURACY_LEVEL_CITY`, enough for the sun and the moon
+const ACCURACY_CITY: u32 = 4;
+/// Meters the location has to move before GeoClue reports it again
+const DISTANCE_THRESHOLD: u32 = 10_000;
+
+thread_local! {
+    static CONFIGURED: Cell<Option<Location>> = const { Cell::new(None) };
+    static FOUND: Cell<Option<Location>> = const { Cell::new(None) };
+    static REQUESTED: Cell<bool> = const { Cell::new(false) };
+    /// Updates only arrive while the client lives
+    static CLIENT: RefCell<Option<DBusProxy>> = const { RefCell::new(None) };
+}
+
+pub fn configure(config: &AstronomyConfig) {
+    CONFIGURED.set(config.location);
+}
+
+/// Where the user is, from `astronomy.json` or else GeoClue. The first call asks GeoClue, so
+/// it stays `None` until GeoClue answers.
+pub fn location() -> Option<Location> {
+    if let Some(location) = CONFIGURED.get() {
+        return Some(location);
+    }
+    if !REQUESTED.replace(true) {
+        glib::spawn_future_local(async {
+            if let Err(e) = start_geoclue().await {
+                eprintln!("{:?}", e);
+            }
+        });
+    }
+    FOUND.get()
+}
+
+async fn start_geoclue() -> Result<(), WatsonError> {
+    let manager = proxy(MANAGER_PATH, MANAGER_INTERFACE).await?;
+    let reply = manager
+        .call_future("GetClient", None, DBusCallFlags::NONE, -1)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
+    let path = reply
+        .child_value(0)
+        .str()
+        .map(String::from)
+        .ok_or_else(|| watson_err!(WatsonErrorKind::InvalidData, "GeoClue returned no client"))?;
+
+    let client = proxy(&path, CLIENT_INTERFACE).await?;
+    for (name, value) in [
+        ("DesktopId", "watson".to_variant()),
+        ("RequestedAccuracyLevel", ACCURACY_CITY.to_variant()),
+        ("DistanceThreshold", DISTANCE_THRESHOLD.to_variant()),
+    ] {
+        client
+            .call_future(
+                "org.freedesktop.DBus.Properties.Set",
+                Some(&(CLIENT_INTERFACE, name, value).to_variant()),
+                DBusCallFlags::NONE,
+                -1,
+            )
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertySet, e.to_string()))?;
+    }
+
+    client.connect_g_signal(None, |_, _, signal, parameters| {
+        if signal != "LocationUpdated" {
+            return;
+        }
+        let Some(path) = parameters.child_value(1).str().map(String::from) else {
+            return;
+        };
+        glib::spawn_future_local(async move {
+            match read_location(&path).await {
+                Ok(location) => FOUND.set(Some(location)),
+                Err(e) => eprintln!("{:?}", e),
+            }
+        });
+    });
+    CLIENT.replace(Some(client.clone()));
+
+    client
+        .call_future("Start", None, DBusCallFlags::NONE, -1)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
+    Ok(())
+}
+
+async fn read_location(path: &str) -> Result<Location, WatsonError> {
+    let proxy = proxy(path, LOCATION_INTERFACE).await?;
+    let property = |name: &str| {
+        proxy
+            .cached_property(name)
+            .and_then(|v| v.get::<f64>())
+            .ok_or_else(|| watson_err!(WatsonErrorKind::DBusPropertyGet, name.to_string()))
+    };
+    Ok(Location {
+        latitude: property("Latitude")?,
+        longitude: property("Longitude")?,
+    })
+}
+
+async fn proxy(path: &str, interface: &str) -> Result<DBusProxy, WatsonError> {
+    DBusProxy::for_bus_future(
+        BusType::System,
+        DBusProxyFlags::NONE,
+        None,
+        GEOCLUE_NAME,
+        path,
+        interface,
+    )
+    .await
+    .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))
+}
@@ -2,12 +2,20 @@ use gtk4::glib::WeakRef;
 
 use crate::ui::g_templates::main_window::MainWindow;
 
-mod g_templates;
+pub mod edit;
+pub mod g_templates;
+pub mod location;
+pub mod output;
+pub mod preview;
+pub mod theme;
 pub mod utils;
 pub mod widgets;
-mod window;
+pub mod window;
+mod window_state;
 
 #[derive(Default)]
 pub struct WatsonUi {
     pub window: WeakRef<MainWindow>,
+    /// The last session ended with the window hidden
+    pub hidden: bool,
 }
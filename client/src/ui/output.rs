@@ -0,0 +1,85 @@
+use std::{cell::RefCell, rc::Rc};
+
+use gtk4::{
+    Widget,
+    gdk::{Display, Monitor},
+    glib::object::{CastNone, IsA, ObjectExt},
+    prelude::{DisplayExt, ListModelExt, MonitorExt, NativeExt, SurfaceExt, WidgetExt},
+};
+use gtk4_layer_shell::LayerShell;
+
+use crate::ui::g_templates::main_window::MainWindow;
+
+/// The monitor `widget` is shown on, `None` until it is
+pub fn current_monitor(widget: &impl IsA<Widget>) -> Option<Monitor> {
+    let surface = widget.native()?.surface()?;
+    surface.display().monitor_at_surface(&surface)
+}
+
+/// The first connected monitor of `targets`, which are connector names like `DP-1`, part of a
+/// monitor's description like `Dell`, or `any`
+pub fn pick_monitor(targets: &[String]) -> Option<Monitor> {
+    let list = Display::default()?.monitors();
+    let monitors: Vec<Monitor> = (0..list.n_items())
+        .filter_map(|i| list.item(i).and_downcast::<Monitor>())
+        .collect();
+    targets.iter().find_map(|target| {
+        monitors
+            .iter()
+            .find(|monitor| matches(target, monitor))
+            .cloned()
+    })
+}
+
+fn matches(target: &str, monitor: &Monitor) -> bool {
+    target == "any"
+        || monitor.connector().is_some_and(|c| c.as_str() == target)
+        || monitor.description().is_some_and(|d| d.contains(target))
+}
+
+/// Calls `f` whenever the window moves to another monitor or monitors come and go
+pub fn connect_monitor_changed<F: Fn(&MainWindow) + 'static>(win: &MainWindow, f: F) {
+    let f = Rc::new(f);
+    // The surface only exists once the window is shown
+    win.connect_realize({
+        let f = Rc::clone(&f);
+        move |win| {
+            let Some(surface) = win.surface() else {
+                return;
+            };
+            let win = win.downgrade();
+            let f = Rc::clone(&f);
+            surface.connect_enter_monitor(move |_, _| {
+                if let Some(win) = win.upgrade() {
+                    f(&win);
+                }
+            });
+        }
+    });
+    if let Some(display) = Display::default() {
+        let win = win.downgrade();
+        display.monitors().connect_items_changed(move |_, _, _, _| {
+            if let Some(win) = win.upgrade() {
+                f(&win);
+            }
+        });
+    }
+}
+
+/// Keeps the window on the first connected monitor of `targets`, moving it over as monitors
+/// are plugged in and out
+pub fn place_window(win: &MainWindow, targets: Vec<String>) {
+    let placed = Rc::new(RefCell::new(None::<Monitor>));
+    let place = move |win: &MainWindow| {
+        let Some(monitor) = pick_monitor(&targets) else {
+            return;
+        };
+        if placed.borrow().as_ref() == Some(&monitor) {
+            return;
+        }
+        win.set_monitor(Some(&monitor));
+        placed.replace(Some(monitor));
+    };
+    place(win);
+    connect_monitor_changed(win, place);
+}
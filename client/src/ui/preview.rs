@@ -0,0 +1,137 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use gtk4::{
+    DrawingArea, Widget,
+    gio::{self, FileMonitor, FileMonitorEvent, FileMonitorFlags, prelude::FileExt},
+    glib::object::{Cast, ObjectExt},
+    pango::Weight,
+    prelude::{DrawingAreaExtManual, FileMonitorExt, WidgetExt},
+    subclass::prelude::ObjectSubclassIsExt,
+};
+use suite_223b::{
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::ui::{g_templates::main_window::MainWindow, widgets::TextLayout};
+
+/// How often the outlines follow the layout, e.g. while the window is resized
+const OUTLINE_REFRESH: Duration = Duration::from_millis(250);
+
+/// Handles `watson --preview <file>`. Returns the layout file to preview.
+pub fn preview_request(args: std::env::Args) -> Option<PathBuf> {
+    let mut args = args.skip(1);
+    if args.next().as_deref() != Some("--preview") {
+        return None;
+    }
+    args.next().map(PathBuf::from)
+}
+
+fn children(widget: &Widget) -> impl Iterator<Item = Widget> {
+    std::iter::successors(widget.first_child(), |c| c.next_sibling())
+}
+
+/// `#id` for widgets with an id. Top level widgets without one are named after their css
+/// classes.
+fn outline_label(widget: &Widget, top_level: bool) -> Option<String> {
+    let name = widget.widget_name();
+    // GTK falls back to the type name
+    if name != widget.type_().name() {
+        return Some(format!("#{name}"));
+    }
+    if !top_level {
+        return None;
+    }
+    let classes = widget.css_classes();
+    if classes.is_empty() {
+        return Some(name.to_string());
+    }
+    Some(classes.iter().map(|c| format!(".{c}")).collect())
+}
+
+/// Draws the bounding boxes of the top level widgets and of every widget with an id over the
+/// window's content
+pub fn outline_widgets(win: &MainWindow) {
+    let imp = win.imp();
+    let area = DrawingArea::new();
+    area.set_can_target(false);
+
+    let viewport = imp.viewport.downgrade();
+    area.set_draw_func(move |area, ctx, _, _| {
+        let Some(viewport) = viewport.upgrade() else {
+            return;
+        };
+        ctx.set_line_width(1.0);
+
+        let mut stack: Vec<(Widget, bool)> =
+            children(viewport.upcast_ref()).map(|c| (c, true)).collect();
+        while let Some((widget, top_level)) = stack.pop() {
+            stack.extend(children(&widget).map(|c| (c, false)));
+            if !widget.is_drawable() {
+                continue;
+            }
+            let Some(label) = outline_label(&widget, top_level) else {
+                continue;
+            };
+            let Some(bounds) = widget.compute_bounds(area) else {
+                continue;
+            };
+
+            let (x, y) = (bounds.x() as f64, bounds.y() as f64);
+            let (w, h) = (bounds.width() as f64, bounds.height() as f64);
+            ctx.set_source_rgba(0.91, 0.28, 0.33, if top_level { 0.9 } else { 0.6 });
+            ctx.rectangle(x + 0.5, y + 0.5, w - 1.0, h - 1.0);
+            let _ = ctx.stroke();
+            TextLayout::new(ctx, &label, "Sans", 11.0, Weight::Normal)
+                .max_width(w - 6.0)
+                .show_baseline(ctx, x + 3.0, y + 12.0);
+        }
+    });
+
+    gtk4::glib::timeout_add_local(OUTLINE_REFRESH, {
+        let area = area.downgrade();
+        move || match area.upgrade() {
+            Some(area) => {
+                area.queue_draw();
+                gtk4::glib::ControlFlow::Continue
+            }
+            None => gtk4::glib::ControlFlow::Break,
+        }
+    });
+
+    imp.overlay.add_overlay(&area);
+}
+
+/// Sends on `tx` whenever a layout file next to `path` is saved, which covers includes in the
+/// same directory. Watching stops once the monitor is dropped.
+pub fn watch_layout(
+    path: &Path,
+    tx: UnboundedSender<Option<String>>,
+) -> Result<FileMonitor, WatsonError> {
+    let dir = path
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let monitor = gio::File::for_path(dir)
+        .monitor_directory(FileMonitorFlags::NONE, gio::Cancellable::NONE)
+        .map_err(|e| watson_err!(WatsonErrorKind::IO, e.to_string()))?;
+    monitor.connect_changed(move |_, file, _, event| {
+        let is_layout = file
+            .path()
+            .is_some_and(|p| p.extension().is_some_and(|e| e == "json"));
+        // Editors either write in place or replace the file
+        if is_layout
+            && matches!(
+                event,
+                FileMonitorEvent::ChangesDoneHint | FileMonitorEvent::Created
+            )
+        {
+            let _result = tx.send(None);
+        }
+    });
+    Ok(monitor)
+}
@@ -0,0 +1,504 @@
+use std::{
+    cell::{Cell, RefCell},
+    path::{Path, PathBuf},
+    rc::Rc,
+    str::FromStr,
+};
+
+use gtk4::{
+    CssProvider, Widget,
+    gdk::{Display, RGBA, Texture},
+    gio::{
+        self, BusType, DBusCallFlags, DBusProxy, DBusProxyFlags, FileMonitor, FileMonitorEvent,
+        FileMonitorFlags, prelude::FileExt,
+    },
+    glib::{self, Variant, variant::ToVariant},
+    prelude::{DBusProxyExt, FileMonitorExt, TextureExt, TextureExtManual, WidgetExt},
+};
+use suite_223b::{
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+use crate::{
+    config::ThemeConfig,
+    ui::widgets::{Hsl, Rgba},
+};
+
+/// `--accent` of the stylesheet, for renderers asking before a palette exists
+const DEFAULT_ACCENT: &str = "#5688c7";
+/// Pixels sampled from the wallpaper, whatever its size
+const MAX_SAMPLES: usize = 4096;
+const ITERATIONS: usize = 12;
+
+const PORTAL_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SETTINGS_INTERFACE: &str = "org.freedesktop.portal.Settings";
+const BACKGROUND_NAMESPACE: &str = "org.gnome.desktop.background";
+const BACKGROUND_KEY: &str = "picture-uri";
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+const ACCENT_KEY: &str = "accent-color";
+
+thread_local! {
+    static PALETTE: RefCell<Option<Palette>> = const { RefCell::new(None) };
+    /// The desktop's accent from the portal, used until the wallpaper gives one
+    static SYSTEM_ACCENT: Cell<Option<Rgba>> = const { Cell::new(None) };
+}
+
+/// Colors of the wallpaper
+#[derive(Debug, Clone)]
+pub struct Palette {
+    /// Most common first
+    pub colors: Vec<Rgba>,
+    /// The most colorful of the common ones
+    pub accent: Rgba,
+}
+impl Palette {
+    /// Groups the pixels into `size` clusters with k-means
+    fn from_pixels(pixels: &[[f64; 3]], size: usize) -> Option<Self> {
+        let clusters = kmeans(pixels, size.max(1))?;
+        let color = |c: [f64; 3]| Rgba {
+            r: c[0],
+            g: c[1],
+            b: c[2],
+            a: 1.0,
+        };
+        // Large and saturated beats large and grey
+        let accent = clusters
+            .iter()
+            .max_by(|a, b| accent_score(a).total_cmp(&accent_score(b)))
+            .map(|(c, _)| color(*c))?;
+        Some(Self {
+            colors: clusters.into_iter().map(|(c, _)| color(c)).collect(),
+            accent,
+        })
+    }
+}
+
+fn accent_score((center, count): &([f64; 3], usize)) -> f64 {
+    let hsl = Hsl::from(Rgba {
+        r: center[0],
+        g: center[1],
+        b: center[2],
+        a: 1.0,
+    });
+    // Near black and white count for little however saturated
+    let chroma = hsl.s * (1.0 - (2.0 * hsl.l - 1.0).abs());
+    *count as f64 * (0.1 + chroma)
+}
+
+/// Resolves `accent` and `palette-<n>` in color configs, so renderers follow the wallpaper
+pub fn named_color(name: &str) -> Option<Rgba> {
+    let index = match name {
+        "accent" => None,
+        _ => Some(name.strip_prefix("palette-")?.parse::<usize>().ok()?),
+    };
+    let Some(palette) = PALETTE.with_borrow(Clone::clone) else {
+        return SYSTEM_ACCENT
+            .get()
+            .or_else(|| Rgba::from_str(DEFAULT_ACCENT).ok());
+    };
+    Some(match index {
+        Some(i) => palette.colors.get(i).copied().unwrap_or(palette.accent),
+        None => palette.accent,
+    })
+}
+
+/// Keeps `--accent` and `--palette-<n>` in line with the wallpaper. Lives as long as the app.
+pub struct Theming {
+    provider: CssProvider,
+    palette_size: usize,
+    monitor: RefCell<Option<FileMonitor>>,
+    portal: RefCell<Option<DBusProxy>>,
+}
+impl Theming {
+    pub fn start(config: ThemeConfig) {
+        if !config.wallpaper_colors {
+            return;
+        }
+        let Some(display) = Display::default() else {
+            return;
+        };
+        let provider = CssProvider::new();
+        // Over the variables of the stylesheet and the desktop's accent
+        gtk4::style_context_add_provider_for_display(
+            &display,
+            &provider,
+            gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION + 2,
+        );
+        let theming = Rc::new(Self {
+            provider,
+            palette_size: config.palette_size,
+            monitor: RefCell::new(None),
+            portal: RefCell::new(None),
+        });
+
+        match config.wallpaper {
+            Some(path) => {
+                if let Err(e) = theming.watch_file(path) {
+                    eprintln!("{:?}", e);
+                }
+            }
+            None => {
+                glib::spawn_future_local(async move {
+                    if let Err(e) = theming.watch_portal().await {
+                        eprintln!("{:?}", e);
+                    }
+                });
+            }
+        }
+    }
+
+    fn watch_file(self: &Rc<Self>, path: PathBuf) -> Result<(), WatsonError> {
+        let monitor = gio::File::for_path(&path)
+            .monitor_file(FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+            .map_err(|e| watson_err!(WatsonErrorKind::IO, e.to_string()))?;
+        monitor.connect_changed({
+            let theming = Rc::clone(self);
+            let path = path.clone();
+            move |_, _, _, event| {
+                // Written in place, replaced, or a symlink swapped by a wallpaper tool
+                if matches!(
+                    event,
+                    FileMonitorEvent::ChangesDoneHint
+                        | FileMonitorEvent::Created
+                        | FileMonitorEvent::MovedIn
+                        | FileMonitorEvent::Renamed
+                ) {
+                    theming.regenerate(path.clone());
+                }
+            }
+        });
+        self.monitor.replace(Some(monitor));
+        self.regenerate(path);
+        Ok(())
+    }
+
+    /// Follows GNOME's background through the desktop portal's settings
+    async fn watch_portal(self: &Rc<Self>) -> Result<(), WatsonError> {
+        let proxy = DBusProxy::for_bus_future(
+            BusType::Session,
+            DBusProxyFlags::NONE,
+            None,
+            PORTAL_NAME,
+            PORTAL_PATH,
+            SETTINGS_INTERFACE,
+        )
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+
+        proxy.connect_g_signal(None, {
+            let theming = Rc::clone(self);
+            move |_, _, signal, parameters| {
+                if signal != "SettingChanged" {
+                    return;
+                }
+                let Some((namespace, key, value)) = parameters.get::<(String, String, Variant)>()
+                else {
+                    return;
+                };
+                if namespace == BACKGROUND_NAMESPACE
+                    && key == BACKGROUND_KEY
+                    && let Some(path) = wallpaper_path(&value)
+                {
+                    theming.regenerate(path);
+                }
+            }
+        });
+        // Changes still arrive when the first read fails
+        self.portal.replace(Some(proxy.clone()));
+
+        let reply = proxy
+            .call_future(
+                "ReadOne",
+                Some(&(BACKGROUND_NAMESPACE, BACKGROUND_KEY).to_variant()),
+                DBusCallFlags::NONE,
+                -1,
+            )
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
+
+        let path = reply
+            .child_value(0)
+            .as_variant()
+            .and_then(|v| wallpaper_path(&v))
+            .ok_or_else(|| {
+                watson_err!(
+                    WatsonErrorKind::InvalidData,
+                    "The desktop portal has no wallpaper, set `wallpaper` in theme.json"
+                )
+            })?;
+        self.regenerate(path);
+        Ok(())
+    }
+
+    fn regenerate(self: &Rc<Self>, path: PathBuf) {
+        let theming = Rc::clone(self);
+        glib::spawn_future_local(async move {
+            let size = theming.palette_size;
+            let Ok(result) = gio::spawn_blocking(move || extract_palette(&path, size)).await else {
+                return;
+            };
+            match result {
+                Ok(palette) => theming.apply(palette),
+                Err(e) => eprintln!("{:?}", e),
+            }
+        });
+    }
+
+    fn apply(&self, palette: Palette) {
+        let mut css = String::from(":root {\n");
+        css.push_str(&format!("    --accent: {};\n", RGBA::from(palette.accent)));
+        for (i, color) in palette.colors.iter().enumerate() {
+            css.push_str(&format!("    --palette-{i}: {};\n", RGBA::from(*color)));
+        }
+        css.push('}');
+        self.provider.load_from_string(&css);
+
+        PALETTE.set(Some(palette));
+        // Renderers read the palette while drawing
+        for window in gtk4::Window::list_toplevels() {
+            redraw(&window);
+        }
+    }
+}
+
+/// Follows the desktop's color scheme and accent through the portal's settings, which also
+/// reach into Flatpak. Lives as long as the app.
+pub struct Appearance {
+    provider: CssProvider,
+    portal: RefCell<Option<DBusProxy>>,
+}
+impl Appearance {
+    pub fn start() {
+        let Some(display) = Display::default() else {
+            return;
+        };
+        let provider = CssProvider::new();
+        // Over the stylesheet, under the wallpaper's colors
+        gtk4::style_context_add_provider_for_display(
+            &display,
+            &provider,
+            gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION + 1,
+        );
+        let appearance = Rc::new(Self {
+            provider,
+            portal: RefCell::new(None),
+        });
+        glib::spawn_future_local(async move {
+            if let Err(e) = appearance.watch_portal().await {
+                eprintln!("{:?}", e);
+            }
+        });
+    }
+
+    async fn watch_portal(self: &Rc<Self>) -> Result<(), WatsonError> {
+        let proxy = DBusProxy::for_bus_future(
+            BusType::Session,
+            DBusProxyFlags::NONE,
+            None,
+            PORTAL_NAME,
+            PORTAL_PATH,
+            SETTINGS_INTERFACE,
+        )
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+
+        proxy.connect_g_signal(None, {
+            let appearance = Rc::clone(self);
+            move |_, _, signal, parameters| {
+                if signal != "SettingChanged" {
+                    return;
+                }
+                let Some((namespace, key, value)) = parameters.get::<(String, String, Variant)>()
+                else {
+                    return;
+                };
+                if namespace == APPEARANCE_NAMESPACE {
+                    appearance.apply(&key, &value);
+                }
+            }
+        });
+        self.portal.replace(Some(proxy.clone()));
+
+        for key in [COLOR_SCHEME_KEY, ACCENT_KEY] {
+            let reply = proxy
+                .call_future(
+                    "ReadOne",
+                    Some(&(APPEARANCE_NAMESPACE, key).to_variant()),
+                    DBusCallFlags::NONE,
+                    -1,
+                )
+                .await
+                .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
+            if let Some(value) = reply.child_value(0).as_variant() {
+                self.apply(key, &value);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&self, key: &str, value: &Variant) {
+        match key {
+            COLOR_SCHEME_KEY => {
+                // 0 is no preference, GTK's own setting stays
+                let dark = match value.get::<u32>() {
+                    Some(1) => true,
+                    Some(2) => false,
+                    _ => return,
+                };
+                if let Some(settings) = gtk4::Settings::default() {
+                    settings.set_gtk_application_prefer_dark_theme(dark);
+                }
+            }
+            ACCENT_KEY => {
+                let accent = portal_accent(value);
+                SYSTEM_ACCENT.set(accent);
+                let css = accent
+                    .map(|a| format!(":root {{\n    --accent: {};\n}}", RGBA::from(a)))
+                    .unwrap_or_default();
+                self.provider.load_from_string(&css);
+                if PALETTE.with_borrow(Option::is_none) {
+                    for window in gtk4::Window::list_toplevels() {
+                        redraw(&window);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `accent-color` is `(ddd)`, out of range values mean the desktop has none
+fn portal_accent(value: &Variant) -> Option<Rgba> {
+    let (r, g, b) = value.get::<(f64, f64, f64)>()?;
+    [r, g, b]
+        .iter()
+        .all(|c| (0.0..=1.0).contains(c))
+        .then_some(Rgba { r, g, b, a: 1.0 })
+}
+
+fn redraw(widget: &Widget) {
+    widget.queue_draw();
+    let mut child = widget.first_child();
+    while let Some(c) = child {
+        redraw(&c);
+        child = c.next_sibling();
+    }
+}
+
+/// `picture-uri` holds a `file://` uri
+fn wallpaper_path(value: &Variant) -> Option<PathBuf> {
+    gio::File::for_uri(value.str()?).path()
+}
+
+fn extract_palette(path: &Path, size: usize) -> Result<Palette, WatsonError> {
+    let texture = Texture::from_filename(path)
+        .map_err(|e| watson_err!(WatsonErrorKind::FileRead, e.to_string()))?;
+    let (width, height) = (texture.width() as usize, texture.height() as usize);
+    let stride = width * 4;
+    let mut data = vec![0u8; stride * height];
+    // Premultiplied BGRA
+    texture.download(&mut data, stride);
+
+    let step = (width * height / MAX_SAMPLES).max(1);
+    let pixels: Vec<[f64; 3]> = data
+        .chunks_exact(4)
+        .step_by(step)
+        .filter(|p| p[3] > 0)
+        .map(|p| {
+            let a = p[3] as f64;
+            [p[2] as f64 / a, p[1] as f64 / a, p[0] as f64 / a]
+        })
+        .collect();
+    Palette::from_pixels(&pixels, size).ok_or_else(|| {
+        watson_err!(
+            WatsonErrorKind::InvalidData,
+            format!("{} has no visible pixels", path.display())
+        )
+    })
+}
+
+fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum()
+}
+
+/// Cluster centers with their sizes, largest first. Seeded with the pixels farthest apart, so
+/// the same wallpaper always gives the same palette.
+fn kmeans(pixels: &[[f64; 3]], k: usize) -> Option<Vec<([f64; 3], usize)>> {
+    let nearest = |centers: &[[f64; 3]], p: &[f64; 3]| {
+        centers
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i, distance(c, p)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    };
+
+    let mut centers = vec![*pixels.first()?];
+    while centers.len() < k {
+        let (farthest, d) = pixels
+            .iter()
+            .map(|p| (*p, nearest(&centers, p).map_or(0.0, |(_, d)| d)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+        // Fewer distinct colors than asked for
+        if d == 0.0 {
+            break;
+        }
+        centers.push(farthest);
+    }
+
+    let mut counts = vec![0; centers.len()];
+    for _ in 0..ITERATIONS {
+        let mut sums = vec![[0.0; 3]; centers.len()];
+        counts = vec![0; centers.len()];
+        for p in pixels {
+            let Some((i, _)) = nearest(&centers, p) else {
+                continue;
+            };
+            counts[i] += 1;
+            sums[i].iter_mut().zip(p).for_each(|(s, v)| *s += v);
+        }
+        for ((center, sum), count) in centers.iter_mut().zip(&sums).zip(&counts) {
+            if *count > 0 {
+                *center = sum.map(|s| s / *count as f64);
+            }
+        }
+    }
+
+    let mut clusters: Vec<([f64; 3], usize)> = centers
+        .into_iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    clusters.sort_by(|a, b| b.1.cmp(&a.1));
+    Some(clusters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_prefers_colorful_accent() {
+        let grey = [0.5, 0.5, 0.5];
+        let blue = [0.1, 0.2, 0.9];
+        let pixels: Vec<[f64; 3]> = std::iter::repeat_n(grey, 70)
+            .chain(std::iter::repeat_n(blue, 30))
+            .collect();
+
+        let palette = Palette::from_pixels(&pixels, 3).unwrap();
+        // Only two colors to find
+        assert_eq!(palette.colors.len(), 2);
+        assert!((palette.colors[0].r - 0.5).abs() < 1e-9);
+        assert!((palette.accent.b - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_portal_accent_out_of_range_is_unset() {
+        let accent = portal_accent(&(0.2, 0.4, 1.0).to_variant()).unwrap();
+        assert!((accent.g - 0.4).abs() < 1e-9);
+        assert!(portal_accent(&(-1.0, -1.0, -1.0).to_variant()).is_none());
+        assert!(portal_accent(&1u32.to_variant()).is_none());
+    }
+}
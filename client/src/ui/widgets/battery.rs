@@ -4,29 +4,33 @@ use crate::{
     config::WidgetSpec,
     ui::widgets::utils::{
         WidgetOption,
+        animation::Spin,
+        power,
         render::{CairoShapesExt, Rgba},
+        state::StateClass,
     },
 };
 use gtk4::{
     Align, Box, DrawingArea,
-    cairo::{Context, LineCap},
-    glib::{WeakRef, object::ObjectExt},
+    cairo::{Context, LineCap, LinearGradient},
+    gio,
+    glib::{self, WeakRef, object::ObjectExt},
     prelude::{BoxExt, DrawingAreaExtManual, WidgetExt},
 };
-use suite_223b::{
-    protocol::BatteryState,
-    utils::errors::{WatsonError, WatsonErrorKind},
-    watson_err,
-};
+use suite_223b::{protocol::BatteryState, utils::battery::read_battery};
+
+/// Seconds for the sheen to go once around the ring while charging
+const CHARGING_PERIOD: f64 = 3.0;
 
 #[derive(Clone, Debug)]
 pub struct Battery {
     pub weak: WeakRef<DrawingArea>,
     pub status: Rc<Cell<BatteryStatus>>,
+    threshold: u8,
 }
 impl Battery {
     pub fn poll_state(&self) {
-        self.status.set(BatteryStatus::poll());
+        self.set_status(BatteryStatus::poll());
     }
     pub fn update_state(&self, state: BatteryState, percentage: u32) {
         let status = match state {
@@ -35,7 +39,13 @@ impl Battery {
             BatteryState::Discharging => BatteryStatus::Discharging(percentage),
             _ => BatteryStatus::Invalid,
         };
-        self.status.set(status)
+        self.set_status(status)
+    }
+    fn set_status(&self, status: BatteryStatus) {
+        self.status.set(status);
+        if let Some(strong) = self.weak.upgrade() {
+            status.set_classes(&strong, self.threshold);
+        }
     }
     pub fn queue_draw(&self) {
         if let Some(strong) = self.weak.upgrade() {
@@ -47,10 +57,16 @@ impl Battery {
 pub struct BatteryBuilder {
     ui: WidgetOption<DrawingArea>,
     status: Rc<Cell<BatteryStatus>>,
+    threshold: u8,
 }
 impl BatteryBuilder {
     pub fn new(specs: WidgetSpec, in_holder: bool) -> Self {
         let base = specs.base();
+        let interval = base.update_interval.unwrap_or(30);
+        let threshold = match specs {
+            WidgetSpec::Battery { threshold, .. } => threshold,
+            _ => 0,
+        };
 
         let builder = DrawingArea::builder().css_classes(["widget", "battery"]);
 
@@ -78,36 +94,64 @@ impl BatteryBuilder {
             bat_area.add_css_class(class);
         }
 
-        let status = BatteryStatus::poll();
-        let status = Rc::new(Cell::new(status));
+        let status = Rc::new(Cell::new(BatteryStatus::Invalid));
+        let spin = Rc::new(Spin::new(CHARGING_PERIOD));
 
         bat_area.set_draw_func({
             let status = Rc::clone(&status);
+            let spin = Rc::clone(&spin);
             move |area, ctx, width, height| {
                 let tooltip = status
                     .get()
                     .to_percentage()
                     .map(|s| format!("{}%", s * 100.0).to_string());
                 area.set_tooltip_markup(tooltip.as_deref());
-                Battery::draw(area, ctx, width, height, &specs, Rc::clone(&status));
+                Battery::draw(area, ctx, width, height, &specs, Rc::clone(&status), &spin);
             }
         });
-
-        let clock_area_clone = bat_area.downgrade();
-        gtk4::glib::timeout_add_seconds_local(30, {
+        bat_area.add_tick_callback({
             let status = Rc::clone(&status);
-            move || {
-                status.set(BatteryStatus::poll());
-                if let Some(clock) = clock_area_clone.upgrade() {
-                    clock.queue_draw();
+            move |widget, frame_clock| {
+                let charging = matches!(status.get(), BatteryStatus::Charging(_));
+                if !charging || power::is_saving_power() {
+                    spin.stop();
+                    return gtk4::glib::ControlFlow::Continue;
                 }
+                spin.update(frame_clock);
+                widget.queue_draw();
                 gtk4::glib::ControlFlow::Continue
             }
         });
 
+        let refresh = {
+            let status = Rc::clone(&status);
+            let area = bat_area.downgrade();
+            move || {
+                let status = Rc::clone(&status);
+                let area = area.clone();
+                glib::spawn_future_local(async move {
+                    // Off the main thread, the BSD sources wait for subprocesses
+                    let Ok(polled) = gio::spawn_blocking(BatteryStatus::poll).await else {
+                        return;
+                    };
+                    status.set(polled);
+                    if let Some(area) = area.upgrade() {
+                        polled.set_classes(&area, threshold);
+                        area.queue_draw();
+                    }
+                });
+            }
+        };
+        refresh();
+        power::every(interval, move || {
+            refresh();
+            gtk4::glib::ControlFlow::Continue
+        });
+
         Self {
             ui: WidgetOption::Owned(bat_area),
             status,
+            threshold,
         }
     }
     pub fn for_box(mut self, container: &Box) -> Self {
@@ -121,6 +165,7 @@ impl BatteryBuilder {
         Battery {
             weak,
             status: self.status,
+            threshold: self.threshold,
         }
     }
 }
@@ -133,6 +178,7 @@ impl Battery {
         height: i32,
         specs: &WidgetSpec,
         status: Rc<Cell<BatteryStatus>>,
+        spin: &Spin,
     ) {
         let WidgetSpec::Battery {
             base: _,
@@ -195,6 +241,10 @@ impl Battery {
         );
         ctx.stroke().unwrap();
 
+        if matches!(status.get(), BatteryStatus::Charging(_)) && !power::is_saving_power() {
+            Self::draw_sheen(ctx, &bat, percentage, spin.phase());
+        }
+
         ctx.save().unwrap();
         ctx.translate(bat.center, bat.center);
         ctx.set_line_width(0.1);
@@ -213,6 +263,26 @@ impl Battery {
         ctx.fill().unwrap();
         ctx.restore().unwrap();
     }
+    /// A light band across the progress arc, turning with `phase`
+    fn draw_sheen(ctx: &Context, bat: &BatteryContext, percentage: f64, phase: f64) {
+        let radius = bat.height / 2.0 - bat.line_width / 2.0;
+        let angle = phase * std::f64::consts::TAU;
+        let (dx, dy) = (radius * angle.cos(), radius * angle.sin());
+        let sheen = LinearGradient::new(
+            bat.center - dx,
+            bat.center - dy,
+            bat.center + dx,
+            bat.center + dy,
+        );
+        sheen.add_color_stop_rgba(0.0, 1.0, 1.0, 1.0, 0.0);
+        sheen.add_color_stop_rgba(0.8, 1.0, 1.0, 1.0, 0.0);
+        sheen.add_color_stop_rgba(0.95, 1.0, 1.0, 1.0, 0.35);
+        sheen.add_color_stop_rgba(1.0, 1.0, 1.0, 1.0, 0.0);
+
+        ctx.set_source(&sheen).unwrap();
+        CairoShapesExt::circle_path(ctx, bat.center, bat.center, radius, percentage);
+        ctx.stroke().unwrap();
+    }
     fn draw_bolt(ctx: &Context, bat: &BatteryContext) {
         let bolt_size = bat.height * 0.25;
         ctx.scale(bolt_size, bolt_size);
@@ -293,44 +363,13 @@ pub enum BatteryStatus {
 }
 impl BatteryStatus {
     fn poll() -> Self {
-        let status_path = "/sys/class/power_supply/BAT0/status";
-        let status = {
-            let status_opt = std::fs::read_to_string(status_path);
-
-            match status_opt {
-                Ok(c) => c.trim().to_lowercase(),
-                _ => return Self::Invalid,
-            }
-        };
-
-        let capacity = match Self::capacity() {
-            Ok(cap) => cap,
-            Err(_) => return Self::Invalid,
-        };
-
-        match status.as_str() {
-            "discharging" => Self::Discharging(capacity),
-            "full" => Self::Full(capacity),
-            "charging" => Self::Charging(capacity),
+        match read_battery() {
+            Ok((BatteryState::Discharging, capacity)) => Self::Discharging(capacity),
+            Ok((BatteryState::Full, capacity)) => Self::Full(capacity),
+            Ok((BatteryState::Charging, capacity)) => Self::Charging(capacity),
             _ => Self::Invalid,
         }
     }
-    fn capacity() -> Result<u32, WatsonError> {
-        let capacity_path = "/sys/class/power_supply/BAT0/capacity";
-        let capacity = {
-            let capacity_opt = std::fs::read_to_string(capacity_path)
-                .expect("Failed to read capacity")
-                .trim()
-                .parse::<u32>();
-
-            match capacity_opt {
-                Ok(c) => c,
-                Err(e) => return Err(watson_err!(WatsonErrorKind::Deserialize, e.to_string())),
-            }
-        };
-
-        Ok(capacity)
-    }
     fn to_percentage(&self) -> Option<f64> {
         match self {
             Self::Full(d) => Some(*d as f64 / 100.0),
@@ -339,4 +378,17 @@ impl BatteryStatus {
             Self::Invalid => None,
         }
     }
+    /// `.warning` at or below `threshold` percent, `.critical` at or below half of it
+    fn set_classes(&self, area: &DrawingArea, threshold: u8) {
+        let threshold = threshold as u32;
+        let low = match *self {
+            Self::Discharging(d) => Some(d),
+            _ => None,
+        };
+        let critical = low.is_some_and(|d| d <= threshold / 2);
+        StateClass::Charging.set(area, matches!(self, Self::Charging(_)));
+        StateClass::Critical.set(area, critical);
+        StateClass::Warning.set(area, !critical && low.is_some_and(|d| d <= threshold));
+        StateClass::Stale.set(area, matches!(self, Self::Invalid));
+    }
 }
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use gtk4::{
+    Box, GestureClick, Grid, Image, Label, Popover, Widget,
+    glib::object::{Cast, IsA, ObjectExt},
+    prelude::{BoxExt, GestureExt, GridExt, PopoverExt, WidgetExt},
+};
+use suite_223b::protocol::{AtomicSystemState, BluetoothDevice};
+
+use crate::ui::widgets::utils::{attach_popover, locale::tr};
+
+/// Connected devices with their battery and codec, opened with a right click on the bluetooth
+/// button
+pub struct BluetoothPopover;
+impl BluetoothPopover {
+    pub fn attach(target: &impl IsA<Widget>, system_state: Arc<AtomicSystemState>) {
+        let target = target.upcast_ref::<Widget>();
+
+        let popover = Popover::builder()
+            .css_classes(["network-popover", "bluetooth-popover"])
+            .has_arrow(true)
+            .build();
+        attach_popover(&popover, target);
+
+        let click = GestureClick::builder().button(3).build();
+        click.connect_pressed({
+            let popover = popover.downgrade();
+            move |gesture, _, _, _| {
+                gesture.set_state(gtk4::EventSequenceState::Claimed);
+                let Some(popover) = popover.upgrade() else {
+                    return;
+                };
+                let devices = system_state
+                    .bluetooth_devices
+                    .read()
+                    .map(|d| d.clone())
+                    .unwrap_or_default();
+                popover.set_child(Some(&Self::content(&devices)));
+                popover.popup();
+            }
+        });
+        target.add_controller(click);
+    }
+
+    fn content(devices: &[BluetoothDevice]) -> Box {
+        let holder = Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(8)
+            .build();
+
+        if devices.is_empty() {
+            holder.append(
+                &Label::builder()
+                    .label(tr("No devices connected"))
+                    .css_classes(["network-popover-title"])
+                    .build(),
+            );
+            return holder;
+        }
+
+        let grid = Grid::builder().column_spacing(12).row_spacing(4).build();
+        for (i, device) in devices.iter().enumerate() {
+            let row = i as i32;
+            let icon =
+                Image::from_icon_name(device.icon.as_deref().unwrap_or("bluetooth-symbolic"));
+            grid.attach(&icon, 0, row, 1, 1);
+            grid.attach(
+                &Label::builder()
+                    .label(&device.name)
+                    .css_classes(["network-popover-title"])
+                    .xalign(0.0)
+                    .hexpand(true)
+                    .build(),
+                1,
+                row,
+                1,
+                1,
+            );
+            if let Some(codec) = &device.codec {
+                grid.attach(
+                    &Label::builder()
+                        .label(codec)
+                        .css_classes(["network-popover-key"])
+                        .build(),
+                    2,
+                    row,
+                    1,
+                    1,
+                );
+            }
+            if let Some(battery) = device.battery {
+                grid.attach(&Label::new(Some(&format!("{battery}%"))), 3, row, 1, 1);
+            }
+        }
+        holder.append(&grid);
+        holder
+    }
+}
+
+/// Battery and codec of the first connected device that reports either, e.g. `80% · AAC`
+pub fn badge_text(devices: &[BluetoothDevice]) -> Option<String> {
+    let device = devices
+        .iter()
+        .find(|d| d.battery.is_some() || d.codec.is_some())?;
+    let battery = device.battery.map(|b| format!("{b}%"));
+    let parts: Vec<&str> = battery
+        .as_deref()
+        .into_iter()
+        .chain(device.codec.as_deref())
+        .collect();
+    Some(parts.join(" · "))
+}
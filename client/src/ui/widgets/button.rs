@@ -1,9 +1,14 @@
 use crate::{
+    DAEMON_TX,
     config::WidgetSpec,
-    ui::widgets::utils::{interactives::WidgetBehavior, render::Rgba},
+    ui::widgets::{
+        BackendFunc, BackendFuncType, BluetoothPopover, ColorFormat, ColorPicker, NetworkPopover,
+        bluetooth,
+        utils::{interactives::WidgetBehavior, power, render::Rgba, state::StateClass},
+    },
 };
 use gtk4::{
-    Box as GtkBox, DrawingArea, GestureClick, Image, Overlay, Widget,
+    Box as GtkBox, DrawingArea, GestureClick, Image, Label, Overlay, Widget,
     cairo::Context,
     glib::{
         WeakRef,
@@ -11,34 +16,161 @@ use gtk4::{
     },
     prelude::{BoxExt, DrawingAreaExtManual, WidgetExt},
 };
-use std::sync::Arc;
-use suite_223b::protocol::AtomicSystemState;
+use std::sync::{Arc, atomic::Ordering};
+use suite_223b::protocol::{AtomicSystemState, PowerProfiles, Request};
+
+/// Replaces the wifi icon while a cable carries the primary connection
+const WIRED_ICON: &str = "network-wired-symbolic";
+/// Shown while the network waits for a captive portal sign-in
+const PORTAL_ICON: &str = "network-wireless-no-route-symbolic";
 
 pub struct Button {
     pub weak: WeakRef<Widget>,
     pub func: Box<dyn WidgetBehavior>,
+    holder: WeakRef<Overlay>,
+    icon: WeakRef<Image>,
+    /// Set from the config, never replaced by state changes
+    custom_icon: bool,
+    /// Headset battery and codec on the bluetooth button, missed notifications on the
+    /// do-not-disturb button
+    badge: Option<WeakRef<Label>>,
+    picker: Option<ColorPicker>,
 }
 impl Button {
+    /// Set on the color picker button
+    pub fn color_picker(&self) -> Option<&ColorPicker> {
+        self.picker.as_ref()
+    }
     pub fn queue_draw(&self) {
         if let Some(strong) = self.weak.upgrade() {
             strong.queue_draw();
         }
     }
+    /// Shows the current system state, e.g. after the daemon rejected a click
+    pub fn refresh(&self, state: &AtomicSystemState) {
+        let value = self.func.get_percentage(state);
+        if let Some(holder) = self.holder.upgrade() {
+            set_state_class(&holder, value);
+        }
+        if !self.custom_icon {
+            if let Some(icon) = self.icon.upgrade() {
+                icon.set_icon_name(Some(icon_for(self.func.as_ref(), state, value)));
+            }
+        }
+        if let Some(badge) = self.badge.as_ref().and_then(|b| b.upgrade()) {
+            set_badge(&badge, self.func.func(), state);
+        }
+        self.queue_draw();
+    }
+    /// Explains why the profile can't be changed or performs worse than selected
+    pub fn show_power_profiles(&self, profiles: &PowerProfiles) {
+        let Some(holder) = self.holder.upgrade() else {
+            return;
+        };
+        let mut lines: Vec<String> = profiles
+            .holds
+            .iter()
+            .map(|h| {
+                format!(
+                    "{} held by {}: {}",
+                    h.profile.to_string(),
+                    h.application,
+                    h.reason
+                )
+            })
+            .collect();
+        if let Some(reason) = &profiles.degraded {
+            lines.push(format!("Performance limited: {}", reason.replace('-', " ")));
+        }
+        let tooltip = lines.join("\n");
+        holder.set_tooltip_text((!tooltip.is_empty()).then_some(tooltip.as_str()));
+    }
+}
+
+fn set_state_class(target: &Overlay, value: u8) {
+    let state_class = target
+        .css_classes()
+        .iter()
+        .find(|s| s.starts_with("state-"))
+        .map(|v| v.to_string());
+    if let Some(class) = state_class {
+        target.remove_css_class(&class);
+    }
+    target.add_css_class(&format!("state-{value}"));
+    StateClass::Active.set(target, value != 0);
+}
+
+fn set_badge(badge: &Label, func: BackendFuncType, state: &AtomicSystemState) {
+    let text = match func {
+        BackendFuncType::Dnd => missed_text(state.missed.load(Ordering::Relaxed)),
+        _ => state
+            .bluetooth_devices
+            .read()
+            .ok()
+            .and_then(|d| bluetooth::badge_text(&d)),
+    };
+    badge.set_visible(text.is_some());
+    badge.set_text(text.as_deref().unwrap_or_default());
+}
+
+fn add_badge(overlay: &Overlay, func: BackendFuncType, state: &AtomicSystemState) -> Label {
+    let label = Label::builder()
+        .css_classes(["button-badge"])
+        .halign(gtk4::Align::Center)
+        .valign(gtk4::Align::End)
+        .can_target(false)
+        .build();
+    set_badge(&label, func, state);
+    overlay.add_overlay(&label);
+    label
+}
+
+fn missed_text(missed: u32) -> Option<String> {
+    match missed {
+        0 => None,
+        1..=99 => Some(missed.to_string()),
+        _ => Some("99+".into()),
+    }
+}
+
+fn icon_for<'a>(func: &'a dyn WidgetBehavior, state: &AtomicSystemState, value: u8) -> &'a str {
+    if func.func() == BackendFuncType::Wifi {
+        let Ok(connectivity) = state.connectivity.read() else {
+            return func.icon_name(value);
+        };
+        if connectivity.portal.is_some() {
+            return PORTAL_ICON;
+        }
+        if connectivity.is_wired() {
+            return WIRED_ICON;
+        }
+    }
+    func.icon_name(value)
 }
 
 pub struct ButtonBuilder {
     area: DrawingArea,
     overlay: Overlay,
+    icon: Image,
+    custom_icon: bool,
+    badge: Option<Label>,
+    picker: Option<ColorPicker>,
     func: Box<dyn WidgetBehavior>,
 }
 impl ButtonBuilder {
     pub fn new(specs: WidgetSpec, system_state: Arc<AtomicSystemState>, in_holder: bool) -> Self {
         let (base, func, icon) = specs.as_button().unwrap();
+        let color_format = match &func {
+            BackendFunc::ColorPicker { format } => *format,
+            _ => ColorFormat::default(),
+        };
         let func = func.build();
+        let interval = base.update_interval.unwrap_or(30);
 
         let perc = func.get_percentage(&system_state);
 
-        let icon = icon.unwrap_or(func.icon_name(perc).to_string());
+        let custom_icon = icon.is_some();
+        let icon = icon.unwrap_or(icon_for(func.as_ref(), &system_state, perc).to_string());
 
         let initial_class = format!("state-{perc}");
         let builder =
@@ -95,7 +227,7 @@ impl ButtonBuilder {
         });
 
         let area_clone = area.downgrade();
-        gtk4::glib::timeout_add_seconds_local(30, {
+        power::every(interval, {
             move || {
                 if let Some(clock) = area_clone.upgrade() {
                     clock.queue_draw();
@@ -104,11 +236,35 @@ impl ButtonBuilder {
             }
         });
 
+        let mut badge = None;
+        let mut picker = None;
+        match func.func() {
+            BackendFuncType::Wifi => NetworkPopover::attach(&overlay, Arc::clone(&system_state)),
+            BackendFuncType::Bluetooth => {
+                BluetoothPopover::attach(&overlay, Arc::clone(&system_state));
+                badge = Some(add_badge(&overlay, func.func(), &system_state));
+            }
+            BackendFuncType::Dnd => {
+                badge = Some(add_badge(&overlay, func.func(), &system_state));
+            }
+            BackendFuncType::ColorPicker => {
+                picker = Some(ColorPicker::attach(&overlay, color_format));
+            }
+            // Available profiles and holds aren't part of the system state
+            BackendFuncType::Powermode => {
+                let _result = DAEMON_TX.get().map(|d| d.send(Request::PowerProfiles));
+            }
+            _ => {}
+        }
         Button::connect_clicked(&overlay, &svg_icon, &func, system_state);
 
         Self {
             area,
             overlay,
+            icon: svg_icon,
+            custom_icon,
+            badge,
+            picker,
             func,
         }
     }
@@ -121,6 +277,11 @@ impl ButtonBuilder {
         Button {
             weak,
             func: self.func,
+            holder: self.overlay.downgrade(),
+            icon: self.icon.downgrade(),
+            custom_icon: self.custom_icon,
+            badge: self.badge.map(|b| b.downgrade()),
+            picker: self.picker,
         }
     }
 }
@@ -151,19 +312,10 @@ impl Button {
                 times.set(times.get() ^ 1);
                 if let Some(target) = target.upgrade() {
                     if let Some(new_state) = new_state {
-                        let state_class = target
-                            .css_classes()
-                            .iter()
-                            .find(|s| s.starts_with("state-"))
-                            .map(|v| v.to_string());
-                        if let Some(class) = state_class {
-                            target.remove_css_class(&class);
-                        }
-
-                        target.add_css_class(&format!("state-{new_state}"));
+                        set_state_class(&target, new_state);
 
                         // efficient icon replace logic
-                        let new_icon = func.icon_name(new_state);
+                        let new_icon = icon_for(func.as_ref(), &state, new_state);
                         if let Some(icon_widget) = icon.upgrade() {
                             icon_widget.set_icon_name(Some(&new_icon));
                         }
@@ -1,29 +1,40 @@
+use chrono::NaiveTime;
 use gtk4::{
     Box, DrawingArea, EventControllerKey, GestureClick, Stack,
-    glib::object::ObjectExt,
+    glib::{WeakRef, object::ObjectExt},
+    pango::Weight,
     prelude::{
         BoxExt, DrawingAreaExtManual, EventControllerExt, GestureSingleExt, WidgetExt,
         WidgetExtManual,
     },
 };
 use std::{cell::RefCell, rc::Rc};
+use suite_223b::protocol::DataKind;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
 use crate::{
     config::WidgetSpec,
+    startup,
     ui::{
         g_templates::event_details::EventDetails,
         widgets::{
             Calendar,
             calendar::{
                 CalendarContext, CalendarRenderer, cache::CalendarCache,
-                data_store::CalendarDataStore,
+                data_store::CalendarDataStore, search::EventSearch,
+            },
+            utils::{
+                animation::{AnimationDirection, AnimationState, EaseFunction},
+                locale, power,
+                refresh::RefreshOverlay,
+                render::device_scale,
+                state::StateClass,
+                text::TextLayout,
             },
-            utils::animation::{AnimationDirection, AnimationState, EaseFunction},
         },
     },
 };
 
-#[derive(Default)]
 pub struct CalendarBuilder {
     area: DrawingArea,
     stack: Stack,
@@ -31,6 +42,12 @@ pub struct CalendarBuilder {
     animation_state: Rc<AnimationState>,
     data_store: Rc<CalendarDataStore>,
     context: Rc<RefCell<CalendarContext>>,
+    /// `None` reloads every calendar, a href refetches that calendar
+    refresh_tx: UnboundedSender<Option<String>>,
+    refresh_rx: RefCell<Option<UnboundedReceiver<Option<String>>>>,
+    refresh: RefreshOverlay,
+    /// Seconds between redraws
+    interval: u32,
 }
 impl CalendarBuilder {
     pub fn new() -> Self {
@@ -51,11 +68,14 @@ impl CalendarBuilder {
             .css_classes(["inner-widget", "calendar"])
             .build();
 
-        stack.add_named(&area, Some("calendar"));
+        let (overlay, refresh) = RefreshOverlay::new(&area, DataKind::Calendar);
+        stack.add_named(&overlay, Some("calendar"));
 
         let details = EventDetails::new();
         stack.add_named(&details, Some("details"));
 
+        let (refresh_tx, refresh_rx) = mpsc::unbounded_channel();
+
         Self {
             stack,
             area,
@@ -63,9 +83,13 @@ impl CalendarBuilder {
             animation_state: Rc::new(AnimationState::new()),
             data_store: Rc::new(CalendarDataStore::new()),
             context: Rc::new(RefCell::new(CalendarContext::new())),
+            refresh_tx,
+            refresh_rx: RefCell::new(Some(refresh_rx)),
+            refresh,
+            interval: 60,
         }
     }
-    pub fn for_spec(self, specs: &WidgetSpec) -> Self {
+    pub fn for_spec(mut self, specs: &WidgetSpec) -> Self {
         let WidgetSpec::Calendar {
             base,
             hours_past,
@@ -77,6 +101,9 @@ impl CalendarBuilder {
         };
         self.context.borrow_mut().for_specs(specs);
         self.data_store.for_specs(specs);
+        if let Some(interval) = base.update_interval {
+            self.interval = interval;
+        }
 
         // Calculate height
         let span = (hours_past + hours_future).clamp(1, 24);
@@ -112,22 +139,26 @@ impl CalendarBuilder {
             move |area, ctx, width, height| {
                 let mut context = context.borrow_mut();
 
-                if context.needs_init {
-                    // Measure time label once for offset
-                    ctx.select_font_face(
-                        &context.font,
-                        gtk4::cairo::FontSlant::Normal,
-                        gtk4::cairo::FontWeight::Bold,
-                    );
-                    ctx.set_font_size(12.0);
-                    let ext = ctx.text_extents("00:00").unwrap();
-                    context.line_offset = ext.width() + 10.0;
+                if context.needs_init || context.scale != device_scale(area) {
+                    // Measure the widest time labels once for offset
+                    let widest = [10, 22]
+                        .into_iter()
+                        .filter_map(|hour| NaiveTime::from_hms_opt(hour, 0, 0))
+                        .map(|time| {
+                            let label = locale::format_time(time, context.timeline_format());
+                            TextLayout::new(ctx, &label, &context.font, 12.0, Weight::Normal)
+                                .size()
+                                .0
+                        })
+                        .fold(0.0, f64::max);
+                    context.line_offset = widest + 10.0;
 
                     let events_timed = data_store.timed.borrow();
                     context.update(area, width as f64, height as f64, events_timed.len());
                     context.cache.hitboxes =
                         CalendarCache::calculate_hitboxes(&*events_timed, &context);
                     context.cache.last_window_start = context.window_start;
+                    context.cache.last_scale = context.scale;
                     area.queue_draw();
                 }
                 let renderer = CalendarRenderer::new(ctx, &context, state.progress.get());
@@ -149,21 +180,25 @@ impl CalendarBuilder {
         });
 
         // Minute interval redraw
-        gtk4::glib::timeout_add_seconds_local(60, {
+        power::every(self.interval, {
             let calendar_ref = self.area.downgrade();
             let context = Rc::clone(&self.context);
             let data_store = Rc::clone(&self.data_store);
             move || {
                 if let Some(area) = calendar_ref.upgrade() {
+                    // Past midnight the new day is already prefetched
+                    let rolled_over = data_store.roll_over();
+
                     let mut context = context.borrow_mut();
                     let events_timed = data_store.timed.borrow();
                     let w = area.width() as f64;
                     let h = area.height() as f64;
                     context.update(&area, w, h, events_timed.len());
 
-                    if context.is_dirty(w, h) {
+                    if rolled_over || context.is_dirty(w, h) {
                         context.cache.hitboxes =
                             CalendarCache::calculate_hitboxes(&*events_timed, &context);
+                        context.cache.last_scale = context.scale;
                         area.queue_draw();
                     }
 
@@ -185,7 +220,11 @@ impl CalendarBuilder {
             let details_weak = self.details.downgrade();
             let data_store = Rc::clone(&self.data_store);
             let context = Rc::clone(&self.context);
-            move |_gesture, _n_press, x, y| {
+            move |gesture, _n_press, x, y| {
+                // Right clicks open the search
+                if gesture.current_button() == 3 {
+                    return;
+                }
                 let Some(stack) = stack_weak.upgrade() else {
                     return;
                 };
@@ -240,49 +279,100 @@ impl CalendarBuilder {
             }
         });
         self.details.add_controller(controller);
+
+        EventSearch::attach(
+            &self.area,
+            &self.stack,
+            &self.details,
+            Rc::clone(&self.data_store),
+        );
     }
-    /// Loads first batch of events and handles async fetching of remote events
+    /// Loads the cached events, then fetches the remote ones. Both wait for the window's first
+    /// frame, decoding the cache needs the master key.
     /// WARING: Has to be called after drawing is attatched! Otherwise, drawing of cached events
     /// will fail.
     fn attatch_refresh(&self) {
-        // Draw first events once the stack shows
-        let _ = self.data_store.load_from_cache();
-        self.animation_state.start(AnimationDirection::Forward {
-            duration: 0.7,
-            function: EaseFunction::EaseOutCubic,
-        });
-
-        // Get the calendar events async once the application finished starting up
-        gtk4::glib::idle_add_local_full(gtk4::glib::Priority::LOW, {
+        startup::defer("calendar cache", {
             let animation_state = Rc::clone(&self.animation_state);
             let data_store = Rc::clone(&self.data_store);
             let context = Rc::clone(&self.context);
+            let area = self.area.downgrade();
+            let refresh = self.refresh.clone();
+            let refresh_rx = self.refresh_rx.take();
             move || {
-                gtk4::glib::MainContext::default().spawn_local({
-                    let animation_state = Rc::clone(&animation_state);
-                    let data_store = Rc::clone(&data_store);
-                    let context = Rc::clone(&context);
-                    async move {
-                        let num_changes = data_store.refresh().await;
-                        if num_changes > 0 {
-                            let mut context = context.borrow_mut();
-                            context.cache.hitboxes = CalendarCache::calculate_hitboxes(
-                                &*data_store.timed.borrow(),
-                                &context,
-                            );
-                            context.cache.last_window_start = context.window_start;
-                            // Internally ques draw
-                            animation_state.start(AnimationDirection::Forward {
-                                duration: 0.7,
-                                function: EaseFunction::EaseOutCubic,
-                            });
+                if let Err(e) = data_store.load_from_cache() {
+                    eprintln!("{:?}", e);
+                }
+                animation_state.start(AnimationDirection::Forward {
+                    duration: 0.7,
+                    function: EaseFunction::EaseOutCubic,
+                });
+
+                gtk4::glib::MainContext::default().spawn_local(async move {
+                    refresh.set_refreshing(true);
+                    Self::refresh_events(
+                        &data_store,
+                        &context,
+                        &animation_state,
+                        &area,
+                        false,
+                        false,
+                    )
+                    .await;
+                    refresh.set_refreshing(false);
+
+                    // Refetch whenever the daemon reports a remote change or a refresh was
+                    // asked for
+                    let Some(mut refresh_rx) = refresh_rx else {
+                        return;
+                    };
+                    while let Some(href) = refresh_rx.recv().await {
+                        refresh.set_refreshing(true);
+                        if let Some(href) = &href {
+                            data_store.invalidate_calendar(href);
                         }
+                        Self::refresh_events(
+                            &data_store,
+                            &context,
+                            &animation_state,
+                            &area,
+                            href.is_none(),
+                            true,
+                        )
+                        .await;
+                        refresh.set_refreshing(false);
                     }
                 });
-                gtk4::glib::ControlFlow::Break
             }
         });
     }
+    async fn refresh_events(
+        data_store: &CalendarDataStore,
+        context: &RefCell<CalendarContext>,
+        animation_state: &AnimationState,
+        area: &WeakRef<DrawingArea>,
+        reload: bool,
+        force_redraw: bool,
+    ) {
+        let num_changes = match reload {
+            true => data_store.reload().await,
+            false => data_store.refresh().await,
+        };
+        if let Some(area) = area.upgrade() {
+            StateClass::Stale.set(&area, data_store.is_stale());
+        }
+        if num_changes > 0 || force_redraw {
+            let mut context = context.borrow_mut();
+            context.cache.hitboxes =
+                CalendarCache::calculate_hitboxes(&*data_store.timed.borrow(), &context);
+            context.cache.last_window_start = context.window_start;
+            // Internally ques draw
+            animation_state.start(AnimationDirection::Forward {
+                duration: 0.7,
+                function: EaseFunction::EaseOutCubic,
+            });
+        }
+    }
 
     pub fn for_box(self, container: &Box) -> Self {
         container.append(&self.stack);
@@ -303,6 +393,7 @@ impl CalendarBuilder {
             area: self.area.downgrade(),
             stack: self.stack.downgrade(),
             details: self.details.downgrade(),
+            refresh_tx: self.refresh_tx,
         }
     }
 }
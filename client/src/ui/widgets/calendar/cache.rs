@@ -1,4 +1,4 @@
-use chrono::{Local, NaiveDateTime};
+use chrono::NaiveDateTime;
 use suite_223b::calendar::utils::CalDavEvent;
 
 use crate::ui::widgets::calendar::{context::CalendarContext, types::EventHitbox};
@@ -7,7 +7,14 @@ struct TempLayout {
     index: usize,
     start_secs: f64,
     end_secs: f64,
-    lane: u8,
+    lane: usize,
+    /// Number of lanes the event stretches over
+    span: usize,
+}
+impl TempLayout {
+    fn overlaps(&self, other: &TempLayout) -> bool {
+        self.start_secs < other.end_secs && self.end_secs > other.start_secs
+    }
 }
 
 #[derive(Default)]
@@ -15,6 +22,7 @@ pub struct CalendarCache {
     pub last_width: f64,
     pub last_height: f64,
     pub last_window_start: NaiveDateTime,
+    pub last_scale: f64,
     pub hitboxes: Vec<EventHitbox>,
 }
 impl CalendarCache {
@@ -30,12 +38,7 @@ impl CalendarCache {
             .iter()
             .enumerate()
             .filter_map(|(idx, event)| {
-                let start = event.start.as_ref()?.utc_time().with_timezone(&Local);
-                let end = event.end.as_ref()?.utc_time().with_timezone(&Local);
-
-                let duration = end.signed_duration_since(start);
-                let start_dt = context.todate.and_time(start.time());
-                let end_dt = start_dt + duration;
+                let (start_dt, end_dt) = event.local_span_on(context.todate)?;
 
                 if end_dt <= context.window_start || start_dt >= context.window_end {
                     return None;
@@ -49,11 +52,17 @@ impl CalendarCache {
                     start_secs: (visible_start - context.window_start).num_seconds() as f64,
                     end_secs: (visible_end - context.window_start).num_seconds() as f64,
                     lane: 0,
+                    span: 1,
                 })
             })
             .collect();
 
-        spans.sort_by(|a, b| a.start_secs.total_cmp(&b.start_secs));
+        // Longer events first on equal starts so they get the leftmost column
+        spans.sort_by(|a, b| {
+            a.start_secs
+                .total_cmp(&b.start_secs)
+                .then(b.end_secs.total_cmp(&a.end_secs))
+        });
 
         let mut hitboxes = Vec::with_capacity(spans.len());
         let mut cluster: Vec<TempLayout> = Vec::new();
@@ -87,29 +96,48 @@ impl CalendarCache {
 
         let mut max_lane = 0;
 
+        // Assign each event the leftmost free column
         for i in 0..cluster.len() {
             let mut lane = 0;
-            while cluster[..i].iter().any(|prev| {
-                prev.lane == lane
-                    && cluster[i].start_secs < prev.end_secs
-                    && cluster[i].end_secs > prev.start_secs
-            }) {
+            while cluster[..i]
+                .iter()
+                .any(|prev| prev.lane == lane && cluster[i].overlaps(prev))
+            {
                 lane += 1;
             }
             cluster[i].lane = lane;
             max_lane = max_lane.max(lane);
         }
 
-        let lanes_totel = (max_lane + 1) as f64;
-        let lane_width = (ctx.inner_width - ctx.line_offset) / lanes_totel;
+        // Expand events to the right as long as the neighboring columns are empty
+        let lanes_total = max_lane + 1;
+        for i in 0..cluster.len() {
+            let mut span = 1;
+            while cluster[i].lane + span < lanes_total {
+                let next_lane = cluster[i].lane + span;
+                let blocked = cluster
+                    .iter()
+                    .any(|other| other.lane == next_lane && cluster[i].overlaps(other));
+                if blocked {
+                    break;
+                }
+                span += 1;
+            }
+            cluster[i].span = span;
+        }
+
+        let lane_width = (ctx.inner_width - ctx.line_offset) / lanes_total as f64;
 
         for i in 0..cluster.len() {
             let item = &cluster[i];
 
-            let y_start =
-                (item.start_secs / ctx.total_seconds) * ctx.inner_height + ctx.padding_top;
-            let y_end = (item.end_secs / ctx.total_seconds) * ctx.inner_height + ctx.padding_top;
-            let x = ctx.padding + ctx.line_offset + (item.lane as f64 * lane_width);
+            // Hitboxes live in logical pixels but are aligned to device pixels so edges stay
+            // crisp on fractionally scaled outputs
+            let y_start = ctx
+                .snap((item.start_secs / ctx.total_seconds) * ctx.inner_height + ctx.padding_top);
+            let y_end =
+                ctx.snap((item.end_secs / ctx.total_seconds) * ctx.inner_height + ctx.padding_top);
+            let x = ctx.snap(ctx.padding + ctx.line_offset + (item.lane as f64 * lane_width));
             let h = (y_end - y_start).max(18.0);
 
             let has_neighbor_above = results.iter().any(|prev_hb| {
@@ -123,10 +151,106 @@ impl CalendarCache {
                 index: item.index,
                 x,
                 y: y_start,
-                w: lane_width - 3.0,
+                w: ctx.snap(lane_width * item.span as f64 - 3.0),
                 h,
                 has_neighbor_above,
             })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, NaiveDate, TimeZone};
+    use suite_223b::calendar::utils::{CalEventType, structs::DateTimeSpec};
+
+    fn day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 6, 10).unwrap()
+    }
+
+    fn at(hour: u32, min: u32) -> Option<DateTimeSpec> {
+        let local = day().and_hms_opt(hour, min, 0).unwrap();
+        Some(DateTimeSpec::DateTime {
+            value: Local.from_local_datetime(&local).unwrap().to_utc(),
+        })
+    }
+
+    fn event(start: (u32, u32), end: (u32, u32)) -> CalDavEvent {
+        CalDavEvent {
+            start: at(start.0, start.1),
+            end: at(end.0, end.1),
+            event_type: CalEventType::Timed,
+            ..Default::default()
+        }
+    }
+
+    /// A whole day window, 100px per hour and lanes sharing 1200px
+    fn context() -> CalendarContext {
+        CalendarContext {
+            todate: day(),
+            window_start: day().and_hms_opt(0, 0, 0).unwrap(),
+            window_end: day().succ_opt().unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            total_seconds: 24.0 * 3600.0,
+            inner_width: 1200.0,
+            inner_height: 2400.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_overlapping_events_share_the_width() {
+        let events = [event((9, 0), (11, 0)), event((10, 0), (12, 0))];
+        let hitboxes = CalendarCache::calculate_hitboxes(&events, &context());
+
+        assert_eq!(hitboxes.len(), 2);
+        assert_eq!((hitboxes[0].x, hitboxes[0].w), (0.0, 597.0));
+        assert_eq!((hitboxes[1].x, hitboxes[1].w), (600.0, 597.0));
+        assert_eq!((hitboxes[1].y, hitboxes[1].h), (1000.0, 200.0));
+    }
+
+    #[test]
+    fn test_touching_events_stack_in_one_column() {
+        let events = [event((9, 0), (10, 0)), event((10, 0), (11, 0))];
+        let hitboxes = CalendarCache::calculate_hitboxes(&events, &context());
+
+        assert_eq!(hitboxes.len(), 2);
+        assert!(hitboxes.iter().all(|hb| hb.x == 0.0 && hb.w == 1197.0));
+        assert!(!hitboxes[0].has_neighbor_above);
+        assert!(hitboxes[1].has_neighbor_above);
+    }
+
+    #[test]
+    fn test_whole_day_event_keeps_the_first_column() {
+        let events = [
+            event((10, 0), (11, 0)),
+            event((0, 0), (23, 59)),
+            event((12, 0), (13, 0)),
+            event((12, 30), (14, 0)),
+        ];
+        let hitboxes = CalendarCache::calculate_hitboxes(&events, &context());
+
+        // Sorted by start, the whole day event comes first
+        let indices: Vec<_> = hitboxes.iter().map(|hb| hb.index).collect();
+        assert_eq!(indices, [1, 0, 2, 3]);
+
+        assert_eq!((hitboxes[0].x, hitboxes[0].w), (0.0, 397.0));
+        // Nothing else runs next to it, so it spans both remaining columns
+        assert_eq!((hitboxes[1].x, hitboxes[1].w), (400.0, 797.0));
+        assert_eq!((hitboxes[2].x, hitboxes[2].w), (400.0, 397.0));
+        assert_eq!((hitboxes[3].x, hitboxes[3].w), (800.0, 397.0));
+    }
+
+    #[test]
+    fn test_lanes_past_u8() {
+        let events = vec![event((9, 0), (10, 0)); 300];
+        let ctx = CalendarContext {
+            inner_width: 3000.0,
+            ..context()
+        };
+        let hitboxes = CalendarCache::calculate_hitboxes(&events, &ctx);
+
+        assert_eq!(hitboxes.len(), 300);
+        assert_eq!(hitboxes[299].x, 2990.0);
+    }
+}
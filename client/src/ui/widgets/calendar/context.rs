@@ -10,12 +10,17 @@ use crate::{
             cache::CalendarCache,
             types::{CalendarConfig, CalendarHMFormat},
         },
-        utils::render::Rgba,
+        utils::{
+            locale,
+            render::{Rgba, device_scale, snap},
+        },
     },
 };
 
 pub struct CalendarContext {
     pub font: String,
+    /// Device pixels per logical pixel
+    pub scale: f64,
     pub padding: f64,
     pub padding_top: f64,
 
@@ -34,6 +39,8 @@ pub struct CalendarContext {
     pub total_seconds: f64,
 
     pub hm_format: Option<CalendarHMFormat>,
+    /// Draw sunrise, sunset and the golden hours
+    pub sun: bool,
 
     pub cache: CalendarCache,
     pub needs_init: bool,
@@ -42,6 +49,7 @@ impl Default for CalendarContext {
     fn default() -> Self {
         Self {
             font: String::from("Sans"),
+            scale: 1.0,
             padding: 0.0,
             padding_top: 0.0,
             text: Rgba::default(),
@@ -56,6 +64,7 @@ impl Default for CalendarContext {
             hours_past: 4,
             total_seconds: 8.0 * 3600.0,
             hm_format: None,
+            sun: false,
             cache: CalendarCache::default(),
             needs_init: true,
         }
@@ -94,6 +103,7 @@ impl CalendarContext {
             hm_format,
             hours_past,
             hours_future,
+            sun,
         } = spec.as_calendar();
 
         // Calculations
@@ -103,9 +113,18 @@ impl CalendarContext {
         self.hours_past = hours_past;
         self.total_seconds = (self.hours_to_show * 3600) as f64;
         self.hm_format = hm_format.cloned();
+        self.sun = sun;
+    }
+    /// Format of the hour labels on the timeline
+    pub fn timeline_format(&self) -> &str {
+        self.hm_format
+            .as_ref()
+            .and_then(|f| f.timeline.as_deref())
+            .unwrap_or(locale::hour_format())
     }
     pub fn update(&mut self, area: &DrawingArea, width: f64, height: f64, num_events: usize) {
         self.text = area.color().into();
+        self.scale = device_scale(area);
 
         self.padding = (width as f64 * 0.05).min(20.0);
         self.padding_top = if num_events != 0 { 120.0 } else { 100.0 };
@@ -125,5 +144,14 @@ impl CalendarContext {
             || self.cache.last_width != width
             || self.cache.last_height != height
             || self.cache.last_window_start != self.window_start
+            || self.cache.last_scale != self.scale
+    }
+    /// Snaps a logical coordinate to the device pixel grid
+    pub fn snap(&self, value: f64) -> f64 {
+        snap(value, self.scale)
+    }
+    /// Width of a single device pixel in logical coordinates
+    pub fn hairline(&self) -> f64 {
+        1.0 / self.scale
     }
 }
@@ -1,16 +1,21 @@
 use std::{
-    cell::RefCell,
-    collections::HashSet,
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashSet},
     fs,
-    io::{BufReader, BufWriter},
     rc::Rc,
 };
 
 use bincode::config;
-use chrono::Local;
+use chrono::{Days, Local, NaiveDate};
 use suite_223b::{
-    auth::CredentialManager,
-    calendar::utils::{CalDavEvent, CalEventType},
+    auth::{CredentialManager, open, seal},
+    calendar::{
+        subscription::SubscriptionClient,
+        utils::{
+            CalDavEvent, CalEventType, CalendarRegistry, CompactEvents, OccurrenceCache,
+            structs::Partstat,
+        },
+    },
     utils::{
         errors::{WatsonError, WatsonErrorKind},
         paths::get_cache_dir,
@@ -20,118 +25,307 @@ use suite_223b::{
 
 use crate::{config::WidgetSpec, ui::widgets::calendar::types::CalendarRule};
 
+const CACHE_FILE: &str = "calendar_cache.bin";
+/// Magic and schema version. The events after it are sealed with the master key, whose tag also
+/// covers the header, so it doubles as the checksum.
+const CACHE_HEADER: &[u8] = b"WCAL\x04";
+
 #[derive(Debug, Default)]
 pub struct CalendarDataStore {
+    /// Events of the shown day, what the widget draws
     pub timed: Rc<RefCell<Vec<CalDavEvent>>>,
     pub allday: Rc<RefCell<Vec<CalDavEvent>>>,
     pub selection: Rc<RefCell<Option<CalendarRule>>>,
+    /// Every event of the prefetch window
+    events: RefCell<Vec<CalDavEvent>>,
+    /// Indices into `events` by the days they occur on
+    by_date: RefCell<BTreeMap<NaiveDate, Vec<usize>>>,
+    /// The day the window is centred on
+    today: Cell<NaiveDate>,
+    /// The day `timed` and `allday` belong to
+    day: Cell<NaiveDate>,
+    /// Days fetched before and after today
+    prefetch_days: Cell<u16>,
+    /// Whether the last refresh failed for any account, so some events may be outdated
+    stale: Cell<bool>,
+    /// Declined invitations stay out of `timed` and `allday`
+    hide_declined: Cell<bool>,
 }
 impl CalendarDataStore {
     pub fn new() -> Self {
+        let today = Local::now().date_naive();
         Self {
             timed: Rc::new(RefCell::new(Vec::new())),
             allday: Rc::new(RefCell::new(Vec::new())),
             selection: Rc::new(RefCell::new(None)),
+            events: RefCell::new(Vec::new()),
+            by_date: RefCell::new(BTreeMap::new()),
+            today: Cell::new(today),
+            day: Cell::new(today),
+            prefetch_days: Cell::new(7),
+            stale: Cell::new(false),
+            hide_declined: Cell::new(false),
         }
     }
     pub fn for_specs(&self, spec: &WidgetSpec) {
-        if let WidgetSpec::Calendar { selection, .. } = spec {
+        if let WidgetSpec::Calendar {
+            selection,
+            prefetch_days,
+            hide_declined,
+            ..
+        } = spec
+        {
             if selection.is_some() {
                 *self.selection.borrow_mut() = selection.clone();
             }
+            self.prefetch_days.set(*prefetch_days);
+            self.hide_declined.set(*hide_declined);
+        }
+    }
+
+    /// The days kept around today
+    fn window(&self) -> impl Iterator<Item = NaiveDate> {
+        let days = Days::new(self.prefetch_days.get() as u64);
+        let today = self.today.get();
+        let first = today.checked_sub_days(days).unwrap_or(today);
+        let last = today.checked_add_days(days).unwrap_or(today);
+        first.iter_days().take_while(move |d| *d <= last)
+    }
+    fn in_window(&self, event: &CalDavEvent) -> bool {
+        self.window().any(|day| event.occurs_on_day(&day))
+    }
+    /// Rebuilds `by_date` after `events` changed
+    fn index(&self) {
+        let events = self.events.borrow();
+        let mut by_date = self.by_date.borrow_mut();
+        by_date.clear();
+        for day in self.window() {
+            let indices: Vec<usize> = events
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| e.occurs_on_day(&day))
+                .map(|(i, _)| i)
+                .collect();
+            if !indices.is_empty() {
+                by_date.insert(day, indices);
+            }
         }
     }
-    pub fn load_from_cache(&self) -> Result<(), WatsonError> {
-        let mut path = get_cache_dir()?;
-        path.push("calendar_cache.bin");
 
+    /// Prefetched events on `date`, empty outside the window
+    pub fn events_for(&self, date: NaiveDate) -> Vec<CalDavEvent> {
+        let events = self.events.borrow();
+        self.by_date
+            .borrow()
+            .get(&date)
+            .map(|indices| indices.iter().map(|&i| events[i].clone()).collect())
+            .unwrap_or_default()
+    }
+    /// Prefetched events matching `query`, by start. Covers the client's own window of
+    /// `prefetch_days`, the daemon only keeps today's events.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<CalDavEvent> {
+        let mut found: Vec<CalDavEvent> = self
+            .events
+            .borrow()
+            .iter()
+            .filter(|e| e.matches(query))
+            .cloned()
+            .collect();
+        found.sort_by_key(CalDavEvent::start_utc);
+        found.truncate(limit);
+        found
+    }
+    pub fn day(&self) -> NaiveDate {
+        self.day.get()
+    }
+    /// Shows the events of `date`. Events already shown on that day don't fade in again.
+    pub fn show_day(&self, date: NaiveDate) {
+        let shown: HashSet<String> = if date == self.day.replace(date) {
+            let timed = self.timed.borrow();
+            let allday = self.allday.borrow();
+            timed
+                .iter()
+                .chain(allday.iter())
+                .filter(|e| e.seen.get())
+                .map(|e| e.uid.clone())
+                .collect()
+        } else {
+            HashSet::new()
+        };
+
+        let (mut timed, mut allday) = (Vec::new(), Vec::new());
+        for event in self.events_for(date) {
+            if self.hide_declined.get() && event.own_partstat() == Some(Partstat::Declined) {
+                continue;
+            }
+            event.seen.set(shown.contains(&event.uid));
+            match event.event_type {
+                CalEventType::Timed => timed.push(event),
+                CalEventType::AllDay => allday.push(event),
+            }
+        }
+        *self.timed.borrow_mut() = timed;
+        *self.allday.borrow_mut() = allday;
+    }
+    /// Moves on to the new day after midnight, true if the shown events changed
+    pub fn roll_over(&self) -> bool {
+        let today = Local::now().date_naive();
+        let last = self.today.replace(today);
+        if last == today {
+            return false;
+        }
+        if let Some(first) = self.window().next() {
+            OccurrenceCache::global().evict_before(&first);
+        }
+        self.events.borrow_mut().retain(|e| self.in_window(e));
+        self.index();
+
+        // Stay on a day navigated to
+        if self.day.get() != last {
+            return false;
+        }
+        self.show_day(today);
+        true
+    }
+
+    /// Loads the events of the last session. A corrupt cache, or one written by another
+    /// version, is an error and the next refresh starts cold.
+    pub fn load_from_cache(&self) -> Result<(), WatsonError> {
+        let path = get_cache_dir()?.join(CACHE_FILE);
         if !path.exists() {
             return Ok(());
         }
 
-        let file = fs::File::open(path)
-            .map_err(|e| watson_err!(WatsonErrorKind::FileOpen, e.to_string()))?;
-
-        let mut reader = BufReader::new(file);
-        let config = config::standard();
-        let (mut cached_timed, mut cached_allday): (Vec<CalDavEvent>, Vec<CalDavEvent>) =
-            bincode::serde::decode_from_reader(&mut reader, config)
+        let data =
+            fs::read(&path).map_err(|e| watson_err!(WatsonErrorKind::FileRead, e.to_string()))?;
+        let (header, sealed) = data.split_at_checked(CACHE_HEADER.len()).ok_or_else(|| {
+            watson_err!(WatsonErrorKind::InvalidData, "Calendar cache is truncated")
+        })?;
+        if header != CACHE_HEADER {
+            return Err(watson_err!(
+                WatsonErrorKind::InvalidData,
+                "Calendar cache is from another version"
+            ));
+        }
+        let key = CredentialManager::master_key()?;
+        let plain = open(sealed, &key, header)?;
+        let (events, _): (CompactEvents, _) =
+            bincode::serde::decode_from_slice(&plain, config::standard())
                 .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+        let mut events = events.unpack(CalendarRegistry::global());
 
         // Cache invalidation
         let today = Local::now().date_naive();
-        cached_timed.retain(|e| {
-            e.seen.set(false);
-            e.occurs_on_day(&today)
-        });
-        cached_allday.retain(|e| {
-            e.seen.set(false);
-            e.occurs_on_day(&today)
-        });
+        self.today.set(today);
+        if let Some(first) = self.window().next() {
+            OccurrenceCache::global().evict_before(&first);
+        }
+        events.retain(|e| self.in_window(e));
 
-        *self.timed.borrow_mut() = cached_timed;
-        *self.allday.borrow_mut() = cached_allday;
+        *self.events.borrow_mut() = events;
+        self.index();
+        self.show_day(today);
 
         Ok(())
     }
     pub fn save_to_cache(&self) -> Result<(), WatsonError> {
-        let mut path = get_cache_dir()?;
-        path.push("calendar_cache.bin");
+        let path = get_cache_dir()?.join(CACHE_FILE);
 
-        let file = fs::File::create(path)
-            .map_err(|e| watson_err!(WatsonErrorKind::FileOpen, e.to_string()))?;
-
-        let mut writer = BufWriter::new(file);
-        let config = config::standard();
-        let data = (&*self.timed.borrow(), &*self.allday.borrow());
-        bincode::serde::encode_into_std_write(&data, &mut writer, config)
+        let events = CompactEvents::pack(&self.events.borrow());
+        let plain = bincode::serde::encode_to_vec(&events, config::standard())
             .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+        let key = CredentialManager::master_key()?;
+        let mut data = CACHE_HEADER.to_vec();
+        data.extend(seal(&plain, &key, CACHE_HEADER)?);
 
-        Ok(())
+        fs::write(&path, data).map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))
+    }
+    /// Drops all events of a calendar so the next refresh picks up its current state
+    pub fn invalidate_calendar(&self, href: &str) {
+        let occurrences = OccurrenceCache::global();
+        self.events.borrow_mut().retain(|e| {
+            if e.calendar_info.href == href {
+                occurrences.invalidate(&e.uid);
+                return false;
+            }
+            true
+        });
+        self.index();
+        self.show_day(self.day.get());
     }
+    pub fn is_stale(&self) -> bool {
+        self.stale.get()
+    }
+    /// Adds the events that weren't known yet, returning how many
     pub async fn refresh(&self) -> usize {
+        let seen_ids: HashSet<String> =
+            self.events.borrow().iter().map(|e| e.uid.clone()).collect();
+        let new_events = self.fetch(&seen_ids).await;
+        let num_changes = new_events.len();
+        if num_changes > 0 {
+            self.events.borrow_mut().extend(new_events);
+            self.index();
+            self.show_day(self.day.get());
+
+            let _ = self.save_to_cache();
+        }
+        num_changes
+    }
+    /// Fetches every event again and replaces the known ones, so changed and deleted events are
+    /// picked up too. Nothing is replaced if a provider failed.
+    pub async fn reload(&self) -> usize {
+        let events = self.fetch(&HashSet::new()).await;
+        if self.stale.get() {
+            return 0;
+        }
+        let occurrences = OccurrenceCache::global();
+        for event in self.events.borrow().iter() {
+            occurrences.invalidate(&event.uid);
+        }
+        let num_events = events.len();
+        self.events.replace(events);
+        self.index();
+        self.show_day(self.day.get());
+        let _ = self.save_to_cache();
+        num_events
+    }
+    /// Events of all providers in the prefetch window, except those in `skip`. Marks the store
+    /// stale if any provider failed.
+    async fn fetch(&self, skip: &HashSet<String>) -> Vec<CalDavEvent> {
+        self.stale.set(true);
         let mut credential_manager = match CredentialManager::new() {
             Ok(m) => m,
             Err(e) => {
                 eprintln!("{:?}", e);
-                return 0;
+                return Vec::new();
             }
         };
         if let Err(e) = credential_manager.unlock() {
             eprintln!("{:?}", e);
-            return 0;
+            return Vec::new();
         }
 
-        let today = Local::now().date_naive();
-        let mut new_timed = Vec::new();
-        let mut new_allday = Vec::new();
-        let seen_ids: HashSet<String> = {
-            let timed = self.timed.borrow();
-            let allday = self.allday.borrow();
-
-            let mut ids = HashSet::with_capacity(timed.len() + allday.len());
-
-            ids.extend(timed.iter().map(|e| e.uid.clone()));
-            ids.extend(allday.iter().map(|e| e.uid.clone()));
-            ids
-        };
-
-        for account in credential_manager.credentials {
-            let Some(mut provider) = account.provider() else {
-                continue;
-            };
+        let mut failed = false;
+        self.today.set(Local::now().date_naive());
+        let mut new_events = Vec::new();
 
+        let providers = credential_manager
+            .credentials
+            .into_iter()
+            .filter_map(|account| account.provider())
+            .chain(SubscriptionClient::load());
+        for mut provider in providers {
             if let Err(e) = provider.init().await {
-                // TODO: Log err
                 eprintln!("{:?}", e);
+                failed = true;
                 continue;
             }
 
             let calendars = match provider.get_calendars().await {
                 Ok(v) => v,
                 Err(e) => {
-                    // TODO: Log err
                     eprintln!("{:?}", e);
+                    failed = true;
                     continue;
                 }
             };
@@ -139,43 +333,24 @@ impl CalendarDataStore {
             let mut events = match provider.get_events(calendars).await {
                 Ok(v) => v,
                 Err(e) => {
-                    // TODO: Log err
                     eprintln!("{:?}", e);
+                    failed = true;
                     continue;
                 }
             };
 
             // Filter events
             if let Some(selection) = &*self.selection.borrow() {
-                events.retain(|e| {
-                    selection.is_allowed(&e.calendar_info.name) && e.occurs_on_day(&today)
-                });
+                events.retain(|e| selection.is_allowed(&e.calendar_info.name) && self.in_window(e));
             } else {
-                events.retain(|e| e.occurs_on_day(&today));
+                events.retain(|e| self.in_window(e));
             }
 
-            // Extend the Events
-            {
-                for item in events {
-                    if !seen_ids.contains(&item.uid) {
-                        item.seen.set(false);
-                        match item.event_type {
-                            CalEventType::Timed => new_timed.push(item),
-                            CalEventType::AllDay => new_allday.push(item),
-                        }
-                    }
-                }
-            }
-        }
-        let num_changes = new_timed.len() + new_allday.len();
-        if num_changes > 0 {
-            {
-                self.timed.borrow_mut().extend(new_timed);
-                self.allday.borrow_mut().extend(new_allday);
-            }
-
-            let _ = self.save_to_cache();
+            events.retain(|e| !skip.contains(&e.uid));
+            CalendarRegistry::global().intern_events(&mut events);
+            new_events.extend(events);
         }
-        num_changes
+        self.stale.set(failed);
+        new_events
     }
 }
@@ -4,12 +4,14 @@ use crate::ui::{
 };
 
 use gtk4::{DrawingArea, Stack, glib::WeakRef};
+use tokio::sync::mpsc::UnboundedSender;
 
 mod builder;
 mod cache;
 mod context;
 mod data_store;
 mod renderer;
+mod search;
 pub mod types;
 
 pub use builder::CalendarBuilder;
@@ -20,9 +22,18 @@ pub struct Calendar {
     pub area: WeakRef<DrawingArea>,
     pub stack: WeakRef<Stack>,
     pub details: WeakRef<EventDetails>,
+    refresh_tx: UnboundedSender<Option<String>>,
 }
 impl Calendar {
     pub fn builder() -> CalendarBuilder {
         CalendarBuilder::new()
     }
+    /// Refetches the events of the calendar with the given href
+    pub fn calendar_changed(&self, href: String) {
+        let _ = self.refresh_tx.send(Some(href));
+    }
+    /// Refetches every calendar, for `Request::ForceRefresh`
+    pub fn reload(&self) {
+        let _ = self.refresh_tx.send(None);
+    }
 }
@@ -1,14 +1,27 @@
 use std::{rc::Rc, str::FromStr};
 
-use chrono::{Local, NaiveTime, Timelike};
-use gtk4::cairo::{Context, FontSlant, FontWeight};
-use suite_223b::calendar::utils::CalDavEvent;
+use chrono::{DateTime, Duration, Local, NaiveDateTime, NaiveTime, Timelike, Utc};
+use gtk4::{cairo::Context, pango::Weight};
+use suite_223b::{
+    calendar::utils::{CalDavEvent, FreeSlot, free_slots, structs::Partstat},
+    utils::astronomy::SunTimes,
+};
 
-use crate::ui::widgets::{
-    calendar::{CalendarContext, EventHitbox, data_store::CalendarDataStore},
-    utils::render::{CairoShapesExt, Rgba},
+use crate::ui::{
+    location,
+    widgets::{
+        calendar::{CalendarContext, EventHitbox, data_store::CalendarDataStore},
+        utils::{
+            locale::{self, tr},
+            render::{CairoShapesExt, Rgba, SUN_COLOR},
+            text::TextLayout,
+        },
+    },
 };
 
+/// Shorter gaps between events don't count as free time
+const MIN_FREE_MINUTES: i64 = 15;
+
 pub struct CalendarRenderer<'c> {
     ctx: &'c Context,
     context: &'c CalendarContext,
@@ -16,9 +29,6 @@ pub struct CalendarRenderer<'c> {
 }
 impl<'c> CalendarRenderer<'c> {
     pub fn new(ctx: &'c Context, context: &'c CalendarContext, progress: f64) -> Self {
-        // Set default font
-        ctx.select_font_face(&context.font, FontSlant::Normal, FontWeight::Normal);
-
         Self {
             ctx,
             context,
@@ -32,6 +42,20 @@ impl<'c> CalendarRenderer<'c> {
         // Hour lines and timeline
         self.draw_timeline();
 
+        if self.context.sun {
+            self.draw_sun();
+        }
+
+        let free = free_slots(
+            &data_store.timed.borrow(),
+            self.context.todate,
+            self.context.window_start,
+            self.context.window_end,
+            Duration::minutes(MIN_FREE_MINUTES),
+        );
+        self.draw_free(&free);
+        self.draw_next_free(&data_store.timed.borrow());
+
         // Drawing events
         let mut allday_x = 0.0;
         for event in data_store.allday.borrow().iter() {
@@ -47,6 +71,9 @@ impl<'c> CalendarRenderer<'c> {
         // Current time indicator
         self.draw_time_indicator();
     }
+    fn text(&self, text: &str, size: f64, weight: Weight) -> TextLayout {
+        TextLayout::new(self.ctx, text, &self.context.font, size, weight)
+    }
     fn draw_header(&self) {
         // Header: Date
         self.ctx.set_source_rgb(
@@ -54,12 +81,13 @@ impl<'c> CalendarRenderer<'c> {
             self.context.text.g,
             self.context.text.b,
         );
-        self.ctx.set_font_size(50.0);
-        let today_string = self.context.todate.format("%b %-d").to_string();
-        let ext1 = self.ctx.text_extents(&today_string).unwrap();
-        self.ctx
-            .move_to(self.context.padding, self.context.padding + ext1.height());
-        self.ctx.show_text(&today_string).unwrap();
+        let hm_format = self.context.hm_format.as_ref();
+        let fmt = hm_format
+            .and_then(|f| f.date.as_deref())
+            .unwrap_or(locale::day_format());
+        let today_string = locale::format_date(self.context.todate, fmt);
+        let today = self.text(&today_string, 50.0, Weight::Normal);
+        today.show(self.ctx, self.context.padding, self.context.padding);
 
         // Header: Weekday String
         self.ctx.set_source_rgba(
@@ -68,18 +96,19 @@ impl<'c> CalendarRenderer<'c> {
             self.context.accent.b,
             self.context.accent.a,
         );
-        self.ctx.set_font_size(15.0);
-        let weekday_string = self.context.todate.format("%A").to_string();
-        self.ctx.move_to(
-            self.context.padding,
-            self.context.padding + ext1.height() + 20.0,
-        );
-        self.ctx.show_text(&weekday_string).unwrap();
+        let fmt = hm_format.and_then(|f| f.weekday.as_deref()).unwrap_or("%A");
+        let weekday_string = locale::format_date(self.context.todate, fmt);
+        self.text(&weekday_string, 15.0, Weight::Normal)
+            .show_baseline(
+                self.ctx,
+                self.context.padding,
+                self.context.padding + today.baseline() + 20.0,
+            );
     }
 
     fn draw_timeline(&self) {
-        // Draw hour lines
-        self.ctx.set_line_width(0.5);
+        // Draw hour lines, one device pixel wide and centered on a pixel row
+        self.ctx.set_line_width(self.context.hairline());
         self.ctx.set_line_cap(gtk4::cairo::LineCap::Round);
         self.ctx.set_source_rgba(
             self.context.text.r,
@@ -88,8 +117,10 @@ impl<'c> CalendarRenderer<'c> {
             0.2,
         );
         for offset in 0..self.context.hours_to_show {
-            let y = (offset as f64 / self.context.hours_to_show as f64) * self.context.inner_height
-                + self.context.padding_top;
+            let y = self.context.snap(
+                (offset as f64 / self.context.hours_to_show as f64) * self.context.inner_height
+                    + self.context.padding_top,
+            ) + self.context.hairline() / 2.0;
             self.ctx
                 .move_to(self.context.padding + self.context.line_offset, y);
             self.ctx
@@ -98,7 +129,6 @@ impl<'c> CalendarRenderer<'c> {
         self.ctx.stroke().unwrap();
 
         // Draw hour labels
-        self.ctx.set_font_size(12.0);
         self.ctx.set_source_rgb(
             self.context.text.r,
             self.context.text.g,
@@ -109,27 +139,166 @@ impl<'c> CalendarRenderer<'c> {
                 + self.context.padding_top;
             let hour = (self.context.window_start.hour() + offset as u32) % 24;
 
-            let fmt_str = self
-                .context
-                .hm_format
-                .as_ref()
-                .map(|f| f.timeline.as_str())
-                .unwrap_or("%H:%M");
-
-            let label = NaiveTime::from_hms_opt(hour, 0, 0)
-                .unwrap()
-                .format(fmt_str)
-                .to_string();
-            CairoShapesExt::vert_centered_text(self.ctx, &label, self.context.padding, y);
+            let label = locale::format_time(
+                NaiveTime::from_hms_opt(hour, 0, 0).unwrap(),
+                self.context.timeline_format(),
+            );
+            self.text(&label, 12.0, Weight::Normal).show_vert_centered(
+                self.ctx,
+                self.context.padding,
+                y,
+            );
         }
     }
+    /// Golden hours as bands behind the events, sunrise and sunset as dashed lines
+    fn draw_sun(&self) -> Option<()> {
+        let location = location::location()?;
+        let sun = SunTimes::on(self.context.todate, location)?;
+        let color = Rgba::from_str(SUN_COLOR).unwrap_or_default();
+        let x_start = self.context.padding + self.context.line_offset;
+        let x_end = self.context.inner_width + self.context.padding;
+
+        let bands = [
+            (Some(sun.sunrise), sun.golden_morning),
+            (sun.golden_evening, Some(sun.sunset)),
+        ];
+        self.ctx.set_source_rgba(color.r, color.g, color.b, 0.12);
+        for (start, end) in bands {
+            let (Some(start), Some(end)) = (start, end) else {
+                continue;
+            };
+            let top = self.y_of(start).max(self.context.padding_top);
+            let bottom = self
+                .y_of(end)
+                .min(self.context.padding_top + self.context.inner_height);
+            if bottom > top {
+                self.ctx
+                    .rectangle(x_start, top, x_end - x_start, bottom - top);
+            }
+        }
+        self.ctx.fill().unwrap();
+
+        self.ctx.set_line_width(1.0);
+        self.ctx.set_dash(&[4.0, 4.0], 0.0);
+        for (time, label) in [(sun.sunrise, tr("Sunrise")), (sun.sunset, tr("Sunset"))] {
+            let y = self.y_of(time);
+            if y < self.context.padding_top
+                || y > self.context.padding_top + self.context.inner_height
+            {
+                continue;
+            }
+            let y = self.context.snap(y) + 0.5;
+            self.ctx.set_source_rgba(color.r, color.g, color.b, 0.8);
+            self.ctx.move_to(x_start, y);
+            self.ctx.line_to(x_end, y);
+            self.ctx.stroke().unwrap();
+            self.text(label, 10.0, Weight::Normal)
+                .show_rjust(self.ctx, x_end, y - 8.0);
+        }
+        self.ctx.set_dash(&[], 0.0);
+        Some(())
+    }
+    /// Gaps between events as faint bands, labelled where they are tall enough
+    fn draw_free(&self, slots: &[FreeSlot]) {
+        let x_start = self.context.padding + self.context.line_offset;
+        let x_end = self.context.inner_width + self.context.padding;
+        for slot in slots {
+            let top = self.context.snap(self.y_of_local(slot.start));
+            let bottom = self.context.snap(self.y_of_local(slot.end));
+            self.ctx.set_source_rgba(
+                self.context.text.r,
+                self.context.text.g,
+                self.context.text.b,
+                0.04,
+            );
+            self.ctx
+                .rectangle(x_start, top, x_end - x_start, bottom - top);
+            self.ctx.fill().unwrap();
+
+            if bottom - top >= 24.0 {
+                self.ctx.set_source_rgba(
+                    self.context.text.r,
+                    self.context.text.g,
+                    self.context.text.b,
+                    0.35,
+                );
+                self.text(tr("Free"), 10.0, Weight::Normal).show_rjust(
+                    self.ctx,
+                    x_end - 6.0,
+                    top + 10.0,
+                );
+            }
+        }
+    }
+    /// The next free time of the day under the header, e.g. `Next free: 14:30–16:00`
+    fn draw_next_free(&self, events: &[CalDavEvent]) -> Option<()> {
+        let now = Local::now().naive_local();
+        if now.date() != self.context.todate || events.is_empty() {
+            return None;
+        }
+        let end_of_day = self.context.todate.succ_opt()?.and_hms_opt(0, 0, 0)?;
+        let slot = free_slots(
+            events,
+            self.context.todate,
+            now,
+            end_of_day,
+            Duration::minutes(MIN_FREE_MINUTES),
+        )
+        .into_iter()
+        .next()?;
+
+        let fmt = self
+            .context
+            .hm_format
+            .as_ref()
+            .and_then(|f| f.event.as_deref())
+            .unwrap_or(locale::time_format());
+        let time = |t: NaiveDateTime| locale::format_time(t.time(), fmt);
+        let label = match (slot.start <= now, slot.end >= end_of_day) {
+            // Nothing left today
+            (true, true) => return None,
+            (true, false) => format!("{} {}", tr("Free until"), time(slot.end)),
+            (false, true) => format!("{} {}", tr("Free from"), time(slot.start)),
+            (false, false) => format!(
+                "{}: {}–{}",
+                tr("Next free"),
+                time(slot.start),
+                time(slot.end)
+            ),
+        };
+
+        self.ctx.set_source_rgba(
+            self.context.text.r,
+            self.context.text.g,
+            self.context.text.b,
+            0.6,
+        );
+        self.text(&label, 11.0, Weight::Normal).show_rjust(
+            self.ctx,
+            self.context.padding + self.context.inner_width,
+            self.context.padding + 12.0,
+        );
+        Some(())
+    }
+    /// Position of a local time on the timeline
+    fn y_of_local(&self, time: NaiveDateTime) -> f64 {
+        (time - self.context.window_start).num_seconds() as f64 / self.context.total_seconds
+            * self.context.inner_height
+            + self.context.padding_top
+    }
+    /// Position of `time` on the timeline, outside of it before or after the window
+    fn y_of(&self, time: DateTime<Utc>) -> f64 {
+        self.y_of_local(time.with_timezone(&Local).naive_local())
+    }
     fn draw_time_indicator(&self) {
         let now_full = Local::now().naive_local();
         if now_full >= self.context.window_start && now_full <= self.context.window_end {
-            let current_y = (now_full - self.context.window_start).num_seconds() as f64
-                / self.context.total_seconds
-                * self.context.inner_height
-                + self.context.padding_top;
+            let current_y = self.context.snap(
+                (now_full - self.context.window_start).num_seconds() as f64
+                    / self.context.total_seconds
+                    * self.context.inner_height
+                    + self.context.padding_top,
+            );
             let x_start = self.context.padding + self.context.line_offset - 6.0;
 
             self.ctx.set_source_rgba(
@@ -167,6 +336,10 @@ impl<'c> CalendarRenderer<'c> {
             }
             self.progress
         };
+        // Declined invitations are ghosted, tentative ones get a dashed rim
+        let partstat = event.own_partstat();
+        let declined = partstat == Some(Partstat::Declined);
+        let alpha = if declined { alpha * 0.4 } else { alpha };
 
         // Coloring
         let color_str = event.calendar_info.color.as_deref().unwrap_or("#e9a949");
@@ -196,13 +369,36 @@ impl<'c> CalendarRenderer<'c> {
 
         // Border
         self.ctx.save().unwrap();
-        let rim_grad = gtk4::cairo::LinearGradient::new(x, y, x, y + h);
-        rim_grad.add_color_stop_rgba(0.0, base_color.r, base_color.g, base_color.b, 0.5 * alpha);
-        rim_grad.add_color_stop_rgba(0.5, base_color.r, base_color.g, base_color.b, 0.1 * alpha);
-        rim_grad.add_color_stop_rgba(1.0, base_color.r, base_color.g, base_color.b, 0.3 * alpha);
-
         self.ctx.set_line_width(1.0);
-        self.ctx.set_source(&rim_grad).unwrap();
+        if partstat == Some(Partstat::Tentative) {
+            self.ctx.set_dash(&[4.0, 3.0], 0.0);
+            self.ctx
+                .set_source_rgba(base_color.r, base_color.g, base_color.b, 0.7 * alpha);
+        } else {
+            let rim_grad = gtk4::cairo::LinearGradient::new(x, y, x, y + h);
+            rim_grad.add_color_stop_rgba(
+                0.0,
+                base_color.r,
+                base_color.g,
+                base_color.b,
+                0.5 * alpha,
+            );
+            rim_grad.add_color_stop_rgba(
+                0.5,
+                base_color.r,
+                base_color.g,
+                base_color.b,
+                0.1 * alpha,
+            );
+            rim_grad.add_color_stop_rgba(
+                1.0,
+                base_color.r,
+                base_color.g,
+                base_color.b,
+                0.3 * alpha,
+            );
+            self.ctx.set_source(&rim_grad).unwrap();
+        }
         self.ctx.stroke().unwrap();
         self.ctx.restore().unwrap();
 
@@ -212,65 +408,55 @@ impl<'c> CalendarRenderer<'c> {
             .context
             .hm_format
             .as_ref()
-            .map(|f| f.event.as_str())
-            .unwrap_or("%H:%M");
+            .and_then(|f| f.event.as_deref())
+            .unwrap_or(locale::time_format());
         let time_str = event
             .start
             .as_ref()
-            .map(|s| format!("{}", s.local().format(fmt_str)))
+            .map(|s| locale::format_datetime(&s.local(), fmt_str))
             .unwrap_or_default();
 
         // Label
         self.ctx
             .set_source_rgba(base_color.r, base_color.g, base_color.b, 0.8 * alpha);
-        self.ctx
-            .select_font_face(&self.context.font, FontSlant::Normal, FontWeight::Bold);
 
         let is_tiny_event = h < 30.0;
         let padding_x = 10.0;
+        let inner_width = w - 2.0 * padding_x;
 
         if is_tiny_event {
-            self.ctx.set_font_size(10.0);
             let cy = y + (h / 2.0);
 
             // Only draw time if lane is wide enough
-            // let _time_extents = ctx.text_extents(&time_str).unwrap();
+            let mut summary_width = inner_width;
             if w > 100.0 {
-                CairoShapesExt::rjust_text(self.ctx, &time_str, x + w - 8.0, cy, true);
+                let time = self.text(&time_str, 10.0, Weight::Bold);
+                summary_width -= time.size().0 + 4.0;
+                time.show_rjust(self.ctx, x + w - 8.0, cy);
             }
 
-            // Draw summary with clipping (implicit)
-            CairoShapesExt::vert_centered_text(self.ctx, &summary, x + padding_x, cy);
+            self.text(summary, 10.0, Weight::Bold)
+                .max_width(summary_width)
+                .strikethrough(declined)
+                .show_vert_centered(self.ctx, x + padding_x, cy);
         } else {
             // Multi-line layout
-            self.ctx.set_font_size(11.0);
-            self.ctx.move_to(x + padding_x, y + 16.0);
-            self.ctx.show_text(&summary).unwrap();
-
-            self.ctx.set_font_size(9.0);
-            self.ctx
-                .select_font_face(&self.context.font, FontSlant::Normal, FontWeight::Normal);
+            self.text(summary, 11.0, Weight::Bold)
+                .max_width(inner_width)
+                .strikethrough(declined)
+                .show_baseline(self.ctx, x + padding_x, y + 16.0);
 
             // Time below title
-            self.ctx.move_to(x + padding_x, y + 28.0);
-            self.ctx.show_text(&time_str).unwrap();
+            self.text(&time_str, 9.0, Weight::Normal)
+                .max_width(inner_width)
+                .show_baseline(self.ctx, x + padding_x, y + 28.0);
 
             // Location at bottom or 3rd line
             if let Some(loc) = &event.location {
                 if h > 45.0 {
-                    let loc_width = self.ctx.text_extents(&loc).unwrap().width();
-                    let inner_width = w - 2.0 * padding_x;
-                    let text = if loc_width > inner_width {
-                        let avg_char_width = loc_width / loc.len() as f64;
-                        let take_chars = (inner_width / avg_char_width).floor() as usize;
-
-                        &format!("{}…", &loc[..take_chars.saturating_sub(1)])
-                    } else {
-                        loc
-                    };
-
-                    self.ctx.move_to(x + padding_x, y + 40.0);
-                    self.ctx.show_text(&text).unwrap();
+                    self.text(loc, 9.0, Weight::Normal)
+                        .max_width(inner_width)
+                        .show_baseline(self.ctx, x + padding_x, y + 40.0);
                 }
             }
         }
@@ -279,26 +465,39 @@ impl<'c> CalendarRenderer<'c> {
     }
     fn draw_allday_event(&self, event: &CalDavEvent, x_offset: &mut f64) {
         let color = event.calendar_info.color.as_deref().unwrap_or("#e9a949");
+        let partstat = event.own_partstat();
+        let declined = partstat == Some(Partstat::Declined);
+        let alpha = if declined { 0.4 } else { 1.0 };
 
         // Event label
-        let title = &event.title;
-        let extent = self.ctx.text_extents(&title).unwrap();
+        let title = self
+            .text(&event.title, 11.0, Weight::Normal)
+            .strikethrough(declined);
+        let (text_width, text_height) = title.size();
 
         // Color
         let color_str = event.calendar_info.color.as_deref().unwrap_or("#e9a949");
         let base_color = Rgba::from_str(color_str).unwrap_or_default();
 
-        // Subtract x_bearing here to align ink to padding
-        let width = extent.width() + 10.0;
-        let height = extent.height() + 10.0;
-        let x_start = self.context.padding - extent.x_bearing() + *x_offset;
+        let width = text_width + 10.0;
+        let height = text_height + 6.0;
+        let x_start = self.context.padding + *x_offset;
         let y_start = self.context.padding_top - 15.0 - height;
 
         *x_offset += width + 5.0;
 
         let event_color = Rgba::from_str(color).unwrap_or_default();
+        // Subscription feeds like holidays are outlined to set them apart from own events,
+        // tentative ones get a dashed outline
+        let tentative = partstat == Some(Partstat::Tentative);
+        let outlined = event.calendar_info.subscription || tentative;
+        let fill = if event.calendar_info.subscription {
+            0.12
+        } else {
+            0.45
+        };
         self.ctx
-            .set_source_rgba(base_color.r, base_color.g, base_color.b, 0.45);
+            .set_source_rgba(base_color.r, base_color.g, base_color.b, fill * alpha);
         CairoShapesExt::rounded_rectangle(
             self.ctx,
             x_start,
@@ -307,9 +506,20 @@ impl<'c> CalendarRenderer<'c> {
             height,
             (5.0, 5.0, 5.0, 5.0),
         );
-        self.ctx.fill().unwrap();
+        if outlined {
+            self.ctx.fill_preserve().unwrap();
+            self.ctx
+                .set_source_rgba(base_color.r, base_color.g, base_color.b, 0.6 * alpha);
+            self.ctx.set_line_width(self.context.hairline());
+            if tentative {
+                self.ctx.set_dash(&[4.0, 3.0], 0.0);
+            }
+            self.ctx.stroke().unwrap();
+            self.ctx.set_dash(&[], 0.0);
+        } else {
+            self.ctx.fill().unwrap();
+        }
 
-        self.ctx.set_font_size(11.0);
         self.ctx.set_source_rgba(
             0.0,
             0.0,
@@ -317,12 +527,7 @@ impl<'c> CalendarRenderer<'c> {
             0.7 * event_color.perceived_brightness_gamma(),
         );
         self.ctx
-            .set_source_rgba(base_color.r, base_color.g, base_color.b, 0.8);
-        CairoShapesExt::centered_text(
-            self.ctx,
-            &title,
-            x_start + width / 2.0,
-            y_start + height / 2.0,
-        );
+            .set_source_rgba(base_color.r, base_color.g, base_color.b, 0.8 * alpha);
+        title.show_centered(self.ctx, x_start + width / 2.0, y_start + height / 2.0);
     }
 }
@@ -0,0 +1,182 @@
+use std::{cell::RefCell, rc::Rc};
+
+use gtk4::{
+    Box, DrawingArea, GestureClick, Label, ListBox, ListBoxRow, Popover, ScrolledWindow,
+    SearchEntry, Stack,
+    gdk::Rectangle,
+    glib::{WeakRef, object::ObjectExt},
+    prelude::{BoxExt, EditableExt, GestureExt, PopoverExt, WidgetExt},
+};
+use suite_223b::calendar::utils::{CalDavEvent, CalEventType};
+
+use crate::ui::{
+    g_templates::event_details::EventDetails,
+    widgets::{
+        calendar::data_store::CalendarDataStore,
+        utils::{
+            attach_popover,
+            locale::{self, tr},
+        },
+    },
+};
+
+/// Results listed at most
+const MAX_RESULTS: usize = 20;
+
+/// Popover for finding events across all calendars, opened with a right click on the calendar.
+/// Picking a result opens its details.
+pub struct EventSearch {
+    data_store: Rc<CalendarDataStore>,
+    results: RefCell<Vec<CalDavEvent>>,
+    list: WeakRef<ListBox>,
+}
+impl EventSearch {
+    pub fn attach(
+        area: &DrawingArea,
+        stack: &Stack,
+        details: &EventDetails,
+        data_store: Rc<CalendarDataStore>,
+    ) {
+        let popover = Popover::builder()
+            .css_classes(["network-popover", "calendar-search"])
+            .has_arrow(true)
+            .build();
+        attach_popover(&popover, area);
+
+        let holder = Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(6)
+            .build();
+        let entry = SearchEntry::builder()
+            .placeholder_text(tr("Search events"))
+            .build();
+        let list = ListBox::builder()
+            .css_classes(["calendar-search-results"])
+            .selection_mode(gtk4::SelectionMode::None)
+            .visible(false)
+            .build();
+        list.set_placeholder(Some(
+            &Label::builder()
+                .label(tr("No events found"))
+                .css_classes(["network-popover-key"])
+                .build(),
+        ));
+        let scroll = ScrolledWindow::builder()
+            .hscrollbar_policy(gtk4::PolicyType::Never)
+            .propagate_natural_height(true)
+            .max_content_height(320)
+            .min_content_width(260)
+            .child(&list)
+            .build();
+        holder.append(&entry);
+        holder.append(&scroll);
+        popover.set_child(Some(&holder));
+
+        let search = Rc::new(Self {
+            data_store,
+            results: RefCell::new(Vec::new()),
+            list: list.downgrade(),
+        });
+
+        entry.connect_search_changed({
+            let search = Rc::clone(&search);
+            move |entry| search.update(&entry.text())
+        });
+
+        let open = Rc::new({
+            let search = Rc::clone(&search);
+            let popover = popover.downgrade();
+            let stack = stack.downgrade();
+            let details = details.downgrade();
+            move |index: usize| {
+                let results = search.results.borrow();
+                let (Some(event), Some(stack), Some(details)) =
+                    (results.get(index), stack.upgrade(), details.upgrade())
+                else {
+                    return;
+                };
+                if let Some(popover) = popover.upgrade() {
+                    popover.popdown();
+                }
+                stack.set_visible_child_name("details");
+                details.grab_focus();
+                details.set_event(event);
+            }
+        });
+        list.connect_row_activated({
+            let open = Rc::clone(&open);
+            move |_, row| open(row.index().max(0) as usize)
+        });
+        // Enter opens the first result
+        entry.connect_activate(move |_| open(0));
+
+        let click = GestureClick::builder().button(3).build();
+        click.connect_pressed({
+            let popover = popover.downgrade();
+            let entry = entry.downgrade();
+            move |gesture, _, x, y| {
+                gesture.set_state(gtk4::EventSequenceState::Claimed);
+                let (Some(popover), Some(entry)) = (popover.upgrade(), entry.upgrade()) else {
+                    return;
+                };
+                popover.set_pointing_to(Some(&Rectangle::new(x as i32, y as i32, 1, 1)));
+                popover.popup();
+                entry.grab_focus();
+            }
+        });
+        area.add_controller(click);
+    }
+
+    fn update(&self, query: &str) {
+        let Some(list) = self.list.upgrade() else {
+            return;
+        };
+        let results = self.data_store.search(query, MAX_RESULTS);
+        list.remove_all();
+        for event in &results {
+            list.append(&result_row(event));
+        }
+        list.set_visible(!query.trim().is_empty());
+        self.results.replace(results);
+    }
+}
+
+fn result_row(event: &CalDavEvent) -> ListBoxRow {
+    let when = event
+        .start
+        .as_ref()
+        .map(|start| {
+            let start = start.local();
+            let date = locale::format_date(start.date_naive(), locale::day_format());
+            match event.event_type {
+                CalEventType::AllDay => date,
+                CalEventType::Timed => format!(
+                    "{date}, {}",
+                    locale::format_datetime(&start, locale::time_format())
+                ),
+            }
+        })
+        .unwrap_or_default();
+
+    let holder = Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .css_classes(["calendar-search-row"])
+        .build();
+    holder.append(
+        &Label::builder()
+            .label(event.title.as_str())
+            .css_classes(["calendar-search-title"])
+            .xalign(0.0)
+            .ellipsize(gtk4::pango::EllipsizeMode::End)
+            .build(),
+    );
+    holder.append(
+        &Label::builder()
+            .label(format!("{when} · {}", event.calendar_info.name))
+            .css_classes(["network-popover-key"])
+            .xalign(0.0)
+            .ellipsize(gtk4::pango::EllipsizeMode::End)
+            .build(),
+    );
+    ListBoxRow::builder().child(&holder).build()
+}
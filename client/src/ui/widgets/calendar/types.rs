@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 pub struct CalendarConfig<'w> {
@@ -6,23 +7,22 @@ pub struct CalendarConfig<'w> {
     pub hm_format: Option<&'w CalendarHMFormat>,
     pub hours_past: u8,
     pub hours_future: u8,
+    pub sun: bool,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// strftime formats, by the locale and `clock_format` when unset
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(default)]
 pub struct CalendarHMFormat {
-    pub event: String,
-    pub timeline: String,
-}
-impl Default for CalendarHMFormat {
-    fn default() -> Self {
-        Self {
-            event: "%H:%M".into(),
-            timeline: "%H:%M".into(),
-        }
-    }
+    pub event: Option<String>,
+    pub timeline: Option<String>,
+    /// Header date, e.g. `%b %-d`
+    pub date: Option<String>,
+    /// Below the header date, e.g. `%A`
+    pub weekday: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum CalendarRule {
     /// Show all except these specific calendars.
@@ -1,23 +1,48 @@
-use std::{f64::consts::PI, fs, str::FromStr};
+use std::{
+    cell::{Cell, RefCell},
+    f64::consts::PI,
+    fs,
+    str::FromStr,
+    time::Duration,
+};
 
 use crate::{
     config::WidgetSpec,
-    ui::widgets::utils::render::{CairoShapesExt, Rgba},
+    ui::{
+        g_templates::snapshot_area::SnapshotArea,
+        location,
+        widgets::utils::{
+            is_suspended, locale, power,
+            render::{CairoShapesExt, Rgba, SUN_COLOR},
+            text::TextLayout,
+        },
+    },
 };
-use chrono::{DateTime, Local, Timelike};
+use chrono::{DateTime, Local, Timelike, Utc};
 use chrono_tz::Tz;
 use gtk4::{
-    DrawingArea,
+    Snapshot,
     cairo::Context,
+    gdk::RGBA,
     glib::object::ObjectExt,
-    prelude::{DrawingAreaExtManual, WidgetExt},
+    graphene::{Point, Rect, Size},
+    gsk::{RenderNode, RoundedRect},
+    pango::Weight,
+    prelude::{SnapshotExt, WidgetExt},
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use suite_223b::utils::astronomy::{Moon, SunTimes};
 
 #[derive(Default)]
 pub struct ClockConfig {
     time_zone: Option<String>,
+    secondary_time_zone: Option<String>,
+    secondary_style: SecondaryStyle,
     hand_style: HandStyle,
+    second_hand: SecondHand,
+    time_format: Option<String>,
+    complications: Vec<ClockComplication>,
     accent_color: String,
     font: String,
 }
@@ -25,7 +50,12 @@ impl From<&WidgetSpec> for ClockConfig {
     fn from(value: &WidgetSpec) -> Self {
         if let WidgetSpec::Clock {
             time_zone,
+            secondary_time_zone,
+            secondary_style,
             hand_style,
+            second_hand,
+            time_format,
+            complications,
             accent_color,
             font,
             ..
@@ -33,7 +63,12 @@ impl From<&WidgetSpec> for ClockConfig {
         {
             Self {
                 time_zone: time_zone.clone(),
+                secondary_time_zone: secondary_time_zone.clone(),
+                secondary_style: *secondary_style,
                 hand_style: hand_style.clone(),
+                second_hand: *second_hand,
+                time_format: time_format.clone(),
+                complications: complications.clone(),
                 accent_color: accent_color.clone(),
                 font: font.clone(),
             }
@@ -42,19 +77,99 @@ impl From<&WidgetSpec> for ClockConfig {
         }
     }
 }
+impl ClockConfig {
+    fn tz(&self) -> Tz {
+        match &self.time_zone {
+            Some(tz_str) => tz_str.parse::<Tz>().unwrap_or(Tz::UTC),
+            None => {
+                fs::read_link("/etc/localtime")
+                    .ok()
+                    .and_then(|path| {
+                        // Extract "Europe/Berlin" from "/usr/share/zoneinfo/Europe/Berlin"
+                        let path_str = path.to_str()?;
+                        let parts: Vec<&str> = path_str.split("zoneinfo/").collect();
+                        parts.get(1).map(|&name| name.to_string())
+                    })
+                    .and_then(|name| name.parse::<Tz>().ok())
+                    // 3. Absolute fallback if symlink is missing or unparseable
+                    .unwrap_or(Tz::UTC)
+            }
+        }
+    }
+    fn secondary_tz(&self) -> Option<Tz> {
+        self.secondary_time_zone
+            .as_ref()
+            .map(|tz_str| tz_str.parse::<Tz>().unwrap_or(Tz::UTC))
+    }
+    fn secondary(&self) -> Option<SecondaryStyle> {
+        self.secondary_time_zone
+            .as_ref()
+            .map(|_| self.secondary_style)
+    }
+}
+
+/// How the clock shows `secondary_time_zone`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SecondaryStyle {
+    /// Thin 24-hour ring with a dot at the other zone's time
+    #[default]
+    Ring,
+    /// Small dial with its own hands below the center
+    SubDial,
+}
+
+/// How the second hand moves, which also decides how often the clock redraws
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SecondHand {
+    /// Jumps once per second
+    #[default]
+    Tick,
+    /// Moves smoothly, redrawn on every frame while the clock is visible
+    Sweep,
+    /// No second hand, redrawn once per minute
+    Hidden,
+}
+
+/// Extras drawn on the face, from `astronomy.json`'s location
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockComplication {
+    /// Dots on the rim at sunrise, filled, and sunset, hollow
+    Daylight,
+    /// The moon's phase left of the center
+    Moon,
+}
+
+/// Everything the static clock face depends on. The face is only rerasterized when one of these
+/// changes.
+#[derive(PartialEq)]
+struct FaceKey {
+    width: f64,
+    height: f64,
+    color: RGBA,
+    offset: String,
+}
+
+#[derive(Default)]
+struct FaceCache {
+    key: Option<FaceKey>,
+    node: Option<RenderNode>,
+}
+
 pub struct Clock;
 impl Clock {
-    pub fn new(specs: WidgetSpec) -> DrawingArea {
+    pub fn new(specs: WidgetSpec) -> SnapshotArea {
         let config = ClockConfig::from(&specs);
         let base = specs.base();
 
-        let clock_area = DrawingArea::builder()
-            .vexpand(false)
-            .hexpand(false)
-            .css_classes(["widget", "clock"])
-            .valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
-            .halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
-            .build();
+        let clock_area = SnapshotArea::new();
+        clock_area.set_vexpand(false);
+        clock_area.set_hexpand(false);
+        clock_area.set_css_classes(&["widget", "clock"]);
+        clock_area.set_valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Start));
+        clock_area.set_halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Start));
 
         if let Some(id) = specs.id() {
             clock_area.set_widget_name(id);
@@ -65,79 +180,155 @@ impl Clock {
 
         clock_area.set_size_request(200, 200);
 
-        clock_area.set_draw_func({
-            let config = config;
-            move |area, ctx, width, height| {
-                Clock::draw(area, ctx, width, height, &config);
+        // The exact time, the face has no numbers to read it from
+        clock_area.set_has_tooltip(true);
+        clock_area.connect_query_tooltip({
+            let tz = config.tz();
+            let time_format = config.time_format.clone();
+            move |_, _, _, _, tooltip| {
+                let now = Local::now().with_timezone(&tz);
+                let fmt = time_format.as_deref().unwrap_or(locale::time_format());
+                tooltip.set_text(Some(&format!(
+                    "{}\n{}",
+                    locale::format_datetime(&now, fmt),
+                    locale::format_datetime(&now, "%A, %-d %B"),
+                )));
+                true
             }
         });
 
-        // Make update every second
-        let clock_area_clone = clock_area.downgrade();
-        gtk4::glib::timeout_add_seconds_local(1, move || {
-            if let Some(clock) = clock_area_clone.upgrade() {
-                clock.queue_draw();
+        let second_hand = config.second_hand;
+        let interval = base.update_interval;
+        clock_area.set_snapshot_func({
+            let config = config;
+            let tz = config.tz();
+            let face = RefCell::new(FaceCache::default());
+            move |area, snapshot, width, height| {
+                Clock::snapshot(area, snapshot, width, height, &config, tz, &face);
             }
-            gtk4::glib::ControlFlow::Continue
         });
 
+        match second_hand {
+            // The frame clock only runs while the window is shown
+            SecondHand::Sweep => {
+                // Ticks instead while saving power
+                let last_second = Cell::new(0);
+                clock_area.add_tick_callback(move |widget, _| {
+                    let second = Local::now().second();
+                    if is_suspended() || power::is_saving_power() && last_second.get() == second {
+                        return gtk4::glib::ControlFlow::Continue;
+                    }
+                    last_second.set(second);
+                    widget.queue_draw();
+                    gtk4::glib::ControlFlow::Continue
+                });
+            }
+            SecondHand::Tick => Self::redraw_every(&clock_area, interval.unwrap_or(1)),
+            SecondHand::Hidden => {
+                // Start at the next full minute so the minute hand moves on time
+                let area = clock_area.downgrade();
+                let delay = 60 - Local::now().second().min(59) as u64;
+                gtk4::glib::timeout_add_local_once(Duration::from_secs(delay), move || {
+                    if let Some(clock) = area.upgrade() {
+                        clock.queue_draw();
+                        Self::redraw_every(&clock, interval.unwrap_or(60));
+                    }
+                });
+            }
+        }
+
         clock_area
     }
-    pub fn draw(_area: &DrawingArea, ctx: &Context, width: i32, height: i32, config: &ClockConfig) {
-        let ClockConfig {
-            time_zone,
-            hand_style: head_style,
-            accent_color,
-            font,
-        } = config;
-
-        let tz: Tz = match time_zone {
-            Some(tz_str) => tz_str.parse::<Tz>().unwrap_or(Tz::UTC),
-            None => {
-                fs::read_link("/etc/localtime")
-                    .ok()
-                    .and_then(|path| {
-                        // Extract "Europe/Berlin" from "/usr/share/zoneinfo/Europe/Berlin"
-                        let path_str = path.to_str()?;
-                        let parts: Vec<&str> = path_str.split("zoneinfo/").collect();
-                        parts.get(1).map(|&name| name.to_string())
-                    })
-                    .and_then(|name| name.parse::<Tz>().ok())
-                    // 3. Absolute fallback if symlink is missing or unparseable
-                    .unwrap_or(Tz::UTC)
+    fn redraw_every(clock_area: &SnapshotArea, seconds: u32) {
+        let clock_area_clone = clock_area.downgrade();
+        power::every(seconds, move || match clock_area_clone.upgrade() {
+            Some(clock) => {
+                clock.queue_draw();
+                gtk4::glib::ControlFlow::Continue
             }
-        };
-
+            None => gtk4::glib::ControlFlow::Break,
+        });
+    }
+    fn snapshot(
+        area: &SnapshotArea,
+        snapshot: &Snapshot,
+        width: f64,
+        height: f64,
+        config: &ClockConfig,
+        tz: Tz,
+        face: &RefCell<FaceCache>,
+    ) {
         let now_full: DateTime<Tz> = Local::now().with_timezone(&tz);
         let now = now_full.time();
-
-        let padding = (width as f64 * 0.03).max(5.0);
-        let inner_height = height as f64 - 2.0 * padding;
-        let inner_width = width as f64 - 2.0 * padding;
-
-        let clock = ClockContext {
-            color: Rgba::from(_area.color()),
-            center: inner_height / 2.0 + padding,
-            head_margin: 12.0,
-            radius: (inner_width.min(inner_height) / 2.0) as f64,
-
-            hour: now.hour() as f64,
-            minute: now.minute() as f64,
-            second: now.second() as f64,
+        let mut clock = ClockContext::new(area.color(), width, height, &now);
+        if config.second_hand == SecondHand::Sweep {
+            clock.second += now.nanosecond().min(999_999_999) as f64 / 1e9;
+        }
+        let bounds = Rect::new(0.0, 0.0, width as f32, height as f32);
+
+        // Clock face, rasterized once and reused as a render node
+        let key = FaceKey {
+            width,
+            height,
+            color: area.color(),
+            offset: now_full.offset().to_string(),
         };
+        let mut face = face.borrow_mut();
+        if face.key.as_ref() != Some(&key) || face.node.is_none() {
+            let face_snapshot = Snapshot::new();
+            let ctx = face_snapshot.append_cairo(&bounds);
+            Self::draw_face(&ctx, &clock, config, &tz, &key.offset);
+            drop(ctx);
+            face.node = face_snapshot.to_node();
+            face.key = Some(key);
+        }
+        if let Some(node) = &face.node {
+            snapshot.append_node(node);
+        }
 
-        ctx.select_font_face(
-            &font,
-            gtk4::cairo::FontSlant::Normal,
-            gtk4::cairo::FontWeight::Bold,
-        );
-        ctx.set_font_size(15.0);
+        // Hour and minute hands move every second as well, so there is nothing to cache
+        let ctx = snapshot.append_cairo(&bounds);
+        ctx.set_line_cap(gtk4::cairo::LineCap::Round);
+        if let Some(secondary) = config.secondary_tz() {
+            let time = now_full.with_timezone(&secondary);
+            let accent = Rgba::from_str(&config.accent_color).unwrap_or_default();
+            match config.secondary_style {
+                SecondaryStyle::Ring => Self::ring_time(&ctx, &clock, &time, &accent, config),
+                SecondaryStyle::SubDial => Self::subdial_hands(&ctx, &clock, &time, &accent),
+            }
+        }
+        for complication in &config.complications {
+            match complication {
+                ClockComplication::Daylight => Self::draw_daylight(&ctx, &clock, &now_full),
+                ClockComplication::Moon => Self::draw_moon(&ctx, &clock, &now_full),
+            }
+        }
+        config.hand_style.hour_head(&ctx, &clock);
+        config.hand_style.minute_hand(&ctx, &clock);
+        drop(ctx);
+
+        // Second hand and screws are plain color nodes
+        if config.second_hand != SecondHand::Hidden {
+            HandStyle::Modern {
+                color: config.accent_color.clone(),
+                width: 6.0,
+            }
+            .second_head(snapshot, &clock);
+        }
+
+        let screw = Rgba::from_str("#bf4759").unwrap_or_default();
+        Self::append_circle(snapshot, &clock, 4.5, &RGBA::BLACK);
+        Self::append_circle(snapshot, &clock, 3.0, &screw.into());
+        Self::append_circle(snapshot, &clock, 1.5, &RGBA::WHITE);
+    }
+    fn draw_face(ctx: &Context, clock: &ClockContext, config: &ClockConfig, tz: &Tz, offset: &str) {
+        let font = &config.font;
         ctx.set_line_cap(gtk4::cairo::LineCap::Round);
 
         // Clock Face
         let inverse = clock.color.invert();
         ctx.set_source_rgb(inverse.r, inverse.g, inverse.b);
-        CairoShapesExt::circle(ctx, clock.center, clock.center, inner_height / 2.0);
+        CairoShapesExt::circle(ctx, clock.center, clock.center, clock.face_radius);
 
         CairoShapesExt::circle(ctx, clock.center, clock.center, 5.0);
 
@@ -161,7 +352,8 @@ impl Clock {
 
             // Draw text
             ctx.set_source_rgb(muted3.r, muted3.g, muted3.b);
-            CairoShapesExt::centered_text(ctx, &i.to_string(), x3, y3);
+            TextLayout::new(ctx, &i.to_string(), font, 15.0, Weight::Bold)
+                .show_centered(ctx, x3, y3);
         }
 
         // Draw minute marks
@@ -186,48 +378,215 @@ impl Clock {
         }
 
         // Draw Zimezone
-        ctx.select_font_face(
-            &font,
-            gtk4::cairo::FontSlant::Normal,
-            gtk4::cairo::FontWeight::Normal,
-        );
         if let Some(tz_str) = tz.name().split('/').last().map(|s| s.replace('_', " ")) {
-            let time_offset = now_full.offset().to_string();
             ctx.set_source_rgb(0.8, 0.8, 0.8);
 
-            CairoShapesExt::centered_text(ctx, &time_offset, clock.center, clock.center + 35.0);
-
-            ctx.set_font_size(18.0);
-            CairoShapesExt::centered_text(ctx, &tz_str, clock.center, clock.center - 35.0);
+            // Make room for the second time zone below the center
+            let (offset_y, offset_size) = match config.secondary() {
+                None => (35.0, 15.0),
+                Some(SecondaryStyle::Ring) => (28.0, 15.0),
+                Some(SecondaryStyle::SubDial) => (-16.0, 12.0),
+            };
+            TextLayout::new(ctx, offset, font, offset_size, Weight::Normal).show_centered(
+                ctx,
+                clock.center,
+                clock.center + offset_y,
+            );
+
+            TextLayout::new(ctx, &tz_str, font, 18.0, Weight::Normal)
+                .max_width(clock.radius * 1.4)
+                .show_centered(ctx, clock.center, clock.center - 35.0);
         }
 
-        // Draw Hour Hand
-        head_style.hour_head(ctx, &clock);
-
-        // Draw Minute Hand
-        head_style.minute_hand(ctx, &clock);
-
-        // Draw Second Hand
-        HandStyle::Modern {
-            color: accent_color.clone(),
-            width: 6.0,
+        match config.secondary() {
+            Some(SecondaryStyle::Ring) => Self::ring_face(ctx, clock),
+            Some(SecondaryStyle::SubDial) => Self::subdial_face(ctx, clock),
+            None => {}
         }
-        .second_head(&ctx, &clock);
-
-        // Draw screws
-        ctx.set_source_rgb(0.0, 0.0, 0.0);
-        CairoShapesExt::circle(ctx, clock.center, clock.center, 4.5);
-
-        let Rgba { r, g, b, a } = Rgba::from_str("#bf4759").unwrap_or_default();
-        ctx.set_source_rgba(r, g, b, a);
-        CairoShapesExt::circle(ctx, clock.center, clock.center, 3.0);
-
-        ctx.set_source_rgb(1.0, 1.0, 1.0);
-        CairoShapesExt::circle(ctx, clock.center, clock.center, 1.5);
+    }
+    fn draw_daylight(ctx: &Context, clock: &ClockContext, now: &DateTime<Tz>) -> Option<()> {
+        let sun = SunTimes::on(now.date_naive(), location::location()?)?;
+        let color = Rgba::from_str(SUN_COLOR).unwrap_or_default();
+        ctx.set_source_rgba(color.r, color.g, color.b, color.a);
+        ctx.set_line_width(1.5);
+        let radius = clock.radius - 1.5;
+        for (time, filled) in [(sun.sunrise, true), (sun.sunset, false)] {
+            let time = time.with_timezone(&now.timezone());
+            let hours = (time.hour() % 12) as f64 + time.minute() as f64 / 60.0;
+            let angle = hours * (2.0 * PI / 12.0);
+            ctx.new_path();
+            ctx.arc(
+                clock.center + radius * angle.sin(),
+                clock.center - radius * angle.cos(),
+                2.5,
+                0.0,
+                2.0 * PI,
+            );
+            if filled {
+                ctx.fill().unwrap();
+            } else {
+                ctx.stroke().unwrap();
+            }
+        }
+        Some(())
+    }
+    /// The lit part as seen from the northern hemisphere, growing from the right
+    fn draw_moon(ctx: &Context, clock: &ClockContext, now: &DateTime<Tz>) {
+        let moon = Moon::at(now.with_timezone(&Utc));
+        let radius = clock.radius * 0.08;
+        let cx = clock.center - clock.radius * 0.42;
+        let cy = clock.center;
+
+        ctx.save().unwrap();
+        ctx.translate(cx, cy);
+        ctx.scale(if moon.waxing() { 1.0 } else { -1.0 }, 1.0);
+        ctx.set_source_rgba(clock.color.r, clock.color.g, clock.color.b, 0.15);
+        ctx.new_path();
+        ctx.arc(0.0, 0.0, radius, 0.0, 2.0 * PI);
+        ctx.fill().unwrap();
+
+        // Limb on the lit side, then back along the terminator, an ellipse that narrows to
+        // nothing at the quarters
+        let terminator = 1.0 - 2.0 * moon.illumination;
+        ctx.new_path();
+        ctx.arc(0.0, 0.0, radius, -PI / 2.0, PI / 2.0);
+        const STEPS: usize = 24;
+        for step in 0..=STEPS {
+            let t = PI / 2.0 - PI * step as f64 / STEPS as f64;
+            ctx.line_to(terminator * radius * t.cos(), radius * t.sin());
+        }
+        ctx.close_path();
+        ctx.set_source_rgba(clock.color.r, clock.color.g, clock.color.b, 0.8);
+        ctx.fill().unwrap();
+        ctx.restore().unwrap();
+    }
+    /// Radius of the 24-hour ring, inside the numbers and past the hour hand
+    fn ring_radius(clock: &ClockContext) -> f64 {
+        clock.radius * 0.6
+    }
+    fn ring_face(ctx: &Context, clock: &ClockContext) {
+        let muted = clock.color.lerp(&clock.color.invert(), 0.6);
+        ctx.set_source_rgb(muted.r, muted.g, muted.b);
+        ctx.set_line_width(1.0);
+
+        let radius = Self::ring_radius(clock);
+        ctx.new_path();
+        ctx.arc(clock.center, clock.center, radius, 0.0, 2.0 * PI);
+        ctx.stroke().unwrap();
+
+        // Longer ticks every six hours, midnight at the top
+        for i in 0..24 {
+            let angle = i as f64 * (2.0 * PI / 24.0);
+            let length = if i % 6 == 0 { 4.0 } else { 2.0 };
+            ctx.move_to(
+                clock.center + (radius - length) * angle.sin(),
+                clock.center - (radius - length) * angle.cos(),
+            );
+            ctx.line_to(
+                clock.center + (radius + length) * angle.sin(),
+                clock.center - (radius + length) * angle.cos(),
+            );
+            ctx.stroke().unwrap();
+        }
+    }
+    fn ring_time(
+        ctx: &Context,
+        clock: &ClockContext,
+        time: &DateTime<Tz>,
+        accent: &Rgba,
+        config: &ClockConfig,
+    ) {
+        let hours = time.hour() as f64 + time.minute() as f64 / 60.0;
+        let angle = hours * (2.0 * PI / 24.0);
+        let radius = Self::ring_radius(clock);
+
+        ctx.set_source_rgba(accent.r, accent.g, accent.b, accent.a);
+        ctx.new_path();
+        ctx.arc(
+            clock.center + radius * angle.sin(),
+            clock.center - radius * angle.cos(),
+            3.5,
+            0.0,
+            2.0 * PI,
+        );
+        ctx.fill().unwrap();
+
+        let name = time
+            .timezone()
+            .name()
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .replace('_', " ");
+        let fmt = config
+            .time_format
+            .as_deref()
+            .unwrap_or(locale::time_format());
+        let label = format!("{} {}", name, locale::format_datetime(time, fmt));
+        ctx.set_source_rgb(0.8, 0.8, 0.8);
+        TextLayout::new(ctx, &label, &config.font, 11.0, Weight::Normal)
+            .max_width(clock.radius * 0.9)
+            .show_centered(ctx, clock.center, clock.center + 44.0);
+    }
+    /// Center and radius of the sub-dial
+    fn subdial(clock: &ClockContext) -> (f64, f64, f64) {
+        (
+            clock.center,
+            clock.center + clock.radius * 0.42,
+            clock.radius * 0.18,
+        )
+    }
+    fn subdial_face(ctx: &Context, clock: &ClockContext) {
+        let (x, y, radius) = Self::subdial(clock);
+        let muted = clock.color.lerp(&clock.color.invert(), 0.6);
+        ctx.set_source_rgb(muted.r, muted.g, muted.b);
+        ctx.set_line_width(1.0);
+        ctx.new_path();
+        ctx.arc(x, y, radius, 0.0, 2.0 * PI);
+        ctx.stroke().unwrap();
+
+        for i in 0..4 {
+            let angle = i as f64 * (PI / 2.0);
+            ctx.move_to(
+                x + (radius - 3.0) * angle.sin(),
+                y - (radius - 3.0) * angle.cos(),
+            );
+            ctx.line_to(x + radius * angle.sin(), y - radius * angle.cos());
+            ctx.stroke().unwrap();
+        }
+    }
+    fn subdial_hands(ctx: &Context, clock: &ClockContext, time: &DateTime<Tz>, accent: &Rgba) {
+        let (x, y, radius) = Self::subdial(clock);
+        let hour = (time.hour() % 12) as f64 + time.minute() as f64 / 60.0;
+        let minute = time.minute() as f64;
+        let hands = [
+            (hour * (2.0 * PI / 12.0), radius * 0.55, 2.0, *accent),
+            (minute * (2.0 * PI / 60.0), radius * 0.85, 1.2, clock.color),
+        ];
+        for (angle, length, width, color) in hands {
+            ctx.set_source_rgba(color.r, color.g, color.b, color.a);
+            ctx.set_line_width(width);
+            ctx.move_to(x, y);
+            ctx.line_to(x + length * angle.sin(), y - length * angle.cos());
+            ctx.stroke().unwrap();
+        }
+    }
+    fn append_circle(snapshot: &Snapshot, clock: &ClockContext, radius: f64, color: &RGBA) {
+        let r = radius as f32;
+        let rect = Rect::new(
+            (clock.center - radius) as f32,
+            (clock.center - radius) as f32,
+            2.0 * r,
+            2.0 * r,
+        );
+        let corner = Size::new(r, r);
+        snapshot.push_rounded_clip(&RoundedRect::new(rect, corner, corner, corner, corner));
+        snapshot.append_color(color, &rect);
+        snapshot.pop();
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum HandStyle {
     Modern { color: String, width: f64 },
@@ -377,29 +736,34 @@ impl HandStyle {
         }
     }
 
-    fn second_head(&self, ctx: &Context, clock: &ClockContext) {
+    fn second_head(&self, snapshot: &Snapshot, clock: &ClockContext) {
         match self {
             Self::Modern { color, .. } => {
-                // Draw second head
-                let Rgba { r, g, b, a } = Rgba::from_str(&color).unwrap_or_default();
-                ctx.set_source_rgba(r, g, b, a);
-                ctx.set_line_width(2.0);
-                let line_length = clock.radius * 0.8;
-                let angle = clock.second as f64 * (PI / 30.0);
-                let x1 = clock.center + line_length * angle.sin();
-                let y1 = clock.center - line_length * angle.cos();
-
-                ctx.move_to(clock.center, clock.center);
-                ctx.line_to(x1, y1);
-                ctx.stroke().unwrap();
-
-                let angle = (clock.second as f64 - 30.0) * (PI / 30.0);
-                let x1 = clock.center + 1.3 * clock.head_margin * angle.sin();
-                let y1 = clock.center - 1.3 * clock.head_margin * angle.cos();
-
-                ctx.move_to(clock.center, clock.center);
-                ctx.line_to(x1, y1);
-                ctx.stroke().unwrap();
+                // Draw second head as a single bar rotated around the center, reaching past the
+                // center by the tail length
+                let color: RGBA = Rgba::from_str(&color).unwrap_or_default().into();
+                let line_width = 2.0_f32;
+                let line_length = (clock.radius * 0.8) as f32;
+                let tail = (1.3 * clock.head_margin) as f32;
+                let angle = clock.second * 6.0;
+
+                snapshot.save();
+                snapshot.translate(&Point::new(clock.center as f32, clock.center as f32));
+                snapshot.rotate(angle as f32);
+
+                let half = line_width / 2.0;
+                let rect = Rect::new(
+                    -half,
+                    -line_length - half,
+                    line_width,
+                    line_length + tail + line_width,
+                );
+                let corner = Size::new(half, half);
+                snapshot.push_rounded_clip(&RoundedRect::new(rect, corner, corner, corner, corner));
+                snapshot.append_color(&color, &rect);
+                snapshot.pop();
+
+                snapshot.restore();
             }
             _ => {}
         }
@@ -411,8 +775,28 @@ struct ClockContext {
     center: f64,
     head_margin: f64,
     radius: f64,
+    face_radius: f64,
 
     hour: f64,
     minute: f64,
     second: f64,
 }
+impl ClockContext {
+    fn new(color: RGBA, width: f64, height: f64, now: &impl Timelike) -> Self {
+        let padding = (width * 0.03).max(5.0);
+        let inner_height = height - 2.0 * padding;
+        let inner_width = width - 2.0 * padding;
+
+        Self {
+            color: Rgba::from(color),
+            center: inner_height / 2.0 + padding,
+            head_margin: 12.0,
+            radius: inner_width.min(inner_height) / 2.0,
+            face_radius: inner_height / 2.0,
+
+            hour: now.hour() as f64,
+            minute: now.minute() as f64,
+            second: now.second() as f64,
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::{cell::RefCell, fs, path::PathBuf, rc::Rc};
+
+use gtk4::{
+    Box, DrawingArea, GestureClick, Label, Popover, Widget,
+    gdk::Display,
+    glib::{
+        WeakRef,
+        object::{Cast, IsA, ObjectExt},
+    },
+    prelude::{BoxExt, DisplayExt, DrawingAreaExtManual, GestureExt, PopoverExt, WidgetExt},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use suite_223b::{
+    protocol::PickedColor,
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::get_data_dir,
+    },
+    watson_err,
+};
+
+use crate::ui::widgets::utils::{attach_popover, locale::tr};
+
+/// Colors kept in the popover, newest first
+const HISTORY_LEN: usize = 8;
+
+/// What the `colorpicker` button copies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorFormat {
+    /// `#1e90ff`
+    #[default]
+    Hex,
+    /// `rgb(30, 144, 255)`
+    Rgb,
+}
+impl ColorFormat {
+    pub fn format(self, color: PickedColor) -> String {
+        let PickedColor { red, green, blue } = color;
+        match self {
+            Self::Hex => format!("#{red:02x}{green:02x}{blue:02x}"),
+            Self::Rgb => format!("rgb({red}, {green}, {blue})"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ColorHistory {
+    colors: Vec<PickedColor>,
+}
+impl ColorHistory {
+    fn path() -> Result<PathBuf, WatsonError> {
+        Ok(get_data_dir()?.join("colors.json"))
+    }
+    fn load() -> Self {
+        Self::path()
+            .ok()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+    fn save(&self) -> Result<(), WatsonError> {
+        let bytes = serde_json::to_vec(self)
+            .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+        fs::write(Self::path()?, bytes)
+            .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))
+    }
+    /// Picking a color again moves it to the front
+    fn push(&mut self, color: PickedColor) {
+        self.colors.retain(|c| *c != color);
+        self.colors.insert(0, color);
+        self.colors.truncate(HISTORY_LEN);
+    }
+}
+
+/// Copies picked colors, the last few are listed in a popover opened with a right click on the
+/// color picker button
+#[derive(Clone, Debug)]
+pub struct ColorPicker {
+    format: ColorFormat,
+    history: Rc<RefCell<ColorHistory>>,
+}
+impl ColorPicker {
+    pub fn attach(target: &impl IsA<Widget>, format: ColorFormat) -> Self {
+        let target = target.upcast_ref::<Widget>();
+        let picker = Self {
+            format,
+            history: Rc::new(RefCell::new(ColorHistory::load())),
+        };
+
+        let popover = Popover::builder()
+            .css_classes(["network-popover", "color-popover"])
+            .has_arrow(true)
+            .build();
+        attach_popover(&popover, target);
+
+        let click = GestureClick::builder().button(3).build();
+        click.connect_pressed({
+            let popover = popover.downgrade();
+            let picker = picker.clone();
+            move |gesture, _, _, _| {
+                gesture.set_state(gtk4::EventSequenceState::Claimed);
+                let Some(popover) = popover.upgrade() else {
+                    return;
+                };
+                popover.set_child(Some(&picker.content(popover.downgrade())));
+                popover.popup();
+            }
+        });
+        target.add_controller(click);
+
+        picker
+    }
+
+    /// Copies `color` to the clipboard and remembers it. Returns the copied text.
+    pub fn picked(&self, color: PickedColor) -> String {
+        let text = self.copy(color);
+        let mut history = self.history.borrow_mut();
+        history.push(color);
+        if let Err(e) = history.save() {
+            eprintln!("{:?}", e);
+        }
+        text
+    }
+
+    fn copy(&self, color: PickedColor) -> String {
+        let text = self.format.format(color);
+        if let Some(display) = Display::default() {
+            display.clipboard().set_text(&text);
+        }
+        text
+    }
+
+    fn content(&self, popover: WeakRef<Popover>) -> Box {
+        let holder = Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(4)
+            .build();
+
+        let history = self.history.borrow();
+        if history.colors.is_empty() {
+            holder.append(
+                &Label::builder()
+                    .label(tr("No colors picked yet"))
+                    .css_classes(["network-popover-title"])
+                    .build(),
+            );
+            return holder;
+        }
+
+        for &color in &history.colors {
+            let row = Box::builder()
+                .css_classes(["color-history-row"])
+                .spacing(8)
+                .build();
+            let swatch = DrawingArea::builder()
+                .css_classes(["color-swatch"])
+                .content_width(16)
+                .content_height(16)
+                .valign(gtk4::Align::Center)
+                .build();
+            swatch.set_draw_func(move |_, ctx, width, height| {
+                ctx.set_source_rgb(
+                    color.red as f64 / 255.0,
+                    color.green as f64 / 255.0,
+                    color.blue as f64 / 255.0,
+                );
+                ctx.rectangle(0.0, 0.0, width as f64, height as f64);
+                let _ = ctx.fill();
+            });
+            row.append(&swatch);
+            row.append(
+                &Label::builder()
+                    .label(self.format.format(color))
+                    .xalign(0.0)
+                    .build(),
+            );
+
+            // Copies the color again
+            let click = GestureClick::new();
+            click.connect_released({
+                let picker = self.clone();
+                let popover = popover.clone();
+                move |_, _, _, _| {
+                    picker.copy(color);
+                    if let Some(popover) = popover.upgrade() {
+                        popover.popdown();
+                    }
+                }
+            });
+            row.add_controller(click);
+            holder.append(&row);
+        }
+        holder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_formats() {
+        let color = PickedColor {
+            red: 30,
+            green: 144,
+            blue: 255,
+        };
+        assert_eq!(ColorFormat::Hex.format(color), "#1e90ff");
+        assert_eq!(ColorFormat::Rgb.format(color), "rgb(30, 144, 255)");
+    }
+
+    #[test]
+    fn test_history_moves_repicked_colors_to_front() {
+        let color = |red| PickedColor {
+            red,
+            ..Default::default()
+        };
+        let mut history = ColorHistory::default();
+        for red in 0..10 {
+            history.push(color(red));
+        }
+        assert_eq!(history.colors.len(), HISTORY_LEN);
+        assert_eq!(history.colors[0], color(9));
+
+        history.push(color(5));
+        assert_eq!(history.colors[0], color(5));
+        assert_eq!(history.colors.iter().filter(|c| **c == color(5)).count(), 1);
+    }
+}
@@ -0,0 +1,91 @@
+use gtk4::{
+    Box, Button, Label,
+    glib::{WeakRef, object::ObjectExt},
+    prelude::{BoxExt, ButtonExt, WidgetExt},
+};
+use suite_223b::protocol::{FocusState, Request};
+
+use crate::{
+    DAEMON_TX,
+    config::WidgetSpec,
+    ui::widgets::utils::{WidgetOption, locale::tr},
+};
+
+/// A button per profile in the daemon's `focus.json`. Clicking one starts it, clicking the
+/// active one ends it.
+#[derive(Clone, Debug)]
+pub struct Focus {
+    holder: WeakRef<Box>,
+}
+impl Focus {
+    pub fn set_state(&self, state: &FocusState) {
+        let Some(holder) = self.holder.upgrade() else {
+            return;
+        };
+        while let Some(child) = holder.first_child() {
+            holder.remove(&child);
+        }
+        for profile in &state.profiles {
+            let active = state.active.as_ref() == Some(profile);
+            let button = Button::builder()
+                .label(profile.as_str())
+                .css_classes(["focus-profile"])
+                .build();
+            if active {
+                button.add_css_class("active");
+            }
+            let request = Request::SetFocus((!active).then(|| profile.clone()));
+            button.connect_clicked(move |_| {
+                let _result = DAEMON_TX.get().map(|d| d.send(request.clone()));
+            });
+            holder.append(&button);
+        }
+        if state.profiles.is_empty() {
+            let empty = Label::builder()
+                .label(tr("No focus profiles"))
+                .css_classes(["focus-empty"])
+                .build();
+            holder.append(&empty);
+        }
+    }
+}
+
+pub struct FocusBuilder {
+    ui: WidgetOption<Box>,
+}
+impl FocusBuilder {
+    pub fn new(specs: &WidgetSpec) -> Self {
+        let base = specs.base();
+
+        let holder = Box::builder()
+            .css_classes(["widget", "focus"])
+            .spacing(6)
+            .valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .build();
+        if let Some(id) = &base.id {
+            holder.set_widget_name(id);
+        }
+        if let Some(class) = &base.class {
+            holder.add_css_class(class);
+        }
+
+        // The daemon answers with the profiles and the active one
+        let _result = DAEMON_TX.get().map(|d| d.send(Request::Focus));
+
+        Self {
+            ui: WidgetOption::Owned(holder),
+        }
+    }
+    pub fn for_box(mut self, container: &Box) -> Self {
+        if let Some(widget) = self.ui.take() {
+            container.append(&widget);
+        }
+        self
+    }
+    pub fn build(self) -> Focus {
+        Focus {
+            holder: self.ui.downgrade(),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use gtk4::{
+    Box, Image, Label, Switch,
+    glib::{self, WeakRef, object::ObjectExt},
+    prelude::{BoxExt, WidgetExt},
+};
+use suite_223b::protocol::{HomeAssistantEntity, Request};
+
+use crate::{
+    DAEMON_TX,
+    config::WidgetSpec,
+    ui::widgets::utils::{WidgetOption, locale::tr},
+};
+
+/// Home Assistant entities picked in the widget config. Lights and switches get a switch,
+/// sensors show their value.
+#[derive(Clone, Debug)]
+pub struct HomeAssistant {
+    holder: WeakRef<Box>,
+    /// Only these entities in this order, the lights and switches when empty
+    entities: Rc<Vec<String>>,
+    rows: Rc<RefCell<HashMap<String, EntityRow>>>,
+}
+impl HomeAssistant {
+    /// Rebuilds the rows from every entity of the instance
+    pub fn set_entities(&self, entities: &[HomeAssistantEntity]) {
+        let Some(holder) = self.holder.upgrade() else {
+            return;
+        };
+        while let Some(child) = holder.first_child() {
+            holder.remove(&child);
+        }
+        let mut rows = self.rows.borrow_mut();
+        rows.clear();
+
+        let shown: Vec<&HomeAssistantEntity> = if self.entities.is_empty() {
+            entities.iter().filter(|e| e.toggleable()).collect()
+        } else {
+            self.entities
+                .iter()
+                .filter_map(|id| entities.iter().find(|e| e.id == *id))
+                .collect()
+        };
+        for entity in shown {
+            let (widget, row) = EntityRow::new(entity);
+            holder.append(&widget);
+            rows.insert(entity.id.clone(), row);
+        }
+    }
+    pub fn set_entity(&self, entity: &HomeAssistantEntity) {
+        if let Some(row) = self.rows.borrow().get(&entity.id) {
+            row.update(entity);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EntityRow {
+    row: WeakRef<Box>,
+    name: WeakRef<Label>,
+    /// Sensors and anything else that can't be switched
+    value: WeakRef<Label>,
+    switch: WeakRef<Switch>,
+}
+impl EntityRow {
+    fn new(entity: &HomeAssistantEntity) -> (Box, Self) {
+        let row = Box::builder()
+            .css_classes(["homeassistant-entity", entity.domain()])
+            .spacing(8)
+            .tooltip_text(entity.id.as_str())
+            .build();
+        let icon = Image::from_icon_name(domain_icon(entity.domain()));
+        let name = Label::builder()
+            .css_classes(["homeassistant-name"])
+            .xalign(0.0)
+            .hexpand(true)
+            .ellipsize(gtk4::pango::EllipsizeMode::End)
+            .build();
+        let value = Label::builder()
+            .css_classes(["homeassistant-value"])
+            .visible(!entity.toggleable())
+            .build();
+        let switch = Switch::builder()
+            .valign(gtk4::Align::Center)
+            .visible(entity.toggleable())
+            .build();
+
+        let id = entity.id.clone();
+        // Stays in between until Home Assistant reports the new state
+        switch.connect_state_set(move |switch, on| {
+            if on == switch.state() {
+                return glib::Propagation::Proceed;
+            }
+            let _result = DAEMON_TX.get().map(|d| {
+                d.send(Request::SetHomeAssistantEntity {
+                    entity: id.clone(),
+                    on,
+                })
+            });
+            glib::Propagation::Stop
+        });
+
+        row.append(&icon);
+        row.append(&name);
+        row.append(&value);
+        row.append(&switch);
+        let entity_row = Self {
+            row: row.downgrade(),
+            name: name.downgrade(),
+            value: value.downgrade(),
+            switch: switch.downgrade(),
+        };
+        entity_row.update(entity);
+        (row, entity_row)
+    }
+
+    fn update(&self, entity: &HomeAssistantEntity) {
+        let (Some(row), Some(name), Some(value), Some(switch)) = (
+            self.row.upgrade(),
+            self.name.upgrade(),
+            self.value.upgrade(),
+            self.switch.upgrade(),
+        ) else {
+            return;
+        };
+        name.set_label(&entity.name);
+        value.set_label(&value_text(entity));
+        // State first, so the handler doesn't take this for the user flipping it
+        switch.set_state(entity.is_on());
+        switch.set_active(entity.is_on());
+        switch.set_sensitive(entity.is_available());
+        if entity.is_available() {
+            row.remove_css_class("unavailable");
+        } else {
+            row.add_css_class("unavailable");
+        }
+    }
+}
+
+/// `21.5 °C`, or the state as Home Assistant spells it
+fn value_text(entity: &HomeAssistantEntity) -> String {
+    if !entity.is_available() {
+        return tr("Unavailable").to_string();
+    }
+    match &entity.unit {
+        Some(unit) => format!("{} {}", entity.state, unit),
+        None => entity.state.clone(),
+    }
+}
+
+fn domain_icon(domain: &str) -> &'static str {
+    match domain {
+        "light" => "display-brightness-symbolic",
+        "switch" | "input_boolean" | "automation" => "system-shutdown-symbolic",
+        "fan" => "weather-windy-symbolic",
+        "sensor" | "binary_sensor" => "utilities-system-monitor-symbolic",
+        _ => "go-home-symbolic",
+    }
+}
+
+pub struct HomeAssistantBuilder {
+    holder: WeakRef<Box>,
+    ui: WidgetOption<Box>,
+    entities: Vec<String>,
+}
+impl HomeAssistantBuilder {
+    pub fn new(specs: &WidgetSpec) -> Self {
+        let base = specs.base();
+        let entities = match specs {
+            WidgetSpec::HomeAssistant { entities, .. } => entities.clone(),
+            _ => Vec::new(),
+        };
+
+        let holder = Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .css_classes(["widget", "homeassistant"])
+            .spacing(4)
+            .valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Fill))
+            .build();
+        if let Some(id) = &base.id {
+            holder.set_widget_name(id);
+        }
+        if let Some(class) = &base.class {
+            holder.add_css_class(class);
+        }
+
+        // The daemon answers with the entities it knows
+        let _result = DAEMON_TX.get().map(|d| d.send(Request::HomeAssistant));
+
+        Self {
+            holder: holder.downgrade(),
+            ui: WidgetOption::Owned(holder),
+            entities,
+        }
+    }
+    pub fn for_box(mut self, container: &Box) -> Self {
+        if let Some(widget) = self.ui.take() {
+            container.append(&widget);
+        }
+        self
+    }
+    pub fn build(self) -> HomeAssistant {
+        HomeAssistant {
+            holder: self.holder,
+            entities: Rc::new(self.entities),
+            rows: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_text() {
+        let mut entity = HomeAssistantEntity {
+            id: "sensor.outside".into(),
+            name: "Outside".into(),
+            state: "12.5".into(),
+            unit: Some("°C".into()),
+        };
+        assert_eq!(value_text(&entity), "12.5 °C");
+        entity.unit = None;
+        assert_eq!(value_text(&entity), "12.5");
+        entity.state = "unavailable".into();
+        assert_eq!(value_text(&entity), tr("Unavailable"));
+    }
+}
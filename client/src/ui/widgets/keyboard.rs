@@ -0,0 +1,70 @@
+use gtk4::{
+    Box, GestureClick, Label,
+    glib::{WeakRef, object::ObjectExt},
+    prelude::{BoxExt, WidgetExt},
+};
+use suite_223b::protocol::Request;
+
+use crate::{DAEMON_TX, config::WidgetSpec, ui::widgets::utils::WidgetOption};
+
+/// Short code of the active keyboard layout, e.g. `DE`. Clicking switches to the next layout.
+#[derive(Clone, Debug)]
+pub struct KeyboardLayout {
+    label: WeakRef<Label>,
+}
+impl KeyboardLayout {
+    pub fn set_layout(&self, name: &str, short: &str) {
+        if let Some(label) = self.label.upgrade() {
+            label.set_label(short);
+            label.set_tooltip_text(Some(name));
+        }
+    }
+}
+
+pub struct KeyboardLayoutBuilder {
+    ui: WidgetOption<Box>,
+    label: WeakRef<Label>,
+}
+impl KeyboardLayoutBuilder {
+    pub fn new(specs: &WidgetSpec) -> Self {
+        let base = specs.base();
+
+        let holder = Box::builder()
+            .css_classes(["keyboard-layout"])
+            .valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .build();
+        if let Some(id) = &base.id {
+            holder.set_widget_name(id);
+        }
+        if let Some(class) = &base.class {
+            holder.add_css_class(class);
+        }
+
+        let label = Label::builder().label("--").build();
+        holder.append(&label);
+
+        let click = GestureClick::new();
+        click.connect_released(|_, _, _, _| {
+            let _result = DAEMON_TX.get().map(|d| d.send(Request::NextKeyboardLayout));
+        });
+        holder.add_controller(click);
+
+        // The daemon answers with the current layout
+        let _result = DAEMON_TX.get().map(|d| d.send(Request::KeyboardLayout));
+
+        Self {
+            label: label.downgrade(),
+            ui: WidgetOption::Owned(holder),
+        }
+    }
+    pub fn for_box(mut self, container: &Box) -> Self {
+        if let Some(widget) = self.ui.take() {
+            container.append(&widget);
+        }
+        self
+    }
+    pub fn build(self) -> KeyboardLayout {
+        KeyboardLayout { label: self.label }
+    }
+}
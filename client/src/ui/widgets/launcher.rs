@@ -0,0 +1,517 @@
+use std::{cell::RefCell, collections::HashMap, fs, path::PathBuf, rc::Rc};
+
+use gtk4::{
+    Box, EventControllerKey, Image, Label, ListBox, PropagationPhase, SearchEntry,
+    gdk::Key,
+    gio::{self, prelude::AppInfoExt},
+    glib::{
+        Propagation, WeakRef,
+        object::{Cast, CastNone, ObjectExt},
+    },
+    prelude::{BoxExt, EditableExt, EventControllerExt, GtkWindowExt, ListBoxRowExt, WidgetExt},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use suite_223b::{
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::get_data_dir,
+    },
+    watson_err,
+};
+
+use crate::{
+    config::WidgetSpec,
+    startup,
+    ui::widgets::utils::{WidgetOption, locale::tr},
+};
+
+/// A user defined entry, e.g. `{ "name": "Lock", "exec": "loginctl lock-session" }`
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct LauncherCommand {
+    pub name: String,
+    pub exec: String,
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct LauncherConfig {
+    commands: Vec<LauncherCommand>,
+    recent_files: bool,
+    max_results: usize,
+}
+impl From<&WidgetSpec> for LauncherConfig {
+    fn from(value: &WidgetSpec) -> Self {
+        match value {
+            WidgetSpec::Launcher {
+                commands,
+                recent_files,
+                max_results,
+                ..
+            } => Self {
+                commands: commands.clone(),
+                recent_files: *recent_files,
+                max_results: *max_results,
+            },
+            _ => Self {
+                commands: Vec::new(),
+                recent_files: true,
+                max_results: 8,
+            },
+        }
+    }
+}
+
+enum Target {
+    App(gio::AppInfo),
+    Uri(String),
+    Command(LauncherCommand),
+}
+
+struct Candidate {
+    /// Stable identifier used for the launch history
+    key: String,
+    name: String,
+    keywords: Vec<String>,
+    detail: Option<String>,
+    icon: Option<gio::Icon>,
+    icon_name: Option<String>,
+    target: Target,
+}
+impl Candidate {
+    /// Applications from the freedesktop `.desktop` entries on `$XDG_DATA_DIRS`
+    fn apps() -> impl Iterator<Item = Self> {
+        gio::AppInfo::all()
+            .into_iter()
+            .filter(|app| app.should_show())
+            .map(|app| {
+                let keywords = app
+                    .downcast_ref::<gio::DesktopAppInfo>()
+                    .map(|d| d.keywords().iter().map(|k| k.to_string()).collect())
+                    .unwrap_or_default();
+                Self {
+                    key: app
+                        .id()
+                        .map(|id| id.to_string())
+                        .unwrap_or_else(|| app.name().to_string()),
+                    name: app.display_name().to_string(),
+                    keywords,
+                    detail: app.description().map(|d| d.to_string()),
+                    icon: app.icon(),
+                    icon_name: None,
+                    target: Target::App(app),
+                }
+            })
+    }
+
+    fn recent_files() -> impl Iterator<Item = Self> {
+        gtk4::RecentManager::default()
+            .items()
+            .into_iter()
+            .filter(|item| item.exists())
+            .map(|item| {
+                let uri = item.uri().to_string();
+                Self {
+                    key: uri.clone(),
+                    name: item.display_name().to_string(),
+                    keywords: Vec::new(),
+                    detail: item.uri_display().map(|d| d.to_string()),
+                    icon: item.gicon(),
+                    icon_name: None,
+                    target: Target::Uri(uri),
+                }
+            })
+    }
+
+    fn commands(commands: &[LauncherCommand]) -> impl Iterator<Item = Self> {
+        commands.iter().map(|cmd| Self {
+            key: format!("command:{}", cmd.name),
+            name: cmd.name.clone(),
+            keywords: Vec::new(),
+            detail: Some(cmd.exec.clone()),
+            icon: None,
+            icon_name: Some(
+                cmd.icon
+                    .clone()
+                    .unwrap_or_else(|| "utilities-terminal-symbolic".into()),
+            ),
+            target: Target::Command(cmd.clone()),
+        })
+    }
+
+    /// Best match of `query` against the name and, with a penalty, the keywords
+    fn score(&self, query: &str) -> Option<i64> {
+        let keywords = self
+            .keywords
+            .iter()
+            .filter_map(|k| fuzzy_score(query, k))
+            .map(|s| s - 10);
+        fuzzy_score(query, &self.name)
+            .into_iter()
+            .chain(keywords)
+            .max()
+    }
+
+    fn launch(&self, context: &gio::AppLaunchContext) -> Result<(), WatsonError> {
+        let result = match &self.target {
+            Target::App(app) => app.launch(&[], Some(context)),
+            Target::Uri(uri) => gio::AppInfo::launch_default_for_uri(uri, Some(context)),
+            Target::Command(cmd) => gio::AppInfo::create_from_commandline(
+                &cmd.exec,
+                Some(&cmd.name),
+                gio::AppInfoCreateFlags::NONE,
+            )
+            .and_then(|app| app.launch(&[], Some(context))),
+        };
+        result.map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, e.to_string()))
+    }
+}
+
+/// Subsequence match of `query` in `candidate`, rewarding word starts and consecutive
+/// characters. Returns `None` if not every character of the query occurs in order.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let query: Vec<char> = query
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+
+    let mut score = 0i64;
+    let mut matched = 0;
+    let mut last: Option<usize> = None;
+    for (i, c) in candidate.iter().enumerate() {
+        if matched == query.len() {
+            break;
+        }
+        if *c != query[matched] {
+            continue;
+        }
+
+        score += 1;
+        if i == 0 || !candidate[i - 1].is_alphanumeric() {
+            score += 8;
+        }
+        score += match last {
+            Some(l) if l + 1 == i => 5,
+            Some(l) => -((i - l - 1).min(3) as i64),
+            None => -(i.min(5) as i64),
+        };
+        last = Some(i);
+        matched += 1;
+    }
+
+    (matched == query.len()).then_some(score)
+}
+
+/// How often each entry was launched, persisted in `$XDG_DATA_HOME/watson/launcher.json`
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct LaunchHistory {
+    counts: HashMap<String, u32>,
+}
+impl LaunchHistory {
+    fn path() -> Result<PathBuf, WatsonError> {
+        Ok(get_data_dir()?.join("launcher.json"))
+    }
+    fn load() -> Self {
+        Self::path()
+            .ok()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+    fn save(&self) -> Result<(), WatsonError> {
+        let bytes = serde_json::to_vec(self)
+            .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+        fs::write(Self::path()?, bytes)
+            .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))
+    }
+    fn record(&mut self, key: &str) {
+        *self.counts.entry(key.to_string()).or_default() += 1;
+        if let Err(e) = self.save() {
+            eprintln!("{:?}", e);
+        }
+    }
+    fn bonus(&self, key: &str) -> i64 {
+        self.counts
+            .get(key)
+            .map(|c| ((*c as f64).ln_1p() * 12.0) as i64)
+            .unwrap_or(0)
+    }
+}
+
+struct LauncherState {
+    config: LauncherConfig,
+    candidates: Vec<Candidate>,
+    history: LaunchHistory,
+    /// Indices into `candidates`, in the order they are listed
+    shown: Vec<usize>,
+}
+impl LauncherState {
+    /// Only the configured commands, see `load_candidates`
+    fn new(config: LauncherConfig) -> Self {
+        Self {
+            candidates: Candidate::commands(&config.commands).collect(),
+            config,
+            history: LaunchHistory::load(),
+            shown: Vec::new(),
+        }
+    }
+
+    /// Adds the applications and recent files, scanning them is slow
+    fn load_candidates(&mut self) {
+        self.candidates.extend(Candidate::apps());
+        if self.config.recent_files {
+            self.candidates.extend(Candidate::recent_files());
+        }
+    }
+
+    /// Ranks every candidate against `query`. An empty query lists the most used entries.
+    fn rank(&mut self, query: &str) {
+        let mut ranked: Vec<(i64, usize)> = self
+            .candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| {
+                let bonus = self.history.bonus(&c.key);
+                if query.trim().is_empty() {
+                    (bonus > 0).then_some((bonus, i))
+                } else {
+                    c.score(query).map(|s| (s + bonus, i))
+                }
+            })
+            .collect();
+
+        ranked.sort_by(|(a, ai), (b, bi)| {
+            b.cmp(a)
+                .then_with(|| self.candidates[*ai].name.cmp(&self.candidates[*bi].name))
+        });
+        self.shown = ranked
+            .into_iter()
+            .take(self.config.max_results)
+            .map(|(_, i)| i)
+            .collect();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Launcher {
+    entry: WeakRef<SearchEntry>,
+}
+impl Launcher {
+    pub fn focus(&self) {
+        if let Some(entry) = self.entry.upgrade() {
+            entry.grab_focus();
+        }
+    }
+}
+
+pub struct LauncherBuilder {
+    ui: WidgetOption<Box>,
+    entry: WeakRef<SearchEntry>,
+}
+impl LauncherBuilder {
+    pub fn new(specs: &WidgetSpec) -> Self {
+        let base = specs.base();
+        let state = Rc::new(RefCell::new(LauncherState::new(LauncherConfig::from(
+            specs,
+        ))));
+
+        let holder = Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .css_classes(["widget", "launcher"])
+            .hexpand(true)
+            .valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Fill))
+            .build();
+        if let Some(id) = &base.id {
+            holder.set_widget_name(id);
+        }
+        if let Some(class) = &base.class {
+            holder.add_css_class(class);
+        }
+
+        let entry = SearchEntry::builder()
+            .css_classes(["launcher-entry"])
+            .placeholder_text(tr("Search"))
+            .hexpand(true)
+            .build();
+        let list = ListBox::builder()
+            .css_classes(["launcher-results"])
+            .selection_mode(gtk4::SelectionMode::Browse)
+            .build();
+        holder.append(&entry);
+        holder.append(&list);
+
+        entry.connect_search_changed({
+            let state = Rc::clone(&state);
+            let list = list.downgrade();
+            move |entry| {
+                if let Some(list) = list.upgrade() {
+                    state.borrow_mut().rank(&entry.text());
+                    populate(&list, &state.borrow());
+                }
+            }
+        });
+
+        entry.connect_activate({
+            let state = Rc::clone(&state);
+            let list = list.downgrade();
+            move |entry| {
+                let Some(list) = list.upgrade() else { return };
+                let index = list
+                    .selected_row()
+                    .or_else(|| list.row_at_index(0))
+                    .map(|row| row.index());
+                if let Some(index) = index {
+                    launch(entry, &state, index as usize);
+                }
+            }
+        });
+
+        list.connect_row_activated({
+            let state = Rc::clone(&state);
+            let entry = entry.downgrade();
+            move |_, row| {
+                if let Some(entry) = entry.upgrade() {
+                    launch(&entry, &state, row.index() as usize);
+                }
+            }
+        });
+
+        // Keyboard first: the focus stays in the entry while arrows move the selection
+        let controller = EventControllerKey::new();
+        controller.set_propagation_phase(PropagationPhase::Capture);
+        controller.connect_key_pressed({
+            let list = list.downgrade();
+            let entry = entry.downgrade();
+            move |_, key, _, _| {
+                let (Some(list), Some(entry)) = (list.upgrade(), entry.upgrade()) else {
+                    return Propagation::Proceed;
+                };
+                let step = match key {
+                    Key::Down | Key::Tab => 1,
+                    Key::Up | Key::ISO_Left_Tab => -1,
+                    Key::Escape if !entry.text().is_empty() => {
+                        entry.set_text("");
+                        return Propagation::Stop;
+                    }
+                    _ => return Propagation::Proceed,
+                };
+                let current = list.selected_row().map(|r| r.index()).unwrap_or(-1);
+                if let Some(row) = list.row_at_index((current + step).max(0)) {
+                    list.select_row(Some(&row));
+                }
+                Propagation::Stop
+            }
+        });
+        entry.add_controller(controller);
+
+        state.borrow_mut().rank("");
+        populate(&list, &state.borrow());
+
+        startup::defer("launcher apps", {
+            let list = list.downgrade();
+            let entry = entry.downgrade();
+            move || {
+                let (Some(list), Some(entry)) = (list.upgrade(), entry.upgrade()) else {
+                    return;
+                };
+                let mut state = state.borrow_mut();
+                state.load_candidates();
+                state.rank(&entry.text());
+                populate(&list, &state);
+            }
+        });
+
+        Self {
+            ui: WidgetOption::Owned(holder),
+            entry: entry.downgrade(),
+        }
+    }
+    pub fn for_box(mut self, container: &Box) -> Self {
+        if let Some(widget) = self.ui.take() {
+            container.append(&widget);
+        }
+        self
+    }
+    pub fn build(self) -> Launcher {
+        Launcher { entry: self.entry }
+    }
+}
+
+fn populate(list: &ListBox, state: &LauncherState) {
+    list.remove_all();
+    for &i in &state.shown {
+        let candidate = &state.candidates[i];
+
+        let row = Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .css_classes(["launcher-row"])
+            .spacing(10)
+            .build();
+
+        let icon = match (&candidate.icon, &candidate.icon_name) {
+            (Some(gicon), _) => Image::from_gicon(gicon),
+            (None, Some(name)) => Image::from_icon_name(name),
+            (None, None) => Image::from_icon_name("application-x-executable"),
+        };
+        icon.set_pixel_size(24);
+        row.append(&icon);
+
+        let labels = Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .valign(gtk4::Align::Center)
+            .build();
+        labels.append(
+            &Label::builder()
+                .label(&candidate.name)
+                .xalign(0.0)
+                .ellipsize(gtk4::pango::EllipsizeMode::End)
+                .build(),
+        );
+        if let Some(detail) = &candidate.detail {
+            labels.append(
+                &Label::builder()
+                    .label(detail)
+                    .css_classes(["dim-label"])
+                    .xalign(0.0)
+                    .ellipsize(gtk4::pango::EllipsizeMode::End)
+                    .build(),
+            );
+        }
+        row.append(&labels);
+        list.append(&row);
+    }
+
+    if let Some(first) = list.row_at_index(0) {
+        list.select_row(Some(&first));
+    }
+}
+
+fn launch(entry: &SearchEntry, state: &Rc<RefCell<LauncherState>>, index: usize) {
+    let context = entry.display().app_launch_context();
+    let mut state = state.borrow_mut();
+    let Some(&candidate) = state.shown.get(index) else {
+        return;
+    };
+
+    match state.candidates[candidate].launch(context.upcast_ref()) {
+        Ok(_) => {
+            let key = state.candidates[candidate].key.clone();
+            state.history.record(&key);
+            drop(state);
+
+            // Hidden rather than closed, closing the last window would end the client
+            entry.set_text("");
+            if let Some(win) = entry.root().and_downcast::<gtk4::Window>() {
+                win.set_visible(false);
+            }
+        }
+        Err(e) => eprintln!("{:?}", e),
+    }
+}
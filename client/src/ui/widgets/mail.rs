@@ -0,0 +1,131 @@
+use gtk4::{
+    Box, GestureClick, Image, Label, Spinner,
+    glib::{WeakRef, object::ObjectExt},
+    prelude::{BoxExt, WidgetExt},
+};
+use suite_223b::protocol::{DataKind, MailAccount, Request};
+
+use crate::{
+    DAEMON_TX,
+    config::WidgetSpec,
+    ui::widgets::utils::{WidgetOption, locale::tr, refresh},
+};
+
+/// Unread mail across the accounts in `mail.json`, the tooltip lists the newest subjects. A
+/// click checks the accounts right away.
+#[derive(Clone, Debug)]
+pub struct MailBadge {
+    holder: WeakRef<Box>,
+    count: WeakRef<Label>,
+    icon: WeakRef<Image>,
+    spinner: WeakRef<Spinner>,
+}
+impl MailBadge {
+    pub fn set_accounts(&self, accounts: &[MailAccount]) {
+        let (Some(holder), Some(count)) = (self.holder.upgrade(), self.count.upgrade()) else {
+            return;
+        };
+        let unread: u32 = accounts.iter().map(|a| a.unread).sum();
+        count.set_label(&unread.to_string());
+        count.set_visible(unread > 0);
+        if unread > 0 {
+            holder.add_css_class("unread");
+        } else {
+            holder.remove_css_class("unread");
+        }
+        holder.set_tooltip_text(Some(&tooltip(accounts)));
+    }
+    /// Swaps the icon for a spinner while the accounts are checked
+    pub fn set_refreshing(&self, refreshing: bool) {
+        let (Some(icon), Some(spinner)) = (self.icon.upgrade(), self.spinner.upgrade()) else {
+            return;
+        };
+        icon.set_visible(!refreshing);
+        spinner.set_visible(refreshing);
+        spinner.set_spinning(refreshing);
+    }
+}
+
+fn tooltip(accounts: &[MailAccount]) -> String {
+    if accounts.iter().all(|a| a.unread == 0) {
+        return tr("No unread mail").to_string();
+    }
+    accounts
+        .iter()
+        .filter(|a| a.unread > 0)
+        .map(|a| {
+            let mut lines = vec![format!("{} ({})", a.label, a.unread)];
+            lines.extend(
+                a.latest
+                    .iter()
+                    .map(|m| format!("  {}: {}", m.from, m.subject)),
+            );
+            lines.join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub struct MailBadgeBuilder {
+    ui: WidgetOption<Box>,
+    count: WeakRef<Label>,
+    icon: WeakRef<Image>,
+    spinner: WeakRef<Spinner>,
+}
+impl MailBadgeBuilder {
+    pub fn new(specs: &WidgetSpec) -> Self {
+        let base = specs.base();
+
+        let holder = Box::builder()
+            .css_classes(["mail"])
+            .spacing(4)
+            .tooltip_text(tr("No unread mail"))
+            .valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .build();
+        if let Some(id) = &base.id {
+            holder.set_widget_name(id);
+        }
+        if let Some(class) = &base.class {
+            holder.add_css_class(class);
+        }
+
+        let icon = Image::from_icon_name("mail-unread-symbolic");
+        let count = Label::builder()
+            .css_classes(["mail-count"])
+            .visible(false)
+            .build();
+        let spinner = Spinner::builder().visible(false).build();
+        holder.append(&icon);
+        holder.append(&spinner);
+        holder.append(&count);
+
+        let click = GestureClick::builder().button(1).build();
+        click.connect_released(|_, _, _, _| refresh::request(DataKind::Mail));
+        holder.add_controller(click);
+
+        // The daemon answers with the last known counts
+        let _result = DAEMON_TX.get().map(|d| d.send(Request::Mail));
+
+        Self {
+            count: count.downgrade(),
+            icon: icon.downgrade(),
+            spinner: spinner.downgrade(),
+            ui: WidgetOption::Owned(holder),
+        }
+    }
+    pub fn for_box(mut self, container: &Box) -> Self {
+        if let Some(widget) = self.ui.take() {
+            container.append(&widget);
+        }
+        self
+    }
+    pub fn build(self) -> MailBadge {
+        MailBadge {
+            holder: self.ui.downgrade(),
+            count: self.count,
+            icon: self.icon,
+            spinner: self.spinner,
+        }
+    }
+}
@@ -1,28 +1,59 @@
 mod battery;
+mod bluetooth;
 mod button;
 pub mod calendar;
 mod clock;
+mod color_picker;
+mod focus;
+mod home_assistant;
+mod keyboard;
+mod launcher;
+mod mail;
+mod network;
+mod notes;
 mod notifications;
+mod privacy;
+mod recording;
+mod screen_time;
 mod slider;
+mod ticker;
 mod utils;
 
 use std::{cell::RefCell, rc::Rc, sync::Arc};
 
 pub use battery::{Battery, BatteryBuilder};
+pub use bluetooth::BluetoothPopover;
 pub use button::{Button, ButtonBuilder};
 pub use calendar::Calendar;
-pub use clock::{Clock, HandStyle};
+pub use clock::{Clock, ClockComplication, HandStyle, SecondHand, SecondaryStyle};
+pub use color_picker::{ColorFormat, ColorPicker};
+pub use focus::{Focus, FocusBuilder};
+pub use home_assistant::{HomeAssistant, HomeAssistantBuilder};
+pub use keyboard::{KeyboardLayout, KeyboardLayoutBuilder};
+pub use launcher::{Launcher, LauncherBuilder, LauncherCommand};
+pub use mail::{MailBadge, MailBadgeBuilder};
+pub use network::NetworkPopover;
+pub use notes::{Notes, NotesBuilder};
+pub use utils::animation::EaseFunction;
 pub use utils::backend_functions::*;
+pub use utils::render::{Hsl, Rgba};
+pub use utils::state::StateClass;
+pub use utils::text::TextLayout;
+pub use utils::{attach_popover, is_suspended, locale, pending, power, set_suspended};
 
 use gtk4::{
-    Align, AspectFrame, Box, DrawingArea, Separator,
+    Align, AspectFrame, Box, Separator,
     glib::{WeakRef, object::ObjectExt},
     prelude::{BoxExt, WidgetExt},
 };
-pub use notifications::{NotificationCentre, NotificationCentreBuilder};
+pub use notifications::{NOTIFICATION_PAGE, NotificationCentre, NotificationCentreBuilder};
+pub use privacy::{PrivacyIndicator, PrivacyIndicatorBuilder};
+pub use recording::{RecordingIndicator, RecordingIndicatorBuilder};
+pub use screen_time::{ScreenTime, ScreenTimeBuilder};
 pub use slider::{Slider, SliderBuilder, SliderRange};
+pub use ticker::{Ticker, TickerBuilder};
 
-use crate::{WatsonState, config::WidgetSpec};
+use crate::{WatsonState, config::WidgetSpec, ui::g_templates::snapshot_area::SnapshotArea};
 
 pub fn create_widgets(
     viewport: &Box,
@@ -30,6 +61,11 @@ pub fn create_widgets(
     state: Rc<RefCell<WatsonState>>,
     in_holder: bool,
 ) {
+    // Quick settings pass theirs on to the column they turn into
+    let base = match &spec {
+        WidgetSpec::QuickSettings { .. } => None,
+        _ => Some(spec.base().clone()),
+    };
     match spec {
         WidgetSpec::Battery { .. } => {
             let bat = BatteryBuilder::new(spec, in_holder)
@@ -58,6 +94,53 @@ pub fn create_widgets(
 
             viewport.append(&clock);
         }
+        WidgetSpec::Keyboard { .. } => {
+            let keyboard = KeyboardLayoutBuilder::new(&spec).for_box(&viewport).build();
+            state
+                .borrow_mut()
+                .widgets
+                .push(WatsonWidget::KeyboardLayout(keyboard));
+        }
+        WidgetSpec::Focus { .. } => {
+            let focus = FocusBuilder::new(&spec).for_box(&viewport).build();
+            state.borrow_mut().widgets.push(WatsonWidget::Focus(focus));
+        }
+        WidgetSpec::Launcher { .. } => {
+            let launcher = LauncherBuilder::new(&spec).for_box(&viewport).build();
+            state
+                .borrow_mut()
+                .widgets
+                .push(WatsonWidget::Launcher(launcher));
+        }
+        WidgetSpec::Mail { .. } => {
+            let mail = MailBadgeBuilder::new(&spec).for_box(&viewport).build();
+            state.borrow_mut().widgets.push(WatsonWidget::Mail(mail));
+        }
+        WidgetSpec::ScreenTime { .. } => {
+            let screen_time = ScreenTimeBuilder::new(&spec).for_box(&viewport).build();
+            state
+                .borrow_mut()
+                .widgets
+                .push(WatsonWidget::ScreenTime(screen_time));
+        }
+        WidgetSpec::HomeAssistant { .. } => {
+            let home_assistant = HomeAssistantBuilder::new(&spec).for_box(&viewport).build();
+            state
+                .borrow_mut()
+                .widgets
+                .push(WatsonWidget::HomeAssistant(home_assistant));
+        }
+        WidgetSpec::Ticker { .. } => {
+            let ticker = TickerBuilder::new(&spec).for_box(&viewport).build();
+            state
+                .borrow_mut()
+                .widgets
+                .push(WatsonWidget::Ticker(ticker));
+        }
+        WidgetSpec::Notes { .. } => {
+            let notes = NotesBuilder::new(&spec).for_box(&viewport).build();
+            state.borrow_mut().widgets.push(WatsonWidget::Notes(notes));
+        }
         WidgetSpec::Notifications { .. } => {
             let notification_centre = NotificationCentreBuilder::new(&spec)
                 .for_box(&viewport)
@@ -68,6 +151,29 @@ pub fn create_widgets(
                 .widgets
                 .push(WatsonWidget::NotificationCentre(notification_centre));
         }
+        WidgetSpec::Recording { .. } => {
+            let recording = state
+                .borrow()
+                .system_state
+                .recording
+                .load(std::sync::atomic::Ordering::Relaxed);
+            let indicator = RecordingIndicatorBuilder::new(&spec, recording)
+                .for_box(&viewport)
+                .build();
+            state
+                .borrow_mut()
+                .widgets
+                .push(WatsonWidget::RecordingIndicator(indicator));
+        }
+        WidgetSpec::Privacy { .. } => {
+            let indicator = PrivacyIndicatorBuilder::new(&spec)
+                .for_box(&viewport)
+                .build();
+            state
+                .borrow_mut()
+                .widgets
+                .push(WatsonWidget::PrivacyIndicator(indicator));
+        }
         WidgetSpec::Button { .. } => {
             let button = {
                 ButtonBuilder::new(spec, Arc::clone(&state.borrow().system_state), in_holder)
@@ -90,6 +196,11 @@ pub fn create_widgets(
                 .widgets
                 .push(WatsonWidget::Slider(slider));
         }
+        WidgetSpec::QuickSettings { .. } => {
+            if let Some(panel) = spec.into_quick_settings() {
+                create_widgets(viewport, panel, state, in_holder);
+            }
+        }
         WidgetSpec::Column {
             base,
             spacing,
@@ -124,6 +235,7 @@ pub fn create_widgets(
                 viewport.append(&col);
             }
 
+            state.borrow_mut().containers.push(col.downgrade());
             for child in children {
                 create_widgets(&col, child, state.clone(), true);
             }
@@ -162,6 +274,7 @@ pub fn create_widgets(
                 viewport.append(&row);
             }
 
+            state.borrow_mut().containers.push(row.downgrade());
             for child in children {
                 create_widgets(&row, child, state.clone(), true);
             }
@@ -199,6 +312,20 @@ pub fn create_widgets(
             viewport.append(&separator);
         }
     }
+
+    let (Some(base), Some(widget)) = (base, viewport.last_child()) else {
+        return;
+    };
+    base.apply_geometry(&widget);
+    if base.click_through {
+        state.borrow_mut().passthrough.push(widget.downgrade());
+    }
+    if let Some(targets) = base.monitor {
+        state
+            .borrow_mut()
+            .placed
+            .push((widget.downgrade(), targets));
+    }
 }
 
 macro_rules! define_widgets {
@@ -232,8 +359,18 @@ macro_rules! define_widgets {
 define_widgets! {
     Battery(Battery),
     Calendar(Calendar),
-    Clock(WeakRef<DrawingArea>),
+    Clock(WeakRef<SnapshotArea>),
+    Focus(Focus),
+    HomeAssistant(HomeAssistant),
+    KeyboardLayout(KeyboardLayout),
+    Launcher(Launcher),
+    Mail(MailBadge),
     NotificationCentre(NotificationCentre),
+    Notes(Notes),
+    PrivacyIndicator(PrivacyIndicator),
+    RecordingIndicator(RecordingIndicator),
     Button(Button),
     Slider(Slider),
+    Ticker(Ticker),
+    ScreenTime(ScreenTime),
 }
@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use gtk4::{
+    Box, Button, GestureClick, Grid, Label, Popover, Widget, gio,
+    glib::object::{Cast, IsA, ObjectExt},
+    prelude::{BoxExt, ButtonExt, GestureExt, GridExt, PopoverExt, WidgetExt},
+};
+use suite_223b::protocol::{AtomicSystemState, Connectivity};
+
+use crate::ui::widgets::utils::{attach_popover, locale::tr};
+
+/// Details of the primary connection, opened with a right click on the wifi button
+pub struct NetworkPopover;
+impl NetworkPopover {
+    pub fn attach(target: &impl IsA<Widget>, system_state: Arc<AtomicSystemState>) {
+        let target = target.upcast_ref::<Widget>();
+
+        let popover = Popover::builder()
+            .css_classes(["network-popover"])
+            .has_arrow(true)
+            .build();
+        attach_popover(&popover, target);
+
+        let click = GestureClick::builder().button(3).build();
+        click.connect_pressed({
+            let popover = popover.downgrade();
+            move |gesture, _, _, _| {
+                gesture.set_state(gtk4::EventSequenceState::Claimed);
+                let Some(popover) = popover.upgrade() else {
+                    return;
+                };
+                let connectivity = system_state
+                    .connectivity
+                    .read()
+                    .map(|c| c.clone())
+                    .unwrap_or_default();
+                popover.set_child(Some(&Self::content(&connectivity)));
+                popover.popup();
+            }
+        });
+        target.add_controller(click);
+    }
+
+    fn content(connectivity: &Connectivity) -> Box {
+        let holder = Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(8)
+            .build();
+
+        let Some(info) = &connectivity.primary else {
+            holder.append(
+                &Label::builder()
+                    .label(tr("Disconnected"))
+                    .css_classes(["network-popover-title"])
+                    .build(),
+            );
+            return holder;
+        };
+
+        holder.append(
+            &Label::builder()
+                .label(info.ssid.as_deref().unwrap_or(&info.id))
+                .css_classes(["network-popover-title"])
+                .xalign(0.0)
+                .build(),
+        );
+
+        let grid = Grid::builder().column_spacing(12).row_spacing(4).build();
+        let mut rows: Vec<(&str, String)> = Vec::new();
+        if info.speed > 0 {
+            rows.push((tr("Speed"), format!("{} Mb/s", info.speed)));
+        }
+        rows.extend(info.ipv4.iter().map(|a| ("IPv4", a.clone())));
+        rows.extend(info.ipv6.iter().map(|a| ("IPv6", a.clone())));
+        rows.extend(connectivity.wired.iter().map(|d| {
+            let link = match d.carrier {
+                true if d.speed > 0 => format!("{} Mb/s", d.speed),
+                true => tr("connected").to_string(),
+                false => tr("unplugged").to_string(),
+            };
+            (d.interface.as_str(), link)
+        }));
+
+        for (i, (key, value)) in rows.into_iter().enumerate() {
+            let key = Label::builder()
+                .label(key)
+                .css_classes(["network-popover-key"])
+                .xalign(0.0)
+                .build();
+            let value = Label::builder()
+                .label(value)
+                .selectable(true)
+                .xalign(0.0)
+                .build();
+            grid.attach(&key, 0, i as i32, 1, 1);
+            grid.attach(&value, 1, i as i32, 1, 1);
+        }
+        holder.append(&grid);
+
+        if let Some(url) = connectivity.portal.clone() {
+            holder.append(
+                &Label::builder()
+                    .label(tr("Sign-in required"))
+                    .css_classes(["network-popover-warning"])
+                    .xalign(0.0)
+                    .build(),
+            );
+            let open = Button::with_label(tr("Open portal"));
+            open.connect_clicked(move |_| {
+                let context = None::<&gio::AppLaunchContext>;
+                if let Err(e) = gio::AppInfo::launch_default_for_uri(&url, context) {
+                    eprintln!("{:?}", e);
+                }
+            });
+            holder.append(&open);
+        }
+        holder
+    }
+}
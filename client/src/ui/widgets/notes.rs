@@ -0,0 +1,390 @@
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    path::PathBuf,
+    rc::Rc,
+    time::Duration,
+};
+
+use gtk4::{
+    Box, Button, DrawingArea, DropDown, GestureDrag, Overlay, ScrolledWindow, StringList, TextView,
+    ToggleButton,
+    glib::{
+        self, SourceId, WeakRef,
+        object::{Cast, ObjectExt},
+    },
+    prelude::{
+        BoxExt, ButtonExt, DrawingAreaExtManual, GestureDragExt, ListModelExt, TextBufferExt,
+        TextViewExt, ToggleButtonExt, WidgetExt,
+    },
+};
+use serde::{Deserialize, Serialize};
+use suite_223b::{
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::get_data_dir,
+    },
+    watson_err,
+};
+
+use crate::{
+    config::WidgetSpec,
+    ui::widgets::utils::{WidgetOption, locale::tr},
+};
+
+/// Changes are written once typing or drawing pauses for this long
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+type Stroke = Vec<(f64, f64)>;
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct Note {
+    name: String,
+    text: String,
+    /// Ink in widget coordinates
+    strokes: Vec<Stroke>,
+}
+
+/// All notes, persisted in `$XDG_DATA_HOME/watson/notes.json`
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct NoteBook {
+    notes: Vec<Note>,
+}
+impl NoteBook {
+    fn path() -> Result<PathBuf, WatsonError> {
+        Ok(get_data_dir()?.join("notes.json"))
+    }
+    fn load() -> Self {
+        let mut book: Self = Self::path()
+            .ok()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        if book.notes.is_empty() {
+            book.notes.push(Note {
+                name: tr("Notes").to_string(),
+                ..Default::default()
+            });
+        }
+        book
+    }
+    fn save(&self) -> Result<(), WatsonError> {
+        let bytes = serde_json::to_vec(self)
+            .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+        fs::write(Self::path()?, bytes)
+            .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))
+    }
+    /// `Note 2`, `Note 3`, ... whichever is free first
+    fn next_name(&self) -> String {
+        (2..)
+            .map(|i| format!("{} {i}", tr("Note")))
+            .find(|name| self.notes.iter().all(|n| &n.name != name))
+            .unwrap_or_default()
+    }
+}
+
+/// State shared by the signal handlers of one notes widget
+struct NotesInner {
+    book: RefCell<NoteBook>,
+    current: Cell<usize>,
+    pending_save: RefCell<Option<SourceId>>,
+    text: WeakRef<TextView>,
+    ink: WeakRef<DrawingArea>,
+    /// Set while a note is loaded into the text view, so loading doesn't count as an edit
+    loading: Cell<bool>,
+}
+impl NotesInner {
+    fn schedule_save(self: &Rc<Self>) {
+        if let Some(source) = self.pending_save.take() {
+            source.remove();
+        }
+        let inner = Rc::downgrade(self);
+        let source = glib::timeout_add_local_once(SAVE_DELAY, move || {
+            let Some(inner) = inner.upgrade() else {
+                return;
+            };
+            inner.pending_save.replace(None);
+            if let Err(e) = inner.book.borrow().save() {
+                eprintln!("{:?}", e);
+            }
+        });
+        self.pending_save.replace(Some(source));
+    }
+
+    fn show(&self, index: usize) {
+        self.current.set(index);
+        let book = self.book.borrow();
+        let Some(note) = book.notes.get(index) else {
+            return;
+        };
+        if let Some(text) = self.text.upgrade() {
+            self.loading.set(true);
+            text.buffer().set_text(&note.text);
+            self.loading.set(false);
+        }
+        if let Some(ink) = self.ink.upgrade() {
+            ink.queue_draw();
+        }
+    }
+
+    fn with_current(&self, f: impl FnOnce(&mut Note)) {
+        if let Some(note) = self.book.borrow_mut().notes.get_mut(self.current.get()) {
+            f(note);
+        }
+    }
+}
+
+/// Scratchpad with text and ink, shown and hidden with `watson toggle notes`
+#[derive(Clone, Debug)]
+pub struct Notes {
+    holder: WeakRef<Box>,
+}
+impl Notes {
+    /// The outermost widget of the notes
+    pub fn root(&self) -> Option<gtk4::Widget> {
+        self.holder.upgrade().map(|h| h.upcast())
+    }
+}
+
+pub struct NotesBuilder {
+    ui: WidgetOption<Box>,
+}
+impl NotesBuilder {
+    pub fn new(specs: &WidgetSpec) -> Self {
+        let base = specs.base();
+
+        let holder = Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .css_classes(["widget", "notes"])
+            .spacing(6)
+            .valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Fill))
+            .halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Fill))
+            .hexpand(true)
+            .vexpand(true)
+            .build();
+        if let Some(id) = &base.id {
+            holder.set_widget_name(id);
+        }
+        if let Some(class) = &base.class {
+            holder.add_css_class(class);
+        }
+
+        let book = NoteBook::load();
+        let names = StringList::new(
+            &book
+                .notes
+                .iter()
+                .map(|n| n.name.as_str())
+                .collect::<Vec<_>>(),
+        );
+
+        let text = TextView::builder()
+            .css_classes(["notes-text"])
+            .wrap_mode(gtk4::WrapMode::WordChar)
+            .hexpand(true)
+            .vexpand(true)
+            .build();
+        let ink = DrawingArea::builder()
+            .css_classes(["notes-ink"])
+            .can_target(false)
+            .build();
+
+        let inner = Rc::new(NotesInner {
+            book: RefCell::new(book),
+            current: Cell::new(0),
+            pending_save: RefCell::new(None),
+            text: text.downgrade(),
+            ink: ink.downgrade(),
+            loading: Cell::new(false),
+        });
+
+        // Header: note picker, new note, ink mode and clearing the ink
+        let header = Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .css_classes(["notes-header"])
+            .spacing(4)
+            .build();
+        let picker = DropDown::builder().model(&names).hexpand(true).build();
+        picker.connect_selected_notify({
+            let inner = Rc::clone(&inner);
+            move |picker| inner.show(picker.selected() as usize)
+        });
+        let add = Button::builder()
+            .icon_name("list-add-symbolic")
+            .tooltip_text(tr("New note"))
+            .build();
+        add.connect_clicked({
+            let inner = Rc::clone(&inner);
+            let picker = picker.downgrade();
+            move |_| {
+                let name = {
+                    let mut book = inner.book.borrow_mut();
+                    let name = book.next_name();
+                    book.notes.push(Note {
+                        name: name.clone(),
+                        ..Default::default()
+                    });
+                    name
+                };
+                names.append(&name);
+                inner.schedule_save();
+                if let Some(picker) = picker.upgrade() {
+                    picker.set_selected(names.n_items().saturating_sub(1));
+                }
+            }
+        });
+        let pen = ToggleButton::builder()
+            .icon_name("document-edit-symbolic")
+            .tooltip_text(tr("Draw"))
+            .build();
+        pen.connect_toggled({
+            let ink = ink.downgrade();
+            move |pen| {
+                // The text view gets the input back while the pen is away
+                if let Some(ink) = ink.upgrade() {
+                    ink.set_can_target(pen.is_active());
+                }
+            }
+        });
+        let clear = Button::builder()
+            .icon_name("edit-clear-symbolic")
+            .tooltip_text(tr("Clear drawing"))
+            .build();
+        clear.connect_clicked({
+            let inner = Rc::clone(&inner);
+            move |_| {
+                inner.with_current(|note| note.strokes.clear());
+                inner.schedule_save();
+                if let Some(ink) = inner.ink.upgrade() {
+                    ink.queue_draw();
+                }
+            }
+        });
+        header.append(&picker);
+        header.append(&add);
+        header.append(&pen);
+        header.append(&clear);
+
+        text.buffer().connect_changed({
+            let inner = Rc::clone(&inner);
+            move |buffer| {
+                if inner.loading.get() {
+                    return;
+                }
+                let content = buffer
+                    .text(&buffer.start_iter(), &buffer.end_iter(), false)
+                    .to_string();
+                inner.with_current(|note| note.text = content);
+                inner.schedule_save();
+            }
+        });
+
+        ink.set_draw_func({
+            let inner = Rc::downgrade(&inner);
+            move |area, ctx, _, _| {
+                let Some(inner) = inner.upgrade() else {
+                    return;
+                };
+                let book = inner.book.borrow();
+                let Some(note) = book.notes.get(inner.current.get()) else {
+                    return;
+                };
+                let color = area.color();
+                ctx.set_source_rgba(
+                    color.red() as f64,
+                    color.green() as f64,
+                    color.blue() as f64,
+                    color.alpha() as f64,
+                );
+                ctx.set_line_width(2.0);
+                ctx.set_line_cap(gtk4::cairo::LineCap::Round);
+                ctx.set_line_join(gtk4::cairo::LineJoin::Round);
+                for stroke in &note.strokes {
+                    let mut points = stroke.iter();
+                    let Some(&(x, y)) = points.next() else {
+                        continue;
+                    };
+                    ctx.move_to(x, y);
+                    // A single tap still leaves a dot
+                    ctx.line_to(x, y);
+                    for &(x, y) in points {
+                        ctx.line_to(x, y);
+                    }
+                    let _ = ctx.stroke();
+                }
+            }
+        });
+
+        let drag = GestureDrag::new();
+        drag.connect_drag_begin({
+            let inner = Rc::clone(&inner);
+            move |_, x, y| {
+                inner.with_current(|note| note.strokes.push(vec![(x, y)]));
+                if let Some(ink) = inner.ink.upgrade() {
+                    ink.queue_draw();
+                }
+            }
+        });
+        drag.connect_drag_update({
+            let inner = Rc::clone(&inner);
+            move |drag, dx, dy| {
+                let Some((x, y)) = drag.start_point() else {
+                    return;
+                };
+                inner.with_current(|note| {
+                    if let Some(stroke) = note.strokes.last_mut() {
+                        stroke.push((x + dx, y + dy));
+                    }
+                });
+                if let Some(ink) = inner.ink.upgrade() {
+                    ink.queue_draw();
+                }
+            }
+        });
+        drag.connect_drag_end({
+            let inner = Rc::clone(&inner);
+            move |_, _, _| inner.schedule_save()
+        });
+        ink.add_controller(drag);
+
+        let scroll = ScrolledWindow::builder()
+            .child(&text)
+            .hexpand(true)
+            .vexpand(true)
+            .build();
+        let canvas = Overlay::builder()
+            .css_classes(["notes-canvas"])
+            .child(&scroll)
+            .build();
+        canvas.add_overlay(&ink);
+
+        holder.append(&header);
+        holder.append(&canvas);
+        inner.show(0);
+
+        // Nothing typed in the last moments gets lost when the widgets are rebuilt
+        holder.connect_destroy(move |_| {
+            if let Some(source) = inner.pending_save.take() {
+                source.remove();
+                if let Err(e) = inner.book.borrow().save() {
+                    eprintln!("{:?}", e);
+                }
+            }
+        });
+
+        Self {
+            ui: WidgetOption::Owned(holder),
+        }
+    }
+    pub fn for_box(mut self, container: &Box) -> Self {
+        if let Some(widget) = self.ui.take() {
+            container.append(&widget);
+        }
+        self
+    }
+    pub fn build(self) -> Notes {
+        Notes {
+            holder: self.ui.downgrade(),
+        }
+    }
+}
@@ -1,61 +1,130 @@
 use std::rc::Rc;
 
 use gtk4::{
-    Box, ListBox,
-    glib::{WeakRef, object::ObjectExt},
-    prelude::BoxExt,
+    Box, ListView, PolicyType, PositionType, ScrolledWindow,
+    gio::{ListStore, prelude::ListModelExt},
+    glib::{
+        WeakRef,
+        object::{Cast, CastNone, ObjectExt},
+    },
+    prelude::{BoxExt, WidgetExt},
 };
-use suite_223b::notification::Notification;
+use suite_223b::{notification::Notification, protocol::Request};
 
 use crate::{
+    DAEMON_TX,
     config::WidgetSpec,
     ui::{
         g_templates::{
-            notification::NotificationWidget, notification_centre::NotificationCollection,
+            notification_centre::NotificationCollection, notification_obj::NotificationObj,
         },
         widgets::utils::WidgetOption,
     },
 };
 
+/// How many stored notifications are fetched at a time
+pub const NOTIFICATION_PAGE: usize = 50;
+/// The list scrolls beyond this height
+const MAX_HEIGHT: i32 = 480;
+
 #[derive(Clone, Debug)]
 pub struct NotificationCentre {
-    list: WeakRef<ListBox>,
+    collection: WeakRef<NotificationCollection>,
+    store: WeakRef<ListStore>,
 }
 impl NotificationCentre {
+    /// Adds a notification in order, newest first, or updates it in place if one with the same
+    /// id is shown
     pub fn insert(&self, notification: Rc<Notification>) {
-        if let Some(list) = self.list.upgrade() {
-            let widget = NotificationWidget::new(notification);
-            list.append(&widget);
+        if let Some(store) = self.store.upgrade() {
+            let existing = Self::position(&store, notification.id);
+            let obj = NotificationObj::new(notification);
+            match existing {
+                Some(index) => store.splice(index, 1, &[obj]),
+                None => {
+                    store.insert_sorted(&obj, |a, b| id_of(b).cmp(&id_of(a)));
+                }
+            }
+        }
+    }
+    pub fn remove(&self, id: u32) {
+        if let Some(store) = self.store.upgrade() {
+            if let Some(index) = Self::position(&store, id) {
+                store.remove(index);
+            }
         }
     }
-    // pub fn remove(&self, index: u32) {
-    //     if let Some(list) = self.list.upgrade() {
-    //         list.remove(index);
-    //     }
-    // }
+    fn position(store: &ListStore, id: u32) -> Option<u32> {
+        (0..store.n_items()).find(|&i| {
+            store
+                .item(i)
+                .and_downcast::<NotificationObj>()
+                .is_some_and(|n| n.id() == id)
+        })
+    }
+    /// The outermost widget of the notification centre
+    pub fn root(&self) -> Option<gtk4::Widget> {
+        self.collection.upgrade().map(|c| c.upcast())
+    }
+}
+
+fn id_of(item: &gtk4::glib::Object) -> u32 {
+    item.downcast_ref::<NotificationObj>()
+        .map(NotificationObj::id)
+        .unwrap_or_default()
 }
 
 pub struct NotificationCentreBuilder {
     ui: WidgetOption<NotificationCollection>,
-    list: WeakRef<ListBox>,
+    collection: WeakRef<NotificationCollection>,
+    store: WeakRef<ListStore>,
 }
 impl NotificationCentreBuilder {
     pub fn new(specs: &WidgetSpec) -> Self {
         let collection = NotificationCollection::new();
         let base = specs.base();
 
-        let list = ListBox::builder()
-            .selection_mode(gtk4::SelectionMode::None)
+        let (model, factory, store) = NotificationCollection::construct_liststore();
+        let list = ListView::builder()
+            .model(&model)
+            .factory(&factory)
+            .hexpand(true)
+            .build();
+
+        let scroll = ScrolledWindow::builder()
+            .hscrollbar_policy(PolicyType::Never)
+            .propagate_natural_height(true)
+            .max_content_height(MAX_HEIGHT)
             .hexpand(true)
             .valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
             .halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .child(&list)
             .build();
 
-        collection.append(&list);
+        // Fetch older notifications once the end of the list is reached
+        scroll.connect_edge_reached({
+            let store = store.clone();
+            move |_, position| {
+                let Some(store) = store.upgrade() else {
+                    return;
+                };
+                if position == PositionType::Bottom {
+                    let _result = DAEMON_TX.get().map(|d| {
+                        d.send(Request::PendingNotifications {
+                            offset: store.n_items() as usize,
+                            limit: NOTIFICATION_PAGE,
+                        })
+                    });
+                }
+            }
+        });
+
+        collection.append(&scroll);
 
         Self {
+            collection: collection.downgrade(),
             ui: WidgetOption::Owned(collection),
-            list: list.downgrade(),
+            store,
         }
     }
     pub fn for_box(mut self, container: &Box) -> Self {
@@ -65,6 +134,9 @@ impl NotificationCentreBuilder {
         self
     }
     pub fn build(self) -> NotificationCentre {
-        NotificationCentre { list: self.list }
+        NotificationCentre {
+            collection: self.collection,
+            store: self.store,
+        }
     }
 }
@@ -0,0 +1,115 @@
+use gtk4::{
+    Box, Image,
+    glib::{WeakRef, object::ObjectExt},
+    prelude::{BoxExt, WidgetExt},
+};
+use suite_223b::protocol::{DeviceUse, Request};
+
+use crate::{
+    DAEMON_TX,
+    config::WidgetSpec,
+    ui::widgets::utils::{WidgetOption, locale::tr},
+};
+
+/// Dots for the camera and the microphone, only visible while an application uses them. The
+/// tooltip names the applications.
+#[derive(Clone, Debug)]
+pub struct PrivacyIndicator {
+    holder: WeakRef<Box>,
+    camera: WeakRef<Box>,
+    microphone: WeakRef<Box>,
+}
+impl PrivacyIndicator {
+    pub fn set_state(&self, used: &DeviceUse) {
+        let Some(holder) = self.holder.upgrade() else {
+            return;
+        };
+        holder.set_visible(used.is_active());
+        holder.set_tooltip_text(Some(&tooltip(used)));
+        if let Some(camera) = self.camera.upgrade() {
+            camera.set_visible(!used.camera.is_empty());
+        }
+        if let Some(microphone) = self.microphone.upgrade() {
+            microphone.set_visible(!used.microphone.is_empty());
+        }
+    }
+}
+
+fn tooltip(used: &DeviceUse) -> String {
+    [("Camera", &used.camera), ("Microphone", &used.microphone)]
+        .into_iter()
+        .filter(|(_, apps)| !apps.is_empty())
+        .map(|(device, apps)| format!("{}: {}", tr(device), apps.join(", ")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn device_dot(class: &str, icon: &str) -> Box {
+    let device = Box::builder()
+        .css_classes(["privacy-device", class])
+        .spacing(4)
+        .visible(false)
+        .build();
+    let dot = Box::builder()
+        .css_classes(["privacy-dot"])
+        .valign(gtk4::Align::Center)
+        .width_request(8)
+        .height_request(8)
+        .build();
+    device.append(&dot);
+    device.append(&Image::from_icon_name(icon));
+    device
+}
+
+pub struct PrivacyIndicatorBuilder {
+    ui: WidgetOption<Box>,
+    camera: Box,
+    microphone: Box,
+}
+impl PrivacyIndicatorBuilder {
+    pub fn new(specs: &WidgetSpec) -> Self {
+        let base = specs.base();
+
+        let holder = Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .css_classes(["privacy-indicator"])
+            .spacing(8)
+            .visible(false)
+            .valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .build();
+        if let Some(id) = &base.id {
+            holder.set_widget_name(id);
+        }
+        if let Some(class) = &base.class {
+            holder.add_css_class(class);
+        }
+
+        let camera = device_dot("camera", "camera-web-symbolic");
+        let microphone = device_dot("microphone", "audio-input-microphone-symbolic");
+        holder.append(&camera);
+        holder.append(&microphone);
+
+        // Something may have been in use before the client started
+        let _result = DAEMON_TX.get().map(|d| d.send(Request::DeviceUse));
+
+        Self {
+            ui: WidgetOption::Owned(holder),
+            camera,
+            microphone,
+        }
+    }
+    pub fn for_box(mut self, container: &Box) -> Self {
+        if let Some(widget) = self.ui.take() {
+            container.append(&widget);
+        }
+        self
+    }
+    pub fn build(self) -> PrivacyIndicator {
+        PrivacyIndicator {
+            holder: self.ui.downgrade(),
+            camera: self.camera.downgrade(),
+            microphone: self.microphone.downgrade(),
+        }
+    }
+}
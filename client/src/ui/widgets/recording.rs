@@ -0,0 +1,68 @@
+use gtk4::{
+    Box, Label,
+    glib::{WeakRef, object::ObjectExt},
+    prelude::{BoxExt, WidgetExt},
+};
+
+use crate::{config::WidgetSpec, ui::widgets::utils::WidgetOption};
+
+/// Pulsing red dot that is only visible while a screen recording is running
+#[derive(Clone, Debug)]
+pub struct RecordingIndicator {
+    holder: WeakRef<Box>,
+}
+impl RecordingIndicator {
+    pub fn set_active(&self, active: bool) {
+        if let Some(holder) = self.holder.upgrade() {
+            holder.set_visible(active);
+        }
+    }
+}
+
+pub struct RecordingIndicatorBuilder {
+    ui: WidgetOption<Box>,
+}
+impl RecordingIndicatorBuilder {
+    pub fn new(specs: &WidgetSpec, recording: bool) -> Self {
+        let base = specs.base();
+
+        let holder = Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .css_classes(["recording-indicator"])
+            .spacing(6)
+            .visible(recording)
+            .valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .build();
+        if let Some(id) = &base.id {
+            holder.set_widget_name(id);
+        }
+        if let Some(class) = &base.class {
+            holder.add_css_class(class);
+        }
+
+        let dot = Box::builder()
+            .css_classes(["recording-dot"])
+            .valign(gtk4::Align::Center)
+            .width_request(10)
+            .height_request(10)
+            .build();
+        holder.append(&dot);
+        holder.append(&Label::builder().label("REC").build());
+
+        Self {
+            ui: WidgetOption::Owned(holder),
+        }
+    }
+    pub fn for_box(mut self, container: &Box) -> Self {
+        if let Some(widget) = self.ui.take() {
+            container.append(&widget);
+        }
+        self
+    }
+    pub fn build(self) -> RecordingIndicator {
+        RecordingIndicator {
+            holder: self.ui.downgrade(),
+        }
+    }
+}
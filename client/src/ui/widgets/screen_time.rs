@@ -0,0 +1,170 @@
+use gtk4::{
+    Box, DrawingArea, Label,
+    glib::{WeakRef, object::ObjectExt},
+    prelude::{BoxExt, DrawingAreaExtManual, WidgetExt},
+};
+use suite_223b::protocol::{AppUsage, Request, duration_label};
+
+use crate::{
+    DAEMON_TX,
+    config::WidgetSpec,
+    ui::widgets::utils::{WidgetOption, locale::tr, render::CairoShapesExt},
+};
+
+const BAR_WIDTH: i32 = 80;
+const BAR_HEIGHT: i32 = 6;
+
+/// Today's most used applications as bars, tracked by the daemon's `ScreenTime` service
+#[derive(Clone, Debug)]
+pub struct ScreenTime {
+    holder: WeakRef<Box>,
+    /// Rows shown at most
+    apps: usize,
+}
+impl ScreenTime {
+    pub fn set_usage(&self, usage: &[AppUsage]) {
+        let Some(holder) = self.holder.upgrade() else {
+            return;
+        };
+        while let Some(child) = holder.first_child() {
+            holder.remove(&child);
+        }
+        // Sorted longest first by the daemon
+        let longest = usage.first().map_or(0, |u| u.seconds);
+        for app in usage.iter().take(self.apps) {
+            holder.append(&usage_row(app, longest));
+        }
+        if usage.is_empty() {
+            let empty = Label::builder()
+                .label(tr("No screen time yet"))
+                .css_classes(["screen-time-empty"])
+                .build();
+            holder.append(&empty);
+        }
+    }
+}
+
+fn usage_row(usage: &AppUsage, longest: u64) -> Box {
+    let row = Box::builder()
+        .css_classes(["screen-time-app"])
+        .spacing(8)
+        .build();
+    if usage.over_limit() {
+        row.add_css_class("over-limit");
+    }
+    if let Some(limit) = usage.limit {
+        row.set_tooltip_text(Some(&format!("{}: {}", tr("Limit"), duration_label(limit))));
+    }
+
+    let name = Label::builder()
+        .label(usage.app.as_str())
+        .css_classes(["screen-time-name"])
+        .xalign(0.0)
+        .hexpand(true)
+        .build();
+    let time = Label::builder()
+        .label(duration_label(usage.seconds))
+        .css_classes(["screen-time-duration"])
+        .build();
+
+    let bar = DrawingArea::builder()
+        .css_classes(["screen-time-bar"])
+        .content_width(BAR_WIDTH)
+        .content_height(BAR_HEIGHT)
+        .valign(gtk4::Align::Center)
+        .build();
+    let fraction = bar_fraction(usage.seconds, longest);
+    bar.set_draw_func(move |area, ctx, width, height| {
+        let color = area.color();
+        ctx.set_source_rgba(
+            color.red() as f64,
+            color.green() as f64,
+            color.blue() as f64,
+            color.alpha() as f64,
+        );
+        let radius = height as f64 / 2.0;
+        let length = (width as f64 * fraction).max(height as f64);
+        CairoShapesExt::rounded_rectangle(
+            ctx,
+            0.0,
+            0.0,
+            length,
+            height as f64,
+            (radius, radius, radius, radius),
+        );
+        let _ = ctx.fill();
+    });
+
+    row.append(&name);
+    row.append(&bar);
+    row.append(&time);
+    row
+}
+
+/// Share of the bar's width, relative to the most used application
+fn bar_fraction(seconds: u64, longest: u64) -> f64 {
+    if longest == 0 {
+        return 0.0;
+    }
+    (seconds as f64 / longest as f64).clamp(0.0, 1.0)
+}
+
+pub struct ScreenTimeBuilder {
+    ui: WidgetOption<Box>,
+    apps: usize,
+}
+impl ScreenTimeBuilder {
+    pub fn new(specs: &WidgetSpec) -> Self {
+        let base = specs.base();
+        let apps = match specs {
+            WidgetSpec::ScreenTime { apps, .. } => *apps,
+            _ => 0,
+        };
+
+        let holder = Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .css_classes(["widget", "screen-time"])
+            .spacing(4)
+            .valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Start))
+            .halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Fill))
+            .build();
+        if let Some(id) = &base.id {
+            holder.set_widget_name(id);
+        }
+        if let Some(class) = &base.class {
+            holder.add_css_class(class);
+        }
+
+        // Updated every minute afterwards
+        let _result = DAEMON_TX.get().map(|d| d.send(Request::ScreenTime));
+
+        Self {
+            ui: WidgetOption::Owned(holder),
+            apps,
+        }
+    }
+    pub fn for_box(mut self, container: &Box) -> Self {
+        if let Some(widget) = self.ui.take() {
+            container.append(&widget);
+        }
+        self
+    }
+    pub fn build(self) -> ScreenTime {
+        ScreenTime {
+            holder: self.ui.downgrade(),
+            apps: self.apps,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar_fraction() {
+        assert_eq!(bar_fraction(30, 120), 0.25);
+        assert_eq!(bar_fraction(120, 120), 1.0);
+        assert_eq!(bar_fraction(0, 0), 0.0);
+    }
+}
@@ -1,23 +1,29 @@
 use crate::{
     config::{WidgetBase, WidgetOrientation, WidgetSpec},
-    ui::widgets::utils::{
-        animation::*,
-        interactives::WidgetBehavior,
-        render::{CairoShapesExt, Rgba},
+    ui::{
+        g_templates::snapshot_area::SnapshotArea,
+        widgets::utils::{
+            animation::*,
+            interactives::WidgetBehavior,
+            render::{device_scale, snap},
+            state::StateClass,
+        },
     },
 };
 use gtk4::{
-    Box as GtkBox, DrawingArea, GestureDrag, Image, Overlay, Widget,
-    cairo::Context,
+    Box as GtkBox, GestureDrag, Image, Overlay, Snapshot, Widget,
+    gdk::RGBA,
     glib::{
         WeakRef,
         object::{Cast, CastNone, ObjectExt},
     },
+    graphene::{Rect, Size},
+    gsk::RoundedRect,
     prelude::{
-        BoxExt, DrawingAreaExtManual, EventControllerExt, GestureDragExt, WidgetExt,
-        WidgetExtManual,
+        BoxExt, EventControllerExt, GestureDragExt, SnapshotExt, WidgetExt, WidgetExtManual,
     },
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
     cell::{Cell, RefCell},
@@ -27,6 +33,17 @@ use std::{
 };
 use suite_223b::protocol::AtomicSystemState;
 
+/// Seconds it takes to follow a change made elsewhere, e.g. with the volume keys
+const FOLLOW_DURATION: f64 = 0.15;
+
+/// How a slider moves apart from following the system state
+struct Motion {
+    /// Thickens the bar while it is held
+    press: AnimationState,
+    /// The drawn value, easing towards the system state
+    shown: Tween,
+}
+
 pub struct Slider {
     pub weak: WeakRef<Widget>,
     pub func: Box<dyn WidgetBehavior>,
@@ -41,7 +58,7 @@ impl Slider {
 }
 
 pub struct SliderBuilder {
-    area: DrawingArea,
+    area: SnapshotArea,
     overlay: Overlay,
     func: Box<dyn WidgetBehavior>,
     edit_lock: Rc<Cell<bool>>,
@@ -63,8 +80,14 @@ impl SliderBuilder {
             area.add_css_class(&class);
         }
 
-        let animation_state = Rc::new(AnimationState::new());
         let edit_lock = Rc::new(Cell::new(false));
+        let motion = Rc::new(Motion {
+            press: AnimationState::new(),
+            shown: Tween::new(
+                func.get_percentage(&system_state) as f64 / 100.0,
+                FOLLOW_DURATION,
+            ),
+        });
 
         Slider::connect_drag(
             &area,
@@ -72,42 +95,42 @@ impl SliderBuilder {
             &func,
             orientation,
             icon,
-            Rc::clone(&animation_state),
+            Rc::clone(&motion),
             Rc::clone(&edit_lock),
         );
 
-        area.set_draw_func({
+        area.set_snapshot_func({
             let system_state = Arc::clone(&system_state);
             let func = func.clone();
-            let animation_state = Rc::clone(&animation_state);
-            move |area, ctx, w, h| match orientation {
-                WidgetOrientation::Vertical => Slider::draw_vert(
-                    area,
-                    ctx,
-                    w,
-                    h,
-                    Arc::clone(&system_state),
-                    &func,
-                    Rc::clone(&animation_state),
-                ),
-                WidgetOrientation::Horizontal => Slider::draw_horz(
-                    area,
-                    ctx,
-                    w,
-                    h,
-                    Arc::clone(&system_state),
-                    &func,
-                    Rc::clone(&animation_state),
-                ),
+            let motion = Rc::clone(&motion);
+            let edit_lock = Rc::clone(&edit_lock);
+            move |area, snapshot, w, h| {
+                // The pointer wins over changes arriving during a drag, they are caught up
+                // with once it ends
+                if !edit_lock.get() {
+                    motion
+                        .shown
+                        .retarget(func.get_percentage(&system_state) as f64 / 100.0);
+                }
+                let percentage = motion.shown.value();
+                match orientation {
+                    WidgetOrientation::Vertical => {
+                        Slider::snapshot_vert(area, snapshot, w, h, percentage)
+                    }
+                    WidgetOrientation::Horizontal => {
+                        Slider::snapshot_horz(area, snapshot, w, h, percentage, &motion.press)
+                    }
+                }
             }
         });
         area.add_tick_callback({
-            let animation_state = Rc::clone(&animation_state);
+            let motion = Rc::clone(&motion);
             move |widget, frame_clock| {
-                if !animation_state.running.get() {
+                if !motion.press.running.get() && !motion.shown.running() {
                     return gtk4::glib::ControlFlow::Continue;
                 }
-                animation_state.update(frame_clock);
+                motion.press.update(frame_clock);
+                motion.shown.update(frame_clock);
                 widget.queue_draw();
                 gtk4::glib::ControlFlow::Continue
             }
@@ -124,7 +147,7 @@ impl SliderBuilder {
         base: &WidgetBase,
         func: &Box<dyn WidgetBehavior>,
         in_holder: bool,
-    ) -> (Overlay, DrawingArea, WeakRef<Image>) {
+    ) -> (Overlay, SnapshotArea, WeakRef<Image>) {
         let icon = func.icon_name(50).to_string();
 
         let builder = Overlay::builder()
@@ -145,7 +168,7 @@ impl SliderBuilder {
         }
         .build();
 
-        let area = DrawingArea::new();
+        let area = SnapshotArea::new();
         let svg_icon = Image::builder()
             .icon_name(icon)
             .css_classes(["active"])
@@ -161,7 +184,7 @@ impl SliderBuilder {
         base: &WidgetBase,
         func: &Box<dyn WidgetBehavior>,
         in_holder: bool,
-    ) -> (Overlay, DrawingArea, WeakRef<Image>) {
+    ) -> (Overlay, SnapshotArea, WeakRef<Image>) {
         let builder = Overlay::builder()
             .css_classes(["widget", "slider", "horizontal"])
             .hexpand(true)
@@ -183,11 +206,10 @@ impl SliderBuilder {
             .can_target(false)
             .build();
 
-        let area = DrawingArea::builder()
-            .css_classes(["slider-obj"])
-            .hexpand(true)
-            .vexpand(true)
-            .build();
+        let area = SnapshotArea::new();
+        area.set_css_classes(&["slider-obj"]);
+        area.set_hexpand(true);
+        area.set_vexpand(true);
 
         let content = GtkBox::builder()
             .orientation(gtk4::Orientation::Horizontal)
@@ -217,108 +239,107 @@ impl SliderBuilder {
 }
 
 impl Slider {
-    fn draw_vert(
-        _area: &DrawingArea,
-        ctx: &Context,
-        width: i32,
-        height: i32,
-        state: Arc<AtomicSystemState>,
-        func: &Box<dyn WidgetBehavior>,
-        _animation_state: Rc<AnimationState>,
+    fn rgba(color: &RGBA, alpha: f32) -> RGBA {
+        RGBA::new(color.red(), color.green(), color.blue(), alpha)
+    }
+    /// Appends a pill shaped color node
+    fn append_pill(snapshot: &Snapshot, color: &RGBA, x: f64, y: f64, w: f64, h: f64) {
+        let rect = Rect::new(x as f32, y as f32, w.max(0.0) as f32, h as f32);
+        let radius = (h / 2.0).min(w / 2.0).max(0.0) as f32;
+        snapshot.push_rounded_clip(&RoundedRect::new(
+            rect,
+            Size::new(radius, radius),
+            Size::new(radius, radius),
+            Size::new(radius, radius),
+            Size::new(radius, radius),
+        ));
+        snapshot.append_color(color, &rect);
+        snapshot.pop();
+    }
+    fn snapshot_vert(
+        area: &SnapshotArea,
+        snapshot: &Snapshot,
+        width: f64,
+        height: f64,
+        percentage: f64,
     ) {
-        let color: Rgba = _area.color().into();
-        let percentage = func.get_percentage(&state) as f64 / 100.0;
+        let color = Self::rgba(&area.color(), 1.0);
 
-        let h = height as f64;
-        let w = width as f64;
-        let fill_height = h * percentage;
-        let y_start = h - fill_height;
+        let scale = device_scale(area);
+        let fill_height = snap(height * percentage, scale);
+        let y_start = height - fill_height;
 
         // Background
-        ctx.set_source_rgba(color.r, color.g, color.b, 1.0);
-        ctx.rectangle(0.0, y_start, w, fill_height);
-        ctx.fill().unwrap();
+        snapshot.append_color(
+            &color,
+            &Rect::new(0.0, y_start as f32, width as f32, fill_height as f32),
+        );
     }
-    fn draw_horz(
-        _area: &DrawingArea,
-        ctx: &Context,
-        width: i32,
-        height: i32,
-        state: Arc<AtomicSystemState>,
-        func: &Box<dyn WidgetBehavior>,
-        animation_state: Rc<AnimationState>,
+    fn snapshot_horz(
+        area: &SnapshotArea,
+        snapshot: &Snapshot,
+        width: f64,
+        height: f64,
+        percentage: f64,
+        press: &AnimationState,
     ) {
-        let color: Rgba = _area.color().into();
-        let percentage = func.get_percentage(&state) as f64 / 100.0;
+        let color = area.color();
 
-        let progress = animation_state.progress.get();
+        let progress = press.progress.get();
 
         let thickness = 5.0 + 2.0 * progress;
-        let h = height as f64;
-        let w = width as f64;
-        let fill_width = w * percentage;
+        let scale = device_scale(area);
+        let fill_width = snap(width * percentage, scale);
 
         let bg_height = 5.0;
-        let bg_y = (h - bg_height) / 2.0;
+        let bg_y = snap((height - bg_height) / 2.0, scale);
 
-        let progress_y = (h - thickness) / 2.0;
+        let progress_y = snap((height - thickness) / 2.0, scale);
 
-        ctx.set_source_rgba(color.r, color.g, color.b, 0.3);
-        CairoShapesExt::rounded_rectangle(
-            ctx,
+        Self::append_pill(
+            snapshot,
+            &Self::rgba(&color, 0.3),
             0.0,
             bg_y,
-            w,
+            width,
             bg_height,
-            (
-                bg_height / 2.0,
-                bg_height / 2.0,
-                bg_height / 2.0,
-                bg_height / 2.0,
-            ),
         );
-        ctx.fill().unwrap();
-
-        ctx.set_source_rgba(color.r, color.g, color.b, 1.0);
-        CairoShapesExt::rounded_rectangle(
-            ctx,
+        Self::append_pill(
+            snapshot,
+            &Self::rgba(&color, 1.0),
             0.0,
             progress_y,
             fill_width,
             thickness,
-            (
-                thickness / 2.0,
-                thickness / 2.0,
-                thickness / 2.0,
-                thickness / 2.0,
-            ),
         );
-        ctx.fill().unwrap();
     }
 
     fn connect_drag(
-        target: &DrawingArea,
+        target: &SnapshotArea,
         system_state: Arc<AtomicSystemState>,
         func: &Box<dyn WidgetBehavior>,
         orientation: WidgetOrientation,
         icon: WeakRef<Image>,
-        animation_state: Rc<AnimationState>,
+        motion: Rc<Motion>,
         edit_lock: Rc<Cell<bool>>,
     ) {
         let drag = GestureDrag::new();
         let perc = func.get_percentage(&system_state);
+        // Last value the pointer picked
+        let dragged = Rc::new(Cell::new(perc));
 
         drag.connect_drag_begin({
             let system_state = Arc::clone(&system_state);
             let icon = icon.clone();
-            let animation_state = Rc::clone(&animation_state);
+            let motion = Rc::clone(&motion);
             let edit_lock = Rc::clone(&edit_lock);
+            let dragged = Rc::clone(&dragged);
             let func = func.clone();
             move |gesture, x, y| {
                 edit_lock.set(true);
-                let target = gesture.widget().and_downcast::<DrawingArea>().unwrap();
-                target.add_css_class("moving");
-                animation_state.start(AnimationDirection::Forward {
+                let target = gesture.widget().and_downcast::<SnapshotArea>().unwrap();
+                StateClass::Dragging.set(&target, true);
+                motion.press.start(AnimationDirection::Forward {
                     duration: 0.05,
                     function: EaseFunction::EaseIn,
                 });
@@ -341,6 +362,8 @@ impl Slider {
                     icon_widget.set_icon_name(Some(&next_icon));
                 }
 
+                motion.shown.set(new_p as f64);
+                dragged.set(new_percent);
                 func.set_percentage(&system_state, new_percent);
                 func.execute(&system_state);
                 target.queue_draw();
@@ -351,10 +374,12 @@ impl Slider {
             let system_state = Arc::clone(&system_state);
             let last_seen_percent = Rc::new(Cell::new(0u8));
             let last_sent_time = Rc::new(Cell::new(Instant::now()));
-            let last_seen_icon = Rc::new(RefCell::new(func.icon_name(perc)));
+            let last_seen_icon = Rc::new(RefCell::new(func.icon_name(perc).to_string()));
+            let motion = Rc::clone(&motion);
+            let dragged = Rc::clone(&dragged);
             let func = func.clone();
             move |gesture, x, y| {
-                let target = gesture.widget().and_downcast::<DrawingArea>().unwrap();
+                let target = gesture.widget().and_downcast::<SnapshotArea>().unwrap();
 
                 if let Some((x_start, y_start)) = gesture.start_point() {
                     let new_p = match orientation {
@@ -372,6 +397,8 @@ impl Slider {
                     };
 
                     let new_percent = (new_p * 100.0) as u8;
+                    motion.shown.set(new_p as f64);
+                    dragged.set(new_percent);
                     func.set_percentage(&system_state, new_percent);
                     let now = Instant::now();
 
@@ -384,7 +411,7 @@ impl Slider {
                             if let Some(icon_widget) = icon.upgrade() {
                                 icon_widget.set_icon_name(Some(&next_icon));
                             }
-                            last_seen_icon.replace(next_icon);
+                            last_seen_icon.replace(next_icon.to_string());
                         }
                         target.queue_draw();
 
@@ -399,19 +426,21 @@ impl Slider {
         });
         drag.connect_drag_end({
             let system_state = Arc::clone(&system_state);
-            let animation_state = Rc::clone(&animation_state);
             let edit_lock = Rc::clone(&edit_lock);
             let func = func.clone();
             move |gesture, _, _| {
                 edit_lock.set(false);
-                let target = gesture.widget().and_downcast::<DrawingArea>().unwrap();
-                target.remove_css_class("moving");
+                let target = gesture.widget().and_downcast::<SnapshotArea>().unwrap();
+                StateClass::Dragging.set(&target, false);
 
-                animation_state.start(AnimationDirection::Backward {
+                motion.press.start(AnimationDirection::Backward {
                     duration: 0.1,
                     function: EaseFunction::EaseOut,
                 });
-                func.as_request(&system_state);
+                // The pointer wins over changes that arrived during the drag. The daemon's
+                // answer is eased to like any other change.
+                func.set_percentage(&system_state, dragged.get());
+                func.execute(&system_state);
             }
         });
 
@@ -419,7 +448,7 @@ impl Slider {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct SliderRange {
     min: i32,
     max: i32,
@@ -0,0 +1,214 @@
+use std::rc::Rc;
+
+use gtk4::{
+    Box, DrawingArea, Label, Overlay,
+    glib::{WeakRef, object::ObjectExt},
+    prelude::{BoxExt, DrawingAreaExtManual, WidgetExt},
+};
+use suite_223b::protocol::{DataKind, Quote, Request};
+
+use crate::{
+    DAEMON_TX,
+    config::WidgetSpec,
+    ui::widgets::utils::{WidgetOption, refresh::RefreshOverlay},
+};
+
+const SPARKLINE_WIDTH: i32 = 60;
+const SPARKLINE_HEIGHT: i32 = 20;
+
+/// Price, day change and a sparkline of the session for each symbol in `finance.json`
+#[derive(Clone, Debug)]
+pub struct Ticker {
+    holder: WeakRef<Box>,
+    /// Only these symbols, all of them when empty
+    symbols: Rc<Vec<String>>,
+    refresh: RefreshOverlay,
+}
+impl Ticker {
+    pub fn set_refreshing(&self, refreshing: bool) {
+        self.refresh.set_refreshing(refreshing);
+    }
+    pub fn set_quotes(&self, quotes: &[Quote]) {
+        let Some(holder) = self.holder.upgrade() else {
+            return;
+        };
+        while let Some(child) = holder.first_child() {
+            holder.remove(&child);
+        }
+        let shown = quotes.iter().filter(|q| {
+            self.symbols.is_empty()
+                || self
+                    .symbols
+                    .iter()
+                    .any(|s| s.eq_ignore_ascii_case(&q.symbol))
+        });
+        for quote in shown {
+            holder.append(&quote_row(quote));
+        }
+    }
+}
+
+fn quote_row(quote: &Quote) -> Box {
+    let trend = if quote.change_percent >= 0.0 {
+        "up"
+    } else {
+        "down"
+    };
+    let row = Box::builder()
+        .css_classes(["ticker-quote", trend])
+        .spacing(8)
+        .build();
+    if let Some(currency) = &quote.currency {
+        row.set_tooltip_text(Some(&format!(
+            "{} {:.2} {}",
+            quote.symbol, quote.price, currency
+        )));
+    }
+
+    let symbol = Label::builder()
+        .label(quote.symbol.as_str())
+        .css_classes(["ticker-symbol"])
+        .xalign(0.0)
+        .hexpand(true)
+        .build();
+    let price = Label::builder()
+        .label(format!("{:.2}", quote.price))
+        .css_classes(["ticker-price"])
+        .build();
+    let change = Label::builder()
+        .label(format!("{:+.2}%", quote.change_percent))
+        .css_classes(["ticker-change"])
+        .build();
+
+    let sparkline = DrawingArea::builder()
+        .css_classes(["ticker-sparkline"])
+        .content_width(SPARKLINE_WIDTH)
+        .content_height(SPARKLINE_HEIGHT)
+        .valign(gtk4::Align::Center)
+        .build();
+    let history = quote.history.clone();
+    sparkline.set_draw_func(move |area, ctx, width, height| {
+        let Some(points) = sparkline_points(&history, width as f64, height as f64) else {
+            return;
+        };
+        let color = area.color();
+        ctx.set_source_rgba(
+            color.red() as f64,
+            color.green() as f64,
+            color.blue() as f64,
+            color.alpha() as f64,
+        );
+        ctx.set_line_width(1.5);
+        ctx.set_line_join(gtk4::cairo::LineJoin::Round);
+        for (i, (x, y)) in points.into_iter().enumerate() {
+            if i == 0 {
+                ctx.move_to(x, y);
+            } else {
+                ctx.line_to(x, y);
+            }
+        }
+        let _ = ctx.stroke();
+    });
+
+    row.append(&symbol);
+    row.append(&sparkline);
+    row.append(&price);
+    row.append(&change);
+    row
+}
+
+/// `values` scaled into a `width` x `height` area, the highest price at the top
+fn sparkline_points(values: &[f64], width: f64, height: f64) -> Option<Vec<(f64, f64)>> {
+    if values.len() < 2 {
+        return None;
+    }
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    // A flat line sits in the middle
+    let range = if max > min { max - min } else { 1.0 };
+    let offset = if max > min { 0.0 } else { 0.5 };
+    let step = width / (values.len() - 1) as f64;
+    // Half the line width off each edge, so the extremes aren't clipped
+    let inset = 1.0;
+    let usable = (height - 2.0 * inset).max(0.0);
+    Some(
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let y = inset + usable * (1.0 - ((v - min) / range + offset));
+                (i as f64 * step, y)
+            })
+            .collect(),
+    )
+}
+
+pub struct TickerBuilder {
+    /// The quotes, wrapped by `refresh`'s overlay
+    holder: WeakRef<Box>,
+    ui: WidgetOption<Overlay>,
+    symbols: Vec<String>,
+    refresh: RefreshOverlay,
+}
+impl TickerBuilder {
+    pub fn new(specs: &WidgetSpec) -> Self {
+        let base = specs.base();
+        let symbols = match specs {
+            WidgetSpec::Ticker { symbols, .. } => symbols.clone(),
+            _ => Vec::new(),
+        };
+
+        let holder = Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .css_classes(["widget", "ticker"])
+            .spacing(4)
+            .build();
+        let (overlay, refresh) = RefreshOverlay::new(&holder, DataKind::Quotes);
+        overlay.set_valign(base.valign.map(|d| d.into()).unwrap_or(gtk4::Align::Start));
+        overlay.set_halign(base.halign.map(|d| d.into()).unwrap_or(gtk4::Align::Fill));
+        if let Some(id) = &base.id {
+            holder.set_widget_name(id);
+        }
+        if let Some(class) = &base.class {
+            holder.add_css_class(class);
+        }
+
+        // The daemon answers with the cached quotes
+        let _result = DAEMON_TX.get().map(|d| d.send(Request::Quotes));
+
+        Self {
+            holder: holder.downgrade(),
+            ui: WidgetOption::Owned(overlay),
+            symbols,
+            refresh,
+        }
+    }
+    pub fn for_box(mut self, container: &Box) -> Self {
+        if let Some(widget) = self.ui.take() {
+            container.append(&widget);
+        }
+        self
+    }
+    pub fn build(self) -> Ticker {
+        Ticker {
+            holder: self.holder,
+            symbols: Rc::new(self.symbols),
+            refresh: self.refresh,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparkline_points() {
+        let points = sparkline_points(&[1.0, 3.0, 2.0], 10.0, 12.0).unwrap();
+        assert_eq!(points, [(0.0, 11.0), (5.0, 1.0), (10.0, 6.0)]);
+
+        let flat = sparkline_points(&[2.0, 2.0], 10.0, 12.0).unwrap();
+        assert_eq!(flat, [(0.0, 6.0), (10.0, 6.0)]);
+        assert!(sparkline_points(&[2.0], 10.0, 12.0).is_none());
+    }
+}
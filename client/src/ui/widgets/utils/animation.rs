@@ -2,6 +2,8 @@ use std::cell::Cell;
 
 use gtk4::gdk::FrameClock;
 
+use crate::ui::widgets::utils::power::is_saving_power;
+
 #[allow(dead_code)]
 #[derive(Clone, Copy, Default)]
 pub enum EaseFunction {
@@ -85,6 +87,12 @@ impl AnimationState {
         if !self.running.get() {
             return;
         }
+        // Animations are skipped while saving power
+        if is_saving_power() {
+            self.progress.set(self.direction.get().end());
+            self.running.set(false);
+            return;
+        }
 
         let now = frame_clock.frame_time(); // microseconds
 
@@ -125,3 +133,128 @@ impl AnimationState {
         self.direction.set(AnimationDirection::Uninitialized);
     }
 }
+
+/// Eases a value towards its latest target, e.g. a slider following changes made elsewhere
+pub struct Tween {
+    animation: AnimationState,
+    from: Cell<f64>,
+    to: Cell<f64>,
+    /// Seconds
+    duration: f64,
+}
+impl Tween {
+    pub fn new(value: f64, duration: f64) -> Self {
+        Self {
+            animation: AnimationState::new(),
+            from: Cell::new(value),
+            to: Cell::new(value),
+            duration,
+        }
+    }
+    /// What to draw in this frame
+    pub fn value(&self) -> f64 {
+        if !self.animation.running.get() {
+            return self.to.get();
+        }
+        let (from, to) = (self.from.get(), self.to.get());
+        from + (to - from) * self.animation.progress.get()
+    }
+    /// Eases from the value drawn now to `target`, unless that is where it's headed already
+    pub fn retarget(&self, target: f64) {
+        if (target - self.to.get()).abs() < f64::EPSILON {
+            return;
+        }
+        self.from.set(self.value());
+        self.to.set(target);
+        self.animation.start(AnimationDirection::Forward {
+            duration: self.duration,
+            function: EaseFunction::EaseOutCubic,
+        });
+    }
+    /// Jumps to `value`, e.g. while it follows the pointer
+    pub fn set(&self, value: f64) {
+        self.from.set(value);
+        self.to.set(value);
+        self.animation.running.set(false);
+    }
+    pub fn running(&self) -> bool {
+        self.animation.running.get()
+    }
+    pub fn update(&self, frame_clock: &FrameClock) {
+        self.animation.update(frame_clock);
+    }
+}
+
+/// Loops from 0 to 1 every `period` seconds for as long as it's updated, e.g. while charging
+pub struct Spin {
+    phase: Cell<f64>,
+    last_time: Cell<Option<i64>>,
+    /// Seconds
+    period: f64,
+}
+impl Spin {
+    pub fn new(period: f64) -> Self {
+        Self {
+            phase: Cell::new(0.0),
+            last_time: Cell::new(None),
+            period,
+        }
+    }
+    pub fn phase(&self) -> f64 {
+        self.phase.get()
+    }
+    pub fn update(&self, frame_clock: &FrameClock) {
+        self.advance(frame_clock.frame_time());
+    }
+    /// `now` in microseconds
+    fn advance(&self, now: i64) {
+        if let Some(last) = self.last_time.get() {
+            let elapsed = (now - last) as f64 / 1_000_000.0;
+            self.phase
+                .set((self.phase.get() + elapsed / self.period).fract());
+        }
+        self.last_time.set(Some(now));
+    }
+    /// Pauses, the next update continues from the same phase
+    pub fn stop(&self) {
+        self.last_time.set(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tween_retarget() {
+        let tween = Tween::new(0.2, 0.15);
+        assert_eq!(tween.value(), 0.2);
+
+        tween.retarget(0.8);
+        assert!(tween.running());
+        // Starts from where it was drawn
+        assert_eq!(tween.value(), 0.2);
+
+        // Following the pointer stops the easing
+        tween.set(0.5);
+        assert!(!tween.running());
+        assert_eq!(tween.value(), 0.5);
+        tween.retarget(0.5);
+        assert!(!tween.running());
+    }
+
+    #[test]
+    fn test_spin_wraps_and_pauses() {
+        let spin = Spin::new(2.0);
+        spin.advance(0);
+        spin.advance(500_000);
+        assert_eq!(spin.phase(), 0.25);
+        spin.advance(2_500_000);
+        assert_eq!(spin.phase(), 0.25);
+
+        // A pause doesn't count as elapsed time
+        spin.stop();
+        spin.advance(10_000_000);
+        assert_eq!(spin.phase(), 0.25);
+    }
+}
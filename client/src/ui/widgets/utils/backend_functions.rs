@@ -1,9 +1,13 @@
-use std::sync::atomic::Ordering;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU8, Ordering},
+};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use suite_223b::protocol::Request;
 
-use crate::ui::widgets::utils::interactives::*;
+use crate::ui::widgets::{ColorFormat, utils::interactives::*};
 macro_rules! define_backend_functions {
     (
         $(
@@ -12,7 +16,7 @@ macro_rules! define_backend_functions {
             } )?
         ),* $(,)?
     ) => {
-        #[derive(Debug, Clone, Deserialize, Serialize)]
+        #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
         #[serde(rename_all = "lowercase")]
         pub enum BackendFunc {
             $(
@@ -53,9 +57,27 @@ define_backend_functions!(
     Powermode,
     Volume,
     Brightness,
+    Screenshot,
+    ScreenRecord,
+    /// Picks a color on screen and copies it, e.g. `{ "colorpicker": { "format": "rgb" } }`
+    ColorPicker {
+        #[serde(default)]
+        format: ColorFormat,
+    },
+    NightLight,
+    NightLightIntensity,
+    /// Brightness of an external monitor over DDC/CI
+    Display {
+        /// DRM connector, e.g. `DP-1`
+        device: String,
+    },
     Custom {
         id: String,
         states: Vec<FunctionConfig>,
+        /// Wait for the commands and report when they fail, killing them after the daemon's
+        /// timeout. They are started and left running otherwise.
+        #[serde(default)]
+        capture: bool,
     }
 );
 impl Default for BackendFunc {
@@ -65,7 +87,7 @@ impl Default for BackendFunc {
 }
 
 // ----- Backend Functions
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Default, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema, Default, Hash)]
 pub struct FunctionConfig {
     icon: String,
     command: String,
@@ -109,6 +131,11 @@ impl BackendFunc {
                 ],
                 max_states: 3,
                 field: |s| &s.powermode,
+                available: |s, v| {
+                    s.power_profiles
+                        .read()
+                        .is_ok_and(|p| p.is_available(v.into()))
+                },
                 request_builder: |v| Request::SetPowerMode(v),
                 func,
             }),
@@ -120,7 +147,21 @@ impl BackendFunc {
                     "display-brightness-high-symbolic",
                 ],
                 field: |s| &s.brightness,
-                request_builder: |v| Request::SetBacklight(v),
+                request_builder: |v| Request::SetBacklight {
+                    device: None,
+                    percent: v,
+                },
+                func,
+            }),
+            Self::Display { device } => Box::new(DisplayRange {
+                icons: &[
+                    "display-brightness-off-symbolic",
+                    "display-brightness-low-symbolic",
+                    "display-brightness-medium-symbolic",
+                    "display-brightness-high-symbolic",
+                ],
+                device: device.into(),
+                value: Arc::new(AtomicU8::new(0)),
                 func,
             }),
             Self::Volume => Box::new(RangeBehavior {
@@ -134,24 +175,52 @@ impl BackendFunc {
                 request_builder: |v| Request::SetVolume(v),
                 func,
             }),
-            Self::Custom { id, states, .. } => {
-                let l_id: &'static str = Box::leak(id.into_boxed_str());
-                let l_states: Vec<(&'static str, &'static str)> = states
+            Self::Screenshot => Box::new(ActionButton {
+                icon: "camera-photo-symbolic",
+                request: || Request::Screenshot,
+                func,
+            }),
+            Self::ScreenRecord => Box::new(ToggleButton {
+                icons: ["media-record-symbolic", "media-playback-stop-symbolic"],
+                getter: |s| s.recording.load(Ordering::Relaxed),
+                setter: |s, v| s.recording.store(v, Ordering::Relaxed),
+                // The daemon only knows whether it is recording once the portal dialog is done
+                request_builder: |_| Request::ToggleRecording,
+                func,
+            }),
+            Self::ColorPicker { .. } => Box::new(ActionButton {
+                icon: "color-select-symbolic",
+                request: || Request::PickColor,
+                func,
+            }),
+            Self::NightLight => Box::new(ToggleButton {
+                icons: ["night-light-disabled-symbolic", "night-light-symbolic"],
+                getter: |s| s.night_light.load(Ordering::Relaxed),
+                setter: |s, v| s.night_light.store(v, Ordering::Relaxed),
+                request_builder: |v| Request::SetNightLight(v),
+                func,
+            }),
+            Self::NightLightIntensity => Box::new(RangeBehavior {
+                icons: &["night-light-disabled-symbolic", "night-light-symbolic"],
+                field: |s| &s.night_light_intensity,
+                request_builder: |v| Request::SetNightLightIntensity(v),
+                func,
+            }),
+            Self::Custom {
+                id,
+                states,
+                capture,
+            } => {
+                let states: Arc<[(Arc<str>, Arc<str>)]> = states
                     .into_iter()
-                    .map(|s| {
-                        let i = Box::leak(s.icon.into_boxed_str()) as &'static str;
-                        let c = Box::leak(s.command.into_boxed_str()) as &'static str;
-                        (i, c)
-                    })
+                    .map(|s| (s.icon.into(), s.command.into()))
                     .collect();
 
-                let l_slice: &'static [(&'static str, &'static str)] =
-                    Box::leak(l_states.into_boxed_slice());
-
                 Box::new(DynamicCycleButton {
-                    id: l_id,
-                    states: l_slice,
-                    max_states: l_slice.len() as u8,
+                    id: id.into(),
+                    max_states: states.len() as u8,
+                    states,
+                    capture,
                     func,
                 })
             }
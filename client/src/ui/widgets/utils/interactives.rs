@@ -1,18 +1,30 @@
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{
+    Arc,
+    atomic::{AtomicU8, Ordering},
+};
 
 use suite_223b::protocol::{AtomicSystemState, Request};
 
-use crate::{DAEMON_TX, ui::widgets::utils::backend_functions::BackendFuncType};
+use crate::{
+    DAEMON_TX,
+    ui::widgets::utils::{backend_functions::BackendFuncType, pending},
+};
 
 pub trait WidgetBehavior {
     fn clone_box(&self) -> Box<dyn WidgetBehavior>;
-    fn icon_name(&self, val: u8) -> &'static str;
+    fn icon_name(&self, val: u8) -> &str;
     fn set_percentage(&self, state: &AtomicSystemState, value: u8);
     fn as_request(&self, state: &AtomicSystemState) -> Option<(u8, Request)>;
     fn get_percentage(&self, state: &AtomicSystemState) -> u8;
     fn func(&self) -> BackendFuncType;
+    /// The external display this controls, if any
+    fn device(&self) -> Option<&str> {
+        None
+    }
     fn execute(&self, state: &AtomicSystemState) -> Option<u8> {
+        let previous = self.get_percentage(state);
         let (val, request) = self.as_request(state)?;
+        let request = pending::track(self.clone_box(), previous, request);
         DAEMON_TX.get().map(|d| d.send(request));
         Some(val)
     }
@@ -38,7 +50,7 @@ impl WidgetBehavior for ToggleButton {
     fn set_percentage(&self, state: &AtomicSystemState, value: u8) {
         (self.setter)(state, value != 0);
     }
-    fn icon_name(&self, val: u8) -> &'static str {
+    fn icon_name(&self, val: u8) -> &str {
         let idx = val.clamp(0, 1);
         self.icons[idx as usize]
     }
@@ -52,14 +64,50 @@ impl WidgetBehavior for ToggleButton {
     }
 }
 
+/// Stateless button that sends the same request on every click
+#[derive(Clone)]
+pub struct ActionButton {
+    pub icon: &'static str,
+    pub request: fn() -> Request,
+    pub func: BackendFuncType,
+}
+impl WidgetBehavior for ActionButton {
+    fn clone_box(&self) -> Box<dyn WidgetBehavior> {
+        Box::new(self.clone())
+    }
+    fn get_percentage(&self, _state: &AtomicSystemState) -> u8 {
+        0
+    }
+    fn set_percentage(&self, _state: &AtomicSystemState, _value: u8) {}
+    fn icon_name(&self, _val: u8) -> &str {
+        self.icon
+    }
+    fn as_request(&self, _state: &AtomicSystemState) -> Option<(u8, Request)> {
+        Some((0, (self.request)()))
+    }
+    fn func(&self) -> BackendFuncType {
+        self.func
+    }
+}
+
 #[derive(Clone)]
 pub struct CycleButton {
     pub icons: &'static [&'static str], // List of icons for each state
     pub max_states: u8,
     pub field: fn(&AtomicSystemState) -> &std::sync::atomic::AtomicU8,
+    /// States the cycle skips, e.g. profiles the machine doesn't support
+    pub available: fn(&AtomicSystemState, u8) -> bool,
     pub request_builder: fn(u8) -> Request,
     pub func: BackendFuncType,
 }
+impl CycleButton {
+    fn next_state(&self, state: &AtomicSystemState, current: u8) -> u8 {
+        (1..=self.max_states)
+            .map(|step| (current + step) % self.max_states)
+            .find(|&s| (self.available)(state, s))
+            .unwrap_or(current)
+    }
+}
 impl WidgetBehavior for CycleButton {
     fn clone_box(&self) -> Box<dyn WidgetBehavior> {
         Box::new(self.clone())
@@ -69,7 +117,7 @@ impl WidgetBehavior for CycleButton {
         let mut old = atomic.load(Ordering::Relaxed);
         let mut target;
         loop {
-            target = (old + 1) % self.max_states;
+            target = self.next_state(state, old);
             match atomic.compare_exchange_weak(old, target, Ordering::SeqCst, Ordering::Relaxed) {
                 Ok(_) => break,
                 Err(actual) => old = actual,
@@ -78,7 +126,7 @@ impl WidgetBehavior for CycleButton {
         Some((target, (self.request_builder)(target)))
     }
 
-    fn icon_name(&self, val: u8) -> &'static str {
+    fn icon_name(&self, val: u8) -> &str {
         self.icons
             .get(val as usize)
             .copied()
@@ -115,17 +163,8 @@ impl WidgetBehavior for RangeBehavior {
         Some((target, (self.request_builder)(target)))
     }
 
-    fn icon_name(&self, val: u8) -> &'static str {
-        if self.icons.is_empty() {
-            return "image-missing";
-        }
-
-        let max_idx = self.icons.len().saturating_sub(1);
-        let index = (val as usize * max_idx + 50) / 100;
-        self.icons
-            .get(index as usize)
-            .copied()
-            .unwrap_or("image-missing")
+    fn icon_name(&self, val: u8) -> &str {
+        range_icon(self.icons, val)
     }
 
     fn set_percentage(&self, state: &AtomicSystemState, value: u8) {
@@ -141,6 +180,67 @@ impl WidgetBehavior for RangeBehavior {
     }
 }
 
+/// The icon of `icons` closest to `val` percent
+fn range_icon(icons: &'static [&'static str], val: u8) -> &'static str {
+    icon_index(icons.len(), val)
+        .map(|i| icons[i])
+        .unwrap_or("image-missing")
+}
+
+/// Index of the one of `len` evenly spread icons closest to `val` percent
+fn icon_index(len: usize, val: u8) -> Option<usize> {
+    let max_idx = len.checked_sub(1)?;
+    // Integer rounding: (val * max + 50) / 100
+    let index = (val.min(100) as usize * max_idx + 50) / 100;
+    Some(index.min(max_idx))
+}
+
+/// Brightness slider of one external display. Its value isn't part of the system state, so the
+/// slider keeps it.
+#[derive(Clone)]
+pub struct DisplayRange {
+    pub icons: &'static [&'static str],
+    pub device: Arc<str>,
+    pub value: Arc<AtomicU8>,
+    pub func: BackendFuncType,
+}
+impl WidgetBehavior for DisplayRange {
+    fn clone_box(&self) -> Box<dyn WidgetBehavior> {
+        Box::new(self.clone())
+    }
+
+    fn as_request(&self, _state: &AtomicSystemState) -> Option<(u8, Request)> {
+        let target = self.value.load(Ordering::Relaxed);
+        Some((
+            target,
+            Request::SetBacklight {
+                device: Some(self.device.to_string()),
+                percent: target,
+            },
+        ))
+    }
+
+    fn icon_name(&self, val: u8) -> &str {
+        range_icon(self.icons, val)
+    }
+
+    fn set_percentage(&self, _state: &AtomicSystemState, value: u8) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    fn get_percentage(&self, _state: &AtomicSystemState) -> u8 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    fn func(&self) -> BackendFuncType {
+        self.func
+    }
+
+    fn device(&self) -> Option<&str> {
+        Some(&self.device)
+    }
+}
+
 impl Clone for Box<dyn WidgetBehavior> {
     fn clone(&self) -> Self {
         self.clone_box()
@@ -149,10 +249,12 @@ impl Clone for Box<dyn WidgetBehavior> {
 
 #[derive(Clone)]
 pub struct DynamicCycleButton {
-    // leaked slice of leaked string pairs: [(Icon, Command)]
-    pub states: &'static [(&'static str, &'static str)],
-    pub id: &'static str,
+    /// `(icon, command)` of every state, shared between clones
+    pub states: Arc<[(Arc<str>, Arc<str>)]>,
+    pub id: Arc<str>,
     pub max_states: u8,
+    /// See `Request::Command`
+    pub capture: bool,
     pub func: BackendFuncType,
 }
 impl WidgetBehavior for DynamicCycleButton {
@@ -164,7 +266,7 @@ impl WidgetBehavior for DynamicCycleButton {
         // 1. Get the atomic value from the map
         let atomic = state
             .dynamic_states
-            .entry(self.id)
+            .entry(Arc::clone(&self.id))
             .or_insert(AtomicU8::new(0));
 
         // 2. Perform the atomic swap/cycle
@@ -178,35 +280,34 @@ impl WidgetBehavior for DynamicCycleButton {
             }
         }
 
-        // 3. Get the leaked command string for the new state
-        let command = self.states.get(target as usize)?.1;
+        // 3. Get the command for the new state
+        let command = &self.states.get(target as usize)?.1;
 
-        Some((target, Request::Command(command.to_string())))
+        Some((
+            target,
+            Request::Command {
+                cmd: command.to_string(),
+                capture: self.capture,
+            },
+        ))
     }
 
-    fn icon_name(&self, val: u8) -> &'static str {
-        if self.states.is_empty() {
-            return "image-missing";
-        }
-
-        let max_idx = self.states.len() - 1;
-        // Integer rounding: (val * max + 50) / 100
-        let index = ((val as usize * max_idx) + 50) / 100;
-        let safe_index = index.min(max_idx);
-
-        self.states[safe_index].0
+    fn icon_name(&self, val: u8) -> &str {
+        icon_index(self.states.len(), val)
+            .map(|i| &*self.states[i].0)
+            .unwrap_or("image-missing")
     }
 
     fn get_percentage(&self, state: &AtomicSystemState) -> u8 {
         state
             .dynamic_states
-            .get(self.id)
+            .get(&*self.id)
             .map(|a| a.load(Ordering::Relaxed))
             .unwrap_or(0)
     }
 
     fn set_percentage(&self, state: &AtomicSystemState, value: u8) {
-        if let Some(atomic) = state.dynamic_states.get(self.id) {
+        if let Some(atomic) = state.dynamic_states.get(&*self.id) {
             atomic.store(value, Ordering::Relaxed);
         }
     }
@@ -215,3 +316,40 @@ impl WidgetBehavior for DynamicCycleButton {
         self.func
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_icon_index_rounds_to_nearest() {
+        assert_eq!(icon_index(0, 50), None);
+        assert_eq!(icon_index(1, 0), Some(0));
+        assert_eq!(icon_index(1, 100), Some(0));
+        // Four icons sit at 0, 33, 67 and 100 percent
+        assert_eq!(icon_index(4, 0), Some(0));
+        assert_eq!(icon_index(4, 16), Some(0));
+        assert_eq!(icon_index(4, 17), Some(1));
+        assert_eq!(icon_index(4, 50), Some(2));
+        assert_eq!(icon_index(4, 84), Some(3));
+        assert_eq!(icon_index(4, 100), Some(3));
+        assert_eq!(icon_index(4, 255), Some(3));
+    }
+
+    #[test]
+    fn test_cycle_icons() {
+        let button = DynamicCycleButton {
+            states: [("a", "true"), ("b", "false")]
+                .into_iter()
+                .map(|(i, c)| (i.into(), c.into()))
+                .collect(),
+            id: "custom".into(),
+            max_states: 2,
+            capture: false,
+            func: BackendFuncType::Custom,
+        };
+        assert_eq!(button.icon_name(0), "a");
+        assert_eq!(button.icon_name(100), "b");
+        assert_eq!(range_icon(&[], 50), "image-missing");
+    }
+}
@@ -0,0 +1,301 @@
+use std::{borrow::Cow, env, fmt::Display, sync::OnceLock};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Timelike};
+use pure_rust_locales::{Locale, locale_match};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::config::LocaleConfig;
+
+/// Whether times are written with 12 or 24 hours
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockFormat {
+    #[serde(rename = "12h")]
+    H12,
+    #[serde(rename = "24h")]
+    H24,
+    /// Like the locale of `LC_TIME`
+    #[default]
+    Auto,
+}
+
+static CLOCK_FORMAT: OnceLock<ClockFormat> = OnceLock::new();
+
+pub fn configure(config: &LocaleConfig) {
+    let _ = CLOCK_FORMAT.set(config.clock_format);
+}
+
+/// Locale dates and times are written in, from `LC_ALL`, `LC_TIME` or `LANG` like libc does
+pub fn time_locale() -> Locale {
+    static LOCALE: OnceLock<Locale> = OnceLock::new();
+    *LOCALE.get_or_init(|| from_env(&["LC_ALL", "LC_TIME", "LANG"]).unwrap_or(Locale::POSIX))
+}
+
+fn is_12h() -> bool {
+    match CLOCK_FORMAT.get().copied().unwrap_or_default() {
+        ClockFormat::H12 => true,
+        ClockFormat::H24 => false,
+        ClockFormat::Auto => {
+            let t_fmt = locale_match!(time_locale() => LC_TIME::T_FMT);
+            ["%p", "%r", "%I", "%l"].iter().any(|s| t_fmt.contains(s))
+        }
+    }
+}
+
+/// Hours and minutes by `clock_format`, `%H:%M` or `%-I:%M %p`
+pub fn time_format() -> &'static str {
+    if is_12h() { "%-I:%M %p" } else { "%H:%M" }
+}
+
+/// Full hours by `clock_format`, `%H:%M` or `%-I %p`
+pub fn hour_format() -> &'static str {
+    if is_12h() { "%-I %p" } else { "%H:%M" }
+}
+
+/// Day and short month in the order of the locale's dates, e.g. `Mar 4` or `4 Mär`
+pub fn day_format() -> &'static str {
+    let d_fmt = locale_match!(time_locale() => LC_TIME::D_FMT);
+    let day = d_fmt.find(['d', 'e']);
+    let month = d_fmt.find(['m', 'b']);
+    match (day, month) {
+        (Some(day), Some(month)) if day < month => "%-d %b",
+        _ => "%b %-d",
+    }
+}
+
+pub fn format_date(date: NaiveDate, fmt: &str) -> String {
+    date.format_localized(fmt, time_locale()).to_string()
+}
+
+pub fn format_time(time: NaiveTime, fmt: &str) -> String {
+    // Only dates know how to localize
+    NaiveDate::default()
+        .and_time(time)
+        .and_utc()
+        .format_localized(&am_pm(fmt, time.hour()), time_locale())
+        .to_string()
+}
+
+pub fn format_datetime<Tz: TimeZone>(time: &DateTime<Tz>, fmt: &str) -> String
+where
+    Tz::Offset: Display,
+{
+    time.format_localized(&am_pm(fmt, time.hour()), time_locale())
+        .to_string()
+}
+
+/// Spells out `%p` for locales that have no AM and PM, which would leave 12-hour times ambiguous
+fn am_pm(fmt: &str, hour: u32) -> Cow<'_, str> {
+    let names = locale_match!(time_locale() => LC_TIME::AM_PM);
+    if !fmt.contains("%p") || names.iter().all(|name| !name.is_empty()) {
+        return Cow::Borrowed(fmt);
+    }
+    Cow::Owned(fmt.replace("%p", if hour < 12 { "AM" } else { "PM" }))
+}
+
+/// `text` in the language of `LC_ALL`, `LC_MESSAGES` or `LANG`, itself if there is no
+/// translation
+pub fn tr(text: &'static str) -> &'static str {
+    static LANGUAGE: OnceLock<Option<String>> = OnceLock::new();
+    let language = LANGUAGE.get_or_init(|| {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| value.split(['_', '.', '@']).next().map(String::from))
+    });
+    let column = match language.as_deref() {
+        Some("de") => 0,
+        Some("fr") => 1,
+        Some("es") => 2,
+        _ => return text,
+    };
+    TRANSLATIONS
+        .iter()
+        .find(|(key, _)| *key == text)
+        .map_or(text, |(_, translated)| translated[column])
+}
+
+/// Widget strings in German, French and Spanish
+const TRANSLATIONS: &[(&str, [&str; 3])] = &[
+    ("Disconnected", ["Getrennt", "Déconnecté", "Desconectado"]),
+    ("Speed", ["Geschwindigkeit", "Vitesse", "Velocidad"]),
+    ("connected", ["verbunden", "connecté", "conectado"]),
+    ("unplugged", ["ausgesteckt", "débranché", "desenchufado"]),
+    ("Search", ["Suchen", "Rechercher", "Buscar"]),
+    ("Refresh", ["Aktualisieren", "Actualiser", "Actualizar"]),
+    ("Starts", ["Beginnt", "Début", "Empieza"]),
+    ("Ends", ["Endet", "Fin", "Termina"]),
+    ("Attendees", ["Teilnehmer", "Participants", "Asistentes"]),
+    (
+        "Search events",
+        [
+            "Termine durchsuchen",
+            "Rechercher des événements",
+            "Buscar eventos",
+        ],
+    ),
+    (
+        "No events found",
+        [
+            "Keine Termine gefunden",
+            "Aucun événement trouvé",
+            "No se encontraron eventos",
+        ],
+    ),
+    ("Copied", ["Kopiert", "Copié", "Copiado"]),
+    (
+        "notification while you were away",
+        [
+            "Benachrichtigung während deiner Abwesenheit",
+            "notification pendant votre absence",
+            "notificación mientras no estabas",
+        ],
+    ),
+    (
+        "notifications while you were away",
+        [
+            "Benachrichtigungen während deiner Abwesenheit",
+            "notifications pendant votre absence",
+            "notificaciones mientras no estabas",
+        ],
+    ),
+    (
+        "No colors picked yet",
+        [
+            "Noch keine Farben ausgewählt",
+            "Aucune couleur choisie",
+            "Aún no hay colores elegidos",
+        ],
+    ),
+    ("Camera", ["Kamera", "Caméra", "Cámara"]),
+    ("Microphone", ["Mikrofon", "Microphone", "Micrófono"]),
+    ("Sunrise", ["Sonnenaufgang", "Lever du soleil", "Amanecer"]),
+    ("Free", ["Frei", "Libre", "Libre"]),
+    ("Free until", ["Frei bis", "Libre jusqu'à", "Libre hasta"]),
+    ("Free from", ["Frei ab", "Libre à partir de", "Libre desde"]),
+    ("Accept", ["Annehmen", "Accepter", "Aceptar"]),
+    ("Maybe", ["Vielleicht", "Peut-être", "Quizás"]),
+    ("Decline", ["Ablehnen", "Refuser", "Rechazar"]),
+    ("Organizer", ["Organisator", "Organisateur", "Organizador"]),
+    (
+        "Invited as",
+        ["Eingeladen als", "Invité en tant que", "Invitado como"],
+    ),
+    (
+        "Next free",
+        [
+            "Nächste freie Zeit",
+            "Prochain créneau libre",
+            "Próximo hueco libre",
+        ],
+    ),
+    (
+        "Sunset",
+        ["Sonnenuntergang", "Coucher du soleil", "Atardecer"],
+    ),
+    ("N/A", ["k. A.", "n. d.", "n/d"]),
+    (
+        "Unavailable",
+        ["Nicht verfügbar", "Indisponible", "No disponible"],
+    ),
+    (
+        "No devices connected",
+        [
+            "Keine Geräte verbunden",
+            "Aucun appareil connecté",
+            "Ningún dispositivo conectado",
+        ],
+    ),
+    (
+        "Sign-in required",
+        [
+            "Anmeldung erforderlich",
+            "Connexion requise",
+            "Se requiere iniciar sesión",
+        ],
+    ),
+    (
+        "Open portal",
+        ["Portal öffnen", "Ouvrir le portail", "Abrir el portal"],
+    ),
+    ("Notes", ["Notizen", "Notes", "Notas"]),
+    ("Note", ["Notiz", "Note", "Nota"]),
+    ("New note", ["Neue Notiz", "Nouvelle note", "Nueva nota"]),
+    ("Draw", ["Zeichnen", "Dessiner", "Dibujar"]),
+    (
+        "Clear drawing",
+        ["Zeichnung löschen", "Effacer le dessin", "Borrar el dibujo"],
+    ),
+    (
+        "No unread mail",
+        [
+            "Keine ungelesenen E-Mails",
+            "Aucun message non lu",
+            "No hay correo sin leer",
+        ],
+    ),
+    (
+        "No focus profiles",
+        [
+            "Keine Fokusprofile",
+            "Aucun profil de concentration",
+            "No hay perfiles de concentración",
+        ],
+    ),
+    (
+        "No screen time yet",
+        [
+            "Noch keine Bildschirmzeit",
+            "Pas encore de temps d'écran",
+            "Aún no hay tiempo de pantalla",
+        ],
+    ),
+    ("Limit", ["Limit", "Limite", "Límite"]),
+    ("Snooze", ["Schlummern", "Reporter", "Posponer"]),
+    ("15 minutes", ["15 Minuten", "15 minutes", "15 minutos"]),
+    ("1 hour", ["1 Stunde", "1 heure", "1 hora"]),
+    ("minutes", ["Minuten", "minutes", "minutos"]),
+    (
+        "No additional details.",
+        [
+            "Keine weiteren Details.",
+            "Aucun détail supplémentaire.",
+            "Sin más detalles.",
+        ],
+    ),
+];
+
+fn from_env(vars: &[&str]) -> Option<Locale> {
+    let value = vars
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())?;
+    parse(&value)
+}
+
+/// `de_DE.UTF-8@euro` to `de_DE`. Bare languages like `de` get their main country.
+fn parse(value: &str) -> Option<Locale> {
+    let name = value.split(['.', '@']).next()?;
+    match name {
+        "C" | "POSIX" => Some(Locale::POSIX),
+        _ => Locale::try_from(name)
+            .or_else(|_| Locale::try_from(format!("{name}_{}", name.to_uppercase()).as_str()))
+            .ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_locale_names() {
+        assert!(parse("de_DE.UTF-8") == Some(Locale::de_DE));
+        assert!(parse("fr_FR@euro") == Some(Locale::fr_FR));
+        assert!(parse("de") == Some(Locale::de_DE));
+        assert!(parse("C.UTF-8") == Some(Locale::POSIX));
+        assert!(parse("xx_YY").is_none());
+    }
+}
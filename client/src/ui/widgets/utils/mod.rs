@@ -1,12 +1,42 @@
-use gtk4::glib::{
-    WeakRef,
-    object::{ObjectExt, ObjectType},
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use gtk4::{
+    Popover, Widget,
+    glib::{
+        WeakRef,
+        object::{IsA, ObjectExt, ObjectType},
+    },
+    prelude::WidgetExt,
 };
 
 pub mod animation;
 pub mod backend_functions;
 pub mod interactives;
+pub mod locale;
+pub mod pending;
+pub mod power;
+pub mod refresh;
 pub mod render;
+pub mod state;
+pub mod text;
+
+/// Set while the system sleeps or the session is idle, periodic redraws are skipped meanwhile
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_suspended() -> bool {
+    SUSPENDED.load(Ordering::Relaxed)
+}
+pub fn set_suspended(suspended: bool) {
+    SUSPENDED.store(suspended, Ordering::Relaxed);
+}
+
+/// Parents `popover` to `parent` and unparents it again once `parent` is destroyed. Popovers are
+/// not owned by their parent and have to be unparented manually
+pub fn attach_popover(popover: &Popover, parent: &impl IsA<Widget>) {
+    popover.set_parent(parent);
+    let popover = popover.clone();
+    parent.connect_destroy(move |_| popover.unparent());
+}
 
 pub enum WidgetOption<T: ObjectType> {
     Borrowed(WeakRef<T>),
@@ -0,0 +1,63 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+
+use suite_223b::protocol::{AtomicSystemState, Request};
+
+use crate::ui::widgets::{BackendFuncType, utils::interactives::WidgetBehavior};
+
+/// A value the UI shows before the daemon confirmed it
+struct PendingChange {
+    id: u32,
+    /// Last value the daemon confirmed, restored if the change fails
+    previous: u8,
+    func: Box<dyn WidgetBehavior>,
+}
+
+thread_local! {
+    static NEXT_ID: Cell<u32> = const { Cell::new(0) };
+    static PENDING: RefCell<HashMap<BackendFuncType, PendingChange>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Remembers `previous` until the daemon acknowledges `request` and wraps it so it does.
+/// Another change of the same function before the acknowledgment keeps the original
+/// `previous`.
+pub fn track(func: Box<dyn WidgetBehavior>, previous: u8, request: Request) -> Request {
+    let id = NEXT_ID.with(|n| {
+        let id = n.get().wrapping_add(1);
+        n.set(id);
+        id
+    });
+    PENDING.with_borrow_mut(|pending| {
+        let previous = pending
+            .get(&func.func())
+            .map(|p| p.previous)
+            .unwrap_or(previous);
+        pending.insert(func.func(), PendingChange { id, previous, func });
+    });
+    Request::Tracked {
+        id,
+        request: Box::new(request),
+    }
+}
+
+/// Settles the change acknowledged with `id`. Returns whether it was rolled back, in which
+/// case the widgets showing it have to be refreshed.
+pub fn resolve(id: u32, failed: bool, state: &AtomicSystemState) -> bool {
+    let Some(change) = PENDING.with_borrow_mut(|pending| {
+        let func = pending
+            .iter()
+            .find_map(|(func, p)| (p.id == id).then_some(*func))?;
+        pending.remove(&func)
+    }) else {
+        // Superseded by a newer change of the same function
+        return false;
+    };
+
+    if failed {
+        change.func.set_percentage(state, change.previous);
+    }
+    failed
+}
@@ -0,0 +1,66 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+use gtk4::{
+    gio,
+    glib::{self, ControlFlow},
+};
+use suite_223b::{protocol::BatteryState, utils::battery::read_battery};
+
+use crate::{config::PowerConfig, ui::widgets::utils::is_suspended};
+
+/// Whether the config allows saving power at all
+static ENABLED: AtomicBool = AtomicBool::new(true);
+/// Set while the battery discharges, intervals stretch and animations pause meanwhile
+static SAVING: AtomicBool = AtomicBool::new(false);
+static STRETCH: AtomicU32 = AtomicU32::new(4);
+
+pub fn configure(config: &PowerConfig) {
+    ENABLED.store(config.power_saving, Ordering::Relaxed);
+    STRETCH.store(config.stretch.max(1), Ordering::Relaxed);
+    // The daemon only reports changes. Read off the main thread, the BSD sources wait for
+    // subprocesses.
+    glib::spawn_future_local(async {
+        let Ok(read) = gio::spawn_blocking(read_battery).await else {
+            return;
+        };
+        battery_changed(read.map_or(BatteryState::Invalid, |(state, _)| state));
+    });
+}
+
+pub fn is_saving_power() -> bool {
+    SAVING.load(Ordering::Relaxed)
+}
+
+/// Returns whether power saving turned on or off
+pub fn battery_changed(state: BatteryState) -> bool {
+    let saving = ENABLED.load(Ordering::Relaxed) && state == BatteryState::Discharging;
+    SAVING.swap(saving, Ordering::Relaxed) != saving
+}
+
+/// `seconds`, or longer while saving power
+pub fn interval(seconds: u32) -> u32 {
+    if is_saving_power() {
+        seconds.saturating_mul(STRETCH.load(Ordering::Relaxed))
+    } else {
+        seconds
+    }
+}
+
+/// Calls `f` every `seconds` until it breaks. Runs are skipped while suspended and spaced out
+/// while saving power.
+pub fn every<F: FnMut() -> ControlFlow + 'static>(seconds: u32, f: F) {
+    schedule(seconds.max(1), Rc::new(RefCell::new(f)));
+}
+
+fn schedule<F: FnMut() -> ControlFlow + 'static>(seconds: u32, f: Rc<RefCell<F>>) {
+    glib::timeout_add_seconds_local_once(interval(seconds), move || {
+        if !is_suspended() && (f.borrow_mut())() == ControlFlow::Break {
+            return;
+        }
+        schedule(seconds, f);
+    });
+}
@@ -0,0 +1,58 @@
+use gtk4::{
+    Button, Overlay, Spinner, Widget,
+    glib::{WeakRef, object::ObjectExt},
+    prelude::{ButtonExt, IsA, WidgetExt},
+};
+use suite_223b::protocol::{DataKind, Request};
+
+use crate::{DAEMON_TX, ui::widgets::utils::locale::tr};
+
+/// Asks the daemon to fetch `kind` again, it answers with `Response::Refreshing`
+pub fn request(kind: DataKind) {
+    let _result = DAEMON_TX.get().map(|d| d.send(Request::ForceRefresh(kind)));
+}
+
+/// Refresh button in the top right corner of a widget, replaced by a spinner while the refresh
+/// is in flight
+#[derive(Clone, Debug)]
+pub struct RefreshOverlay {
+    button: WeakRef<Button>,
+    spinner: WeakRef<Spinner>,
+}
+impl RefreshOverlay {
+    /// Wraps `child`, the returned overlay takes its place
+    pub fn new(child: &impl IsA<Widget>, kind: DataKind) -> (Overlay, Self) {
+        let overlay = Overlay::builder().child(child).build();
+        let button = Button::builder()
+            .icon_name("view-refresh-symbolic")
+            .tooltip_text(tr("Refresh"))
+            .css_classes(["flat", "refresh-button"])
+            .halign(gtk4::Align::End)
+            .valign(gtk4::Align::Start)
+            .build();
+        button.connect_clicked(move |_| request(kind));
+        let spinner = Spinner::builder()
+            .css_classes(["refresh-spinner"])
+            .halign(gtk4::Align::End)
+            .valign(gtk4::Align::Start)
+            .visible(false)
+            .build();
+        overlay.add_overlay(&button);
+        overlay.add_overlay(&spinner);
+
+        let refresh = Self {
+            button: button.downgrade(),
+            spinner: spinner.downgrade(),
+        };
+        (overlay, refresh)
+    }
+
+    pub fn set_refreshing(&self, refreshing: bool) {
+        let (Some(button), Some(spinner)) = (self.button.upgrade(), self.spinner.upgrade()) else {
+            return;
+        };
+        button.set_visible(!refreshing);
+        spinner.set_visible(refreshing);
+        spinner.set_spinning(refreshing);
+    }
+}
@@ -1,6 +1,32 @@
 use std::str::FromStr;
 
-use gtk4::{cairo::Context, gdk::RGBA};
+use gtk4::{
+    Widget,
+    cairo::Context,
+    gdk::RGBA,
+    prelude::{IsA, NativeExt, SurfaceExt, WidgetExt},
+};
+
+use crate::ui::theme::named_color;
+
+/// Sunrise, sunset and golden hour markers
+pub const SUN_COLOR: &str = "#e9a949";
+
+/// Ratio between device and logical pixels of the surface the widget is shown on.
+///
+/// Fractional on scaled outputs (e.g. `1.5`), unlike `scale_factor()` which rounds up.
+pub fn device_scale(widget: &impl IsA<Widget>) -> f64 {
+    widget
+        .native()
+        .and_then(|n| n.surface())
+        .map(|s| s.scale())
+        .unwrap_or_else(|| widget.scale_factor() as f64)
+}
+
+/// Rounds a logical coordinate to the closest device pixel boundary
+pub fn snap(value: f64, scale: f64) -> f64 {
+    (value * scale).round() / scale
+}
 
 pub struct CairoShapesExt;
 impl CairoShapesExt {
@@ -79,39 +105,6 @@ impl CairoShapesExt {
         ctx.new_path();
         ctx.arc(x, y, radius, start_angle, end_angle);
     }
-    pub fn centered_text(ctx: &Context, text: &str, cx: f64, cy: f64) {
-        let ext = ctx.text_extents(text).unwrap();
-        let font_ext = ctx.font_extents().unwrap();
-
-        // Horizontal: center ink box
-        let x = cx - (ext.width() / 2.0 + ext.x_bearing());
-
-        // Vertical: center using baseline + ascent/descent
-        let y = cy + (font_ext.ascent() - font_ext.descent()) / 2.0;
-
-        ctx.move_to(x, y);
-        ctx.show_text(text).unwrap();
-    }
-    pub fn vert_centered_text(ctx: &Context, text: &str, cx: f64, cy: f64) {
-        let font_ext = ctx.font_extents().unwrap();
-        let y = cy + (font_ext.ascent() - font_ext.descent()) / 2.0;
-        ctx.move_to(cx, y);
-        ctx.show_text(text).unwrap();
-    }
-    pub fn rjust_text(ctx: &Context, text: &str, cx: f64, cy: f64, center_vert: bool) {
-        let ext = ctx.text_extents(text).unwrap();
-
-        let y = if center_vert {
-            let font_ext = ctx.font_extents().unwrap();
-            cy + (font_ext.ascent() - font_ext.descent()) / 2.0
-        } else {
-            cy
-        };
-
-        let x = cx - (ext.width() + ext.x_bearing());
-        ctx.move_to(x, y);
-        ctx.show_text(text).unwrap();
-    }
 }
 
 #[derive(Default, Debug, Clone, Copy)]
@@ -161,6 +154,10 @@ impl FromStr for Rgba {
     type Err = ();
 
     fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        // `accent` and `palette-<n>` follow the wallpaper
+        if let Some(color) = named_color(hex) {
+            return Ok(color);
+        }
         let hex = hex.trim_start_matches('#');
         let len = hex.len();
         if len != 6 && len != 8 {
@@ -246,6 +243,12 @@ impl From<RGBA> for Rgba {
     }
 }
 
+impl From<Rgba> for RGBA {
+    fn from(v: Rgba) -> Self {
+        RGBA::new(v.r as f32, v.g as f32, v.b as f32, v.a as f32)
+    }
+}
+
 fn hue_to_rgb(p: f64, q: f64, t: f64) -> f64 {
     let mut t = t;
     if t < 0.0 {
@@ -0,0 +1,37 @@
+use gtk4::{Widget, glib::object::IsA, prelude::WidgetExt};
+
+/// Classes the custom widgets set as their state changes, next to their own class, e.g.
+/// `.battery.warning`, `.slider-obj.dragging` or `.button.active`. Themes can rely on these.
+///
+/// - `.active`: Button, its function is on, any `state-<n>` but `state-0`
+/// - `.disabled`: Slider, its backend is gone or the dock config turns it off
+/// - `.dragging`: Slider, while the user drags it
+/// - `.charging`: Battery, plugged in
+/// - `.warning`: Battery, discharging at or below `threshold`
+/// - `.critical`: Battery, discharging at or below half of `threshold`
+/// - `.stale`: Battery and Calendar, the last update failed and older data is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::AsRefStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum StateClass {
+    Active,
+    Disabled,
+    Dragging,
+    Charging,
+    Warning,
+    Critical,
+    Stale,
+}
+impl StateClass {
+    /// Adds or removes the class, leaving the widget alone if nothing changes
+    pub fn set(self, widget: &impl IsA<Widget>, on: bool) {
+        let class = self.as_ref();
+        if widget.has_css_class(class) == on {
+            return;
+        }
+        if on {
+            widget.add_css_class(class);
+        } else {
+            widget.remove_css_class(class);
+        }
+    }
+}
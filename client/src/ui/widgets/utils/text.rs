@@ -0,0 +1,75 @@
+use gtk4::{
+    cairo::Context,
+    pango::{self, AttrInt, AttrList, EllipsizeMode, FontDescription, Layout, Weight},
+};
+
+/// Text shaped and drawn through Pango.
+///
+/// Unlike the Cairo "toy" text API this handles complex scripts, mixed RTL/LTR text and
+/// ellipsizing on grapheme boundaries.
+pub struct TextLayout {
+    layout: Layout,
+}
+impl TextLayout {
+    pub fn new(ctx: &Context, text: &str, font: &str, size: f64, weight: Weight) -> Self {
+        let layout = pangocairo::functions::create_layout(ctx);
+
+        let mut desc = FontDescription::from_string(font);
+        desc.set_absolute_size(size * pango::SCALE as f64);
+        desc.set_weight(weight);
+
+        layout.set_font_description(Some(&desc));
+        layout.set_auto_dir(true);
+        layout.set_single_paragraph_mode(true);
+        layout.set_text(text);
+
+        Self { layout }
+    }
+    /// Ellipsizes the text at the end once it exceeds `width` pixels
+    pub fn max_width(self, width: f64) -> Self {
+        self.layout
+            .set_width((width.max(0.0) * pango::SCALE as f64) as i32);
+        self.layout.set_ellipsize(EllipsizeMode::End);
+        self
+    }
+    /// Strikes the text through, e.g. for declined events
+    pub fn strikethrough(self, strike: bool) -> Self {
+        if strike {
+            let attrs = AttrList::new();
+            attrs.insert(AttrInt::new_strikethrough(true));
+            self.layout.set_attributes(Some(&attrs));
+        }
+        self
+    }
+    /// Logical size in pixels
+    pub fn size(&self) -> (f64, f64) {
+        let (w, h) = self.layout.pixel_size();
+        (w as f64, h as f64)
+    }
+    /// Distance from the top of the layout to the baseline of the first line
+    pub fn baseline(&self) -> f64 {
+        self.layout.baseline() as f64 / pango::SCALE as f64
+    }
+
+    /// Draws the layout with its top left corner at `(x, y)`
+    pub fn show(&self, ctx: &Context, x: f64, y: f64) {
+        ctx.move_to(x, y);
+        pangocairo::functions::show_layout(ctx, &self.layout);
+    }
+    /// Draws the layout with its baseline at `y`, like `show_text` would
+    pub fn show_baseline(&self, ctx: &Context, x: f64, y: f64) {
+        self.show(ctx, x, y - self.baseline());
+    }
+    pub fn show_centered(&self, ctx: &Context, cx: f64, cy: f64) {
+        let (w, h) = self.size();
+        self.show(ctx, cx - w / 2.0, cy - h / 2.0);
+    }
+    pub fn show_vert_centered(&self, ctx: &Context, x: f64, cy: f64) {
+        let (_, h) = self.size();
+        self.show(ctx, x, cy - h / 2.0);
+    }
+    pub fn show_rjust(&self, ctx: &Context, x: f64, cy: f64) {
+        let (w, h) = self.size();
+        self.show(ctx, x - w, cy - h / 2.0);
+    }
+}
@@ -1,41 +1,269 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+
 use gtk4::{
-    EventControllerKey, PropagationPhase,
-    glib::object::ObjectExt,
-    prelude::{EventControllerExt, GtkWindowExt, WidgetExt},
+    EventControllerKey, EventControllerMotion, PropagationPhase, Widget,
+    cairo::{RectangleInt, Region},
+    gdk::FrameClock,
+    glib::{self, SourceId, object::ObjectExt},
+    prelude::{EventControllerExt, GtkWindowExt, NativeExt, SurfaceExt, WidgetExt},
+};
+use gtk4_layer_shell::{Edge, LayerShell};
+
+use crate::{
+    config::{AutohideConfig, WindowConfig, load_window_config},
+    ui::{
+        WatsonUi, g_templates::main_window::MainWindow, output::place_window,
+        widgets::EaseFunction, window_state::WindowState,
+    },
 };
-use gtk4_layer_shell::LayerShell;
 
-use crate::ui::{WatsonUi, g_templates::main_window::MainWindow};
+/// Layer-shell namespace compositor rules can match to blur behind the window
+const BLUR_NAMESPACE: &str = "watson-blur";
 
 impl WatsonUi {
     pub fn window(&mut self) -> MainWindow {
-        let win = MainWindow::new(100, 1.0);
+        let config = load_window_config().unwrap_or_else(|e| {
+            eprintln!("{:?}", e);
+            WindowConfig::default()
+        });
+        let win = MainWindow::new(100, config.opacity.unwrap_or(1.0).clamp(0.0, 1.0));
 
         win.init_layer_shell();
+        if config.blur {
+            win.set_namespace(Some(BLUR_NAMESPACE));
+        }
         win.set_layer(gtk4_layer_shell::Layer::Top);
         win.set_anchor(gtk4_layer_shell::Edge::Top, true);
         win.set_anchor(gtk4_layer_shell::Edge::Right, true);
         win.set_anchor(gtk4_layer_shell::Edge::Bottom, true);
         win.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::OnDemand);
         win.set_exclusive_zone(0);
+        close_on_escape(&win);
+        if let Some(autohide) = config.autohide {
+            Autohide::attach(&win, autohide);
+        }
+        if let Some(targets) = &config.monitor {
+            place_window(&win, targets.clone());
+        }
+        if !config.pin {
+            self.hidden = !WindowState::attach(&win, config.monitor.is_none());
+        }
+
+        self.window = win.downgrade();
+        win
+    }
+    /// A plain resizable window for `watson --preview`, leaving the desktop surface alone
+    pub fn preview_window(&mut self) -> MainWindow {
+        let win = MainWindow::new(420, 1.0);
+        win.set_default_height(720);
+        win.set_title(Some("Watson Preview"));
+        close_on_escape(&win);
+
+        self.window = win.downgrade();
+        win
+    }
+}
+
+fn close_on_escape(win: &MainWindow) {
+    let controller = EventControllerKey::new();
+    controller.set_propagation_phase(PropagationPhase::Bubble);
+    controller.connect_key_pressed({
+        let win = win.downgrade();
+        move |_gesture, key, _keycode, _state| {
+            if key == gtk4::gdk::Key::Escape {
+                if let Some(win) = win.upgrade() {
+                    win.close();
+                    return gtk4::glib::Propagation::Stop;
+                }
+            }
+            gtk4::glib::Propagation::Proceed
+        }
+    });
+    win.add_controller(controller);
+}
+
+/// Leaves the widgets `passthrough` returns out of the window's input region after every frame,
+/// so clicks on them reach whatever is below the window
+pub fn connect_input_region(win: &MainWindow, passthrough: impl Fn() -> Vec<Widget> + 'static) {
+    let passthrough = Rc::new(passthrough);
+    win.connect_realize(move |win| {
+        let Some(clock) = win.surface().and_then(|s| s.frame_clock()) else {
+            return;
+        };
+        let applied = RefCell::new(None::<Region>);
+        clock.connect_after_paint({
+            let win = win.downgrade();
+            let passthrough = Rc::clone(&passthrough);
+            move |_| {
+                let Some(win) = win.upgrade() else {
+                    return;
+                };
+                let region = input_region(&win, &passthrough());
+                // Setting the same region again would only cost another commit
+                if region.is_none() || *applied.borrow() == region {
+                    return;
+                }
+                if let (Some(surface), Some(region)) = (win.surface(), &region) {
+                    surface.set_input_region(region);
+                }
+                applied.replace(region);
+            }
+        });
+    });
+}
+
+/// The whole surface minus the bounds of `passthrough`, in surface coordinates
+fn input_region(win: &MainWindow, passthrough: &[Widget]) -> Option<Region> {
+    let surface = win.surface()?;
+    let region =
+        Region::create_rectangle(&RectangleInt::new(0, 0, surface.width(), surface.height()));
+    let (dx, dy) = win.surface_transform();
+    for widget in passthrough {
+        let Some(bounds) = widget.compute_bounds(win) else {
+            continue;
+        };
+        let x = (bounds.x() as f64 + dx).floor() as i32;
+        let y = (bounds.y() as f64 + dy).floor() as i32;
+        let rect = RectangleInt::new(
+            x,
+            y,
+            (bounds.x() as f64 + dx + bounds.width() as f64).ceil() as i32 - x,
+            (bounds.y() as f64 + dy + bounds.height() as f64).ceil() as i32 - y,
+        );
+        region.subtract_rectangle(&rect).ok()?;
+    }
+    Some(region)
+}
 
-        let controller = EventControllerKey::new();
-        controller.set_propagation_phase(PropagationPhase::Bubble);
-        controller.connect_key_pressed({
+/// Slides the window off its screen edge with a negative layer-shell margin, leaving `peek`
+/// pixels for the pointer to reveal it again
+struct Autohide {
+    config: AutohideConfig,
+    /// Pixels the window is pushed off screen, 0 is fully shown
+    offset: Cell<f64>,
+    /// Offsets of the running slide
+    from: Cell<f64>,
+    to: Cell<f64>,
+    /// Frame time the running slide started at, in microseconds
+    started: Cell<Option<i64>>,
+    running: Cell<bool>,
+    hide_timeout: RefCell<Option<SourceId>>,
+}
+impl Autohide {
+    fn attach(win: &MainWindow, config: AutohideConfig) {
+        let autohide = Rc::new(Self {
+            config,
+            offset: Cell::new(0.0),
+            from: Cell::new(0.0),
+            to: Cell::new(0.0),
+            started: Cell::new(None),
+            running: Cell::new(false),
+            hide_timeout: RefCell::new(None),
+        });
+
+        let motion = EventControllerMotion::new();
+        motion.connect_enter({
+            let autohide = Rc::clone(&autohide);
+            let win = win.downgrade();
+            move |_, _, _| {
+                if let Some(win) = win.upgrade() {
+                    autohide.reveal(&win);
+                }
+            }
+        });
+        motion.connect_leave({
+            let autohide = Rc::clone(&autohide);
             let win = win.downgrade();
-            move |_gesture, key, _keycode, _state| {
-                if key == gtk4::gdk::Key::Escape {
-                    if let Some(win) = win.upgrade() {
-                        win.close();
-                        return gtk4::glib::Propagation::Stop;
+            move |_| {
+                if let Some(win) = win.upgrade() {
+                    autohide.hide_later(&win);
+                }
+            }
+        });
+        win.add_controller(motion);
+
+        // Shown on request, e.g. `watson show`, it stays until the pointer was there and left
+        win.connect_visible_notify({
+            let autohide = Rc::clone(&autohide);
+            move |win| {
+                if win.is_visible() {
+                    autohide.reveal(win);
+                }
+            }
+        });
+
+        autohide.hide_later(win);
+    }
+
+    fn slide_to(self: &Rc<Self>, win: &MainWindow, offset: f64) {
+        self.from.set(self.offset.get());
+        self.to.set(offset);
+        self.started.set(None);
+        // The frame clock only runs while sliding
+        if !self.running.replace(true) {
+            win.add_tick_callback({
+                let autohide = Rc::clone(self);
+                move |win, frame_clock| {
+                    if autohide.update(win, frame_clock) {
+                        glib::ControlFlow::Continue
+                    } else {
+                        glib::ControlFlow::Break
                     }
                 }
-                gtk4::glib::Propagation::Proceed
+            });
+        }
+    }
+
+    fn reveal(self: &Rc<Self>, win: &MainWindow) {
+        if let Some(source) = self.hide_timeout.take() {
+            source.remove();
+        }
+        self.slide_to(win, 0.0);
+    }
+
+    fn hide_later(self: &Rc<Self>, win: &MainWindow) {
+        if let Some(source) = self.hide_timeout.take() {
+            source.remove();
+        }
+        let source = glib::timeout_add_local_once(Duration::from_millis(self.config.delay), {
+            let autohide = Rc::clone(self);
+            let win = win.downgrade();
+            move || {
+                autohide.hide_timeout.take();
+                if let Some(win) = win.upgrade() {
+                    let hidden = (win.width() - autohide.config.peek).max(0);
+                    autohide.slide_to(&win, hidden as f64);
+                }
             }
         });
-        win.add_controller(controller);
+        self.hide_timeout.replace(Some(source));
+    }
 
-        self.window = win.downgrade();
-        win
+    /// Moves the window one frame further, false once the slide is done
+    fn update(&self, win: &MainWindow, frame_clock: &FrameClock) -> bool {
+        let now = frame_clock.frame_time();
+        let started = self.started.get().unwrap_or(now);
+        self.started.set(Some(started));
+        let duration = self.config.duration.max(1) as f64 * 1000.0;
+        let linear = ((now - started) as f64 / duration).min(1.0);
+
+        // Accelerate off screen, decelerate into view
+        let (from, to) = (self.from.get(), self.to.get());
+        let function = if to > from {
+            EaseFunction::EaseIn
+        } else {
+            EaseFunction::EaseOutCubic
+        };
+        let offset = from + (to - from) * function.apply(linear);
+        self.offset.set(offset);
+        win.set_margin(Edge::Right, -(offset.round() as i32));
+
+        let running = linear < 1.0;
+        self.running.set(running);
+        running
     }
 }
@@ -0,0 +1,233 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use gtk4::{
+    gdk::{Display, Monitor},
+    glib::{
+        self,
+        object::{CastNone, ObjectExt},
+    },
+    prelude::{GtkWindowExt, ListModelExt, MonitorExt, NativeExt, SurfaceExt, WidgetExt},
+};
+use gtk4_layer_shell::{Edge, LayerShell};
+use serde::{Deserialize, Serialize};
+use suite_223b::{
+    utils::{
+        errors::{ResultExt, WatsonError, WatsonErrorKind},
+        paths::get_data_dir,
+    },
+    watson_err,
+};
+
+use crate::ui::g_templates::main_window::MainWindow;
+
+const STATE_FILE: &str = "window_state.json";
+
+/// Size and position of the window on one monitor
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Geometry {
+    width: i32,
+    margin_top: i32,
+    margin_bottom: i32,
+}
+
+/// `$XDG_DATA_HOME/watson/window_state.json`, where the last session left the main window
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct SavedState {
+    /// Connector of the monitor the window was on, e.g. `DP-1`
+    monitor: Option<String>,
+    visible: bool,
+    /// By connector
+    monitors: HashMap<String, Geometry>,
+}
+impl Default for SavedState {
+    fn default() -> Self {
+        Self {
+            monitor: None,
+            visible: true,
+            monitors: HashMap::new(),
+        }
+    }
+}
+
+/// Puts the main window back where the last session left it and writes every change down.
+/// While its monitor is unplugged the window goes where the compositor puts it, and moves back
+/// once a monitor of that name shows up again.
+pub struct WindowState {
+    saved: RefCell<SavedState>,
+    /// Connector of the monitor the window is on right now
+    current: RefCell<Option<String>>,
+    path: PathBuf,
+}
+impl WindowState {
+    /// Returns whether the window should be shown on startup. `restore_monitor` is false when
+    /// the config picks the monitor.
+    pub fn attach(win: &MainWindow, restore_monitor: bool) -> bool {
+        let path = match get_data_dir() {
+            Ok(dir) => dir.join(STATE_FILE),
+            Err(e) => {
+                eprintln!("{:?}", e);
+                return true;
+            }
+        };
+        let saved = load(&path).unwrap_or_else(|e| {
+            eprintln!("{:?}", e);
+            SavedState::default()
+        });
+        let visible = saved.visible;
+        let state = Rc::new(Self {
+            saved: RefCell::new(saved),
+            current: RefCell::new(None),
+            path,
+        });
+
+        let monitor = state.saved.borrow().monitor.clone();
+        if restore_monitor && let Some(monitor) = monitor.as_deref().and_then(find_monitor) {
+            state.move_to(win, &monitor);
+        }
+
+        // The surface only exists once the window is shown
+        win.connect_realize({
+            let state = Rc::clone(&state);
+            move |win| {
+                let Some(surface) = win.surface() else {
+                    return;
+                };
+                let win = win.downgrade();
+                surface.connect_enter_monitor({
+                    let state = Rc::clone(&state);
+                    move |_, monitor| {
+                        if let Some(win) = win.upgrade() {
+                            state.entered(&win, monitor);
+                        }
+                    }
+                });
+            }
+        });
+        win.connect_visible_notify({
+            let state = Rc::clone(&state);
+            move |win| {
+                state.saved.borrow_mut().visible = win.is_visible();
+                state.save(win);
+            }
+        });
+        win.connect_close_request({
+            let state = Rc::clone(&state);
+            move |win| {
+                state.save(win);
+                glib::Propagation::Proceed
+            }
+        });
+
+        if restore_monitor && let Some(display) = Display::default() {
+            display.monitors().connect_items_changed({
+                let state = Rc::clone(&state);
+                let win = win.downgrade();
+                move |_, _, _, _| {
+                    if let Some(win) = win.upgrade() {
+                        state.monitors_changed(&win);
+                    }
+                }
+            });
+        }
+        visible
+    }
+
+    fn move_to(&self, win: &MainWindow, monitor: &Monitor) {
+        win.set_monitor(Some(monitor));
+        self.apply(win, monitor);
+    }
+
+    /// Sizes the window like it was the last time it was on `monitor`
+    fn apply(&self, win: &MainWindow, monitor: &Monitor) {
+        let Some(connector) = monitor.connector() else {
+            return;
+        };
+        let saved = self.saved.borrow();
+        if let Some(geometry) = saved.monitors.get(connector.as_str()) {
+            win.set_default_width(geometry.width);
+            win.set_margin(Edge::Top, geometry.margin_top);
+            win.set_margin(Edge::Bottom, geometry.margin_bottom);
+        }
+    }
+
+    fn entered(&self, win: &MainWindow, monitor: &Monitor) {
+        let Some(connector) = monitor.connector().map(String::from) else {
+            return;
+        };
+        if self.current.borrow().as_ref() == Some(&connector) {
+            return;
+        }
+        self.current.replace(Some(connector.clone()));
+        self.apply(win, monitor);
+
+        // Only standing in while the saved monitor is unplugged
+        let saved = self.saved.borrow().monitor.clone();
+        if saved.is_some_and(|saved| saved != connector && find_monitor(&saved).is_none()) {
+            return;
+        }
+        self.saved.borrow_mut().monitor = Some(connector);
+        self.save(win);
+    }
+
+    /// Moves the window back to its monitor once that is plugged in again
+    fn monitors_changed(&self, win: &MainWindow) {
+        let saved = self.saved.borrow().monitor.clone();
+        let Some(saved) = saved else {
+            return;
+        };
+        if self.current.borrow().as_ref() == Some(&saved) {
+            return;
+        }
+        if let Some(monitor) = find_monitor(&saved) {
+            self.move_to(win, &monitor);
+        }
+    }
+
+    fn save(&self, win: &MainWindow) {
+        let mut saved = self.saved.borrow_mut();
+        // Hidden windows have no size
+        if win.width() > 0
+            && let Some(connector) = self.current.borrow().clone()
+        {
+            let geometry = Geometry {
+                width: win.width(),
+                margin_top: win.margin(Edge::Top),
+                margin_bottom: win.margin(Edge::Bottom),
+            };
+            saved.monitors.insert(connector, geometry);
+        }
+        if let Err(e) = write(&self.path, &saved) {
+            eprintln!("{:?}", e);
+        }
+    }
+}
+
+fn find_monitor(connector: &str) -> Option<Monitor> {
+    let monitors = Display::default()?.monitors();
+    (0..monitors.n_items())
+        .filter_map(|i| monitors.item(i).and_downcast::<Monitor>())
+        .find(|m| m.connector().is_some_and(|c| c.as_str() == connector))
+}
+
+fn load(path: &Path) -> Result<SavedState, WatsonError> {
+    if !path.exists() {
+        return Ok(SavedState::default());
+    }
+    let file = File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))
+}
+
+fn write(path: &Path, state: &SavedState) -> Result<(), WatsonError> {
+    let data = serde_json::to_vec_pretty(state)
+        .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+    fs::write(path, data).with_context(|| format!("Could not write {}", path.display()))
+}
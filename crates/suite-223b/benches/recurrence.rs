@@ -0,0 +1,107 @@
+use std::{cell::Cell, hint::black_box};
+
+use chrono::{Days, Local, NaiveDate, TimeZone, Utc};
+use criterion::{Criterion, criterion_group, criterion_main};
+use suite_223b::calendar::utils::{
+    CalDavEvent, CalEventType, OccurrenceCache, RecurrenceHandler,
+    structs::{DateTimeSpec, RecurrenceRule},
+};
+
+const RULES: &[&str] = &[
+    "FREQ=DAILY",
+    "FREQ=DAILY;INTERVAL=3",
+    "FREQ=WEEKLY;BYDAY=MO,WE,FR",
+    "FREQ=WEEKLY;INTERVAL=2;BYDAY=TU",
+    "FREQ=MONTHLY;BYMONTHDAY=1,15,-1",
+    "FREQ=MONTHLY;BYDAY=MO;BYMONTH=1,4,7,10",
+    "FREQ=YEARLY;BYYEARDAY=1,100,-1",
+    "FREQ=YEARLY;BYWEEKNO=1,-1;BYDAY=MO;UNTIL=20300101T000000Z",
+];
+
+fn calendar(size: usize) -> Vec<CalDavEvent> {
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap();
+    (0..size)
+        .map(|i| {
+            let start = start + chrono::Duration::days(i as i64 % 28);
+            CalDavEvent {
+                uid: format!("bench-{i}"),
+                title: format!("Event {i}"),
+                start: Some(DateTimeSpec::DateTime { value: start }),
+                end: Some(DateTimeSpec::DateTime {
+                    value: start + chrono::Duration::hours(1),
+                }),
+                recurrence: Some(RecurrenceRule::new(RULES[i % RULES.len()].to_string())),
+                exdates: vec![DateTimeSpec::Date(
+                    NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(),
+                )],
+                event_type: CalEventType::Timed,
+                seen: Cell::new(false),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+fn bench_handler(c: &mut Criterion) {
+    let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let target = NaiveDate::from_ymd_opt(2026, 3, 16).unwrap();
+    let rdates = Vec::new();
+    let exdates = Vec::new();
+
+    let mut group = c.benchmark_group("recurrence_handler");
+    for rule in RULES {
+        group.bench_function(format!("parse/{rule}"), |b| {
+            b.iter(|| RecurrenceHandler::from_raw(black_box(rule), &rdates, &exdates))
+        });
+
+        let handler = RecurrenceHandler::from_raw(rule, &rdates, &exdates);
+        group.bench_function(format!("is_active_on/{rule}"), |b| {
+            b.iter(|| handler.is_active_on(black_box(&start), black_box(&target)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_calendar(c: &mut Criterion) {
+    let events = calendar(500);
+    let today = Local::now().date_naive();
+    let days: Vec<NaiveDate> = (0..7)
+        .filter_map(|i| today.checked_add_days(Days::new(i)))
+        .collect();
+
+    let mut group = c.benchmark_group("calendar_500");
+    group.bench_function("uncached", |b| {
+        b.iter(|| {
+            events
+                .iter()
+                .filter(|e| {
+                    let start = e.start_utc().unwrap().with_timezone(&Local).date_naive();
+                    let raw = &e.recurrence.as_ref().unwrap().raw;
+                    RecurrenceHandler::from_raw(raw, &e.rdates, &e.exdates)
+                        .is_active_on(&start, black_box(&today))
+                })
+                .count()
+        })
+    });
+    group.bench_function("cached", |b| {
+        OccurrenceCache::global().clear();
+        b.iter(|| {
+            events
+                .iter()
+                .filter(|e| e.occurs_on_day(black_box(&today)))
+                .count()
+        })
+    });
+    group.bench_function("cached_week", |b| {
+        OccurrenceCache::global().clear();
+        b.iter(|| {
+            days.iter()
+                .map(|day| events.iter().filter(|e| e.occurs_on_day(day)).count())
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_handler, bench_calendar);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use suite_223b::{
+    protocol::Request,
+    tokio::{decode_sized, max_message_size, read_frame, set_max_message_size},
+};
+
+// Small enough that the fuzzer actually hits the limit
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+fuzz_target!(|data: &[u8]| {
+    set_max_message_size(MAX_MESSAGE_SIZE);
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("failed to build runtime");
+
+    rt.block_on(async {
+        // Read frames back to back like the daemon does on a client stream
+        let mut reader = data;
+        while let Ok(frame) = read_frame(&mut reader).await {
+            assert!(frame.len() <= max_message_size());
+            let _ = decode_sized::<Request>(&frame);
+        }
+    });
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use suite_223b::{
+    protocol::Request,
+    tokio::{SizedMessageObj, decode_sized},
+};
+
+fuzz_target!(|data: &[u8]| {
+    // Whatever the daemon accepts has to survive a round trip
+    if let Ok(req) = decode_sized::<Request>(data) {
+        let out = SizedMessageObj::from_struct(&req).expect("decoded request failed to encode");
+        let again = decode_sized::<Request>(out.bytes()).expect("encoded request failed to decode");
+        assert_eq!(format!("{:?}", req), format!("{:?}", again));
+    }
+});
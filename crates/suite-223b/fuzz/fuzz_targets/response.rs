@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use suite_223b::{
+    protocol::Response,
+    tokio::{SizedMessageObj, decode_sized},
+};
+
+fuzz_target!(|data: &[u8]| {
+    // Whatever the client accepts has to survive a round trip
+    if let Ok(resp) = decode_sized::<Response>(data) {
+        let out = SizedMessageObj::from_struct(&resp).expect("decoded response failed to encode");
+        let again =
+            decode_sized::<Response>(out.bytes()).expect("encoded response failed to decode");
+        assert_eq!(format!("{:?}", resp), format!("{:?}", again));
+    }
+});
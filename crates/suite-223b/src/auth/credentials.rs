@@ -21,7 +21,7 @@ use crate::{
     calendar::{
         google::GoogleCalendarClient, icloud::ICloudCalendarClient, protocol::CalendarProvider,
     },
-    utils::errors::{WatsonError, WatsonErrorKind},
+    utils::errors::{ResultExt, WatsonError, WatsonErrorKind},
     watson_err,
 };
 
@@ -57,6 +57,22 @@ fn decrypt(
         .map_err(|e| watson_err!(WatsonErrorKind::Decryption, e.to_string()))
 }
 
+/// Encrypts `plaintext` under a fresh nonce, which is prepended to the ciphertext
+pub fn seal(plaintext: &[u8], key: &[u8], aad: &[u8]) -> Result<Vec<u8>, WatsonError> {
+    let mut nonce = [0u8; 24];
+    OsRng.fill_bytes(&mut nonce);
+    let mut sealed = nonce.to_vec();
+    sealed.extend(encrypt(plaintext, key, &nonce, aad)?);
+    Ok(sealed)
+}
+/// Reverses `seal`, failing if the data or `aad` changed since
+pub fn open(sealed: &[u8], key: &[u8], aad: &[u8]) -> Result<Vec<u8>, WatsonError> {
+    let (nonce, ciphertext) = sealed
+        .split_at_checked(24)
+        .ok_or_else(|| watson_err!(WatsonErrorKind::Decryption, "Sealed data too short"))?;
+    decrypt(ciphertext, key, nonce, aad)
+}
+
 // ------- Serde Types (disk only) ------------
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "state", rename_all = "lowercase")]
@@ -376,6 +392,7 @@ impl Credential {
             CredentialService::None => None,
             CredentialService::Icloud => Some(Box::new(ICloudCalendarClient::new(self))),
             CredentialService::Google => Some(Box::new(GoogleCalendarClient::new(self))),
+            CredentialService::HomeAssistant => None,
         }
     }
 }
@@ -406,6 +423,10 @@ pub enum CredentialService {
 
     #[strum(serialize = "Google")]
     Google,
+
+    /// The instance's url as the username and a long-lived access token as the password
+    #[strum(serialize = "Home Assistant")]
+    HomeAssistant,
 }
 impl CredentialService {
     pub fn available_services() -> impl Iterator<Item = Self> {
@@ -432,13 +453,42 @@ impl CredentialManager {
         Ok((cred, key))
     }
     pub fn new() -> Result<Self, WatsonError> {
-        let (cred_path, key_path) = Self::paths()?;
+        let (cred_path, _) = Self::paths()?;
+        let key = Self::master_key()?;
+
+        // Create credentials
+        let credentials: Vec<Credential> = match File::open(&cred_path) {
+            Ok(file) => {
+                let reader = BufReader::new(file);
+                let credentials: Vec<CredentialSerde> = serde_json::from_reader(reader)
+                    .with_context(|| format!("Could not parse {}", cred_path.display()))?;
+
+                credentials
+                    .into_iter()
+                    .map(TryInto::try_into)
+                    .collect::<Result<Vec<_>, WatsonError>>()?
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                File::create_new(&cred_path)
+                    .and_then(|mut file| file.write_all(b"[]"))
+                    .with_context(|| format!("Could not create {}", cred_path.display()))?;
+                Vec::new()
+            }
+            Err(e) => {
+                return Err(watson_err!(WatsonErrorKind::FileWrite, e.to_string()));
+            }
+        };
+
+        Ok(Self { key, credentials })
+    }
+    /// The key credentials and caches are encrypted with, created on first use
+    pub fn master_key() -> Result<[u8; 32], WatsonError> {
+        let (_, key_path) = Self::paths()?;
 
         // Create parent dir if it doesnt exist
-        if let Some(p) = cred_path.parent() {
+        if let Some(p) = key_path.parent() {
             if !p.exists() {
-                create_dir_all(p)
-                    .map_err(|e| watson_err!(WatsonErrorKind::DirCreate, e.to_string()))?;
+                create_dir_all(p).with_context(|| format!("Could not create {}", p.display()))?;
             }
         }
 
@@ -446,62 +496,35 @@ impl CredentialManager {
         let mut key = [0u8; 32];
         match File::open(&key_path) {
             Ok(mut file) => {
-                let meta = file
-                    .metadata()
-                    .map_err(|e| watson_err!(WatsonErrorKind::FileMetadata, e.to_string()))?;
+                let meta = file.metadata()?;
                 if meta.len() != 32 {
                     return Err(watson_err!(WatsonErrorKind::FileRead, "Invalid key length"));
                 }
                 file.read_exact(&mut key)
-                    .map_err(|e| watson_err!(WatsonErrorKind::FileRead, e.to_string()))?;
+                    .with_context(|| format!("Could not read {}", key_path.display()))?;
             }
             Err(e) if e.kind() == io::ErrorKind::NotFound => {
                 // Fill key
                 OsRng.fill_bytes(&mut key);
 
                 // Save key
-                let mut file = OpenOptions::new()
+                OpenOptions::new()
                     .write(true)
                     .create_new(true)
                     .mode(0o600)
                     .open(&key_path)
-                    .map_err(|e| watson_err!(WatsonErrorKind::FileCreate, e.to_string()))?;
-                file.write_all(&key)
-                    .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))?;
-
-                file.sync_all()
-                    .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))?;
+                    .and_then(|mut file| {
+                        file.write_all(&key)?;
+                        file.sync_all()
+                    })
+                    .with_context(|| format!("Could not save {}", key_path.display()))?;
             }
             Err(e) => {
                 return Err(watson_err!(WatsonErrorKind::FileRead, e.to_string()));
             }
         }
 
-        // Create credentials
-        let credentials: Vec<Credential> = match File::open(&cred_path) {
-            Ok(file) => {
-                let reader = BufReader::new(file);
-                let credentials: Vec<CredentialSerde> = serde_json::from_reader(reader)
-                    .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
-
-                credentials
-                    .into_iter()
-                    .map(TryInto::try_into)
-                    .collect::<Result<Vec<_>, WatsonError>>()?
-            }
-            Err(e) if e.kind() == io::ErrorKind::NotFound => {
-                let mut file = File::create_new(cred_path)
-                    .map_err(|e| watson_err!(WatsonErrorKind::FileCreate, e.to_string()))?;
-                file.write_all(b"[]")
-                    .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))?;
-                Vec::new()
-            }
-            Err(e) => {
-                return Err(watson_err!(WatsonErrorKind::FileWrite, e.to_string()));
-            }
-        };
-
-        Ok(Self { key, credentials })
+        Ok(key)
     }
     pub fn lock(&mut self) -> Result<(), WatsonError> {
         for credential in &mut self.credentials {
@@ -521,16 +544,14 @@ impl CredentialManager {
         // Encrypt credentials
         self.lock()?;
 
-        let mut file = File::create(&cred_path)
-            .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))?;
+        let mut file = File::create(&cred_path)?;
 
         let payload: Vec<CredentialSerde> =
             self.credentials.iter().cloned().map(Into::into).collect();
-        let json = serde_json::to_vec(&payload)
-            .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+        let json = serde_json::to_vec(&payload)?;
 
         file.write_all(&json)
-            .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))?;
+            .with_context(|| format!("Could not write {}", cred_path.display()))?;
 
         self.unlock()?;
 
@@ -559,3 +580,22 @@ impl CredentialManager {
         Some(self.credentials.remove(index))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_round_trip() {
+        let key = [7u8; 32];
+        let sealed = seal(b"standup", &key, b"header").unwrap();
+        assert_eq!(open(&sealed, &key, b"header").unwrap(), b"standup");
+
+        // Other headers, flipped bits and truncation all fail the tag
+        assert!(open(&sealed, &key, b"other").is_err());
+        let mut corrupt = sealed.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert!(open(&corrupt, &key, b"header").is_err());
+        assert!(open(&sealed[..10], &key, b"header").is_err());
+    }
+}
@@ -2,6 +2,6 @@ mod credentials;
 mod tui;
 
 pub use credentials::{
-    Credential, CredentialData, CredentialManager, CredentialSecret, CredentialService,
+    Credential, CredentialData, CredentialManager, CredentialSecret, CredentialService, open, seal,
 };
 pub use tui::AuthTui;
@@ -252,23 +252,26 @@ fn render_new_account(s: &NewAccountState) {
 
     match &s.data {
         CredentialData::Password { username, secret } => {
+            let (username_field, secret_field) = field_names(s.service);
             println!(
-                "{} Username: {}",
+                "{} {}: {}",
                 if matches!(s.field, AccountField::Username) {
                     ">"
                 } else {
                     " "
                 },
+                username_field,
                 username
             );
 
             println!(
-                "{} Password: {}",
+                "{} {}: {}",
                 if matches!(s.field, AccountField::Password) {
                     ">"
                 } else {
                     " "
                 },
+                secret_field,
                 "*".repeat(secret.len())
             );
         }
@@ -340,7 +343,9 @@ async fn update_new_account(
         }
         Input::Down | Input::Tab if matches!(s.field, AccountField::Service) => {
             s.field = match s.service {
-                CredentialService::Icloud => AccountField::Username,
+                CredentialService::Icloud | CredentialService::HomeAssistant => {
+                    AccountField::Username
+                }
                 CredentialService::Google => AccountField::OpenBrowser,
                 _ => AccountField::Service,
             };
@@ -352,7 +357,9 @@ async fn update_new_account(
                 AccountField::Password => AccountField::Username,
                 AccountField::Label => match s.service {
                     CredentialService::Google => AccountField::OpenBrowser,
-                    CredentialService::Icloud => AccountField::Password,
+                    CredentialService::Icloud | CredentialService::HomeAssistant => {
+                        AccountField::Password
+                    }
                     _ => AccountField::Service,
                 },
                 _ => AccountField::Service,
@@ -453,10 +460,12 @@ fn update_service_selection(
                             refresh_token: CredentialSecret::Decrypted(String::new()),
                             expires_at: 0,
                         },
-                        CredentialService::Icloud => CredentialData::Password {
-                            username: CredentialSecret::Decrypted(String::new()),
-                            secret: CredentialSecret::Decrypted(String::new()),
-                        },
+                        CredentialService::Icloud | CredentialService::HomeAssistant => {
+                            CredentialData::Password {
+                                username: CredentialSecret::Decrypted(String::new()),
+                                secret: CredentialSecret::Decrypted(String::new()),
+                            }
+                        }
                         CredentialService::None => CredentialData::Empty,
                     };
 
@@ -614,24 +623,27 @@ fn render_edit(s: &EditState, manager: &mut CredentialManager) {
         cred.service
     );
 
+    let (username_field, secret_field) = field_names(cred.service);
     println!(
-        "{} Username: {}",
+        "{} {}: {}",
         if matches!(s.field, AccountField::Username) {
             ">"
         } else {
             " "
         },
+        username_field,
         username
     );
 
     if let CredentialSecret::Decrypted(secret) = secret {
         println!(
-            "{} Password: {}",
+            "{} {}: {}",
             if matches!(s.field, AccountField::Password) {
                 ">"
             } else {
                 " "
             },
+            secret_field,
             "*".repeat(secret.len())
         );
     }
@@ -774,6 +786,14 @@ fn update_edit(
 
 // ---------- Terminal utils ----------
 
+/// What the username and the password of `service` hold
+fn field_names(service: CredentialService) -> (&'static str, &'static str) {
+    match service {
+        CredentialService::HomeAssistant => ("URL", "Token"),
+        _ => ("Username", "Password"),
+    }
+}
+
 fn clear() {
     print!("\x1b[2J\x1b[H");
     stdout().flush().unwrap();
@@ -61,7 +61,11 @@ pub fn client_auth() -> Result<(), WatsonError> {
         .append_pair("prompt", "consent")
         .append_pair(
             "scope",
-            "openid email https://www.googleapis.com/auth/calendar",
+            concat!(
+                "openid email https://www.googleapis.com/auth/calendar",
+                " https://www.googleapis.com/auth/gmail.readonly",
+                " https://www.googleapis.com/auth/carddav",
+            ),
         );
 
     Command::new("xdg-open")
@@ -87,8 +91,7 @@ pub async fn exchange_code_for_tokens(code: &str) -> Result<GoogleTokenResponse,
         .post("https://oauth2.googleapis.com/token")
         .form(&params)
         .send()
-        .await
-        .map_err(|e| watson_err!(WatsonErrorKind::HttpGetRequest, e.to_string()))?;
+        .await?;
 
     if !resp.status().is_success() {
         return Err(watson_err!(
@@ -97,13 +100,9 @@ pub async fn exchange_code_for_tokens(code: &str) -> Result<GoogleTokenResponse,
         ));
     }
 
-    let text = resp
-        .text()
-        .await
-        .map_err(|e| watson_err!(WatsonErrorKind::HttpPostRequest, e.to_string()))?;
+    let text = resp.text().await?;
 
-    serde_json::from_str(&text)
-        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))
+    Ok(serde_json::from_str(&text)?)
 }
 
 pub async fn wait_for_auth_code() -> Result<String, WatsonError> {
@@ -178,8 +177,7 @@ impl GoogleAuth {
             .post("https://oauth2.googleapis.com/token")
             .form(&params)
             .send()
-            .await
-            .map_err(|e| watson_err!(WatsonErrorKind::HttpGetRequest, e.to_string()))?;
+            .await?;
 
         if !resp.status().is_success() {
             return Err(watson_err!(
@@ -188,13 +186,9 @@ impl GoogleAuth {
             ));
         }
 
-        let text = resp
-            .text()
-            .await
-            .map_err(|e| watson_err!(WatsonErrorKind::HttpPostRequest, e.to_string()))?;
+        let text = resp.text().await?;
 
-        let response: GoogleRefreshTokenResponse = serde_json::from_str(&text)
-            .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+        let response: GoogleRefreshTokenResponse = serde_json::from_str(&text)?;
 
         Ok(response.access_token)
     }
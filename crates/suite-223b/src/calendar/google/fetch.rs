@@ -7,19 +7,21 @@ use std::{
 use async_trait::async_trait;
 use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
 use regex::bytes::Regex;
-use reqwest::Client;
-use serde::Deserialize;
+use reqwest::{Client, header::CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     auth::{Credential, CredentialData},
     calendar::{
         google::auth::GoogleAuth,
+        invite::Invitation,
         protocol::CalendarProvider,
         utils::{
             CalDavEvent, CalEventType, CalendarInfo, Meeting,
             structs::{Attendee, DateTimeSpec, Partstat, RecurrenceRule},
         },
     },
+    contacts::email_address,
     utils::errors::{WatsonError, WatsonErrorKind},
     watson_err,
 };
@@ -34,7 +36,18 @@ pub struct GoogleApiErrorResponse {
 
 #[derive(Deserialize)]
 pub struct GoogleError {
-    message: String,
+    pub message: String,
+}
+
+/// Google's message for a failed request, or the status when the body has none
+fn api_error(status: reqwest::StatusCode, text: &str) -> WatsonError {
+    match serde_json::from_str::<GoogleApiErrorResponse>(text) {
+        Ok(error) => watson_err!(WatsonErrorKind::GoogleCalendar, error.error.message),
+        Err(_) => watson_err!(
+            WatsonErrorKind::GoogleCalendar,
+            format!("Google Calendar answered with {}", status)
+        ),
+    }
 }
 
 //--------------------
@@ -55,13 +68,19 @@ struct GoogleCalendarEntry {
 
     #[serde(rename = "backgroundColor")]
     pub color: Option<String>,
+
+    /// The primary calendar's id is the account's address
+    #[serde(default)]
+    pub primary: bool,
 }
 impl From<GoogleCalendarEntry> for CalendarInfo {
     fn from(value: GoogleCalendarEntry) -> Self {
         Self {
+            owner: value.primary.then(|| value.id.clone()),
             href: value.id,
             name: value.summary,
             color: value.color,
+            ..Default::default()
         }
     }
 }
@@ -72,6 +91,27 @@ impl From<GoogleCalendarEntry> for CalendarInfo {
 #[derive(Deserialize)]
 struct GoogleCalendarEventList {
     pub items: Vec<GoogleCalendarEvent>,
+
+    #[serde(rename = "nextSyncToken")]
+    pub next_sync_token: Option<String>,
+}
+
+/// Response of an incremental sync, only the ids are requested
+#[derive(Deserialize)]
+struct GoogleCalendarChangeList {
+    #[serde(default)]
+    pub items: Vec<GoogleCalendarChange>,
+
+    #[serde(rename = "nextSyncToken")]
+    pub next_sync_token: Option<String>,
+    #[serde(rename = "nextPageToken")]
+    pub next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GoogleCalendarChange {
+    #[serde(rename = "id")]
+    pub _id: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -179,6 +219,8 @@ pub struct GoogleEventUser {
     pub organizer: Option<bool>,
     #[serde(rename = "responseStatus")]
     pub partstat: Option<Partstat>,
+    #[serde(rename = "self", default)]
+    pub is_self: bool,
 }
 impl From<GoogleEventUser> for Attendee {
     fn from(v: GoogleEventUser) -> Self {
@@ -192,10 +234,74 @@ impl From<GoogleEventUser> for Attendee {
             display_name: v.display_name,
             role,
             partstat: v.partstat,
+            avatar: None,
+            is_self: v.is_self,
         }
     }
 }
 
+/// Body of `events.import` for an invitation
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GoogleEventImport {
+    #[serde(rename = "iCalUID")]
+    ical_uid: String,
+    summary: String,
+    description: Option<String>,
+    location: Option<String>,
+    start: GoogleEventTime,
+    end: GoogleEventTime,
+    recurrence: Vec<String>,
+    organizer: Option<GoogleImportUser>,
+    attendees: Vec<GoogleImportUser>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GoogleEventTime {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_time: Option<DateTime<Utc>>,
+}
+impl From<&DateTimeSpec> for GoogleEventTime {
+    fn from(v: &DateTimeSpec) -> Self {
+        match v {
+            DateTimeSpec::Date(date) => Self {
+                date: Some(*date),
+                date_time: None,
+            },
+            DateTimeSpec::DateTime { value } => Self {
+                date: None,
+                date_time: Some(*value),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GoogleImportUser {
+    email: String,
+    display_name: Option<String>,
+    response_status: &'static str,
+}
+
+#[derive(Deserialize)]
+struct GoogleImportedEvent {
+    id: String,
+}
+
+/// `responseStatus` as Google spells it
+fn response_status(partstat: Option<Partstat>) -> &'static str {
+    match partstat {
+        Some(Partstat::Accepted) => "accepted",
+        Some(Partstat::Declined) => "declined",
+        Some(Partstat::Tentative) => "tentative",
+        _ => "needsAction",
+    }
+}
+
 //--------------------
 //------ Client ------
 //--------------------
@@ -203,12 +309,15 @@ impl From<GoogleEventUser> for Attendee {
 pub struct GoogleCalendarClient {
     client: Client,
     credential: Credential,
+    /// Calendar id -> syncToken of the last full or incremental fetch
+    sync_tokens: HashMap<String, String>,
 }
 impl GoogleCalendarClient {
     pub fn new(credential: Credential) -> Self {
         Self {
             client: Client::new(),
             credential,
+            sync_tokens: HashMap::new(),
         }
     }
 }
@@ -252,26 +361,16 @@ impl CalendarProvider for GoogleCalendarClient {
             .get(url)
             .bearer_auth(&access_token)
             .send()
-            .await
-            .map_err(|e| watson_err!(WatsonErrorKind::HttpGetRequest, e.to_string()))?;
+            .await?;
 
         let status = resp.status();
-        let text = resp
-            .text()
-            .await
-            .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+        let text = resp.text().await?;
 
         if !status.is_success() {
-            let error: GoogleApiErrorResponse = serde_json::from_str(&text)
-                .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
-            return Err(watson_err!(
-                WatsonErrorKind::GoogleCalendar,
-                error.error.message
-            ));
+            return Err(api_error(status, &text));
         }
 
-        let list: GoogleCalendarList = serde_json::from_str(&text)
-            .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+        let list: GoogleCalendarList = serde_json::from_str(&text)?;
 
         Ok(list.items.into_iter().map(|i| i.into()).collect())
     }
@@ -302,34 +401,187 @@ impl CalendarProvider for GoogleCalendarClient {
                 .get(&url)
                 .bearer_auth(access_token)
                 .send()
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::HttpGetRequest, e.to_string()))?;
+                .await?;
 
             let status = resp.status();
-            let text = resp
-                .text()
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+            let text = resp.text().await?;
 
             if !status.is_success() {
-                continue;
+                return Err(api_error(status, &text));
+            }
+
+            let list = serde_json::from_str::<GoogleCalendarEventList>(&text)?;
+            if let Some(token) = list.next_sync_token {
+                self.sync_tokens.insert(calendar.href.clone(), token);
             }
 
             let calendar_rc = Arc::new(calendar);
-            println!("{:?}", text);
-            let tmp_events: Vec<CalDavEvent> =
-                serde_json::from_str::<GoogleCalendarEventList>(&text)
-                    .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?
-                    .items
-                    .into_iter()
-                    .map(|v| v.to_cal_dav_event(calendar_rc.clone()))
-                    .collect();
+            let tmp_events: Vec<CalDavEvent> = list
+                .items
+                .into_iter()
+                .map(|v| v.to_cal_dav_event(calendar_rc.clone()))
+                .collect();
 
             events.extend(tmp_events);
         }
 
         Ok(events)
     }
+
+    async fn changed_calendars(
+        &mut self,
+        calendars: &[CalendarInfo],
+    ) -> Result<Vec<String>, WatsonError> {
+        self.refresh().await?;
+
+        let CredentialData::OAuth { access_token, .. } = &self.credential.data else {
+            return Err(watson_err!(
+                WatsonErrorKind::GoogleAuth,
+                "Invalid auth type provided."
+            ));
+        };
+
+        let mut changed = Vec::new();
+        for calendar in calendars {
+            let Some(token) = self.sync_tokens.get(&calendar.href) else {
+                // Never fetched, nothing to compare against
+                changed.push(calendar.href.clone());
+                continue;
+            };
+
+            let url = format!(
+                "https://www.googleapis.com/calendar/v3/calendars/{}/events",
+                calendar.href
+            );
+            let resp = self
+                .client
+                .get(&url)
+                .bearer_auth(access_token)
+                .query(&[
+                    ("syncToken", token.as_str()),
+                    ("fields", "items(id),nextSyncToken,nextPageToken"),
+                ])
+                .send()
+                .await?;
+
+            let status = resp.status();
+            if status == reqwest::StatusCode::GONE {
+                // Token expired, requires a full sync
+                self.sync_tokens.remove(&calendar.href);
+                changed.push(calendar.href.clone());
+                continue;
+            }
+            let text = resp.text().await?;
+            if !status.is_success() {
+                return Err(api_error(status, &text));
+            }
+
+            let list: GoogleCalendarChangeList = serde_json::from_str(&text)?;
+
+            if !list.items.is_empty() || list.next_page_token.is_some() {
+                changed.push(calendar.href.clone());
+            }
+            if let Some(token) = list.next_sync_token {
+                self.sync_tokens.insert(calendar.href.clone(), token);
+            }
+        }
+
+        Ok(changed)
+    }
+
+    async fn respond(
+        &mut self,
+        calendar: &CalendarInfo,
+        invitation: Invitation,
+        address: &str,
+        answer: Partstat,
+    ) -> Result<(), WatsonError> {
+        self.refresh().await?;
+
+        let CredentialData::OAuth { access_token, .. } = &self.credential.data else {
+            return Err(watson_err!(
+                WatsonErrorKind::GoogleAuth,
+                "Invalid auth type provided."
+            ));
+        };
+
+        let event = &invitation.event;
+        let (Some(start), Some(end)) = (&event.start, &event.end) else {
+            return Err(watson_err!(
+                WatsonErrorKind::InvalidData,
+                "Invitation without start or end"
+            ));
+        };
+        let attendees = event
+            .attendees
+            .iter()
+            .filter_map(|a| {
+                let partstat = if a.has_address(address) {
+                    Some(answer)
+                } else {
+                    a.partstat
+                };
+                Some(GoogleImportUser {
+                    email: email_address(a.email.as_deref()?),
+                    display_name: a.display_name.clone(),
+                    response_status: response_status(partstat),
+                })
+            })
+            .collect();
+        let body = GoogleEventImport {
+            ical_uid: event.uid.clone(),
+            summary: event.title.clone(),
+            description: event.description.clone(),
+            location: event.location.clone(),
+            start: start.into(),
+            end: end.into(),
+            recurrence: invitation.recurrence(),
+            organizer: event.organizer.as_deref().map(|o| GoogleImportUser {
+                email: email_address(o),
+                display_name: None,
+                response_status: response_status(Some(Partstat::Accepted)),
+            }),
+            attendees,
+        };
+
+        let url = format!(
+            "https://www.googleapis.com/calendar/v3/calendars/{}/events/import",
+            calendar.href
+        );
+        let resp = self
+            .client
+            .post(&url)
+            .bearer_auth(access_token)
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(&body)?)
+            .send()
+            .await?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        if !status.is_success() {
+            return Err(api_error(status, &text));
+        }
+
+        // The import carries the answer, a declined invitation doesn't stay in the calendar
+        if answer == Partstat::Declined {
+            let imported: GoogleImportedEvent = serde_json::from_str(&text)?;
+            let url = format!(
+                "https://www.googleapis.com/calendar/v3/calendars/{}/events/{}",
+                calendar.href, imported.id
+            );
+            let resp = self
+                .client
+                .delete(&url)
+                .bearer_auth(access_token)
+                .send()
+                .await?;
+            let status = resp.status();
+            if !status.is_success() {
+                return Err(api_error(status, &resp.text().await?));
+            }
+        }
+        Ok(())
+    }
 }
 
 pub fn parse_meeting(text: &str) -> Option<Meeting> {
@@ -1,5 +1,6 @@
 mod auth;
 mod fetch;
 
-pub use auth::{client_auth, exchange_code_for_tokens, wait_for_auth_code};
+pub use auth::{GoogleAuth, client_auth, exchange_code_for_tokens, wait_for_auth_code};
+pub(crate) use fetch::GoogleApiErrorResponse;
 pub use fetch::GoogleCalendarClient;
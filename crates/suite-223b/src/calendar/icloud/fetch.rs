@@ -14,8 +14,9 @@ use crate::{
             protocol::PropfindRequest,
             utils::{parse_ical, unfold_ics},
         },
+        invite::Invitation,
         protocol::CalendarProvider,
-        utils::{CalDavEvent, CalendarInfo},
+        utils::{CalDavEvent, CalendarInfo, structs::Partstat},
     },
     utils::errors::{WatsonError, WatsonErrorKind},
     watson_err,
@@ -26,6 +27,8 @@ pub struct ICloudCalendarClient {
     headers: HeaderMap,
     data: CredentialData,
     principal: Option<String>,
+    /// Calendar href -> ctag of the last fetch
+    ctags: HashMap<String, String>,
 }
 impl Default for ICloudCalendarClient {
     fn default() -> Self {
@@ -42,6 +45,7 @@ impl Default for ICloudCalendarClient {
             headers,
             data: CredentialData::Empty,
             principal: None,
+            ctags: HashMap::new(),
         }
     }
 }
@@ -67,47 +71,36 @@ impl ICloudCalendarClient {
         );
 
         let body = request.body();
+        let method = reqwest::Method::from_bytes(params.method).unwrap();
+        let resp = self
+            .authorized(method, &params.url)?
+            .headers(headers)
+            .body(body)
+            .send()
+            .await?;
 
-        let resp = match &self.data {
-            CredentialData::Password { username, secret } => self
-                .client
-                .request(
-                    reqwest::Method::from_bytes(params.method).unwrap(),
-                    params.url,
-                )
-                .basic_auth(&username, Some(&secret))
-                .headers(headers)
-                .body(body)
-                .send()
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::HttpGetRequest, e.to_string()))?,
-            CredentialData::OAuth { access_token, .. } => self
-                .client
-                .request(
-                    reqwest::Method::from_bytes(params.method).unwrap(),
-                    params.url,
-                )
-                .bearer_auth(access_token)
-                .headers(headers)
-                .body(body)
-                .send()
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::HttpGetRequest, e.to_string()))?,
-            CredentialData::Empty => {
-                return Err(watson_err!(
-                    WatsonErrorKind::UndefinedAttribute,
-                    "Undefined credential data."
-                ));
-            }
-        };
-
-        let text = resp
-            .text()
-            .await
-            .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+        let text = resp.text().await?;
 
         Ok(text)
     }
+    /// A request to `url` with the account's credentials
+    fn authorized(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+    ) -> Result<reqwest::RequestBuilder, WatsonError> {
+        let request = self.client.request(method, url);
+        match &self.data {
+            CredentialData::Password { username, secret } => {
+                Ok(request.basic_auth(username, Some(secret)))
+            }
+            CredentialData::OAuth { access_token, .. } => Ok(request.bearer_auth(access_token)),
+            CredentialData::Empty => Err(watson_err!(
+                WatsonErrorKind::UndefinedAttribute,
+                "Undefined credential data."
+            )),
+        }
+    }
     pub async fn get_principal(&mut self) -> Result<(), WatsonError> {
         let request = PropfindRequest::Principal;
         let text = self.make_request(request).await?;
@@ -149,6 +142,28 @@ impl ICloudCalendarClient {
         }
         Ok(())
     }
+    pub async fn get_ctag(&mut self, url: &str) -> Result<Option<String>, WatsonError> {
+        let request = PropfindRequest::Ctag {
+            url: url.to_string(),
+        };
+        let text = self.make_request(request).await?;
+
+        let mut reader = Reader::from_str(&text);
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(ref e)) if e.local_name().as_ref() == b"getctag" => {
+                    if let Ok(Event::Text(t)) = reader.read_event() {
+                        return Ok(t.decode().ok().map(|s| s.to_string()));
+                    }
+                    break;
+                }
+                Ok(Event::Eof) => break,
+                Err(_) => break,
+                _ => (),
+            }
+        }
+        Ok(None)
+    }
 }
 
 #[async_trait]
@@ -170,6 +185,13 @@ impl CalendarProvider for ICloudCalendarClient {
             principal: principal.to_string(),
         };
         let text = self.make_request(request).await?;
+        // The Apple ID is the address invitations are sent to
+        let owner = match &self.data {
+            CredentialData::Password { username, .. } if !username.is_locked() => {
+                Some(username.to_string())
+            }
+            _ => None,
+        };
 
         let mut reader = Reader::from_str(&text);
         let mut buf = Vec::new();
@@ -210,6 +232,8 @@ impl CalendarProvider for ICloudCalendarClient {
                                 href,
                                 name,
                                 color: color.take(),
+                                owner: owner.clone(),
+                                ..Default::default()
                             });
                         }
                     }
@@ -233,6 +257,10 @@ impl CalendarProvider for ICloudCalendarClient {
     ) -> Result<Vec<CalDavEvent>, WatsonError> {
         let mut out = Vec::new();
         for info in calendar_info {
+            if let Ok(Some(ctag)) = self.get_ctag(&info.href).await {
+                self.ctags.insert(info.href.clone(), ctag);
+            }
+
             let request = PropfindRequest::Events {
                 url: info.href.clone(),
             };
@@ -279,4 +307,68 @@ impl CalendarProvider for ICloudCalendarClient {
 
         Ok(out)
     }
+
+    async fn changed_calendars(
+        &mut self,
+        calendars: &[CalendarInfo],
+    ) -> Result<Vec<String>, WatsonError> {
+        let mut changed = Vec::new();
+        for info in calendars {
+            let ctag = self.get_ctag(&info.href).await?;
+            let unchanged = matches!(
+                (&ctag, self.ctags.get(&info.href)),
+                (Some(new), Some(old)) if new == old
+            );
+            if !unchanged {
+                changed.push(info.href.clone());
+            }
+        }
+        Ok(changed)
+    }
+
+    async fn respond(
+        &mut self,
+        calendar: &CalendarInfo,
+        invitation: Invitation,
+        address: &str,
+        answer: Partstat,
+    ) -> Result<(), WatsonError> {
+        let uid: String = invitation
+            .event
+            .uid
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || "-_.@".contains(*c))
+            .collect();
+        let url = format!("https://caldav.icloud.com{}{}.ics", calendar.href, uid);
+
+        // Saving the answered copy is what sends the reply
+        let resp = self
+            .authorized(reqwest::Method::PUT, &url)?
+            .header(CONTENT_TYPE, "text/calendar; charset=utf-8")
+            .body(invitation.answered(address, answer))
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(watson_err!(
+                WatsonErrorKind::Http,
+                format!("Saving the invitation failed with {}", resp.status())
+            ));
+        }
+        if answer == Partstat::Declined {
+            let resp = self
+                .authorized(reqwest::Method::DELETE, &url)?
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                return Err(watson_err!(
+                    WatsonErrorKind::Http,
+                    format!(
+                        "Removing the declined invitation failed with {}",
+                        resp.status()
+                    )
+                ));
+            }
+        }
+        Ok(())
+    }
 }
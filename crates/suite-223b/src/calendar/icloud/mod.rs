@@ -1,5 +1,5 @@
 mod fetch;
 mod protocol;
-mod utils;
+pub(crate) mod utils;
 
 pub use fetch::ICloudCalendarClient;
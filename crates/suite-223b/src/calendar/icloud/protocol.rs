@@ -1,7 +1,15 @@
 pub enum PropfindRequest {
     Principal,
-    Calendars { principal: String },
-    Events { url: String },
+    Calendars {
+        principal: String,
+    },
+    Events {
+        url: String,
+    },
+    /// Collection tag, changes whenever anything inside the calendar changes
+    Ctag {
+        url: String,
+    },
 }
 pub struct PropfindParams {
     pub url: String,
@@ -26,6 +34,11 @@ impl PropfindRequest {
                 depth: "1",
                 method: b"REPORT",
             },
+            Self::Ctag { url } => PropfindParams {
+                url: format!("https://caldav.icloud.com{}", url),
+                depth: "0",
+                method: b"PROPFIND",
+            },
         }
     }
     pub fn body(&self) -> &'static str {
@@ -65,6 +78,15 @@ impl PropfindRequest {
                 </calendar-query>
                 "#
             }
+            Self::Ctag { .. } => {
+                r#"
+                <propfind xmlns="DAV:" xmlns:cs="http://calendarserver.org/ns/">
+                  <prop>
+                    <cs:getctag/>
+                  </prop>
+                </propfind>
+                "#
+            }
         }
     }
 }
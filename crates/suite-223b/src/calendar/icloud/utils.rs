@@ -60,3 +60,254 @@ pub fn parse_ical(ics: String, calendar_info: Arc<CalendarInfo>) -> Vec<CalDavEv
 
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::utils::{
+        CalEventType, Meeting,
+        structs::{DateTimeSpec, Partstat, RecurrenceRule},
+    };
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use proptest::prelude::*;
+
+    fn parse_fixture(ics: &str) -> Vec<CalDavEvent> {
+        let info = Arc::new(CalendarInfo {
+            href: "/calendars/fixtures/".into(),
+            name: "Fixtures".into(),
+            color: None,
+            ..Default::default()
+        });
+        parse_ical(unfold_ics(ics), info)
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> Option<DateTimeSpec> {
+        Some(DateTimeSpec::DateTime {
+            value: Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap(),
+        })
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn golden(uid: &str, title: &str, events: &[CalDavEvent]) -> CalDavEvent {
+        let actual = events
+            .iter()
+            .find(|e| e.uid == uid)
+            .unwrap_or_else(|| panic!("missing event `{uid}`"));
+        CalDavEvent {
+            uid: uid.into(),
+            title: title.into(),
+            calendar_info: actual.calendar_info.clone(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_folded_lines() {
+        let events = parse_fixture(include_str!("../../../tests/fixtures/ics/folded.ics"));
+        assert_eq!(events.len(), 1);
+
+        let expected = CalDavEvent {
+            description: Some(
+                "Agenda:\\n1. Roadmap\\n2. Hiring\\n3. Budget for the next quarter".into(),
+            ),
+            location: Some("Room 4.12, Building B".into()),
+            start: utc(2024, 1, 15, 9, 0),
+            end: utc(2024, 1, 15, 10, 0),
+            sequence: Some(2),
+            url: Some("https://example.com/meetings/quarterly".into()),
+            event_type: CalEventType::Timed,
+            ..golden(
+                "folded-1@watson",
+                "Quarterly planning with the extended platform team",
+                &events,
+            )
+        };
+        assert_eq!(events[0], expected);
+    }
+
+    #[test]
+    fn test_quoted_tzid_and_exdates() {
+        let events = parse_fixture(include_str!("../../../tests/fixtures/ics/quoted_tzid.ics"));
+        assert_eq!(events.len(), 1);
+
+        // Europe/Berlin is UTC+1 in winter
+        let expected = CalDavEvent {
+            start: utc(2024, 1, 15, 8, 0),
+            end: utc(2024, 1, 15, 8, 30),
+            recurrence: Some(RecurrenceRule::new(
+                "FREQ=WEEKLY;BYDAY=MO;UNTIL=20240401T000000Z".into(),
+            )),
+            exdates: vec![
+                utc(2024, 1, 22, 8, 0).unwrap(),
+                utc(2024, 2, 5, 8, 0).unwrap(),
+                utc(2024, 2, 12, 8, 0).unwrap(),
+            ],
+            event_type: CalEventType::Timed,
+            ..golden("weekly-1@watson", "Standup", &events)
+        };
+        assert_eq!(events[0], expected);
+
+        let event = &events[0];
+        assert!(event.occurs_on_day_in(&date(2024, 1, 15), &Utc));
+        assert!(event.occurs_on_day_in(&date(2024, 1, 29), &Utc));
+        assert!(!event.occurs_on_day_in(&date(2024, 1, 22), &Utc));
+        assert!(!event.occurs_on_day_in(&date(2024, 2, 12), &Utc));
+        assert!(!event.occurs_on_day_in(&date(2024, 1, 30), &Utc));
+        assert!(!event.occurs_on_day_in(&date(2024, 4, 8), &Utc));
+    }
+
+    #[test]
+    fn test_weird_tzids() {
+        let events = parse_fixture(include_str!("../../../tests/fixtures/ics/weird_tzid.ics"));
+        assert_eq!(events.len(), 2);
+
+        // America/New_York is UTC-4 in summer
+        let expected = CalDavEvent {
+            start: utc(2024, 7, 4, 22, 0),
+            end: utc(2024, 7, 5, 0, 0),
+            event_type: CalEventType::Timed,
+            ..golden("tz-mozilla@watson", "Fireworks", &events)
+        };
+        assert_eq!(events[0], expected);
+
+        // Europe/Berlin is UTC+2 in summer
+        let expected = CalDavEvent {
+            start: utc(2024, 6, 10, 12, 0),
+            end: utc(2024, 6, 10, 13, 0),
+            event_type: CalEventType::Timed,
+            meeting: Some(Meeting::MicrosoftTeams {
+                url: "https://teams.microsoft.com/l/meetup-join/abc".into(),
+            }),
+            ..golden("tz-windows@watson", "Review", &events)
+        };
+        assert_eq!(events[1], expected);
+    }
+
+    #[test]
+    fn test_allday_spans() {
+        let events = parse_fixture(include_str!("../../../tests/fixtures/ics/allday.ics"));
+        assert_eq!(events.len(), 2);
+
+        let expected = CalDavEvent {
+            start: Some(DateTimeSpec::Date(date(2024, 3, 1))),
+            end: Some(DateTimeSpec::Date(date(2024, 3, 4))),
+            event_type: CalEventType::AllDay,
+            ..golden("allday-1@watson", "Conference", &events)
+        };
+        assert_eq!(events[0], expected);
+
+        // DTEND is exclusive
+        let event = &events[0];
+        assert!(event.occurs_on_day(&date(2024, 3, 1)));
+        assert!(event.occurs_on_day(&date(2024, 3, 3)));
+        assert!(!event.occurs_on_day(&date(2024, 2, 29)));
+        assert!(!event.occurs_on_day(&date(2024, 3, 4)));
+
+        let expected = CalDavEvent {
+            start: Some(DateTimeSpec::Date(date(1990, 5, 12))),
+            recurrence: Some(RecurrenceRule::new("FREQ=YEARLY".into())),
+            exdates: vec![
+                DateTimeSpec::Date(date(2020, 5, 12)),
+                DateTimeSpec::Date(date(2021, 5, 12)),
+            ],
+            event_type: CalEventType::AllDay,
+            ..golden("allday-2@watson", "Birthday", &events)
+        };
+        assert_eq!(events[1], expected);
+
+        let event = &events[1];
+        assert!(event.occurs_on_day(&date(2024, 5, 12)));
+        assert!(!event.occurs_on_day(&date(2020, 5, 12)));
+        assert!(!event.occurs_on_day(&date(2024, 5, 13)));
+    }
+
+    #[test]
+    fn test_own_partstat() {
+        let info = Arc::new(CalendarInfo {
+            owner: Some("me@icloud.com".into()),
+            ..Default::default()
+        });
+        let ics = include_str!("../../../tests/fixtures/ics/attendees.ics");
+        let events = parse_ical(unfold_ics(ics), info);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].own_partstat(), Some(Partstat::Declined));
+        assert_eq!(events[1].own_partstat(), Some(Partstat::Tentative));
+
+        // Someone else's calendar
+        let events = parse_fixture(ics);
+        assert_eq!(events[0].own_partstat(), None);
+    }
+
+    #[test]
+    fn test_search_matches() {
+        let events = parse_fixture(include_str!("../../../tests/fixtures/ics/attendees.ics"));
+        let review = &events[0];
+        assert!(review.matches("design"));
+        assert!(review.matches("REVIEW jane"));
+        assert!(review.matches("example.com"));
+        assert!(!review.matches("design offsite"));
+        assert!(!review.matches("  "));
+    }
+
+    /// Content lines without line breaks that don't start with folding whitespace
+    fn content_line() -> impl Strategy<Value = String> {
+        "[^\r\n \t][^\r\n]{0,120}"
+    }
+
+    /// Folds `line` at the given char positions using `sep` followed by `ws`
+    fn fold(line: &str, cuts: &[usize], sep: &str, ws: char) -> String {
+        let chars: Vec<char> = line.chars().collect();
+        let mut cuts: Vec<usize> = cuts
+            .iter()
+            .map(|c| 1 + c % chars.len().max(1))
+            .filter(|c| *c < chars.len())
+            .collect();
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        let mut out = String::with_capacity(line.len() + cuts.len() * 3);
+        for (i, c) in chars.iter().enumerate() {
+            if cuts.binary_search(&i).is_ok() {
+                out.push_str(sep);
+                out.push(ws);
+            }
+            out.push(*c);
+        }
+        out
+    }
+
+    proptest! {
+        #[test]
+        fn unfold_without_breaks_is_identity(line in "[^\r\n]{0,200}") {
+            prop_assert_eq!(unfold_ics(&line), line);
+        }
+
+        #[test]
+        fn unfold_reverses_folding(
+            lines in prop::collection::vec(content_line(), 1..8),
+            cuts in prop::collection::vec(any::<usize>(), 0..6),
+            crlf in any::<bool>(),
+            tab in any::<bool>(),
+        ) {
+            let sep = if crlf { "\r\n" } else { "\n" };
+            let ws = if tab { '\t' } else { ' ' };
+
+            let folded: Vec<String> = lines.iter().map(|l| fold(l, &cuts, sep, ws)).collect();
+            let input = folded.join(sep) + sep;
+
+            prop_assert_eq!(unfold_ics(&input), lines.join("\n") + "\n");
+        }
+
+        #[test]
+        fn unfold_normalizes_line_endings(
+            lines in prop::collection::vec(content_line(), 1..8),
+        ) {
+            let out = unfold_ics(&lines.join("\r\n"));
+            prop_assert!(!out.contains('\r'));
+            prop_assert_eq!(out.lines().count(), lines.len());
+        }
+    }
+}
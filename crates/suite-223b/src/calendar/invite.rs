@@ -0,0 +1,187 @@
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    calendar::{
+        icloud::utils::{parse_ical, unfold_ics},
+        utils::{
+            CalDavEvent, CalendarInfo,
+            structs::{Attendee, Partstat},
+        },
+    },
+    contacts::email_address,
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+/// A meeting invitation from an `.ics` file, e.g. an email attachment (iTIP `METHOD:REQUEST`)
+#[derive(Debug, Clone)]
+pub struct Invitation {
+    /// The calendar object as received, unfolded
+    ics: String,
+    pub event: CalDavEvent,
+}
+impl Invitation {
+    /// Fails for anything but a request, replies and cancellations aren't answered
+    pub fn parse(ics: &str) -> Result<Self, WatsonError> {
+        let ics = unfold_ics(ics);
+        let method = ics
+            .lines()
+            .find_map(|line| line.trim_end().strip_prefix("METHOD:"));
+        if !method.is_some_and(|m| m.eq_ignore_ascii_case("REQUEST")) {
+            return Err(watson_err!(
+                WatsonErrorKind::InvalidData,
+                "Not an invitation, METHOD:REQUEST is missing"
+            ));
+        }
+        let event = parse_ical(ics.clone(), Arc::new(CalendarInfo::default()))
+            .into_iter()
+            .next()
+            .ok_or_else(|| watson_err!(WatsonErrorKind::InvalidData, "Invitation without event"))?;
+        Ok(Self { ics, event })
+    }
+    pub fn load(path: &Path) -> Result<Self, WatsonError> {
+        let ics = std::fs::read_to_string(path).map_err(|e| {
+            watson_err!(
+                WatsonErrorKind::FileRead,
+                format!("{}: {}", path.display(), e)
+            )
+        })?;
+        Self::parse(&ics)
+    }
+
+    /// The attendee with one of `addresses`
+    pub fn attendee<'a>(&self, addresses: impl IntoIterator<Item = &'a str>) -> Option<&Attendee> {
+        let addresses: Vec<&str> = addresses.into_iter().collect();
+        self.event
+            .attendees
+            .iter()
+            .find(|a| addresses.iter().any(|address| a.has_address(address)))
+    }
+
+    /// The invitation as stored in the calendar of the attendee `address`, with their answer.
+    /// CalDAV servers send the reply to the organizer when it's saved (implicit scheduling).
+    pub fn answered(&self, address: &str, answer: Partstat) -> String {
+        let mut out = String::with_capacity(self.ics.len());
+        for line in self.ics.lines().map(str::trim_end) {
+            if line.starts_with("METHOD:") {
+                continue;
+            }
+            match split_property(line) {
+                Some((params, value))
+                    if params.starts_with("ATTENDEE")
+                        && email_address(value) == email_address(address) =>
+                {
+                    let kept: Vec<&str> = split_unquoted(params, ';')
+                        .into_iter()
+                        .filter(|p| !p.starts_with("PARTSTAT=") && !p.starts_with("RSVP="))
+                        .collect();
+                    out.push_str(&kept.join(";"));
+                    out.push_str(";PARTSTAT=");
+                    out.push_str(answer.as_ical());
+                    out.push(':');
+                    out.push_str(value);
+                }
+                _ => out.push_str(line),
+            }
+            out.push_str("\r\n");
+        }
+        out
+    }
+
+    /// `RRULE`, `RDATE` and `EXDATE` lines of the main event, as Google expects them
+    pub fn recurrence(&self) -> Vec<String> {
+        self.ics
+            .lines()
+            .skip_while(|line| !line.starts_with("BEGIN:VEVENT"))
+            .take_while(|line| !line.starts_with("END:VEVENT"))
+            .filter(|line| {
+                ["RRULE", "RDATE", "EXDATE"]
+                    .iter()
+                    .any(|p| line.starts_with(p))
+            })
+            .map(|line| line.trim_end().to_string())
+            .collect()
+    }
+}
+
+/// Name with parameters and value of a content line, split at the first colon outside quotes
+fn split_property(line: &str) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some((&line[..i], &line[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn split_unquoted(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&s[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVITE: &str = "BEGIN:VCALENDAR\r\n\
+        VERSION:2.0\r\n\
+        METHOD:REQUEST\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:planning-42@example.com\r\n\
+        DTSTART:20250307T090000Z\r\n\
+        DTEND:20250307T100000Z\r\n\
+        SUMMARY:Planning\r\n\
+        RRULE:FREQ=WEEKLY;COUNT=4\r\n\
+        ORGANIZER;CN=Alice:mailto:alice@example.com\r\n\
+        ATTENDEE;CN=\"Doe: Bob\";PARTSTAT=NEEDS-ACTION;RSVP=TRUE:mailto:Bob@example.com\r\n\
+        ATTENDEE;CN=Carol;PARTSTAT=ACCEPTED:mailto:carol@example.com\r\n\
+        END:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    #[test]
+    fn test_parse_and_answer_invitation() {
+        let invitation = Invitation::parse(INVITE).unwrap();
+        assert_eq!(invitation.event.uid, "planning-42@example.com");
+        assert_eq!(
+            invitation
+                .attendee(["nobody@example.com", "bob@example.com"])
+                .and_then(|a| a.partstat),
+            Some(Partstat::NeedsAction)
+        );
+        assert_eq!(invitation.recurrence(), ["RRULE:FREQ=WEEKLY;COUNT=4"]);
+
+        let answered = invitation.answered("bob@example.com", Partstat::Accepted);
+        assert!(!answered.contains("METHOD:"));
+        assert!(
+            answered
+                .contains("ATTENDEE;CN=\"Doe: Bob\";PARTSTAT=ACCEPTED:mailto:Bob@example.com\r\n")
+        );
+        // Other attendees stay as they are
+        assert!(answered.contains("ATTENDEE;CN=Carol;PARTSTAT=ACCEPTED:mailto:carol@example.com"));
+
+        let stored = parse_ical(answered, Arc::new(CalendarInfo::default()));
+        assert_eq!(stored[0].attendees[0].partstat, Some(Partstat::Accepted));
+    }
+
+    #[test]
+    fn test_only_requests_are_invitations() {
+        let reply = INVITE.replace("METHOD:REQUEST", "METHOD:REPLY");
+        assert!(Invitation::parse(&reply).is_err());
+        let export = INVITE.replace("METHOD:REQUEST\r\n", "");
+        assert!(Invitation::parse(&export).is_err());
+    }
+}
@@ -1,4 +1,7 @@
 pub mod google;
 pub mod icloud;
+pub mod invite;
 pub mod protocol;
+pub mod subscription;
+pub mod travel;
 pub mod utils;
@@ -1,11 +1,15 @@
 use crate::{
-    calendar::utils::{CalDavEvent, CalendarInfo},
-    utils::errors::WatsonError,
+    calendar::{
+        invite::Invitation,
+        utils::{CalDavEvent, CalendarInfo, structs::Partstat},
+    },
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
 };
 use async_trait::async_trait;
 
 #[async_trait]
-pub trait CalendarProvider {
+pub trait CalendarProvider: Send {
     // Init required parameters
     async fn init(&mut self) -> Result<(), WatsonError>;
 
@@ -20,4 +24,29 @@ pub trait CalendarProvider {
         &mut self,
         calendars: Vec<CalendarInfo>,
     ) -> Result<Vec<CalDavEvent>, WatsonError>;
+
+    /// Returns the hrefs of all calendars that changed since they were last fetched.
+    ///
+    /// Providers without cheap change detection report every calendar as changed.
+    async fn changed_calendars(
+        &mut self,
+        calendars: &[CalendarInfo],
+    ) -> Result<Vec<String>, WatsonError> {
+        Ok(calendars.iter().map(|c| c.href.clone()).collect())
+    }
+
+    /// Answers `invitation` as the attendee `address`, the provider sends the reply to the
+    /// organizer. Unless declined, the event is added to `calendar`.
+    async fn respond(
+        &mut self,
+        _calendar: &CalendarInfo,
+        _invitation: Invitation,
+        _address: &str,
+        _answer: Partstat,
+    ) -> Result<(), WatsonError> {
+        Err(watson_err!(
+            WatsonErrorKind::InvalidData,
+            "The account can't answer invitations"
+        ))
+    }
 }
@@ -0,0 +1,241 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::{
+    calendar::{
+        icloud::utils::{parse_ical, unfold_ics},
+        protocol::CalendarProvider,
+        utils::{CalDavEvent, CalendarInfo},
+    },
+    config::profile::load_config_file,
+    utils::{
+        errors::{ResultExt, WatsonError},
+        paths::get_cache_dir,
+    },
+};
+
+/// `$XDG_CONFIG_HOME/watson/subscriptions.json`, read-only ICS feeds, e.g.
+/// `{ "feeds": [{ "name": "Holidays", "url": "webcal://example.org/holidays.ics" }] }`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SubscriptionConfig {
+    pub feeds: Vec<Subscription>,
+    /// Hours a downloaded feed is used before it is fetched again
+    pub cache_hours: u64,
+}
+impl Default for SubscriptionConfig {
+    fn default() -> Self {
+        Self {
+            feeds: Vec::new(),
+            cache_hours: 24,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Subscription {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Feeds stay out of reminder notifications unless this is set
+    #[serde(default)]
+    pub reminders: bool,
+}
+impl Subscription {
+    /// `webcal://` is a plain HTTPS download
+    pub fn http_url(&self) -> String {
+        match self
+            .url
+            .strip_prefix("webcal://")
+            .or_else(|| self.url.strip_prefix("webcals://"))
+        {
+            Some(rest) => format!("https://{rest}"),
+            None => self.url.clone(),
+        }
+    }
+    pub fn calendar_info(&self) -> CalendarInfo {
+        CalendarInfo {
+            href: self.url.clone(),
+            name: self.name.clone(),
+            color: self.color.clone(),
+            subscription: true,
+            silent: !self.reminders,
+            owner: None,
+        }
+    }
+}
+
+/// Provider for the feeds in `subscriptions.json`. Downloads are cached on disk, so clients that
+/// rebuild their providers on every refresh don't fetch the feeds again.
+pub struct SubscriptionClient {
+    client: Client,
+    config: SubscriptionConfig,
+}
+impl SubscriptionClient {
+    pub fn new(config: SubscriptionConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+        }
+    }
+    /// `None` if no feeds are configured
+    pub fn load() -> Option<Box<dyn CalendarProvider>> {
+        let config: SubscriptionConfig = match load_config_file("subscriptions") {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                return None;
+            }
+        };
+        if config.feeds.is_empty() {
+            return None;
+        }
+        Some(Box::new(Self::new(config)))
+    }
+
+    fn cache_path(url: &str) -> Result<PathBuf, WatsonError> {
+        let name: String = url
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let dir = get_cache_dir()?.join("subscriptions");
+        fs::create_dir_all(&dir).with_context(|| "Could not create subscription cache")?;
+        Ok(dir.join(format!("{name}.ics")))
+    }
+    fn is_fresh(&self, path: &Path) -> bool {
+        let lifetime = Duration::from_secs(self.config.cache_hours * 3600);
+        fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age < lifetime)
+    }
+    async fn ics(&self, feed: &Subscription) -> Result<String, WatsonError> {
+        let path = Self::cache_path(&feed.url)?;
+        if self.is_fresh(&path)
+            && let Ok(ics) = fs::read_to_string(&path)
+        {
+            return Ok(ics);
+        }
+
+        let download = async {
+            let resp = self
+                .client
+                .get(feed.http_url())
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok::<_, WatsonError>(resp.text().await?)
+        };
+        match download.await {
+            Ok(ics) => {
+                if let Err(e) = fs::write(&path, &ics) {
+                    eprintln!("{:?}", e);
+                }
+                Ok(ics)
+            }
+            // An outdated copy beats no holidays at all
+            Err(e) => fs::read_to_string(&path).map_err(|_| e),
+        }
+    }
+}
+
+#[async_trait]
+impl CalendarProvider for SubscriptionClient {
+    async fn init(&mut self) -> Result<(), WatsonError> {
+        Ok(())
+    }
+    async fn refresh(&mut self) -> Result<(), WatsonError> {
+        Ok(())
+    }
+    async fn get_calendars(&mut self) -> Result<Vec<CalendarInfo>, WatsonError> {
+        Ok(self
+            .config
+            .feeds
+            .iter()
+            .map(Subscription::calendar_info)
+            .collect())
+    }
+    async fn get_events(
+        &mut self,
+        calendars: Vec<CalendarInfo>,
+    ) -> Result<Vec<CalDavEvent>, WatsonError> {
+        let mut events = Vec::new();
+        for calendar in calendars {
+            let Some(feed) = self.config.feeds.iter().find(|f| f.url == calendar.href) else {
+                continue;
+            };
+            // One broken feed shouldn't hide the others
+            match self.ics(feed).await {
+                Ok(ics) => events.extend(parse_ical(unfold_ics(&ics), Arc::new(calendar))),
+                Err(e) => eprintln!("{:?}", e),
+            }
+        }
+        Ok(events)
+    }
+    /// Feeds change rarely, they only count as changed once their cached copy expired
+    async fn changed_calendars(
+        &mut self,
+        calendars: &[CalendarInfo],
+    ) -> Result<Vec<String>, WatsonError> {
+        let mut changed = Vec::new();
+        for calendar in calendars {
+            if !self.is_fresh(&Self::cache_path(&calendar.href)?) {
+                changed.push(calendar.href.clone());
+            }
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(url: &str) -> Subscription {
+        Subscription {
+            name: "Holidays".into(),
+            url: url.into(),
+            color: None,
+            reminders: false,
+        }
+    }
+
+    #[test]
+    fn test_webcal_urls() {
+        assert_eq!(
+            feed("webcal://example.org/holidays.ics").http_url(),
+            "https://example.org/holidays.ics"
+        );
+        assert_eq!(
+            feed("webcals://example.org/holidays.ics").http_url(),
+            "https://example.org/holidays.ics"
+        );
+        assert_eq!(
+            feed("http://example.org/holidays.ics").http_url(),
+            "http://example.org/holidays.ics"
+        );
+    }
+
+    #[test]
+    fn test_subscriptions_are_silent_by_default() {
+        let config: SubscriptionConfig = serde_json::from_str(
+            r#"{ "feeds": [{ "name": "Holidays", "url": "webcal://example.org/h.ics" }] }"#,
+        )
+        .unwrap();
+        assert_eq!(config.cache_hours, 24);
+
+        let info = config.feeds[0].calendar_info();
+        assert!(info.subscription);
+        assert!(info.silent);
+    }
+}
@@ -0,0 +1,320 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Local, Timelike, Utc, Weekday};
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::{
+    utils::{
+        astronomy::Location,
+        errors::{WatsonError, WatsonErrorKind},
+    },
+    watson_err,
+};
+
+const OSRM_URL: &str = "https://router.project-osrm.org";
+const NOMINATIM_URL: &str = "https://nominatim.openstreetmap.org/search";
+const GOOGLE_URL: &str = "https://maps.googleapis.com/maps/api/distancematrix/json";
+/// Nominatim refuses requests without one
+const USER_AGENT: &str = concat!("watson/", env!("CARGO_PKG_VERSION"));
+
+/// `$XDG_CONFIG_HOME/watson/travel.json`, e.g.
+/// `{ "home": { "latitude": 52.52, "longitude": 13.40 }, "router": { "kind": "osrm" } }`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TravelConfig {
+    pub home: Option<Location>,
+    pub work: Option<Location>,
+    /// Local hours `[start, end)` on weekdays in which trips start at `work`
+    pub work_hours: (u32, u32),
+    pub router: RouterConfig,
+    /// Minutes the reminder comes before it is time to leave
+    pub reminder_minutes: i64,
+    /// Minutes added to each route for parking and finding the room
+    pub buffer_minutes: i64,
+}
+impl Default for TravelConfig {
+    fn default() -> Self {
+        Self {
+            home: None,
+            work: None,
+            work_hours: (9, 17),
+            router: RouterConfig::default(),
+            reminder_minutes: 10,
+            buffer_minutes: 5,
+        }
+    }
+}
+impl TravelConfig {
+    /// Where a trip leaving at `at` starts, `None` without a configured home
+    pub fn origin_at(&self, at: DateTime<Local>) -> Option<Location> {
+        let weekday = !matches!(at.weekday(), Weekday::Sat | Weekday::Sun);
+        let (start, end) = self.work_hours;
+        match self.work {
+            Some(work) if weekday && (start..end).contains(&at.hour()) => Some(work),
+            _ => self.home,
+        }
+    }
+
+    /// When to leave for an event starting at `start` that is `travel` away
+    pub fn leave_by(&self, start: DateTime<Utc>, travel: Duration) -> DateTime<Utc> {
+        let travel =
+            chrono::Duration::from_std(travel).unwrap_or_else(|_| chrono::Duration::zero());
+        start - travel - chrono::Duration::minutes(self.buffer_minutes)
+    }
+
+    pub fn router(&self) -> Box<dyn TravelRouter> {
+        match &self.router {
+            RouterConfig::Osrm { url } => Box::new(OsrmRouter::new(url.clone())),
+            RouterConfig::Google { key } => Box::new(GoogleRouter::new(key.clone())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum RouterConfig {
+    /// Free-form locations are geocoded with Nominatim first
+    Osrm {
+        #[serde(default = "default_osrm_url")]
+        url: String,
+    },
+    Google {
+        key: String,
+    },
+}
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self::Osrm {
+            url: default_osrm_url(),
+        }
+    }
+}
+fn default_osrm_url() -> String {
+    OSRM_URL.to_string()
+}
+
+/// Travel time lookups for event reminders
+#[async_trait]
+pub trait TravelRouter: Send + Sync {
+    /// Driving time from `origin` to the free-form `destination`, e.g. an event's `location`
+    async fn travel_time(
+        &self,
+        origin: Location,
+        destination: &str,
+    ) -> Result<Duration, WatsonError>;
+}
+
+pub struct OsrmRouter {
+    client: Client,
+    url: String,
+}
+impl OsrmRouter {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: Client::new(),
+            url,
+        }
+    }
+
+    async fn geocode(&self, query: &str) -> Result<Location, WatsonError> {
+        #[derive(Deserialize)]
+        struct Place {
+            lat: String,
+            lon: String,
+        }
+
+        let text = self
+            .client
+            .get(NOMINATIM_URL)
+            .header("User-Agent", USER_AGENT)
+            .query(&[("q", query), ("format", "json"), ("limit", "1")])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let places: Vec<Place> = serde_json::from_str(&text)?;
+        let place = places
+            .first()
+            .ok_or_else(|| watson_err!(WatsonErrorKind::InvalidData, "Unknown place {}", query))?;
+        match (place.lat.parse(), place.lon.parse()) {
+            (Ok(latitude), Ok(longitude)) => Ok(Location {
+                latitude,
+                longitude,
+            }),
+            _ => Err(watson_err!(
+                WatsonErrorKind::InvalidData,
+                "Nominatim returned invalid coordinates for {}",
+                query
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl TravelRouter for OsrmRouter {
+    async fn travel_time(
+        &self,
+        origin: Location,
+        destination: &str,
+    ) -> Result<Duration, WatsonError> {
+        #[derive(Deserialize)]
+        struct Route {
+            duration: f64,
+        }
+        #[derive(Deserialize)]
+        struct Routes {
+            routes: Vec<Route>,
+        }
+
+        let target = self.geocode(destination).await?;
+        let url = format!(
+            "{}/route/v1/driving/{},{};{},{}",
+            self.url.trim_end_matches('/'),
+            origin.longitude,
+            origin.latitude,
+            target.longitude,
+            target.latitude
+        );
+        let text = self
+            .client
+            .get(url)
+            .query(&[("overview", "false")])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let routes: Routes = serde_json::from_str(&text)?;
+        let route = routes.routes.first().ok_or_else(|| {
+            watson_err!(WatsonErrorKind::InvalidData, "No route to {}", destination)
+        })?;
+        Ok(Duration::from_secs_f64(route.duration.max(0.0)))
+    }
+}
+
+pub struct GoogleRouter {
+    client: Client,
+    key: String,
+}
+impl GoogleRouter {
+    pub fn new(key: String) -> Self {
+        Self {
+            client: Client::new(),
+            key,
+        }
+    }
+}
+
+#[async_trait]
+impl TravelRouter for GoogleRouter {
+    async fn travel_time(
+        &self,
+        origin: Location,
+        destination: &str,
+    ) -> Result<Duration, WatsonError> {
+        #[derive(Deserialize)]
+        struct Value {
+            value: u64,
+        }
+        #[derive(Deserialize)]
+        struct Element {
+            status: String,
+            duration: Option<Value>,
+        }
+        #[derive(Deserialize)]
+        struct Row {
+            elements: Vec<Element>,
+        }
+        #[derive(Deserialize)]
+        struct Matrix {
+            rows: Vec<Row>,
+        }
+
+        let origin = format!("{},{}", origin.latitude, origin.longitude);
+        let text = self
+            .client
+            .get(GOOGLE_URL)
+            .query(&[
+                ("origins", origin.as_str()),
+                ("destinations", destination),
+                ("key", self.key.as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let matrix: Matrix = serde_json::from_str(&text)?;
+        let element = matrix
+            .rows
+            .first()
+            .and_then(|r| r.elements.first())
+            .ok_or_else(|| {
+                watson_err!(WatsonErrorKind::InvalidData, "No route to {}", destination)
+            })?;
+        match &element.duration {
+            Some(duration) if element.status == "OK" => Ok(Duration::from_secs(duration.value)),
+            _ => Err(watson_err!(
+                WatsonErrorKind::InvalidData,
+                "No route to {}: {}",
+                destination,
+                element.status
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const HOME: Location = Location {
+        latitude: 52.52,
+        longitude: 13.40,
+    };
+    const WORK: Location = Location {
+        latitude: 52.50,
+        longitude: 13.45,
+    };
+
+    #[test]
+    fn test_origin_follows_work_hours() {
+        let config = TravelConfig {
+            home: Some(HOME),
+            work: Some(WORK),
+            ..Default::default()
+        };
+        // 2025-03-07 is a Friday
+        let friday = |hour| Local.with_ymd_and_hms(2025, 3, 7, hour, 0, 0).unwrap();
+        assert_eq!(config.origin_at(friday(8)), Some(HOME));
+        assert_eq!(config.origin_at(friday(9)), Some(WORK));
+        assert_eq!(config.origin_at(friday(17)), Some(HOME));
+        let saturday = Local.with_ymd_and_hms(2025, 3, 8, 11, 0, 0).unwrap();
+        assert_eq!(config.origin_at(saturday), Some(HOME));
+    }
+
+    #[test]
+    fn test_leave_by_includes_buffer() {
+        let config = TravelConfig::default();
+        let start = Utc.with_ymd_and_hms(2025, 3, 7, 14, 0, 0).unwrap();
+        assert_eq!(
+            config.leave_by(start, Duration::from_secs(15 * 60)),
+            Utc.with_ymd_and_hms(2025, 3, 7, 13, 40, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_router_config() {
+        let config: TravelConfig =
+            serde_json::from_str(r#"{ "router": { "kind": "google", "key": "abc" } }"#).unwrap();
+        assert!(matches!(config.router, RouterConfig::Google { key } if key == "abc"));
+
+        let config: TravelConfig =
+            serde_json::from_str(r#"{ "router": { "kind": "osrm" } }"#).unwrap();
+        assert!(matches!(config.router, RouterConfig::Osrm { url } if url == OSRM_URL));
+    }
+}
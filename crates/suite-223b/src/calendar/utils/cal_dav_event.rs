@@ -1,6 +1,6 @@
 use std::{borrow::Cow, cell::Cell, sync::Arc};
 
-use chrono::{DateTime, Datelike, Days, Local, NaiveDate, Utc, Weekday};
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
 use ical::parser::ical::component::IcalEvent;
 use serde::{Deserialize, Serialize};
 
@@ -9,7 +9,8 @@ use crate::{
         protocol,
         utils::{
             funcs::{last_day_of_month, parse_exdate, parse_rdate, parse_until, parse_utc},
-            structs::{Attendee, DateTimeSpec, RecurrenceRule},
+            occurrences::OccurrenceCache,
+            structs::{Attendee, DateTimeSpec, Partstat, RecurrenceRule},
         },
     },
     utils::errors::{WatsonError, WatsonErrorKind},
@@ -21,6 +22,13 @@ pub struct CalendarInfo {
     pub href: String,
     pub name: String,
     pub color: Option<String>,
+    /// Read-only feed from `subscriptions.json`, e.g. public holidays
+    pub subscription: bool,
+    /// Events never trigger reminders
+    pub silent: bool,
+    /// Address of the account, to find the user among the attendees
+    #[serde(default)]
+    pub owner: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -151,8 +159,9 @@ impl TryFrom<IcalEvent> for CalDavEvent {
                 }
 
                 "RRULE" => out.recurrence = prop.value.map(|v| RecurrenceRule::new(v)),
-                "RDATE" => out.rdates = parse_rdate(prop).unwrap_or_default(),
-                "EXDATE" => out.exdates = parse_exdate(prop).unwrap_or_default(),
+                // Both may appear multiple times
+                "RDATE" => out.rdates.extend(parse_rdate(prop).unwrap_or_default()),
+                "EXDATE" => out.exdates.extend(parse_exdate(prop).unwrap_or_default()),
 
                 "LAST-MODIFIED" => out.last_modified = prop.value.and_then(|v| parse_utc(&v)),
 
@@ -216,21 +225,29 @@ impl Default for CalEventType {
 
 impl CalDavEvent {
     pub fn occurs_on_day(&self, day_to_check: &NaiveDate) -> bool {
+        if self.recurrence.is_none() {
+            return self.occurs_on_day_in(day_to_check, &Local);
+        }
+        OccurrenceCache::global().get_or_insert_with(self, day_to_check, || {
+            self.occurs_on_day_in(day_to_check, &Local)
+        })
+    }
+    /// Same as [`Self::occurs_on_day`] with days taken in `tz`, bypassing the occurrence cache
+    pub fn occurs_on_day_in<T: TimeZone>(&self, day_to_check: &NaiveDate, tz: &T) -> bool {
         let Some(start) = self.start.as_ref() else {
             return false;
         };
 
-        let start_local = start.utc_time().with_timezone(&Local).date_naive();
+        let start_local = start.date_in(tz);
         let mut end_local = self
             .end
             .as_ref()
-            .map(|e| e.utc_time().with_timezone(&Local).date_naive())
+            .map(|e| e.date_in(tz))
             .unwrap_or(start_local);
 
         if let Some(recurrence) = &self.recurrence {
-            let handler = RecurrenceHandler::from_raw(&recurrence.raw, &self.rdates, &self.exdates);
-            let active = handler.is_active_on(&start_local, day_to_check);
-            return active;
+            return RecurrenceHandler::from_raw(&recurrence.raw, &self.rdates, &self.exdates)
+                .is_active_in(&start_local, day_to_check, tz);
         }
 
         if self.event_type == CalEventType::AllDay {
@@ -241,6 +258,45 @@ impl CalDavEvent {
 
         *day_to_check >= start_local && *day_to_check <= end_local
     }
+    /// How the user answered the invitation, None for events without attendees or when the
+    /// user is not among them
+    pub fn own_partstat(&self) -> Option<Partstat> {
+        let owner = self.calendar_info.owner.as_deref();
+        self.attendees
+            .iter()
+            .find(|a| a.is_self || owner.is_some_and(|o| a.has_address(o)))
+            .and_then(|a| a.partstat)
+    }
+    /// Whether every word of `query` shows up in the title, location, description or an
+    /// attendee, ignoring case
+    pub fn matches(&self, query: &str) -> bool {
+        let attendees = self
+            .attendees
+            .iter()
+            .flat_map(|a| [a.display_name.as_deref(), a.email.as_deref()]);
+        let haystack = [
+            Some(self.title.as_str()),
+            self.location.as_deref(),
+            self.description.as_deref(),
+        ]
+        .into_iter()
+        .chain(attendees)
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("\n")
+        .to_lowercase();
+
+        let mut words = query.split_whitespace().peekable();
+        words.peek().is_some() && words.all(|w| haystack.contains(&w.to_lowercase()))
+    }
+    /// Local start and end on `day`. Recurring events keep the time of day and length of their
+    /// first occurrence.
+    pub fn local_span_on(&self, day: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        let start = self.start.as_ref()?.utc_time().with_timezone(&Local);
+        let end = self.end.as_ref()?.utc_time().with_timezone(&Local);
+        let start_dt = day.and_time(start.time());
+        Some((start_dt, start_dt + end.signed_duration_since(start)))
+    }
     #[inline(always)]
     pub fn start_utc(&self) -> Option<DateTime<Utc>> {
         self.start.as_ref().map(|spec| spec.utc_time())
@@ -398,6 +454,16 @@ impl<'d> RecurrenceHandler<'d> {
 
     #[inline(always)]
     pub fn is_active_on(&self, dt_start: &NaiveDate, target: &NaiveDate) -> bool {
+        self.is_active_in(dt_start, target, &Local)
+    }
+    /// Same as [`Self::is_active_on`] with R/EXDATES taken in `tz`
+    #[inline(always)]
+    pub fn is_active_in<T: TimeZone>(
+        &self,
+        dt_start: &NaiveDate,
+        target: &NaiveDate,
+        tz: &T,
+    ) -> bool {
         // UNTIL early return
         if let Some(u) = &self.until {
             let until_date = DateTime::from_timestamp(*u, 0)
@@ -409,18 +475,10 @@ impl<'d> RecurrenceHandler<'d> {
         }
 
         // RDATE and EXDATE check
-        if self
-            .exdates
-            .iter()
-            .any(|ex| ex.utc_time().date_naive() == *target)
-        {
+        if self.exdates.iter().any(|ex| ex.date_in(tz) == *target) {
             return false;
         }
-        if self
-            .rdates
-            .iter()
-            .any(|ex| ex.utc_time().date_naive() == *target)
-        {
+        if self.rdates.iter().any(|rd| rd.date_in(tz) == *target) {
             return true;
         }
 
@@ -0,0 +1,105 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+use super::{CalDavEvent, CalEventType, structs::Partstat};
+
+/// Time without timed events, in local time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreeSlot {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+/// Gaps of at least `min` between the timed events of `day` within `from..until`, in order.
+/// Declined invitations don't block any time.
+pub fn free_slots(
+    events: &[CalDavEvent],
+    day: NaiveDate,
+    from: NaiveDateTime,
+    until: NaiveDateTime,
+    min: Duration,
+) -> Vec<FreeSlot> {
+    let mut busy: Vec<(NaiveDateTime, NaiveDateTime)> = events
+        .iter()
+        .filter(|e| e.event_type == CalEventType::Timed)
+        .filter(|e| e.own_partstat() != Some(Partstat::Declined))
+        .filter_map(|e| e.local_span_on(day))
+        .filter(|(start, end)| *end > from && *start < until)
+        .collect();
+    busy.sort();
+
+    let mut slots = Vec::new();
+    let mut cursor = from;
+    for (start, end) in busy.into_iter().chain([(until, until)]) {
+        if start > cursor && start - cursor >= min {
+            slots.push(FreeSlot {
+                start: cursor,
+                end: start,
+            });
+        }
+        cursor = cursor.max(end);
+    }
+    slots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::utils::structs::DateTimeSpec;
+    use chrono::{Local, NaiveTime, TimeZone};
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 3, 7)
+            .unwrap()
+            .and_time(NaiveTime::from_hms_opt(hour, minute, 0).unwrap())
+    }
+
+    fn timed(start: NaiveDateTime, end: NaiveDateTime) -> CalDavEvent {
+        let utc = |t: NaiveDateTime| DateTimeSpec::DateTime {
+            value: Local.from_local_datetime(&t).unwrap().to_utc(),
+        };
+        CalDavEvent {
+            start: Some(utc(start)),
+            end: Some(utc(end)),
+            event_type: CalEventType::Timed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_free_slots_between_events() {
+        let events = [
+            timed(at(9, 0), at(10, 0)),
+            // Overlapping the first one
+            timed(at(9, 30), at(11, 0)),
+            timed(at(11, 10), at(12, 0)),
+            timed(at(14, 30), at(16, 0)),
+        ];
+        let slots = free_slots(
+            &events,
+            at(0, 0).date(),
+            at(8, 0),
+            at(18, 0),
+            Duration::minutes(15),
+        );
+        let spans: Vec<_> = slots.iter().map(|s| (s.start, s.end)).collect();
+        // The ten minutes after 11:00 are too short
+        assert_eq!(
+            spans,
+            [
+                (at(8, 0), at(9, 0)),
+                (at(12, 0), at(14, 30)),
+                (at(16, 0), at(18, 0))
+            ]
+        );
+
+        let slots = free_slots(
+            &events,
+            at(0, 0).date(),
+            at(9, 45),
+            at(15, 0),
+            Duration::minutes(15),
+        );
+        assert_eq!(slots[0].start, at(12, 0));
+        assert_eq!(slots.len(), 1);
+    }
+}
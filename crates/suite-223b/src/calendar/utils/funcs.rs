@@ -43,10 +43,11 @@ pub fn parse_until(s: &str) -> Option<DateTimeSpec> {
 
 pub use parse_rdate as parse_exdate;
 pub fn parse_rdate(prop: ical::property::Property) -> Option<Vec<DateTimeSpec>> {
+    let tzid = tzid_param(&prop.params);
     let val = &prop.value?;
     Some(
         val.split(',')
-            .filter_map(|p| parse_until(p))
+            .filter_map(|p| DateTimeSpec::parse(p, tzid.as_deref()).ok())
             .collect::<Vec<DateTimeSpec>>(),
     )
 }
@@ -1,5 +1,11 @@
 mod cal_dav_event;
+mod free;
 pub mod funcs;
+mod occurrences;
+mod registry;
 pub mod structs;
 
-pub use cal_dav_event::{CalDavEvent, CalEventType, CalendarInfo, Meeting};
+pub use cal_dav_event::{CalDavEvent, CalEventType, CalendarInfo, Meeting, RecurrenceHandler};
+pub use free::{FreeSlot, free_slots};
+pub use occurrences::OccurrenceCache;
+pub use registry::{CalendarRegistry, CompactEvents};
@@ -0,0 +1,118 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::OnceLock,
+};
+
+use chrono::NaiveDate;
+use dashmap::DashMap;
+
+use crate::calendar::utils::CalDavEvent;
+
+/// Memoized results of RRULE evaluation, keyed by uid and date. Lookups borrow the uid, only
+/// the first day cached for an event allocates.
+///
+/// Every entry stores a fingerprint of the recurrence relevant fields, so an event that changed
+/// remotely (new RRULE, SEQUENCE, LAST-MODIFIED, RDATE or EXDATE) is reevaluated even when
+/// nobody invalidated it explicitly.
+#[derive(Debug, Default)]
+pub struct OccurrenceCache {
+    entries: DashMap<String, HashMap<NaiveDate, (u64, bool)>>,
+}
+
+impl OccurrenceCache {
+    pub fn global() -> &'static Self {
+        static CACHE: OnceLock<OccurrenceCache> = OnceLock::new();
+        CACHE.get_or_init(OccurrenceCache::default)
+    }
+
+    /// Returns the cached result for `event` on `day`, evaluating it with `eval` on a miss.
+    pub fn get_or_insert_with(
+        &self,
+        event: &CalDavEvent,
+        day: &NaiveDate,
+        eval: impl FnOnce() -> bool,
+    ) -> bool {
+        let fingerprint = Self::fingerprint(event);
+
+        if let Some(days) = self.entries.get(event.uid.as_str())
+            && let Some(&(cached_fingerprint, active)) = days.get(day)
+            && cached_fingerprint == fingerprint
+        {
+            return active;
+        }
+
+        let active = eval();
+        match self.entries.get_mut(event.uid.as_str()) {
+            Some(mut days) => {
+                days.insert(*day, (fingerprint, active));
+            }
+            None => {
+                self.entries.insert(
+                    event.uid.clone(),
+                    HashMap::from([(*day, (fingerprint, active))]),
+                );
+            }
+        }
+        active
+    }
+
+    /// Drops every cached day of the event with `uid`
+    pub fn invalidate(&self, uid: &str) {
+        self.entries.remove(uid);
+    }
+
+    /// Drops every cached day before `day`
+    pub fn evict_before(&self, day: &NaiveDate) {
+        self.entries.retain(|_, days| {
+            days.retain(|key_day, _| key_day >= day);
+            !days.is_empty()
+        });
+    }
+
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+
+    /// Cached days over all events
+    pub fn len(&self) -> usize {
+        self.entries.iter().map(|days| days.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn fingerprint(event: &CalDavEvent) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        event.recurrence.as_ref().map(|r| &r.raw).hash(&mut hasher);
+        event.start_utc().map(|s| s.timestamp()).hash(&mut hasher);
+        event.sequence.hash(&mut hasher);
+        event.last_modified.hash(&mut hasher);
+        event.rdates.hash(&mut hasher);
+        event.exdates.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::utils::structs::DateTimeSpec;
+
+    #[test]
+    fn test_moved_exdate_is_reevaluated() {
+        let cache = OccurrenceCache::default();
+        let day = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        let mut event = CalDavEvent {
+            uid: "weekly".into(),
+            exdates: vec![DateTimeSpec::Date(day)],
+            ..Default::default()
+        };
+        assert!(!cache.get_or_insert_with(&event, &day, || false));
+
+        // Same number of exceptions, another day
+        event.exdates = vec![DateTimeSpec::Date(day.succ_opt().unwrap())];
+        assert!(cache.get_or_insert_with(&event, &day, || true));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::calendar::utils::{CalDavEvent, CalendarInfo};
+
+/// Calendars by href, so all events of a calendar share one `CalendarInfo` instead of each
+/// refresh or cache load bringing its own copies.
+#[derive(Debug, Default)]
+pub struct CalendarRegistry {
+    calendars: DashMap<String, Arc<CalendarInfo>>,
+}
+
+impl CalendarRegistry {
+    pub fn global() -> &'static Self {
+        static REGISTRY: OnceLock<CalendarRegistry> = OnceLock::new();
+        REGISTRY.get_or_init(CalendarRegistry::default)
+    }
+
+    /// The registered info of `info.href`. A calendar that changed, e.g. got renamed, replaces
+    /// the registered one.
+    pub fn intern(&self, info: &Arc<CalendarInfo>) -> Arc<CalendarInfo> {
+        if let Some(known) = self.calendars.get(info.href.as_str())
+            && (Arc::ptr_eq(&known, info) || **known == **info)
+        {
+            return Arc::clone(&known);
+        }
+        self.calendars.insert(info.href.clone(), Arc::clone(info));
+        Arc::clone(info)
+    }
+
+    /// Points every event at the registered info of its calendar
+    pub fn intern_events(&self, events: &mut [CalDavEvent]) {
+        for event in events {
+            event.calendar_info = self.intern(&event.calendar_info);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.calendars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calendars.is_empty()
+    }
+}
+
+/// Events for on-disk caches, with every calendar stored once and referenced by index
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CompactEvents {
+    calendars: Vec<CalendarInfo>,
+    /// Index into `calendars` and the event, whose own `calendar_info` is left empty
+    events: Vec<(u32, CalDavEvent)>,
+}
+
+impl CompactEvents {
+    pub fn pack(events: &[CalDavEvent]) -> Self {
+        let empty = Arc::new(CalendarInfo::default());
+        let mut calendars = Vec::new();
+        let mut indices: HashMap<&str, u32> = HashMap::new();
+        let events = events
+            .iter()
+            .map(|event| {
+                let info = &event.calendar_info;
+                let index = *indices.entry(info.href.as_str()).or_insert_with(|| {
+                    calendars.push((**info).clone());
+                    calendars.len() as u32 - 1
+                });
+                let event = CalDavEvent {
+                    calendar_info: Arc::clone(&empty),
+                    ..event.clone()
+                };
+                (index, event)
+            })
+            .collect();
+
+        Self { calendars, events }
+    }
+
+    /// The events with their calendars taken from the registry. Events pointing at a calendar
+    /// that isn't stored are dropped.
+    pub fn unpack(self, registry: &CalendarRegistry) -> Vec<CalDavEvent> {
+        let calendars: Vec<Arc<CalendarInfo>> = self
+            .calendars
+            .into_iter()
+            .map(|c| registry.intern(&Arc::new(c)))
+            .collect();
+        self.events
+            .into_iter()
+            .filter_map(|(index, mut event)| {
+                event.calendar_info = Arc::clone(calendars.get(index as usize)?);
+                Some(event)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(uid: &str, calendar: &Arc<CalendarInfo>) -> CalDavEvent {
+        CalDavEvent {
+            uid: uid.into(),
+            calendar_info: Arc::clone(calendar),
+            ..Default::default()
+        }
+    }
+
+    fn calendar(href: &str, name: &str) -> Arc<CalendarInfo> {
+        Arc::new(CalendarInfo {
+            href: href.into(),
+            name: name.into(),
+            color: Some("#e9a949".into()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_intern_shares_equal_calendars() {
+        let registry = CalendarRegistry::default();
+        let first = registry.intern(&calendar("/work/", "Work"));
+        let again = registry.intern(&calendar("/work/", "Work"));
+        assert!(Arc::ptr_eq(&first, &again));
+
+        // Renamed remotely
+        let renamed = registry.intern(&calendar("/work/", "Office"));
+        assert!(!Arc::ptr_eq(&first, &renamed));
+        assert_eq!(
+            registry.intern(&calendar("/work/", "Office")).name,
+            "Office"
+        );
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn test_compact_round_trip() {
+        let work = calendar("/calendars/work/", "Work");
+        let home = calendar("/calendars/home/", "Home");
+        let events: Vec<CalDavEvent> = (0..50)
+            .map(|i| {
+                event(
+                    &format!("event-{i}"),
+                    if i % 2 == 0 { &work } else { &home },
+                )
+            })
+            .collect();
+
+        let config = bincode::config::standard();
+        let compact = bincode::serde::encode_to_vec(CompactEvents::pack(&events), config).unwrap();
+        let plain = bincode::serde::encode_to_vec(&events, config).unwrap();
+        assert!(compact.len() < plain.len());
+
+        let (decoded, _): (CompactEvents, _) =
+            bincode::serde::decode_from_slice(&compact, config).unwrap();
+        let registry = CalendarRegistry::default();
+        let unpacked = decoded.unpack(&registry);
+        assert_eq!(unpacked, events);
+        assert!(Arc::ptr_eq(
+            &unpacked[0].calendar_info,
+            &unpacked[2].calendar_info
+        ));
+        assert_eq!(registry.len(), 2);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{str::FromStr, time::Duration};
+use std::{path::PathBuf, str::FromStr, time::Duration};
 
 use chrono::{
     DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, offset::LocalResult,
@@ -7,6 +7,7 @@ use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    contacts::email_address,
     utils::errors::{WatsonError, WatsonErrorKind},
     watson_err,
 };
@@ -24,7 +25,7 @@ pub enum EventFilter {
     },
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
 pub enum DateTimeSpec {
     Date(NaiveDate),
     DateTime { value: DateTime<Utc> },
@@ -64,15 +65,15 @@ impl TryFrom<ical::property::Property> for DateTimeSpec {
             ));
         };
 
-        let mut tzid = None;
-        if let Some(params) = value.params {
-            for (k, v) in params {
-                if k.as_str() == "TZID" {
-                    tzid = v.into_iter().next();
-                }
-            }
-        }
-
+        let tzid = tzid_param(&value.params);
+        Self::parse(inner, tzid.as_deref())
+    }
+}
+impl DateTimeSpec {
+    /// Parses a DATE or DATE-TIME value. Floating times are interpreted in `tzid` if given,
+    /// otherwise as UTC.
+    pub fn parse(inner: &str, tzid: Option<&str>) -> Result<Self, WatsonError> {
+        let inner = inner.trim();
         if inner.len() == 8 {
             // It's a date-only value: "YYYYMMDD"
             Ok(DateTimeSpec::Date(
@@ -98,9 +99,8 @@ impl TryFrom<ical::property::Property> for DateTimeSpec {
 
             let dt_utc = if is_utc {
                 Utc.from_utc_datetime(&naive)
-            } else if let Some(tzid) = &tzid {
-                let tzid = windows_to_iana(&tzid);
-                let tz: Tz = tzid.parse().map_err(|_| {
+            } else if let Some(tzid) = tzid {
+                let tz = resolve_tzid(tzid).ok_or_else(|| {
                     watson_err!(
                         WatsonErrorKind::InvalidAttribute,
                         "Failed to parse TZID `{}` into a valid timezone",
@@ -108,15 +108,20 @@ impl TryFrom<ical::property::Property> for DateTimeSpec {
                     )
                 })?;
 
-                tz.from_local_datetime(&naive)
-                    .single()
-                    .ok_or(watson_err!(
-                        WatsonErrorKind::InvalidAttribute,
-                        "Ambiguous or non-existent local datetime `{}` in timezone `{}`",
-                        naive,
-                        tzid
-                    ))?
-                    .with_timezone(&Utc)
+                match tz.from_local_datetime(&naive) {
+                    LocalResult::Single(dt) => dt,
+                    // Fall back: take the earlier of the two instants
+                    LocalResult::Ambiguous(a, _) => a,
+                    LocalResult::None => {
+                        return Err(watson_err!(
+                            WatsonErrorKind::InvalidAttribute,
+                            "Non-existent local datetime `{}` in timezone `{}`",
+                            naive,
+                            tzid
+                        ));
+                    }
+                }
+                .with_timezone(&Utc)
             } else {
                 Utc.from_utc_datetime(&naive)
             };
@@ -124,6 +129,44 @@ impl TryFrom<ical::property::Property> for DateTimeSpec {
             Ok(DateTimeSpec::DateTime { value: dt_utc })
         }
     }
+    /// The calendar day this value falls on in the local timezone. DATE values are floating and
+    /// are returned unchanged.
+    pub fn local_date(&self) -> NaiveDate {
+        self.date_in(&Local)
+    }
+    /// Same as [`Self::local_date`] for an explicit timezone
+    pub fn date_in<T: TimeZone>(&self, tz: &T) -> NaiveDate {
+        match self {
+            Self::Date(d) => *d,
+            Self::DateTime { value } => value.with_timezone(tz).date_naive(),
+        }
+    }
+}
+
+/// Extracts the TZID parameter of a property
+pub fn tzid_param(params: &Option<Vec<(String, Vec<String>)>>) -> Option<String> {
+    params
+        .as_ref()?
+        .iter()
+        .find(|(k, _)| k.as_str() == "TZID")
+        .and_then(|(_, v)| v.first())
+        .map(|v| v.trim_matches('"').to_string())
+}
+
+/// Maps a TZID as found in the wild to a timezone.
+///
+/// Besides IANA names this accepts Windows zone names and vendor prefixed paths such as
+/// `/freeassociation.sourceforge.net/Tzfile/Europe/Berlin`.
+pub fn resolve_tzid(tzid: &str) -> Option<Tz> {
+    let tzid = windows_to_iana(tzid.trim());
+    if let Ok(tz) = tzid.parse::<Tz>() {
+        return Some(tz);
+    }
+
+    // Strip vendor prefixes one segment at a time
+    tzid.match_indices('/')
+        .filter_map(|(i, _)| tzid[i + 1..].parse::<Tz>().ok())
+        .next()
 }
 
 fn windows_to_iana(tzid: &str) -> String {
@@ -171,6 +214,18 @@ impl FromStr for Partstat {
         }
     }
 }
+impl Partstat {
+    /// The `PARTSTAT` parameter value
+    pub fn as_ical(&self) -> &'static str {
+        match self {
+            Self::Accepted => "ACCEPTED",
+            Self::Declined => "DECLINED",
+            Self::Tentative => "TENTATIVE",
+            Self::Delegated => "DELEGATED",
+            Self::NeedsAction | Self::Unknown => "NEEDS-ACTION",
+        }
+    }
+}
 
 #[derive(Default, Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Attendee {
@@ -179,11 +234,23 @@ pub struct Attendee {
     pub display_name: Option<String>,
     pub role: Option<String>,
     pub partstat: Option<Partstat>,
+    /// Photo from the address book, filled in by the daemon
+    #[serde(default)]
+    pub avatar: Option<PathBuf>,
+    /// The user of the account the event was fetched with, when the provider tells
+    #[serde(default)]
+    pub is_self: bool,
 }
 impl Attendee {
     pub fn is_valid(&self) -> bool {
         self.email.is_some()
     }
+    /// Whether the attendee's address is `address`, ignoring the `mailto:` scheme and case
+    pub fn has_address(&self, address: &str) -> bool {
+        self.email
+            .as_deref()
+            .is_some_and(|email| email_address(email) == email_address(address))
+    }
 }
 pub struct InvalidAttendee;
 impl TryFrom<ical::property::Property> for Attendee {
@@ -210,6 +277,8 @@ impl TryFrom<ical::property::Property> for Attendee {
             display_name: cn,
             role,
             partstat,
+            avatar: None,
+            is_self: false,
         };
 
         if attendee.is_valid() {
@@ -1,15 +1,25 @@
-use crate::{auth::AuthTui, utils::errors::WatsonError};
+use std::path::PathBuf;
+
+use crate::{
+    auth::AuthTui, config::profile::set_profile, protocol::SocketData, utils::errors::WatsonError,
+};
 
 pub struct ArgParse;
 impl ArgParse {
     pub async fn parse(args: std::env::Args) -> Result<(), WatsonError> {
-        let args = args.skip(1).peekable();
-        for arg in args {
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
             match arg.as_str() {
                 "auth" => {
                     let mut tui = AuthTui::new()?;
                     tui.run().await?;
                 }
+                "--profile" => set_profile(args.next()),
+                "--socket" => {
+                    if let Some(path) = args.next() {
+                        SocketData::set_override(PathBuf::from(path));
+                    }
+                }
                 _ => {}
             }
         }
@@ -1 +1,2 @@
 pub mod flags;
+pub mod profile;
@@ -0,0 +1,127 @@
+use std::{fs, fs::File, io::BufReader, path::Path, sync::OnceLock};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    utils::{
+        errors::{ResultExt, WatsonError, WatsonErrorKind},
+        paths::get_config_dir,
+    },
+    watson_err,
+};
+
+static PROFILE: OnceLock<Option<String>> = OnceLock::new();
+
+/// Selects the profile whose overrides are merged over every config file. `None` picks the
+/// profile named after the host if `profile.<hostname>.json` exists. Only the first call counts.
+pub fn set_profile(name: Option<String>) {
+    let _ = PROFILE.set(name.or_else(host_profile));
+}
+
+pub fn active_profile() -> Option<&'static str> {
+    PROFILE.get_or_init(host_profile).as_deref()
+}
+
+fn host_profile() -> Option<String> {
+    let host = fs::read_to_string("/proc/sys/kernel/hostname").ok()?;
+    let host = host.trim();
+    get_config_dir()
+        .ok()?
+        .join(format!("profile.{host}.json"))
+        .exists()
+        .then(|| host.to_string())
+}
+
+fn read_json(path: &Path) -> Result<Option<Value>, WatsonError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let file = File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+    serde_json::from_reader(BufReader::new(file))
+        .map(Some)
+        .map_err(|e| {
+            watson_err!(
+                WatsonErrorKind::ConfigError,
+                format!("{}: {e}", path.display())
+            )
+        })
+}
+
+/// Merges `overlay` into `base`. Objects are merged key by key, anything else, arrays included,
+/// is replaced.
+pub fn deep_merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// `<name>.json` from the config directory with the `name` section of the active profile merged
+/// over it, e.g. `{ "night_light": { "intensity": 40 } }` in `profile.laptop.json`.
+/// `None` if neither exists.
+pub fn load_merged(name: &str) -> Result<Option<Value>, WatsonError> {
+    let dir = get_config_dir()?;
+    let mut value = read_json(&dir.join(format!("{name}.json")))?;
+    if let Some(profile) = active_profile()
+        && let Some(Value::Object(mut sections)) =
+            read_json(&dir.join(format!("profile.{profile}.json")))?
+        && let Some(overlay) = sections.remove(name)
+    {
+        match &mut value {
+            Some(base) => deep_merge(base, overlay),
+            None => value = Some(overlay),
+        }
+    }
+    Ok(value)
+}
+
+/// [`load_merged`] deserialized into `T`, the default if there is no config
+pub fn load_config_file<T: DeserializeOwned + Default>(name: &str) -> Result<T, WatsonError> {
+    match load_merged(name)? {
+        Some(value) => serde_json::from_value(value)
+            .map_err(|e| watson_err!(WatsonErrorKind::ConfigError, format!("{name}.json: {e}"))),
+        None => Ok(T::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_deep_merge() {
+        let mut base = json!({
+            "intensity": 60,
+            "schedule": { "type": "fixed", "start": "21:00", "end": "07:00" },
+            "widgets": [1, 2, 3],
+        });
+        deep_merge(
+            &mut base,
+            json!({
+                "schedule": { "start": "22:00" },
+                "widgets": [4],
+                "latitude": 52.5,
+            }),
+        );
+        assert_eq!(
+            base,
+            json!({
+                "intensity": 60,
+                "schedule": { "type": "fixed", "start": "22:00", "end": "07:00" },
+                "widgets": [4],
+                "latitude": 52.5,
+            })
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use quick_xml::{Reader, escape::resolve_predefined_entity, events::Event};
+use reqwest::{
+    Client, Method, StatusCode, Url,
+    header::{CONTENT_TYPE, LOCATION},
+    redirect::Policy,
+};
+
+use crate::{
+    auth::{Credential, CredentialData, CredentialService},
+    calendar::google::GoogleAuth,
+    contacts::{VCard, parse_vcard},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+const ICLOUD_CARDDAV: &str = "https://contacts.icloud.com/";
+const GOOGLE_CARDDAV: &str = "https://www.googleapis.com/.well-known/carddav";
+/// Discovery answers with redirects, which must keep their method and body
+const MAX_REDIRECTS: usize = 5;
+
+const PRINCIPAL: &str =
+    r#"<d:propfind xmlns:d="DAV:"><d:prop><d:current-user-principal/></d:prop></d:propfind>"#;
+const HOME_SET: &str = r#"<d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:carddav"><d:prop><c:addressbook-home-set/></d:prop></d:propfind>"#;
+const ADDRESS_BOOKS: &str =
+    r#"<d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/></d:prop></d:propfind>"#;
+const CARDS: &str = r#"<c:addressbook-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:carddav"><d:prop><c:address-data/></d:prop></c:addressbook-query>"#;
+
+/// One `<response>` of a multistatus answer
+#[derive(Debug, Default, PartialEq)]
+struct DavResponse {
+    href: String,
+    /// Text of each property, or the `href` inside it
+    props: HashMap<String, String>,
+    /// Children of `resourcetype`, e.g. `addressbook`
+    types: Vec<String>,
+}
+
+/// Reads the address books of an account, using the credentials calendars use
+pub struct CardDavClient {
+    client: Client,
+    credential: Credential,
+    base: Url,
+}
+impl CardDavClient {
+    /// `None` for accounts without a known CardDAV server
+    pub fn for_credential(credential: Credential) -> Option<Self> {
+        let base = match (&credential.data, &credential.service) {
+            (CredentialData::Password { .. }, CredentialService::Icloud) => ICLOUD_CARDDAV,
+            (CredentialData::OAuth { .. }, CredentialService::Google) => GOOGLE_CARDDAV,
+            _ => return None,
+        };
+        let client = Client::builder()
+            .redirect(Policy::none())
+            .build()
+            .inspect_err(|e| eprintln!("{:?}", e))
+            .ok()?;
+        Some(Self {
+            client,
+            credential,
+            base: Url::parse(base).ok()?,
+        })
+    }
+
+    async fn refresh(&mut self) -> Result<(), WatsonError> {
+        if let CredentialData::OAuth {
+            expires_at,
+            refresh_token,
+            access_token,
+            ..
+        } = &mut self.credential.data
+            && *expires_at <= Utc::now().timestamp() + 120
+        {
+            let new_token = GoogleAuth::refresh_credential(&refresh_token.take()).await?;
+            *access_token = crate::auth::CredentialSecret::Decrypted(new_token);
+            *expires_at = Utc::now().timestamp() + 3600;
+            self.credential.save()?;
+        }
+        Ok(())
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        url: &Url,
+        depth: &'static str,
+        body: &'static str,
+    ) -> Result<reqwest::Response, WatsonError> {
+        let mut request = self.client.request(method, url.clone());
+        if !body.is_empty() {
+            request = request
+                .header("Depth", depth)
+                .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+                .body(body);
+        }
+        let request = match &self.credential.data {
+            CredentialData::Password { username, secret } => {
+                request.basic_auth(username, Some(secret))
+            }
+            CredentialData::OAuth { access_token, .. } => request.bearer_auth(access_token),
+            CredentialData::Empty => {
+                return Err(watson_err!(
+                    WatsonErrorKind::UndefinedAttribute,
+                    "Undefined credential data."
+                ));
+            }
+        };
+        Ok(request.send().await?)
+    }
+
+    /// A WebDAV request that follows redirects, returning the parsed multistatus and the URL
+    /// that answered
+    async fn dav(
+        &self,
+        method: &'static [u8],
+        url: &Url,
+        depth: &'static str,
+        body: &'static str,
+    ) -> Result<(Url, Vec<DavResponse>), WatsonError> {
+        let method = Method::from_bytes(method)
+            .map_err(|e| watson_err!(WatsonErrorKind::HttpGetRequest, e.to_string()))?;
+        let mut url = url.clone();
+        for _ in 0..MAX_REDIRECTS {
+            let resp = self.send(method.clone(), &url, depth, body).await?;
+            let status = resp.status();
+            if status.is_redirection() {
+                let location = resp
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|l| l.to_str().ok())
+                    .and_then(|l| url.join(l).ok())
+                    .ok_or_else(|| {
+                        watson_err!(WatsonErrorKind::HttpGetRequest, "Redirect without location")
+                    })?;
+                url = location;
+                continue;
+            }
+            if status != StatusCode::MULTI_STATUS && !status.is_success() {
+                return Err(watson_err!(
+                    WatsonErrorKind::HttpGetRequest,
+                    "{} answered {}",
+                    url,
+                    status
+                ));
+            }
+            let text = resp.text().await?;
+            return Ok((url, parse_multistatus(&text)));
+        }
+        Err(watson_err!(
+            WatsonErrorKind::HttpGetRequest,
+            "Too many redirects from {}",
+            self.base
+        ))
+    }
+
+    /// The single property of a depth 0 `PROPFIND`, resolved against the URL that answered
+    async fn find(&self, url: &Url, body: &'static str, prop: &str) -> Result<Url, WatsonError> {
+        let (answered, responses) = self.dav(b"PROPFIND", url, "0", body).await?;
+        let href = responses
+            .iter()
+            .find_map(|r| r.props.get(prop))
+            .ok_or_else(|| watson_err!(WatsonErrorKind::InvalidData, "No {} at {}", prop, url))?;
+        answered
+            .join(href)
+            .map_err(|e| watson_err!(WatsonErrorKind::UrlFormat, e.to_string()))
+    }
+
+    /// Principal -> address book home -> address books -> cards
+    pub async fn fetch_cards(&mut self) -> Result<Vec<VCard>, WatsonError> {
+        self.refresh().await?;
+
+        let base = self.base.clone();
+        let principal = self
+            .find(&base, PRINCIPAL, "current-user-principal")
+            .await?;
+        let home = self
+            .find(&principal, HOME_SET, "addressbook-home-set")
+            .await?;
+        let (home, collections) = self.dav(b"PROPFIND", &home, "1", ADDRESS_BOOKS).await?;
+
+        let mut cards = Vec::new();
+        for book in collections
+            .iter()
+            .filter(|r| r.types.iter().any(|t| t == "addressbook"))
+        {
+            let Ok(url) = home.join(&book.href) else {
+                continue;
+            };
+            let (_, responses) = self.dav(b"REPORT", &url, "1", CARDS).await?;
+            cards.extend(
+                responses
+                    .iter()
+                    .filter_map(|r| r.props.get("address-data"))
+                    .filter_map(|data| parse_vcard(data)),
+            );
+        }
+        Ok(cards)
+    }
+
+    /// Photos given as a link, which usually need the same authorization
+    pub async fn fetch_photo(&mut self, url: &str) -> Result<Vec<u8>, WatsonError> {
+        let url = self
+            .base
+            .join(url)
+            .map_err(|e| watson_err!(WatsonErrorKind::UrlFormat, e.to_string()))?;
+        let resp = self
+            .send(Method::GET, &url, "0", "")
+            .await?
+            .error_for_status()?;
+        Ok(resp.bytes().await?.to_vec())
+    }
+}
+
+fn parse_multistatus(xml: &str) -> Vec<DavResponse> {
+    let mut reader = Reader::from_str(xml);
+    let mut responses = Vec::new();
+    let mut current: Option<DavResponse> = None;
+    // Local names of the open elements
+    let mut path: Vec<String> = Vec::new();
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if name == "response" {
+                    current = Some(DavResponse::default());
+                }
+                path.push(name);
+            }
+            Ok(Event::Empty(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if path.last().is_some_and(|p| p == "resourcetype")
+                    && let Some(response) = current.as_mut()
+                {
+                    response.types.push(name);
+                }
+            }
+            Ok(Event::End(_)) => {
+                if path.pop().is_some_and(|p| p == "response")
+                    && let Some(mut response) = current.take()
+                {
+                    // Indentation around nested hrefs ends up in the text
+                    for value in response.props.values_mut() {
+                        *value = value.trim().to_string();
+                    }
+                    responses.push(response);
+                }
+            }
+            Ok(Event::Text(t)) => {
+                if let Ok(value) = t.decode() {
+                    push_text(&path, current.as_mut(), &value);
+                }
+            }
+            Ok(Event::CData(t)) => {
+                if let Ok(value) = t.decode() {
+                    push_text(&path, current.as_mut(), &value);
+                }
+            }
+            // `&amp;`, `&#13;` and friends arrive on their own
+            Ok(Event::GeneralRef(r)) => {
+                let resolved = match r.resolve_char_ref() {
+                    Ok(Some(c)) => Some(c.to_string()),
+                    _ => r
+                        .decode()
+                        .ok()
+                        .and_then(|name| resolve_predefined_entity(&name).map(str::to_string)),
+                };
+                if let Some(value) = resolved {
+                    push_text(&path, current.as_mut(), &value);
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                eprintln!("Error parsing XML: {:?}", e);
+                break;
+            }
+            _ => {}
+        }
+    }
+    responses
+}
+
+fn push_text(path: &[String], response: Option<&mut DavResponse>, value: &str) {
+    let Some(response) = response else {
+        return;
+    };
+    if path.ends_with(&["response".to_string(), "href".to_string()]) {
+        response.href.push_str(value.trim());
+        return;
+    }
+    // The property a text belongs to is the element right below `prop`
+    if let Some(prop) = path
+        .iter()
+        .position(|p| p == "prop")
+        .and_then(|i| path.get(i + 1))
+    {
+        response
+            .props
+            .entry(prop.clone())
+            .or_default()
+            .push_str(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multistatus() {
+        let xml = r#"<?xml version="1.0"?>
+            <d:multistatus xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav">
+              <d:response>
+                <d:href>/123/carddavhome/</d:href>
+                <d:propstat><d:prop>
+                  <card:addressbook-home-set><d:href>https://p42.contacts.icloud.com/123/carddavhome/</d:href></card:addressbook-home-set>
+                  <d:resourcetype><d:collection/></d:resourcetype>
+                </d:prop></d:propstat>
+              </d:response>
+              <d:response>
+                <d:href>/123/carddavhome/card/</d:href>
+                <d:propstat><d:prop>
+                  <d:resourcetype><d:collection/><card:addressbook/></d:resourcetype>
+                  <card:address-data>BEGIN:VCARD&#13;
+FN:Tom &amp; Jerry&#13;
+END:VCARD</card:address-data>
+                </d:prop></d:propstat>
+              </d:response>
+            </d:multistatus>"#;
+        let responses = parse_multistatus(xml);
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0].href, "/123/carddavhome/");
+        assert_eq!(
+            responses[0].props["addressbook-home-set"],
+            "https://p42.contacts.icloud.com/123/carddavhome/"
+        );
+        assert_eq!(responses[1].types, ["collection", "addressbook"]);
+        assert_eq!(
+            responses[1].props["address-data"],
+            "BEGIN:VCARD\r\nFN:Tom & Jerry\r\nEND:VCARD"
+        );
+    }
+}
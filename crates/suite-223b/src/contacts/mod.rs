@@ -0,0 +1,342 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use base64::{Engine, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::Credential,
+    calendar::{icloud::utils::unfold_ics, utils::structs::Attendee},
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::get_cache_dir,
+    },
+    watson_err,
+};
+
+pub mod carddav;
+
+pub use carddav::CardDavClient;
+
+const CONTACTS_CACHE: &str = "contacts.json";
+const AVATAR_DIR: &str = "avatars";
+
+/// What an address book knows about one email address
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Contact {
+    pub name: String,
+    /// Photo written to `$XDG_CACHE_HOME/watson/avatars`
+    pub avatar: Option<PathBuf>,
+}
+
+/// Contacts of all CardDAV accounts by lowercase address, cached in
+/// `$XDG_CACHE_HOME/watson/contacts.json` so names show up before the first sync
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ContactBook {
+    contacts: HashMap<String, Contact>,
+}
+impl ContactBook {
+    fn path() -> Result<PathBuf, WatsonError> {
+        Ok(get_cache_dir()?.join(CONTACTS_CACHE))
+    }
+
+    pub fn load() -> Self {
+        Self::path()
+            .ok()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), WatsonError> {
+        let bytes = serde_json::to_vec(self)
+            .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+        fs::write(Self::path()?, bytes)
+            .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))
+    }
+
+    /// Downloads the contacts of every account that offers CardDAV. Accounts that fail keep
+    /// nothing, the caller decides whether to replace an older book.
+    pub async fn sync(credentials: Vec<Credential>) -> Self {
+        let mut book = Self::default();
+        for credential in credentials {
+            let Some(mut client) = CardDavClient::for_credential(credential) else {
+                continue;
+            };
+            let cards = match client.fetch_cards().await {
+                Ok(cards) => cards,
+                Err(e) => {
+                    eprintln!("{:?}", e);
+                    continue;
+                }
+            };
+            for card in cards {
+                let avatar = match &card.photo {
+                    Some(Photo::Data(bytes)) => write_avatar(&card.emails[0], bytes),
+                    Some(Photo::Url(url)) => match client.fetch_photo(url).await {
+                        Ok(bytes) => write_avatar(&card.emails[0], &bytes),
+                        Err(e) => {
+                            eprintln!("{:?}", e);
+                            None
+                        }
+                    },
+                    None => None,
+                };
+                let contact = Contact {
+                    name: card.name.clone(),
+                    avatar,
+                };
+                for email in card.emails {
+                    book.contacts
+                        .entry(email)
+                        .or_insert_with(|| contact.clone());
+                }
+            }
+        }
+        book
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contacts.is_empty()
+    }
+
+    pub fn get(&self, email: &str) -> Option<&Contact> {
+        self.contacts.get(&email_address(email))
+    }
+
+    /// Fills in the name and avatar of an attendee the invite only had an address for
+    pub fn enrich(&self, attendee: &mut Attendee) {
+        let Some(contact) = attendee.email.as_deref().and_then(|e| self.get(e)) else {
+            return;
+        };
+        // Invites often repeat the address as the name
+        if attendee
+            .display_name
+            .as_deref()
+            .is_none_or(|n| n.trim().is_empty() || n.contains('@'))
+        {
+            attendee.display_name = Some(contact.name.clone());
+        }
+        attendee.avatar = contact.avatar.clone();
+    }
+}
+
+/// `mailto:Jane@Example.org` -> `jane@example.org`
+pub fn email_address(raw: &str) -> String {
+    let raw = raw.trim();
+    let raw = raw
+        .get(..7)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+        .map_or(raw, |_| &raw[7..]);
+    raw.to_lowercase()
+}
+
+/// Up to two letters for an avatar placeholder, `Jane van Doe` -> `JD`
+pub fn initials(name: &str) -> String {
+    let name = name.split('@').next().unwrap_or(name);
+    let words: Vec<&str> = name
+        .split(|c: char| c.is_whitespace() || c == '.' || c == '_')
+        .filter(|w| w.chars().next().is_some_and(char::is_alphanumeric))
+        .collect();
+    let first = words.first().and_then(|w| w.chars().next());
+    let last = words
+        .get(1..)
+        .and_then(|w| w.last())
+        .and_then(|w| w.chars().next());
+    first
+        .into_iter()
+        .chain(last)
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+fn write_avatar(email: &str, bytes: &[u8]) -> Option<PathBuf> {
+    let result = (|| {
+        let dir = get_cache_dir()?.join(AVATAR_DIR);
+        fs::create_dir_all(&dir)
+            .map_err(|e| watson_err!(WatsonErrorKind::DirCreate, e.to_string()))?;
+        let file: String = email
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = dir.join(file);
+        fs::write(&path, bytes)
+            .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))?;
+        Ok::<_, WatsonError>(path)
+    })();
+    result.inspect_err(|e| eprintln!("{:?}", e)).ok()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Photo {
+    Data(Vec<u8>),
+    Url(String),
+}
+
+/// The parts of a vCard (3.0 or 4.0) that attendees are matched with
+#[derive(Debug, Clone, PartialEq)]
+pub struct VCard {
+    pub name: String,
+    /// Lowercase, never empty
+    pub emails: Vec<String>,
+    pub photo: Option<Photo>,
+}
+
+/// `None` for cards without a name or address, e.g. groups
+pub fn parse_vcard(card: &str) -> Option<VCard> {
+    let card = unfold_ics(card);
+    let mut formatted = None;
+    let mut structured = None;
+    let mut emails = Vec::new();
+    let mut photo = None;
+
+    for line in card.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let mut params = key.split(';');
+        // Apple groups properties as `item1.EMAIL`
+        let property = params
+            .next()
+            .and_then(|p| p.rsplit('.').next())
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        match property.as_str() {
+            "FN" => formatted = Some(unescape(value)),
+            "N" => {
+                // Family;Given;Additional;Prefix;Suffix
+                let mut parts = value.split(';').map(unescape);
+                let family = parts.next().unwrap_or_default();
+                let given = parts.next().unwrap_or_default();
+                structured = Some(format!("{given} {family}").trim().to_string());
+            }
+            "EMAIL" => {
+                let email = email_address(value);
+                if !email.is_empty() && !emails.contains(&email) {
+                    emails.push(email);
+                }
+            }
+            "PHOTO" => photo = parse_photo(params, value),
+            _ => {}
+        }
+    }
+
+    let name = formatted
+        .filter(|n| !n.trim().is_empty())
+        .or(structured.filter(|n| !n.is_empty()))?;
+    if emails.is_empty() {
+        return None;
+    }
+    Some(VCard {
+        name,
+        emails,
+        photo,
+    })
+}
+
+fn parse_photo<'a>(mut params: impl Iterator<Item = &'a str>, value: &str) -> Option<Photo> {
+    let decode = |data: &str| {
+        let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+        general_purpose::STANDARD.decode(data).ok().map(Photo::Data)
+    };
+    // vCard 4.0: `PHOTO:data:image/jpeg;base64,...`
+    if let Some(data) = value.strip_prefix("data:") {
+        return decode(data.split_once(',')?.1);
+    }
+    // vCard 3.0: `PHOTO;ENCODING=b;TYPE=JPEG:...`
+    if params.any(|p| {
+        let p = p.to_ascii_uppercase();
+        p == "ENCODING=B" || p == "ENCODING=BASE64"
+    }) {
+        return decode(value);
+    }
+    (value.starts_with("https://") || value.starts_with("http://"))
+        .then(|| Photo::Url(value.to_string()))
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push(' '),
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+    out.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vcard() {
+        let card = "BEGIN:VCARD\r\nVERSION:3.0\r\nN:Doe;Jane;;;\r\nFN:Jane Doe\r\n\
+                    item1.EMAIL;type=INTERNET;type=pref:Jane@Example.org\r\n\
+                    EMAIL;type=INTERNET:jane.doe@work.example\r\n\
+                    PHOTO;ENCODING=b;TYPE=JPEG:/9j/\r\n 4AA=\r\nEND:VCARD\r\n";
+        let card = parse_vcard(card).unwrap();
+        assert_eq!(card.name, "Jane Doe");
+        assert_eq!(card.emails, ["jane@example.org", "jane.doe@work.example"]);
+        assert_eq!(
+            card.photo,
+            Some(Photo::Data(vec![0xff, 0xd8, 0xff, 0xe0, 0x00]))
+        );
+    }
+
+    #[test]
+    fn test_parse_vcard_4() {
+        let card = "BEGIN:VCARD\nVERSION:4.0\nN:Smith;Ann\\, Jr.;;;\nEMAIL:ann@example.org\n\
+                    PHOTO:https://example.org/ann.png\nEND:VCARD";
+        let card = parse_vcard(card).unwrap();
+        assert_eq!(card.name, "Ann, Jr. Smith");
+        assert_eq!(
+            card.photo,
+            Some(Photo::Url("https://example.org/ann.png".into()))
+        );
+        // Nothing to match attendees with
+        assert!(parse_vcard("BEGIN:VCARD\nFN:Office\nEND:VCARD").is_none());
+    }
+
+    #[test]
+    fn test_enrich_attendee() {
+        let mut book = ContactBook::default();
+        book.contacts.insert(
+            "jane@example.org".into(),
+            Contact {
+                name: "Jane Doe".into(),
+                avatar: Some("/tmp/jane".into()),
+            },
+        );
+        let mut attendee = Attendee {
+            email: Some("mailto:JANE@example.org".into()),
+            display_name: Some("jane@example.org".into()),
+            ..Default::default()
+        };
+        book.enrich(&mut attendee);
+        assert_eq!(attendee.display_name.as_deref(), Some("Jane Doe"));
+        assert_eq!(attendee.avatar, Some("/tmp/jane".into()));
+
+        // Names from the invite win
+        let mut attendee = Attendee {
+            email: Some("mailto:jane@example.org".into()),
+            display_name: Some("Jane (Marketing)".into()),
+            ..Default::default()
+        };
+        book.enrich(&mut attendee);
+        assert_eq!(attendee.display_name.as_deref(), Some("Jane (Marketing)"));
+    }
+
+    #[test]
+    fn test_initials() {
+        assert_eq!(initials("Jane van Doe"), "JD");
+        assert_eq!(initials("jane.doe@example.org"), "JD");
+        assert_eq!(initials("Jane"), "J");
+        assert_eq!(initials(""), "");
+    }
+}
@@ -6,12 +6,6 @@ impl TryFrom<OwnedValue> for PowerMode {
 
     fn try_from(value: OwnedValue) -> Result<Self, Self::Error> {
         let s: String = value.try_into()?;
-
-        match s.as_str() {
-            "power-saver" => Ok(Self::PowerSave),
-            "balanced" => Ok(Self::Balanced),
-            "performance" => Ok(Self::Performace),
-            _ => Err(Self::Error::InvalidGUID),
-        }
+        s.parse().map_err(|_| Self::Error::InvalidGUID)
     }
 }
@@ -0,0 +1,296 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+
+use crate::{
+    protocol::Quote,
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+const YAHOO_URL: &str = "https://query1.finance.yahoo.com";
+/// Yahoo turns away clients without one
+const USER_AGENT: &str = concat!(
+    "Mozilla/5.0 (compatible; watson/",
+    env!("CARGO_PKG_VERSION"),
+    ")"
+);
+/// Polling more often than this only gets the address blocked
+const MIN_INTERVAL: u64 = 60;
+/// Points kept for sparklines, a full session at 5 minutes is 78
+const HISTORY_POINTS: usize = 48;
+
+/// `$XDG_CONFIG_HOME/watson/finance.json`, e.g.
+/// `{ "symbols": ["AAPL", "BTC-USD"], "interval": 300 }`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FinanceConfig {
+    pub symbols: Vec<String>,
+    /// Seconds between polls, at least a minute
+    pub interval: u64,
+    pub provider: ProviderConfig,
+}
+impl Default for FinanceConfig {
+    fn default() -> Self {
+        Self {
+            symbols: Vec::new(),
+            interval: 300,
+            provider: ProviderConfig::default(),
+        }
+    }
+}
+impl FinanceConfig {
+    pub fn interval(&self) -> u64 {
+        self.interval.max(MIN_INTERVAL)
+    }
+
+    pub fn provider(&self) -> Box<dyn QuoteProvider> {
+        match &self.provider {
+            ProviderConfig::Yahoo { url } => Box::new(YahooFinance::new(url.clone())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ProviderConfig {
+    /// Anything serving Yahoo's `v8/finance/chart` API
+    Yahoo {
+        #[serde(default = "default_yahoo_url")]
+        url: String,
+    },
+}
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self::Yahoo {
+            url: default_yahoo_url(),
+        }
+    }
+}
+fn default_yahoo_url() -> String {
+    YAHOO_URL.to_string()
+}
+
+/// Price lookups for the ticker widget
+#[async_trait]
+pub trait QuoteProvider: Send + Sync {
+    /// Fails with `WatsonErrorKind::RateLimited` when asked to slow down
+    async fn quote(&self, symbol: &str) -> Result<Quote, WatsonError>;
+}
+
+pub struct YahooFinance {
+    client: Client,
+    url: String,
+}
+impl YahooFinance {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl QuoteProvider for YahooFinance {
+    async fn quote(&self, symbol: &str) -> Result<Quote, WatsonError> {
+        let url = format!(
+            "{}/v8/finance/chart/{}",
+            self.url.trim_end_matches('/'),
+            symbol
+        );
+        let resp = self
+            .client
+            .get(url)
+            .header("User-Agent", USER_AGENT)
+            .query(&[("range", "1d"), ("interval", "5m")])
+            .send()
+            .await?;
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+            return Err(watson_err!(
+                WatsonErrorKind::RateLimited,
+                "Too many quote requests"
+            ));
+        }
+        let text = resp.error_for_status()?.text().await?;
+        parse_chart(&text)
+    }
+}
+
+fn parse_chart(text: &str) -> Result<Quote, WatsonError> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Meta {
+        symbol: String,
+        currency: Option<String>,
+        regular_market_price: f64,
+        previous_close: Option<f64>,
+        chart_previous_close: Option<f64>,
+    }
+    #[derive(Deserialize)]
+    struct Prices {
+        #[serde(default)]
+        close: Vec<Option<f64>>,
+    }
+    #[derive(Deserialize)]
+    struct Indicators {
+        #[serde(default)]
+        quote: Vec<Prices>,
+    }
+    #[derive(Deserialize)]
+    struct ChartResult {
+        meta: Meta,
+        indicators: Option<Indicators>,
+    }
+    #[derive(Deserialize)]
+    struct ChartError {
+        description: String,
+    }
+    #[derive(Deserialize)]
+    struct Chart {
+        result: Option<Vec<ChartResult>>,
+        error: Option<ChartError>,
+    }
+    #[derive(Deserialize)]
+    struct Response {
+        chart: Chart,
+    }
+
+    let chart = serde_json::from_str::<Response>(text)?.chart;
+    if let Some(error) = chart.error {
+        return Err(watson_err!(WatsonErrorKind::InvalidData, error.description));
+    }
+    let result = chart
+        .result
+        .and_then(|r| r.into_iter().next())
+        .ok_or_else(|| watson_err!(WatsonErrorKind::InvalidData, "Empty chart"))?;
+
+    let meta = result.meta;
+    let previous = meta.previous_close.or(meta.chart_previous_close);
+    let change_percent = match previous {
+        Some(previous) if previous != 0.0 => (meta.regular_market_price / previous - 1.0) * 100.0,
+        _ => 0.0,
+    };
+    let closes: Vec<f64> = result
+        .indicators
+        .and_then(|i| i.quote.into_iter().next())
+        .map(|q| q.close.into_iter().flatten().collect())
+        .unwrap_or_default();
+
+    Ok(Quote {
+        symbol: meta.symbol,
+        price: meta.regular_market_price,
+        change_percent,
+        currency: meta.currency,
+        history: downsample(&closes, HISTORY_POINTS),
+    })
+}
+
+/// At most `points` values spread evenly over `values`, keeping the first and the last
+fn downsample(values: &[f64], points: usize) -> Vec<f64> {
+    if values.len() <= points || points < 2 {
+        return values.to_vec();
+    }
+    let step = (values.len() - 1) as f64 / (points - 1) as f64;
+    (0..points)
+        .map(|i| values[(i as f64 * step).round() as usize])
+        .collect()
+}
+
+/// Spaces requests out and backs off after `429 Too Many Requests`
+#[derive(Debug)]
+pub struct RateLimiter {
+    spacing: Duration,
+    backoff: Duration,
+    max_backoff: Duration,
+    next: Option<Instant>,
+    /// Current backoff, zero while requests go through
+    penalty: Duration,
+}
+impl RateLimiter {
+    pub fn new(spacing: Duration, backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            spacing,
+            backoff,
+            max_backoff,
+            next: None,
+            penalty: Duration::ZERO,
+        }
+    }
+
+    /// How long to wait before the next request may go out
+    pub fn delay(&self, now: Instant) -> Duration {
+        self.next
+            .map(|next| next.saturating_duration_since(now))
+            .unwrap_or_default()
+    }
+
+    /// Records the outcome of a request sent at `now`
+    pub fn record(&mut self, now: Instant, limited: bool) {
+        if limited {
+            self.penalty = (self.penalty * 2).clamp(self.backoff, self.max_backoff);
+            self.next = Some(now + self.penalty);
+        } else {
+            self.penalty = Duration::ZERO;
+            self.next = Some(now + self.spacing);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chart() {
+        let text = r#"{"chart":{"result":[{
+            "meta":{"currency":"USD","symbol":"AAPL","regularMarketPrice":202.0,"chartPreviousClose":200.0},
+            "timestamp":[1,2,3],
+            "indicators":{"quote":[{"close":[200.5,null,202.0]}]}
+        }],"error":null}}"#;
+        let quote = parse_chart(text).unwrap();
+        assert_eq!(quote.symbol, "AAPL");
+        assert_eq!(quote.currency.as_deref(), Some("USD"));
+        assert!((quote.change_percent - 1.0).abs() < 1e-9);
+        assert_eq!(quote.history, [200.5, 202.0]);
+
+        let text = r#"{"chart":{"result":null,"error":{"code":"Not Found","description":"No data found, symbol may be delisted"}}}"#;
+        assert_eq!(
+            parse_chart(text).unwrap_err().kind,
+            WatsonErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_downsample() {
+        let values: Vec<f64> = (0..100).map(f64::from).collect();
+        let points = downsample(&values, 5);
+        assert_eq!(points, [0.0, 25.0, 50.0, 74.0, 99.0]);
+        assert_eq!(downsample(&values[..3], 5), [0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_rate_limiter_backs_off() {
+        let second = Duration::from_secs(1);
+        let mut limiter = RateLimiter::new(second, 60 * second, 600 * second);
+        let now = Instant::now();
+        assert_eq!(limiter.delay(now), Duration::ZERO);
+
+        limiter.record(now, false);
+        assert_eq!(limiter.delay(now), second);
+        limiter.record(now, true);
+        assert_eq!(limiter.delay(now), 60 * second);
+        limiter.record(now, true);
+        assert_eq!(limiter.delay(now), 120 * second);
+        for _ in 0..5 {
+            limiter.record(now, true);
+        }
+        assert_eq!(limiter.delay(now), 600 * second);
+
+        // One request going through is enough to recover
+        limiter.record(now, false);
+        assert_eq!(limiter.delay(now), second);
+    }
+}
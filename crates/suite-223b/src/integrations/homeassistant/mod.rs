@@ -0,0 +1,331 @@
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode, Url, header::CONTENT_TYPE};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    auth::{Credential, CredentialData, CredentialService},
+    protocol::HomeAssistantEntity,
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+pub mod websocket;
+
+use websocket::WebSocket;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Home Assistant stays quiet while nothing changes, so it's asked for a pong after this long
+const KEEPALIVE: Duration = Duration::from_secs(60);
+
+/// `$XDG_CONFIG_HOME/watson/homeassistant.json`, e.g. `{ "credential": "Home" }`. The
+/// credential is a Home Assistant account added with `watson auth`, holding the instance's url
+/// and a long-lived access token.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HomeAssistantConfig {
+    /// Label of the credential, the only Home Assistant credential if unset
+    pub credential: Option<String>,
+}
+impl HomeAssistantConfig {
+    /// Picks the configured credential out of `credentials`
+    pub fn credential(&self, credentials: Vec<Credential>) -> Result<Credential, WatsonError> {
+        let mut matching = credentials.into_iter().filter(|c| match &self.credential {
+            Some(label) => c.label == *label,
+            None => matches!(c.service, CredentialService::HomeAssistant),
+        });
+        match (matching.next(), matching.next()) {
+            (Some(credential), None) => Ok(credential),
+            (Some(_), Some(_)) => Err(watson_err!(
+                WatsonErrorKind::ConfigError,
+                "Several Home Assistant credentials, set `credential` in homeassistant.json"
+            )),
+            (None, _) => Err(watson_err!(
+                WatsonErrorKind::CredentialEntry,
+                "No Home Assistant credential{}",
+                self.credential
+                    .as_ref()
+                    .map(|l| format!(" labeled {l}"))
+                    .unwrap_or_default()
+            )),
+        }
+    }
+}
+
+/// Talks to one Home Assistant instance over its REST and WebSocket APIs
+pub struct HomeAssistantClient {
+    client: Client,
+    url: Url,
+    token: String,
+}
+impl HomeAssistantClient {
+    /// From a credential whose username is the instance's url and whose secret is the token
+    pub fn new(credential: &Credential) -> Result<Self, WatsonError> {
+        let CredentialData::Password { username, secret } = &credential.data else {
+            return Err(watson_err!(
+                WatsonErrorKind::CredentialRead,
+                "Home Assistant needs a url and an access token"
+            ));
+        };
+        let url = Url::parse(username.to_string().trim())
+            .map_err(|e| watson_err!(WatsonErrorKind::UrlFormat, e.to_string()))?;
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| watson_err!(WatsonErrorKind::Http, e.to_string()))?;
+        Ok(Self {
+            client,
+            url,
+            token: secret.to_string(),
+        })
+    }
+
+    fn endpoint(&self, path: &str) -> Result<Url, WatsonError> {
+        let base = self.url.as_str().trim_end_matches('/');
+        Url::parse(&format!("{base}{path}"))
+            .map_err(|e| watson_err!(WatsonErrorKind::UrlFormat, e.to_string()))
+    }
+
+    /// Every entity of the instance
+    pub async fn states(&self) -> Result<Vec<HomeAssistantEntity>, WatsonError> {
+        let resp = self
+            .client
+            .get(self.endpoint("/api/states")?)
+            .bearer_auth(&self.token)
+            .send()
+            .await?;
+        check_status(resp.status())?;
+        let text = resp.error_for_status()?.text().await?;
+        parse_states(&text)
+    }
+
+    /// Switches a light, switch or anything else `homeassistant.turn_on` understands
+    pub async fn set(&self, entity: &str, on: bool) -> Result<(), WatsonError> {
+        let service = if on { "turn_on" } else { "turn_off" };
+        let body = json!({ "entity_id": entity });
+        let resp = self
+            .client
+            .post(self.endpoint(&format!("/api/services/homeassistant/{service}"))?)
+            .bearer_auth(&self.token)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await?;
+        check_status(resp.status())?;
+        resp.error_for_status()?;
+        Ok(())
+    }
+
+    /// Signs in over the WebSocket API and subscribes to state changes
+    pub async fn subscribe(&self) -> Result<StateChanges, WatsonError> {
+        let mut url = self.endpoint("/api/websocket")?;
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        url.set_scheme(scheme)
+            .map_err(|_| watson_err!(WatsonErrorKind::UrlFormat, "Can't use {}", url))?;
+        let mut changes = StateChanges {
+            socket: WebSocket::connect(&url).await?,
+            next_id: 1,
+        };
+        expect(&mut changes.socket, "auth_required").await?;
+        let auth = json!({ "type": "auth", "access_token": self.token });
+        changes.socket.send_text(&auth.to_string()).await?;
+        expect(&mut changes.socket, "auth_ok").await?;
+
+        let id = changes.next_id();
+        let subscribe = json!({
+            "id": id,
+            "type": "subscribe_events",
+            "event_type": "state_changed",
+        });
+        changes.socket.send_text(&subscribe.to_string()).await?;
+        let result = expect(&mut changes.socket, "result").await?;
+        if result.success != Some(true) {
+            return Err(watson_err!(
+                WatsonErrorKind::HomeAssistant,
+                "Subscribing to state changes failed"
+            ));
+        }
+        Ok(changes)
+    }
+}
+
+/// Entities changing state, see `HomeAssistantClient::subscribe`
+pub struct StateChanges {
+    socket: WebSocket,
+    next_id: u64,
+}
+impl StateChanges {
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// The next entity whose state or attributes changed. Fails once the connection is lost.
+    pub async fn next(&mut self) -> Result<HomeAssistantEntity, WatsonError> {
+        let mut pinged = false;
+        loop {
+            let text = match tokio::time::timeout(KEEPALIVE, self.socket.recv_text()).await {
+                Ok(text) => text?,
+                Err(_) if !pinged => {
+                    let id = self.next_id();
+                    let ping = json!({ "id": id, "type": "ping" });
+                    self.socket.send_text(&ping.to_string()).await?;
+                    pinged = true;
+                    continue;
+                }
+                Err(_) => {
+                    return Err(watson_err!(
+                        WatsonErrorKind::Timeout,
+                        "Home Assistant stopped answering"
+                    ));
+                }
+            };
+            pinged = false;
+            if let Some(entity) = parse_event(&text)? {
+                return Ok(entity);
+            }
+        }
+    }
+}
+
+/// A message of the WebSocket API, only what the client looks at
+#[derive(Debug, Deserialize)]
+struct Message {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    success: Option<bool>,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    event: Option<Event>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Event {
+    data: EventData,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventData {
+    /// Missing when the entity was removed
+    new_state: Option<State>,
+}
+
+#[derive(Debug, Deserialize)]
+struct State {
+    entity_id: String,
+    state: String,
+    #[serde(default)]
+    attributes: Attributes,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Attributes {
+    friendly_name: Option<String>,
+    unit_of_measurement: Option<String>,
+}
+
+impl From<State> for HomeAssistantEntity {
+    fn from(s: State) -> Self {
+        Self {
+            name: s
+                .attributes
+                .friendly_name
+                .unwrap_or_else(|| s.entity_id.clone()),
+            id: s.entity_id,
+            state: s.state,
+            unit: s.attributes.unit_of_measurement,
+        }
+    }
+}
+
+fn check_status(status: StatusCode) -> Result<(), WatsonError> {
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(watson_err!(
+            WatsonErrorKind::HomeAssistant,
+            "Home Assistant refused the access token"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Waits for a message of type `kind`, failing on `auth_invalid`
+async fn expect(socket: &mut WebSocket, kind: &str) -> Result<Message, WatsonError> {
+    let text = socket.recv_text().await?;
+    let message: Message = serde_json::from_str(&text)
+        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+    match message.kind.as_str() {
+        k if k == kind => Ok(message),
+        "auth_invalid" => Err(watson_err!(
+            WatsonErrorKind::HomeAssistant,
+            "Home Assistant refused the access token: {}",
+            message.message.unwrap_or_default()
+        )),
+        other => Err(watson_err!(
+            WatsonErrorKind::HomeAssistant,
+            "Expected {} from Home Assistant, got {}",
+            kind,
+            other
+        )),
+    }
+}
+
+fn parse_states(text: &str) -> Result<Vec<HomeAssistantEntity>, WatsonError> {
+    let states: Vec<State> = serde_json::from_str(text)
+        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+    Ok(states.into_iter().map(Into::into).collect())
+}
+
+/// The new state carried by a `state_changed` event, `None` for anything else
+fn parse_event(text: &str) -> Result<Option<HomeAssistantEntity>, WatsonError> {
+    let message: Message = serde_json::from_str(text)
+        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+    Ok(message
+        .event
+        .filter(|_| message.kind == "event")
+        .and_then(|e| e.data.new_state)
+        .map(Into::into))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_states() {
+        let text = r#"[
+            {"entity_id": "light.kitchen", "state": "on",
+             "attributes": {"friendly_name": "Kitchen", "brightness": 180}},
+            {"entity_id": "sensor.outside", "state": "12.5",
+             "attributes": {"unit_of_measurement": "°C"}, "last_changed": "2025-01-01T00:00:00Z"}
+        ]"#;
+        let states = parse_states(text).unwrap();
+        assert_eq!(states[0].name, "Kitchen");
+        assert!(states[0].toggleable() && states[0].is_on());
+        assert_eq!(states[1].name, "sensor.outside");
+        assert_eq!(states[1].unit.as_deref(), Some("°C"));
+        assert!(!states[1].toggleable());
+    }
+
+    #[test]
+    fn test_parse_event() {
+        let changed = r#"{"id": 1, "type": "event", "event": {"event_type": "state_changed",
+            "data": {"entity_id": "switch.fan", "old_state": null,
+                     "new_state": {"entity_id": "switch.fan", "state": "off", "attributes": {}}}}}"#;
+        let entity = parse_event(changed).unwrap().unwrap();
+        assert_eq!(entity.id, "switch.fan");
+        assert_eq!(entity.state, "off");
+
+        let removed = r#"{"id": 1, "type": "event", "event": {"event_type": "state_changed",
+            "data": {"entity_id": "switch.fan", "new_state": null}}}"#;
+        assert!(parse_event(removed).unwrap().is_none());
+        assert!(
+            parse_event(r#"{"id": 2, "type": "pong"}"#)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use reqwest::Url;
+use tokio::{net::TcpStream, time::timeout};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async_with_config,
+    tungstenite::{Message, protocol::WebSocketConfig},
+};
+
+use crate::{
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+/// Larger messages end the connection, state changes are a few kilobytes at most
+const MAX_MESSAGE: usize = 16 * 1024 * 1024;
+
+/// Home Assistant's text messages over a `ws://` or `wss://` connection
+pub struct WebSocket {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+impl WebSocket {
+    /// Connects to a `ws://` or `wss://` url
+    pub async fn connect(url: &Url) -> Result<Self, WatsonError> {
+        let config = WebSocketConfig::default()
+            .max_message_size(Some(MAX_MESSAGE))
+            .max_frame_size(Some(MAX_MESSAGE));
+        let (stream, _) = timeout(
+            CONNECT_TIMEOUT,
+            connect_async_with_config(url.as_str(), Some(config), false),
+        )
+        .await
+        .map_err(|_| watson_err!(WatsonErrorKind::Timeout, "Connecting to {}", url))?
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
+        Ok(Self { stream })
+    }
+
+    pub async fn send_text(&mut self, text: &str) -> Result<(), WatsonError> {
+        self.stream
+            .send(Message::text(text))
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))
+    }
+
+    /// The next text message. Pings are answered while waiting for it.
+    pub async fn recv_text(&mut self) -> Result<String, WatsonError> {
+        loop {
+            let message = self
+                .stream
+                .next()
+                .await
+                .transpose()
+                .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?;
+            match message {
+                Some(Message::Text(text)) => return Ok(text.to_string()),
+                Some(Message::Close(_)) | None => {
+                    return Err(watson_err!(
+                        WatsonErrorKind::StreamRead,
+                        "WebSocket closed by the server"
+                    ));
+                }
+                // Pings, pongs and binary messages
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{accept_hdr_async, tungstenite::handshake::server};
+
+    #[tokio::test]
+    async fn test_upgrade_keeps_port_and_query() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let seen = Arc::new(Mutex::new(None));
+
+        let server = tokio::spawn({
+            let seen = Arc::clone(&seen);
+            async move {
+                let (tcp, _) = listener.accept().await.unwrap();
+                let callback = |request: &server::Request, response| {
+                    let host = request.headers()["host"].to_str().unwrap().to_string();
+                    *seen.lock().unwrap() = Some((host, request.uri().to_string()));
+                    Ok(response)
+                };
+                let mut socket = accept_hdr_async(tcp, callback).await.unwrap();
+                socket.send(Message::text("hello")).await.unwrap();
+            }
+        });
+
+        let url = Url::parse(&format!("ws://127.0.0.1:{port}/api/websocket?x=1")).unwrap();
+        let mut socket = WebSocket::connect(&url).await.unwrap();
+        assert_eq!(socket.recv_text().await.unwrap(), "hello");
+        server.await.unwrap();
+
+        let (host, uri) = seen.lock().unwrap().clone().unwrap();
+        assert_eq!(host, format!("127.0.0.1:{port}"));
+        assert_eq!(uri, "/api/websocket?x=1");
+    }
+}
@@ -0,0 +1,2 @@
+//! Clients for services whose state the widgets show and change
+pub mod homeassistant;
@@ -1,6 +1,10 @@
 pub mod auth;
 pub mod calendar;
 pub mod config;
+pub mod contacts;
+pub mod finance;
+pub mod integrations;
+pub mod mail;
 pub mod notification;
 pub mod protocol;
 pub mod tokio;
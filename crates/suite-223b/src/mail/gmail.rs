@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::Client;
+use serde::{Deserialize, de::DeserializeOwned};
+
+use crate::{
+    auth::{Credential, CredentialData},
+    calendar::google::{GoogleApiErrorResponse, GoogleAuth},
+    mail::{MailProvider, sender_name},
+    protocol::{MailAccount, MailSummary},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+const GMAIL_URL: &str = "https://gmail.googleapis.com/gmail/v1/users/me";
+/// Gmail only pushes through Cloud Pub/Sub, so the inbox is polled instead
+const POLL_INTERVAL: Duration = Duration::from_secs(120);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Label {
+    messages_unread: u32,
+}
+
+#[derive(Deserialize)]
+struct MessageRef {
+    id: String,
+}
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct MessageList {
+    messages: Vec<MessageRef>,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    name: String,
+    value: String,
+}
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Payload {
+    headers: Vec<Header>,
+}
+#[derive(Deserialize)]
+struct Message {
+    id: String,
+    #[serde(default)]
+    payload: Payload,
+}
+impl From<Message> for MailSummary {
+    fn from(message: Message) -> Self {
+        let header = |name: &str| {
+            message
+                .payload
+                .headers
+                .iter()
+                .find(|h| h.name.eq_ignore_ascii_case(name))
+                .map(|h| h.value.clone())
+                .unwrap_or_default()
+        };
+        Self {
+            from: sender_name(&header("From")),
+            subject: header("Subject"),
+            id: message.id,
+        }
+    }
+}
+
+/// Unread mail of a Google account through the Gmail API
+pub struct GmailClient {
+    client: Client,
+    credential: Credential,
+}
+impl GmailClient {
+    pub fn new(credential: Credential) -> Self {
+        Self {
+            client: Client::new(),
+            credential,
+        }
+    }
+
+    async fn refresh(&mut self) -> Result<(), WatsonError> {
+        if let CredentialData::OAuth {
+            expires_at,
+            refresh_token,
+            access_token,
+            ..
+        } = &mut self.credential.data
+            && *expires_at <= Utc::now().timestamp() + 120
+        {
+            let new_token = GoogleAuth::refresh_credential(&refresh_token.take()).await?;
+            *access_token = crate::auth::CredentialSecret::Decrypted(new_token);
+            *expires_at = Utc::now().timestamp() + 3600;
+            self.credential.save()?;
+        }
+        Ok(())
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, WatsonError> {
+        let CredentialData::OAuth { access_token, .. } = &self.credential.data else {
+            return Err(watson_err!(
+                WatsonErrorKind::GoogleAuth,
+                "Invalid auth type provided."
+            ));
+        };
+        let resp = self
+            .client
+            .get(format!("{GMAIL_URL}/{path}"))
+            .bearer_auth(access_token)
+            .query(query)
+            .send()
+            .await?;
+
+        let status = resp.status();
+        let text = resp.text().await?;
+        if !status.is_success() {
+            let error: GoogleApiErrorResponse = serde_json::from_str(&text)?;
+            return Err(watson_err!(WatsonErrorKind::Mail, error.error.message));
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+#[async_trait]
+impl MailProvider for GmailClient {
+    async fn check(&mut self, latest: usize) -> Result<MailAccount, WatsonError> {
+        self.refresh().await?;
+
+        let inbox: Label = self.get("labels/INBOX", &[]).await?;
+        let mut latest_mail = Vec::new();
+        if inbox.messages_unread > 0 && latest > 0 {
+            let max = latest.to_string();
+            let list: MessageList = self
+                .get(
+                    "messages",
+                    &[("q", "is:unread in:inbox"), ("maxResults", &max)],
+                )
+                .await?;
+            for message in list.messages {
+                let message: Message = self
+                    .get(
+                        &format!("messages/{}", message.id),
+                        &[
+                            ("format", "metadata"),
+                            ("metadataHeaders", "From"),
+                            ("metadataHeaders", "Subject"),
+                        ],
+                    )
+                    .await?;
+                latest_mail.push(message.into());
+            }
+        }
+
+        Ok(MailAccount {
+            label: self.credential.label.clone(),
+            unread: inbox.messages_unread,
+            latest: latest_mail,
+        })
+    }
+
+    async fn wait(&mut self) -> Result<(), WatsonError> {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_summary() {
+        let message: Message = serde_json::from_str(
+            r#"{
+                "id": "18c2",
+                "payload": { "headers": [
+                    { "name": "Subject", "value": "Lunch?" },
+                    { "name": "From", "value": "Jane Doe <jane@example.org>" }
+                ] }
+            }"#,
+        )
+        .unwrap();
+        let summary = MailSummary::from(message);
+        assert_eq!(summary.id, "18c2");
+        assert_eq!(summary.from, "Jane Doe");
+        assert_eq!(summary.subject, "Lunch?");
+    }
+}
@@ -0,0 +1,457 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose};
+use regex::Regex;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::timeout,
+};
+use tokio_native_tls::{TlsConnector, TlsStream};
+
+use crate::{
+    auth::{Credential, CredentialData},
+    mail::{MailProvider, sender_name},
+    protocol::{MailAccount, MailSummary},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+/// Servers drop idling clients after 30 minutes, RFC 2177 asks to restart IDLE before that
+const IDLE_TIMEOUT: Duration = Duration::from_secs(25 * 60);
+/// Greeting, login and single commands
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Unread mail over IMAP, waits for changes with IDLE
+pub struct ImapClient {
+    credential: Credential,
+    host: String,
+    port: u16,
+    mailbox: String,
+    /// Reconnected after errors
+    connection: Option<ImapConnection>,
+}
+impl ImapClient {
+    pub fn new(credential: Credential, host: String, port: u16, mailbox: String) -> Self {
+        Self {
+            credential,
+            host,
+            port,
+            mailbox,
+            connection: None,
+        }
+    }
+
+    async fn connection(&mut self) -> Result<&mut ImapConnection, WatsonError> {
+        if self.connection.is_none() {
+            let CredentialData::Password { username, secret } = &self.credential.data else {
+                return Err(watson_err!(
+                    WatsonErrorKind::CredentialRead,
+                    "IMAP needs a username and password"
+                ));
+            };
+            let mut connection = ImapConnection::connect(&self.host, self.port).await?;
+            connection
+                .command(&format!(
+                    "LOGIN {} {}",
+                    quote(&username.to_string())?,
+                    quote(&secret.to_string())?
+                ))
+                .await?;
+            // Read-only, so fetching headers doesn't mark anything as seen
+            connection
+                .command(&format!("EXAMINE {}", quote(&self.mailbox)?))
+                .await?;
+            self.connection = Some(connection);
+        }
+        Ok(self.connection.as_mut().expect("Connected above"))
+    }
+}
+
+#[async_trait]
+impl MailProvider for ImapClient {
+    async fn check(&mut self, latest: usize) -> Result<MailAccount, WatsonError> {
+        let label = self.credential.label.clone();
+        let result = async {
+            let connection = self.connection().await?;
+            let mut unseen = parse_search(&connection.command("UID SEARCH UNSEEN").await?);
+            unseen.sort_unstable_by(|a, b| b.cmp(a));
+
+            let mut latest_mail = Vec::new();
+            if !unseen.is_empty() && latest > 0 {
+                let uids: Vec<String> = unseen.iter().take(latest).map(u32::to_string).collect();
+                let lines = connection
+                    .command(&format!(
+                        "UID FETCH {} (UID BODY.PEEK[HEADER.FIELDS (FROM SUBJECT)])",
+                        uids.join(",")
+                    ))
+                    .await?;
+                latest_mail = lines.iter().filter_map(|l| parse_fetch(l)).collect();
+                latest_mail.sort_by(|a: &MailSummary, b| {
+                    let uid = |m: &MailSummary| m.id.parse::<u32>().unwrap_or_default();
+                    uid(b).cmp(&uid(a))
+                });
+            }
+            Ok::<_, WatsonError>(MailAccount {
+                label,
+                unread: unseen.len() as u32,
+                latest: latest_mail,
+            })
+        }
+        .await;
+        if result.is_err() {
+            self.connection = None;
+        }
+        result
+    }
+
+    async fn wait(&mut self) -> Result<(), WatsonError> {
+        let result = async {
+            let connection = self.connection().await?;
+            connection.idle().await
+        }
+        .await;
+        if result.is_err() {
+            self.connection = None;
+        }
+        result
+    }
+
+    fn interrupted(&mut self) {
+        self.connection = None;
+    }
+}
+
+struct ImapConnection {
+    stream: BufReader<TlsStream<TcpStream>>,
+    tag: u32,
+}
+impl ImapConnection {
+    async fn connect(host: &str, port: u16) -> Result<Self, WatsonError> {
+        let tcp = timeout(COMMAND_TIMEOUT, TcpStream::connect((host, port)))
+            .await
+            .map_err(|_| watson_err!(WatsonErrorKind::Timeout, "Connecting to {}", host))?
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
+        let connector = tokio_native_tls::native_tls::TlsConnector::new()
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
+        let tls = TlsConnector::from(connector)
+            .connect(host, tcp)
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
+
+        let mut connection = Self {
+            stream: BufReader::new(tls),
+            tag: 0,
+        };
+        let greeting = timeout(COMMAND_TIMEOUT, connection.read_response())
+            .await
+            .map_err(|_| watson_err!(WatsonErrorKind::Timeout, "No greeting from {}", host))??;
+        if !greeting.starts_with("* OK") && !greeting.starts_with("* PREAUTH") {
+            return Err(watson_err!(WatsonErrorKind::Mail, greeting));
+        }
+        Ok(connection)
+    }
+
+    /// Runs a command and returns its untagged responses
+    async fn command(&mut self, command: &str) -> Result<Vec<String>, WatsonError> {
+        self.tag += 1;
+        let tag = format!("a{}", self.tag);
+        self.write(&format!("{tag} {command}\r\n")).await?;
+
+        let mut untagged = Vec::new();
+        loop {
+            let line = timeout(COMMAND_TIMEOUT, self.read_response())
+                .await
+                .map_err(|_| {
+                    watson_err!(WatsonErrorKind::Timeout, "IMAP server stopped answering")
+                })??;
+            let Some(status) = line.strip_prefix(&tag) else {
+                untagged.push(line);
+                continue;
+            };
+            let status = status.trim_start();
+            if status.starts_with("OK") {
+                return Ok(untagged);
+            }
+            // Never echo the password back into logs
+            let command = command.split_whitespace().next().unwrap_or_default();
+            return Err(watson_err!(
+                WatsonErrorKind::Mail,
+                "{} failed: {}",
+                command,
+                status
+            ));
+        }
+    }
+
+    /// Waits in IDLE until the server reports a change or `IDLE_TIMEOUT` passes
+    async fn idle(&mut self) -> Result<(), WatsonError> {
+        self.tag += 1;
+        let tag = format!("a{}", self.tag);
+        self.write(&format!("{tag} IDLE\r\n")).await?;
+
+        let accepted = timeout(COMMAND_TIMEOUT, self.read_response())
+            .await
+            .map_err(|_| {
+                watson_err!(WatsonErrorKind::Timeout, "IMAP server stopped answering")
+            })??;
+        if !accepted.starts_with('+') {
+            return Err(watson_err!(
+                WatsonErrorKind::Mail,
+                "IDLE refused: {}",
+                accepted
+            ));
+        }
+
+        let waited = timeout(IDLE_TIMEOUT, async {
+            loop {
+                let line = self.read_response().await?;
+                if is_mailbox_change(&line) {
+                    return Ok::<_, WatsonError>(());
+                }
+            }
+        })
+        .await;
+        if let Ok(Err(e)) = waited {
+            return Err(e);
+        }
+
+        self.write("DONE\r\n").await?;
+        loop {
+            let line = timeout(COMMAND_TIMEOUT, self.read_response())
+                .await
+                .map_err(|_| {
+                    watson_err!(WatsonErrorKind::Timeout, "IMAP server stopped answering")
+                })??;
+            if line.starts_with(&tag) {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn write(&mut self, data: &str) -> Result<(), WatsonError> {
+        let stream = self.stream.get_mut();
+        stream
+            .write_all(data.as_bytes())
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))?;
+        stream
+            .flush()
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))
+    }
+
+    /// One response line with its literals (`{12}` followed by 12 bytes) inlined
+    async fn read_response(&mut self) -> Result<String, WatsonError> {
+        let mut response = Vec::new();
+        loop {
+            let mut line = Vec::new();
+            let read = self
+                .stream
+                .read_until(b'\n', &mut line)
+                .await
+                .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?;
+            if read == 0 {
+                return Err(watson_err!(
+                    WatsonErrorKind::StreamRead,
+                    "IMAP server closed the connection"
+                ));
+            }
+            while line.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+                line.pop();
+            }
+            response.extend_from_slice(&line);
+
+            let Some(size) = literal_size(&line) else {
+                break;
+            };
+            let mut literal = vec![0; size];
+            self.stream
+                .read_exact(&mut literal)
+                .await
+                .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?;
+            response.push(b'\n');
+            response.extend_from_slice(&literal);
+        }
+        Ok(String::from_utf8_lossy(&response).into_owned())
+    }
+}
+
+/// Size of the literal a line announces with a trailing `{n}`
+fn literal_size(line: &[u8]) -> Option<usize> {
+    let line = std::str::from_utf8(line).ok()?;
+    let start = line.strip_suffix('}')?.rfind('{')?;
+    line[start + 1..line.len() - 1].parse().ok()
+}
+
+fn is_mailbox_change(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    words.next() == Some("*")
+        && words.next().is_some_and(|n| n.parse::<u32>().is_ok())
+        && words
+            .next()
+            .is_some_and(|w| matches!(w, "EXISTS" | "EXPUNGE" | "FETCH" | "RECENT"))
+}
+
+/// IMAP quoted string. Line breaks would end the command early, so they and other control
+/// characters are refused rather than escaped.
+fn quote(value: &str) -> Result<String, WatsonError> {
+    if value.chars().any(char::is_control) {
+        // The value may be a password, don't repeat it
+        return Err(watson_err!(
+            WatsonErrorKind::InvalidData,
+            "IMAP strings can't contain control characters"
+        ));
+    }
+    Ok(format!(
+        "\"{}\"",
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    ))
+}
+
+/// UIDs from `* SEARCH 4 8 15`
+fn parse_search(lines: &[String]) -> Vec<u32> {
+    lines
+        .iter()
+        .filter_map(|l| l.strip_prefix("* SEARCH"))
+        .flat_map(|l| l.split_whitespace().filter_map(|n| n.parse().ok()))
+        .collect()
+}
+
+/// A `FETCH` response with its header literal inlined
+fn parse_fetch(response: &str) -> Option<MailSummary> {
+    let (envelope, headers) = response.split_once('\n')?;
+    let uid = envelope
+        .split_whitespace()
+        .skip_while(|w| !w.trim_start_matches('(').eq_ignore_ascii_case("UID"))
+        .nth(1)?
+        .trim_end_matches(')');
+
+    // Folded header lines continue with whitespace
+    let unfolded = headers.replace("\r\n ", " ").replace("\r\n\t", " ");
+    let mut from = String::new();
+    let mut subject = String::new();
+    for line in unfolded.lines() {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "from" => from = sender_name(&decode_header(value.trim())),
+            "subject" => subject = decode_header(value.trim()),
+            _ => {}
+        }
+    }
+    Some(MailSummary {
+        id: uid.to_string(),
+        from,
+        subject,
+    })
+}
+
+/// Decodes RFC 2047 encoded words, e.g. `=?UTF-8?B?R3LDvMOfZQ==?=`
+pub fn decode_header(value: &str) -> String {
+    let words =
+        Regex::new(r"=\?([^?]+)\?([BbQq])\?([^?]*)\?=").expect("Encoded word pattern is valid");
+    // Whitespace between two encoded words is not part of the text
+    let value = Regex::new(r"\?=\s+=\?")
+        .expect("Encoded word gap pattern is valid")
+        .replace_all(value, "?==?");
+    words
+        .replace_all(&value, |caps: &regex::Captures| {
+            let bytes = match &caps[2] {
+                "B" | "b" => general_purpose::STANDARD.decode(&caps[3]).ok(),
+                _ => Some(decode_q(&caps[3])),
+            };
+            let Some(bytes) = bytes else {
+                return caps[0].to_string();
+            };
+            if caps[1].eq_ignore_ascii_case("iso-8859-1") || caps[1].eq_ignore_ascii_case("latin1")
+            {
+                bytes.iter().map(|b| *b as char).collect()
+            } else {
+                String::from_utf8_lossy(&bytes).into_owned()
+            }
+        })
+        .into_owned()
+}
+
+/// The `Q` encoding, quoted-printable with `_` for spaces
+fn decode_q(text: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'_' => out.push(b' '),
+            b'=' if i + 2 < bytes.len() => match u8::from_str_radix(&text[i + 1..i + 3], 16) {
+                Ok(b) => {
+                    out.push(b);
+                    i += 2;
+                }
+                Err(_) => out.push(b'='),
+            },
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_size() {
+        assert_eq!(
+            literal_size(b"* 3 FETCH (UID 12 BODY[HEADER.FIELDS (FROM SUBJECT)] {58}"),
+            Some(58)
+        );
+        assert_eq!(literal_size(b"* OK IMAP4rev1 ready"), None);
+    }
+
+    #[test]
+    fn test_parse_search() {
+        let lines = vec!["* SEARCH 4 8 15".to_string(), "* 7 EXISTS".to_string()];
+        assert_eq!(parse_search(&lines), vec![4, 8, 15]);
+        assert!(parse_search(&["* SEARCH".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_fetch() {
+        let response = "* 3 FETCH (UID 12 BODY[HEADER.FIELDS (FROM SUBJECT)] {80}\n\
+                        From: \"Jane Doe\" <jane@example.org>\r\n\
+                        Subject: Quarterly\r\n report\r\n\r\n)";
+        let summary = parse_fetch(response).unwrap();
+        assert_eq!(summary.id, "12");
+        assert_eq!(summary.from, "Jane Doe");
+        assert_eq!(summary.subject, "Quarterly report");
+    }
+
+    #[test]
+    fn test_decode_header() {
+        assert_eq!(decode_header("=?UTF-8?B?R3LDvMOfZQ==?="), "Grüße");
+        assert_eq!(
+            decode_header("=?ISO-8859-1?Q?Caf=E9_au_lait?="),
+            "Café au lait"
+        );
+        assert_eq!(decode_header("=?UTF-8?Q?a?= =?UTF-8?Q?b?= c"), "ab c");
+        assert_eq!(decode_header("Plain subject"), "Plain subject");
+    }
+
+    #[test]
+    fn test_mailbox_changes() {
+        assert!(is_mailbox_change("* 23 EXISTS"));
+        assert!(is_mailbox_change("* 5 EXPUNGE"));
+        assert!(!is_mailbox_change("* OK Still here"));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote(r#"pa"ss\word"#).unwrap(), r#""pa\"ss\\word""#);
+        for value in ["pass\r\nA1 DELETE INBOX", "pass\n", "pa\0ss"] {
+            assert!(quote(value).is_err());
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::{
+    auth::{Credential, CredentialData, CredentialService},
+    mail::{gmail::GmailClient, imap::ImapClient},
+    protocol::MailAccount,
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+pub mod gmail;
+pub mod imap;
+
+const ICLOUD_IMAP: &str = "imap.mail.me.com";
+
+/// `$XDG_CONFIG_HOME/watson/mail.json`, e.g.
+/// `{ "accounts": [{ "credential": "Work", "host": "imap.example.org" }] }`. `credential` is the
+/// label of an account added with `watson auth`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MailConfig {
+    pub accounts: Vec<MailAccountConfig>,
+    /// Subjects kept per account
+    pub latest: usize,
+}
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            accounts: Vec::new(),
+            latest: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MailAccountConfig {
+    pub credential: String,
+    /// IMAP server, iCloud accounts default to theirs. Google accounts use the Gmail API.
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_mailbox")]
+    pub mailbox: String,
+}
+impl MailAccountConfig {
+    /// Gmail for Google credentials, IMAP for passwords
+    pub fn provider(&self, credential: Credential) -> Result<Box<dyn MailProvider>, WatsonError> {
+        match (&credential.data, credential.service) {
+            (CredentialData::OAuth { .. }, CredentialService::Google) => {
+                Ok(Box::new(GmailClient::new(credential)))
+            }
+            (CredentialData::Password { .. }, service) => {
+                let host = match (&self.host, service) {
+                    (Some(host), _) => host.clone(),
+                    (None, CredentialService::Icloud) => ICLOUD_IMAP.to_string(),
+                    (None, _) => {
+                        return Err(watson_err!(
+                            WatsonErrorKind::ConfigError,
+                            "Mail account {} needs a `host`",
+                            self.credential
+                        ));
+                    }
+                };
+                Ok(Box::new(ImapClient::new(
+                    credential,
+                    host,
+                    self.port,
+                    self.mailbox.clone(),
+                )))
+            }
+            _ => Err(watson_err!(
+                WatsonErrorKind::ConfigError,
+                "Credential {} can't be used for mail",
+                self.credential
+            )),
+        }
+    }
+}
+fn default_port() -> u16 {
+    993
+}
+fn default_mailbox() -> String {
+    "INBOX".to_string()
+}
+
+/// A mailbox whose unread messages are counted
+#[async_trait]
+pub trait MailProvider: Send {
+    /// Unread count and the newest `latest` unread messages
+    async fn check(&mut self, latest: usize) -> Result<MailAccount, WatsonError>;
+    /// Returns once the mailbox may have changed
+    async fn wait(&mut self) -> Result<(), WatsonError>;
+    /// Called after `wait` was cancelled, e.g. to drop a connection left in the middle of IDLE
+    fn interrupted(&mut self) {}
+}
+
+/// `Jane Doe <jane@example.org>` -> `Jane Doe`
+pub fn sender_name(from: &str) -> String {
+    let from = from.trim();
+    match from.split_once('<') {
+        Some((name, address)) => {
+            let name = name.trim().trim_matches('"').trim();
+            if name.is_empty() {
+                address.trim_end_matches('>').to_string()
+            } else {
+                name.to_string()
+            }
+        }
+        None => from.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sender_name() {
+        assert_eq!(sender_name("\"Jane Doe\" <jane@example.org>"), "Jane Doe");
+        assert_eq!(sender_name("Jane Doe <jane@example.org>"), "Jane Doe");
+        assert_eq!(sender_name("<jane@example.org>"), "jane@example.org");
+        assert_eq!(sender_name("jane@example.org"), "jane@example.org");
+    }
+}
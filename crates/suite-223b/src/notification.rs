@@ -25,6 +25,22 @@ pub struct Notification {
     pub urgency: Urgency,
 }
 
+/// Reason passed along with the `NotificationClosed` signal
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum CloseReason {
+    /// The notification expired
+    Expired = 1,
+    /// The notification was dismissed by the user
+    Dismissed = 2,
+    /// The notification was closed by a call to `CloseNotification`
+    ClosedByCall = 3,
+    Undefined = 4,
+    /// Put away until `Request::SnoozeNotification` is due. Only told to clients, the sending
+    /// application isn't notified.
+    Snoozed = 5,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub enum Urgency {
     Low,
@@ -1,25 +1,126 @@
+use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use std::{
-    cell::Cell,
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
     io::{Read, Write},
     ops::Not,
     os::unix::net::UnixStream,
-    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        Arc, OnceLock, RwLock,
+        atomic::{AtomicBool, AtomicU8, AtomicU32, Ordering},
+    },
 };
-use strum::{AsRefStr, EnumIter};
+use strum::{AsRefStr, EnumIter, EnumString};
 
-use crate::notification::Notification;
+use crate::notification::{CloseReason, Notification};
 
 use crate::{
     calendar::utils::{CalDavEvent, structs::EventFilter},
-    utils::errors::{WatsonError, WatsonErrorKind},
+    tokio::check_frame_len,
+    utils::{
+        battery::read_battery,
+        errors::{WatsonError, WatsonErrorDto, WatsonErrorKind},
+        paths::get_runtime_dir,
+    },
     watson_err,
 };
 
+static SOCKET_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
 pub struct SocketData;
 impl SocketData {
-    pub const SOCKET_ADDR: &'static str = "/tmp/watson.sock";
+    /// Uses `path` instead of the socket of the display, e.g. from `--socket`. Only the first call
+    /// counts.
+    pub fn set_override(path: PathBuf) {
+        let _ = SOCKET_OVERRIDE.set(path);
+    }
+    pub fn overridden() -> Option<&'static Path> {
+        SOCKET_OVERRIDE.get().map(PathBuf::as_path)
+    }
+
+    /// Where the daemon listens, `$XDG_RUNTIME_DIR/watson/<display>.sock`, so every user and seat
+    /// gets its own daemon
+    pub fn socket_path() -> Result<PathBuf, WatsonError> {
+        if let Some(path) = SOCKET_OVERRIDE.get() {
+            return Ok(path.clone());
+        }
+        Ok(get_runtime_dir()?.join(format!("{}.sock", Self::display_name())))
+    }
+
+    /// The daemon socket clients connect to. Without one for this display, e.g. in a terminal
+    /// without `WAYLAND_DISPLAY` or behind the systemd socket unit, the only daemon socket in the
+    /// runtime directory.
+    pub fn discover() -> Result<PathBuf, WatsonError> {
+        let path = Self::socket_path()?;
+        if SOCKET_OVERRIDE.get().is_some() || path.exists() {
+            return Ok(path);
+        }
+        Ok(discover_in(&get_runtime_dir()?).unwrap_or(path))
+    }
+
+    /// The client's single-instance socket next to the daemon's, `<display>-client.sock`
+    pub fn client_socket_path() -> Result<PathBuf, WatsonError> {
+        let path = Self::socket_path()?;
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(path.with_file_name(format!("{stem}{CLIENT_SOCKET_SUFFIX}")))
+    }
+
+    /// Where plugins register as daemon services, `<display>-plugins.sock`. Plugins the daemon
+    /// starts find it in `WATSON_PLUGIN_SOCKET`.
+    pub fn plugin_socket_path() -> Result<PathBuf, WatsonError> {
+        let path = Self::socket_path()?;
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(path.with_file_name(format!("{stem}{PLUGIN_SOCKET_SUFFIX}")))
+    }
+
+    /// `WAYLAND_DISPLAY` or `DISPLAY` as a file name, `default` outside a graphical session
+    pub fn display_name() -> String {
+        ["WAYLAND_DISPLAY", "DISPLAY"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            // Wayland allows absolute paths to the compositor's socket
+            .and_then(|value| value.rsplit('/').next().map(String::from))
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "default".into())
+    }
+
+    /// Where the daemon keeps the token `Request::Authenticate` expects, if it asks for one
+    pub fn token_path() -> Result<PathBuf, WatsonError> {
+        Ok(get_runtime_dir()?.join("token"))
+    }
+}
+
+const CLIENT_SOCKET_SUFFIX: &str = "-client.sock";
+const PLUGIN_SOCKET_SUFFIX: &str = "-plugins.sock";
+
+/// The daemon socket in `dir` if there is exactly one
+fn discover_in(dir: &Path) -> Option<PathBuf> {
+    let mut sockets = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.ends_with(".sock")
+                        && !name.ends_with(CLIENT_SOCKET_SUFFIX)
+                        && !name.ends_with(PLUGIN_SOCKET_SUFFIX)
+                })
+        });
+    let socket = sockets.next()?;
+    sockets.next().is_none().then_some(socket)
 }
 
 #[repr(u8)]
@@ -29,6 +130,27 @@ pub enum DaemonService {
     AudioService = 1,
 }
 
+/// Daemon services that can be switched off and on at runtime, see `Request::SetServiceEnabled`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter, AsRefStr)]
+pub enum ManagedService {
+    /// The audio actor behind `SetVolume` and volume updates
+    Audio,
+    /// Polling the calendar providers for remote changes
+    CalendarSync,
+    /// Polling quotes of the symbols in `finance.json`
+    Finance,
+    /// Following the entities of the Home Assistant instance from `homeassistant.json`
+    HomeAssistant,
+    /// Watching the accounts in `mail.json` for unread mail
+    Mail,
+    /// The Prometheus exporter, only with `--metrics-port`
+    Metrics,
+    /// Watching which applications use the camera and the microphone
+    Privacy,
+    /// Tracking how long each application is focused, see `Request::ScreenTime`
+    ScreenTime,
+}
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
 pub enum BatteryState {
     Charging,
@@ -38,20 +160,184 @@ pub enum BatteryState {
 }
 impl BatteryState {
     pub fn capacity() -> Result<u32, WatsonError> {
-        let capacity_path = "/sys/class/power_supply/BAT0/capacity";
-        let capacity = {
-            let capacity_opt = std::fs::read_to_string(capacity_path)
-                .expect("Failed to read capacity")
-                .trim()
-                .parse::<u32>();
-
-            match capacity_opt {
-                Ok(c) => c,
-                Err(e) => return Err(watson_err!(WatsonErrorKind::Deserialize, e.to_string())),
-            }
-        };
-        Ok(capacity)
+        read_battery().map(|(_, capacity)| capacity)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectionKind {
+    Wireless,
+    Wired,
+    #[default]
+    Other,
+}
+
+/// The primary network connection as reported by NetworkManager
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ConnectionInfo {
+    /// Connection profile name
+    pub id: String,
+    pub kind: ConnectionKind,
+    /// Only set for wireless connections
+    pub ssid: Option<String>,
+    /// Addresses in CIDR notation, e.g. `192.168.0.2/24`
+    pub ipv4: Vec<String>,
+    pub ipv6: Vec<String>,
+    /// Link speed in Mb/s, 0 if unknown
+    pub speed: u32,
+}
+
+/// An ethernet interface, whether or not it carries the primary connection
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct WiredDevice {
+    /// e.g. `enp3s0`
+    pub interface: String,
+    /// Whether a cable is plugged in
+    pub carrier: bool,
+    /// Negotiated speed in Mb/s, 0 without carrier
+    pub speed: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Connectivity {
+    /// `None` while offline
+    pub primary: Option<ConnectionInfo>,
+    pub wired: Vec<WiredDevice>,
+    /// Page to sign in on while NetworkManager reports a captive portal
+    pub portal: Option<String>,
+}
+impl Connectivity {
+    /// Whether a cable carries the primary connection
+    pub fn is_wired(&self) -> bool {
+        self.primary
+            .as_ref()
+            .is_some_and(|p| p.kind == ConnectionKind::Wired)
+    }
+}
+
+/// A connected Bluetooth device as reported by BlueZ
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct BluetoothDevice {
+    pub name: String,
+    /// Freedesktop icon name from BlueZ, e.g. `audio-headset`
+    pub icon: Option<String>,
+    /// Charge in percent, for devices that report it through `org.bluez.Battery1`
+    pub battery: Option<u8>,
+    /// Codec of the audio stream, e.g. `SBC`, `AAC` or `LDAC`
+    pub codec: Option<String>,
+}
+
+/// Unread mail of one account from `mail.json`
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct MailAccount {
+    /// Label of the credential the account signs in with
+    pub label: String,
+    pub unread: u32,
+    /// The newest unread messages, newest first
+    pub latest: Vec<MailSummary>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct MailSummary {
+    /// IMAP UID or Gmail message id, unique within the account
+    pub id: String,
+    /// Display name of the sender, the address if there is none
+    pub from: String,
+    pub subject: String,
+}
+
+/// Latest quote of one symbol from `finance.json`
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Quote {
+    /// As the provider spells it, e.g. `AAPL` or `BTC-USD`
+    pub symbol: String,
+    pub price: f64,
+    /// Against the previous close
+    pub change_percent: f64,
+    pub currency: Option<String>,
+    /// Prices of the current session, oldest first
+    pub history: Vec<f64>,
+}
+
+/// State of one Home Assistant entity
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct HomeAssistantEntity {
+    /// e.g. `light.kitchen`
+    pub id: String,
+    /// The friendly name, the id if there is none
+    pub name: String,
+    /// `on`, `off`, `21.5`, `unavailable`, ...
+    pub state: String,
+    /// Unit of sensors, e.g. `°C`
+    pub unit: Option<String>,
+}
+impl HomeAssistantEntity {
+    /// The part of the id before the dot, e.g. `light`
+    pub fn domain(&self) -> &str {
+        self.id.split_once('.').map_or("", |(domain, _)| domain)
     }
+    /// Whether `Request::SetHomeAssistantEntity` can switch it
+    pub fn toggleable(&self) -> bool {
+        matches!(
+            self.domain(),
+            "light" | "switch" | "fan" | "input_boolean" | "automation"
+        )
+    }
+    pub fn is_on(&self) -> bool {
+        self.state == "on"
+    }
+    pub fn is_available(&self) -> bool {
+        !matches!(self.state.as_str(), "unavailable" | "unknown")
+    }
+}
+
+/// How long an application was focused today
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct AppUsage {
+    /// Window class, e.g. `firefox`
+    pub app: String,
+    pub seconds: u64,
+    /// Daily limit in seconds from `screen_time.json`
+    pub limit: Option<u64>,
+}
+impl AppUsage {
+    pub fn over_limit(&self) -> bool {
+        self.limit.is_some_and(|limit| self.seconds >= limit)
+    }
+}
+
+/// `1 h 05 min`, or `45 min` under an hour
+pub fn duration_label(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    match minutes / 60 {
+        0 => format!("{minutes} min"),
+        hours => format!("{hours} h {:02} min", minutes % 60),
+    }
+}
+
+/// State of power-profiles-daemon beyond the active profile
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct PowerProfiles {
+    pub active: PowerMode,
+    /// Profiles the machine supports, in cycling order
+    pub available: Vec<PowerMode>,
+    /// Why `performance` is throttled, e.g. `lap-detected` or `high-operating-temperature`
+    pub degraded: Option<String>,
+    pub holds: Vec<ProfileHold>,
+}
+impl PowerProfiles {
+    pub fn is_available(&self, mode: PowerMode) -> bool {
+        // Nothing known yet, e.g. before the daemon answered
+        self.available.is_empty() || self.available.contains(&mode)
+    }
+}
+
+/// An application keeping a profile active until it releases it or exits
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ProfileHold {
+    pub application: String,
+    pub profile: PowerMode,
+    pub reason: String,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -61,14 +347,23 @@ pub struct SystemState {
     pub powermode: Cell<u8>,
     pub brightness: Cell<u8>,
     pub volume: Cell<u8>,
+    pub night_light: Cell<bool>,
+    pub night_light_intensity: Cell<u8>,
+    pub connectivity: RefCell<Connectivity>,
+    pub bluetooth_devices: RefCell<Vec<BluetoothDevice>>,
 }
-#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
 pub struct SystemStateRaw {
     pub wifi: bool,
     pub bluetooth: bool,
     pub powermode: u8,
     pub brightness: u8,
     pub volume: u8,
+    pub night_light: bool,
+    pub night_light_intensity: u8,
+    pub connectivity: Connectivity,
+    /// Connected devices, empty while Bluetooth is off
+    pub bluetooth_devices: Vec<BluetoothDevice>,
 }
 #[derive(Debug, Default)]
 pub struct AtomicSystemState {
@@ -80,7 +375,15 @@ pub struct AtomicSystemState {
     pub powermode: AtomicU8,
     pub brightness: AtomicU8,
     pub volume: AtomicU8,
-    pub dynamic_states: DashMap<&'static str, AtomicU8>,
+    pub recording: AtomicBool,
+    /// Notifications received during do-not-disturb
+    pub missed: AtomicU32,
+    pub night_light: AtomicBool,
+    pub night_light_intensity: AtomicU8,
+    pub connectivity: RwLock<Connectivity>,
+    pub bluetooth_devices: RwLock<Vec<BluetoothDevice>>,
+    pub power_profiles: RwLock<PowerProfiles>,
+    pub dynamic_states: DashMap<Arc<str>, AtomicU8>,
 }
 
 #[repr(u8)]
@@ -94,6 +397,7 @@ pub enum UpdateField {
     Powermode = 4,
     Brightness = 5,
     Volume = 6,
+    NightLight = 7,
 }
 impl From<u8> for UpdateField {
     fn from(v: u8) -> Self {
@@ -104,6 +408,7 @@ impl From<u8> for UpdateField {
             4 => Self::Powermode,
             5 => Self::Brightness,
             6 => Self::Volume,
+            7 => Self::NightLight,
             _ => Self::None,
         }
     }
@@ -119,6 +424,27 @@ impl AtomicSystemState {
         self.powermode.store(state.powermode, Ordering::Relaxed);
         self.brightness.store(state.brightness, Ordering::Relaxed);
         self.volume.store(state.volume, Ordering::Relaxed);
+        self.night_light.store(state.night_light, Ordering::Relaxed);
+        self.night_light_intensity
+            .store(state.night_light_intensity, Ordering::Relaxed);
+        self.set_connectivity(state.connectivity);
+        if let Ok(mut devices) = self.bluetooth_devices.write() {
+            *devices = state.bluetooth_devices;
+        }
+    }
+    pub fn set_connectivity(&self, connectivity: Connectivity) {
+        if let Ok(mut current) = self.connectivity.write() {
+            *current = connectivity;
+        }
+    }
+    pub fn set_power_profiles(&self, profiles: PowerProfiles) {
+        self.powermode
+            .store(profiles.active.into(), Ordering::Relaxed);
+        self.updated
+            .fetch_or(1 << UpdateField::Powermode as u8, Ordering::Relaxed);
+        if let Ok(mut current) = self.power_profiles.write() {
+            *current = profiles;
+        }
     }
 }
 
@@ -130,35 +456,392 @@ impl From<&SystemStateRaw> for SystemState {
             powermode: Cell::new(v.powermode),
             brightness: Cell::new(v.brightness),
             volume: Cell::new(v.volume),
+            night_light: Cell::new(v.night_light),
+            night_light_intensity: Cell::new(v.night_light_intensity),
+            connectivity: RefCell::new(v.connectivity.clone()),
+            bluetooth_devices: RefCell::new(v.bluetooth_devices.clone()),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum InternalMessage {
     BatteryState {
         state: BatteryState,
         percentage: u32,
     },
     Notification(u32),
+    NotificationClosed {
+        id: u32,
+        reason: CloseReason,
+    },
+    /// Notifications received while silent
+    MissedNotifications(u32),
+    /// Do-not-disturb ended after `missed` notifications arrived
+    DndEnded {
+        missed: u32,
+    },
     VolumeStateChange {
         percentage: u8,
     },
+    /// The audio server went away or came back
+    AudioAvailable(bool),
+    Surface {
+        surface: Surface,
+        action: SurfaceAction,
+    },
+    CalendarChanged {
+        calendar: String,
+    },
+    /// A screen recording started or stopped
+    RecordingState(bool),
+    /// The user picked a color with `Request::PickColor`
+    ColorPicked(PickedColor),
+    NightLight {
+        enabled: bool,
+        intensity: u8,
+    },
+    KeyboardLayout {
+        name: String,
+        short: String,
+    },
+    /// The primary connection or a wired interface changed
+    Connectivity(Connectivity),
+    /// A request that finished in the background failed
+    Error(WatsonErrorDto),
+    /// A toggle was changed outside of Watson
+    SystemState(SystemStateRaw),
+    PowerProfiles(PowerProfiles),
+    Session(SessionState),
+    Dock(DockState),
+    /// External displays whose brightness changed
+    Displays(Vec<DisplayBrightness>),
+    /// A job added with `Request::ScheduleJob` is due
+    JobDue {
+        id: String,
+    },
+    /// Unread counts changed in one of the mail accounts
+    Mail(Vec<MailAccount>),
+    /// New quotes arrived
+    Quotes(Vec<Quote>),
+    /// Connected to Home Assistant, every entity it has
+    HomeAssistant(Vec<HomeAssistantEntity>),
+    /// A Home Assistant entity changed state
+    HomeAssistantEntity(HomeAssistantEntity),
+    /// Today's focus time per application, sent every minute while tracking
+    ScreenTime(Vec<AppUsage>),
+    /// A focus profile started or ended
+    Focus(FocusState),
+    Refreshing {
+        kind: DataKind,
+        active: bool,
+    },
+    /// An application started or stopped using the camera or the microphone
+    DeviceUse(DeviceUse),
+    /// Only queued for connections that sent `Request::TraceProtocol`
+    Trace(TraceEvent),
+    /// A plugin sent one of the broadcasts it declared
+    PluginEvent {
+        plugin: String,
+        kind: String,
+        payload: String,
+    },
+}
+
+/// Which way a traced message went, see `Request::TraceProtocol`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceDirection {
+    /// From a client to the daemon
+    Request,
+    /// The daemon's answer to one client
+    Response,
+    /// Sent to every client
+    Broadcast,
+}
+
+/// A message that went over the socket
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TraceEvent {
+    pub at: DateTime<Utc>,
+    /// The connection, `None` for broadcasts
+    pub client: Option<u64>,
+    pub direction: TraceDirection,
+    /// Debug form of the message, cut to a few hundred bytes
+    pub payload: String,
+    /// Messages left out before this one to keep the rate down
+    pub skipped: u64,
+}
+impl std::fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.skipped > 0 {
+            writeln!(f, "... {} messages skipped", self.skipped)?;
+        }
+        let at = self.at.with_timezone(&chrono::Local).format("%H:%M:%S%.3f");
+        let (client, arrow) = match (self.client, self.direction) {
+            (_, TraceDirection::Broadcast) => ("*".to_string(), "<="),
+            (Some(id), TraceDirection::Request) => (format!("#{id}"), "->"),
+            (Some(id), TraceDirection::Response) => (format!("#{id}"), "<-"),
+            (None, _) => ("?".to_string(), "--"),
+        };
+        write!(f, "{at} {client:>4} {arrow} {}", self.payload)
+    }
+}
+
+/// What a plugin introduces itself with on `SocketData::plugin_socket_path`. The user is asked
+/// before a plugin is let in for the first time and whenever its manifest grows.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginManifest {
+    /// Unique among the connected plugins, e.g. `home-assistant`
+    pub name: String,
+    pub version: String,
+    /// Requests clients may send it with `Request::Plugin`
+    pub requests: Vec<String>,
+    /// Broadcasts it may send, clients receive them as `Response::PluginEvent`
+    pub broadcasts: Vec<String>,
+}
+impl PluginManifest {
+    /// Whether approving `self` covers `other`, i.e. it asks for nothing more
+    pub fn covers(&self, other: &PluginManifest) -> bool {
+        self.name == other.name
+            && other.requests.iter().all(|r| self.requests.contains(r))
+            && other.broadcasts.iter().all(|b| self.broadcasts.contains(b))
+    }
+}
+
+/// From a plugin to the daemon, length-prefixed bincode like the client protocol
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum PluginMessage {
+    /// The first message, answered with `PluginCall::Welcome` or `PluginCall::Rejected`
+    Hello(PluginManifest),
+    /// Answers `PluginCall::Call`, the payload goes to the client as `Response::Plugin`
+    Reply { call: u32, payload: String },
+    /// Fails `PluginCall::Call`, the client gets `Response::Error`
+    Failed { call: u32, message: String },
+    /// Sent to every client, `kind` must be one of `PluginManifest::broadcasts`
+    Broadcast { kind: String, payload: String },
+}
+
+/// From the daemon to a plugin
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum PluginCall {
+    Welcome,
+    /// The user declined or the manifest is invalid, the daemon hangs up after this
+    Rejected(String),
+    /// A client sent `Request::Plugin`, `request` is one of `PluginManifest::requests`
+    Call {
+        call: u32,
+        request: String,
+        payload: String,
+    },
+}
+
+/// Sleep and idle state of the login session as reported by logind
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionState {
+    /// The system is about to suspend or hibernate
+    Sleeping,
+    Resumed,
+    Idle,
+    Active,
+}
+
+/// Lid and dock state together with what the daemon's dock config asks clients to do about it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DockState {
+    pub lid_closed: bool,
+    pub docked: bool,
+    /// Widget layout to show, `None` for the default one
+    pub layout: Option<String>,
+    /// False while the brightness slider would control a display that is off
+    pub brightness_slider: bool,
+}
+impl Default for DockState {
+    fn default() -> Self {
+        Self {
+            lid_closed: false,
+            docked: false,
+            layout: None,
+            brightness_slider: true,
+        }
+    }
+}
+
+/// The active focus profile and the ones configured in the daemon's `focus.json`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FocusState {
+    pub active: Option<String>,
+    /// Names of every profile, sorted
+    pub profiles: Vec<String>,
+    /// Widget layout the active profile asks for, `None` keeps the dock's or the default one
+    pub layout: Option<String>,
+}
+
+/// A color picked from the screen
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PickedColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+/// Applications currently using the camera and the microphone
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceUse {
+    /// Application names, sorted, empty while the device is unused
+    pub camera: Vec<String>,
+    pub microphone: Vec<String>,
+}
+impl DeviceUse {
+    pub fn is_active(&self) -> bool {
+        !self.camera.is_empty() || !self.microphone.is_empty()
+    }
+}
+
+/// Brightness of an external display, controlled over DDC/CI
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayBrightness {
+    /// DRM connector, e.g. `DP-1`
+    pub device: String,
+    /// Model name reported by the monitor
+    pub name: String,
+    pub percent: u8,
+}
+
+/// Whether the daemon receives notifications, i.e. owns `org.freedesktop.Notifications`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotificationServer {
+    /// Not connected to the session bus yet
+    #[default]
+    Starting,
+    Owned,
+    /// Another notification daemon, e.g. dunst or mako, has the name
+    Taken {
+        /// Process name of the owner if it could be found
+        owner: Option<String>,
+    },
+    /// Started with `--no-notifications`
+    Disabled,
+}
+
+/// When a job of the daemon's scheduler runs
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobSchedule {
+    /// Five fields: minute, hour, day of month, month and day of week, in local time
+    Cron(String),
+    /// Every that many seconds
+    Every(u64),
+    /// Once, a time that already passed runs right away
+    At(DateTime<Utc>),
+}
+
+/// A job of the daemon's scheduler, see `Request::ScheduledJobs`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    pub id: String,
+    pub schedule: JobSchedule,
+    pub next_run: Option<DateTime<Utc>>,
+    pub last_run: Option<DateTime<Utc>>,
+    /// Added with `Request::ScheduleJob`, kept across restarts. The daemon's own jobs aren't.
+    pub persistent: bool,
+}
+
+/// What the daemon knows at one point in time, see `Request::DumpState`. A daemon started with
+/// `--load-state` picks it up again, for reproducing what a user saw.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub version: String,
+    pub taken: DateTime<Utc>,
+    pub system: SystemStateRaw,
+    pub silent: bool,
+    pub auto_dnd: bool,
+    /// Stored notifications without their bodies, snapshots end up in bug reports
+    pub notifications: Vec<Notification>,
+    /// Bits of the `DaemonService`s clients registered
+    pub registered_services: u8,
+    /// Bits of the `DaemonService`s that can't run on this system
+    pub unavailable_services: u8,
+    pub disabled_services: Vec<ManagedService>,
+    pub jobs: Vec<ScheduledJob>,
+}
+
+/// Counters of a running daemon, see `Request::GetMetrics`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonMetrics {
+    pub uptime_secs: u64,
+    pub connected_clients: usize,
+    pub accepted_clients: u64,
+    pub messages_broadcast: u64,
+    /// Messages a slow client missed
+    pub dropped_messages: u64,
+    pub evicted_clients: u64,
+    pub notifications_received: u64,
+    pub notifications_stored: usize,
+    /// Approximate heap size of the stored notifications
+    pub notification_bytes: usize,
+    /// Failed requests by `Request::service`
+    pub errors: BTreeMap<String, u64>,
+}
+
+/// Parts of the client that can be opened and closed over IPC, e.g. from a compositor keybinding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumString, AsRefStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Surface {
+    Window,
+    NotificationCentre,
+    Calendar,
+    Notes,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumString, AsRefStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum SurfaceAction {
+    Show,
+    Hide,
+    Toggle,
+}
+
+/// Remote data the daemon can be asked to fetch again, see `Request::ForceRefresh`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, EnumString, AsRefStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum DataKind {
+    Calendar,
+    Mail,
+    Quotes,
+    All,
+}
+impl DataKind {
+    /// Whether refreshing `self` refreshes `other` too
+    pub fn includes(self, other: DataKind) -> bool {
+        self == Self::All || self == other
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub enum Response {
     Ok,
-    Error(String),
+    Error(WatsonErrorDto),
     Pong,
     Todo,
     Status {
         running: bool,
         silent: bool,
+        notifications: NotificationServer,
     },
 
     Notification(Option<Notification>),
     Notifications(Vec<Notification>),
+    /// Id of the notification created by `Request::Notify`
+    Notified(u32),
+    NotificationClosed {
+        id: u32,
+        reason: CloseReason,
+    },
+    /// How many notifications arrived since do-not-disturb started, sent whenever that changes
+    MissedNotifications(u32),
+    /// Do-not-disturb ended, for a summary of the `missed` notifications. Resets the count.
+    DndEnded {
+        missed: u32,
+    },
 
     SystemState(SystemStateRaw),
     BatteryState {
@@ -168,12 +851,102 @@ pub enum Response {
     VolumeState {
         percentage: u8,
     },
+    /// Volume control is unavailable while the audio server restarts or is missing
+    AudioAvailable(bool),
     Events(Vec<CalDavEvent>),
+    /// Events of the calendar with the given href changed remotely
+    CalendarChanged {
+        calendar: String,
+    },
+    Surface {
+        surface: Surface,
+        action: SurfaceAction,
+    },
+    RecordingState(bool),
+    ColorPicked(PickedColor),
+    NightLightState {
+        enabled: bool,
+        intensity: u8,
+    },
+    /// Active keyboard layout, `name` as reported by the compositor, `short` e.g. `DE`
+    KeyboardLayout {
+        name: String,
+        short: String,
+    },
+    Connectivity(Connectivity),
+    PowerProfiles(PowerProfiles),
+    /// Clients should pause periodic redraws while sleeping or idle
+    Session(SessionState),
+    Dock(DockState),
+    Displays(Vec<DisplayBrightness>),
+    Metrics(DaemonMetrics),
+    StateSnapshot(Box<StateSnapshot>),
+    ScheduledJobs(Vec<ScheduledJob>),
+    /// A job added with `Request::ScheduleJob` ran
+    JobDue {
+        id: String,
+    },
+    /// Unread mail of every account from `mail.json`
+    Mail(Vec<MailAccount>),
+    /// Quotes of every symbol from `finance.json`
+    Quotes(Vec<Quote>),
+    /// Answers `Request::HomeAssistant`, also sent after connecting to the instance
+    HomeAssistant(Vec<HomeAssistantEntity>),
+    /// An entity changed state
+    HomeAssistantEntity(HomeAssistantEntity),
+    /// Today's focus time per application, longest first
+    ScreenTime(Vec<AppUsage>),
+    /// Answers `Request::Focus`, also sent whenever a focus profile starts or ends
+    Focus(FocusState),
+    /// A `Request::ForceRefresh` started or finished, for showing that it's in flight
+    Refreshing {
+        kind: DataKind,
+        active: bool,
+    },
+    /// Who uses the camera and the microphone, sent whenever that changes
+    DeviceUse(DeviceUse),
+    /// Traffic of another connection, after `Request::TraceProtocol`
+    Trace(TraceEvent),
+    /// What a plugin answered to `Request::Plugin`
+    Plugin {
+        plugin: String,
+        payload: String,
+    },
+    /// A broadcast declared by a plugin, sent to every client
+    PluginEvent {
+        plugin: String,
+        kind: String,
+        payload: String,
+    },
+    /// The connected plugins, answers `Request::Plugins`
+    Plugins(Vec<PluginManifest>),
+    /// What a successful `Request::Command` with `capture` printed, cut to a few KiB
+    CommandOutput {
+        stdout: String,
+        stderr: String,
+    },
+    /// Result of a `Request::Tracked`
+    Ack {
+        id: u32,
+        error: Option<WatsonErrorDto>,
+    },
 }
 impl Response {
+    /// The `Ack` for a `Request::Tracked` that `self` answered
+    pub fn into_ack(self, id: u32) -> Self {
+        match self {
+            Self::Error(e) => Self::Ack { id, error: Some(e) },
+            _ => Self::Ack { id, error: None },
+        }
+    }
     pub fn is_state_change(&self) -> bool {
         match self {
-            Self::SystemState(_) | Self::VolumeState { .. } | Self::BatteryState { .. } => true,
+            Self::SystemState(_)
+            | Self::VolumeState { .. }
+            | Self::BatteryState { .. }
+            | Self::NightLightState { .. }
+            | Self::Connectivity(_)
+            | Self::PowerProfiles(_) => true,
             _ => false,
         }
     }
@@ -185,18 +958,65 @@ impl<E> IntoResponse for Result<E, WatsonError> {
     fn into_response(self) -> Response {
         match self {
             Ok(_) => Response::Ok,
-            Err(e) => Response::Error(e.message),
+            Err(e) => e.into(),
         }
     }
 }
+impl From<WatsonError> for Response {
+    fn from(e: WatsonError) -> Self {
+        Self::Error(e.into())
+    }
+}
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub enum Request {
     Ping,
     GetStatus,
+    GetMetrics,
+    /// Everything the daemon knows, answered with `Response::StateSnapshot`
+    DumpState,
+    /// Mirrors the requests, responses and broadcasts of the other connections to this one as
+    /// `Response::Trace`, for debugging widgets
+    TraceProtocol,
+    /// Proves the connection may run commands, see `SocketData::token_path`
+    Authenticate(String),
+    /// Starts or stops a daemon service, the choice is kept across restarts
+    SetServiceEnabled {
+        service: ManagedService,
+        enabled: bool,
+    },
     Silence(bool),
+    /// Automatically silence while fullscreen or screencasting
+    SetAutoDnd(bool),
     Notification(u32),
-    PendingNotifications,
+    /// Up to `limit` stored notifications, newest first, skipping the first `offset`
+    PendingNotifications {
+        offset: usize,
+        limit: usize,
+    },
+    /// Remove a notification on behalf of the user
+    DismissNotification(u32),
+    /// The user clicked one of the notification's actions
+    InvokeAction {
+        id: u32,
+        action: String,
+    },
+    /// Hides a notification and delivers it again in `seconds`, also after a restart
+    SnoozeNotification {
+        id: u32,
+        seconds: u64,
+    },
+    /// Creates a notification from Watson itself, stored like one sent over D-Bus. Answered
+    /// with `Response::Notified`.
+    Notify {
+        summary: String,
+        body: String,
+        icon: String,
+        /// Milliseconds, -1 or 0 to keep it until dismissed
+        timeout: i32,
+        /// Action keys and their labels
+        actions: Vec<(String, String)>,
+    },
 
     // Hardware
     RegisterServices(u8),
@@ -204,12 +1024,143 @@ pub enum Request {
     SetWifi(bool),
     SetBluetooth(bool),
     SetPowerMode(u8),
-    SetBacklight(u8),
+    PowerProfiles,
+    /// Keep a profile active regardless of `SetPowerMode` until released
+    HoldPowerProfile {
+        profile: u8,
+        reason: String,
+    },
+    ReleasePowerProfile,
+    DockState,
+    /// `device` is an external display from `Request::Displays`, the built-in backlight if `None`
+    SetBacklight {
+        device: Option<String>,
+        percent: u8,
+    },
+    /// Detects external displays, their brightness arrives as `Response::Displays`
+    Displays,
+    /// Who uses the camera and the microphone, answered with `Response::DeviceUse`
+    DeviceUse,
     SetVolume(u8),
-    Command(String),
+    SetNightLight(bool),
+    SetNightLightIntensity(u8),
+    /// Runs a program with arguments, no shell, if the daemon's allowlist permits it. Started
+    /// and left running unless `capture` is set, then waited for up to the daemon's timeout and
+    /// answered with `Response::CommandOutput`.
+    Command {
+        cmd: String,
+        #[serde(default)]
+        capture: bool,
+    },
+
+    // Capture, all go through xdg-desktop-portal
+    Screenshot,
+    ToggleRecording,
+    /// Lets the user pick a color on screen, answered with `Response::ColorPicked`
+    PickColor,
 
     // Software
     Event(EventFilter),
+    KeyboardLayout,
+    /// Switch to the next configured keyboard layout
+    NextKeyboardLayout,
+    /// Unread mail of every account, answered with `Response::Mail`
+    Mail,
+    /// Last known quotes, answered with `Response::Quotes`
+    Quotes,
+    /// Every Home Assistant entity, answered with `Response::HomeAssistant`
+    HomeAssistant,
+    /// Turns a light, switch or the like on or off
+    SetHomeAssistantEntity {
+        entity: String,
+        on: bool,
+    },
+    /// Answered with `Response::ScreenTime`
+    ScreenTime,
+    /// The active focus profile, answered with `Response::Focus`
+    Focus,
+    /// Applies a profile from the daemon's `focus.json`, `None` ends it and restores what it
+    /// changed
+    SetFocus(Option<String>),
+    /// Fetches remote data now instead of on the next poll. Clients are sent
+    /// `Response::Refreshing` when it starts and ends.
+    ForceRefresh(DataKind),
+
+    // Scheduler
+    ScheduledJobs,
+    /// Adds or replaces a job, clients are sent `Response::JobDue` whenever it runs
+    ScheduleJob {
+        id: String,
+        schedule: JobSchedule,
+    },
+    CancelJob(String),
+
+    // Plugins
+    /// The connected plugins, answered with `Response::Plugins`
+    Plugins,
+    /// Forwarded to `plugin`, answered with `Response::Plugin`. The payload's format is up to
+    /// the plugin, usually JSON.
+    Plugin {
+        plugin: String,
+        request: String,
+        payload: String,
+    },
+
+    // Client surfaces, relayed to every connected client
+    ShowSurface(Surface),
+    HideSurface(Surface),
+    ToggleSurface(Surface),
+
+    /// Answered with `Response::Ack` carrying the same id, so the client can undo what it
+    /// changed optimistically if the request fails
+    Tracked {
+        id: u32,
+        request: Box<Request>,
+    },
+}
+impl Request {
+    /// The request itself, without the `Tracked` wrapper
+    pub fn untracked(&self) -> &Request {
+        match self {
+            Self::Tracked { request, .. } => request.untracked(),
+            other => other,
+        }
+    }
+    /// The part of the daemon that serves the request, used to group metrics
+    pub fn service(&self) -> &'static str {
+        match self.untracked() {
+            Self::Ping
+            | Self::GetStatus
+            | Self::GetMetrics
+            | Self::DumpState
+            | Self::TraceProtocol
+            | Self::Authenticate(_)
+            | Self::SetServiceEnabled { .. }
+            | Self::Tracked { .. } => "daemon",
+            Self::Silence(_)
+            | Self::SetAutoDnd(_)
+            | Self::Notification(_)
+            | Self::PendingNotifications { .. }
+            | Self::DismissNotification(_)
+            | Self::InvokeAction { .. }
+            | Self::SnoozeNotification { .. }
+            | Self::Notify { .. } => "notifications",
+            Self::Command { .. } => "command",
+            Self::Screenshot | Self::ToggleRecording | Self::PickColor => "capture",
+            Self::Event(_) => "calendar",
+            Self::KeyboardLayout | Self::NextKeyboardLayout => "keyboard",
+            Self::Mail => "mail",
+            Self::Quotes => "finance",
+            Self::HomeAssistant | Self::SetHomeAssistantEntity { .. } => "homeassistant",
+            Self::ScreenTime => "screen-time",
+            Self::Focus | Self::SetFocus(_) => "focus",
+            Self::ForceRefresh(_) => "refresh",
+            Self::ScheduledJobs | Self::ScheduleJob { .. } | Self::CancelJob(_) => "scheduler",
+            Self::Plugins | Self::Plugin { .. } => "plugins",
+            Self::ShowSurface(_) | Self::HideSurface(_) | Self::ToggleSurface(_) => "surfaces",
+            _ => "hardware",
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
@@ -242,6 +1193,19 @@ impl From<PowerMode> for u8 {
     }
 }
 
+impl FromStr for PowerMode {
+    type Err = ();
+    /// Parses the names power-profiles-daemon uses
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "power-saver" => Ok(Self::PowerSave),
+            "balanced" => Ok(Self::Balanced),
+            "performance" => Ok(Self::Performace),
+            _ => Err(()),
+        }
+    }
+}
+
 impl ToString for PowerMode {
     fn to_string(&self) -> String {
         match self {
@@ -294,8 +1258,7 @@ impl SizedMessage for UnixStream {
         let buf = buf.as_ref();
 
         // Safely convert buf_len from usize to u32
-        let buf_len: u32 = buf
-            .len()
+        let buf_len: u32 = check_frame_len(buf.len())?
             .try_into()
             .map_err(|_| watson_err!(WatsonErrorKind::InvalidData, "message too large"))?;
 
@@ -315,7 +1278,7 @@ impl SizedMessage for UnixStream {
         // Read message length
         self.read_exact(&mut buf_len)
             .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?;
-        let msg_len = u32::from_be_bytes(buf_len) as usize;
+        let msg_len = check_frame_len(u32::from_be_bytes(buf_len) as usize)?;
 
         let mut buf = vec![0u8; msg_len];
         self.read_exact(&mut buf)
@@ -324,3 +1287,54 @@ impl SizedMessage for UnixStream {
         Ok(buf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover_the_only_daemon_socket() {
+        let dir = std::env::temp_dir().join(format!("watson-sockets-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("token"), "").unwrap();
+        std::fs::write(dir.join("wayland-1-client.sock"), "").unwrap();
+        std::fs::write(dir.join("wayland-1-plugins.sock"), "").unwrap();
+        assert_eq!(discover_in(&dir), None);
+
+        std::fs::write(dir.join("wayland-1.sock"), "").unwrap();
+        assert_eq!(discover_in(&dir), Some(dir.join("wayland-1.sock")));
+
+        // A second seat makes it ambiguous
+        std::fs::write(dir.join("wayland-2.sock"), "").unwrap();
+        assert_eq!(discover_in(&dir), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plugin_approval_covers_smaller_manifests() {
+        let approved = PluginManifest {
+            name: "home-assistant".into(),
+            version: "1.0".into(),
+            requests: vec!["lights".into(), "scenes".into()],
+            broadcasts: vec!["state".into()],
+        };
+        let update = PluginManifest {
+            version: "1.1".into(),
+            requests: vec!["lights".into()],
+            ..approved.clone()
+        };
+        assert!(approved.covers(&update));
+
+        // Asking for more needs the user again
+        let grown = PluginManifest {
+            requests: vec!["lights".into(), "locks".into()],
+            ..approved.clone()
+        };
+        assert!(!approved.covers(&grown));
+        let renamed = PluginManifest {
+            name: "other".into(),
+            ..approved.clone()
+        };
+        assert!(!approved.covers(&renamed));
+    }
+}
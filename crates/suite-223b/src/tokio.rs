@@ -1,12 +1,59 @@
-use serde::Serialize;
+use serde::{Serialize, de::DeserializeOwned};
 use std::future::Future;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::UnixStream;
 use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
 
 use crate::utils::errors::{WatsonError, WatsonErrorKind};
 use crate::watson_err;
 
+/// Upper bound for a single framed message unless changed with [`set_max_message_size`].
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+static MAX_MESSAGE_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_MESSAGE_SIZE);
+
+/// Sets the largest message (excluding the length prefix) that may be read or written.
+pub fn set_max_message_size(bytes: usize) {
+    MAX_MESSAGE_SIZE.store(bytes.min(u32::MAX as usize), Ordering::Relaxed);
+}
+
+pub fn max_message_size() -> usize {
+    MAX_MESSAGE_SIZE.load(Ordering::Relaxed)
+}
+
+/// Validates a length prefix before anything gets allocated for it
+pub fn check_frame_len(len: usize) -> Result<usize, WatsonError> {
+    let max = max_message_size();
+    if len > max {
+        return Err(watson_err!(
+            WatsonErrorKind::InvalidData,
+            "message of {} bytes exceeds the limit of {} bytes",
+            len,
+            max
+        ));
+    }
+    Ok(len)
+}
+
+/// Decodes exactly one message from a frame. Trailing bytes are rejected.
+pub fn decode_sized<T: DeserializeOwned>(buf: &[u8]) -> Result<T, WatsonError> {
+    // The limit `read_frame` enforces, so raising it at runtime covers both
+    check_frame_len(buf.len())?;
+    let config = bincode::config::standard();
+    let (value, read) = bincode::serde::decode_from_slice::<T, _>(buf, config)
+        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+
+    if read != buf.len() {
+        return Err(watson_err!(
+            WatsonErrorKind::Deserialize,
+            "{} trailing bytes after message",
+            buf.len() - read
+        ));
+    }
+    Ok(value)
+}
+
 pub struct SizedMessageObj {
     buffer: Vec<u8>,
 }
@@ -27,6 +74,54 @@ impl SizedMessageObj {
     }
 }
 
+/// Reads one length prefixed frame
+pub async fn read_frame<R>(reader: &mut R) -> Result<Vec<u8>, WatsonError>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    let mut buf_len = [0u8; 4];
+
+    // Read message length
+    reader
+        .read_exact(&mut buf_len)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?;
+    let msg_len = check_frame_len(u32::from_be_bytes(buf_len) as usize)?;
+
+    let mut buf = vec![0u8; msg_len];
+    reader
+        .read_exact(&mut buf)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?;
+
+    Ok(buf)
+}
+
+/// Writes `bytes` as one length prefixed frame
+pub async fn write_frame<W>(writer: &mut W, bytes: &[u8]) -> Result<(), WatsonError>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    // Safely convert buf_len from usize to u32
+    let buf_len: u32 = check_frame_len(bytes.len())?
+        .try_into()
+        .map_err(|_| watson_err!(WatsonErrorKind::InvalidData, "message too long"))?;
+
+    // Write message size to stream
+    writer
+        .write_all(&buf_len.to_be_bytes())
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))?;
+
+    // Write message to stream
+    writer
+        .write_all(bytes)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))?;
+
+    Ok(())
+}
+
 pub trait AsyncSizedMessage {
     fn write_sized<'a>(
         &'a mut self,
@@ -41,47 +136,12 @@ impl AsyncSizedMessage for UnixStream {
         &'a mut self,
         what: SizedMessageObj,
     ) -> impl Future<Output = Result<(), WatsonError>> + Send + 'a {
-        async move {
-            // Safely convert buf_len from usize to u32
-            let buf_len: u32 = what
-                .bytes()
-                .len()
-                .try_into()
-                .map_err(|_| watson_err!(WatsonErrorKind::InvalidData, "message too long"))?;
-
-            // Write message size to stream
-            let len_bytes = buf_len.to_be_bytes();
-            self.write_all(&len_bytes)
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))?;
-
-            // Write message to stream
-            self.write(what.bytes())
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))?;
-
-            Ok(())
-        }
+        async move { write_frame(self, what.bytes()).await }
     }
     fn read_sized<'a>(
         &'a mut self,
     ) -> impl Future<Output = Result<Vec<u8>, WatsonError>> + Send + 'a {
-        async move {
-            let mut buf_len = [0u8; 4];
-
-            // Read message length
-            self.read_exact(&mut buf_len)
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?;
-            let msg_len = u32::from_be_bytes(buf_len) as usize;
-
-            let mut buf = vec![0u8; msg_len];
-            self.read_exact(&mut buf)
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?;
-
-            Ok(buf)
-        }
+        read_frame(self)
     }
 }
 
@@ -100,22 +160,7 @@ impl AsyncSizedMessage for OwnedReadHalf {
     fn read_sized<'a>(
         &'a mut self,
     ) -> impl Future<Output = Result<Vec<u8>, WatsonError>> + Send + 'a {
-        async move {
-            let mut buf_len = [0u8; 4];
-
-            // Read message length
-            self.read_exact(&mut buf_len)
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?;
-            let msg_len = u32::from_be_bytes(buf_len) as usize;
-
-            let mut buf = vec![0u8; msg_len];
-            self.read_exact(&mut buf)
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?;
-
-            Ok(buf)
-        }
+        read_frame(self)
     }
 }
 
@@ -124,27 +169,7 @@ impl AsyncSizedMessage for OwnedWriteHalf {
         &'a mut self,
         what: SizedMessageObj,
     ) -> impl Future<Output = Result<(), WatsonError>> + Send + 'a {
-        async move {
-            // Safely convert buf_len from usize to u32
-            let buf_len: u32 = what
-                .bytes()
-                .len()
-                .try_into()
-                .map_err(|_| watson_err!(WatsonErrorKind::InvalidData, "message too long"))?;
-
-            // Write message size to stream
-            let len_bytes = buf_len.to_be_bytes();
-            self.write_all(&len_bytes)
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))?;
-
-            // Write message to stream
-            self.write(what.bytes())
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))?;
-
-            Ok(())
-        }
+        async move { write_frame(self, what.bytes()).await }
     }
     fn read_sized<'a>(
         &'a mut self,
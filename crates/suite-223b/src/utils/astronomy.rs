@@ -0,0 +1,196 @@
+use std::f64::consts::PI;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Days from one new moon to the next
+const SYNODIC_MONTH: f64 = 29.530588853;
+
+/// A point on earth in degrees, north and east are positive
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// The sun's course over one day in UTC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SunTimes {
+    pub sunrise: DateTime<Utc>,
+    pub sunset: DateTime<Utc>,
+    /// End of the morning golden hour, `None` if the sun stays below 6° all day
+    pub golden_morning: Option<DateTime<Utc>>,
+    /// Start of the evening golden hour
+    pub golden_evening: Option<DateTime<Utc>>,
+}
+impl SunTimes {
+    /// `None` during polar day or night
+    pub fn on(date: NaiveDate, location: Location) -> Option<Self> {
+        let (sunrise, sunset) = sun_times(date, location.latitude, location.longitude)?;
+        // The golden hour lasts while the sun is less than 6° above the horizon
+        let golden = crossings(date, location, 84.0);
+        Some(Self {
+            sunrise,
+            sunset,
+            golden_morning: golden.map(|(morning, _)| morning),
+            golden_evening: golden.map(|(_, evening)| evening),
+        })
+    }
+}
+
+/// Sunrise and sunset in UTC on `date` after NOAA's general solar position equations.
+/// Returns `None` during polar day or night.
+pub fn sun_times(
+    date: NaiveDate,
+    latitude: f64,
+    longitude: f64,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    // 90.833° accounts for refraction and the size of the solar disk
+    crossings(
+        date,
+        Location {
+            latitude,
+            longitude,
+        },
+        90.833,
+    )
+}
+
+/// When the sun's center passes `zenith` degrees from straight up, rising and setting
+fn crossings(
+    date: NaiveDate,
+    location: Location,
+    zenith: f64,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let days = if date.leap_year() { 366.0 } else { 365.0 };
+    let gamma = 2.0 * PI / days * (date.ordinal() as f64 - 1.0);
+
+    // Equation of time in minutes and solar declination in radians
+    let eqtime = 229.18
+        * (0.000075 + 0.001868 * gamma.cos()
+            - 0.032077 * gamma.sin()
+            - 0.014615 * (2.0 * gamma).cos()
+            - 0.040849 * (2.0 * gamma).sin());
+    let decl = 0.006918 - 0.399912 * gamma.cos() + 0.070257 * gamma.sin()
+        - 0.006758 * (2.0 * gamma).cos()
+        + 0.000907 * (2.0 * gamma).sin()
+        - 0.002697 * (3.0 * gamma).cos()
+        + 0.00148 * (3.0 * gamma).sin();
+
+    let lat = location.latitude.to_radians();
+    let cos_ha = zenith.to_radians().cos() / (lat.cos() * decl.cos()) - lat.tan() * decl.tan();
+    if !(-1.0..=1.0).contains(&cos_ha) {
+        return None;
+    }
+    let ha = cos_ha.acos().to_degrees();
+
+    let midnight = date.and_hms_opt(0, 0, 0)?.and_utc();
+    let at = |minutes: f64| midnight + Duration::seconds((minutes * 60.0).round() as i64);
+    Some((
+        at(720.0 - 4.0 * (location.longitude + ha) - eqtime),
+        at(720.0 - 4.0 * (location.longitude - ha) - eqtime),
+    ))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoonPhase {
+    New,
+    WaxingCrescent,
+    FirstQuarter,
+    WaxingGibbous,
+    Full,
+    WaningGibbous,
+    LastQuarter,
+    WaningCrescent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Moon {
+    /// Days since the last new moon
+    pub age: f64,
+    /// Lit fraction of the disk, 0 at new moon and 1 at full moon
+    pub illumination: f64,
+    pub phase: MoonPhase,
+}
+impl Moon {
+    /// Mean phase without perturbations, within a few hours of the real one
+    pub fn at(time: DateTime<Utc>) -> Self {
+        // A new moon to count from, 2000-01-06 18:14 UTC
+        let epoch = Utc.with_ymd_and_hms(2000, 1, 6, 18, 14, 0).unwrap();
+        let days = (time - epoch).num_seconds() as f64 / 86_400.0;
+        let age = days.rem_euclid(SYNODIC_MONTH);
+        let fraction = age / SYNODIC_MONTH;
+        let illumination = (1.0 - (2.0 * PI * fraction).cos()) / 2.0;
+
+        // Named phases cover an eighth of the cycle each, centered on their moment
+        let phase = match ((fraction * 8.0).round() as u8) % 8 {
+            0 => MoonPhase::New,
+            1 => MoonPhase::WaxingCrescent,
+            2 => MoonPhase::FirstQuarter,
+            3 => MoonPhase::WaxingGibbous,
+            4 => MoonPhase::Full,
+            5 => MoonPhase::WaningGibbous,
+            6 => MoonPhase::LastQuarter,
+            _ => MoonPhase::WaningCrescent,
+        };
+        Self {
+            age,
+            illumination,
+            phase,
+        }
+    }
+    pub fn waxing(&self) -> bool {
+        self.age < SYNODIC_MONTH / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Timelike;
+
+    const BERLIN: Location = Location {
+        latitude: 52.52,
+        longitude: 13.405,
+    };
+
+    #[test]
+    fn test_sun_times() {
+        // Berlin, summer solstice: sunrise ~02:43, sunset ~19:33 UTC
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let (sunrise, sunset) = sun_times(date, BERLIN.latitude, BERLIN.longitude).unwrap();
+        let minutes = |t: DateTime<Utc>| (t.hour() * 60 + t.minute()) as i32;
+        assert!((minutes(sunrise) - (2 * 60 + 43)).abs() <= 5, "{sunrise}");
+        assert!((minutes(sunset) - (19 * 60 + 33)).abs() <= 5, "{sunset}");
+
+        // Tromsø has midnight sun
+        assert!(sun_times(date, 69.65, 18.96).is_none());
+    }
+
+    #[test]
+    fn test_golden_hour_inside_daylight() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 20).unwrap();
+        let sun = SunTimes::on(date, BERLIN).unwrap();
+        let morning = sun.golden_morning.unwrap();
+        let evening = sun.golden_evening.unwrap();
+        assert!(sun.sunrise < morning && morning < evening && evening < sun.sunset);
+        // Around the equinox the sun climbs 6° in about three quarters of an hour
+        let minutes = (morning - sun.sunrise).num_minutes();
+        assert!((30..=70).contains(&minutes), "{minutes}");
+    }
+
+    #[test]
+    fn test_moon_phases() {
+        let full = Moon::at(Utc.with_ymd_and_hms(2024, 1, 25, 17, 54, 0).unwrap());
+        assert_eq!(full.phase, MoonPhase::Full);
+        assert!(full.illumination > 0.97, "{}", full.illumination);
+
+        let new = Moon::at(Utc.with_ymd_and_hms(2024, 1, 11, 11, 57, 0).unwrap());
+        assert_eq!(new.phase, MoonPhase::New);
+        assert!(new.illumination < 0.03, "{}", new.illumination);
+
+        let quarter = Moon::at(Utc.with_ymd_and_hms(2024, 1, 18, 3, 53, 0).unwrap());
+        assert_eq!(quarter.phase, MoonPhase::FirstQuarter);
+        assert!(quarter.waxing());
+    }
+}
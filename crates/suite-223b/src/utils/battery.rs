@@ -0,0 +1,229 @@
+//! Reads the battery wherever the system exposes it: sysfs on Linux, `acpiconf` on FreeBSD and
+//! `apm` on OpenBSD. The source is picked once, by what exists on the running system.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+};
+
+use crate::{
+    protocol::BatteryState,
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+const SYSFS_BATTERY: &str = "/sys/class/power_supply/BAT0";
+const ACPICONF: &str = "/usr/sbin/acpiconf";
+const APM: &str = "/usr/sbin/apm";
+
+#[derive(Debug, Clone)]
+pub enum BatterySource {
+    /// `status` and `capacity` of a power supply
+    Sysfs(PathBuf),
+    /// `acpiconf -i 0`
+    Acpiconf,
+    /// `apm -l`, `apm -b` and `apm -a`
+    Apm,
+}
+impl BatterySource {
+    /// The source of this system, `None` without a battery
+    pub fn get() -> Option<&'static Self> {
+        static SOURCE: OnceLock<Option<BatterySource>> = OnceLock::new();
+        SOURCE.get_or_init(Self::detect).as_ref()
+    }
+
+    fn detect() -> Option<Self> {
+        let sysfs = PathBuf::from(SYSFS_BATTERY);
+        if sysfs.exists() {
+            return Some(Self::Sysfs(sysfs));
+        }
+        // FreeBSD ships apm too, but only as a compatibility shim
+        if Path::new(ACPICONF).exists() {
+            return Some(Self::Acpiconf);
+        }
+        if Path::new(APM).exists() {
+            return Some(Self::Apm);
+        }
+        None
+    }
+
+    /// Current state and charge in percent
+    pub fn read(&self) -> Result<(BatteryState, u32), WatsonError> {
+        match self {
+            Self::Sysfs(path) => {
+                let read = |name: &str| {
+                    std::fs::read_to_string(path.join(name))
+                        .map_err(|e| watson_err!(WatsonErrorKind::FileOpen, e.to_string()))
+                };
+                let capacity = read("capacity")?
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+                Ok((parse_sysfs_status(&read("status")?), capacity))
+            }
+            Self::Acpiconf => {
+                let output = run(ACPICONF, &["-i", "0"])?;
+                parse_acpiconf(&output).ok_or_else(|| unexpected(ACPICONF, &output))
+            }
+            Self::Apm => {
+                let (life, battery, ac) =
+                    (run(APM, &["-l"])?, run(APM, &["-b"])?, run(APM, &["-a"])?);
+                parse_apm(&life, &battery, &ac).ok_or_else(|| unexpected(APM, &life))
+            }
+        }
+    }
+
+    /// `read` on a blocking thread, for async code. The BSD sources wait for subprocesses.
+    pub async fn poll(&'static self) -> Result<(BatteryState, u32), WatsonError> {
+        tokio::task::spawn_blocking(|| self.read())
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::TaskJoin, e.to_string()))?
+    }
+
+    /// The charge in percent the firmware stops at, `None` if it charges to full
+    pub fn charge_limit(&self) -> Option<u32> {
+        match self {
+            Self::Sysfs(path) => std::fs::read_to_string(path.join("charge_control_end_threshold"))
+                .ok()
+                .and_then(|s| parse_charge_limit(&s)),
+            Self::Acpiconf | Self::Apm => None,
+        }
+    }
+}
+
+/// Current state and charge of the battery
+pub fn read_battery() -> Result<(BatteryState, u32), WatsonError> {
+    source()?.read()
+}
+
+/// `read_battery` for async code, see `BatterySource::poll`
+pub async fn poll_battery() -> Result<(BatteryState, u32), WatsonError> {
+    source()?.poll().await
+}
+
+fn source() -> Result<&'static BatterySource, WatsonError> {
+    BatterySource::get().ok_or_else(|| watson_err!(WatsonErrorKind::FileExist, "No battery found"))
+}
+
+fn run(program: &str, args: &[&str]) -> Result<String, WatsonError> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, e.to_string()))?;
+    if !output.status.success() {
+        return Err(watson_err!(
+            WatsonErrorKind::CommandExecute,
+            "{} exited with {}",
+            program,
+            output.status
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn unexpected(program: &str, output: &str) -> WatsonError {
+    watson_err!(
+        WatsonErrorKind::Deserialize,
+        format!("Unexpected {} output: {}", program, output.trim())
+    )
+}
+
+fn parse_sysfs_status(status: &str) -> BatteryState {
+    match status.trim().to_lowercase().as_str() {
+        "discharging" => BatteryState::Discharging,
+        "full" => BatteryState::Full,
+        "charging" => BatteryState::Charging,
+        _ => BatteryState::Invalid,
+    }
+}
+
+/// A threshold of 100 is the same as none
+fn parse_charge_limit(threshold: &str) -> Option<u32> {
+    threshold
+        .trim()
+        .parse::<u32>()
+        .ok()
+        .filter(|limit| (1..100).contains(limit))
+}
+
+/// `State:` and `Remaining capacity:` of `acpiconf -i 0`
+fn parse_acpiconf(output: &str) -> Option<(BatteryState, u32)> {
+    let field = |key: &str| {
+        output
+            .lines()
+            .find_map(|l| l.strip_prefix(key))
+            .map(str::trim)
+    };
+    let capacity = field("Remaining capacity:")?
+        .trim_end_matches('%')
+        .parse::<u32>()
+        .ok()?;
+    // e.g. `discharging critical`, or `high` on AC
+    let state = field("State:")?;
+    let state = if state.contains("discharging") {
+        BatteryState::Discharging
+    } else if state.contains("charging") {
+        BatteryState::Charging
+    } else if state.contains("high") {
+        BatteryState::Full
+    } else {
+        BatteryState::Invalid
+    };
+    Some((state, capacity))
+}
+
+/// Outputs of `apm -l` (percent), `apm -b` (3 is charging, 4 no battery) and `apm -a` (1 is on AC)
+fn parse_apm(life: &str, battery: &str, ac: &str) -> Option<(BatteryState, u32)> {
+    // -1 while unknown
+    let capacity = life.trim().parse::<u32>().ok()?;
+    let state = match (battery.trim(), ac.trim()) {
+        ("4", _) => return None,
+        ("3", _) => BatteryState::Charging,
+        (_, "1") => BatteryState::Full,
+        (_, "0") => BatteryState::Discharging,
+        _ => BatteryState::Invalid,
+    };
+    Some((state, capacity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_acpiconf() {
+        let output = "Design capacity:\t4400 mAh\n\
+                      State:\t\t\tdischarging\n\
+                      Remaining capacity:\t93%\n\
+                      Remaining time:\t\t2:33\n";
+        assert_eq!(
+            parse_acpiconf(output),
+            Some((BatteryState::Discharging, 93))
+        );
+
+        let output = "State:\t\t\thigh\nRemaining capacity:\t100%\n";
+        assert_eq!(parse_acpiconf(output), Some((BatteryState::Full, 100)));
+        assert_eq!(parse_acpiconf("State:\t\t\tnot present\n"), None);
+    }
+
+    #[test]
+    fn test_parse_apm() {
+        assert_eq!(
+            parse_apm("57\n", "3\n", "1\n"),
+            Some((BatteryState::Charging, 57))
+        );
+        assert_eq!(
+            parse_apm("57\n", "0\n", "0\n"),
+            Some((BatteryState::Discharging, 57))
+        );
+        assert_eq!(parse_apm("-1\n", "4\n", "1\n"), None);
+    }
+
+    #[test]
+    fn test_parse_charge_limit() {
+        assert_eq!(parse_charge_limit("80\n"), Some(80));
+        assert_eq!(parse_charge_limit("100\n"), None);
+        assert_eq!(parse_charge_limit(""), None);
+    }
+}
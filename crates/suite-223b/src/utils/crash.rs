@@ -0,0 +1,224 @@
+use std::{
+    backtrace::Backtrace,
+    collections::VecDeque,
+    fmt::{Debug, Write as _},
+    fs,
+    panic::PanicHookInfo,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use chrono::Local;
+
+use crate::{
+    config::profile::active_profile,
+    utils::{
+        errors::{ResultExt, WatsonError},
+        paths::{get_config_dir, get_state_dir},
+    },
+};
+
+/// Protocol messages kept for the next crash report
+const MESSAGES_KEPT: usize = 32;
+/// Holds the name of the newest report the user was told about
+const SEEN_FILE: &str = ".seen";
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static CONFIG_HASH: OnceLock<String> = OnceLock::new();
+
+/// Remembers that `message` was sent or received. Only the variant name is kept, so reports
+/// don't leak tokens or notification contents.
+pub fn record(direction: &str, message: &impl Debug) {
+    let debug = format!("{:?}", message);
+    let entry = format!(
+        "{} {direction} {}",
+        Local::now().format("%H:%M:%S%.3f"),
+        variant_name(&debug)
+    );
+    let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    if recent.len() == MESSAGES_KEPT {
+        recent.pop_front();
+    }
+    recent.push_back(entry);
+}
+
+/// `Tracked { id: 1, request: .. }` -> `Tracked`
+fn variant_name(debug: &str) -> &str {
+    let end = debug
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(debug.len());
+    &debug[..end]
+}
+
+/// Writes a report to `$XDG_STATE_HOME/watson/crashes` when `binary` panics, then panics as
+/// before
+pub fn install(binary: &'static str, version: &'static str) {
+    let _ = CONFIG_HASH.set(config_hash().unwrap_or_else(|| "none".into()));
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let backtrace = Backtrace::force_capture();
+        // The panic may have happened while recording
+        let recent: Vec<String> = match RECENT.try_lock() {
+            Ok(recent) => recent.iter().cloned().collect(),
+            Err(_) => Vec::new(),
+        };
+        let report = report(binary, version, info, &backtrace, &recent);
+        match write_report(binary, &report) {
+            Ok(path) => eprintln!("Crash report written to {}", path.display()),
+            Err(e) => eprintln!("{:?}", e),
+        }
+        previous(info);
+    }));
+}
+
+fn report(
+    binary: &str,
+    version: &str,
+    info: &PanicHookInfo,
+    backtrace: &Backtrace,
+    recent: &[String],
+) -> String {
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".into());
+    let location = info
+        .location()
+        .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+        .unwrap_or_default();
+
+    let mut report = String::new();
+    let _ = writeln!(report, "binary:      {binary} {version}");
+    let _ = writeln!(report, "time:        {}", Local::now().to_rfc3339());
+    let _ = writeln!(
+        report,
+        "thread:      {}",
+        std::thread::current().name().unwrap_or("unnamed")
+    );
+    let _ = writeln!(
+        report,
+        "profile:     {}",
+        active_profile().unwrap_or("none")
+    );
+    let _ = writeln!(
+        report,
+        "config hash: {}",
+        CONFIG_HASH.get().map_or("unknown", String::as_str)
+    );
+    let _ = writeln!(report, "panic:       {message}");
+    let _ = writeln!(report, "location:    {location}");
+    let _ = writeln!(report, "\nlast messages:");
+    for entry in recent {
+        let _ = writeln!(report, "  {entry}");
+    }
+    let _ = writeln!(report, "\nbacktrace:\n{backtrace}");
+    report
+}
+
+fn write_report(binary: &str, report: &str) -> Result<PathBuf, WatsonError> {
+    let dir = crash_dir()?;
+    let path = dir.join(format!(
+        "{}-{binary}.txt",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    fs::write(&path, report).with_context(|| "Could not write crash report")?;
+    Ok(path)
+}
+
+pub fn crash_dir() -> Result<PathBuf, WatsonError> {
+    let dir = get_state_dir()?.join("crashes");
+    fs::create_dir_all(&dir).with_context(|| "Could not create crash directory")?;
+    Ok(dir)
+}
+
+/// Reports written since the last `mark_seen`, oldest first
+pub fn unseen_reports() -> Result<Vec<PathBuf>, WatsonError> {
+    let dir = crash_dir()?;
+    let seen = fs::read_to_string(dir.join(SEEN_FILE)).unwrap_or_default();
+    Ok(reports_after(&dir, seen.trim()))
+}
+
+/// Reports are named after the time they were written, so the names sort chronologically
+fn reports_after(dir: &Path, seen: &str) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut reports: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "txt"))
+        .filter(|p| p.file_name().is_some_and(|n| *n > *seen))
+        .collect();
+    reports.sort();
+    reports
+}
+
+/// The reports up to `newest` were shown to the user
+pub fn mark_seen(newest: &Path) -> Result<(), WatsonError> {
+    let name = newest
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    fs::write(crash_dir()?.join(SEEN_FILE), name).with_context(|| "Could not mark crash reports")
+}
+
+/// FNV-1a over the names and contents of the config files, telling apart reports from
+/// different configurations
+fn config_hash() -> Option<String> {
+    let dir = get_config_dir().ok()?;
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for path in files {
+        let name = path.file_name()?.as_encoded_bytes().to_vec();
+        let content = fs::read(&path).unwrap_or_default();
+        for byte in name.into_iter().chain(content) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    Some(format!("{hash:016x}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_name() {
+        assert_eq!(variant_name("Ping"), "Ping");
+        assert_eq!(variant_name("Authenticate(\"secret\")"), "Authenticate");
+        assert_eq!(
+            variant_name("Tracked { id: 1, request: SetWifi(true) }"),
+            "Tracked"
+        );
+    }
+
+    #[test]
+    fn test_reports_after() {
+        let dir = std::env::temp_dir().join(format!("watson-crashes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "20260101-120000-watson.txt",
+            "20260102-090000-watson-daemon.txt",
+            "20260103-180000-watson.txt",
+            SEEN_FILE,
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        assert_eq!(reports_after(&dir, "").len(), 3);
+        let unseen = reports_after(&dir, "20260102-090000-watson-daemon.txt");
+        assert_eq!(unseen, [dir.join("20260103-180000-watson.txt")]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
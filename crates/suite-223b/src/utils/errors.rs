@@ -1,3 +1,7 @@
+use std::{error::Error, fmt, panic::Location};
+
+use serde::{Deserialize, Serialize};
+
 #[macro_export]
 macro_rules! watson_err {
     // Case with just a message literal
@@ -5,6 +9,8 @@ macro_rules! watson_err {
         WatsonError {
             kind: $kind,
             message: $msg.into(),
+            hint: None,
+            source: None,
             file: file!(),
             line: line!(),
         }
@@ -14,6 +20,8 @@ macro_rules! watson_err {
         WatsonError {
             kind: $kind,
             message: format!($fmt, $($args)*),
+            hint: None,
+            source: None,
             file: file!(),
             line: line!(),
         }
@@ -24,19 +32,173 @@ macro_rules! watson_err {
 pub struct WatsonError {
     pub kind: WatsonErrorKind,
     pub message: String,
+    /// What the user can do about it, shown alongside the message
+    pub hint: Option<String>,
+    /// The error this one was caused by
+    pub source: Option<Box<dyn Error + Send + Sync + 'static>>,
     pub file: &'static str,
     pub line: u32,
 }
+impl WatsonError {
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
 
-#[derive(Debug)]
+    /// Wraps `self` in an error of the same kind that describes what was being done when it
+    /// happened. The location is the caller's.
+    #[track_caller]
+    pub fn with_context(self, context: impl Into<String>) -> Self {
+        let location = Location::caller();
+        Self {
+            kind: self.kind,
+            message: context.into(),
+            hint: self.hint.clone(),
+            source: Some(Box::new(self)),
+            file: location.file(),
+            line: location.line(),
+        }
+    }
+
+    #[track_caller]
+    fn from_source(kind: WatsonErrorKind, source: impl Error + Send + Sync + 'static) -> Self {
+        let location = Location::caller();
+        Self {
+            kind,
+            message: source.to_string(),
+            hint: None,
+            source: Some(Box::new(source)),
+            file: location.file(),
+            line: location.line(),
+        }
+    }
+}
+
+/// `{}` prints the message, `{:#}` appends the messages of all sources,
+/// e.g. `Could not load credentials: No such file or directory (os error 2)`
+impl fmt::Display for WatsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if f.alternate() {
+            let mut last = self.message.clone();
+            let mut source = self.source();
+            while let Some(e) = source {
+                // Converted errors repeat the message of what they wrap
+                let message = e.to_string();
+                if message != last {
+                    write!(f, ": {message}")?;
+                }
+                last = message;
+                source = e.source();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Error for WatsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|e| e as &(dyn Error + 'static))
+    }
+}
+
+impl From<std::io::Error> for WatsonError {
+    #[track_caller]
+    fn from(e: std::io::Error) -> Self {
+        Self::from_source(WatsonErrorKind::IO, e)
+    }
+}
+
+impl From<serde_json::Error> for WatsonError {
+    #[track_caller]
+    fn from(e: serde_json::Error) -> Self {
+        let kind = match e.classify() {
+            serde_json::error::Category::Io => WatsonErrorKind::IO,
+            _ => WatsonErrorKind::Deserialize,
+        };
+        Self::from_source(kind, e)
+    }
+}
+
+impl From<reqwest::Error> for WatsonError {
+    #[track_caller]
+    fn from(e: reqwest::Error) -> Self {
+        let kind = if e.is_decode() {
+            WatsonErrorKind::Deserialize
+        } else {
+            WatsonErrorKind::Http
+        };
+        Self::from_source(kind, e)
+    }
+}
+
+#[cfg(feature = "daemon")]
+impl From<zbus::Error> for WatsonError {
+    #[track_caller]
+    fn from(e: zbus::Error) -> Self {
+        let kind = match &e {
+            zbus::Error::FDO(fdo) => match **fdo {
+                zbus::fdo::Error::AccessDenied(_)
+                | zbus::fdo::Error::InteractiveAuthorizationRequired(_) => {
+                    WatsonErrorKind::PermissionDenied
+                }
+                _ => WatsonErrorKind::DBusProxyCall,
+            },
+            zbus::Error::InputOutput(_) | zbus::Error::Address(_) | zbus::Error::Handshake(_) => {
+                WatsonErrorKind::DBusConnect
+            }
+            _ => WatsonErrorKind::DBusProxyCall,
+        };
+        Self::from_source(kind, e)
+    }
+}
+
+/// `with_context` for results whose error converts into a `WatsonError`
+pub trait ResultExt<T> {
+    fn with_context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T, WatsonError>;
+}
+impl<T, E: Into<WatsonError>> ResultExt<T> for Result<T, E> {
+    #[track_caller]
+    fn with_context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T, WatsonError> {
+        match self {
+            Ok(value) => Ok(value),
+            Err(e) => Err(e.into().with_context(context())),
+        }
+    }
+}
+
+/// The parts of a `WatsonError` that are sent to clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatsonErrorDto {
+    pub kind: WatsonErrorKind,
+    pub message: String,
+    pub hint: Option<String>,
+}
+impl From<WatsonError> for WatsonErrorDto {
+    fn from(e: WatsonError) -> Self {
+        Self {
+            kind: e.kind,
+            message: format!("{e:#}"),
+            hint: e.hint,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum WatsonErrorKind {
     GoogleAuth,
     GoogleCalendar,
 
     CommandExecute,
+    /// Not on the daemon's command allowlist
+    CommandDenied,
 
     HttpPostRequest,
     HttpGetRequest,
+    /// Any HTTP failure converted from `reqwest::Error`
+    Http,
+    /// The server asked to slow down with `429 Too Many Requests`
+    RateLimited,
     Deserialize,
     Serialize,
 
@@ -48,6 +210,8 @@ pub enum WatsonErrorKind {
 
     IO,
     TaskJoin,
+    /// The daemon didn't answer in time
+    Timeout,
 
     FileOpen,
     FileCreate,
@@ -88,9 +252,44 @@ pub enum WatsonErrorKind {
 
     BluetoothServiceDisabled,
     BacklightNotFound,
+    /// Refused by polkit or the service itself
+    PermissionDenied,
+    /// The compositor offers nothing to follow, automatic do-not-disturb needs Hyprland
+    UnsupportedCompositor,
 
     Audio,
+    /// An IMAP server or the Gmail API refused or failed
+    Mail,
+    /// A plugin failed a request, went away or isn't connected
+    Plugin,
+    /// Home Assistant refused the token or a service call
+    HomeAssistant,
     Todo,
 
     ConfigError,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_chain() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        let e = WatsonError::from(io).with_context("Could not load credentials");
+
+        assert_eq!(e.kind, WatsonErrorKind::IO);
+        assert_eq!(e.to_string(), "Could not load credentials");
+        assert_eq!(format!("{e:#}"), "Could not load credentials: missing");
+        assert!(e.source().is_some());
+    }
+
+    #[test]
+    fn result_context_keeps_kind() {
+        let result: Result<(), serde_json::Error> = serde_json::from_str::<()>("{").map(|_| ());
+        let e = result.with_context(|| "Could not parse").unwrap_err();
+
+        assert_eq!(e.kind, WatsonErrorKind::Deserialize);
+        assert_eq!(e.line, line!() - 3);
+    }
+}
@@ -1,2 +1,5 @@
+pub mod astronomy;
+pub mod battery;
+pub mod crash;
 pub mod errors;
 pub mod paths;
@@ -4,7 +4,7 @@ use std::{
 };
 
 use crate::{
-    utils::errors::{WatsonError, WatsonErrorKind},
+    utils::errors::{ResultExt, WatsonError, WatsonErrorKind},
     watson_err,
 };
 
@@ -43,7 +43,7 @@ pub fn get_config_dir() -> Result<PathBuf, WatsonError> {
     let dir = xdg_dirs
         .get_config_home()
         .ok_or_else(|| watson_err!(WatsonErrorKind::DirRead, "Could not find config directory"))?;
-    fs::create_dir_all(&dir).map_err(|e| watson_err!(WatsonErrorKind::DirCreate, e.to_string()))?;
+    fs::create_dir_all(&dir).with_context(|| "Could not create config directory")?;
     Ok(dir)
 }
 
@@ -61,12 +61,7 @@ pub fn get_data_dir() -> Result<PathBuf, WatsonError> {
     let dir = xdg_dirs
         .get_data_home()
         .ok_or_else(|| watson_err!(WatsonErrorKind::DirRead, "Could not find data directory"))?;
-    fs::create_dir_all(&dir).map_err(|_| {
-        watson_err!(
-            WatsonErrorKind::DirCreate,
-            "Could not create data directory"
-        )
-    })?;
+    fs::create_dir_all(&dir).with_context(|| "Could not create data directory")?;
     Ok(dir)
 }
 
@@ -79,6 +74,29 @@ pub fn get_cache_dir() -> Result<PathBuf, WatsonError> {
     let dir = xdg_dirs
         .get_cache_home()
         .ok_or_else(|| watson_err!(WatsonErrorKind::DirRead, "Could not find cache directory"))?;
-    fs::create_dir_all(&dir).map_err(|e| watson_err!(WatsonErrorKind::DirCreate, e.to_string()))?;
+    fs::create_dir_all(&dir).with_context(|| "Could not create cache directory")?;
+    Ok(dir)
+}
+
+/// Returns the state directory, `$XDG_STATE_HOME/watson`, for logs and crash reports.
+/// If the directory does not exist, it will be created.
+pub fn get_state_dir() -> Result<PathBuf, WatsonError> {
+    let xdg_dirs = get_xdg_dirs();
+    let dir = xdg_dirs
+        .get_state_home()
+        .ok_or_else(|| watson_err!(WatsonErrorKind::DirRead, "Could not find state directory"))?;
+    fs::create_dir_all(&dir).with_context(|| "Could not create state directory")?;
+    Ok(dir)
+}
+
+/// Returns the runtime directory, `$XDG_RUNTIME_DIR/watson`, which only the user can access.
+/// Falls back to the cache directory without a runtime directory.
+/// If the directory does not exist, it will be created.
+pub fn get_runtime_dir() -> Result<PathBuf, WatsonError> {
+    let Some(runtime) = std::env::var_os("XDG_RUNTIME_DIR") else {
+        return get_cache_dir();
+    };
+    let dir = PathBuf::from(runtime).join("watson");
+    fs::create_dir_all(&dir).with_context(|| "Could not create runtime directory")?;
     Ok(dir)
 }
@@ -0,0 +1,162 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use suite_223b::{
+    protocol::{FocusState, Request, Response, SocketData, StateSnapshot, SystemStateRaw},
+    tokio::{AsyncSizedMessage, SizedMessageObj, decode_sized},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::net::{
+    UnixStream,
+    unix::{OwnedReadHalf, OwnedWriteHalf},
+};
+
+/// One connection to the daemon's socket
+pub struct Connection {
+    stream: UnixStream,
+}
+impl Connection {
+    /// Connects to the daemon of this display, see `SocketData::discover`
+    pub async fn connect() -> Result<Self, WatsonError> {
+        Self::connect_to(&SocketData::discover()?).await
+    }
+    /// Connects to the socket at `path`. Authenticates with the daemon's token if it asks for
+    /// one, which `Request::Command` needs.
+    pub async fn connect_to(path: &Path) -> Result<Self, WatsonError> {
+        let mut stream = UnixStream::connect(path)
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
+
+        // Only exists if the daemon requires it for commands
+        if let Some(token) = SocketData::token_path()
+            .ok()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+        {
+            let msg = SizedMessageObj::from_struct(&Request::Authenticate(token))?;
+            stream.write_sized(msg).await?;
+        }
+        Ok(Self { stream })
+    }
+
+    pub async fn send(&mut self, request: &Request) -> Result<(), WatsonError> {
+        let msg = SizedMessageObj::from_struct(request)?;
+        self.stream.write_sized(msg).await
+    }
+    /// The next answer or broadcast
+    pub async fn recv(&mut self) -> Result<Response, WatsonError> {
+        let buf = self.stream.read_sized().await?;
+        decode_sized(&buf)
+    }
+
+    /// Sends `request` and waits for the response `f` accepts. Broadcasts arriving before it are
+    /// skipped, including `Response::Error`, which can't be told apart from errors of other
+    /// requests. Use [`Connection::call`] to hear about failures.
+    pub async fn ask<T>(
+        &mut self,
+        request: &Request,
+        mut f: impl FnMut(Response) -> Option<T>,
+    ) -> Result<T, WatsonError> {
+        self.send(request).await?;
+        loop {
+            if let Some(value) = f(self.recv().await?) {
+                return Ok(value);
+            }
+        }
+    }
+    /// Sends `request` as `Request::Tracked` and waits until the daemon handled it. Most changes
+    /// aren't answered otherwise.
+    pub async fn call(&mut self, request: Request) -> Result<(), WatsonError> {
+        let id = next_id();
+        let tracked = Request::Tracked {
+            id,
+            request: Box::new(request),
+        };
+        let error = self
+            .ask(&tracked, |r| match r {
+                Response::Ack { id: acked, error } if acked == id => Some(error),
+                _ => None,
+            })
+            .await?;
+        match error {
+            Some(e) => Err(watson_err!(e.kind, e.message)),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn ping(&mut self) -> Result<(), WatsonError> {
+        self.ask(&Request::Ping, |r| {
+            matches!(r, Response::Pong).then_some(())
+        })
+        .await
+    }
+    /// Toggles, brightness, volume and connectivity
+    pub async fn system_state(&mut self) -> Result<SystemStateRaw, WatsonError> {
+        self.ask(&Request::SystemState, |r| match r {
+            Response::SystemState(state) => Some(state),
+            _ => None,
+        })
+        .await
+    }
+    /// Everything the daemon knows, what `watson dump-state` writes
+    pub async fn dump_state(&mut self) -> Result<StateSnapshot, WatsonError> {
+        self.ask(&Request::DumpState, |r| match r {
+            Response::StateSnapshot(snapshot) => Some(*snapshot),
+            _ => None,
+        })
+        .await
+    }
+    pub async fn focus(&mut self) -> Result<FocusState, WatsonError> {
+        self.ask(&Request::Focus, |r| match r {
+            Response::Focus(state) => Some(state),
+            _ => None,
+        })
+        .await
+    }
+
+    pub(crate) fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        self.stream.into_split()
+    }
+}
+
+/// Ids for `Request::Tracked`, unique within the process
+pub(crate) fn next_id() -> u32 {
+    static NEXT: AtomicU32 = AtomicU32::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn test_call_skips_errors_of_other_requests() {
+        let path = std::env::temp_dir().join(format!("watson-call-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let daemon = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            loop {
+                let buf = stream.read_sized().await.unwrap();
+                let Request::Tracked { id, .. } = decode_sized::<Request>(&buf).unwrap() else {
+                    continue;
+                };
+                let other = watson_err!(WatsonErrorKind::Timeout, "Some other request failed");
+                for response in [Response::from(other), Response::Ack { id, error: None }] {
+                    let msg = SizedMessageObj::from_struct(&response).unwrap();
+                    stream.write_sized(msg).await.unwrap();
+                }
+                break;
+            }
+        });
+
+        let mut conn = Connection::connect_to(&path).await.unwrap();
+        assert!(conn.call(Request::Ping).await.is_ok());
+        daemon.await.unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,455 @@
+use std::{
+    collections::HashSet,
+    mem::{Discriminant, discriminant},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use suite_223b::{
+    protocol::{Request, Response, SocketData},
+    tokio::{AsyncSizedMessage, SizedMessageObj, decode_sized},
+    utils::{
+        crash,
+        errors::{WatsonError, WatsonErrorKind},
+    },
+    watson_err,
+};
+use tokio::{
+    net::unix::{OwnedReadHalf, OwnedWriteHalf},
+    sync::{broadcast, mpsc, watch},
+};
+
+use crate::Connection;
+
+/// How long a tracked request may take before it fails with `WatsonErrorKind::Timeout`
+pub const ACK_TIMEOUT: Duration = Duration::from_secs(5);
+/// [`ACK_TIMEOUT`] of requests that may wait for the user to type a polkit password, or for a
+/// command whose output is captured
+pub const SLOW_ACK_TIMEOUT: Duration = Duration::from_secs(120);
+/// Wait before reconnecting, doubled after each failed attempt
+const RECONNECT_DELAY: Duration = Duration::from_millis(250);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Responses a subscriber may fall behind before it misses some
+const RESPONSE_BUFFER: usize = 64;
+
+type InFlight = Arc<Mutex<HashSet<u32>>>;
+
+/// Keeps a connection to the daemon in the background, see [`Engine::spawn`]
+pub struct Engine {
+    connection: Connection,
+    path: PathBuf,
+}
+impl Engine {
+    /// Connects to the daemon of this display, see `SocketData::discover`
+    pub async fn connect() -> Result<Self, WatsonError> {
+        Self::connect_to(SocketData::discover()?).await
+    }
+    pub async fn connect_to(path: PathBuf) -> Result<Self, WatsonError> {
+        Ok(Self {
+            connection: Connection::connect_to(&path).await?,
+            path,
+        })
+    }
+
+    /// Runs the connection on the tokio runtime until every handle is dropped.
+    ///
+    /// When the daemon goes away the engine reconnects, sending `Request::RegisterServices` and
+    /// `Request::TraceProtocol` again, which the daemon keeps per connection. Requests queued in
+    /// the meantime go out once it's back.
+    pub fn spawn(self) -> EngineHandle {
+        let (requests, request_rx) = mpsc::unbounded_channel();
+        let (responses, _) = broadcast::channel(RESPONSE_BUFFER);
+        let (connected_tx, connected) = watch::channel(true);
+        tokio::spawn(self.run(request_rx, responses.clone(), connected_tx));
+        EngineHandle {
+            requests,
+            responses,
+            connected,
+        }
+    }
+
+    async fn run(
+        self,
+        mut requests: mpsc::UnboundedReceiver<Request>,
+        responses: broadcast::Sender<Response>,
+        connected: watch::Sender<bool>,
+    ) {
+        // Tracked requests the daemon hasn't acknowledged yet
+        let in_flight: InFlight = Arc::new(Mutex::new(HashSet::new()));
+        // The latest request of each kind the daemon keeps per connection
+        let mut session: Vec<Request> = Vec::new();
+
+        let mut connection = Some(self.connection);
+        loop {
+            let conn = match connection.take() {
+                Some(conn) => conn,
+                None => match reconnect(&self.path, &requests).await {
+                    Some(conn) => conn,
+                    None => return,
+                },
+            };
+            let (reader, mut writer) = conn.into_split();
+            let mut replay_failed = false;
+            for request in &session {
+                if write(&mut writer, request, &in_flight, &responses)
+                    .await
+                    .is_err()
+                {
+                    replay_failed = true;
+                    break;
+                }
+            }
+            if replay_failed {
+                continue;
+            }
+            connected.send_replace(true);
+
+            let mut reading = tokio::spawn(read(reader, responses.clone(), Arc::clone(&in_flight)));
+            loop {
+                tokio::select! {
+                    // The daemon hung up
+                    _ = &mut reading => break,
+                    request = requests.recv() => {
+                        let Some(request) = request else {
+                            reading.abort();
+                            return;
+                        };
+                        let written = write_coalesced(
+                            request,
+                            &mut requests,
+                            &mut writer,
+                            &mut session,
+                            &in_flight,
+                            &responses,
+                        )
+                        .await;
+                        // The caller of a lost tracked request was told by `write`, everyone
+                        // else through `connected`
+                        if written.is_err() {
+                            reading.abort();
+                            break;
+                        }
+                    }
+                }
+            }
+            connected.send_replace(false);
+        }
+    }
+}
+
+/// Sends `first` and everything queued behind it. A value setter followed by one for the same
+/// target is replaced by it, so dragging a slider only sends the latest value.
+async fn write_coalesced(
+    first: Request,
+    requests: &mut mpsc::UnboundedReceiver<Request>,
+    writer: &mut OwnedWriteHalf,
+    session: &mut Vec<Request>,
+    in_flight: &InFlight,
+    responses: &broadcast::Sender<Response>,
+) -> Result<(), WatsonError> {
+    let mut latest = first;
+    while let Ok(next) = requests.try_recv() {
+        if setter_target(next.untracked())
+            .is_some_and(|t| Some(t) == setter_target(latest.untracked()))
+        {
+            // Replaced before it was sent, nothing to roll back
+            if let Request::Tracked { id, .. } = latest {
+                let _result = responses.send(Response::Ack { id, error: None });
+            }
+        } else {
+            remember(session, &latest);
+            write(writer, &latest, in_flight, responses).await?;
+        }
+        latest = next;
+    }
+    remember(session, &latest);
+    write(writer, &latest, in_flight, responses).await
+}
+
+/// What a setter sets, `None` for requests that must all reach the daemon. Setters of different
+/// devices or entities are different targets.
+fn setter_target(request: &Request) -> Option<(Discriminant<Request>, Option<&str>)> {
+    let target = match request {
+        Request::SetBacklight { device, .. } => device.as_deref(),
+        Request::SetHomeAssistantEntity { entity, .. } => Some(entity.as_str()),
+        Request::SetVolume(_)
+        | Request::SetNightLight(_)
+        | Request::SetNightLightIntensity(_)
+        | Request::SetWifi(_)
+        | Request::SetBluetooth(_)
+        | Request::SetPowerMode(_)
+        | Request::Silence(_)
+        | Request::SetAutoDnd(_)
+        | Request::SetFocus(_) => None,
+        _ => return None,
+    };
+    Some((discriminant(request), target))
+}
+
+fn remember(session: &mut Vec<Request>, request: &Request) {
+    let request = request.untracked();
+    if !matches!(
+        request,
+        Request::RegisterServices(_) | Request::TraceProtocol
+    ) {
+        return;
+    }
+    session.retain(|r| discriminant(r) != discriminant(request));
+    session.push(request.clone());
+}
+
+async fn write(
+    writer: &mut OwnedWriteHalf,
+    request: &Request,
+    in_flight: &InFlight,
+    responses: &broadcast::Sender<Response>,
+) -> Result<(), WatsonError> {
+    let buf = SizedMessageObj::from_struct(request)?;
+    track_ack(request, in_flight, responses);
+    crash::record("out", request.untracked());
+    let written = writer.write_sized(buf).await;
+    // Fails a tracked request right away instead of after its timeout
+    if written.is_err()
+        && let Request::Tracked { id, .. } = *request
+        && in_flight.lock().is_ok_and(|mut f| f.remove(&id))
+    {
+        let error = watson_err!(
+            WatsonErrorKind::StreamWrite,
+            "Lost the connection to the daemon"
+        );
+        let _result = responses.send(Response::Ack {
+            id,
+            error: Some(error.into()),
+        });
+    }
+    written
+}
+
+/// Forwards everything the daemon sends until it hangs up
+async fn read(
+    mut reader: OwnedReadHalf,
+    responses: broadcast::Sender<Response>,
+    in_flight: InFlight,
+) {
+    while let Ok(buf) = reader.read_sized().await {
+        let Ok(response) = decode_sized::<Response>(&buf) else {
+            continue;
+        };
+        crash::record("in", &response);
+        // Acknowledgments after the timeout were already rolled back
+        if let Response::Ack { id, .. } = response
+            && !in_flight.lock().is_ok_and(|mut f| f.remove(&id))
+        {
+            continue;
+        }
+        let _result = responses.send(response);
+    }
+}
+
+/// Connects again with growing delays. Gives up once every handle is dropped.
+async fn reconnect(path: &Path, requests: &mpsc::UnboundedReceiver<Request>) -> Option<Connection> {
+    let mut delay = RECONNECT_DELAY;
+    loop {
+        tokio::time::sleep(delay).await;
+        if requests.is_closed() {
+            return None;
+        }
+        match Connection::connect_to(path).await {
+            Ok(conn) => return Some(conn),
+            Err(_) => delay = (delay * 2).min(MAX_RECONNECT_DELAY),
+        }
+    }
+}
+
+/// How long the daemon may take to acknowledge `request`
+pub fn ack_timeout(request: &Request) -> Duration {
+    match request.untracked() {
+        Request::SetPowerMode(_)
+        | Request::SetBacklight { device: None, .. }
+        | Request::Command { capture: true, .. } => SLOW_ACK_TIMEOUT,
+        _ => ACK_TIMEOUT,
+    }
+}
+
+/// Fails `request` locally if the daemon doesn't acknowledge it within its `ack_timeout`
+fn track_ack(request: &Request, in_flight: &InFlight, responses: &broadcast::Sender<Response>) {
+    let Request::Tracked { id, .. } = *request else {
+        return;
+    };
+    let timeout = ack_timeout(request);
+    if let Ok(mut f) = in_flight.lock() {
+        f.insert(id);
+    }
+    let in_flight = Arc::clone(in_flight);
+    let responses = responses.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        if in_flight.lock().is_ok_and(|mut f| f.remove(&id)) {
+            let error = watson_err!(
+                WatsonErrorKind::Timeout,
+                "The daemon did not respond in time"
+            );
+            let _result = responses.send(Response::Ack {
+                id,
+                error: Some(error.into()),
+            });
+        }
+    });
+}
+
+/// Talks to a running [`Engine`], cheap to clone
+#[derive(Clone)]
+pub struct EngineHandle {
+    requests: mpsc::UnboundedSender<Request>,
+    responses: broadcast::Sender<Response>,
+    connected: watch::Receiver<bool>,
+}
+impl EngineHandle {
+    /// Queues `request`, false once the engine stopped. Send `Request::Tracked` to be told with
+    /// `Response::Ack` whether it worked.
+    pub fn send(&self, request: Request) -> bool {
+        self.requests.send(request).is_ok()
+    }
+    /// The request queue itself, for frontends that keep a plain sender around
+    pub fn sender(&self) -> mpsc::UnboundedSender<Request> {
+        self.requests.clone()
+    }
+    /// Answers and broadcasts from now on. A subscriber falling behind by more than 64 misses
+    /// the oldest ones.
+    pub fn subscribe(&self) -> broadcast::Receiver<Response> {
+        self.responses.subscribe()
+    }
+    /// Whether the daemon is connected. After it restarted, frontends should ask again for the
+    /// state they show.
+    pub fn connected(&self) -> watch::Receiver<bool> {
+        self.connected.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::UnixListener;
+
+    /// Stands in for one daemon connection, answers pings and hangs up on `Silence(true)`
+    async fn serve(listener: &UnixListener) -> Vec<Request> {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        while let Ok(buf) = stream.read_sized().await {
+            match decode_sized::<Request>(&buf).unwrap() {
+                Request::Authenticate(_) => {}
+                Request::Silence(true) => break,
+                request => {
+                    if let Request::Ping = request {
+                        let pong = SizedMessageObj::from_struct(&Response::Pong).unwrap();
+                        stream.write_sized(pong).await.unwrap();
+                    }
+                    received.push(request);
+                }
+            }
+        }
+        received
+    }
+
+    #[test]
+    fn test_ack_timeout_waits_for_polkit() {
+        let tracked = |request| Request::Tracked {
+            id: 1,
+            request: Box::new(request),
+        };
+        assert_eq!(
+            ack_timeout(&tracked(Request::SetPowerMode(0))),
+            SLOW_ACK_TIMEOUT
+        );
+        let command = Request::Command {
+            cmd: "true".into(),
+            capture: false,
+        };
+        assert_eq!(ack_timeout(&tracked(command)), ACK_TIMEOUT);
+        assert_eq!(ack_timeout(&tracked(Request::Silence(true))), ACK_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_only_setters_of_the_same_target_coalesce() {
+        let (mut reader, writer) = tokio::net::UnixStream::pair().unwrap();
+        let (_, mut writer) = writer.into_split();
+        let (tx, mut requests) = mpsc::unbounded_channel();
+        let (responses, _) = broadcast::channel(8);
+        let in_flight: InFlight = Arc::new(Mutex::new(HashSet::new()));
+        let backlight = |device: &str, percent| Request::SetBacklight {
+            device: Some(device.into()),
+            percent,
+        };
+
+        for request in [
+            Request::DismissNotification(2),
+            Request::SetVolume(10),
+            Request::SetVolume(20),
+            backlight("DP-1", 30),
+            backlight("DP-2", 40),
+        ] {
+            tx.send(request).unwrap();
+        }
+        write_coalesced(
+            Request::DismissNotification(1),
+            &mut requests,
+            &mut writer,
+            &mut Vec::new(),
+            &in_flight,
+            &responses,
+        )
+        .await
+        .unwrap();
+        drop(writer);
+
+        let mut received = Vec::new();
+        while let Ok(buf) = reader.read_sized().await {
+            received.push(format!("{:?}", decode_sized::<Request>(&buf).unwrap()));
+        }
+        assert_eq!(
+            received,
+            [
+                "DismissNotification(1)",
+                "DismissNotification(2)",
+                "SetVolume(20)",
+                "SetBacklight { device: Some(\"DP-1\"), percent: 30 }",
+                "SetBacklight { device: Some(\"DP-2\"), percent: 40 }",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_engine_reconnects_and_replays_services() {
+        let path = std::env::temp_dir().join(format!("watson-engine-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let handle = Engine::connect_to(path.clone()).await.unwrap().spawn();
+        let mut responses = handle.subscribe();
+        let mut connected = handle.connected();
+
+        handle.send(Request::RegisterServices(3));
+        handle.send(Request::Ping);
+        handle.send(Request::Silence(true));
+        let first = serve(&listener).await;
+        assert_eq!(first.len(), 2);
+        assert!(matches!(responses.recv().await, Ok(Response::Pong)));
+
+        // Queued while the daemon is gone
+        connected.wait_for(|c| !c).await.unwrap();
+        handle.send(Request::Ping);
+        let (second, _) = tokio::join!(
+            tokio::time::timeout(Duration::from_secs(5), serve(&listener)),
+            async {
+                connected.wait_for(|c| *c).await.unwrap();
+                handle.send(Request::Silence(true));
+            }
+        );
+        // The daemon forgot the services with the old connection
+        let second = second.unwrap();
+        assert!(matches!(second[0], Request::RegisterServices(3)));
+        assert!(matches!(second[1], Request::Ping));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,42 @@
+//! Async client for the Watson daemon, for frontends other than the GTK one.
+//!
+//! The daemon speaks length-prefixed bincode over a unix socket, see
+//! `suite_223b::protocol::{Request, Response}`. Besides answers, every connection receives
+//! broadcasts such as `Response::Notification` or `Response::SystemState`.
+//!
+//! [`Connection`] is a single connection for scripts and command line tools:
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), watson_client::WatsonError> {
+//! let mut daemon = watson_client::Connection::connect().await?;
+//! let state = daemon.system_state().await?;
+//! println!("volume {}%", state.volume);
+//! daemon.call(watson_client::Request::Silence(true)).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Engine`] keeps a connection for the lifetime of a frontend. It reconnects when the daemon
+//! restarts and fails tracked requests the daemon doesn't acknowledge:
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), watson_client::WatsonError> {
+//! let handle = watson_client::Engine::connect().await?.spawn();
+//! let mut responses = handle.subscribe();
+//! handle.send(watson_client::Request::SystemState);
+//! while let Ok(response) = responses.recv().await {
+//!     println!("{:?}", response);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod connection;
+mod engine;
+
+pub use connection::Connection;
+pub use engine::{ACK_TIMEOUT, Engine, EngineHandle, SLOW_ACK_TIMEOUT, ack_timeout};
+pub use suite_223b::{
+    protocol::{Request, Response},
+    utils::errors::{WatsonError, WatsonErrorKind},
+};
@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{
+        Mutex,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+    },
+};
+use suite_223b::protocol::{InternalMessage, TraceDirection};
+use tokio::sync::mpsc::{self, Receiver, Sender, error::TrySendError};
+
+use crate::core::trace::ProtocolTrace;
+
+/// Fan-out of internal messages to every connected client.
+///
+/// Each client owns a bounded queue. A client that does not drain its queue
+/// loses messages instead of stalling the others, and gets evicted once it
+/// dropped `MAX_CONSECUTIVE_DROPS` messages in a row.
+pub struct ConnectionRegistry {
+    clients: Mutex<HashMap<u64, ClientQueue>>,
+    next_id: AtomicU64,
+    metrics: ConnectionMetrics,
+    trace: ProtocolTrace,
+}
+
+struct ClientQueue {
+    tx: Sender<InternalMessage>,
+    /// In-process consumers such as the D-Bus bridge. Not counted as connected clients.
+    internal: bool,
+    /// Receives the traffic of the other clients, see `Request::TraceProtocol`
+    traced: bool,
+    consecutive_drops: AtomicU32,
+    dropped: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct ConnectionMetrics {
+    pub accepted: AtomicU64,
+    pub broadcast: AtomicU64,
+    pub dropped_messages: AtomicU64,
+    pub evicted_clients: AtomicU64,
+}
+
+/// Handle to a registered client. Unregisters the client when dropped.
+pub struct ClientSlot<'a> {
+    pub id: u64,
+    pub rx: Receiver<InternalMessage>,
+    registry: &'a ConnectionRegistry,
+}
+impl Drop for ClientSlot<'_> {
+    fn drop(&mut self) {
+        self.registry.unregister(self.id);
+    }
+}
+
+impl ConnectionRegistry {
+    pub const QUEUE_SIZE: usize = 64;
+    pub const MAX_CONSECUTIVE_DROPS: u32 = 32;
+
+    pub fn new() -> Self {
+        Self {
+            clients: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            metrics: ConnectionMetrics::default(),
+            trace: ProtocolTrace::default(),
+        }
+    }
+
+    pub fn register(&self) -> ClientSlot<'_> {
+        self.register_inner(false)
+    }
+
+    /// Registers an in-process consumer that receives every broadcast but doesn't keep
+    /// services alive once all socket clients are gone.
+    pub fn register_internal(&self) -> ClientSlot<'_> {
+        self.register_inner(true)
+    }
+
+    fn register_inner(&self, internal: bool) -> ClientSlot<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::channel(Self::QUEUE_SIZE);
+        let queue = ClientQueue {
+            tx,
+            internal,
+            traced: false,
+            consecutive_drops: AtomicU32::new(0),
+            dropped: AtomicU64::new(0),
+        };
+
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.insert(id, queue);
+        }
+        if !internal {
+            self.metrics.accepted.fetch_add(1, Ordering::Relaxed);
+        }
+
+        ClientSlot {
+            id,
+            rx,
+            registry: self,
+        }
+    }
+
+    pub fn unregister(&self, id: u64) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.remove(&id);
+        }
+    }
+
+    /// Queues `msg` for every connected client and returns the number of
+    /// clients it was delivered to.
+    pub fn send(&self, msg: InternalMessage) -> usize {
+        self.trace(None, TraceDirection::Broadcast, &msg);
+        let Ok(mut clients) = self.clients.lock() else {
+            return 0;
+        };
+        self.metrics.broadcast.fetch_add(1, Ordering::Relaxed);
+
+        let mut delivered = 0;
+        clients.retain(|id, client| match client.tx.try_send(msg.clone()) {
+            Ok(_) => {
+                client.consecutive_drops.store(0, Ordering::Relaxed);
+                delivered += 1;
+                true
+            }
+            // Internal consumers are part of the daemon and catch up on their own
+            Err(TrySendError::Full(_)) if client.internal => {
+                client.dropped.fetch_add(1, Ordering::Relaxed);
+                self.metrics
+                    .dropped_messages
+                    .fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(TrySendError::Full(_)) => {
+                client.dropped.fetch_add(1, Ordering::Relaxed);
+                self.metrics
+                    .dropped_messages
+                    .fetch_add(1, Ordering::Relaxed);
+
+                let drops = client.consecutive_drops.fetch_add(1, Ordering::Relaxed) + 1;
+                if drops >= Self::MAX_CONSECUTIVE_DROPS {
+                    // Dropping the sender closes the client's queue, which
+                    // ends its connection task.
+                    eprintln!(
+                        "Evicting unresponsive client {} ({} messages dropped)",
+                        id,
+                        client.dropped.load(Ordering::Relaxed)
+                    );
+                    self.metrics.evicted_clients.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                }
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        });
+
+        delivered
+    }
+
+    /// Prints every message to stderr, for `--trace-protocol`
+    pub fn trace_to_log(&self) {
+        self.trace.set_log(true);
+    }
+
+    /// Mirrors the traffic of the other clients to client `id`
+    pub fn subscribe_trace(&self, id: u64) {
+        if let Ok(mut clients) = self.clients.lock()
+            && let Some(client) = clients.get_mut(&id)
+        {
+            client.traced = true;
+        }
+    }
+
+    /// Records a message of client `client`, or a broadcast without one. Traced clients only
+    /// get what arrives within the rate limit, and nothing if their queue is full.
+    pub fn trace(&self, client: Option<u64>, direction: TraceDirection, message: &dyn Debug) {
+        let Ok(clients) = self.clients.lock() else {
+            return;
+        };
+        let subscribed = clients.values().any(|c| c.traced);
+        if !subscribed && !self.trace.logging() {
+            return;
+        }
+        // The debug client's own traffic isn't mirrored back
+        if client.is_some_and(|id| clients.get(&id).is_some_and(|c| c.traced)) {
+            return;
+        }
+        let Some(event) = self.trace.event(client, direction, message) else {
+            return;
+        };
+        if self.trace.logging() {
+            eprintln!("{}", event);
+        }
+        for queue in clients.values().filter(|c| c.traced) {
+            let _ = queue.tx.try_send(InternalMessage::Trace(event.clone()));
+        }
+    }
+
+    /// Number of connected socket clients
+    pub fn len(&self) -> usize {
+        self.clients
+            .lock()
+            .map(|c| c.values().filter(|q| !q.internal).count())
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn metrics(&self) -> &ConnectionMetrics {
+        &self.metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_internal_queues_are_not_evicted() {
+        let registry = ConnectionRegistry::new();
+        let mut client = registry.register();
+        let mut bridge = registry.register_internal();
+
+        let sends =
+            ConnectionRegistry::QUEUE_SIZE + ConnectionRegistry::MAX_CONSECUTIVE_DROPS as usize;
+        for id in 0..sends as u32 {
+            registry.send(InternalMessage::Notification(id));
+        }
+
+        assert_eq!(
+            registry.metrics().evicted_clients.load(Ordering::Relaxed),
+            1
+        );
+        while client.rx.try_recv().is_ok() {}
+        assert!(client.rx.is_closed());
+        while bridge.rx.try_recv().is_ok() {}
+        assert_eq!(registry.send(InternalMessage::Notification(0)), 1);
+        assert!(bridge.rx.try_recv().is_ok());
+    }
+}
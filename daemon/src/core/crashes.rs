@@ -0,0 +1,52 @@
+use std::{process::Command, sync::Arc};
+
+use suite_223b::{
+    notification::Notification,
+    utils::{
+        crash::{mark_seen, unseen_reports},
+        errors::WatsonError,
+    },
+};
+use tokio::sync::RwLock;
+
+use crate::{notify::NotificationDaemon, utils::command::detach};
+
+/// Tells the user about crash reports written since the last start, offering to open the newest
+pub async fn notify_crash_reports(
+    daemon: Arc<RwLock<NotificationDaemon>>,
+) -> Result<(), WatsonError> {
+    let reports = unseen_reports()?;
+    let Some(newest) = reports.last().cloned() else {
+        return Ok(());
+    };
+
+    let body = match reports.len() {
+        1 => format!("A crash report was written to {}", newest.display()),
+        n => format!(
+            "{n} crash reports were written, the newest to {}",
+            newest.display()
+        ),
+    };
+    let notification = Notification {
+        app_name: "Watson".into(),
+        app_icon: "dialog-warning-symbolic".into(),
+        summary: "Watson crashed".into(),
+        body,
+        actions: vec!["open".into(), "Open report".into()],
+        expire_timeout: -1,
+        ..Default::default()
+    };
+    let report = newest.clone();
+    let on_action = Box::new(move |action: Option<&str>| {
+        if action == Some("open")
+            && let Err(e) = detach(Command::new("xdg-open").arg(&report))
+        {
+            eprintln!("{:?}", e);
+        }
+    });
+    daemon
+        .write()
+        .await
+        .insert_with_action(notification, Arc::downgrade(&daemon), on_action);
+    mark_seen(&newest)
+}
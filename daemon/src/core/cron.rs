@@ -0,0 +1,180 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use suite_223b::{
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+/// How far ahead `Cron::next_after` looks before giving up, e.g. on `0 0 31 2 *`
+const SEARCH_DAYS: i64 = 366 * 5;
+
+/// A five field cron expression: minute, hour, day of month, month and day of week. Fields take
+/// `*`, numbers, ranges (`1-5`), steps (`*/15`, `0-30/10`) and lists of those.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    /// Sunday is 0
+    weekdays: u8,
+    /// Like cron, a day matches either field when both are restricted
+    any_day: bool,
+}
+impl Cron {
+    pub fn parse(expr: &str) -> Result<Self, WatsonError> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(invalid(expr, "expected five fields"));
+        };
+        let any_day = days != "*" && weekdays != "*";
+        // 7 is Sunday too
+        let weekdays = field(weekdays, 0, 7).map_err(|e| invalid(expr, e))?;
+        Ok(Self {
+            minutes: field(minutes, 0, 59).map_err(|e| invalid(expr, e))?,
+            hours: field(hours, 0, 23).map_err(|e| invalid(expr, e))? as u32,
+            days: field(days, 1, 31).map_err(|e| invalid(expr, e))? as u32,
+            months: field(months, 1, 12).map_err(|e| invalid(expr, e))? as u16,
+            weekdays: ((weekdays | weekdays >> 7) & 0x7f) as u8,
+            any_day,
+        })
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        if self.any_day {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// The first matching minute after `after`
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let last = start + Duration::days(SEARCH_DAYS);
+        let mut t = start;
+        while t < last {
+            if !self.matches_day(t.date()) {
+                t = next_day(t)?;
+                continue;
+            }
+            if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if self.minutes & (1 << t.minute()) == 0 {
+                t += Duration::minutes(1);
+                continue;
+            }
+            // Skips times that don't exist during a DST change
+            match Local.from_local_datetime(&t).earliest() {
+                Some(local) if local > after => return Some(local),
+                _ => t += Duration::minutes(1),
+            }
+        }
+        None
+    }
+}
+
+fn next_day(t: NaiveDateTime) -> Option<NaiveDateTime> {
+    t.date().succ_opt()?.and_hms_opt(0, 0, 0)
+}
+
+fn invalid(expr: &str, reason: &str) -> WatsonError {
+    watson_err!(
+        WatsonErrorKind::InvalidData,
+        format!("Invalid cron expression `{}`: {}", expr, reason)
+    )
+}
+
+/// Bit `n` is set if `n` matches
+fn field(spec: &str, min: u32, max: u32) -> Result<u64, &'static str> {
+    let mut bits = 0u64;
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| "invalid step")?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err("step of zero");
+        }
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (number(a)?, number(b)?),
+                None if step > 1 => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if first < min || last > max || first > last {
+            return Err("value out of range");
+        }
+        for n in (first..=last).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+fn number(s: &str) -> Result<u32, &'static str> {
+    s.parse().map_err(|_| "not a number")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn test_next_after() {
+        let every_15 = Cron::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            every_15.next_after(at(2025, 3, 4, 10, 7)),
+            Some(at(2025, 3, 4, 10, 15))
+        );
+
+        // Weekdays at 07:30, from a Friday evening (2025-03-07)
+        let alarm = Cron::parse("30 7 * * 1-5").unwrap();
+        assert_eq!(
+            alarm.next_after(at(2025, 3, 7, 20, 0)),
+            Some(at(2025, 3, 10, 7, 30))
+        );
+
+        // The 1st or any Sunday
+        let either = Cron::parse("0 12 1 * 0").unwrap();
+        assert_eq!(
+            either.next_after(at(2025, 3, 4, 0, 0)),
+            Some(at(2025, 3, 9, 12, 0))
+        );
+        assert_eq!(
+            Cron::parse("0 0 * * 7").unwrap(),
+            Cron::parse("0 0 * * 0").unwrap()
+        );
+        assert_eq!(
+            Cron::parse("0 0 31 2 *")
+                .unwrap()
+                .next_after(at(2025, 1, 1, 0, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        for expr in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+        ] {
+            assert!(Cron::parse(expr).is_err(), "{expr}");
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::{str::FromStr, sync::Arc, sync::Mutex};
+
+use suite_223b::{
+    protocol::{BatteryState, InternalMessage, Surface, SurfaceAction},
+    utils::{
+        battery::poll_battery,
+        errors::{WatsonError, WatsonErrorKind},
+    },
+};
+use tokio::sync::RwLock;
+use zbus::{
+    conn::Builder,
+    fdo, interface,
+    object_server::{InterfaceRef, SignalEmitter},
+};
+
+use crate::{DAEMON_TX, notify::NotificationDaemon, relay_surface};
+
+/// `dev.skxxtz.Watson` mirrors the socket protocol for shells that don't speak it, e.g. a
+/// waybar custom module.
+pub struct WatsonBus {
+    daemon: Arc<RwLock<NotificationDaemon>>,
+    /// Last state reported by UPower, it isn't queried on demand
+    battery: Mutex<(BatteryState, u32)>,
+}
+impl WatsonBus {
+    pub const NAME: &'static str = "dev.skxxtz.Watson";
+    pub const PATH: &'static str = "/dev/skxxtz/Watson";
+}
+
+fn to_fdo(e: WatsonError) -> fdo::Error {
+    let message = format!("{e:#}");
+    match e.kind {
+        WatsonErrorKind::PermissionDenied => fdo::Error::AccessDenied(message),
+        _ => fdo::Error::Failed(message),
+    }
+}
+
+#[interface(name = "dev.skxxtz.Watson")]
+impl WatsonBus {
+    #[zbus(property)]
+    async fn volume(&self) -> fdo::Result<u8> {
+        let mut daemon = self.daemon.write().await;
+        daemon.hardware.get_volume().await.map_err(to_fdo)
+    }
+
+    #[zbus(property)]
+    async fn brightness(&self) -> fdo::Result<u8> {
+        let mut daemon = self.daemon.write().await;
+        daemon.hardware.get_brightness().await.map_err(to_fdo)
+    }
+
+    #[zbus(property)]
+    async fn wifi(&self) -> fdo::Result<bool> {
+        let daemon = self.daemon.read().await;
+        daemon.hardware.get_wifi().await.map_err(to_fdo)
+    }
+
+    #[zbus(property)]
+    async fn bluetooth(&self) -> fdo::Result<bool> {
+        let daemon = self.daemon.read().await;
+        daemon.hardware.get_bluetooth().await.map_err(to_fdo)
+    }
+
+    #[zbus(property)]
+    async fn power_mode(&self) -> fdo::Result<String> {
+        let daemon = self.daemon.read().await;
+        let mode = daemon.hardware.get_powermode().await.map_err(to_fdo)?;
+        Ok(mode.to_string())
+    }
+
+    #[zbus(property)]
+    fn battery_percentage(&self) -> u32 {
+        self.battery.lock().map(|b| b.1).unwrap_or(0)
+    }
+
+    #[zbus(property)]
+    fn battery_state(&self) -> String {
+        self.battery
+            .lock()
+            .map(|b| format!("{:?}", b.0).to_lowercase())
+            .unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    async fn silent(&self) -> bool {
+        self.daemon.read().await.settings.silent
+    }
+
+    /// Notifications received during the current do-not-disturb
+    #[zbus(property)]
+    async fn missed_notifications(&self) -> u32 {
+        self.daemon.read().await.settings.missed()
+    }
+
+    async fn set_volume(&self, percentage: u8) -> fdo::Result<()> {
+        // The audio service broadcasts the new volume, which emits `PropertiesChanged`
+        let mut daemon = self.daemon.write().await;
+        daemon
+            .hardware
+            .set_volume(percentage.min(100))
+            .await
+            .map_err(to_fdo)
+    }
+
+    async fn set_brightness(
+        &self,
+        percentage: u8,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        {
+            let mut daemon = self.daemon.write().await;
+            daemon
+                .hardware
+                .set_brightness(percentage.min(100))
+                .await
+                .map_err(to_fdo)?;
+        }
+        self.brightness_changed(&emitter).await?;
+        Ok(())
+    }
+
+    async fn toggle_wifi(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<bool> {
+        let enabled = {
+            let daemon = self.daemon.read().await;
+            let enabled = !daemon.hardware.get_wifi().await.map_err(to_fdo)?;
+            daemon.hardware.set_wifi(enabled).await.map_err(to_fdo)?;
+            enabled
+        };
+        self.wifi_changed(&emitter).await?;
+        Ok(enabled)
+    }
+
+    async fn toggle_bluetooth(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<bool> {
+        let enabled = {
+            let daemon = self.daemon.read().await;
+            let enabled = !daemon.hardware.get_bluetooth().await.map_err(to_fdo)?;
+            daemon
+                .hardware
+                .set_bluetooth(enabled)
+                .await
+                .map_err(to_fdo)?;
+            enabled
+        };
+        self.bluetooth_changed(&emitter).await?;
+        Ok(enabled)
+    }
+
+    async fn cycle_power_mode(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<String> {
+        let mode = {
+            let daemon = self.daemon.read().await;
+            let mode = !daemon.hardware.get_powermode().await.map_err(to_fdo)?;
+            daemon.hardware.set_powermode(mode).await.map_err(to_fdo)?;
+            mode
+        };
+        self.power_mode_changed(&emitter).await?;
+        Ok(mode.to_string())
+    }
+
+    async fn toggle_silent(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<bool> {
+        let silent = {
+            let mut daemon = self.daemon.write().await;
+            let silent = !daemon.settings.silent;
+            daemon.settings.set_silent(silent);
+            silent
+        };
+        self.silent_changed(&emitter).await?;
+        Ok(silent)
+    }
+
+    /// Shows, hides or toggles a client surface, e.g. `("toggle", "notification-centre")`
+    async fn surface(&self, action: &str, surface: &str) -> fdo::Result<()> {
+        let action = SurfaceAction::from_str(action)
+            .map_err(|_| fdo::Error::InvalidArgs(format!("Unknown action `{action}`")))?;
+        let surface = Surface::from_str(surface)
+            .map_err(|_| fdo::Error::InvalidArgs(format!("Unknown surface `{surface}`")))?;
+        relay_surface(surface, action);
+        Ok(())
+    }
+
+    #[zbus(signal)]
+    async fn notification_received(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        app_name: &str,
+        summary: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn calendar_changed(emitter: &SignalEmitter<'_>, calendar: &str) -> zbus::Result<()>;
+}
+
+/// Serves `dev.skxxtz.Watson` and forwards broadcasts as D-Bus signals
+pub async fn watson_bus_listener(daemon: Arc<RwLock<NotificationDaemon>>) -> zbus::Result<()> {
+    let battery = (
+        BatteryState::Invalid,
+        poll_battery().await.map_or(0, |(_, capacity)| capacity),
+    );
+    let bus = WatsonBus {
+        daemon: Arc::clone(&daemon),
+        battery: Mutex::new(battery),
+    };
+    let conn = Builder::session()?
+        .name(WatsonBus::NAME)?
+        .serve_at(WatsonBus::PATH, bus)?
+        .build()
+        .await?;
+    let iface: InterfaceRef<WatsonBus> = conn.object_server().interface(WatsonBus::PATH).await?;
+
+    let Some(connections) = DAEMON_TX.get() else {
+        return Ok(());
+    };
+    let mut slot = connections.register_internal();
+
+    while let Some(msg) = slot.rx.recv().await {
+        let emitter = iface.signal_emitter();
+        let result = match msg {
+            InternalMessage::VolumeStateChange { .. } => {
+                iface.get().await.volume_changed(emitter).await
+            }
+            InternalMessage::BatteryState { state, percentage } => {
+                let bus = iface.get().await;
+                if let Ok(mut battery) = bus.battery.lock() {
+                    *battery = (state, percentage);
+                }
+                match bus.battery_state_changed(emitter).await {
+                    Ok(_) => bus.battery_percentage_changed(emitter).await,
+                    Err(e) => Err(e),
+                }
+            }
+            InternalMessage::Notification(id) => {
+                let (app_name, summary) = daemon
+                    .read()
+                    .await
+                    .get_by_id(id)
+                    .map(|n| (n.app_name.clone(), n.summary.clone()))
+                    .unwrap_or_default();
+                WatsonBus::notification_received(emitter, id, &app_name, &summary).await
+            }
+            InternalMessage::CalendarChanged { calendar } => {
+                WatsonBus::calendar_changed(emitter, &calendar).await
+            }
+            InternalMessage::MissedNotifications(_) | InternalMessage::DndEnded { .. } => {
+                iface
+                    .get()
+                    .await
+                    .missed_notifications_changed(emitter)
+                    .await
+            }
+            InternalMessage::PowerProfiles(_) => {
+                iface.get().await.power_mode_changed(emitter).await
+            }
+            _ => Ok(()),
+        };
+
+        if let Err(e) = result {
+            eprintln!("{:?}", e);
+        }
+    }
+    Ok(())
+}
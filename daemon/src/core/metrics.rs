@@ -0,0 +1,216 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    sync::{
+        Arc, LazyLock, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use suite_223b::{
+    notification::{HintValue, Notification},
+    protocol::DaemonMetrics,
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    sync::RwLock,
+};
+
+use crate::{DAEMON_TX, notify::NotificationDaemon};
+
+/// Counters that don't belong to a single component
+pub static COUNTERS: LazyLock<Counters> = LazyLock::new(Counters::new);
+
+pub struct Counters {
+    started: Instant,
+    pub notifications_received: AtomicU64,
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+}
+impl Counters {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            notifications_received: AtomicU64::new(0),
+            errors: Mutex::new(BTreeMap::new()),
+        }
+    }
+    /// Counts a failed request of `service`, see `Request::service`
+    pub fn record_error(&self, service: &'static str) {
+        if let Ok(mut errors) = self.errors.lock() {
+            *errors.entry(service).or_default() += 1;
+        }
+    }
+}
+
+/// Heap memory held by a notification, give or take allocator overhead
+fn notification_size(n: &Notification) -> usize {
+    let hints: usize = n
+        .hints
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                HintValue::String(s) => s.capacity(),
+                _ => 0,
+            };
+            key.capacity() + value + size_of::<(String, HintValue)>()
+        })
+        .sum();
+    let actions: usize = n
+        .actions
+        .iter()
+        .map(|a| a.capacity() + size_of::<String>())
+        .sum();
+
+    size_of::<Notification>()
+        + n.app_name.capacity()
+        + n.app_icon.capacity()
+        + n.body.capacity()
+        + n.summary.capacity()
+        + actions
+        + hints
+}
+
+pub fn collect(daemon: &NotificationDaemon) -> DaemonMetrics {
+    let mut metrics = DaemonMetrics {
+        uptime_secs: COUNTERS.started.elapsed().as_secs(),
+        notifications_received: COUNTERS.notifications_received.load(Ordering::Relaxed),
+        notifications_stored: daemon.stored().count(),
+        notification_bytes: daemon.stored().map(notification_size).sum(),
+        errors: COUNTERS
+            .errors
+            .lock()
+            .map(|e| e.iter().map(|(k, v)| (k.to_string(), *v)).collect())
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+    if let Some(connections) = DAEMON_TX.get() {
+        let counters = connections.metrics();
+        metrics.connected_clients = connections.len();
+        metrics.accepted_clients = counters.accepted.load(Ordering::Relaxed);
+        metrics.messages_broadcast = counters.broadcast.load(Ordering::Relaxed);
+        metrics.dropped_messages = counters.dropped_messages.load(Ordering::Relaxed);
+        metrics.evicted_clients = counters.evicted_clients.load(Ordering::Relaxed);
+    }
+    metrics
+}
+
+/// Prometheus text exposition of `metrics`
+pub fn prometheus(metrics: &DaemonMetrics) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+        let _ = writeln!(out, "# HELP watson_{name} {help}");
+        let _ = writeln!(out, "# TYPE watson_{name} {kind}");
+        let _ = writeln!(out, "watson_{name} {value}");
+    };
+    metric(
+        "uptime_seconds",
+        "gauge",
+        "Seconds since the daemon started",
+        metrics.uptime_secs,
+    );
+    metric(
+        "connected_clients",
+        "gauge",
+        "Connected socket clients",
+        metrics.connected_clients as u64,
+    );
+    metric(
+        "accepted_clients_total",
+        "counter",
+        "Accepted socket clients",
+        metrics.accepted_clients,
+    );
+    metric(
+        "messages_broadcast_total",
+        "counter",
+        "Messages sent to all clients",
+        metrics.messages_broadcast,
+    );
+    metric(
+        "dropped_messages_total",
+        "counter",
+        "Messages slow clients missed",
+        metrics.dropped_messages,
+    );
+    metric(
+        "evicted_clients_total",
+        "counter",
+        "Clients disconnected for not reading",
+        metrics.evicted_clients,
+    );
+    metric(
+        "notifications_received_total",
+        "counter",
+        "Notifications received over D-Bus",
+        metrics.notifications_received,
+    );
+    metric(
+        "notifications_stored",
+        "gauge",
+        "Notifications in the history",
+        metrics.notifications_stored as u64,
+    );
+    metric(
+        "notification_bytes",
+        "gauge",
+        "Approximate memory held by the notification history",
+        metrics.notification_bytes as u64,
+    );
+
+    let _ = writeln!(out, "# HELP watson_errors_total Failed requests by service");
+    let _ = writeln!(out, "# TYPE watson_errors_total counter");
+    for (service, count) in &metrics.errors {
+        let _ = writeln!(out, "watson_errors_total{{service=\"{service}\"}} {count}");
+    }
+    out
+}
+
+/// Serves `prometheus` on `127.0.0.1:<port>` for every request
+pub async fn metrics_listener(
+    daemon: Arc<RwLock<NotificationDaemon>>,
+    port: u16,
+) -> Result<(), WatsonError> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamBind, e.to_string()))?;
+
+    loop {
+        let (mut stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
+
+        let body = prometheus(&collect(&*daemon.read().await));
+        tokio::spawn(async move {
+            // The request itself doesn't matter, every path gets the metrics
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus_errors_by_service() {
+        let metrics = DaemonMetrics {
+            messages_broadcast: 12,
+            errors: BTreeMap::from([("hardware".to_string(), 3)]),
+            ..Default::default()
+        };
+        let text = prometheus(&metrics);
+        assert!(text.contains("watson_messages_broadcast_total 12\n"));
+        assert!(text.contains("watson_errors_total{service=\"hardware\"} 3\n"));
+    }
+}
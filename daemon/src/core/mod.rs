@@ -1 +1,12 @@
+pub(crate) mod connections;
+pub(crate) mod crashes;
+pub(crate) mod cron;
+pub(crate) mod dbus;
+pub(crate) mod metrics;
+pub(crate) mod peer;
+pub(crate) mod plugins;
 pub(crate) mod registry;
+pub(crate) mod scheduler;
+pub(crate) mod services;
+pub(crate) mod trace;
+pub(crate) mod watchdog;
@@ -0,0 +1,51 @@
+use std::{
+    fs::{self, OpenOptions, Permissions},
+    io::{Read, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::Path,
+};
+
+use suite_223b::{
+    protocol::SocketData,
+    utils::errors::{ResultExt, WatsonError},
+};
+use tokio::net::UnixStream;
+
+/// True if the process on the other end runs as the same user as the daemon
+pub fn is_owner(stream: &UnixStream) -> bool {
+    match stream.peer_cred() {
+        Ok(cred) => cred.uid() == unsafe { libc::getuid() },
+        Err(e) => {
+            eprintln!("Could not read peer credentials: {}", e);
+            false
+        }
+    }
+}
+
+/// Makes the socket accessible to the owner only
+pub fn restrict_socket(path: &Path) -> Result<(), WatsonError> {
+    fs::set_permissions(path, Permissions::from_mode(0o600))
+        .with_context(|| format!("Could not restrict {}", path.display()))
+}
+
+/// Creates a random token and writes it to `SocketData::token_path`, readable by the owner only
+pub fn create_token() -> Result<String, WatsonError> {
+    let mut bytes = [0u8; 16];
+    fs::File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut bytes))
+        .with_context(|| "Could not generate a token")?;
+    let token: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+
+    let path = SocketData::token_path()?;
+    // The mode only applies to new files
+    let _ = fs::remove_file(&path);
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut f| f.write_all(token.as_bytes()))
+        .with_context(|| format!("Could not write {}", path.display()))?;
+    Ok(token)
+}
@@ -0,0 +1,482 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    process::Stdio,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use suite_223b::{
+    config::profile::load_config_file,
+    notification::Notification,
+    protocol::{InternalMessage, PluginCall, PluginManifest, PluginMessage, SocketData},
+    tokio::{AsyncSizedMessage, SizedMessageObj, decode_sized},
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::get_data_dir,
+    },
+    watson_err,
+};
+use tokio::{
+    net::{UnixListener, UnixStream},
+    process::Command,
+    sync::{RwLock, mpsc, oneshot},
+};
+
+use crate::{
+    DAEMON_TX,
+    core::{
+        peer::{is_owner, restrict_socket},
+        watchdog::{MAX_CRASHES, RestartPolicy},
+    },
+    notify::NotificationDaemon,
+};
+
+const PLUGINS_CONFIG: &str = "plugins";
+const APPROVED_FILE: &str = "approved_plugins.json";
+/// How long a plugin may take to answer `Request::Plugin`
+const CALL_TIMEOUT: Duration = Duration::from_secs(10);
+/// Calls waiting to be written to one plugin
+const PLUGIN_QUEUE: usize = 16;
+
+/// `$XDG_CONFIG_HOME/watson/plugins.json`, plugins the daemon starts and restarts, e.g.
+/// `{ "plugins": [{ "exec": ["watson-hass", "--url", "http://homeassistant.local:8123"] }] }`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct PluginsConfig {
+    plugins: Vec<PluginEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PluginEntry {
+    /// Program followed by its arguments, no shell
+    exec: Vec<String>,
+}
+
+type PendingCall = oneshot::Sender<Result<String, WatsonError>>;
+
+/// Plugins connected on `SocketData::plugin_socket_path` and the calls they still owe
+#[derive(Default)]
+pub struct PluginHost {
+    links: Mutex<HashMap<String, PluginLink>>,
+    /// By call id, with the plugin that owes the answer
+    pending: Mutex<HashMap<u32, (String, PendingCall)>>,
+    approved: Mutex<ApprovedPlugins>,
+    next_call: AtomicU32,
+}
+
+struct PluginLink {
+    manifest: PluginManifest,
+    tx: mpsc::Sender<PluginCall>,
+}
+
+impl PluginHost {
+    /// With the plugins the user approved before
+    pub fn load() -> Self {
+        Self {
+            approved: Mutex::new(ApprovedPlugins::load()),
+            ..Default::default()
+        }
+    }
+
+    /// Manifests of the connected plugins by name
+    pub fn list(&self) -> Vec<PluginManifest> {
+        let Ok(links) = self.links.lock() else {
+            return Vec::new();
+        };
+        let mut manifests: Vec<PluginManifest> =
+            links.values().map(|l| l.manifest.clone()).collect();
+        manifests.sort_by(|a, b| a.name.cmp(&b.name));
+        manifests
+    }
+
+    pub fn is_approved(&self, manifest: &PluginManifest) -> bool {
+        self.approved
+            .lock()
+            .is_ok_and(|a| a.manifests.iter().any(|m| m.covers(manifest)))
+    }
+
+    /// Lets `manifest` in without asking from now on
+    pub fn approve(&self, manifest: PluginManifest) -> Result<(), WatsonError> {
+        let mut approved = self
+            .approved
+            .lock()
+            .map_err(|_| watson_err!(WatsonErrorKind::Plugin, "Approvals are poisoned"))?;
+        approved.manifests.retain(|m| m.name != manifest.name);
+        approved.manifests.push(manifest);
+        approved.save()
+    }
+
+    /// Forwards a client's `Request::Plugin` and waits for the plugin's answer
+    pub async fn call(
+        &self,
+        plugin: &str,
+        request: &str,
+        payload: &str,
+    ) -> Result<String, WatsonError> {
+        let tx = {
+            let links = self
+                .links
+                .lock()
+                .map_err(|_| watson_err!(WatsonErrorKind::Plugin, "Plugins are poisoned"))?;
+            let link = links.get(plugin).ok_or_else(|| {
+                watson_err!(
+                    WatsonErrorKind::Plugin,
+                    format!("Plugin {} is not connected", plugin)
+                )
+            })?;
+            if !link.manifest.requests.iter().any(|r| r == request) {
+                return Err(watson_err!(
+                    WatsonErrorKind::Plugin,
+                    format!("Plugin {} does not provide {}", plugin, request)
+                ));
+            }
+            link.tx.clone()
+        };
+
+        let call = self.next_call.fetch_add(1, Ordering::Relaxed);
+        let (answer_tx, answer) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(call, (plugin.to_string(), answer_tx));
+        }
+        let sent = tx
+            .send(PluginCall::Call {
+                call,
+                request: request.to_string(),
+                payload: payload.to_string(),
+            })
+            .await;
+        if sent.is_err() {
+            self.forget(call);
+            return Err(went_away(plugin));
+        }
+
+        match tokio::time::timeout(CALL_TIMEOUT, answer).await {
+            Ok(Ok(result)) => result,
+            // Dropped when the plugin disconnected
+            Ok(Err(_)) => Err(went_away(plugin)),
+            Err(_) => {
+                self.forget(call);
+                Err(watson_err!(
+                    WatsonErrorKind::Timeout,
+                    format!("Plugin {} did not answer in time", plugin)
+                ))
+            }
+        }
+    }
+
+    fn forget(&self, call: u32) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&call);
+        }
+    }
+
+    /// Hands a plugin's answer to the waiting client. Answers after the timeout are dropped.
+    fn complete(&self, plugin: &str, call: u32, result: Result<String, WatsonError>) {
+        let Ok(mut pending) = self.pending.lock() else {
+            return;
+        };
+        // Plugins can only answer their own calls
+        if pending.get(&call).is_some_and(|(owner, _)| owner == plugin)
+            && let Some((_, answer)) = pending.remove(&call)
+        {
+            let _ = answer.send(result);
+        }
+    }
+
+    /// False if a plugin of the same name is connected already
+    fn attach(&self, manifest: PluginManifest, tx: mpsc::Sender<PluginCall>) -> bool {
+        let Ok(mut links) = self.links.lock() else {
+            return false;
+        };
+        if links.contains_key(&manifest.name) {
+            return false;
+        }
+        links.insert(manifest.name.clone(), PluginLink { manifest, tx });
+        true
+    }
+
+    /// Fails the calls the plugin still owed
+    fn detach(&self, plugin: &str) {
+        if let Ok(mut links) = self.links.lock() {
+            links.remove(plugin);
+        }
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.retain(|_, (owner, _)| owner != plugin);
+        }
+    }
+}
+
+fn went_away(plugin: &str) -> WatsonError {
+    watson_err!(
+        WatsonErrorKind::Plugin,
+        format!("Plugin {} disconnected", plugin)
+    )
+}
+
+/// Manifests the user allowed, kept across restarts
+#[derive(Default)]
+struct ApprovedPlugins {
+    manifests: Vec<PluginManifest>,
+    /// Approvals are asked again after a restart without one
+    path: Option<PathBuf>,
+}
+impl ApprovedPlugins {
+    fn load() -> Self {
+        let path = match get_data_dir() {
+            Ok(dir) => dir.join(APPROVED_FILE),
+            Err(e) => {
+                eprintln!("{:?}", e);
+                return Self::default();
+            }
+        };
+        let manifests = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                eprintln!(
+                    "{:?}",
+                    watson_err!(WatsonErrorKind::Deserialize, e.to_string())
+                );
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            manifests,
+            path: Some(path),
+        }
+    }
+
+    fn save(&self) -> Result<(), WatsonError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let data = serde_json::to_vec_pretty(&self.manifests)
+            .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+        fs::write(path, data).map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))
+    }
+}
+
+/// Why `manifest` can't be let in, if it can't
+fn invalid(manifest: &PluginManifest) -> Option<&'static str> {
+    let valid_name = !manifest.name.is_empty()
+        && manifest
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_name {
+        return Some("Plugin names may only contain letters, digits, - and _");
+    }
+    None
+}
+
+/// Accepts plugins on `SocketData::plugin_socket_path` and starts the ones from `plugins.json`
+pub async fn plugin_listener(daemon: Arc<RwLock<NotificationDaemon>>) -> Result<(), WatsonError> {
+    let path = SocketData::plugin_socket_path()?;
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamBind, e.to_string()))?;
+    restrict_socket(&path)?;
+
+    let config: PluginsConfig = load_config_file(PLUGINS_CONFIG).unwrap_or_else(|e| {
+        eprintln!("{:?}", e);
+        PluginsConfig::default()
+    });
+    for entry in config.plugins {
+        let socket = path.clone();
+        tokio::spawn(async move {
+            if let Err(e) = supervise(entry, socket).await {
+                eprintln!("{:?}", e);
+            }
+        });
+    }
+
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
+        if !is_owner(&stream) {
+            eprintln!("Refused a plugin of another user");
+            continue;
+        }
+        let daemon = Arc::clone(&daemon);
+        tokio::spawn(async move {
+            if let Err(e) = serve_plugin(stream, daemon).await {
+                eprintln!("{:?}", e);
+            }
+        });
+    }
+}
+
+/// Runs a plugin from `plugins.json` and starts it again whenever it crashes
+async fn supervise(entry: PluginEntry, socket: PathBuf) -> Result<(), WatsonError> {
+    let Some((program, args)) = entry.exec.split_first() else {
+        return Ok(());
+    };
+    let mut policy = RestartPolicy::new();
+    loop {
+        let started = Instant::now();
+        let status = Command::new(program)
+            .args(args)
+            .env("WATSON_PLUGIN_SOCKET", &socket)
+            .stdin(Stdio::null())
+            // Ends with the daemon
+            .kill_on_drop(true)
+            .status()
+            .await
+            .map_err(|e| {
+                watson_err!(
+                    WatsonErrorKind::CommandExecute,
+                    format!("Could not start plugin {}: {}", program, e)
+                )
+            })?;
+
+        let Some(delay) = policy.on_exit(status.success(), started.elapsed()) else {
+            if status.success() {
+                return Ok(());
+            }
+            return Err(watson_err!(
+                WatsonErrorKind::Plugin,
+                format!(
+                    "Plugin {} crashed {} times in a row, giving up",
+                    program, MAX_CRASHES
+                )
+            ));
+        };
+        eprintln!(
+            "Plugin {} exited with {}, restarting in {:?}",
+            program, status, delay
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Runs one plugin connection, from the handshake until either side hangs up
+pub async fn serve_plugin(
+    mut stream: UnixStream,
+    daemon: Arc<RwLock<NotificationDaemon>>,
+) -> Result<(), WatsonError> {
+    let manifest = match decode_sized::<PluginMessage>(&stream.read_sized().await?)? {
+        PluginMessage::Hello(manifest) => manifest,
+        _ => return reject(&mut stream, "Expected Hello").await,
+    };
+    if let Some(reason) = invalid(&manifest) {
+        return reject(&mut stream, reason).await;
+    }
+
+    let host = Arc::clone(&daemon.read().await.plugins);
+    if !host.is_approved(&manifest) {
+        if !ask_approval(&daemon, &manifest).await {
+            return reject(&mut stream, "Declined by the user").await;
+        }
+        host.approve(manifest.clone())?;
+    }
+
+    let (tx, mut rx) = mpsc::channel(PLUGIN_QUEUE);
+    if !host.attach(manifest.clone(), tx) {
+        return reject(&mut stream, "A plugin of this name is connected already").await;
+    }
+    let result = relay(&mut stream, &host, &manifest, &mut rx).await;
+    host.detach(&manifest.name);
+    result
+}
+
+async fn relay(
+    stream: &mut UnixStream,
+    host: &PluginHost,
+    manifest: &PluginManifest,
+    rx: &mut mpsc::Receiver<PluginCall>,
+) -> Result<(), WatsonError> {
+    stream
+        .write_sized(SizedMessageObj::from_struct(&PluginCall::Welcome)?)
+        .await?;
+    loop {
+        tokio::select! {
+            result = stream.read_sized() => {
+                let Ok(buf) = result else {
+                    // The plugin hung up
+                    return Ok(());
+                };
+                let Ok(message) = decode_sized::<PluginMessage>(&buf) else {
+                    continue;
+                };
+                match message {
+                    PluginMessage::Reply { call, payload } => {
+                        host.complete(&manifest.name, call, Ok(payload));
+                    }
+                    PluginMessage::Failed { call, message } => {
+                        let error = watson_err!(WatsonErrorKind::Plugin, message);
+                        host.complete(&manifest.name, call, Err(error));
+                    }
+                    PluginMessage::Broadcast { kind, payload } => {
+                        // Only what the user approved reaches the clients
+                        if !manifest.broadcasts.contains(&kind) {
+                            eprintln!("Plugin {} sent undeclared broadcast {}", manifest.name, kind);
+                            continue;
+                        }
+                        let _result = DAEMON_TX.get().map(|d| {
+                            d.send(InternalMessage::PluginEvent {
+                                plugin: manifest.name.clone(),
+                                kind,
+                                payload,
+                            })
+                        });
+                    }
+                    PluginMessage::Hello(_) => {}
+                }
+            }
+            call = rx.recv() => {
+                let Some(call) = call else {
+                    return Ok(());
+                };
+                stream.write_sized(SizedMessageObj::from_struct(&call)?).await?;
+            }
+        }
+    }
+}
+
+async fn reject(stream: &mut UnixStream, reason: &str) -> Result<(), WatsonError> {
+    let msg = SizedMessageObj::from_struct(&PluginCall::Rejected(reason.to_string()))?;
+    stream.write_sized(msg).await
+}
+
+/// Asks the user with a notification whether `manifest` may register, false if it's dismissed
+async fn ask_approval(daemon: &Arc<RwLock<NotificationDaemon>>, manifest: &PluginManifest) -> bool {
+    let list = |items: &[String]| match items.is_empty() {
+        true => "nothing".to_string(),
+        false => items.join(", "),
+    };
+    let body = format!(
+        "{} {} wants to serve {} and broadcast {}",
+        manifest.name,
+        manifest.version,
+        list(&manifest.requests),
+        list(&manifest.broadcasts)
+    );
+    let notification = Notification {
+        app_name: "Watson".into(),
+        app_icon: "application-x-addon-symbolic".into(),
+        summary: "Allow plugin?".into(),
+        body,
+        actions: ["allow", "Allow", "deny", "Deny"].map(Into::into).to_vec(),
+        expire_timeout: 0,
+        ..Default::default()
+    };
+    let (tx, answer) = oneshot::channel();
+    let mut tx = Some(tx);
+    let on_action = Box::new(move |action: Option<&str>| {
+        if let Some(tx) = tx.take() {
+            let _ = tx.send(action == Some("allow"));
+        }
+    });
+    daemon
+        .write()
+        .await
+        .insert_with_action(notification, Arc::downgrade(daemon), on_action);
+    answer.await.unwrap_or(false)
+}
@@ -12,12 +12,15 @@ pub struct ServiceRegistry {
     /// 0. BatteryStateListener
     /// ```
     registered_services: AtomicU8,
+    /// Services that can't run here, e.g. without an audio server. Never active.
+    unavailable_services: AtomicU8,
 }
 #[allow(dead_code)]
 impl ServiceRegistry {
     pub fn new() -> Self {
         Self {
             registered_services: AtomicU8::new(0),
+            unavailable_services: AtomicU8::new(0),
         }
     }
 
@@ -33,11 +36,43 @@ impl ServiceRegistry {
 
     pub fn is_active(&self, service: DaemonService) -> bool {
         let mask = 1 << service as u8;
-        (self.registered_services.load(Ordering::Relaxed) & mask) != 0
+        (self.active() & mask) != 0
     }
 
     pub fn has_any_listeners(&self) -> bool {
-        self.registered_services.load(Ordering::Relaxed) != 0
+        self.active() != 0
+    }
+
+    /// Keeps `service` inactive no matter what clients register
+    pub fn mark_unavailable(&self, service: DaemonService) {
+        self.unavailable_services
+            .fetch_or(1 << service as u8, Ordering::Relaxed);
+    }
+
+    /// Undoes `mark_unavailable`, e.g. after a service was restarted
+    pub fn mark_available(&self, service: DaemonService) {
+        self.unavailable_services
+            .fetch_and(!(1 << service as u8), Ordering::Relaxed);
+    }
+
+    pub fn is_available(&self, service: DaemonService) -> bool {
+        let mask = 1 << service as u8;
+        (self.unavailable_services.load(Ordering::Relaxed) & mask) == 0
+    }
+
+    /// Bits of the services clients registered, see `DaemonService`
+    pub fn registered(&self) -> u8 {
+        self.registered_services.load(Ordering::Relaxed)
+    }
+
+    /// Bits of the services marked unavailable
+    pub fn unavailable(&self) -> u8 {
+        self.unavailable_services.load(Ordering::Relaxed)
+    }
+
+    fn active(&self) -> u8 {
+        self.registered_services.load(Ordering::Relaxed)
+            & !self.unavailable_services.load(Ordering::Relaxed)
     }
 
     pub fn set_registered_services(&self, services: u8) {
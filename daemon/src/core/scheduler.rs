@@ -0,0 +1,427 @@
+use std::{
+    collections::HashMap,
+    fs,
+    future::Future,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, Utc};
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use suite_223b::{
+    protocol::{InternalMessage, JobSchedule, ScheduledJob},
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::get_data_dir,
+    },
+    watson_err,
+};
+use tokio::sync::Notify;
+
+use crate::{DAEMON_TX, core::cron::Cron};
+
+/// Longest the run loop sleeps, so suspend and clock changes delay jobs by a minute at most
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+const JOBS_FILE: &str = "scheduled_jobs.json";
+
+type Handler = Arc<dyn Fn() -> BoxFuture<'static, ()> + Send + Sync>;
+/// A job to run now, with the flag its handler clears when done
+type Due = (String, Option<Handler>, Arc<AtomicBool>);
+
+struct Job {
+    schedule: JobSchedule,
+    /// Parsed `JobSchedule::Cron`
+    cron: Option<Cron>,
+    next_run: Option<DateTime<Local>>,
+    last_run: Option<DateTime<Local>>,
+    /// The daemon's own jobs run this, jobs added over IPC tell the clients instead
+    handler: Option<Handler>,
+    /// A run that takes longer than the schedule skips the next one
+    running: Arc<AtomicBool>,
+}
+impl Job {
+    fn new(
+        schedule: JobSchedule,
+        handler: Option<Handler>,
+        last_run: Option<DateTime<Local>>,
+    ) -> Result<Self, WatsonError> {
+        let cron = match &schedule {
+            JobSchedule::Cron(expr) => Some(Cron::parse(expr)?),
+            JobSchedule::Every(0) => {
+                return Err(watson_err!(
+                    WatsonErrorKind::InvalidData,
+                    "Interval of zero"
+                ));
+            }
+            _ => None,
+        };
+        let mut job = Self {
+            schedule,
+            cron,
+            next_run: None,
+            last_run,
+            handler,
+            running: Arc::new(AtomicBool::new(false)),
+        };
+        job.next_run = job.next_after(Local::now());
+        Ok(job)
+    }
+
+    /// When to run after running, or being added, at `now`. `None` once a one-shot job ran.
+    fn next_after(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        match &self.schedule {
+            JobSchedule::Cron(_) => self.cron.as_ref()?.next_after(now),
+            JobSchedule::Every(secs) => Some(now + chrono::Duration::seconds(*secs as i64)),
+            JobSchedule::At(at) => self.last_run.is_none().then(|| at.with_timezone(&Local)),
+        }
+    }
+
+    fn info(&self, id: &str) -> ScheduledJob {
+        ScheduledJob {
+            id: id.to_string(),
+            schedule: self.schedule.clone(),
+            next_run: self.next_run.map(|t| t.with_timezone(&Utc)),
+            last_run: self.last_run.map(|t| t.with_timezone(&Utc)),
+            persistent: self.handler.is_none(),
+        }
+    }
+}
+
+/// How a job added over IPC is kept across restarts
+#[derive(Serialize, Deserialize)]
+struct SavedJob {
+    id: String,
+    schedule: JobSchedule,
+    last_run: Option<DateTime<Utc>>,
+}
+
+/// Runs the daemon's periodic work, and the jobs clients add with `Request::ScheduleJob`.
+/// Cron jobs missed while the daemon was down are skipped, one-shot jobs run late. Client jobs
+/// falling due while no client is connected wait for the next one to connect.
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Mutex<HashMap<String, Job>>,
+    /// Wakes the run loop when a job was added or a client connected
+    changed: Notify,
+    /// Where jobs of clients are kept, they are lost on restart without one
+    path: Option<PathBuf>,
+}
+impl Scheduler {
+    /// A scheduler with the client jobs saved before the last restart
+    pub fn load() -> Self {
+        let path = match get_data_dir() {
+            Ok(dir) => dir.join(JOBS_FILE),
+            Err(e) => {
+                eprintln!("{:?}", e);
+                return Self::default();
+            }
+        };
+        let saved: Vec<SavedJob> = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                eprintln!(
+                    "{:?}",
+                    watson_err!(WatsonErrorKind::Deserialize, e.to_string())
+                );
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+
+        let mut jobs = HashMap::new();
+        for saved in saved {
+            let last_run = saved.last_run.map(|t| t.with_timezone(&Local));
+            match Job::new(saved.schedule, None, last_run) {
+                Ok(job) => {
+                    jobs.insert(saved.id, job);
+                }
+                Err(e) => eprintln!("{:?}", e),
+            }
+        }
+        Self {
+            jobs: Mutex::new(jobs),
+            changed: Notify::new(),
+            path: Some(path),
+        }
+    }
+
+    /// Runs `f` on `schedule` for as long as the daemon runs
+    pub fn register<F, Fut>(&self, id: &str, schedule: JobSchedule, f: F) -> Result<(), WatsonError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler: Handler = Arc::new(move || Box::pin(f()));
+        let job = Job::new(schedule, Some(handler), None)?;
+        self.jobs
+            .lock()
+            .expect("Poisoned")
+            .insert(id.to_string(), job);
+        self.changed.notify_one();
+        Ok(())
+    }
+
+    /// Stops running one of the daemon's own jobs
+    pub fn unregister(&self, id: &str) {
+        let mut jobs = self.jobs.lock().expect("Poisoned");
+        if jobs.get(id).is_some_and(|job| job.handler.is_some()) {
+            jobs.remove(id);
+        }
+    }
+
+    /// Adds a client job, replacing the one with the same id
+    pub fn schedule(&self, id: String, schedule: JobSchedule) -> Result<(), WatsonError> {
+        {
+            let mut jobs = self.jobs.lock().expect("Poisoned");
+            if jobs.get(&id).is_some_and(|job| job.handler.is_some()) {
+                return Err(daemon_job(&id));
+            }
+            jobs.insert(id, Job::new(schedule, None, None)?);
+        }
+        self.changed.notify_one();
+        self.save()
+    }
+    pub fn cancel(&self, id: &str) -> Result<(), WatsonError> {
+        {
+            let mut jobs = self.jobs.lock().expect("Poisoned");
+            match jobs.get(id) {
+                Some(job) if job.handler.is_some() => return Err(daemon_job(id)),
+                Some(_) => {
+                    jobs.remove(id);
+                }
+                None => {
+                    return Err(watson_err!(
+                        WatsonErrorKind::InvalidData,
+                        format!("No job `{}`", id)
+                    ));
+                }
+            }
+        }
+        self.save()
+    }
+
+    /// Runs one of the daemon's own jobs now and returns once it finished. Its schedule starts
+    /// over from now, a job that is already running isn't started a second time.
+    pub async fn run_now(&self, id: &str) -> Result<(), WatsonError> {
+        let (handler, running) = {
+            let mut jobs = self.jobs.lock().expect("Poisoned");
+            let Some(job) = jobs.get_mut(id) else {
+                return Err(watson_err!(
+                    WatsonErrorKind::InvalidData,
+                    format!("No job `{}`", id)
+                ));
+            };
+            let Some(handler) = job.handler.clone() else {
+                return Err(watson_err!(
+                    WatsonErrorKind::InvalidData,
+                    format!("`{}` is run by the clients", id)
+                ));
+            };
+            if job.running.swap(true, Ordering::AcqRel) {
+                return Ok(());
+            }
+            let now = Local::now();
+            job.last_run = Some(now);
+            job.next_run = job.next_after(now);
+            (handler, Arc::clone(&job.running))
+        };
+        self.changed.notify_one();
+        handler().await;
+        running.store(false, Ordering::Release);
+        Ok(())
+    }
+
+    /// Hands the client jobs that fell due meanwhile to a client that just connected
+    pub fn client_connected(&self) {
+        self.changed.notify_one();
+    }
+
+    pub fn jobs(&self) -> Vec<ScheduledJob> {
+        let jobs = self.jobs.lock().expect("Poisoned");
+        let mut infos: Vec<ScheduledJob> = jobs.iter().map(|(id, job)| job.info(id)).collect();
+        infos.sort_by(|a, b| a.id.cmp(&b.id));
+        infos
+    }
+
+    fn save(&self) -> Result<(), WatsonError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let saved: Vec<SavedJob> = self
+            .jobs
+            .lock()
+            .expect("Poisoned")
+            .iter()
+            .filter(|(_, job)| job.handler.is_none())
+            .map(|(id, job)| SavedJob {
+                id: id.clone(),
+                schedule: job.schedule.clone(),
+                last_run: job.last_run.map(|t| t.with_timezone(&Utc)),
+            })
+            .collect();
+        let data = serde_json::to_vec_pretty(&saved)
+            .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+        fs::write(path, data).map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))
+    }
+
+    /// Runs jobs as they become due
+    pub async fn run(&self) {
+        loop {
+            let connected = DAEMON_TX.get().is_some_and(|d| !d.is_empty());
+            let (due, clients_ran) = self.take_due(Local::now(), connected);
+            for (id, handler, running) in due {
+                match handler {
+                    Some(handler) => {
+                        tokio::spawn(async move {
+                            handler().await;
+                            running.store(false, Ordering::Release);
+                        });
+                    }
+                    None => {
+                        let _result = DAEMON_TX
+                            .get()
+                            .map(|d| d.send(InternalMessage::JobDue { id }));
+                    }
+                }
+            }
+            if clients_ran && let Err(e) = self.save() {
+                eprintln!("{:?}", e);
+            }
+
+            let wait = self
+                .next_run(connected)
+                .map(|next| (next - Local::now()).to_std().unwrap_or(Duration::ZERO))
+                .unwrap_or(MAX_SLEEP)
+                .min(MAX_SLEEP);
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.changed.notified() => {}
+            }
+        }
+    }
+
+    /// Moves every due job to its next run, returning the ones to run and whether a client's
+    /// job was among them. Client jobs stay due without `connected` clients to tell.
+    fn take_due(&self, now: DateTime<Local>, connected: bool) -> (Vec<Due>, bool) {
+        let mut jobs = self.jobs.lock().expect("Poisoned");
+        let mut due = Vec::new();
+        let mut clients_ran = false;
+        for (id, job) in jobs.iter_mut() {
+            if job.next_run.is_none_or(|next| next > now) || (job.handler.is_none() && !connected) {
+                continue;
+            }
+            job.last_run = Some(now);
+            job.next_run = job.next_after(now);
+            // Cleared by the handler once it finished
+            if job.handler.is_some() && job.running.swap(true, Ordering::AcqRel) {
+                continue;
+            }
+            clients_ran |= job.handler.is_none();
+            due.push((id.clone(), job.handler.clone(), Arc::clone(&job.running)));
+        }
+        // One-shot jobs are done once they ran
+        jobs.retain(|_, job| {
+            job.next_run.is_some()
+                || (job.handler.is_some() && !matches!(job.schedule, JobSchedule::At(_)))
+        });
+        (due, clients_ran)
+    }
+
+    /// The next job `take_due` would return, `client_connected` wakes the loop for the others
+    fn next_run(&self, connected: bool) -> Option<DateTime<Local>> {
+        let jobs = self.jobs.lock().expect("Poisoned");
+        jobs.values()
+            .filter(|job| connected || job.handler.is_some())
+            .filter_map(|job| job.next_run)
+            .min()
+    }
+}
+
+fn daemon_job(id: &str) -> WatsonError {
+    watson_err!(
+        WatsonErrorKind::InvalidData,
+        format!("`{}` is one of the daemon's own jobs", id)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_jobs_run_when_due() {
+        let scheduler = Arc::new(Scheduler::default());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        scheduler
+            .register("tick", JobSchedule::Every(1), move || {
+                let tx = tx.clone();
+                async move {
+                    let _ = tx.send(());
+                }
+            })
+            .unwrap();
+        assert!(scheduler.cancel("tick").is_err());
+
+        tokio::spawn({
+            let scheduler = Arc::clone(&scheduler);
+            async move { scheduler.run().await }
+        });
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("Job did not run");
+    }
+
+    #[test]
+    fn test_client_jobs_wait_for_a_client() {
+        let scheduler = Scheduler::default();
+        let past = Utc::now() - chrono::Duration::minutes(5);
+        scheduler
+            .schedule("alarm".into(), JobSchedule::At(past))
+            .unwrap();
+
+        let (due, clients_ran) = scheduler.take_due(Local::now(), false);
+        assert!(due.is_empty() && !clients_ran);
+        assert_eq!(scheduler.next_run(false), None);
+        assert_eq!(scheduler.jobs().len(), 1);
+
+        // The one-shot job runs once a client is there, and is gone afterwards
+        let (due, clients_ran) = scheduler.take_due(Local::now(), true);
+        assert_eq!(due.len(), 1);
+        assert!(clients_ran);
+        assert!(scheduler.jobs().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_now() {
+        let scheduler = Scheduler::default();
+        let runs = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        scheduler
+            .register("hourly", JobSchedule::Every(3600), {
+                let runs = Arc::clone(&runs);
+                move || {
+                    runs.fetch_add(1, Ordering::Relaxed);
+                    async {}
+                }
+            })
+            .unwrap();
+        scheduler
+            .schedule("alarm".into(), JobSchedule::Every(3600))
+            .unwrap();
+
+        scheduler.run_now("hourly").await.unwrap();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+        let job = scheduler
+            .jobs()
+            .into_iter()
+            .find(|j| j.id == "hourly")
+            .unwrap();
+        assert!(job.last_run.is_some());
+
+        assert!(scheduler.run_now("alarm").await.is_err());
+        assert!(scheduler.run_now("missing").await.is_err());
+    }
+}
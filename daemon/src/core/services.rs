@@ -0,0 +1,372 @@
+use std::{
+    fs,
+    sync::{Arc, Weak},
+};
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use suite_223b::{
+    config::profile::load_config_file,
+    protocol::{DaemonService, InternalMessage, ManagedService},
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::get_config_dir,
+    },
+    watson_err,
+};
+use tokio::{
+    sync::{Notify, RwLock, mpsc},
+    task::AbortHandle,
+};
+
+use crate::{
+    DAEMON_TX,
+    core::metrics::metrics_listener,
+    hardware::{AudioCommand, AudioServer, device_use_listener},
+    notify::NotificationDaemon,
+    software::{
+        CALENDAR_REFRESH_JOB,
+        contacts::{CONTACTS_SYNC_JOB, schedule_contacts_sync},
+        finance::{FINANCE_JOB, schedule_quotes},
+        homeassistant::watch_home_assistant,
+        mail::watch_mail,
+        schedule_calendar_refresh,
+        screen_time::{SCREEN_TIME_JOB, start_screen_time},
+        travel::{TRAVEL_REMINDER_JOB, TravelReminders, schedule_travel_reminders},
+    },
+};
+
+const SERVICES_CONFIG: &str = "services";
+
+/// `$XDG_CONFIG_HOME/watson/services.json`, e.g. `{ "disabled": ["Metrics"] }`. Written by
+/// `Request::SetServiceEnabled`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ServicesConfig {
+    disabled: Vec<ManagedService>,
+}
+
+/// What it takes to stop each running `ManagedService`
+#[derive(Default)]
+pub struct Services {
+    config: ServicesConfig,
+    /// Choices are only written back to the config outside of tests
+    persist: bool,
+    /// For services holding on to the daemon, set by `start_services`
+    daemon: Weak<RwLock<NotificationDaemon>>,
+    metrics_port: Option<u16>,
+    /// Ends the audio actor's thread
+    audio: Option<Arc<Notify>>,
+    calendar_sync: bool,
+    finance: bool,
+    home_assistant: Option<AbortHandle>,
+    metrics: Option<AbortHandle>,
+    mail: Option<AbortHandle>,
+    privacy: Option<AbortHandle>,
+    /// Follows the focused window
+    screen_time: Option<AbortHandle>,
+}
+impl Services {
+    /// With the choices made before the last restart
+    pub fn load() -> Self {
+        let config = load_config_file(SERVICES_CONFIG).unwrap_or_else(|e| {
+            eprintln!("{:?}", e);
+            ServicesConfig::default()
+        });
+        Self {
+            config,
+            persist: true,
+            ..Default::default()
+        }
+    }
+
+    /// With the choices from a state snapshot, which are not written back
+    pub fn restored(disabled: Vec<ManagedService>) -> Self {
+        Self {
+            config: ServicesConfig { disabled },
+            ..Default::default()
+        }
+    }
+
+    /// The daemon itself, for work that runs later. Dangles until `start_services`.
+    pub fn daemon(&self) -> Weak<RwLock<NotificationDaemon>> {
+        self.daemon.clone()
+    }
+
+    pub fn is_enabled(&self, service: ManagedService) -> bool {
+        !self.config.disabled.contains(&service)
+    }
+
+    pub fn disabled(&self) -> &[ManagedService] {
+        &self.config.disabled
+    }
+
+    fn is_running(&self, service: ManagedService) -> bool {
+        match service {
+            ManagedService::Audio => self.audio.is_some(),
+            ManagedService::CalendarSync => self.calendar_sync,
+            ManagedService::Finance => self.finance,
+            ManagedService::HomeAssistant => self
+                .home_assistant
+                .as_ref()
+                .is_some_and(|t| !t.is_finished()),
+            ManagedService::Metrics => self.metrics.as_ref().is_some_and(|t| !t.is_finished()),
+            ManagedService::Mail => self.mail.as_ref().is_some_and(|t| !t.is_finished()),
+            ManagedService::Privacy => self.privacy.as_ref().is_some_and(|t| !t.is_finished()),
+            // Still counts the usage when the compositor can't tell the focused window
+            ManagedService::ScreenTime => self.screen_time.is_some(),
+        }
+    }
+
+    fn save(&self) -> Result<(), WatsonError> {
+        if !self.persist {
+            return Ok(());
+        }
+        let path = get_config_dir()?.join(format!("{SERVICES_CONFIG}.json"));
+        let data = serde_json::to_vec_pretty(&self.config)
+            .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+        fs::write(path, data).map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))
+    }
+}
+
+impl NotificationDaemon {
+    /// Starts the services the user didn't switch off
+    pub fn start_services(
+        &mut self,
+        daemon: Weak<RwLock<NotificationDaemon>>,
+        metrics_port: Option<u16>,
+    ) {
+        self.services.daemon = daemon;
+        self.services.metrics_port = metrics_port;
+
+        // Clients don't wait for audio that never starts
+        self.register.mark_unavailable(DaemonService::AudioService);
+        let caps = self.hardware.capabilities();
+        for service in ManagedService::iter() {
+            let possible = match service {
+                ManagedService::Audio => caps.pulse || caps.pipewire,
+                ManagedService::CalendarSync => true,
+                ManagedService::Finance => true,
+                ManagedService::HomeAssistant => true,
+                ManagedService::Metrics => metrics_port.is_some(),
+                ManagedService::Mail => true,
+                ManagedService::Privacy => true,
+                ManagedService::ScreenTime => true,
+            };
+            if !possible || !self.services.is_enabled(service) {
+                continue;
+            }
+            if let Err(e) = self.start_service(service) {
+                eprintln!("{:?}", e);
+            }
+        }
+    }
+
+    pub fn set_service_enabled(
+        &mut self,
+        service: ManagedService,
+        enabled: bool,
+    ) -> Result<(), WatsonError> {
+        match (enabled, self.services.is_running(service)) {
+            (true, false) => self.start_service(service)?,
+            (false, true) => self.stop_service(service),
+            _ => {}
+        }
+        self.services.config.disabled.retain(|s| *s != service);
+        if !enabled {
+            self.services.config.disabled.push(service);
+        }
+        self.services.save()
+    }
+
+    fn start_service(&mut self, service: ManagedService) -> Result<(), WatsonError> {
+        match service {
+            ManagedService::Audio => self.start_audio(),
+            ManagedService::CalendarSync => {
+                schedule_calendar_refresh(&self.scheduler, Arc::clone(&self.software.events))?;
+                schedule_contacts_sync(&self.scheduler, Arc::clone(&self.software.contacts))?;
+                if let Some(reminders) = TravelReminders::load() {
+                    schedule_travel_reminders(
+                        &self.scheduler,
+                        Arc::clone(&self.software.events),
+                        Arc::new(reminders),
+                        self.services.daemon(),
+                    )?;
+                }
+                self.services.calendar_sync = true;
+                Ok(())
+            }
+            ManagedService::Finance => {
+                schedule_quotes(&self.scheduler, Arc::clone(&self.software.ticker))?;
+                self.services.finance = true;
+                Ok(())
+            }
+            ManagedService::HomeAssistant => {
+                let task = tokio::spawn(watch_home_assistant(Arc::clone(
+                    &self.software.home_assistant,
+                )));
+                self.services.home_assistant = Some(task.abort_handle());
+                Ok(())
+            }
+            ManagedService::Metrics => {
+                let port = self.services.metrics_port.ok_or_else(|| {
+                    watson_err!(
+                        WatsonErrorKind::TcpServer,
+                        "Metrics need the daemon to be started with --metrics-port"
+                    )
+                })?;
+                let daemon = self.services.daemon.upgrade().ok_or_else(|| {
+                    watson_err!(WatsonErrorKind::TcpServer, "Daemon is shutting down")
+                })?;
+                let task = tokio::spawn(async move {
+                    if let Err(e) = metrics_listener(daemon, port).await {
+                        eprintln!("{:?}", e);
+                    }
+                });
+                self.services.metrics = Some(task.abort_handle());
+                Ok(())
+            }
+            ManagedService::Mail => {
+                let task = tokio::spawn(watch_mail(
+                    Arc::clone(&self.software.mail),
+                    self.services.daemon(),
+                ));
+                self.services.mail = Some(task.abort_handle());
+                Ok(())
+            }
+            ManagedService::Privacy => {
+                let listener = device_use_listener(
+                    self.hardware.privacy(),
+                    self.services.daemon(),
+                    self.hardware.capabilities().pipewire,
+                );
+                let task = tokio::spawn(async move {
+                    if let Err(e) = listener.await {
+                        eprintln!("{:?}", e);
+                    }
+                });
+                self.services.privacy = Some(task.abort_handle());
+                Ok(())
+            }
+            ManagedService::ScreenTime => {
+                let task = start_screen_time(
+                    &self.scheduler,
+                    Arc::clone(&self.software.screen_time),
+                    self.services.daemon(),
+                )?;
+                self.services.screen_time = Some(task);
+                Ok(())
+            }
+        }
+    }
+
+    fn stop_service(&mut self, service: ManagedService) {
+        match service {
+            ManagedService::Audio => {
+                if let Some(stop) = self.services.audio.take() {
+                    stop.notify_one();
+                }
+                self.hardware.clear_audio();
+                self.register.mark_unavailable(DaemonService::AudioService);
+            }
+            ManagedService::CalendarSync => {
+                self.scheduler.unregister(CALENDAR_REFRESH_JOB);
+                self.scheduler.unregister(TRAVEL_REMINDER_JOB);
+                self.scheduler.unregister(CONTACTS_SYNC_JOB);
+                self.services.calendar_sync = false;
+            }
+            ManagedService::Finance => {
+                self.scheduler.unregister(FINANCE_JOB);
+                self.services.finance = false;
+            }
+            ManagedService::HomeAssistant => {
+                if let Some(task) = self.services.home_assistant.take() {
+                    task.abort();
+                }
+                self.software.home_assistant.clear();
+                let _result = DAEMON_TX
+                    .get()
+                    .map(|d| d.send(InternalMessage::HomeAssistant(Vec::new())));
+            }
+            ManagedService::Metrics => {
+                // Closes the listening socket too
+                if let Some(task) = self.services.metrics.take() {
+                    task.abort();
+                }
+            }
+            ManagedService::Mail => {
+                if let Some(task) = self.services.mail.take() {
+                    task.abort();
+                }
+                self.software.mail.clear();
+                let _result = DAEMON_TX
+                    .get()
+                    .map(|d| d.send(InternalMessage::Mail(Vec::new())));
+            }
+            ManagedService::Privacy => {
+                // Also ends `pw-dump`
+                if let Some(task) = self.services.privacy.take() {
+                    task.abort();
+                }
+                self.hardware.privacy().clear();
+            }
+            ManagedService::ScreenTime => {
+                if let Some(task) = self.services.screen_time.take() {
+                    task.abort();
+                }
+                self.scheduler.unregister(SCREEN_TIME_JOB);
+                self.software.screen_time.stop();
+            }
+        }
+    }
+
+    /// Runs the audio actor on a thread with a runtime of its own
+    fn start_audio(&mut self) -> Result<(), WatsonError> {
+        let caps = self.hardware.capabilities();
+        if !(caps.pulse || caps.pipewire) {
+            return Err(watson_err!(WatsonErrorKind::Audio, "No audio server found"));
+        }
+        let backend = AudioServer::select(&caps);
+        let (audio_tx, audio_rx) = mpsc::channel::<AudioCommand>(16);
+        let stop = Arc::new(Notify::new());
+        let wake_signal = Arc::clone(&self.wake_signal);
+        let register = Arc::clone(&self.register);
+        register.mark_available(DaemonService::AudioService);
+        std::thread::spawn({
+            let audio_tx = audio_tx.clone();
+            let stop = Arc::clone(&stop);
+            move || {
+                let rt = match tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                {
+                    Ok(rt) => rt,
+                    Err(e) => {
+                        eprintln!("{:?}", e);
+                        register.mark_unavailable(DaemonService::AudioService);
+                        return;
+                    }
+                };
+
+                let result = rt.block_on(async {
+                    tokio::select! {
+                        result = backend.run(
+                            audio_tx,
+                            audio_rx,
+                            wake_signal,
+                            Arc::clone(&register),
+                        ) => result,
+                        _ = stop.notified() => Ok(()),
+                    }
+                });
+                if let Err(e) = result {
+                    eprintln!("{:?}", e);
+                    register.mark_unavailable(DaemonService::AudioService);
+                }
+            }
+        });
+        self.hardware.set_audio_state(audio_tx);
+        self.services.audio = Some(stop);
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    fmt::Debug,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use suite_223b::protocol::{TraceDirection, TraceEvent};
+
+/// Traced messages per second at most, the rest is counted and left out
+const MAX_EVENTS_PER_SECOND: u32 = 200;
+/// Payloads are cut after this many bytes
+const MAX_PAYLOAD: usize = 512;
+
+/// Turns socket traffic into `TraceEvent`s for `--trace-protocol` and `Request::TraceProtocol`
+pub struct ProtocolTrace {
+    /// Print every event to stderr
+    log: AtomicBool,
+    window: Mutex<RateWindow>,
+}
+impl Default for ProtocolTrace {
+    fn default() -> Self {
+        Self {
+            log: AtomicBool::new(false),
+            window: Mutex::new(RateWindow::new(Instant::now())),
+        }
+    }
+}
+impl ProtocolTrace {
+    pub fn set_log(&self, log: bool) {
+        self.log.store(log, Ordering::Relaxed);
+    }
+    pub fn logging(&self) -> bool {
+        self.log.load(Ordering::Relaxed)
+    }
+
+    /// The event for `message`, `None` while over the rate limit
+    pub fn event(
+        &self,
+        client: Option<u64>,
+        direction: TraceDirection,
+        message: &dyn Debug,
+    ) -> Option<TraceEvent> {
+        let skipped = self.window.lock().ok()?.admit(Instant::now())?;
+        Some(TraceEvent {
+            at: Utc::now(),
+            client,
+            direction,
+            payload: truncate(format!("{:?}", message), MAX_PAYLOAD),
+            skipped,
+        })
+    }
+}
+
+struct RateWindow {
+    start: Instant,
+    count: u32,
+    skipped: u64,
+}
+impl RateWindow {
+    fn new(start: Instant) -> Self {
+        Self {
+            start,
+            count: 0,
+            skipped: 0,
+        }
+    }
+    /// The number of events left out since the last admitted one, `None` if this one is left
+    /// out too
+    fn admit(&mut self, now: Instant) -> Option<u64> {
+        if now.duration_since(self.start) >= Duration::from_secs(1) {
+            self.start = now;
+            self.count = 0;
+        }
+        if self.count >= MAX_EVENTS_PER_SECOND {
+            self.skipped += 1;
+            return None;
+        }
+        self.count += 1;
+        Some(std::mem::take(&mut self.skipped))
+    }
+}
+
+fn truncate(mut payload: String, max: usize) -> String {
+    if payload.len() > max {
+        let mut end = max;
+        while !payload.is_char_boundary(end) {
+            end -= 1;
+        }
+        payload.truncate(end);
+        payload.push('…');
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_counts_skipped_events() {
+        let start = Instant::now();
+        let mut window = RateWindow::new(start);
+        for _ in 0..MAX_EVENTS_PER_SECOND {
+            assert_eq!(window.admit(start), Some(0));
+        }
+        assert_eq!(window.admit(start), None);
+        assert_eq!(window.admit(start + Duration::from_millis(500)), None);
+        // The next second reports what was left out
+        assert_eq!(window.admit(start + Duration::from_secs(1)), Some(2));
+        assert_eq!(window.admit(start + Duration::from_secs(1)), Some(0));
+    }
+
+    #[test]
+    fn test_truncate_on_char_boundary() {
+        assert_eq!(truncate("short".into(), 8), "short");
+        assert_eq!(truncate("äöü".into(), 3), "ä…");
+    }
+}
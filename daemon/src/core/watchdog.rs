@@ -0,0 +1,117 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use suite_223b::{
+    protocol::SocketData,
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::process::Command;
+
+const CLIENT_BINARY: &str = "watson";
+/// Wait before the first restart, doubled after each crash in a row
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+/// A client that ran this long counts as recovered, its next crash starts the delays over
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+/// Crashes in a row before the watchdog gives up
+pub(crate) const MAX_CRASHES: u32 = 5;
+
+/// When to start the client or a plugin again after it exited
+#[derive(Debug)]
+pub(crate) struct RestartPolicy {
+    crashes: u32,
+}
+impl RestartPolicy {
+    pub(crate) fn new() -> Self {
+        Self { crashes: 0 }
+    }
+
+    /// The wait before the next start, `None` once the process quit on purpose or keeps crashing
+    pub(crate) fn on_exit(&mut self, success: bool, ran: Duration) -> Option<Duration> {
+        if success {
+            return None;
+        }
+        if ran >= STABLE_AFTER {
+            self.crashes = 0;
+        }
+        self.crashes += 1;
+        if self.crashes > MAX_CRASHES {
+            return None;
+        }
+        Some((RESTART_DELAY * 2u32.pow(self.crashes - 1)).min(MAX_RESTART_DELAY))
+    }
+}
+
+/// The client next to the daemon's binary, otherwise the one on `PATH`
+fn client_path() -> PathBuf {
+    std::env::current_exe()
+        .map(|exe| exe.with_file_name(CLIENT_BINARY))
+        .ok()
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(CLIENT_BINARY))
+}
+
+/// Runs the client and starts it again whenever it crashes. A clean exit ends the watch, e.g.
+/// when the user quit or another instance was already running.
+pub async fn watch_client() -> Result<(), WatsonError> {
+    let client = client_path();
+    let mut policy = RestartPolicy::new();
+    loop {
+        let started = Instant::now();
+        let mut command = Command::new(&client);
+        // The client wouldn't find a daemon on another socket
+        if let Some(socket) = SocketData::overridden() {
+            command.arg("--socket").arg(socket);
+        }
+        let status = command.status().await.map_err(|e| {
+            watson_err!(
+                WatsonErrorKind::CommandExecute,
+                format!("Could not start {}: {}", client.display(), e)
+            )
+        })?;
+
+        let Some(delay) = policy.on_exit(status.success(), started.elapsed()) else {
+            if status.success() {
+                return Ok(());
+            }
+            return Err(watson_err!(
+                WatsonErrorKind::CommandExecute,
+                format!(
+                    "The client crashed {} times in a row, giving up",
+                    MAX_CRASHES
+                )
+            ));
+        };
+        eprintln!("Client exited with {}, restarting in {:?}", status, delay);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_policy() {
+        let mut policy = RestartPolicy::new();
+        assert_eq!(policy.on_exit(true, Duration::ZERO), None);
+
+        let quick = Duration::from_secs(1);
+        assert_eq!(policy.on_exit(false, quick), Some(Duration::from_secs(1)));
+        assert_eq!(policy.on_exit(false, quick), Some(Duration::from_secs(2)));
+        assert_eq!(policy.on_exit(false, quick), Some(Duration::from_secs(4)));
+
+        // Running for a while forgives the earlier crashes
+        assert_eq!(
+            policy.on_exit(false, STABLE_AFTER),
+            Some(Duration::from_secs(1))
+        );
+        for _ in 1..MAX_CRASHES {
+            assert!(policy.on_exit(false, quick).is_some());
+        }
+        assert_eq!(policy.on_exit(false, quick), None);
+    }
+}
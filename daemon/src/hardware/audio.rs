@@ -1,21 +1,120 @@
-use std::sync::{
-    Arc,
-    atomic::{AtomicU8, Ordering},
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU8, Ordering},
+    },
+    time::Duration,
 };
 
+use async_trait::async_trait;
 use libpulse_binding::{
-    context::{Context, FlagSet},
+    callbacks::ListResult,
+    context::{
+        Context, FlagSet, State,
+        subscribe::{Facility, InterestMaskSet},
+    },
+    mainloop::threaded::Mainloop,
     volume::{ChannelVolumes, Volume},
 };
+use serde::Deserialize;
 use suite_223b::{
+    config::profile::load_config_file,
     protocol::{DaemonService, InternalMessage},
     utils::errors::{WatsonError, WatsonErrorKind},
     watson_err,
 };
 use tokio::sync::{Notify, mpsc, oneshot};
 
-use crate::{DAEMON_TX, core::registry::ServiceRegistry, hardware::HardwareController};
+use crate::{
+    DAEMON_TX,
+    core::registry::ServiceRegistry,
+    hardware::{Capabilities, HardwareController},
+};
+
+/// Reconnect delay after losing the audio server, doubles up to `MAX_BACKOFF`
+pub(super) const BACKOFF: Duration = Duration::from_secs(1);
+pub(super) const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether the actor is connected to an audio server
+static AVAILABLE: AtomicBool = AtomicBool::new(false);
+
+pub fn audio_available() -> bool {
+    AVAILABLE.load(Ordering::Relaxed)
+}
+
+/// Tells clients when audio control comes and goes
+pub(super) fn set_available(available: bool) {
+    if AVAILABLE.swap(available, Ordering::Relaxed) != available {
+        let _result = DAEMON_TX
+            .get()
+            .map(|d| d.send(InternalMessage::AudioAvailable(available)));
+    }
+}
+
+/// `$XDG_CONFIG_HOME/watson/audio.json`, e.g. `{ "backend": "pipewire" }`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AudioConfig {
+    backend: AudioServer,
+}
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioServer {
+    /// PipeWire if it is running and compiled in, PulseAudio otherwise
+    #[default]
+    Auto,
+    Pulse,
+    Pipewire,
+}
+impl AudioServer {
+    /// The configured backend, resolved to one that can run here
+    pub fn select(caps: &Capabilities) -> Self {
+        let configured = load_config_file::<AudioConfig>("audio")
+            .map(|c| c.backend)
+            .unwrap_or_else(|e| {
+                eprintln!("{:?}", e);
+                Self::Auto
+            });
+        let native = cfg!(feature = "pipewire") && caps.pipewire;
+        match configured {
+            Self::Pipewire if !native => {
+                eprintln!("PipeWire backend not available, using PulseAudio.");
+                Self::Pulse
+            }
+            Self::Auto if native => Self::Pipewire,
+            Self::Auto => Self::Pulse,
+            backend => backend,
+        }
+    }
+
+    /// Runs the actor serving `AudioCommand`s for this backend
+    pub async fn run(
+        self,
+        tx: mpsc::Sender<AudioCommand>,
+        rx: mpsc::Receiver<AudioCommand>,
+        wake_signal: Arc<Notify>,
+        register: Arc<ServiceRegistry>,
+    ) -> Result<(), WatsonError> {
+        match self {
+            #[cfg(feature = "pipewire")]
+            Self::Pipewire => {
+                super::pipewire_audio::pipewire_actor(rx, wake_signal, register).await
+            }
+            _ => audio_actor(tx, rx, wake_signal, register).await,
+        }
+    }
+}
+
+/// Volume of the default sink
+#[async_trait]
+pub trait AudioBackend: Send + Sync {
+    async fn volume(&self) -> Result<u8, WatsonError>;
+    async fn set_volume(&self, percent: u8) -> Result<(), WatsonError>;
+}
+
+/// Forwards to the actor thread of the running `AudioServer`
 #[derive(Debug)]
 pub struct VolumeState {
     tx: mpsc::Sender<AudioCommand>,
@@ -25,6 +124,30 @@ impl VolumeState {
         Self { tx }
     }
 }
+#[async_trait]
+impl AudioBackend for VolumeState {
+    async fn volume(&self) -> Result<u8, WatsonError> {
+        // Without an audio server the actor is gone, the slider just stays at 0
+        if self.tx.is_closed() {
+            return Ok(0);
+        }
+        let (tx, rx) = oneshot::channel::<u8>();
+        self.tx
+            .send(AudioCommand::GetVolume { resp: tx })
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))?;
+
+        rx.await
+            .map_err(|_| watson_err!(WatsonErrorKind::Audio, "AudioActor rejected request."))
+    }
+
+    async fn set_volume(&self, percent: u8) -> Result<(), WatsonError> {
+        self.tx
+            .send(AudioCommand::SetVolume(percent))
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))
+    }
+}
 
 #[derive(Debug)]
 pub enum AudioCommand {
@@ -41,135 +164,276 @@ impl HardwareController {
             Err(_) => return Ok(()),
         };
 
-        if let Some(state) = &self.volume_state {
-            state
-                .tx
-                .send(AudioCommand::SetVolume(percent))
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))?;
+        match &self.audio {
+            Some(audio) => audio.set_volume(percent).await,
+            None => Ok(()),
         }
-
-        Ok(())
     }
 
     pub async fn get_volume(&mut self) -> Result<u8, WatsonError> {
-        if let Some(state) = &self.volume_state {
-            let (tx, rx) = oneshot::channel::<u8>();
-            state
-                .tx
-                .send(AudioCommand::GetVolume { resp: tx })
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))?;
-
-            return rx
-                .await
-                .map_err(|_| watson_err!(WatsonErrorKind::Audio, "AudioActor rejected request."));
-        } else {
-            println!("test");
+        match &self.audio {
+            Some(audio) => audio.volume().await,
+            None => Ok(0),
         }
+    }
 
-        Ok(0)
+    /// Forgets the actor after it was stopped, clients are told audio went away
+    pub fn clear_audio(&mut self) {
+        self.audio = None;
+        set_available(false);
     }
 }
 
+/// Drives volume requests and sink events, reconnecting with backoff whenever the audio server
+/// goes away, e.g. when PipeWire restarts. Only returns an error if PulseAudio can't be used at all.
 pub async fn audio_actor(
     tx: mpsc::Sender<AudioCommand>,
     mut rx: mpsc::Receiver<AudioCommand>,
     wake_signal: Arc<Notify>,
     register: Arc<ServiceRegistry>,
-) {
-    use libpulse_binding::mainloop::threaded::Mainloop;
-    let mut mainloop = Mainloop::new().unwrap();
-    let mut ctx = Context::new(&mainloop, "WatsonDaemon").unwrap();
-    ctx.connect(None, FlagSet::NOAUTOSPAWN, None).unwrap();
+) -> Result<(), WatsonError> {
+    let last_percentage = Arc::new(AtomicU8::new(0));
+    let mut backoff = BACKOFF;
+    loop {
+        let mut conn = PulseConnection::new()?;
+        if let Err(e) = conn.connect(&tx).await {
+            eprintln!("{:?}", e);
+            set_available(false);
+            if !wait(&mut rx, backoff, &last_percentage).await {
+                return Ok(());
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            continue;
+        }
+        println!("Pulse Audio Connected.");
+        backoff = BACKOFF;
+        set_available(true);
+        // The volume may have changed while we were away
+        conn.fetch_default(&last_percentage);
+
+        if !conn
+            .serve(&mut rx, &wake_signal, &register, &last_percentage)
+            .await
+        {
+            return Ok(());
+        }
+        eprintln!("Lost the audio server, reconnecting.");
+        set_available(false);
+    }
+}
 
-    mainloop.start().unwrap();
+/// Answers requests from the last known volume for `duration`, so callers don't block while
+/// there is no server. False once the channel closed.
+pub(super) async fn wait(
+    rx: &mut mpsc::Receiver<AudioCommand>,
+    duration: Duration,
+    last_percentage: &AtomicU8,
+) -> bool {
+    let deadline = tokio::time::sleep(duration);
+    tokio::pin!(deadline);
     loop {
-        match ctx.get_state() {
-            libpulse_binding::context::State::Unconnected => {}
-            libpulse_binding::context::State::Connecting => {}
-            libpulse_binding::context::State::Authorizing => {}
-            libpulse_binding::context::State::SettingName => {}
-            libpulse_binding::context::State::Ready => break,
-            libpulse_binding::context::State::Failed => unimplemented!(),
-            libpulse_binding::context::State::Terminated => unimplemented!(),
+        tokio::select! {
+            _ = &mut deadline => return true,
+            cmd = rx.recv() => match cmd {
+                Some(AudioCommand::GetVolume { resp }) => {
+                    let _ = resp.send(last_percentage.load(Ordering::Relaxed));
+                }
+                Some(_) => {}
+                None => return false,
+            },
         }
     }
-    println!("Pulse Audio Connected.");
-
-    // Event listener for audio events
-    ctx.subscribe(
-        libpulse_binding::context::subscribe::InterestMaskSet::SINK,
-        |_| {},
-    );
-    ctx.set_subscribe_callback(Some(Box::new({
-        move |facility, _operation, index| {
-            if facility == Some(libpulse_binding::context::subscribe::Facility::Sink) {
-                let _ = tx.try_send(AudioCommand::VolumeFetch { index });
+}
+
+fn percent(volume: &ChannelVolumes) -> u8 {
+    ((volume.avg().0 as f64 / Volume::NORMAL.0 as f64) * 100.0) as u8
+}
+
+/// Stores `percentage` and broadcasts it if it changed
+pub(super) fn report_volume(last_percentage: &AtomicU8, percentage: u8) {
+    if last_percentage.swap(percentage, Ordering::Relaxed) != percentage {
+        let _result = DAEMON_TX
+            .get()
+            .map(|d| d.send(InternalMessage::VolumeStateChange { percentage }));
+    }
+}
+
+/// A context on its own mainloop. A failed context can't reconnect, so every attempt uses a
+/// fresh one.
+struct PulseConnection {
+    // Dropped before the mainloop it runs on
+    ctx: Context,
+    mainloop: Mainloop,
+    /// Signalled on every context state change
+    state_rx: mpsc::Receiver<()>,
+}
+impl PulseConnection {
+    fn new() -> Result<Self, WatsonError> {
+        let mainloop = Mainloop::new().ok_or_else(|| {
+            watson_err!(
+                WatsonErrorKind::Audio,
+                "Could not create a PulseAudio mainloop"
+            )
+        })?;
+        let mut ctx = Context::new(&mainloop, "WatsonDaemon").ok_or_else(|| {
+            watson_err!(
+                WatsonErrorKind::Audio,
+                "Could not create a PulseAudio context"
+            )
+        })?;
+        let (state_tx, state_rx) = mpsc::channel::<()>(8);
+        ctx.set_state_callback(Some(Box::new(move || {
+            let _ = state_tx.try_send(());
+        })));
+        Ok(Self {
+            ctx,
+            mainloop,
+            state_rx,
+        })
+    }
+
+    /// Connects and subscribes to sink events, which arrive as `AudioCommand::VolumeFetch`
+    async fn connect(&mut self, tx: &mpsc::Sender<AudioCommand>) -> Result<(), WatsonError> {
+        self.ctx
+            .connect(None, FlagSet::NOAUTOSPAWN, None)
+            .map_err(|e| watson_err!(WatsonErrorKind::Audio, format!("{e}")))?;
+        self.mainloop
+            .start()
+            .map_err(|e| watson_err!(WatsonErrorKind::Audio, format!("{e}")))?;
+
+        tokio::time::timeout(CONNECT_TIMEOUT, self.ready())
+            .await
+            .map_err(|_| {
+                watson_err!(WatsonErrorKind::Timeout, "The audio server did not answer")
+            })??;
+
+        self.ctx.subscribe(InterestMaskSet::SINK, |_| {});
+        self.ctx.set_subscribe_callback(Some(Box::new({
+            let tx = tx.clone();
+            move |facility, _operation, index| {
+                if facility == Some(Facility::Sink) {
+                    let _ = tx.try_send(AudioCommand::VolumeFetch { index });
+                }
+            }
+        })));
+        Ok(())
+    }
+
+    async fn ready(&mut self) -> Result<(), WatsonError> {
+        loop {
+            if self.ctx.get_state() == State::Ready {
+                return Ok(());
+            }
+            if self.is_lost() || self.state_rx.recv().await.is_none() {
+                return Err(watson_err!(
+                    WatsonErrorKind::Audio,
+                    "Could not connect to the audio server"
+                ));
             }
         }
-    })));
+    }
 
-    let last_percentage = Arc::new(AtomicU8::new(0));
-    loop {
-        // Ghost check
+    fn is_lost(&self) -> bool {
+        matches!(self.ctx.get_state(), State::Failed | State::Terminated)
+    }
+
+    /// Handles commands until the server goes away. False once the channel closed.
+    async fn serve(
+        &mut self,
+        rx: &mut mpsc::Receiver<AudioCommand>,
+        wake_signal: &Notify,
+        register: &ServiceRegistry,
+        last_percentage: &Arc<AtomicU8>,
+    ) -> bool {
         loop {
-            if register.is_active(DaemonService::AudioService) {
-                break;
+            // Ghost check
+            loop {
+                if register.is_active(DaemonService::AudioService) {
+                    break;
+                }
+                println!("AudioService not registered.");
+
+                wake_signal.notified().await;
             }
-            println!("AudioService not registered.");
 
-            wake_signal.notified().await;
+            tokio::select! {
+                Some(()) = self.state_rx.recv() => {
+                    if self.is_lost() {
+                        return true;
+                    }
+                }
+                cmd = rx.recv() => match cmd {
+                    Some(cmd) => self.handle(cmd, last_percentage),
+                    None => return false,
+                },
+            }
         }
+    }
 
-        tokio::select! {
-            Some(cmd) = rx.recv() => {
-                match cmd {
-                    AudioCommand::SetVolume(v) => {
-                        if v != last_percentage.load(Ordering::Relaxed) {
-                            last_percentage.store(v, Ordering::Relaxed);
-                            let mut cv = ChannelVolumes::default();
-                            let val = ((v as f64 / 100.0) * Volume::NORMAL.0 as f64) as u32;
-                            cv.set(2, Volume(val));
-                            ctx.introspect().set_sink_volume_by_name("@DEFAULT_SINK@", &cv, None);
-                            mainloop.signal(false);
-                        }
-                    }
-                    AudioCommand::GetVolume { resp } => {
-                        ctx.introspect().get_sink_info_by_name("@DEFAULT_SINK@", {
-                            let mut resp_opt = Some(resp);
-                            let last_percentage = Arc::clone(&last_percentage);
-                            move |info| {
-                                if let libpulse_binding::callbacks::ListResult::Item(i) = info {
-                                    let percent = ((i.volume.avg().0 as f64 / Volume::NORMAL.0 as f64) * 100.0) as u8;
-                                    last_percentage.store(percent, Ordering::Relaxed);
-                                    if let Some(r) = resp_opt.take() {
-                                        let _ = r.send(percent);
-                                    }
-                                }
-                            }
-                        });
-                        mainloop.signal(false);
-                    }
-                    AudioCommand::VolumeFetch { index } => {
-                        ctx.introspect().get_sink_info_by_index(index, {
-                            let last_percentage = Arc::clone(&last_percentage);
-                            move |info| {
-                                if let libpulse_binding::callbacks::ListResult::Item(item) = info {
-                                    let avg_vol = item.volume.avg().0;
-                                    let percentage = ((avg_vol as f64 / Volume::NORMAL.0 as f64) * 100.0) as u8;
-                                    if percentage != last_percentage.load(Ordering::Relaxed) {
-                                        last_percentage.store(percentage, Ordering::Relaxed);
-                                        let _result = DAEMON_TX.get().map(|d| d.send(InternalMessage::VolumeStateChange { percentage }));
-                                    }
+    fn handle(&mut self, cmd: AudioCommand, last_percentage: &Arc<AtomicU8>) {
+        match cmd {
+            AudioCommand::SetVolume(v) => {
+                if v != last_percentage.load(Ordering::Relaxed) {
+                    last_percentage.store(v, Ordering::Relaxed);
+                    let mut cv = ChannelVolumes::default();
+                    let val = ((v as f64 / 100.0) * Volume::NORMAL.0 as f64) as u32;
+                    cv.set(2, Volume(val));
+                    self.ctx
+                        .introspect()
+                        .set_sink_volume_by_name("@DEFAULT_SINK@", &cv, None);
+                    self.mainloop.signal(false);
+                }
+            }
+            AudioCommand::GetVolume { resp } => {
+                self.ctx
+                    .introspect()
+                    .get_sink_info_by_name("@DEFAULT_SINK@", {
+                        let mut resp_opt = Some(resp);
+                        let last_percentage = Arc::clone(last_percentage);
+                        move |info| {
+                            if let ListResult::Item(i) = info {
+                                let percent = percent(&i.volume);
+                                last_percentage.store(percent, Ordering::Relaxed);
+                                if let Some(r) = resp_opt.take() {
+                                    let _ = r.send(percent);
                                 }
                             }
-                        });
-                        mainloop.signal(false);
+                        }
+                    });
+                self.mainloop.signal(false);
+            }
+            AudioCommand::VolumeFetch { index } => {
+                self.ctx.introspect().get_sink_info_by_index(index, {
+                    let last_percentage = Arc::clone(last_percentage);
+                    move |info| {
+                        if let ListResult::Item(item) = info {
+                            report_volume(&last_percentage, percent(&item.volume));
+                        }
                     }
-                }
+                });
+                self.mainloop.signal(false);
             }
-            else => break,
         }
     }
+
+    fn fetch_default(&mut self, last_percentage: &Arc<AtomicU8>) {
+        self.ctx
+            .introspect()
+            .get_sink_info_by_name("@DEFAULT_SINK@", {
+                let last_percentage = Arc::clone(last_percentage);
+                move |info| {
+                    if let ListResult::Item(item) = info {
+                        report_volume(&last_percentage, percent(&item.volume));
+                    }
+                }
+            });
+        self.mainloop.signal(false);
+    }
+}
+impl Drop for PulseConnection {
+    fn drop(&mut self) {
+        self.ctx.set_state_callback(None);
+        self.ctx.disconnect();
+        self.mainloop.stop();
+    }
 }
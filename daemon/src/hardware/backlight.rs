@@ -1,24 +1,87 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
+use async_trait::async_trait;
 use suite_223b::{
     utils::errors::{WatsonError, WatsonErrorKind},
     watson_err,
 };
-use zbus::Proxy;
+use tokio::{
+    process::Command,
+    sync::{OnceCell, Semaphore},
+};
+use zbus::{Connection, Proxy};
+
+use crate::hardware::{HardwareController, polkit::call_authorized};
+
+const SYSFS_BACKLIGHT: &str = "/sys/class/backlight";
+/// FreeBSD's backlight(8) and its devices
+const BACKLIGHT: &str = "/usr/bin/backlight";
+const BACKLIGHT_DEVICES: &str = "/dev/backlight";
+/// OpenBSD's display controls
+const WSCONSCTL: &str = "/sbin/wsconsctl";
+
+/// Brightness of the built-in panel
+#[async_trait]
+pub trait BacklightBackend: Send + Sync {
+    async fn brightness(&self) -> Result<u8, WatsonError>;
+    async fn set_brightness(&self, percent: u8) -> Result<(), WatsonError>;
+}
+
+/// Whether any backend finds a panel
+pub fn backlight_available() -> bool {
+    let has_device = |dir: &str| fs::read_dir(dir).is_ok_and(|mut dir| dir.next().is_some());
+    has_device(SYSFS_BACKLIGHT)
+        || (has_device(BACKLIGHT_DEVICES) && Path::new(BACKLIGHT).exists())
+        || Path::new(WSCONSCTL).exists()
+}
 
-use crate::hardware::HardwareController;
+/// The backend of the running system, sysfs where there is one
+pub fn system_backlight(conn: &Connection) -> Box<dyn BacklightBackend> {
+    if !Path::new(SYSFS_BACKLIGHT).exists() {
+        if Path::new(BACKLIGHT).exists() {
+            return Box::new(BsdBacklight);
+        }
+        if Path::new(WSCONSCTL).exists() {
+            return Box::new(WsconsBacklight);
+        }
+    }
+    Box::new(SysfsBacklight::new(conn.clone()))
+}
 
-pub struct BrightnessState {
+struct BrightnessState {
     path: PathBuf,
     name: String,
     max: u32,
     proxy: Proxy<'static>,
 }
 
-impl HardwareController {
-    // ----- Brightness (Native Sysfs) -----
-    async fn set_brightness_state(&mut self) -> Result<(), WatsonError> {
-        let device_path = fs::read_dir("/sys/class/backlight/")
+/// Reads sysfs, writes through logind, which doesn't need root
+pub struct SysfsBacklight {
+    conn: Connection,
+    /// The first device, found on first use
+    state: OnceCell<BrightnessState>,
+}
+impl SysfsBacklight {
+    pub fn new(conn: Connection) -> Self {
+        Self {
+            conn,
+            state: OnceCell::new(),
+        }
+    }
+
+    async fn state(&self) -> Result<&BrightnessState, WatsonError> {
+        self.state
+            .get_or_try_init(|| BrightnessState::detect(&self.conn))
+            .await
+    }
+}
+impl BrightnessState {
+    async fn detect(conn: &Connection) -> Result<Self, WatsonError> {
+        let device_path = fs::read_dir(SYSFS_BACKLIGHT)
             .map_err(|e| watson_err!(WatsonErrorKind::DirRead, e.to_string()))?
             .next()
             .ok_or_else(|| {
@@ -38,71 +101,158 @@ impl HardwareController {
             .unwrap_or(100);
 
         let proxy = Proxy::new(
-            &self.conn,
+            conn,
             "org.freedesktop.login1",
             "/org/freedesktop/login1/session/auto",
             "org.freedesktop.login1.Session",
         )
         .await
         .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
-        self.brightness_state = Some(BrightnessState {
+        Ok(Self {
             name,
             path: device_path.path(),
             max,
             proxy,
-        });
-        Ok(())
+        })
     }
-    pub async fn set_brightness(&mut self, percent: u8) -> Result<(), WatsonError> {
+}
+#[async_trait]
+impl BacklightBackend for SysfsBacklight {
+    async fn set_brightness(&self, percent: u8) -> Result<(), WatsonError> {
+        let state = self.state().await?;
+        let absolute = (percent as u32 * state.max) / 100;
+
+        // Most logind versions expect (subsystem, device_name, brightness_value)
+        // You can usually pass "backlight" and the device name (e.g., "intel_backlight")
+        // logind only allows this from inside the graphical session
+        call_authorized(
+            &state.proxy,
+            "SetBrightness",
+            &("backlight", state.name.as_str(), absolute),
+            "Run watson-daemon inside your login session or allow org.freedesktop.login1.set-brightness for your user",
+        )
+        .await
+    }
+    async fn brightness(&self) -> Result<u8, WatsonError> {
+        let state = self.state().await?;
+        if state.max == 0 {
+            return Ok(0);
+        }
+
+        let current_raw = fs::read_to_string(state.path.join("brightness"))
+            .map_err(|e| watson_err!(WatsonErrorKind::FileRead, e.to_string()))?;
+        let current: u32 = current_raw.trim().parse().map_err(|_| {
+            watson_err!(
+                WatsonErrorKind::Deserialize,
+                "Failed to parse current brightness as u32."
+            )
+        })?;
+
+        let percent = ((current as f32 / state.max as f32) * 100.0) as u8;
+        Ok(percent)
+    }
+}
+
+/// FreeBSD's backlight(8), which needs write access to `/dev/backlight`
+pub struct BsdBacklight;
+#[async_trait]
+impl BacklightBackend for BsdBacklight {
+    async fn brightness(&self) -> Result<u8, WatsonError> {
+        let output = run(BACKLIGHT, &["-q"]).await?;
+        parse_percent(&output).ok_or_else(|| unexpected(BACKLIGHT, &output))
+    }
+    async fn set_brightness(&self, percent: u8) -> Result<(), WatsonError> {
+        run(BACKLIGHT, &[&percent.to_string()]).await.map(|_| ())
+    }
+}
+
+/// OpenBSD's `display.brightness`, which needs write access to the console
+pub struct WsconsBacklight;
+#[async_trait]
+impl BacklightBackend for WsconsBacklight {
+    async fn brightness(&self) -> Result<u8, WatsonError> {
+        // `display.brightness=42.00%`
+        let output = run(WSCONSCTL, &["display.brightness"]).await?;
+        output
+            .trim()
+            .strip_prefix("display.brightness=")
+            .and_then(parse_percent)
+            .ok_or_else(|| unexpected(WSCONSCTL, &output))
+    }
+    async fn set_brightness(&self, percent: u8) -> Result<(), WatsonError> {
+        let arg = format!("display.brightness={}", percent);
+        run(WSCONSCTL, &[&arg]).await.map(|_| ())
+    }
+}
+
+async fn run(program: &str, args: &[&str]) -> Result<String, WatsonError> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, e.to_string()))?;
+    if !output.status.success() {
+        return Err(watson_err!(
+            WatsonErrorKind::CommandExecute,
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn unexpected(program: &str, output: &str) -> WatsonError {
+    watson_err!(
+        WatsonErrorKind::Deserialize,
+        format!("Unexpected {} output: {}", program, output.trim())
+    )
+}
+
+/// `42`, `42%` or `42.00%`
+fn parse_percent(value: &str) -> Option<u8> {
+    let value: f32 = value.trim().trim_end_matches('%').parse().ok()?;
+    Some(value.clamp(0.0, 100.0).round() as u8)
+}
+
+/// The panel on its own, so a change waiting on polkit doesn't hold the daemon
+#[derive(Clone)]
+pub struct Backlight {
+    backend: Arc<dyn BacklightBackend>,
+    throttle: Arc<Semaphore>,
+}
+impl Backlight {
+    pub async fn set_brightness(&self, percent: u8) -> Result<(), WatsonError> {
         let _permit = match &self.throttle.try_acquire() {
             Ok(p) => p,
             Err(_) => return Ok(()),
         };
+        self.backend.set_brightness(percent).await
+    }
+}
 
-        if self.brightness_state.is_none() {
-            self.set_brightness_state().await?;
-        }
-
-        if let Some(state) = &self.brightness_state {
-            let absolute = (percent as u32 * state.max) / 100;
-
-            // Most logind versions expect (subsystem, device_name, brightness_value)
-            // You can usually pass "backlight" and the device name (e.g., "intel_backlight")
-            state
-                .proxy
-                .call::<_, _, ()>(
-                    "SetBrightness",
-                    &("backlight", state.name.as_str(), absolute),
-                )
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
+impl HardwareController {
+    // ----- Brightness (Native Sysfs) -----
+    pub fn backlight(&self) -> Backlight {
+        Backlight {
+            backend: Arc::clone(&self.backlight),
+            throttle: Arc::clone(&self.throttle),
         }
-
-        Ok(())
+    }
+    pub async fn set_brightness(&mut self, percent: u8) -> Result<(), WatsonError> {
+        self.backlight().set_brightness(percent).await
     }
     pub async fn get_brightness(&mut self) -> Result<u8, WatsonError> {
-        if self.brightness_state.is_none() {
-            self.set_brightness_state().await?;
-        }
+        self.backlight.brightness().await
+    }
+}
 
-        if let Some(state) = &self.brightness_state {
-            if state.max == 0 {
-                return Ok(0);
-            }
-
-            let current_raw = fs::read_to_string(state.path.join("brightness"))
-                .map_err(|e| watson_err!(WatsonErrorKind::FileRead, e.to_string()))?;
-            let current: u32 = current_raw.trim().parse().map_err(|_| {
-                watson_err!(
-                    WatsonErrorKind::Deserialize,
-                    "Failed to parse current brightness as u32."
-                )
-            })?;
-
-            let percent = ((current as f32 / state.max as f32) * 100.0) as u8;
-            return Ok(percent);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Ok(0)
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("42\n"), Some(42));
+        assert_eq!(parse_percent("42.60%"), Some(43));
+        assert_eq!(parse_percent("brightness: 42"), None);
     }
 }
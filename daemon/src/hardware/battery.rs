@@ -0,0 +1,118 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use suite_223b::{
+    notification::{HintValue, Notification, Urgency},
+    protocol::{BatteryState, JobSchedule},
+    utils::{battery::BatterySource, errors::WatsonError},
+};
+use tokio::sync::RwLock;
+
+use crate::{core::scheduler::Scheduler, notify::NotificationDaemon};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Decides when the battery counts as full, once per charge
+#[derive(Debug, Default)]
+struct FullChargeNotice {
+    notified: bool,
+}
+impl FullChargeNotice {
+    /// Whether to notify now. With a charge limit the firmware stops charging below 100%,
+    /// usually reporting neither charging nor full.
+    fn update(&mut self, state: BatteryState, percentage: u32, limit: Option<u32>) -> bool {
+        let full = match state {
+            BatteryState::Discharging => {
+                self.notified = false;
+                return false;
+            }
+            BatteryState::Full => true,
+            _ => limit.is_some_and(|limit| percentage >= limit),
+        };
+        if full && !self.notified {
+            self.notified = true;
+            return true;
+        }
+        false
+    }
+}
+
+/// Reads the battery every `CHECK_INTERVAL` and tells the user to unplug once it's full
+pub fn schedule_full_charge_notice(
+    scheduler: &Scheduler,
+    daemon: Arc<RwLock<NotificationDaemon>>,
+) -> Result<(), WatsonError> {
+    let Some(source) = BatterySource::get() else {
+        return Ok(());
+    };
+    let notice = Arc::new(Mutex::new(FullChargeNotice::default()));
+    scheduler.register(
+        "battery-full",
+        JobSchedule::Every(CHECK_INTERVAL.as_secs()),
+        move || {
+            let daemon = Arc::clone(&daemon);
+            let notice = Arc::clone(&notice);
+            async move {
+                let (state, percentage) = match source.poll().await {
+                    Ok(read) => read,
+                    Err(e) => {
+                        eprintln!("{:?}", e);
+                        return;
+                    }
+                };
+                let limit = source.charge_limit();
+                let full = notice
+                    .lock()
+                    .expect("Poisoned")
+                    .update(state, percentage, limit);
+                if !full {
+                    return;
+                }
+                notify_full(&daemon, limit).await;
+            }
+        },
+    )
+}
+
+async fn notify_full(daemon: &Arc<RwLock<NotificationDaemon>>, limit: Option<u32>) {
+    let body = match limit {
+        Some(limit) => format!("Unplug to preserve battery health\nCharge limit: {limit}%"),
+        None => "Unplug to preserve battery health".to_string(),
+    };
+    let notification = Notification {
+        app_name: "Watson".into(),
+        app_icon: "battery-full-charged-symbolic".into(),
+        summary: "Battery full".into(),
+        body,
+        hints: HashMap::from([("category".into(), HintValue::String("device".into()))]),
+        expire_timeout: -1,
+        urgency: Urgency::Low,
+        ..Default::default()
+    };
+    daemon
+        .write()
+        .await
+        .insert(notification, Arc::downgrade(daemon));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_charge_notifies_once_per_charge() {
+        let mut notice = FullChargeNotice::default();
+        assert!(!notice.update(BatteryState::Charging, 90, None));
+        assert!(notice.update(BatteryState::Full, 100, None));
+        assert!(!notice.update(BatteryState::Full, 100, None));
+
+        // Unplugging starts the next charge
+        assert!(!notice.update(BatteryState::Discharging, 99, None));
+        assert!(!notice.update(BatteryState::Charging, 79, Some(80)));
+        assert!(notice.update(BatteryState::Invalid, 80, Some(80)));
+        assert!(!notice.update(BatteryState::Invalid, 80, Some(80)));
+    }
+}
@@ -0,0 +1,147 @@
+use std::{collections::HashMap, path::Path};
+
+use suite_223b::{
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use zbus::{Connection, fdo::DBusProxy, zvariant::Value};
+
+use crate::hardware::backlight_available;
+
+const PORTAL_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+
+/// What the daemon can reach from where it runs, detected once at startup. Inside Flatpak most
+/// of the host is hidden, so services fall back to portals or report themselves unavailable
+/// instead of failing every request.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Capabilities {
+    pub flatpak: bool,
+    /// A panel in sysfs, or the BSD backlight tools
+    pub backlight: bool,
+    /// A PulseAudio or pipewire-pulse server socket
+    pub pulse: bool,
+    /// A native PipeWire socket
+    pub pipewire: bool,
+    pub network_manager: bool,
+    pub bluez: bool,
+    pub power_profiles: bool,
+    /// Battery events, without it the battery is polled
+    pub upower: bool,
+    /// Sessions, sleep and the lid, missing on most BSDs
+    pub logind: bool,
+    /// xdg-desktop-portal, used for ScreenCast, Screenshot and Background. The client reads the
+    /// color scheme and accent through its Settings.
+    pub portal: bool,
+}
+impl Capabilities {
+    pub async fn detect(system: &Connection) -> Self {
+        let session = Connection::session().await.ok();
+        let system_names = bus_names(Some(system)).await;
+        let session_names = bus_names(session.as_ref()).await;
+
+        let caps = Self {
+            flatpak: Path::new("/.flatpak-info").exists(),
+            backlight: backlight_available(),
+            pulse: pulse_available(),
+            pipewire: pipewire_available(),
+            network_manager: system_names
+                .iter()
+                .any(|n| n == "org.freedesktop.NetworkManager"),
+            bluez: system_names.iter().any(|n| n == "org.bluez"),
+            power_profiles: system_names.iter().any(|n| {
+                n == "net.hadess.PowerProfiles" || n == "org.freedesktop.UPower.PowerProfiles"
+            }),
+            upower: system_names.iter().any(|n| n == "org.freedesktop.UPower"),
+            logind: system_names.iter().any(|n| n == "org.freedesktop.login1"),
+            portal: session_names.iter().any(|n| n == PORTAL_NAME),
+        };
+        eprintln!("{}", caps);
+        caps
+    }
+}
+impl std::fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let missing: Vec<&str> = [
+            (self.backlight, "backlight"),
+            (self.pulse || self.pipewire, "audio"),
+            (self.network_manager, "NetworkManager"),
+            (self.bluez, "BlueZ"),
+            (self.power_profiles, "power profiles"),
+            (self.upower, "UPower"),
+            (self.logind, "logind"),
+            (self.portal, "portal"),
+        ]
+        .into_iter()
+        .filter(|(available, _)| !available)
+        .map(|(_, name)| name)
+        .collect();
+
+        if self.flatpak {
+            write!(f, "Running inside Flatpak. ")?;
+        }
+        if missing.is_empty() {
+            write!(f, "All hardware services available")
+        } else {
+            write!(f, "Unavailable: {}", missing.join(", "))
+        }
+    }
+}
+
+/// Running and activatable names, empty without a connection
+async fn bus_names(conn: Option<&Connection>) -> Vec<String> {
+    let Some(conn) = conn else {
+        return Vec::new();
+    };
+    let Ok(proxy) = DBusProxy::new(conn).await else {
+        return Vec::new();
+    };
+    let mut names = proxy.list_names().await.unwrap_or_default();
+    names.extend(proxy.list_activatable_names().await.unwrap_or_default());
+    names.into_iter().map(|n| n.to_string()).collect()
+}
+
+fn pulse_available() -> bool {
+    if std::env::var_os("PULSE_SERVER").is_some() {
+        return true;
+    }
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .is_some_and(|dir| Path::new(&dir).join("pulse/native").exists())
+}
+
+fn pipewire_available() -> bool {
+    if std::env::var_os("PIPEWIRE_REMOTE").is_some() {
+        return true;
+    }
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .is_some_and(|dir| Path::new(&dir).join("pipewire-0").exists())
+}
+
+/// Asks the Background portal to let the daemon keep running without a window. Flatpak stops
+/// background apps otherwise.
+pub async fn request_background() -> Result<(), WatsonError> {
+    let conn = Connection::session()
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))?;
+    let proxy = zbus::Proxy::new(
+        &conn,
+        PORTAL_NAME,
+        PORTAL_PATH,
+        "org.freedesktop.portal.Background",
+    )
+    .await
+    .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+
+    let options = HashMap::from([
+        (
+            "reason",
+            Value::from("Serve notifications and system controls"),
+        ),
+        ("autostart", Value::from(false)),
+    ]);
+    proxy
+        .call::<_, _, zbus::zvariant::OwnedObjectPath>("RequestBackground", &("", options))
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
+    Ok(())
+}
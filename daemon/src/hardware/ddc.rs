@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use suite_223b::{
+    protocol::{DisplayBrightness, InternalMessage},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::{
+    process::Command,
+    sync::{OnceCell, watch},
+};
+
+use crate::{DAEMON_TX, core::metrics::COUNTERS};
+
+/// VCP feature code of the luminance control
+const BRIGHTNESS: &str = "10";
+/// Monitors ignore commands that follow each other too closely
+const WRITE_INTERVAL: Duration = Duration::from_millis(200);
+/// `ddcutil detect` probes every I2C bus, which takes a few seconds
+const DDCUTIL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// External monitors controlled over DDC/CI through `ddcutil`. Detected on first use, since
+/// probing the buses is slow.
+#[derive(Default)]
+pub struct Ddc {
+    displays: OnceCell<Vec<DdcDisplay>>,
+}
+
+struct DdcDisplay {
+    /// DRM connector, e.g. `DP-1`, or `i2c-<bus>` if ddcutil doesn't know it
+    device: String,
+    name: String,
+    bus: u32,
+    /// Latest requested percentage, written by `writer`
+    target: watch::Sender<u8>,
+}
+
+impl Ddc {
+    async fn displays(&self) -> &[DdcDisplay] {
+        self.displays.get_or_init(detect).await
+    }
+
+    pub async fn brightness(&self) -> Vec<DisplayBrightness> {
+        let mut result = Vec::new();
+        for display in self.displays().await {
+            match get_brightness(display.bus).await {
+                Ok((current, max)) => result.push(DisplayBrightness {
+                    device: display.device.clone(),
+                    name: display.name.clone(),
+                    percent: to_percent(current, max),
+                }),
+                Err(e) => eprintln!("{:?}", e),
+            }
+        }
+        result
+    }
+
+    /// Queues a write, only the latest value is sent once the monitor is ready again
+    pub async fn set_brightness(&self, device: &str, percent: u8) -> Result<(), WatsonError> {
+        let display = self
+            .displays()
+            .await
+            .iter()
+            .find(|d| d.device == device)
+            .ok_or_else(|| {
+                watson_err!(
+                    WatsonErrorKind::BacklightNotFound,
+                    format!("No DDC/CI display {device}")
+                )
+            })?;
+        display.target.send_replace(percent.min(100));
+        Ok(())
+    }
+}
+
+async fn detect() -> Vec<DdcDisplay> {
+    let output = match ddcutil(&["detect", "--brief"]).await {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return Vec::new();
+        }
+    };
+    parse_detect(&output)
+        .into_iter()
+        .map(|(bus, device, name)| {
+            let (target, rx) = watch::channel(0);
+            tokio::spawn(writer(bus, device.clone(), name.clone(), rx));
+            DdcDisplay {
+                device,
+                name,
+                bus,
+                target,
+            }
+        })
+        .collect()
+}
+
+/// Writes the latest target of one display, at most once per `WRITE_INTERVAL`
+async fn writer(bus: u32, device: String, name: String, mut target: watch::Receiver<u8>) {
+    let mut max = None;
+    while target.changed().await.is_ok() {
+        let percent = *target.borrow_and_update();
+        let result = async {
+            let limit = match max {
+                Some(limit) => limit,
+                None => {
+                    let limit = get_brightness(bus).await?.1;
+                    max = Some(limit);
+                    limit
+                }
+            };
+            let value = (percent as u32 * limit as u32 / 100).to_string();
+            ddcutil(&[
+                "--bus",
+                &bus.to_string(),
+                "setvcp",
+                BRIGHTNESS,
+                &value,
+                "--noverify",
+            ])
+            .await
+        }
+        .await;
+
+        let message = match result {
+            Ok(_) => InternalMessage::Displays(vec![DisplayBrightness {
+                device: device.clone(),
+                name: name.clone(),
+                percent,
+            }]),
+            Err(e) => {
+                eprintln!("{:?}", e);
+                COUNTERS.record_error("hardware");
+                InternalMessage::Error(e.into())
+            }
+        };
+        let _result = DAEMON_TX.get().map(|d| d.send(message));
+
+        tokio::time::sleep(WRITE_INTERVAL).await;
+    }
+}
+
+/// Current and maximum brightness
+async fn get_brightness(bus: u32) -> Result<(u16, u16), WatsonError> {
+    let output = ddcutil(&["--bus", &bus.to_string(), "getvcp", BRIGHTNESS, "--terse"]).await?;
+    parse_vcp(&output).ok_or_else(|| {
+        watson_err!(
+            WatsonErrorKind::Deserialize,
+            format!("Unexpected ddcutil output: {}", output.trim())
+        )
+    })
+}
+
+async fn ddcutil(args: &[&str]) -> Result<String, WatsonError> {
+    let mut command = Command::new("ddcutil");
+    command.args(args).kill_on_drop(true);
+    let output = tokio::time::timeout(DDCUTIL_TIMEOUT, command.output())
+        .await
+        .map_err(|_| watson_err!(WatsonErrorKind::Timeout, "ddcutil did not finish"))?
+        .map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, format!("ddcutil: {e}")))?;
+    if !output.status.success() {
+        return Err(watson_err!(
+            WatsonErrorKind::CommandExecute,
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn to_percent(current: u16, max: u16) -> u8 {
+    if max == 0 {
+        return 0;
+    }
+    ((current as u32 * 100) / max as u32).min(100) as u8
+}
+
+/// `(bus, device, name)` of every usable display in `ddcutil detect --brief`
+fn parse_detect(output: &str) -> Vec<(u32, String, String)> {
+    // Blocks start with `Display <n>`, unusable ones with `Invalid display`
+    output
+        .split("\n\n")
+        .filter(|block| block.trim_start().starts_with("Display"))
+        .filter_map(|block| {
+            let field = |key: &str| {
+                block
+                    .lines()
+                    .find_map(|l| l.trim().strip_prefix(key))
+                    .map(str::trim)
+            };
+            let bus = field("I2C bus:")?.rsplit('-').next()?.parse::<u32>().ok()?;
+            // `card1-DP-1` names the connector like the compositor does, without the card
+            let device = field("DRM connector:")
+                .map(|c| c.split_once('-').map_or(c, |(_, name)| name).to_string())
+                .unwrap_or_else(|| format!("i2c-{bus}"));
+            // `GSM:LG HDR 4K:serial`
+            let name = field("Monitor:")
+                .and_then(|m| m.split(':').nth(1))
+                .filter(|m| !m.is_empty())
+                .unwrap_or(&device)
+                .to_string();
+            Some((bus, device, name))
+        })
+        .collect()
+}
+
+/// `VCP 10 C 50 100`, current then maximum
+fn parse_vcp(output: &str) -> Option<(u16, u16)> {
+    let mut parts = output.split_whitespace();
+    if parts.next()? != "VCP" || parts.next()? != BRIGHTNESS || parts.next()? != "C" {
+        return None;
+    }
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_detect_and_vcp() {
+        let output = "Display 1\n   I2C bus:  /dev/i2c-6\n   DRM connector:           card1-DP-2\n   Monitor:                 GSM:LG HDR 4K:0x0001\n\nInvalid display\n   I2C bus:  /dev/i2c-3\n\nDisplay 2\n   I2C bus:  /dev/i2c-7\n   Monitor:                 DEL::\n";
+        assert_eq!(
+            parse_detect(output),
+            vec![
+                (6, "DP-2".to_string(), "LG HDR 4K".to_string()),
+                (7, "i2c-7".to_string(), "i2c-7".to_string()),
+            ]
+        );
+        assert_eq!(parse_vcp("VCP 10 C 50 100\n"), Some((50, 100)));
+        assert_eq!(parse_vcp("VCP 10 ERR\n"), None);
+    }
+}
@@ -0,0 +1,181 @@
+use std::{sync::Arc, time::Duration};
+
+use futures_util::StreamExt;
+use serde::Deserialize;
+use suite_223b::{
+    config::profile::load_config_file,
+    protocol::{DockState, InternalMessage, PowerMode},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::{sync::RwLock, time::MissedTickBehavior};
+use zbus::{Connection, Proxy};
+
+use crate::{DAEMON_TX, hardware::HardwareController, notify::NotificationDaemon};
+
+const UPOWER_NAME: &str = "org.freedesktop.UPower";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+
+/// logind doesn't announce changes of `Docked`, which follows the lid and connected displays
+const DOCK_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// `$XDG_CONFIG_HOME/watson/dock.json`, what to do in each lid and dock state. The dock state
+/// wins where both set something, e.g.
+/// `{ "docked": { "layout": "docked", "power_mode": "performance" },
+///    "lid_closed": { "brightness_slider": false } }`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DockConfig {
+    pub lid_open: DockAction,
+    pub lid_closed: DockAction,
+    pub undocked: DockAction,
+    pub docked: DockAction,
+}
+impl DockConfig {
+    pub fn new() -> Self {
+        Self::load().unwrap_or_else(|e| {
+            eprintln!("{:?}", e);
+            Self::default()
+        })
+    }
+    fn load() -> Result<Self, WatsonError> {
+        load_config_file("dock")
+    }
+    fn resolve(&self, lid_closed: bool, docked: bool) -> DockAction {
+        let lid = if lid_closed {
+            &self.lid_closed
+        } else {
+            &self.lid_open
+        };
+        let dock = if docked { &self.docked } else { &self.undocked };
+        DockAction {
+            layout: dock.layout.clone().or_else(|| lid.layout.clone()),
+            power_mode: dock.power_mode.or(lid.power_mode),
+            brightness_slider: dock.brightness_slider.or(lid.brightness_slider),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DockAction {
+    /// Client layout, read from `$XDG_CONFIG_HOME/watson/<layout>.json`
+    pub layout: Option<String>,
+    pub power_mode: Option<PowerMode>,
+    /// `false` disables the brightness slider, e.g. while the internal display is off
+    pub brightness_slider: Option<bool>,
+}
+
+async fn lid_closed(conn: &Connection) -> Result<bool, WatsonError> {
+    let proxy = Proxy::new(conn, UPOWER_NAME, UPOWER_PATH, UPOWER_NAME)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+    proxy
+        .get_property("LidIsClosed")
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertyGet, e.to_string()))
+}
+
+async fn docked(conn: &Connection) -> Result<bool, WatsonError> {
+    let proxy = Proxy::new(
+        conn,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+    )
+    .await
+    .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+    proxy
+        .get_property("Docked")
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertyGet, e.to_string()))
+}
+
+impl HardwareController {
+    pub async fn get_dock_state(&self) -> Result<DockState, WatsonError> {
+        let lid_closed = lid_closed(&self.conn).await?;
+        let docked = docked(&self.conn).await?;
+        let action = self.dock.resolve(lid_closed, docked);
+        Ok(DockState {
+            lid_closed,
+            docked,
+            layout: action.layout,
+            brightness_slider: action.brightness_slider.unwrap_or(true),
+        })
+    }
+    fn dock_power_mode(&self, state: &DockState) -> Option<PowerMode> {
+        self.dock.resolve(state.lid_closed, state.docked).power_mode
+    }
+}
+
+/// Broadcasts lid and dock changes and switches the power profile the dock config asks for.
+/// Nothing is switched on startup, the user may have picked a profile already.
+pub async fn dock_listener(daemon: Arc<RwLock<NotificationDaemon>>) -> Result<(), WatsonError> {
+    let conn = daemon.read().await.hardware.conn.clone();
+    let upower = Proxy::new(&conn, UPOWER_NAME, UPOWER_PATH, UPOWER_NAME)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+    let mut lid = upower.receive_property_changed::<bool>("LidIsClosed").await;
+
+    let mut interval = tokio::time::interval(DOCK_POLL_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    // Without logind or UPower there is nothing to follow
+    let mut last = daemon.read().await.hardware.get_dock_state().await?;
+    loop {
+        tokio::select! {
+            Some(_) = lid.next() => {}
+            _ = interval.tick() => {}
+        }
+
+        let daemon = daemon.read().await;
+        let current = match daemon.hardware.get_dock_state().await {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                continue;
+            }
+        };
+        if current == last {
+            continue;
+        }
+        let previous = std::mem::replace(&mut last, current.clone());
+
+        if let Some(mode) = daemon.hardware.dock_power_mode(&current)
+            && daemon.hardware.dock_power_mode(&previous) != Some(mode)
+            && let Err(e) = daemon.hardware.set_powermode(mode).await
+        {
+            eprintln!("{:?}", e);
+        }
+        let _result = DAEMON_TX
+            .get()
+            .map(|d| d.send(InternalMessage::Dock(current)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dock_overrides_lid() {
+        let config: DockConfig = serde_json::from_str(
+            r#"{
+                "lid_closed": { "layout": "clamshell", "brightness_slider": false },
+                "docked": { "layout": "docked", "power_mode": "performance" }
+            }"#,
+        )
+        .unwrap();
+
+        let action = config.resolve(true, true);
+        assert_eq!(action.layout.as_deref(), Some("docked"));
+        assert_eq!(action.power_mode, Some(PowerMode::Performace));
+        assert_eq!(action.brightness_slider, Some(false));
+
+        let action = config.resolve(true, false);
+        assert_eq!(action.layout.as_deref(), Some("clamshell"));
+        assert_eq!(action.power_mode, None);
+
+        assert_eq!(config.resolve(false, false), DockAction::default());
+    }
+}
@@ -0,0 +1,127 @@
+//! In-memory backends. Clones share their state, so a test keeps one to look at what the
+//! controller did with the other.
+
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, AtomicU8, AtomicU32, Ordering},
+};
+
+use async_trait::async_trait;
+use suite_223b::{
+    protocol::{
+        BluetoothDevice, Connectivity, InternalMessage, PowerMode, PowerProfiles, ProfileHold,
+    },
+    utils::errors::WatsonError,
+};
+
+use crate::{
+    DAEMON_TX,
+    hardware::{AudioBackend, BacklightBackend, NetworkBackend, PowerBackend},
+};
+
+#[derive(Clone, Default)]
+pub struct MockNetwork {
+    pub wifi: Arc<AtomicBool>,
+    pub bluetooth: Arc<AtomicBool>,
+    pub bluetooth_devices: Arc<Mutex<Vec<BluetoothDevice>>>,
+    pub connectivity: Arc<Mutex<Connectivity>>,
+}
+#[async_trait]
+impl NetworkBackend for MockNetwork {
+    async fn wifi(&self) -> Result<bool, WatsonError> {
+        Ok(self.wifi.load(Ordering::Relaxed))
+    }
+    async fn set_wifi(&self, enabled: bool) -> Result<(), WatsonError> {
+        self.wifi.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+    async fn bluetooth(&self) -> Result<bool, WatsonError> {
+        Ok(self.bluetooth.load(Ordering::Relaxed))
+    }
+    async fn set_bluetooth(&self, enabled: bool) -> Result<(), WatsonError> {
+        self.bluetooth.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+    async fn bluetooth_devices(&self) -> Result<Vec<BluetoothDevice>, WatsonError> {
+        Ok(self.bluetooth_devices.lock().expect("Poisoned").clone())
+    }
+    async fn connectivity(&self) -> Result<Connectivity, WatsonError> {
+        Ok(self.connectivity.lock().expect("Poisoned").clone())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MockBacklight {
+    pub brightness: Arc<AtomicU8>,
+}
+#[async_trait]
+impl BacklightBackend for MockBacklight {
+    async fn brightness(&self) -> Result<u8, WatsonError> {
+        Ok(self.brightness.load(Ordering::Relaxed))
+    }
+    async fn set_brightness(&self, percent: u8) -> Result<(), WatsonError> {
+        self.brightness.store(percent, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MockPower {
+    pub profiles: Arc<Mutex<PowerProfiles>>,
+    next_cookie: Arc<AtomicU32>,
+    /// Cookies of the holds that weren't released
+    pub cookies: Arc<Mutex<Vec<u32>>>,
+}
+#[async_trait]
+impl PowerBackend for MockPower {
+    async fn powermode(&self) -> Result<PowerMode, WatsonError> {
+        Ok(self.profiles.lock().expect("Poisoned").active)
+    }
+    async fn set_powermode(&self, mode: PowerMode) -> Result<(), WatsonError> {
+        self.profiles.lock().expect("Poisoned").active = mode;
+        Ok(())
+    }
+    async fn profiles(&self) -> Result<PowerProfiles, WatsonError> {
+        Ok(self.profiles.lock().expect("Poisoned").clone())
+    }
+    async fn hold(&self, mode: PowerMode, reason: &str) -> Result<u32, WatsonError> {
+        let cookie = self.next_cookie.fetch_add(1, Ordering::Relaxed);
+        self.cookies.lock().expect("Poisoned").push(cookie);
+        self.profiles
+            .lock()
+            .expect("Poisoned")
+            .holds
+            .push(ProfileHold {
+                application: "test".into(),
+                profile: mode,
+                reason: reason.into(),
+            });
+        Ok(cookie)
+    }
+    async fn release(&self, cookie: u32) -> Result<(), WatsonError> {
+        self.cookies
+            .lock()
+            .expect("Poisoned")
+            .retain(|&c| c != cookie);
+        Ok(())
+    }
+}
+
+/// Reports every change to the clients, like the audio actor
+#[derive(Clone, Default)]
+pub struct MockAudio {
+    pub volume: Arc<AtomicU8>,
+}
+#[async_trait]
+impl AudioBackend for MockAudio {
+    async fn volume(&self) -> Result<u8, WatsonError> {
+        Ok(self.volume.load(Ordering::Relaxed))
+    }
+    async fn set_volume(&self, percentage: u8) -> Result<(), WatsonError> {
+        self.volume.store(percentage, Ordering::Relaxed);
+        let _result = DAEMON_TX
+            .get()
+            .map(|d| d.send(InternalMessage::VolumeStateChange { percentage }));
+        Ok(())
+    }
+}
@@ -4,46 +4,147 @@ use suite_223b::{protocol::SystemStateRaw, utils::errors::WatsonError};
 use tokio::sync::{Semaphore, mpsc};
 use zbus::Connection;
 
-use crate::hardware::{audio::VolumeState, backlight::BrightnessState};
+use crate::hardware::{
+    audio::VolumeState, backlight::system_backlight, ddc::Ddc, dock::DockConfig,
+    network::DbusNetwork, night_light::NightLight, power::PowerProfilesDaemon,
+    privacy::DeviceMonitor,
+};
 
 mod audio;
 mod backlight;
+mod battery;
+mod capabilities;
+mod ddc;
+mod dock;
+#[cfg(test)]
+pub mod mock;
 mod network;
+mod night_light;
+#[cfg(feature = "pipewire")]
+mod pipewire_audio;
+mod polkit;
 mod power;
+mod privacy;
+mod reconcile;
+mod session;
 
-pub use audio::{AudioCommand, audio_actor};
+pub use audio::{AudioBackend, AudioCommand, AudioServer, audio_available};
+pub use backlight::{BacklightBackend, backlight_available};
+pub use battery::schedule_full_charge_notice;
+pub use capabilities::{Capabilities, request_background};
+pub use dock::dock_listener;
+pub use network::{NetworkBackend, connectivity_listener};
+pub use night_light::schedule_night_light;
+pub use polkit::{handle_authorized, notify_permission_denied};
+pub use power::{PowerBackend, power_profiles_listener};
+pub use privacy::device_use_listener;
+pub use reconcile::system_state_listener;
+pub use session::session_listener;
 
 pub struct SystemStateBuilder;
 impl SystemStateBuilder {
     pub(crate) async fn new(
         hardware: &mut HardwareController,
     ) -> Result<SystemStateRaw, WatsonError> {
-        Ok(SystemStateRaw {
-            wifi: hardware.get_wifi().await?,
-            bluetooth: hardware.get_bluetooth().await?,
-            powermode: hardware.get_powermode().await?.into(),
-            brightness: hardware.get_brightness().await?,
+        let (night_light, night_light_intensity) = hardware.get_night_light();
+        let caps = hardware.capabilities;
+        // What isn't reachable, e.g. inside Flatpak, keeps its default
+        let mut state = SystemStateRaw {
             volume: hardware.get_volume().await?,
-        })
+            night_light,
+            night_light_intensity,
+            ..Default::default()
+        };
+        if caps.network_manager {
+            state.wifi = hardware.get_wifi().await?;
+            state.connectivity = hardware.get_connectivity().await?;
+        }
+        if caps.bluez {
+            state.bluetooth = hardware.get_bluetooth().await?;
+            if state.bluetooth {
+                state.bluetooth_devices = hardware.get_bluetooth_devices().await?;
+            }
+        }
+        if caps.power_profiles {
+            state.powermode = hardware.get_powermode().await?.into();
+        }
+        if caps.backlight {
+            state.brightness = hardware.get_brightness().await?;
+        }
+        Ok(state)
+    }
+}
+
+/// What the controller reads and switches, replaceable for tests or other platforms
+pub struct Backends {
+    pub network: Box<dyn NetworkBackend>,
+    pub backlight: Box<dyn BacklightBackend>,
+    pub power: Box<dyn PowerBackend>,
+}
+impl Backends {
+    /// NetworkManager, BlueZ, the platform's backlight and power-profiles-daemon
+    pub fn system(conn: &Connection) -> Self {
+        Self {
+            network: Box::new(DbusNetwork::new(conn.clone())),
+            backlight: system_backlight(conn),
+            power: Box::new(PowerProfilesDaemon::new(conn.clone())),
+        }
     }
 }
 
 pub struct HardwareController {
+    /// System bus, for the listeners
     conn: Connection,
-    brightness_state: Option<BrightnessState>,
-    volume_state: Option<VolumeState>,
+    network: Box<dyn NetworkBackend>,
+    backlight: Arc<dyn BacklightBackend>,
+    power: Arc<dyn PowerBackend>,
+    /// Set once the audio actor runs
+    audio: Option<Box<dyn AudioBackend>>,
+    night_light: NightLight,
     throttle: Arc<Semaphore>,
+    /// Cookie of our power profile hold
+    profile_hold: Option<u32>,
+    dock: DockConfig,
+    /// External displays
+    ddc: Arc<Ddc>,
+    /// Camera and microphone use
+    privacy: Arc<DeviceMonitor>,
+    capabilities: Capabilities,
 }
 impl HardwareController {
-    pub fn new(conn: Connection) -> Self {
+    pub fn new(conn: Connection, capabilities: Capabilities) -> Self {
+        let backends = Backends::system(&conn);
+        Self::with_backends(conn, capabilities, backends)
+    }
+    pub fn with_backends(conn: Connection, capabilities: Capabilities, backends: Backends) -> Self {
         Self {
             conn,
-            brightness_state: None,
-            volume_state: None,
+            capabilities,
+            network: backends.network,
+            backlight: Arc::from(backends.backlight),
+            power: Arc::from(backends.power),
+            audio: None,
+            night_light: NightLight::new(),
             throttle: Arc::new(Semaphore::new(1)),
+            profile_hold: None,
+            dock: DockConfig::new(),
+            ddc: Arc::new(Ddc::default()),
+            privacy: Arc::new(DeviceMonitor::default()),
         }
     }
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+    pub fn ddc(&self) -> Arc<Ddc> {
+        Arc::clone(&self.ddc)
+    }
+    pub fn privacy(&self) -> Arc<DeviceMonitor> {
+        Arc::clone(&self.privacy)
+    }
     pub fn set_audio_state(&mut self, tx: mpsc::Sender<AudioCommand>) {
-        self.volume_state.replace(VolumeState::new(tx));
+        self.set_audio(Box::new(VolumeState::new(tx)));
+    }
+    pub fn set_audio(&mut self, audio: Box<dyn AudioBackend>) {
+        self.audio.replace(audio);
     }
 }
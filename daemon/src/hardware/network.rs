@@ -1,20 +1,54 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, process::Command, sync::Arc};
 
+use async_trait::async_trait;
+use futures_util::StreamExt;
 use suite_223b::{
+    notification::Notification,
+    protocol::{
+        BluetoothDevice, ConnectionInfo, ConnectionKind, Connectivity, InternalMessage, WiredDevice,
+    },
     utils::errors::{WatsonError, WatsonErrorKind},
     watson_err,
 };
+use tokio::sync::RwLock;
 use zbus::{
-    Proxy,
-    zvariant::{OwnedObjectPath, OwnedValue},
+    Connection, MatchRule, MessageStream, Proxy,
+    message::Type,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue},
 };
 
-use crate::hardware::HardwareController;
+use crate::{
+    DAEMON_TX, hardware::HardwareController, notify::NotificationDaemon, utils::command::detach,
+};
 
-#[allow(dead_code)]
-impl HardwareController {
-    // ----- Wifi -----
-    pub async fn set_wifi(&self, enabled: bool) -> Result<(), WatsonError> {
+const NM_NAME: &str = "org.freedesktop.NetworkManager";
+/// `NM_CONNECTIVITY_PORTAL`
+const CONNECTIVITY_PORTAL: u32 = 2;
+/// Plain HTTP, so the portal can intercept it, for when NetworkManager has no check URI
+const FALLBACK_PORTAL: &str = "http://neverssl.com";
+
+/// Interfaces and their properties by object path, as `GetManagedObjects` returns them
+type ManagedObjects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
+/// Wifi, Bluetooth and the primary connection
+#[async_trait]
+pub trait NetworkBackend: Send + Sync {
+    async fn wifi(&self) -> Result<bool, WatsonError>;
+    async fn set_wifi(&self, enabled: bool) -> Result<(), WatsonError>;
+    async fn bluetooth(&self) -> Result<bool, WatsonError>;
+    async fn set_bluetooth(&self, enabled: bool) -> Result<(), WatsonError>;
+    /// Connected devices with their battery and audio codec where known
+    async fn bluetooth_devices(&self) -> Result<Vec<BluetoothDevice>, WatsonError>;
+    async fn connectivity(&self) -> Result<Connectivity, WatsonError>;
+}
+
+/// NetworkManager and BlueZ on the system bus
+pub struct DbusNetwork {
+    conn: Connection,
+}
+#[async_trait]
+impl NetworkBackend for DbusNetwork {
+    async fn wifi(&self) -> Result<bool, WatsonError> {
         let proxy = Proxy::new(
             &self.conn,
             "org.freedesktop.NetworkManager",
@@ -25,11 +59,11 @@ impl HardwareController {
         .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
 
         proxy
-            .set_property("WirelessEnabled", enabled)
+            .get_property("WirelessEnabled")
             .await
-            .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertySet, e.to_string()))
+            .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertyGet, e.to_string()))
     }
-    pub async fn get_wifi(&self) -> Result<bool, WatsonError> {
+    async fn set_wifi(&self, enabled: bool) -> Result<(), WatsonError> {
         let proxy = Proxy::new(
             &self.conn,
             "org.freedesktop.NetworkManager",
@@ -40,10 +74,107 @@ impl HardwareController {
         .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
 
         proxy
-            .get_property("WirelessEnabled")
+            .set_property("WirelessEnabled", enabled)
             .await
-            .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertyGet, e.to_string()))
+            .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertySet, e.to_string()))
+    }
+    async fn bluetooth(&self) -> Result<bool, WatsonError> {
+        if let Some(path) = self.bluetooth_path().await? {
+            let adapter = Proxy::new(&self.conn, "org.bluez", path, "org.bluez.Adapter1")
+                .await
+                .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+
+            adapter
+                .get_property("Powered")
+                .await
+                .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertyGet, e.to_string()))
+        } else {
+            Err(watson_err!(
+                WatsonErrorKind::BluetoothServiceDisabled,
+                "Bluetooth service is not enabled. Cannot find bluetooth adapter."
+            ))
+        }
+    }
+    /// Requires bluetooth service running
+    async fn set_bluetooth(&self, enabled: bool) -> Result<(), WatsonError> {
+        if let Some(path) = self.bluetooth_path().await? {
+            let adapter = Proxy::new(&self.conn, "org.bluez", path, "org.bluez.Adapter1")
+                .await
+                .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+
+            adapter
+                .set_property("Powered", enabled)
+                .await
+                .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertySet, e.to_string()))?;
+        }
+        Ok(())
+    }
+    async fn bluetooth_devices(&self) -> Result<Vec<BluetoothDevice>, WatsonError> {
+        let objects = self.bluez_objects().await?;
+        let string = |props: &HashMap<String, OwnedValue>, key: &str| {
+            props.get(key).and_then(|v| v.downcast_ref::<String>().ok())
+        };
+
+        let mut devices = Vec::new();
+        for (path, ifaces) in &objects {
+            let Some(device) = ifaces.get("org.bluez.Device1") else {
+                continue;
+            };
+            let connected = device
+                .get("Connected")
+                .and_then(|v| v.downcast_ref::<bool>().ok())
+                .unwrap_or(false);
+            if !connected {
+                continue;
+            }
+
+            let battery = ifaces
+                .get("org.bluez.Battery1")
+                .and_then(|b| b.get("Percentage"))
+                .and_then(|v| v.downcast_ref::<u8>().ok());
+            // Transports belong to the device by their `Device` property
+            let codec = objects.values().find_map(|ifaces| {
+                let transport = ifaces.get("org.bluez.MediaTransport1")?;
+                let owner = transport
+                    .get("Device")
+                    .and_then(|v| v.downcast_ref::<ObjectPath>().ok())?;
+                if owner.as_str() != path.as_str() {
+                    return None;
+                }
+                let codec = transport
+                    .get("Codec")
+                    .and_then(|v| v.downcast_ref::<u8>().ok())?;
+                let configuration = transport
+                    .get("Configuration")
+                    .and_then(|v| v.try_clone().ok())
+                    .and_then(|v| Vec::<u8>::try_from(v).ok())
+                    .unwrap_or_default();
+                Some(codec_name(codec, &configuration))
+            });
+
+            devices.push(BluetoothDevice {
+                name: string(device, "Alias")
+                    .or_else(|| string(device, "Name"))
+                    .unwrap_or_default(),
+                icon: string(device, "Icon"),
+                battery,
+                codec,
+            });
+        }
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(devices)
+    }
+    async fn connectivity(&self) -> Result<Connectivity, WatsonError> {
+        connectivity(&self.conn).await
     }
+}
+
+#[allow(dead_code)]
+impl DbusNetwork {
+    pub fn new(conn: Connection) -> Self {
+        Self { conn }
+    }
+    // ----- Wifi -----
     pub async fn get_wifi_list(&self) -> Result<HashMap<String, u8>, WatsonError> {
         let proxy = Proxy::new(
             &self.conn,
@@ -107,38 +238,7 @@ impl HardwareController {
         Ok(all_aps)
     }
     // ----- Bluetooth -----
-    /// Requires bluetooth service running
-    pub async fn set_bluetooth(&self, enabled: bool) -> Result<(), WatsonError> {
-        if let Some(path) = self.get_bluetooth_path().await? {
-            let adapter = Proxy::new(&self.conn, "org.bluez", path, "org.bluez.Adapter1")
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
-
-            adapter
-                .set_property("Powered", enabled)
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertySet, e.to_string()))?;
-        }
-        Ok(())
-    }
-    pub async fn get_bluetooth(&self) -> Result<bool, WatsonError> {
-        if let Some(path) = self.get_bluetooth_path().await? {
-            let adapter = Proxy::new(&self.conn, "org.bluez", path, "org.bluez.Adapter1")
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
-
-            adapter
-                .get_property("Powered")
-                .await
-                .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertyGet, e.to_string()))
-        } else {
-            Err(watson_err!(
-                WatsonErrorKind::BluetoothServiceDisabled,
-                "Bluetooth service is not enabled. Cannot find bluetooth adapter."
-            ))
-        }
-    }
-    pub async fn get_bluetooth_path(&self) -> Result<Option<OwnedObjectPath>, WatsonError> {
+    async fn bluez_objects(&self) -> Result<ManagedObjects, WatsonError> {
         let proxy = Proxy::new(
             &self.conn,
             "org.bluez",
@@ -148,15 +248,358 @@ impl HardwareController {
         .await
         .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
 
-        let objects: HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>> = proxy
+        proxy
             .call("GetManagedObjects", &())
             .await
-            .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
-
-        let path = objects
+            .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))
+    }
+    async fn bluetooth_path(&self) -> Result<Option<OwnedObjectPath>, WatsonError> {
+        let path = self
+            .bluez_objects()
+            .await?
             .into_iter()
             .find(|(_, ifaces)| ifaces.contains_key("org.bluez.Adapter1"))
             .map(|(p, _)| p);
         Ok(path)
     }
 }
+
+impl HardwareController {
+    // ----- Wifi -----
+    pub async fn set_wifi(&self, enabled: bool) -> Result<(), WatsonError> {
+        self.network.set_wifi(enabled).await
+    }
+    pub async fn get_wifi(&self) -> Result<bool, WatsonError> {
+        self.network.wifi().await
+    }
+    // ----- Bluetooth -----
+    pub async fn set_bluetooth(&self, enabled: bool) -> Result<(), WatsonError> {
+        self.network.set_bluetooth(enabled).await
+    }
+    pub async fn get_bluetooth(&self) -> Result<bool, WatsonError> {
+        self.network.bluetooth().await
+    }
+    pub async fn get_bluetooth_devices(&self) -> Result<Vec<BluetoothDevice>, WatsonError> {
+        self.network.bluetooth_devices().await
+    }
+    // ----- Connectivity -----
+    pub async fn get_connectivity(&self) -> Result<Connectivity, WatsonError> {
+        self.network.connectivity().await
+    }
+}
+
+/// Name of an A2DP codec from `org.bluez.MediaTransport1`. Vendor codecs are told apart by the
+/// vendor and codec id leading their configuration.
+fn codec_name(codec: u8, configuration: &[u8]) -> String {
+    let name = match codec {
+        0x00 => "SBC",
+        0x01 => "MP3",
+        0x02 => "AAC",
+        0x04 => "ATRAC",
+        0xFF => {
+            let vendor = configuration
+                .get(..4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+            let id = configuration
+                .get(4..6)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]));
+            match (vendor, id) {
+                (Some(0x4F), Some(0x01)) => "aptX",
+                (Some(0xD7), Some(0x24)) => "aptX HD",
+                (Some(0xD7), Some(0x02)) => "aptX LL",
+                (Some(0x12D), Some(0xAA)) => "LDAC",
+                (Some(0x75), Some(0x0102)) => "Samsung Scalable",
+                (Some(0x08A9), Some(0x0001)) => "LC3plus",
+                _ => "Vendor",
+            }
+        }
+        _ => "Unknown",
+    };
+    name.to_string()
+}
+
+async fn nm_proxy<'a>(
+    conn: &Connection,
+    path: &'a OwnedObjectPath,
+    iface: &'a str,
+) -> Result<Proxy<'a>, WatsonError> {
+    Proxy::new(conn, NM_NAME, path.as_str(), iface)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))
+}
+
+async fn connectivity(conn: &Connection) -> Result<Connectivity, WatsonError> {
+    let root = OwnedObjectPath::try_from("/org/freedesktop/NetworkManager")
+        .map_err(|e| watson_err!(WatsonErrorKind::InvalidData, e.to_string()))?;
+    let nm = nm_proxy(conn, &root, NM_NAME).await?;
+    let state: u32 = nm.get_property("Connectivity").await.unwrap_or_default();
+    let check_uri: String = nm
+        .get_property("ConnectivityCheckUri")
+        .await
+        .unwrap_or_default();
+    Ok(Connectivity {
+        primary: primary_connection(conn, &nm).await?,
+        wired: wired_devices(conn, &nm).await?,
+        portal: portal_url(state, check_uri),
+    })
+}
+
+/// Opening the connectivity check URI lets the portal redirect the browser to its login page
+fn portal_url(state: u32, check_uri: String) -> Option<String> {
+    if state != CONNECTIVITY_PORTAL {
+        return None;
+    }
+    if check_uri.is_empty() {
+        return Some(FALLBACK_PORTAL.to_string());
+    }
+    Some(check_uri)
+}
+
+/// Reads the primary connection's addresses, SSID and link speed
+async fn primary_connection(
+    conn: &Connection,
+    nm: &Proxy<'_>,
+) -> Result<Option<ConnectionInfo>, WatsonError> {
+    let primary: OwnedObjectPath = nm
+        .get_property("PrimaryConnection")
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertyGet, e.to_string()))?;
+    if primary.as_str() == "/" {
+        return Ok(None);
+    }
+
+    let active = nm_proxy(
+        conn,
+        &primary,
+        "org.freedesktop.NetworkManager.Connection.Active",
+    )
+    .await?;
+    let id: String = active.get_property("Id").await.unwrap_or_default();
+    let kind = match active
+        .get_property::<String>("Type")
+        .await
+        .unwrap_or_default()
+        .as_str()
+    {
+        "802-11-wireless" => ConnectionKind::Wireless,
+        "802-3-ethernet" => ConnectionKind::Wired,
+        _ => ConnectionKind::Other,
+    };
+    let devices: Vec<OwnedObjectPath> = active.get_property("Devices").await.unwrap_or_default();
+    let mut info = ConnectionInfo {
+        id,
+        kind,
+        ..Default::default()
+    };
+
+    for (property, addresses) in [("Ip4Config", &mut info.ipv4), ("Ip6Config", &mut info.ipv6)] {
+        let Ok(path) = active.get_property::<OwnedObjectPath>(property).await else {
+            continue;
+        };
+        if path.as_str() == "/" {
+            continue;
+        }
+        let iface = if property == "Ip4Config" {
+            "org.freedesktop.NetworkManager.IP4Config"
+        } else {
+            "org.freedesktop.NetworkManager.IP6Config"
+        };
+        let data: Vec<HashMap<String, OwnedValue>> = nm_proxy(conn, &path, iface)
+            .await?
+            .get_property("AddressData")
+            .await
+            .unwrap_or_default();
+        addresses.extend(data.iter().filter_map(|entry| {
+            let address = entry
+                .get("address")
+                .and_then(|v| v.downcast_ref::<String>().ok())?;
+            let prefix = entry
+                .get("prefix")
+                .and_then(|v| v.downcast_ref::<u32>().ok())?;
+            Some(format!("{}/{}", address, prefix))
+        }));
+    }
+
+    match (kind, devices.first()) {
+        (ConnectionKind::Wireless, Some(device)) => {
+            let wireless = nm_proxy(
+                conn,
+                device,
+                "org.freedesktop.NetworkManager.Device.Wireless",
+            )
+            .await?;
+            let bitrate: u32 = wireless.get_property("Bitrate").await.unwrap_or_default();
+            info.speed = bitrate / 1000;
+            if let Ok(ap) = wireless
+                .get_property::<OwnedObjectPath>("ActiveAccessPoint")
+                .await
+                && ap.as_str() != "/"
+            {
+                let ssid: Vec<u8> =
+                    nm_proxy(conn, &ap, "org.freedesktop.NetworkManager.AccessPoint")
+                        .await?
+                        .get_property("Ssid")
+                        .await
+                        .unwrap_or_default();
+                info.ssid = Some(String::from_utf8_lossy(&ssid).into_owned());
+            }
+        }
+        (ConnectionKind::Wired, Some(device)) => {
+            info.speed = nm_proxy(conn, device, "org.freedesktop.NetworkManager.Device.Wired")
+                .await?
+                .get_property("Speed")
+                .await
+                .unwrap_or_default();
+        }
+        _ => {}
+    }
+
+    Ok(Some(info))
+}
+
+/// Every ethernet interface NetworkManager knows about, plugged in or not
+async fn wired_devices(conn: &Connection, nm: &Proxy<'_>) -> Result<Vec<WiredDevice>, WatsonError> {
+    /// `NM_DEVICE_TYPE_ETHERNET`
+    const DEVICE_TYPE_ETHERNET: u32 = 1;
+
+    let devices: Vec<OwnedObjectPath> = nm
+        .call("GetDevices", &())
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
+
+    let mut wired = Vec::new();
+    for path in devices {
+        let device = nm_proxy(conn, &path, "org.freedesktop.NetworkManager.Device").await?;
+        if device.get_property::<u32>("DeviceType").await.ok() != Some(DEVICE_TYPE_ETHERNET) {
+            continue;
+        }
+        let interface: String = device.get_property("Interface").await.unwrap_or_default();
+        let ethernet = nm_proxy(conn, &path, "org.freedesktop.NetworkManager.Device.Wired").await?;
+        let carrier: bool = ethernet.get_property("Carrier").await.unwrap_or_default();
+        let speed: u32 = ethernet.get_property("Speed").await.unwrap_or_default();
+        wired.push(WiredDevice {
+            interface,
+            carrier,
+            speed: if carrier { speed } else { 0 },
+        });
+    }
+    Ok(wired)
+}
+
+/// Broadcasts the primary connection and wired interfaces whenever NetworkManager reports a
+/// relevant change
+pub async fn connectivity_listener(
+    daemon: Arc<RwLock<NotificationDaemon>>,
+) -> Result<(), WatsonError> {
+    // Access point strength and scan results change constantly and never affect the connection
+    const RELEVANT: &[&str] = &[
+        "org.freedesktop.NetworkManager",
+        "org.freedesktop.NetworkManager.Connection.Active",
+        "org.freedesktop.NetworkManager.IP4Config",
+        "org.freedesktop.NetworkManager.IP6Config",
+        "org.freedesktop.NetworkManager.Device.Wireless",
+        "org.freedesktop.NetworkManager.Device",
+        "org.freedesktop.NetworkManager.Device.Wired",
+    ];
+
+    let conn = daemon.read().await.hardware.conn.clone();
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender(NM_NAME)
+        .and_then(|b| b.interface("org.freedesktop.DBus.Properties"))
+        .and_then(|b| b.member("PropertiesChanged"))
+        .and_then(|b| b.path_namespace("/org/freedesktop/NetworkManager"))
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))?
+        .build();
+    let mut stream = MessageStream::for_match_rule(rule, &conn, None)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))?;
+
+    let mut last = connectivity(&conn).await.unwrap_or_default();
+    while let Some(msg) = stream.next().await {
+        let Ok(msg) = msg else { continue };
+        let Ok((iface, _, _)) = msg
+            .body()
+            .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>()
+        else {
+            continue;
+        };
+        if !RELEVANT.contains(&iface.as_str()) {
+            continue;
+        }
+
+        let current = match connectivity(&conn).await {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                continue;
+            }
+        };
+        if current != last {
+            if let Some(url) = current.portal.clone().filter(|_| last.portal.is_none()) {
+                notify_captive_portal(&daemon, url).await;
+            }
+            last = current.clone();
+            let _result = DAEMON_TX
+                .get()
+                .map(|d| d.send(InternalMessage::Connectivity(current)));
+        }
+    }
+    Ok(())
+}
+
+/// Asks the user to sign in to a captive portal. The `open` action opens the login page in the
+/// default browser.
+async fn notify_captive_portal(daemon: &Arc<RwLock<NotificationDaemon>>, url: String) {
+    let notification = Notification {
+        app_name: "Watson".into(),
+        app_icon: "network-wireless-no-route-symbolic".into(),
+        summary: "Sign in to network".into(),
+        body: "This network requires signing in before it can reach the internet".into(),
+        actions: vec!["open".into(), "Open portal".into()],
+        expire_timeout: -1,
+        ..Default::default()
+    };
+    let on_action = Box::new(move |action: Option<&str>| {
+        if action == Some("open")
+            && let Err(e) = detach(Command::new("xdg-open").arg(&url))
+        {
+            eprintln!("{:?}", e);
+        }
+    });
+    daemon
+        .write()
+        .await
+        .insert_with_action(notification, Arc::downgrade(daemon), on_action);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codec_names() {
+        assert_eq!(codec_name(0x00, &[0x21, 0x15, 2, 53]), "SBC");
+        assert_eq!(codec_name(0x02, &[]), "AAC");
+        // Sony's vendor id, then the LDAC codec id and its sample rate
+        assert_eq!(
+            codec_name(0xFF, &[0x2D, 0x01, 0, 0, 0xAA, 0x00, 0x04]),
+            "LDAC"
+        );
+        assert_eq!(codec_name(0xFF, &[0x4F, 0, 0, 0, 0x01, 0x00]), "aptX");
+        assert_eq!(codec_name(0xFF, &[0x4F]), "Vendor");
+    }
+
+    #[test]
+    fn test_portal_url() {
+        assert_eq!(portal_url(4, "http://check.example/".into()), None);
+        assert_eq!(
+            portal_url(CONNECTIVITY_PORTAL, "http://check.example/".into()).as_deref(),
+            Some("http://check.example/")
+        );
+        assert_eq!(
+            portal_url(CONNECTIVITY_PORTAL, String::new()).as_deref(),
+            Some(FALLBACK_PORTAL)
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use std::{
+    process::{Child, Command, Stdio},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Local, NaiveTime, Utc};
+use serde::Deserialize;
+use suite_223b::{
+    config::profile::load_config_file,
+    protocol::{InternalMessage, JobSchedule},
+    utils::{
+        astronomy::sun_times,
+        errors::{WatsonError, WatsonErrorKind},
+    },
+    watson_err,
+};
+use tokio::sync::RwLock;
+
+use crate::{
+    DAEMON_TX, core::scheduler::Scheduler, hardware::HardwareController, notify::NotificationDaemon,
+};
+
+/// Neutral colour temperature, used when the night light is off
+const IDENTITY_TEMPERATURE: u32 = 6500;
+
+/// `$XDG_CONFIG_HOME/watson/night_light.json`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NightLightConfig {
+    pub schedule: NightLightSchedule,
+    /// Colour temperature in Kelvin at 100% intensity
+    pub min_temperature: u32,
+    /// Initial intensity in percent
+    pub intensity: u8,
+}
+impl Default for NightLightConfig {
+    fn default() -> Self {
+        Self {
+            schedule: NightLightSchedule::Manual,
+            min_temperature: 2500,
+            intensity: 60,
+        }
+    }
+}
+impl NightLightConfig {
+    fn load() -> Result<Self, WatsonError> {
+        load_config_file("night_light")
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum NightLightSchedule {
+    /// Only toggled by the user
+    #[default]
+    Manual,
+    /// Local times as `HH:MM`, e.g. `{ "type": "fixed", "start": "21:00", "end": "07:00" }`
+    Fixed { start: String, end: String },
+    /// Active between sunset and sunrise at the given location
+    Sun { latitude: f64, longitude: f64 },
+}
+impl NightLightSchedule {
+    /// Whether the night light should be on at `now`. `None` if there is no opinion.
+    pub fn active_at(&self, now: DateTime<Local>) -> Option<bool> {
+        match self {
+            Self::Manual => None,
+            Self::Fixed { start, end } => {
+                let start = NaiveTime::parse_from_str(start, "%H:%M").ok()?;
+                let end = NaiveTime::parse_from_str(end, "%H:%M").ok()?;
+                let time = now.time();
+                if start <= end {
+                    Some(time >= start && time < end)
+                } else {
+                    Some(time >= start || time < end)
+                }
+            }
+            Self::Sun {
+                latitude,
+                longitude,
+            } => {
+                let (sunrise, sunset) = sun_times(now.date_naive(), *latitude, *longitude)?;
+                let now = now.with_timezone(&Utc);
+                Some(now < sunrise || now >= sunset)
+            }
+        }
+    }
+}
+
+/// Applies a colour temperature to every output
+enum GammaBackend {
+    /// `hyprctl hyprsunset`, needs hyprsunset running
+    Hyprsunset,
+    /// `gammastep -O` through wlr-gamma-control. The gamma table is reset once the process
+    /// exits, so it is kept alive while the night light is on.
+    Gammastep(Option<Child>),
+}
+impl GammaBackend {
+    fn detect() -> Self {
+        if std::env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
+            Self::Hyprsunset
+        } else {
+            Self::Gammastep(None)
+        }
+    }
+
+    fn apply(&mut self, temperature: u32) -> Result<(), WatsonError> {
+        match self {
+            Self::Hyprsunset => {
+                let mut command = Command::new("hyprctl");
+                if temperature >= IDENTITY_TEMPERATURE {
+                    command.args(["hyprsunset", "identity"]);
+                } else {
+                    command.args(["hyprsunset", "temperature", &temperature.to_string()]);
+                }
+                command
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .map(|_| ())
+                    .map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, e.to_string()))
+            }
+            Self::Gammastep(child) => {
+                if let Some(mut old) = child.take() {
+                    let _ = old.kill();
+                    let _ = old.wait();
+                }
+                if temperature >= IDENTITY_TEMPERATURE {
+                    return Ok(());
+                }
+                let spawned = Command::new("gammastep")
+                    .args(["-P", "-O", &temperature.to_string()])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, e.to_string()))?;
+                child.replace(spawned);
+                Ok(())
+            }
+        }
+    }
+}
+impl Drop for GammaBackend {
+    fn drop(&mut self) {
+        if let Self::Gammastep(Some(child)) = self {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+pub struct NightLight {
+    config: NightLightConfig,
+    enabled: bool,
+    intensity: u8,
+    /// Last state the schedule asked for. A manual toggle holds until it changes.
+    scheduled: Option<bool>,
+    backend: GammaBackend,
+}
+impl NightLight {
+    pub fn new() -> Self {
+        let config = NightLightConfig::load().unwrap_or_else(|e| {
+            eprintln!("{:?}", e);
+            NightLightConfig::default()
+        });
+        Self {
+            intensity: config.intensity.min(100),
+            config,
+            enabled: false,
+            scheduled: None,
+            backend: GammaBackend::detect(),
+        }
+    }
+
+    pub fn temperature(&self) -> u32 {
+        if !self.enabled {
+            return IDENTITY_TEMPERATURE;
+        }
+        let min = self.config.min_temperature.min(IDENTITY_TEMPERATURE);
+        IDENTITY_TEMPERATURE - (IDENTITY_TEMPERATURE - min) * self.intensity as u32 / 100
+    }
+
+    /// Follows the schedule. Returns true if the state changed.
+    fn tick(&mut self, now: DateTime<Local>) -> Result<bool, WatsonError> {
+        let scheduled = self.config.schedule.active_at(now);
+        if scheduled == self.scheduled {
+            return Ok(false);
+        }
+        self.scheduled = scheduled;
+        match scheduled {
+            Some(enabled) if enabled != self.enabled => {
+                self.enabled = enabled;
+                self.backend.apply(self.temperature())?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+impl HardwareController {
+    // ----- Night Light -----
+    pub fn get_night_light(&self) -> (bool, u8) {
+        (self.night_light.enabled, self.night_light.intensity)
+    }
+    pub fn set_night_light(&mut self, enabled: bool) -> Result<(), WatsonError> {
+        self.night_light.enabled = enabled;
+        self.night_light
+            .backend
+            .apply(self.night_light.temperature())
+    }
+    pub fn set_night_light_intensity(&mut self, percent: u8) -> Result<(), WatsonError> {
+        self.night_light.intensity = percent.min(100);
+        if !self.night_light.enabled {
+            return Ok(());
+        }
+        self.night_light
+            .backend
+            .apply(self.night_light.temperature())
+    }
+}
+
+/// Switches the night light on and off following the configured schedule, checked every minute
+pub fn schedule_night_light(
+    scheduler: &Scheduler,
+    daemon: Arc<RwLock<NotificationDaemon>>,
+) -> Result<(), WatsonError> {
+    scheduler.register(
+        "night-light",
+        JobSchedule::Cron("* * * * *".into()),
+        move || {
+            let daemon = Arc::clone(&daemon);
+            async move {
+                let mut daemon = daemon.write().await;
+                match daemon.hardware.night_light.tick(Local::now()) {
+                    Ok(true) => {
+                        let (enabled, intensity) = daemon.hardware.get_night_light();
+                        let _result = DAEMON_TX
+                            .get()
+                            .map(|d| d.send(InternalMessage::NightLight { enabled, intensity }));
+                    }
+                    Ok(false) => {}
+                    Err(e) => eprintln!("{:?}", e),
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_fixed_schedule_wraps_midnight() {
+        let schedule = NightLightSchedule::Fixed {
+            start: "21:00".into(),
+            end: "07:00".into(),
+        };
+        let at = |h, m| Local.with_ymd_and_hms(2024, 3, 1, h, m, 0).unwrap();
+        assert_eq!(schedule.active_at(at(22, 0)), Some(true));
+        assert_eq!(schedule.active_at(at(6, 59)), Some(true));
+        assert_eq!(schedule.active_at(at(7, 0)), Some(false));
+        assert_eq!(schedule.active_at(at(12, 0)), Some(false));
+    }
+}
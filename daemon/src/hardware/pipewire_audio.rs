@@ -0,0 +1,381 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::Cursor,
+    rc::Rc,
+    sync::{
+        Arc,
+        atomic::{AtomicU8, Ordering},
+    },
+};
+
+use ::pipewire as pw;
+use pw::{
+    metadata::{Metadata, MetadataListener},
+    node::{Node, NodeListener},
+    spa::{
+        param::ParamType,
+        pod::{
+            Object, Pod, Property, PropertyFlags, Value, ValueArray, deserialize::PodDeserializer,
+            serialize::PodSerializer,
+        },
+        utils::SpaTypes,
+    },
+    types::ObjectType,
+};
+use suite_223b::{
+    protocol::DaemonService,
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::sync::{Notify, mpsc, oneshot};
+
+use crate::{
+    core::registry::ServiceRegistry,
+    hardware::audio::{AudioCommand, BACKOFF, MAX_BACKOFF, report_volume, set_available, wait},
+};
+
+/// Key of the default sink in the `default` metadata object
+const DEFAULT_SINK_KEY: &str = "default.audio.sink";
+
+/// Same job as `audio_actor`, but talks to PipeWire directly. PipeWire objects can't leave their
+/// loop, so every connection runs on its own thread and commands are forwarded to it.
+pub async fn pipewire_actor(
+    mut rx: mpsc::Receiver<AudioCommand>,
+    wake_signal: Arc<Notify>,
+    register: Arc<ServiceRegistry>,
+) -> Result<(), WatsonError> {
+    pw::init();
+    let last_percentage = Arc::new(AtomicU8::new(0));
+    let mut backoff = BACKOFF;
+    loop {
+        let (pw_tx, pw_rx) = pw::channel::channel::<AudioCommand>();
+        let (done_tx, mut done_rx) = oneshot::channel::<Result<(), WatsonError>>();
+        std::thread::spawn({
+            let last_percentage = Arc::clone(&last_percentage);
+            move || {
+                let _ = done_tx.send(run_session(pw_rx, last_percentage));
+            }
+        });
+
+        let ended = loop {
+            // Ghost check
+            loop {
+                if register.is_active(DaemonService::AudioService) {
+                    break;
+                }
+                println!("AudioService not registered.");
+
+                wake_signal.notified().await;
+            }
+
+            tokio::select! {
+                result = &mut done_rx => break result,
+                cmd = rx.recv() => match cmd {
+                    Some(cmd) => {
+                        let _ = pw_tx.send(cmd);
+                    }
+                    None => return Ok(()),
+                },
+            }
+        };
+        set_available(false);
+        match ended {
+            // Was connected, try again right away
+            Ok(Ok(())) => {
+                eprintln!("Lost the audio server, reconnecting.");
+                backoff = BACKOFF;
+            }
+            Ok(Err(e)) => eprintln!("{:?}", e),
+            Err(_) => {
+                return Err(watson_err!(
+                    WatsonErrorKind::Audio,
+                    "The PipeWire thread stopped unexpectedly"
+                ));
+            }
+        }
+        if !wait(&mut rx, backoff, &last_percentage).await {
+            return Ok(());
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Connects and runs the loop until the server goes away. Errors if it can't connect at all.
+fn run_session(
+    commands: pw::channel::Receiver<AudioCommand>,
+    last_percentage: Arc<AtomicU8>,
+) -> Result<(), WatsonError> {
+    let pw_err = |e: pw::Error| watson_err!(WatsonErrorKind::Audio, e.to_string());
+    let mainloop = pw::main_loop::MainLoop::new(None).map_err(pw_err)?;
+    let context = pw::context::Context::new(&mainloop).map_err(pw_err)?;
+    let core = context.connect(None).map_err(pw_err)?;
+    let registry = Rc::new(core.get_registry().map_err(pw_err)?);
+    println!("PipeWire Connected.");
+    set_available(true);
+
+    let sinks = Rc::new(RefCell::new(Sinks::default()));
+
+    // Errors on the core object mean the connection is gone
+    let _core_listener = core
+        .add_listener_local()
+        .error({
+            let mainloop = mainloop.clone();
+            move |id, _seq, res, message| {
+                if id == pw::core::PW_ID_CORE {
+                    eprintln!("PipeWire: {} ({})", message, res);
+                    mainloop.quit();
+                }
+            }
+        })
+        .register();
+
+    let _registry_listener = registry
+        .add_listener_local()
+        .global({
+            let registry = Rc::downgrade(&registry);
+            let sinks = Rc::downgrade(&sinks);
+            let last_percentage = Arc::clone(&last_percentage);
+            move |global| {
+                let (Some(registry), Some(sinks)) = (registry.upgrade(), sinks.upgrade()) else {
+                    return;
+                };
+                let prop = |key: &str| global.props.and_then(|p| p.get(key));
+                match global.type_ {
+                    ObjectType::Node if prop("media.class") == Some("Audio/Sink") => {
+                        let name = prop("node.name").unwrap_or_default().to_string();
+                        match registry.bind::<Node, _>(global) {
+                            Ok(node) => Sinks::add(&sinks, global.id, name, node, &last_percentage),
+                            Err(e) => eprintln!("Could not bind sink {}: {}", name, e),
+                        }
+                    }
+                    ObjectType::Metadata if prop("metadata.name") == Some("default") => {
+                        match registry.bind::<Metadata, _>(global) {
+                            Ok(metadata) => {
+                                Sinks::follow_default(&sinks, metadata, &last_percentage)
+                            }
+                            Err(e) => eprintln!("Could not bind metadata: {}", e),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .global_remove({
+            let sinks = Rc::downgrade(&sinks);
+            move |id| {
+                if let Some(sinks) = sinks.upgrade() {
+                    sinks.borrow_mut().nodes.remove(&id);
+                }
+            }
+        })
+        .register();
+
+    let _commands = commands.attach(mainloop.loop_(), {
+        let sinks = Rc::clone(&sinks);
+        move |cmd| sinks.borrow_mut().handle(cmd, &last_percentage)
+    });
+
+    mainloop.run();
+    Ok(())
+}
+
+struct Sink {
+    name: String,
+    node: Node,
+    _listener: NodeListener,
+    /// Linear per-channel volumes, as PipeWire reports them
+    volumes: Vec<f32>,
+}
+
+#[derive(Default)]
+struct Sinks {
+    nodes: HashMap<u32, Sink>,
+    /// `node.name` of the default sink
+    default: Option<String>,
+    metadata: Option<(Metadata, MetadataListener)>,
+}
+impl Sinks {
+    fn add(
+        this: &Rc<RefCell<Self>>,
+        id: u32,
+        name: String,
+        node: Node,
+        last_percentage: &Arc<AtomicU8>,
+    ) {
+        let listener = node
+            .add_listener_local()
+            .param({
+                let this = Rc::downgrade(this);
+                let last_percentage = Arc::clone(last_percentage);
+                move |_seq, _id, _index, _next, param| {
+                    let (Some(this), Some(volumes)) =
+                        (this.upgrade(), param.and_then(channel_volumes))
+                    else {
+                        return;
+                    };
+                    let mut sinks = this.borrow_mut();
+                    if let Some(sink) = sinks.nodes.get_mut(&id) {
+                        sink.volumes = volumes;
+                    }
+                    if sinks.default_id() == Some(id)
+                        && let Some(percentage) = sinks.default_volume()
+                    {
+                        report_volume(&last_percentage, percentage);
+                    }
+                }
+            })
+            .register();
+        node.subscribe_params(&[ParamType::Props]);
+
+        this.borrow_mut().nodes.insert(
+            id,
+            Sink {
+                name,
+                node,
+                _listener: listener,
+                volumes: Vec::new(),
+            },
+        );
+    }
+
+    fn follow_default(
+        this: &Rc<RefCell<Self>>,
+        metadata: Metadata,
+        last_percentage: &Arc<AtomicU8>,
+    ) {
+        let listener = metadata
+            .add_listener_local()
+            .property({
+                let this = Rc::downgrade(this);
+                let last_percentage = Arc::clone(last_percentage);
+                move |_subject, key, _type, value| {
+                    if key == Some(DEFAULT_SINK_KEY)
+                        && let Some(this) = this.upgrade()
+                    {
+                        let mut sinks = this.borrow_mut();
+                        sinks.default = value.and_then(default_sink_name);
+                        if let Some(percentage) = sinks.default_volume() {
+                            report_volume(&last_percentage, percentage);
+                        }
+                    }
+                    0
+                }
+            })
+            .register();
+        this.borrow_mut().metadata = Some((metadata, listener));
+    }
+
+    fn default_id(&self) -> Option<u32> {
+        let default = self.default.as_deref()?;
+        self.nodes
+            .iter()
+            .find(|(_, sink)| sink.name == default)
+            .map(|(id, _)| *id)
+    }
+
+    fn default_sink(&mut self) -> Option<&mut Sink> {
+        let id = self.default_id()?;
+        self.nodes.get_mut(&id)
+    }
+
+    fn default_volume(&self) -> Option<u8> {
+        let sink = self.nodes.get(&self.default_id()?)?;
+        (!sink.volumes.is_empty()).then(|| to_percent(&sink.volumes))
+    }
+
+    fn handle(&mut self, cmd: AudioCommand, last_percentage: &AtomicU8) {
+        match cmd {
+            AudioCommand::SetVolume(v) => {
+                let Some(sink) = self.default_sink() else {
+                    return;
+                };
+                last_percentage.store(v, Ordering::Relaxed);
+                let channels = sink.volumes.len().max(2);
+                sink.volumes = vec![to_linear(v); channels];
+                match volume_pod(&sink.volumes) {
+                    Some(bytes) => {
+                        if let Some(pod) = Pod::from_bytes(&bytes) {
+                            sink.node.set_param(ParamType::Props, 0, pod);
+                        }
+                    }
+                    None => eprintln!("Could not build the volume for {}", sink.name),
+                }
+            }
+            AudioCommand::GetVolume { resp } => {
+                let percentage = self
+                    .default_volume()
+                    .unwrap_or_else(|| last_percentage.load(Ordering::Relaxed));
+                let _ = resp.send(percentage);
+            }
+            // Sink changes arrive through the node listeners
+            AudioCommand::VolumeFetch { .. } => {}
+        }
+    }
+}
+
+/// `{"name": "alsa_output.pci-0000_00_1f.3.analog-stereo"}` as stored in the metadata
+fn default_sink_name(value: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(value).ok()?;
+    value.get("name")?.as_str().map(String::from)
+}
+
+/// PipeWire volumes are linear, sliders use the cubic scale PulseAudio shows
+fn to_percent(volumes: &[f32]) -> u8 {
+    let avg = volumes.iter().sum::<f32>() / volumes.len() as f32;
+    (avg.cbrt() * 100.0).round() as u8
+}
+
+fn to_linear(percent: u8) -> f32 {
+    (percent as f32 / 100.0).powi(3)
+}
+
+fn channel_volumes(param: &Pod) -> Option<Vec<f32>> {
+    let (_, value) = PodDeserializer::deserialize_any_from(param.as_bytes()).ok()?;
+    let Value::Object(object) = value else {
+        return None;
+    };
+    object
+        .properties
+        .into_iter()
+        .find(|p| p.key == pw::spa::sys::SPA_PROP_channelVolumes)
+        .and_then(|p| match p.value {
+            Value::ValueArray(ValueArray::Float(volumes)) => Some(volumes),
+            _ => None,
+        })
+}
+
+fn volume_pod(volumes: &[f32]) -> Option<Vec<u8>> {
+    let value = Value::Object(Object {
+        type_: SpaTypes::ObjectParamProps.as_raw(),
+        id: ParamType::Props.as_raw(),
+        properties: vec![Property {
+            key: pw::spa::sys::SPA_PROP_channelVolumes,
+            flags: PropertyFlags::empty(),
+            value: Value::ValueArray(ValueArray::Float(volumes.to_vec())),
+        }],
+    });
+    PodSerializer::serialize(Cursor::new(Vec::new()), &value)
+        .ok()
+        .map(|(cursor, _)| cursor.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_sink_and_cubic_volume() {
+        assert_eq!(
+            default_sink_name(r#"{"name":"alsa_output.usb-headset"}"#).as_deref(),
+            Some("alsa_output.usb-headset")
+        );
+        assert_eq!(default_sink_name("{}"), None);
+        for percent in [0, 37, 100] {
+            assert_eq!(
+                to_percent(&[to_linear(percent), to_linear(percent)]),
+                percent
+            );
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use suite_223b::{
+    notification::Notification,
+    protocol::{IntoResponse, Request, Response},
+    utils::errors::{WatsonError, WatsonErrorDto, WatsonErrorKind},
+    watson_err,
+};
+use tokio::sync::RwLock;
+use zbus::{
+    Proxy, fdo,
+    proxy::MethodFlags,
+    zvariant::{DynamicType, Value},
+};
+
+use crate::{RequestHandler, notify::NotificationDaemon};
+
+/// Whether the service refused the call for lack of authorization
+fn is_denied(e: &zbus::Error) -> bool {
+    match e {
+        zbus::Error::MethodError(name, _, _) => matches!(
+            name.as_str(),
+            "org.freedesktop.DBus.Error.AccessDenied"
+                | "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired"
+                | "org.freedesktop.PolicyKit1.Error.NotAuthorized"
+        ),
+        zbus::Error::FDO(e) => matches!(
+            **e,
+            fdo::Error::AccessDenied(_) | fdo::Error::InteractiveAuthorizationRequired(_)
+        ),
+        _ => false,
+    }
+}
+
+/// Calls `method` and, if the service refuses, retries once allowing interactive authorization
+/// so polkit can ask through the user's authentication agent. `hint` tells the user how to grant
+/// the permission for good.
+pub(crate) async fn call_authorized<B>(
+    proxy: &Proxy<'_>,
+    method: &str,
+    body: &B,
+    hint: &str,
+) -> Result<(), WatsonError>
+where
+    B: Serialize + DynamicType,
+{
+    match proxy.call::<_, _, ()>(method, body).await {
+        Ok(()) => return Ok(()),
+        Err(e) if !is_denied(&e) => {
+            return Err(watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()));
+        }
+        Err(_) => {}
+    }
+
+    match proxy
+        .call_with_flags::<_, _, ()>(method, MethodFlags::AllowInteractiveAuth.into(), body)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) if is_denied(&e) => Err(watson_err!(
+            WatsonErrorKind::PermissionDenied,
+            "Not authorized to call {}.{}",
+            proxy.interface(),
+            method
+        )
+        .with_hint(hint)),
+        Err(e) => Err(watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string())),
+    }
+}
+
+/// `call_authorized` for `org.freedesktop.DBus.Properties.Set`
+pub(crate) async fn set_property_authorized(
+    proxy: &Proxy<'_>,
+    property: &str,
+    value: Value<'_>,
+    hint: &str,
+) -> Result<(), WatsonError> {
+    let properties = Proxy::new(
+        proxy.connection(),
+        proxy.destination().to_owned(),
+        proxy.path().to_owned(),
+        "org.freedesktop.DBus.Properties",
+    )
+    .await
+    .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+
+    call_authorized(
+        &properties,
+        "Set",
+        &(proxy.interface().as_str(), property, value),
+        hint,
+    )
+    .await
+}
+
+/// Handles `request`, running the ones whose backends use `call_authorized` without holding
+/// the daemon. polkit may be waiting for the user to type a password meanwhile.
+pub async fn handle_authorized(daemon: &RwLock<NotificationDaemon>, request: Request) -> Response {
+    match request {
+        Request::SetPowerMode(mode) => {
+            let power = daemon.read().await.hardware.power();
+            power.set_powermode(mode.into()).await.into_response()
+        }
+        Request::SetBacklight {
+            device: None,
+            percent,
+        } => {
+            let backlight = daemon.read().await.hardware.backlight();
+            backlight.set_brightness(percent).await.into_response()
+        }
+        request => request.handle(&mut *daemon.write().await).await,
+    }
+}
+
+/// Tells the user about a refused request through our own notification server. The `retry`
+/// action runs the request again, e.g. after starting a polkit agent.
+pub async fn notify_permission_denied(
+    daemon: &Arc<RwLock<NotificationDaemon>>,
+    request: Request,
+    error: WatsonErrorDto,
+) {
+    let body = match &error.hint {
+        Some(hint) => format!("{}\n{}", error.message, hint),
+        None => error.message,
+    };
+    let notification = Notification {
+        app_name: "Watson".into(),
+        app_icon: "dialog-password-symbolic".into(),
+        summary: "Permission denied".into(),
+        body,
+        actions: vec!["retry".into(), "Retry".into()],
+        expire_timeout: -1,
+        ..Default::default()
+    };
+    let weak = Arc::downgrade(daemon);
+    let on_action = Box::new({
+        let weak = weak.clone();
+        move |action: Option<&str>| {
+            let Some(daemon) = weak.upgrade().filter(|_| action == Some("retry")) else {
+                return;
+            };
+            let request = request.clone();
+            tokio::spawn(async move {
+                let resp = handle_authorized(&daemon, request).await;
+                if let Response::Error(e) = resp {
+                    eprintln!("Retry failed: {}", e.message);
+                }
+            });
+        }
+    });
+    daemon
+        .write()
+        .await
+        .insert_with_action(notification, weak, on_action);
+}
@@ -1,44 +1,210 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
 use suite_223b::{
-    protocol::PowerMode,
+    protocol::{InternalMessage, PowerMode, PowerProfiles, ProfileHold},
     utils::errors::{WatsonError, WatsonErrorKind},
     watson_err,
 };
-use zbus::Proxy;
+use tokio::sync::RwLock;
+use zbus::{
+    Connection, Proxy,
+    zvariant::{OwnedValue, Value},
+};
 
-use crate::hardware::HardwareController;
+use crate::{
+    DAEMON_TX,
+    hardware::{HardwareController, polkit::set_property_authorized},
+    notify::NotificationDaemon,
+};
 
-impl HardwareController {
-    // ----- Power Mode -----
-    /// Requires `power-profiles-daemon` installed and running
-    pub async fn set_powermode(&self, mode: PowerMode) -> Result<(), WatsonError> {
-        let proxy = Proxy::new(
-            &self.conn,
-            "net.hadess.PowerProfiles",
-            "/net/hadess/PowerProfiles",
-            "net.hadess.PowerProfiles",
-        )
+const PPD_NAME: &str = "net.hadess.PowerProfiles";
+const PPD_PATH: &str = "/net/hadess/PowerProfiles";
+/// Shown by `powerprofilesctl` next to our holds
+const APPLICATION_ID: &str = "dev.skxxtz.Watson";
+
+async fn ppd_proxy(conn: &Connection) -> Result<Proxy<'static>, WatsonError> {
+    Proxy::new(conn, PPD_NAME, PPD_PATH, PPD_NAME)
         .await
-        .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+        .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))
+}
+
+/// `Profiles` and `ActiveProfileHolds` are lists of dicts with string values
+fn dict_str(dict: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+    dict.get(key).and_then(|v| String::try_from(v.clone()).ok())
+}
+
+async fn power_profiles(conn: &Connection) -> Result<PowerProfiles, WatsonError> {
+    let proxy = ppd_proxy(conn).await?;
+
+    let active: PowerMode = proxy
+        .get_property("ActiveProfile")
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertyGet, e.to_string()))?;
+    let profiles: Vec<HashMap<String, OwnedValue>> = proxy
+        .get_property("Profiles")
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertyGet, e.to_string()))?;
+    // Older versions don't have holds
+    let holds: Vec<HashMap<String, OwnedValue>> = proxy
+        .get_property("ActiveProfileHolds")
+        .await
+        .unwrap_or_default();
+    let degraded: String = proxy
+        .get_property("PerformanceDegraded")
+        .await
+        .unwrap_or_default();
+
+    let mut available: Vec<PowerMode> = profiles
+        .iter()
+        .filter_map(|p| dict_str(p, "Profile")?.parse().ok())
+        .collect();
+    available.sort_by_key(|&m| u8::from(m));
+
+    Ok(PowerProfiles {
+        active,
+        available,
+        degraded: (!degraded.is_empty()).then_some(degraded),
+        holds: holds
+            .iter()
+            .filter_map(|h| {
+                Some(ProfileHold {
+                    application: dict_str(h, "ApplicationId")?,
+                    profile: dict_str(h, "Profile")?.parse().ok()?,
+                    reason: dict_str(h, "Reason").unwrap_or_default(),
+                })
+            })
+            .collect(),
+    })
+}
+
+/// Power profile switching and holds
+#[async_trait]
+pub trait PowerBackend: Send + Sync {
+    async fn powermode(&self) -> Result<PowerMode, WatsonError>;
+    async fn set_powermode(&self, mode: PowerMode) -> Result<(), WatsonError>;
+    async fn profiles(&self) -> Result<PowerProfiles, WatsonError>;
+    /// Returns the cookie that releases the hold
+    async fn hold(&self, mode: PowerMode, reason: &str) -> Result<u32, WatsonError>;
+    async fn release(&self, cookie: u32) -> Result<(), WatsonError>;
+}
+
+/// Requires `power-profiles-daemon` installed and running
+pub struct PowerProfilesDaemon {
+    conn: Connection,
+}
+impl PowerProfilesDaemon {
+    pub fn new(conn: Connection) -> Self {
+        Self { conn }
+    }
+}
+#[async_trait]
+impl PowerBackend for PowerProfilesDaemon {
+    async fn set_powermode(&self, mode: PowerMode) -> Result<(), WatsonError> {
+        let proxy = ppd_proxy(&self.conn).await?;
 
         // Note: power-profiles-daemon expects the string representation
+        set_property_authorized(
+            &proxy,
+            "ActiveProfile",
+            Value::from(mode.to_string()),
+            "Start a polkit authentication agent or allow net.hadess.PowerProfiles.switch-profile for your user",
+        )
+        .await
+    }
+    async fn powermode(&self) -> Result<PowerMode, WatsonError> {
+        let proxy = ppd_proxy(&self.conn).await?;
+
         proxy
-            .set_property("ActiveProfile", mode.to_string())
+            .get_property("ActiveProfile")
             .await
             .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertySet, e.to_string()))
     }
+    async fn profiles(&self) -> Result<PowerProfiles, WatsonError> {
+        power_profiles(&self.conn).await
+    }
+    async fn hold(&self, mode: PowerMode, reason: &str) -> Result<u32, WatsonError> {
+        let proxy = ppd_proxy(&self.conn).await?;
+        proxy
+            .call(
+                "HoldProfile",
+                &(mode.to_string().as_str(), reason, APPLICATION_ID),
+            )
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))
+    }
+    async fn release(&self, cookie: u32) -> Result<(), WatsonError> {
+        let proxy = ppd_proxy(&self.conn).await?;
+        proxy
+            .call::<_, _, ()>("ReleaseProfile", &(cookie,))
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))
+    }
+}
+
+impl HardwareController {
+    // ----- Power Mode -----
+    /// The backend on its own, so a switch waiting on polkit doesn't hold the daemon
+    pub fn power(&self) -> Arc<dyn PowerBackend> {
+        Arc::clone(&self.power)
+    }
+    pub async fn set_powermode(&self, mode: PowerMode) -> Result<(), WatsonError> {
+        self.power.set_powermode(mode).await
+    }
     pub async fn get_powermode(&self) -> Result<PowerMode, WatsonError> {
-        let proxy = Proxy::new(
-            &self.conn,
-            "net.hadess.PowerProfiles",
-            "/net/hadess/PowerProfiles",
-            "net.hadess.PowerProfiles",
-        )
+        self.power.powermode().await
+    }
+    pub async fn get_power_profiles(&self) -> Result<PowerProfiles, WatsonError> {
+        self.power.profiles().await
+    }
+    /// Replaces our previous hold, if any. power-profiles-daemon drops it by itself once the
+    /// daemon exits.
+    pub async fn hold_powermode(
+        &mut self,
+        mode: PowerMode,
+        reason: &str,
+    ) -> Result<(), WatsonError> {
+        self.release_powermode().await?;
+        self.profile_hold = Some(self.power.hold(mode, reason).await?);
+        Ok(())
+    }
+    pub async fn release_powermode(&mut self) -> Result<(), WatsonError> {
+        let Some(cookie) = self.profile_hold.take() else {
+            return Ok(());
+        };
+        self.power.release(cookie).await
+    }
+}
+
+/// Broadcasts profile switches, holds and performance degradation, whoever caused them
+pub async fn power_profiles_listener(
+    daemon: Arc<RwLock<NotificationDaemon>>,
+) -> Result<(), WatsonError> {
+    let conn = daemon.read().await.hardware.conn.clone();
+    let properties = Proxy::new(&conn, PPD_NAME, PPD_PATH, "org.freedesktop.DBus.Properties")
         .await
         .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+    let mut stream = properties
+        .receive_signal("PropertiesChanged")
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))?;
 
-        proxy
-            .get_property("ActiveProfile")
-            .await
-            .map_err(|e| watson_err!(WatsonErrorKind::DBusPropertySet, e.to_string()))
+    let mut last = power_profiles(&conn).await.ok();
+    while stream.next().await.is_some() {
+        let current = match power_profiles(&conn).await {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                continue;
+            }
+        };
+        if last.as_ref() != Some(&current) {
+            last = Some(current.clone());
+            let _result = DAEMON_TX
+                .get()
+                .map(|d| d.send(InternalMessage::PowerProfiles(current)));
+        }
     }
+    Ok(())
 }
@@ -0,0 +1,425 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::Path,
+    process::Stdio,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use serde_json::Value;
+use suite_223b::{
+    notification::{HintValue, Notification, Urgency},
+    protocol::{DeviceUse, InternalMessage},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+    sync::RwLock,
+};
+
+use crate::{DAEMON_TX, notify::NotificationDaemon};
+
+/// Without PipeWire, open device files are looked for this often
+const PROC_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Before following the graph again after PipeWire restarted
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
+/// Who uses the camera and the microphone, as last seen by `device_use_listener`
+#[derive(Debug, Default)]
+pub struct DeviceMonitor {
+    current: Mutex<DeviceUse>,
+}
+impl DeviceMonitor {
+    pub fn current(&self) -> DeviceUse {
+        self.current.lock().expect("Poisoned").clone()
+    }
+
+    /// Stores `next`, returning the applications that started using a device since the last
+    /// state. `None` if nothing changed.
+    fn update(&self, next: DeviceUse) -> Option<DeviceUse> {
+        let mut current = self.current.lock().expect("Poisoned");
+        if *current == next {
+            return None;
+        }
+        let started = |now: &[String], before: &[String]| -> Vec<String> {
+            now.iter()
+                .filter(|a| !before.contains(a))
+                .cloned()
+                .collect()
+        };
+        let started = DeviceUse {
+            camera: started(&next.camera, &current.camera),
+            microphone: started(&next.microphone, &current.microphone),
+        };
+        *current = next;
+        Some(started)
+    }
+
+    /// Forgets the last state once the listener stopped, clients are told nothing is in use
+    pub fn clear(&self) {
+        if self.update(DeviceUse::default()).is_some() {
+            let _result = DAEMON_TX
+                .get()
+                .map(|d| d.send(InternalMessage::DeviceUse(DeviceUse::default())));
+        }
+    }
+
+    async fn publish(&self, next: DeviceUse, daemon: &Weak<RwLock<NotificationDaemon>>) {
+        let Some(started) = self.update(next.clone()) else {
+            return;
+        };
+        let _result = DAEMON_TX
+            .get()
+            .map(|d| d.send(InternalMessage::DeviceUse(next)));
+        if started.is_active() {
+            notify_device_use(daemon, &started).await;
+        }
+    }
+}
+
+/// Follows the PipeWire graph through `pw-dump --monitor`. Where that isn't available, the
+/// processes holding `/dev/video*` or a capture PCM open are polled instead.
+pub async fn device_use_listener(
+    monitor: Arc<DeviceMonitor>,
+    daemon: Weak<RwLock<NotificationDaemon>>,
+    pipewire: bool,
+) -> Result<(), WatsonError> {
+    if pipewire {
+        loop {
+            match watch_pipewire(&monitor, &daemon).await {
+                Ok(()) => tokio::time::sleep(RESTART_DELAY).await,
+                Err(e) => {
+                    eprintln!("{:?}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut interval = tokio::time::interval(PROC_POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let used = tokio::task::spawn_blocking(scan_proc)
+            .await
+            .map_err(|e| watson_err!(WatsonErrorKind::TaskJoin, e.to_string()))?;
+        monitor.publish(used, &daemon).await;
+    }
+}
+
+/// Returns once `pw-dump` exits, e.g. because PipeWire restarted
+async fn watch_pipewire(
+    monitor: &DeviceMonitor,
+    daemon: &Weak<RwLock<NotificationDaemon>>,
+) -> Result<(), WatsonError> {
+    let mut child = Command::new("pw-dump")
+        .args(["--monitor", "--no-colors"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, e.to_string()))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| watson_err!(WatsonErrorKind::CommandExecute, "pw-dump without output"))?;
+    let mut lines = BufReader::new(stdout).lines();
+
+    let mut graph = PipewireGraph::default();
+    // Each update is a pretty printed array, whose brackets are the only unindented lines
+    let mut batch = String::new();
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?
+    {
+        batch.push_str(&line);
+        batch.push('\n');
+        if line != "]" {
+            continue;
+        }
+        match serde_json::from_str::<Vec<Value>>(&batch) {
+            Ok(objects) => {
+                graph.apply(&objects);
+                monitor.publish(graph.device_use(), daemon).await;
+            }
+            Err(e) => eprintln!("{:?}", e),
+        }
+        batch.clear();
+    }
+    let _ = child.wait().await;
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    class: String,
+    app: Option<String>,
+    /// Set on nodes of real devices, e.g. `v4l2` or `alsa`
+    device_api: Option<String>,
+    running: bool,
+}
+
+/// Nodes and links of the PipeWire graph, as far as `pw-dump` told about them
+#[derive(Debug, Default)]
+struct PipewireGraph {
+    nodes: HashMap<u64, Node>,
+    /// Output and input node of each link
+    links: HashMap<u64, (u64, u64)>,
+}
+impl PipewireGraph {
+    /// Updates only carry what changed, objects without `info` are gone
+    fn apply(&mut self, objects: &[Value]) {
+        for object in objects {
+            let Some(id) = object["id"].as_u64() else {
+                continue;
+            };
+            let info = &object["info"];
+            if info.is_null() {
+                self.nodes.remove(&id);
+                self.links.remove(&id);
+                continue;
+            }
+            match object["type"].as_str() {
+                Some("PipeWire:Interface:Node") => {
+                    let node = self.nodes.entry(id).or_default();
+                    if let Some(state) = info["state"].as_str() {
+                        node.running = state == "running";
+                    }
+                    let props = &info["props"];
+                    if props.is_object() {
+                        let prop = |key: &str| props[key].as_str().map(str::to_string);
+                        node.class = prop("media.class").unwrap_or_default();
+                        node.app = prop("application.name")
+                            .or_else(|| prop("application.process.binary"))
+                            .or_else(|| prop("node.name"));
+                        node.device_api = prop("device.api");
+                    }
+                }
+                Some("PipeWire:Interface:Link") => {
+                    if let (Some(output), Some(input)) = (
+                        info["output-node-id"].as_u64(),
+                        info["input-node-id"].as_u64(),
+                    ) {
+                        self.links.insert(id, (output, input));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Running streams fed by a camera or a microphone. Screencasts come from sources without a
+    /// device, level meters from the monitor of a sink.
+    fn device_use(&self) -> DeviceUse {
+        let mut camera = BTreeSet::new();
+        let mut microphone = BTreeSet::new();
+        for (output, input) in self.links.values() {
+            let (Some(source), Some(stream)) = (self.nodes.get(output), self.nodes.get(input))
+            else {
+                continue;
+            };
+            if !stream.running || !stream.class.starts_with("Stream/Input/") {
+                continue;
+            }
+            let Some(app) = stream.app.clone() else {
+                continue;
+            };
+            match source.class.as_str() {
+                "Video/Source" if source.device_api.is_some() => camera.insert(app),
+                "Audio/Source" => microphone.insert(app),
+                _ => false,
+            };
+        }
+        DeviceUse {
+            camera: camera.into_iter().collect(),
+            microphone: microphone.into_iter().collect(),
+        }
+    }
+}
+
+enum Device {
+    Camera,
+    Microphone,
+}
+
+/// `/dev/video0` is a camera, `/dev/snd/pcmC0D0c` a capture device
+fn classify(path: &Path) -> Option<Device> {
+    let path = path.to_str()?;
+    if let Some(n) = path.strip_prefix("/dev/video") {
+        return n
+            .chars()
+            .all(|c| c.is_ascii_digit())
+            .then_some(Device::Camera);
+    }
+    let pcm = path.strip_prefix("/dev/snd/pcmC")?;
+    pcm.ends_with('c').then_some(Device::Microphone)
+}
+
+/// Processes of other users can't be looked into, which is fine for a session daemon
+fn scan_proc() -> DeviceUse {
+    let mut camera = BTreeSet::new();
+    let mut microphone = BTreeSet::new();
+    let Ok(processes) = fs::read_dir("/proc") else {
+        return DeviceUse::default();
+    };
+    for process in processes.flatten() {
+        let dir = process.path();
+        let Ok(fds) = fs::read_dir(dir.join("fd")) else {
+            continue;
+        };
+        let devices = fds
+            .flatten()
+            .filter_map(|fd| fs::read_link(fd.path()).ok())
+            .filter_map(|target| classify(&target));
+        let (mut uses_camera, mut uses_microphone) = (false, false);
+        for device in devices {
+            match device {
+                Device::Camera => uses_camera = true,
+                Device::Microphone => uses_microphone = true,
+            }
+        }
+        if !uses_camera && !uses_microphone {
+            continue;
+        }
+        let Ok(name) = fs::read_to_string(dir.join("comm")) else {
+            continue;
+        };
+        let name = name.trim().to_string();
+        if uses_camera {
+            camera.insert(name.clone());
+        }
+        if uses_microphone {
+            microphone.insert(name);
+        }
+    }
+    DeviceUse {
+        camera: camera.into_iter().collect(),
+        microphone: microphone.into_iter().collect(),
+    }
+}
+
+/// Kept in the notification history, so it can be looked up later who listened when
+async fn notify_device_use(daemon: &Weak<RwLock<NotificationDaemon>>, started: &DeviceUse) {
+    let Some(strong) = daemon.upgrade() else {
+        return;
+    };
+    let (icon, summary) = match (started.camera.is_empty(), started.microphone.is_empty()) {
+        (false, false) => ("camera-web-symbolic", "Camera and microphone in use"),
+        (false, true) => ("camera-web-symbolic", "Camera in use"),
+        _ => ("audio-input-microphone-symbolic", "Microphone in use"),
+    };
+    let apps: BTreeSet<&String> = started.camera.iter().chain(&started.microphone).collect();
+    let body = apps
+        .into_iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let notification = Notification {
+        app_name: "Watson".into(),
+        app_icon: icon.into(),
+        summary: summary.into(),
+        body,
+        hints: HashMap::from([("category".into(), HintValue::String("device".into()))]),
+        expire_timeout: -1,
+        urgency: Urgency::Low,
+        ..Default::default()
+    };
+    strong.write().await.insert(notification, daemon.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graph_finds_streams_of_devices() {
+        let dump: Vec<Value> = serde_json::from_str(
+            r#"[
+              {"id": 40, "type": "PipeWire:Interface:Node", "info": {"state": "suspended",
+                "props": {"media.class": "Video/Source", "device.api": "v4l2"}}},
+              {"id": 41, "type": "PipeWire:Interface:Node", "info": {"state": "running",
+                "props": {"media.class": "Audio/Source", "device.api": "alsa"}}},
+              {"id": 42, "type": "PipeWire:Interface:Node", "info": {"state": "running",
+                "props": {"media.class": "Video/Source", "node.name": "xdpw_stream"}}},
+              {"id": 50, "type": "PipeWire:Interface:Node", "info": {"state": "running",
+                "props": {"media.class": "Stream/Input/Video", "application.name": "Firefox"}}},
+              {"id": 51, "type": "PipeWire:Interface:Node", "info": {"state": "running",
+                "props": {"media.class": "Stream/Input/Audio", "application.process.binary": "zoom"}}},
+              {"id": 52, "type": "PipeWire:Interface:Node", "info": {"state": "running",
+                "props": {"media.class": "Stream/Input/Video", "application.name": "OBS"}}},
+              {"id": 60, "type": "PipeWire:Interface:Link", "info": {"output-node-id": 40, "input-node-id": 50}},
+              {"id": 61, "type": "PipeWire:Interface:Link", "info": {"output-node-id": 41, "input-node-id": 51}},
+              {"id": 62, "type": "PipeWire:Interface:Link", "info": {"output-node-id": 42, "input-node-id": 52}}
+            ]"#,
+        )
+        .unwrap();
+        let mut graph = PipewireGraph::default();
+        graph.apply(&dump);
+        // The screencast isn't a camera
+        assert_eq!(
+            graph.device_use(),
+            DeviceUse {
+                camera: vec!["Firefox".into()],
+                microphone: vec!["zoom".into()],
+            }
+        );
+
+        // A state change without props, then the microphone stream goes away
+        let update: Vec<Value> = serde_json::from_str(
+            r#"[
+              {"id": 50, "type": "PipeWire:Interface:Node", "info": {"state": "idle"}},
+              {"id": 51, "info": null}
+            ]"#,
+        )
+        .unwrap();
+        graph.apply(&update);
+        assert!(!graph.device_use().is_active());
+    }
+
+    #[test]
+    fn test_classify_device_files() {
+        assert!(matches!(
+            classify(Path::new("/dev/video0")),
+            Some(Device::Camera)
+        ));
+        assert!(matches!(
+            classify(Path::new("/dev/snd/pcmC0D0c")),
+            Some(Device::Microphone)
+        ));
+        // Playback and control devices
+        assert!(classify(Path::new("/dev/snd/pcmC0D0p")).is_none());
+        assert!(classify(Path::new("/dev/snd/controlC0")).is_none());
+        assert!(classify(Path::new("/dev/video-foo")).is_none());
+    }
+
+    #[test]
+    fn test_only_new_applications_started() {
+        let monitor = DeviceMonitor::default();
+        let started = monitor
+            .update(DeviceUse {
+                camera: vec![],
+                microphone: vec!["zoom".into()],
+            })
+            .unwrap();
+        assert_eq!(started.microphone, ["zoom"]);
+
+        let started = monitor
+            .update(DeviceUse {
+                camera: vec!["zoom".into()],
+                microphone: vec!["firefox".into(), "zoom".into()],
+            })
+            .unwrap();
+        assert_eq!(started.camera, ["zoom"]);
+        assert_eq!(started.microphone, ["firefox"]);
+
+        // Stopping changes the state without starting anything
+        let started = monitor.update(DeviceUse::default()).unwrap();
+        assert!(!started.is_active());
+        assert!(monitor.update(DeviceUse::default()).is_none());
+    }
+}
@@ -0,0 +1,97 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use futures_util::StreamExt;
+use suite_223b::{
+    protocol::{InternalMessage, SystemStateRaw},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::{sync::RwLock, time::MissedTickBehavior};
+use zbus::{MatchRule, MessageStream, message::Type, zvariant::OwnedValue};
+
+use crate::{DAEMON_TX, hardware::SystemStateBuilder, notify::NotificationDaemon};
+
+/// Properties behind the toggles, keyed by the interface that reports them
+const WATCHED: &[(&str, &str)] = &[
+    ("org.freedesktop.NetworkManager", "WirelessEnabled"),
+    ("org.bluez.Adapter1", "Powered"),
+    ("org.bluez.Device1", "Connected"),
+    ("org.bluez.Battery1", "Percentage"),
+    ("org.bluez.MediaTransport1", "Codec"),
+    ("org.bluez.MediaTransport1", "Configuration"),
+];
+
+/// Safety net for changes that come without a signal, e.g. brightness keys handled by the
+/// kernel or a service that restarted
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Broadcasts the system state whenever it was changed outside of Watson, e.g. through
+/// `nmcli radio wifi off`. Power profiles have their own listener.
+pub async fn system_state_listener(
+    daemon: Arc<RwLock<NotificationDaemon>>,
+) -> Result<(), WatsonError> {
+    let conn = daemon.read().await.hardware.conn.clone();
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .interface("org.freedesktop.DBus.Properties")
+        .and_then(|b| b.member("PropertiesChanged"))
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))?
+        .build();
+    let mut stream = MessageStream::for_match_rule(rule, &conn, None)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))?;
+
+    let mut interval = tokio::time::interval(RECONCILE_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    let mut last = snapshot(&daemon).await.ok();
+    loop {
+        tokio::select! {
+            msg = stream.next() => {
+                let Some(msg) = msg else {
+                    break;
+                };
+                let Ok(msg) = msg else { continue };
+                let Ok((iface, changed, invalidated)) = msg
+                    .body()
+                    .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>()
+                else {
+                    continue;
+                };
+                let relevant = WATCHED.iter().any(|(i, property)| {
+                    *i == iface
+                        && (changed.contains_key(*property)
+                            || invalidated.iter().any(|p| p == property))
+                });
+                if !relevant {
+                    continue;
+                }
+            }
+            _ = interval.tick() => {
+                // Nobody would see the result
+                if DAEMON_TX.get().is_none_or(|d| d.is_empty()) {
+                    continue;
+                }
+            }
+        }
+
+        let current = match snapshot(&daemon).await {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                continue;
+            }
+        };
+        if last.as_ref() != Some(&current) {
+            last = Some(current.clone());
+            let _result = DAEMON_TX
+                .get()
+                .map(|d| d.send(InternalMessage::SystemState(current)));
+        }
+    }
+    Ok(())
+}
+
+async fn snapshot(daemon: &RwLock<NotificationDaemon>) -> Result<SystemStateRaw, WatsonError> {
+    SystemStateBuilder::new(&mut daemon.write().await.hardware).await
+}
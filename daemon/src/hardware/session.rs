@@ -0,0 +1,184 @@
+use std::{process::Command, sync::Arc, time::Duration};
+
+use futures_util::{StreamExt, stream};
+use serde::Deserialize;
+use suite_223b::{
+    config::profile::load_config_file,
+    protocol::{InternalMessage, SessionState},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::sync::RwLock;
+use zbus::{
+    Connection, Proxy,
+    zvariant::{OwnedFd, OwnedObjectPath},
+};
+
+use crate::{DAEMON_TX, notify::NotificationDaemon, utils::command::spawn_detached};
+
+const LOGIND_NAME: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+
+/// logind waits at most `InhibitDelayMaxSec` (5s by default) for delay locks
+const SLEEP_HOOK_TIMEOUT: Duration = Duration::from_secs(4);
+
+/// `$XDG_CONFIG_HOME/watson/session.json`, commands run on session events, e.g.
+/// `{ "on_sleep": "playerctl pause", "on_resume": "notify-send 'Welcome back'" }`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SessionHooks {
+    /// Runs before the system sleeps, which waits for it to finish
+    pub on_sleep: Option<String>,
+    pub on_resume: Option<String>,
+    pub on_idle: Option<String>,
+    pub on_active: Option<String>,
+}
+impl SessionHooks {
+    fn load() -> Result<Self, WatsonError> {
+        load_config_file("session")
+    }
+}
+
+fn broadcast(state: SessionState) {
+    let _result = DAEMON_TX
+        .get()
+        .map(|d| d.send(InternalMessage::Session(state)));
+}
+
+fn run_hook(cmd: &Option<String>) {
+    if let Some(cmd) = cmd
+        && let Err(e) = spawn_detached(cmd)
+    {
+        eprintln!("{:?}", e);
+    }
+}
+
+/// Runs `cmd` through the shell and waits for it, but not longer than logind holds off sleep
+async fn run_sleep_hook(cmd: &str) {
+    let task = tokio::task::spawn_blocking({
+        let cmd = cmd.to_string();
+        move || Command::new("sh").arg("-c").arg(cmd).status()
+    });
+    match tokio::time::timeout(SLEEP_HOOK_TIMEOUT, task).await {
+        Ok(Ok(Ok(_))) => {}
+        Ok(Ok(Err(e))) => eprintln!("Sleep hook failed: {}", e),
+        Ok(Err(e)) => eprintln!("Sleep hook failed: {}", e),
+        Err(_) => eprintln!("Sleep hook still running, suspending anyway"),
+    }
+}
+
+/// Delays sleep until the returned descriptor is dropped
+async fn inhibit_sleep(manager: &Proxy<'_>) -> Option<OwnedFd> {
+    manager
+        .call(
+            "Inhibit",
+            &("sleep", "Watson", "Running sleep hooks", "delay"),
+        )
+        .await
+        .map_err(|e| eprintln!("Could not delay sleep: {}", e))
+        .ok()
+}
+
+/// The graphical session of the user running the daemon, which carries the idle hint
+async fn user_session(conn: &Connection, manager: &Proxy<'_>) -> Option<Proxy<'static>> {
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+    let user: OwnedObjectPath = manager.call("GetUser", &(uid,)).await.ok()?;
+    let user = Proxy::new(conn, LOGIND_NAME, user, "org.freedesktop.login1.User")
+        .await
+        .ok()?;
+    let (_, session): (String, OwnedObjectPath) = user.get_property("Display").await.ok()?;
+    Proxy::new(conn, LOGIND_NAME, session, "org.freedesktop.login1.Session")
+        .await
+        .ok()
+}
+
+/// Follows logind's sleep and idle state. Clients are told so they can pause periodic redraws,
+/// calendars are refreshed after resume and the configured hooks run.
+pub async fn session_listener(daemon: Arc<RwLock<NotificationDaemon>>) -> Result<(), WatsonError> {
+    let hooks = SessionHooks::load().unwrap_or_else(|e| {
+        eprintln!("{:?}", e);
+        SessionHooks::default()
+    });
+    let (conn, events, screen_time) = {
+        let daemon = daemon.read().await;
+        (
+            daemon.hardware.conn.clone(),
+            Arc::clone(&daemon.software.events),
+            Arc::clone(&daemon.software.screen_time),
+        )
+    };
+
+    let manager = Proxy::new(
+        &conn,
+        LOGIND_NAME,
+        LOGIND_PATH,
+        "org.freedesktop.login1.Manager",
+    )
+    .await
+    .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+    let mut sleep = manager
+        .receive_signal("PrepareForSleep")
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))?;
+
+    let session = user_session(&conn, &manager).await;
+    let mut idle = match &session {
+        Some(session) => session
+            .receive_property_changed::<bool>("IdleHint")
+            .await
+            .boxed(),
+        // Not started from a logind session, e.g. in a container
+        None => stream::pending().boxed(),
+    };
+
+    // Only held when there is something to wait for
+    let mut inhibitor = match hooks.on_sleep {
+        Some(_) => inhibit_sleep(&manager).await,
+        None => None,
+    };
+
+    loop {
+        tokio::select! {
+            Some(signal) = sleep.next() => {
+                let Ok(start) = signal.body().deserialize::<bool>() else {
+                    continue;
+                };
+                screen_time.set_paused(start);
+                if start {
+                    broadcast(SessionState::Sleeping);
+                    if let Some(cmd) = &hooks.on_sleep {
+                        run_sleep_hook(cmd).await;
+                    }
+                    drop(inhibitor.take());
+                } else {
+                    broadcast(SessionState::Resumed);
+                    run_hook(&hooks.on_resume);
+                    if hooks.on_sleep.is_some() {
+                        inhibitor = inhibit_sleep(&manager).await;
+                    }
+                    for calendar in events.refresh_changed().await {
+                        let _result = DAEMON_TX
+                            .get()
+                            .map(|d| d.send(InternalMessage::CalendarChanged { calendar }));
+                    }
+                }
+            }
+            Some(changed) = idle.next() => {
+                let Ok(idle) = changed.get().await else {
+                    continue;
+                };
+                screen_time.set_paused(idle);
+                if idle {
+                    broadcast(SessionState::Idle);
+                    run_hook(&hooks.on_idle);
+                } else {
+                    broadcast(SessionState::Active);
+                    run_hook(&hooks.on_active);
+                }
+            }
+            else => break,
+        }
+    }
+    Ok(())
+}
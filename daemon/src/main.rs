@@ -1,95 +1,312 @@
 use async_trait::async_trait;
 use futures_util::StreamExt;
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, OnceLock};
+use suite_223b::config::profile::set_profile;
+use suite_223b::notification::{CloseReason, Notification};
 use suite_223b::protocol::{
-    BatteryState, DaemonService, InternalMessage, IntoResponse, Request, Response, SocketData,
+    BatteryState, DaemonService, InternalMessage, IntoResponse, JobSchedule, NotificationServer,
+    Request, Response, SocketData, Surface, SurfaceAction, TraceDirection,
 };
+use suite_223b::utils::battery::{BatterySource, poll_battery};
+use suite_223b::utils::crash;
 use suite_223b::utils::errors::{WatsonError, WatsonErrorKind};
 use suite_223b::watson_err;
-use tokio::sync::broadcast::Sender;
 use tokio::sync::mpsc;
 use tokio::{
     net::{UnixListener, UnixStream},
-    sync::{RwLock, broadcast},
+    sync::RwLock,
 };
 
 use zbus::{Connection, zvariant::OwnedValue};
 
-use suite_223b::tokio::{AsyncSizedMessage, SizedMessageObj};
+use suite_223b::tokio::{AsyncSizedMessage, SizedMessageObj, decode_sized, set_max_message_size};
 use zbus::conn::Builder;
+use zbus::fdo::{DBusProxy, RequestNameFlags, RequestNameReply};
+use zbus::names::BusName;
 
 mod core;
 mod hardware;
 mod notify;
-use notify::{DaemonHandle, NotificationDaemon};
+use notify::{DaemonHandle, NotificationDaemon, load_snapshot, relay_actions};
 mod calendar;
 mod software;
+#[cfg(test)]
+mod tests;
 mod utils;
 
-use crate::hardware::{AudioCommand, SystemStateBuilder, audio_actor};
-use crate::utils::command::spawn_detached;
+use crate::core::{
+    connections::ConnectionRegistry,
+    crashes::notify_crash_reports,
+    dbus::watson_bus_listener,
+    metrics::{COUNTERS, collect},
+    peer::{is_owner, restrict_socket},
+    plugins::plugin_listener,
+    scheduler::Scheduler,
+    watchdog::watch_client,
+};
+use crate::hardware::{
+    audio_available, connectivity_listener, dock_listener, handle_authorized,
+    notify_permission_denied, power_profiles_listener, request_background,
+    schedule_full_charge_notice, schedule_night_light, session_listener, system_state_listener,
+};
+use crate::software::{
+    dnd::{compositor_dnd_listener, compositor_supported},
+    force_refresh,
+    keyboard::keyboard_layout_listener,
+    shortcuts::global_shortcuts_listener,
+};
+use crate::utils::{flags::DaemonFlags, systemd};
 
-static DAEMON_TX: OnceLock<Sender<InternalMessage>> = OnceLock::new();
+static DAEMON_TX: OnceLock<ConnectionRegistry> = OnceLock::new();
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+const BATTERY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
 
 #[tokio::main]
 async fn main() -> Result<(), WatsonError> {
-    let (tx, rx) = broadcast::channel::<InternalMessage>(16);
-    DAEMON_TX.set(tx).expect("Failed to set daemon_tx");
-
-    let daemon_raw = NotificationDaemon::new().await?;
-    let wake_signal = Arc::clone(&daemon_raw.wake_signal);
-    let daemon = Arc::new(RwLock::new(daemon_raw));
-
-    // Start Battery Service
-    let _result = tokio::spawn(battery_state_listener(Arc::clone(&daemon)));
-
-    // Start Audio Service
-    let audio_tx = {
-        let (audio_tx, audio_rx) = mpsc::channel::<AudioCommand>(16);
-        std::thread::spawn({
-            let audio_tx = audio_tx.clone();
-            let register = Arc::clone(&daemon.read().await.register);
-            move || {
-                let rt = tokio::runtime::Builder::new_current_thread()
-                    .enable_all()
-                    .build()
-                    .unwrap();
-
-                rt.block_on(async move {
-                    audio_actor(audio_tx, audio_rx, Arc::clone(&wake_signal), register).await
-                });
+    let flags = DaemonFlags::parse(std::env::args());
+    set_profile(flags.profile.clone());
+    if let Some(path) = flags.socket.clone() {
+        SocketData::set_override(path);
+    }
+    crash::install("watson-daemon", env!("CARGO_PKG_VERSION"));
+    if let Some(max) = flags.max_message_size {
+        set_max_message_size(max);
+    }
+    let _ = DAEMON_TX.set(ConnectionRegistry::new());
+    if flags.trace_protocol
+        && let Some(connections) = DAEMON_TX.get()
+    {
+        connections.trace_to_log();
+    }
+
+    let daemon = Arc::new(RwLock::new(NotificationDaemon::new().await?));
+
+    // Reproduce what a user saw, from their `watson dump-state`
+    if let Some(path) = &flags.load_state {
+        let snapshot = load_snapshot(path)?;
+        daemon.write().await.restore(snapshot);
+    }
+
+    let caps = daemon.read().await.hardware.capabilities();
+
+    // Start the scheduler, the services below register their periodic work with it
+    let scheduler = Arc::clone(&daemon.read().await.scheduler);
+    tokio::spawn({
+        let scheduler = Arc::clone(&scheduler);
+        async move { scheduler.run().await }
+    });
+
+    // Start audio, calendar sync and metrics, unless switched off with `SetServiceEnabled`
+    daemon
+        .write()
+        .await
+        .start_services(Arc::downgrade(&daemon), flags.metrics_port);
+
+    // Bring back what was snoozed before the restart
+    daemon.write().await.restore_snoozed();
+
+    // Start Battery Service, polling where UPower is missing, e.g. on the BSDs
+    if caps.upower {
+        let _result = tokio::spawn(battery_state_listener(Arc::clone(&daemon)));
+    } else if let Err(e) = schedule_battery_poll(&scheduler, Arc::clone(&daemon)) {
+        eprintln!("{:?}", e);
+    }
+
+    // Tell the user to unplug once the battery is full
+    if let Err(e) = schedule_full_charge_notice(&scheduler, Arc::clone(&daemon)) {
+        eprintln!("{:?}", e);
+    }
+
+    // Start Night Light Schedule
+    if let Err(e) = schedule_night_light(&scheduler, Arc::clone(&daemon)) {
+        eprintln!("{:?}", e);
+    }
+
+    // Bring the client back after crashes
+    if flags.watchdog {
+        tokio::spawn(async {
+            if let Err(e) = watch_client().await {
+                eprintln!("{:?}", e);
             }
         });
-        audio_tx
-    };
-    daemon.write().await.hardware.set_audio_state(audio_tx);
+    }
 
-    // Start Dbus Service
-    let _result = tokio::spawn(dbus_listener(Arc::clone(&daemon)));
+    // Keep running in the background when sandboxed
+    if caps.flatpak {
+        tokio::spawn(async move {
+            if let Err(e) = request_background().await {
+                eprintln!("{:?}", e);
+            }
+        });
+    }
 
-    // Setup Server
-    let _ = std::fs::remove_file(SocketData::SOCKET_ADDR);
-    let listener = UnixListener::bind(SocketData::SOCKET_ADDR)
-        .map_err(|e| watson_err!(WatsonErrorKind::StreamBind, e.to_string()))?;
+    // Follow the primary network connection
+    tokio::spawn({
+        let daemon = Arc::clone(&daemon);
+        async move {
+            if let Err(e) = connectivity_listener(daemon).await {
+                eprintln!("{:?}", e);
+            }
+        }
+    });
+
+    // Pick up toggles changed by other tools
+    tokio::spawn({
+        let daemon = Arc::clone(&daemon);
+        async move {
+            if let Err(e) = system_state_listener(daemon).await {
+                eprintln!("{:?}", e);
+            }
+        }
+    });
+
+    // Follow power profile switches and holds
+    tokio::spawn({
+        let daemon = Arc::clone(&daemon);
+        async move {
+            if let Err(e) = power_profiles_listener(daemon).await {
+                eprintln!("{:?}", e);
+            }
+        }
+    });
+
+    // Follow sleep and idle, running the user's session hooks
+    if caps.logind {
+        tokio::spawn({
+            let daemon = Arc::clone(&daemon);
+            async move {
+                if let Err(e) = session_listener(daemon).await {
+                    eprintln!("{:?}", e);
+                }
+            }
+        });
+    }
+
+    // Follow the lid and docking station, applying the dock config
+    if caps.logind {
+        tokio::spawn({
+            let daemon = Arc::clone(&daemon);
+            async move {
+                if let Err(e) = dock_listener(daemon).await {
+                    eprintln!("{:?}", e);
+                }
+            }
+        });
+    }
+
+    // Follow compositor fullscreen/screencast state
+    tokio::spawn({
+        let daemon = Arc::clone(&daemon);
+        async move {
+            if let Err(e) = compositor_dnd_listener(daemon).await {
+                eprintln!("{:?}", e);
+            }
+        }
+    });
 
-    let connection_count = Arc::new(AtomicUsize::new(0));
+    // Bind the configured shortcuts through the compositor
+    if caps.portal {
+        tokio::spawn({
+            let daemon = Arc::clone(&daemon);
+            async move {
+                if let Err(e) = global_shortcuts_listener(daemon).await {
+                    eprintln!("{:?}", e);
+                }
+            }
+        });
+    }
+
+    // Follow keyboard layout switches
+    tokio::spawn({
+        let keyboard = Arc::clone(&daemon.read().await.software.keyboard);
+        async move {
+            if let Err(e) = keyboard_layout_listener(keyboard).await {
+                eprintln!("{:?}", e);
+            }
+        }
+    });
+
+    // Start Dbus Service, the other services run without it
+    if flags.no_notifications {
+        daemon.write().await.server = NotificationServer::Disabled;
+    }
+    let _result = tokio::spawn(dbus_listener(
+        Arc::clone(&daemon),
+        flags.replace,
+        !flags.no_notifications,
+    ));
+
+    // Offer the reports left behind by earlier crashes
+    tokio::spawn({
+        let daemon = Arc::clone(&daemon);
+        async move {
+            if let Err(e) = notify_crash_reports(daemon).await {
+                eprintln!("{:?}", e);
+            }
+        }
+    });
+
+    // Mirror the socket protocol on the session bus for third-party bars
+    tokio::spawn({
+        let daemon = Arc::clone(&daemon);
+        async move {
+            if let Err(e) = watson_bus_listener(daemon).await {
+                eprintln!("{:?}", e);
+            }
+        }
+    });
+
+    // Let external executables register as services
+    tokio::spawn({
+        let daemon = Arc::clone(&daemon);
+        async move {
+            if let Err(e) = plugin_listener(daemon).await {
+                eprintln!("{:?}", e);
+            }
+        }
+    });
+
+    // Setup Server, preferring a socket inherited from systemd
+    let listener = match systemd::listen_fds() {
+        Some(inherited) => UnixListener::from_std(inherited)
+            .map_err(|e| watson_err!(WatsonErrorKind::StreamListener, e.to_string()))?,
+        None => {
+            let path = SocketData::socket_path()?;
+            let _ = std::fs::remove_file(&path);
+            let listener = UnixListener::bind(&path)
+                .map_err(|e| watson_err!(WatsonErrorKind::StreamBind, e.to_string()))?;
+            // The socket unit sets the mode itself
+            restrict_socket(&path)?;
+            listener
+        }
+    };
+
+    if let Err(e) = systemd::notify("READY=1") {
+        eprintln!("{:?}", e);
+    }
+
+    let connections = DAEMON_TX.get().expect("Failed to get daemon_tx");
     loop {
         let (stream, _) = listener
             .accept()
             .await
             .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
-        connection_count.fetch_add(1, Ordering::SeqCst);
+        // Other users could flip hardware state or run commands
+        if !is_owner(&stream) {
+            eprintln!("Refused a connection from another user");
+            continue;
+        }
 
-        let rx = rx.resubscribe();
+        let slot = connections.register();
+        scheduler.client_connected();
         tokio::spawn({
             let daemon_clone = Arc::clone(&daemon);
-            let count_clone = Arc::clone(&connection_count);
             async move {
-                handle_client(stream, daemon_clone.clone(), rx).await;
-                count_clone.fetch_sub(1, Ordering::SeqCst);
-                if count_clone.load(Ordering::SeqCst) == 0 {
+                let mut slot = slot;
+                handle_client(stream, daemon_clone.clone(), slot.id, &mut slot.rx).await;
+                drop(slot);
+                if connections.is_empty() {
                     daemon_clone.write().await.register.clear();
                 }
             }
@@ -97,21 +314,93 @@ async fn main() -> Result<(), WatsonError> {
     }
 }
 
-async fn dbus_listener(daemon: Arc<RwLock<NotificationDaemon>>) -> zbus::Result<()> {
+/// Connects to the session bus and, when `serve` is set, tries to become the notification
+/// daemon. The connection is kept either way to forward our own notifications.
+async fn dbus_listener(
+    daemon: Arc<RwLock<NotificationDaemon>>,
+    replace: bool,
+    serve: bool,
+) -> zbus::Result<()> {
     // Connect to session bus
-    let daemon_handle = DaemonHandle::new(daemon);
-    let _conn = Builder::session()?
-        .name("org.freedesktop.Notifications")?
-        .serve_at("/org/freedesktop/Notifications", daemon_handle)?
-        .build()
-        .await?;
+    let mut builder = Builder::session()?;
+    if serve {
+        builder = builder.serve_at(DaemonHandle::PATH, DaemonHandle::new(Arc::clone(&daemon)))?;
+    }
+    let conn = builder.build().await?;
+    daemon.write().await.session = Some(conn.clone());
+    tokio::spawn({
+        let daemon = Arc::clone(&daemon);
+        let conn = conn.clone();
+        async move {
+            if let Err(e) = relay_actions(daemon, conn).await {
+                eprintln!("{:?}", e);
+            }
+        }
+    });
+    if !serve {
+        return Ok(());
+    }
 
+    let bus = DBusProxy::new(&conn).await?;
+    let mut lost = bus.receive_name_lost().await?;
+
+    // Always allow others to take over the name, only take it over when asked to
+    let mut flags = RequestNameFlags::AllowReplacement | RequestNameFlags::DoNotQueue;
+    if replace {
+        flags |= RequestNameFlags::ReplaceExisting;
+    }
+    match conn
+        .request_name_with_flags(NOTIFICATIONS_NAME, flags)
+        .await?
+    {
+        RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => {}
+        _ => {
+            let owner = name_owner(&bus).await;
+            eprintln!(
+                "{} is owned by {}. Use --replace to take it over, or --no-notifications to leave it. Other services keep running.",
+                NOTIFICATIONS_NAME,
+                owner.as_deref().unwrap_or("another daemon"),
+            );
+            daemon.write().await.server = NotificationServer::Taken { owner };
+            return Ok(());
+        }
+    }
+
+    daemon.write().await.server = NotificationServer::Owned;
     println!("Notification daemon running");
-    std::future::pending::<()>().await;
+
+    // Another daemon started with its own replace flag
+    while let Some(signal) = lost.next().await {
+        if signal.args()?.name().as_str() != NOTIFICATIONS_NAME {
+            continue;
+        }
+        let owner = name_owner(&bus).await;
+        eprintln!(
+            "{} was taken over by {}. Other services keep running.",
+            NOTIFICATIONS_NAME,
+            owner.as_deref().unwrap_or("another daemon"),
+        );
+        daemon.write().await.server = NotificationServer::Taken { owner };
+        break;
+    }
 
     Ok(())
 }
 
+/// Process name of whoever owns the notification name, its unique bus name if that's unknown
+async fn name_owner(bus: &DBusProxy<'_>) -> Option<String> {
+    let name = BusName::try_from(NOTIFICATIONS_NAME).ok()?;
+    let unique = bus.get_name_owner(name).await.ok()?;
+    let process = match bus
+        .get_connection_unix_process_id(unique.clone().into())
+        .await
+    {
+        Ok(pid) => std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok(),
+        Err(_) => None,
+    };
+    Some(process.map_or_else(|| unique.to_string(), |p| p.trim().to_string()))
+}
+
 async fn battery_state_listener(daemon: Arc<RwLock<NotificationDaemon>>) -> zbus::Result<()> {
     let conn = Connection::system().await?;
     let proxy = zbus::Proxy::new(
@@ -176,13 +465,14 @@ async fn battery_state_listener(daemon: Arc<RwLock<NotificationDaemon>>) -> zbus
                 }
 
                 // Check for changes
-                if let Ok(percentage) = BatteryState::capacity() {
-                    if changed_significantly && last_state != BatteryState::Invalid {
-                        let _ = DAEMON_TX.get().map(|d| d.send(InternalMessage::BatteryState {
-                            state: last_state,
-                            percentage,
-                        }));
-                    }
+                if let Ok((_, percentage)) = poll_battery().await
+                    && changed_significantly
+                    && last_state != BatteryState::Invalid
+                {
+                    let _ = DAEMON_TX.get().map(|d| d.send(InternalMessage::BatteryState {
+                        state: last_state,
+                        percentage,
+                    }));
                 }
 
             }
@@ -191,12 +481,67 @@ async fn battery_state_listener(daemon: Arc<RwLock<NotificationDaemon>>) -> zbus
     Ok(())
 }
 
+/// Reads the battery every `BATTERY_POLL_INTERVAL`, for systems without UPower
+fn schedule_battery_poll(
+    scheduler: &Scheduler,
+    daemon: Arc<RwLock<NotificationDaemon>>,
+) -> Result<(), WatsonError> {
+    let Some(source) = BatterySource::get() else {
+        return Ok(());
+    };
+    let last_state = Arc::new(std::sync::Mutex::new(BatteryState::Invalid));
+    scheduler.register(
+        "battery-poll",
+        JobSchedule::Every(BATTERY_POLL_INTERVAL.as_secs()),
+        move || {
+            let daemon = Arc::clone(&daemon);
+            let last_state = Arc::clone(&last_state);
+            async move {
+                // Ghost check
+                let active = daemon
+                    .read()
+                    .await
+                    .register
+                    .is_active(DaemonService::BatteryStateListener);
+                if !active {
+                    return;
+                }
+
+                match source.poll().await {
+                    Ok((state, percentage)) => {
+                        let mut last_state = last_state.lock().expect("Poisoned");
+                        if state != *last_state {
+                            *last_state = state;
+                            if state != BatteryState::Invalid {
+                                let _ = DAEMON_TX.get().map(|d| {
+                                    d.send(InternalMessage::BatteryState { state, percentage })
+                                });
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("{:?}", e),
+                }
+            }
+        },
+    )
+}
+
 async fn handle_client(
     mut stream: UnixStream,
     daemon: Arc<RwLock<NotificationDaemon>>,
-    mut rx: broadcast::Receiver<InternalMessage>,
+    id: u64,
+    rx: &mut mpsc::Receiver<InternalMessage>,
 ) {
-    let config = bincode::config::standard();
+    let connections = DAEMON_TX.get();
+    let trace = |direction: TraceDirection, message: &dyn std::fmt::Debug| {
+        if let Some(connections) = connections {
+            connections.trace(Some(id), direction, message);
+        }
+    };
+    // Sent with `Request::Authenticate`, allows running commands if the daemon asks for it
+    let mut token: Option<String> = None;
+    // Answers to requests that finish in the background
+    let (replies, mut finished) = mpsc::unbounded_channel::<(Response, Request)>();
     loop {
         tokio::select! {
             result = stream.read_sized() => {
@@ -205,36 +550,109 @@ async fn handle_client(
                     Err(_) => break, // Client disconnected
                 };
 
-                let req: Request = match bincode::serde::decode_from_slice(&buf, config) {
-                    Ok((r, _)) => r,
+                let req: Request = match decode_sized(&buf) {
+                    Ok(r) => r,
                     Err(_) => continue,
                 };
+                crash::record("in", req.untracked());
+                // The token stays out of traces
+                if !matches!(req.untracked(), Request::Authenticate(_)) {
+                    trace(TraceDirection::Request, &req);
+                }
 
-                let daemon_clone = Arc::clone(&daemon);
+                let tracked = match &req {
+                    Request::Tracked { id, .. } => Some(*id),
+                    _ => None,
+                };
+                let ack = |resp: Response| match tracked {
+                    Some(id) => resp.into_ack(id),
+                    None => resp,
+                };
 
-                let resp = {
-                    let mut daemon_guard = daemon_clone.write().await;
-                    req.handle(&mut *daemon_guard).await
+                let resp = match req.untracked() {
+                    Request::Authenticate(t) => {
+                        token = Some(t.clone());
+                        ack(Response::Ok)
+                    }
+                    Request::TraceProtocol => {
+                        if let Some(connections) = connections {
+                            connections.subscribe_trace(id);
+                        }
+                        continue;
+                    }
+                    Request::Command { cmd, capture: false } => {
+                        let commands = Arc::clone(&daemon.read().await.software.commands);
+                        ack(commands.spawn(cmd, token.as_deref()).into_response())
+                    }
+                    // Captured commands may run for a while, nobody should wait on them
+                    Request::Command { cmd, capture: true } => {
+                        let commands = Arc::clone(&daemon.read().await.software.commands);
+                        let (cmd, token) = (cmd.clone(), token.clone());
+                        answer_later(&replies, req, async move {
+                            command_response(commands.run(&cmd, token.as_deref()).await)
+                        });
+                        continue;
+                    }
+                    // Neither should this client's broadcasts wait on plugins
+                    Request::Plugin { plugin, request, payload } => {
+                        let plugins = Arc::clone(&daemon.read().await.plugins);
+                        let (plugin, request, payload) = (plugin.clone(), request.clone(), payload.clone());
+                        answer_later(&replies, req, async move {
+                            plugin_response(&plugin, plugins.call(&plugin, &request, &payload).await)
+                        });
+                        continue;
+                    }
+                    // Nor on Home Assistant
+                    Request::SetHomeAssistantEntity { entity, on } => {
+                        let hub = Arc::clone(&daemon.read().await.software.home_assistant);
+                        let (entity, on) = (entity.clone(), *on);
+                        answer_later(&replies, req, async move {
+                            hub.set(&entity, on).await.into_response()
+                        });
+                        continue;
+                    }
+                    // Or on polkit asking for a password
+                    Request::SetPowerMode(_) | Request::SetBacklight { device: None, .. } => {
+                        let daemon = Arc::clone(&daemon);
+                        let request = req.untracked().clone();
+                        answer_later(&replies, req, async move {
+                            handle_authorized(&daemon, request).await
+                        });
+                        continue;
+                    }
+                    _ => {
+                        let mut daemon_guard = daemon.write().await;
+                        req.clone().handle(&mut daemon_guard).await
+                    }
                 };
+                account(&daemon, &req, &resp);
 
                 if !matches!(resp, Response::Ok) {
-                    if let Ok(out) = SizedMessageObj::from_struct(&resp) {
-                        if stream.write_sized(out).await.is_err() {
-                            break;
-                        }
+                    trace(TraceDirection::Response, &resp);
+                    if let Ok(out) = SizedMessageObj::from_struct(&resp)
+                        && stream.write_sized(out).await.is_err()
+                    {
+                        break;
                     }
                 }
+            }
 
+            Some((resp, req)) = finished.recv() => {
+                account(&daemon, &req, &resp);
+                if !matches!(resp, Response::Ok) {
+                    trace(TraceDirection::Response, &resp);
+                    if let Ok(out) = SizedMessageObj::from_struct(&resp)
+                        && stream.write_sized(out).await.is_err()
+                    {
+                        break;
+                    }
+                }
             }
 
             msg = rx.recv() => {
-                let message = match msg {
-                    Ok(id) => id,
-                    Err(broadcast::error::RecvError::Lagged(_)) => {
-                        // Client fell behind
-                        continue
-                    }
-                    Err(_) => break // channel closed
+                let Some(message) = msg else {
+                    // Queue closed, client got evicted
+                    break
                 };
 
                 let resp = match message {
@@ -242,11 +660,39 @@ async fn handle_client(
                         let daemon = daemon.read().await;
                         Response::Notification(daemon.get_by_id(id).cloned())
                     }
+                    InternalMessage::NotificationClosed { id, reason } => Response::NotificationClosed { id, reason },
+                    InternalMessage::MissedNotifications(count) => Response::MissedNotifications(count),
+                    InternalMessage::DndEnded { missed } => Response::DndEnded { missed },
                     InternalMessage::BatteryState { state, percentage } => Response::BatteryState {
                         state,
                         percentage
                     },
                     InternalMessage::VolumeStateChange { percentage } => Response::VolumeState { percentage },
+                    InternalMessage::AudioAvailable(available) => Response::AudioAvailable(available),
+                    InternalMessage::Surface { surface, action } => Response::Surface { surface, action },
+                    InternalMessage::CalendarChanged { calendar } => Response::CalendarChanged { calendar },
+                    InternalMessage::RecordingState(active) => Response::RecordingState(active),
+                    InternalMessage::ColorPicked(color) => Response::ColorPicked(color),
+                    InternalMessage::NightLight { enabled, intensity } => Response::NightLightState { enabled, intensity },
+                    InternalMessage::KeyboardLayout { name, short } => Response::KeyboardLayout { name, short },
+                    InternalMessage::Connectivity(info) => Response::Connectivity(info),
+                    InternalMessage::Error(e) => Response::Error(e),
+                    InternalMessage::SystemState(state) => Response::SystemState(state),
+                    InternalMessage::PowerProfiles(profiles) => Response::PowerProfiles(profiles),
+                    InternalMessage::Session(state) => Response::Session(state),
+                    InternalMessage::Dock(state) => Response::Dock(state),
+                    InternalMessage::Displays(displays) => Response::Displays(displays),
+                    InternalMessage::JobDue { id } => Response::JobDue { id },
+                    InternalMessage::Mail(accounts) => Response::Mail(accounts),
+                    InternalMessage::Quotes(quotes) => Response::Quotes(quotes),
+                    InternalMessage::HomeAssistant(entities) => Response::HomeAssistant(entities),
+                    InternalMessage::HomeAssistantEntity(entity) => Response::HomeAssistantEntity(entity),
+                    InternalMessage::ScreenTime(usage) => Response::ScreenTime(usage),
+                    InternalMessage::Focus(state) => Response::Focus(state),
+                    InternalMessage::Refreshing { kind, active } => Response::Refreshing { kind, active },
+                    InternalMessage::DeviceUse(used) => Response::DeviceUse(used),
+                    InternalMessage::Trace(event) => Response::Trace(event),
+                    InternalMessage::PluginEvent { plugin, kind, payload } => Response::PluginEvent { plugin, kind, payload },
                 };
 
                 if let Ok(out) = SizedMessageObj::from_struct(&resp) {
@@ -259,6 +705,37 @@ async fn handle_client(
     }
 }
 
+/// Answers `req` from a task of its own, so the client's loop keeps draining broadcasts while
+/// `response` waits on a plugin, the network or a password prompt
+fn answer_later(
+    replies: &mpsc::UnboundedSender<(Response, Request)>,
+    req: Request,
+    response: impl Future<Output = Response> + Send + 'static,
+) {
+    let replies = replies.clone();
+    tokio::spawn(async move {
+        let resp = response.await;
+        let resp = match &req {
+            Request::Tracked { id, .. } => resp.into_ack(*id),
+            _ => resp,
+        };
+        let _ = replies.send((resp, req));
+    });
+}
+
+/// Counts failed requests and offers to retry those polkit refused
+fn account(daemon: &Arc<RwLock<NotificationDaemon>>, req: &Request, resp: &Response) {
+    let (Response::Error(e) | Response::Ack { error: Some(e), .. }) = resp else {
+        return;
+    };
+    COUNTERS.record_error(req.service());
+    if e.kind == WatsonErrorKind::PermissionDenied {
+        let daemon = Arc::clone(daemon);
+        let (retry, error) = (req.untracked().clone(), e.clone());
+        tokio::spawn(async move { notify_permission_denied(&daemon, retry, error).await });
+    }
+}
+
 #[async_trait]
 trait RequestHandler {
     async fn handle(self, daemon: &mut NotificationDaemon) -> Response;
@@ -271,25 +748,91 @@ impl RequestHandler for Request {
             Request::GetStatus => Response::Status {
                 running: true,
                 silent: daemon.settings.silent,
+                notifications: daemon.server.clone(),
+            },
+            Request::GetMetrics => Response::Metrics(collect(daemon)),
+            Request::DumpState => match daemon.snapshot().await {
+                Ok(snapshot) => Response::StateSnapshot(Box::new(snapshot)),
+                Err(e) => e.into(),
             },
             Request::Notification(id) => Response::Notification(daemon.get_by_id(id).cloned()),
-            Request::PendingNotifications => {
-                let notifs = daemon.pending_notifications();
+            Request::PendingNotifications { offset, limit } => {
+                let notifs = daemon.pending_notifications(offset, limit);
                 Response::Notifications(notifs)
             }
+            Request::DismissNotification(id) => {
+                if daemon.close(id, CloseReason::Dismissed).await {
+                    Response::Ok
+                } else {
+                    unknown_notification(id)
+                }
+            }
+            Request::InvokeAction { id, action } => {
+                if daemon.invoke_action(id, action).await {
+                    Response::Ok
+                } else {
+                    unknown_notification(id)
+                }
+            }
+            Request::SnoozeNotification { id, seconds } => {
+                daemon.snooze(id, seconds).into_response()
+            }
+            Request::Notify {
+                summary,
+                body,
+                icon,
+                timeout,
+                actions,
+            } => {
+                let notification = Notification {
+                    app_name: "Watson".into(),
+                    app_icon: icon,
+                    summary,
+                    body,
+                    actions: actions
+                        .into_iter()
+                        .flat_map(|(key, label)| [key, label])
+                        .collect(),
+                    expire_timeout: timeout,
+                    ..Default::default()
+                };
+                let weak = daemon.services.daemon();
+                Response::Notified(daemon.insert(notification, weak))
+            }
             Request::Silence(value) => {
-                daemon.settings.silent = value;
+                daemon.settings.set_silent(value);
                 Response::Ok
             }
+            Request::SetAutoDnd(enabled) => match compositor_supported() {
+                Err(e) if enabled => e.into(),
+                _ => {
+                    daemon.settings.set_auto_dnd_enabled(enabled);
+                    Response::Ok
+                }
+            },
             Request::RegisterServices(services) => {
                 daemon.register.set_registered_services(services);
                 println!("Registered required services. {}", daemon.register);
                 // Wake services
                 daemon.wake_signal.notify_waiters();
+                // A client started during do-not-disturb shows what it missed so far
+                let missed = daemon.settings.missed();
+                if missed > 0 {
+                    let _result = DAEMON_TX
+                        .get()
+                        .map(|d| d.send(InternalMessage::MissedNotifications(missed)));
+                }
+                // Clients assume audio works until told otherwise
+                let wants_audio = services & (1 << DaemonService::AudioService as u8) != 0;
+                if wants_audio && !audio_available() {
+                    let _result = DAEMON_TX
+                        .get()
+                        .map(|d| d.send(InternalMessage::AudioAvailable(false)));
+                }
 
-                match SystemStateBuilder::new(&mut daemon.hardware).await {
+                match daemon.system_state().await {
                     Ok(state) => Response::SystemState(state),
-                    Err(e) => Response::Error(e.message),
+                    Err(e) => e.into(),
                 }
             }
             Request::SetWifi(enabled) => daemon.hardware.set_wifi(enabled).await.into_response(),
@@ -301,18 +844,223 @@ impl RequestHandler for Request {
                 .set_powermode(mode.into())
                 .await
                 .into_response(),
-            Request::SetBacklight(perc) => {
-                daemon.hardware.set_brightness(perc).await.into_response()
+            Request::PowerProfiles => match daemon.hardware.get_power_profiles().await {
+                Ok(profiles) => Response::PowerProfiles(profiles),
+                Err(e) => e.into(),
+            },
+            Request::HoldPowerProfile { profile, reason } => daemon
+                .hardware
+                .hold_powermode(profile.into(), &reason)
+                .await
+                .into_response(),
+            Request::ReleasePowerProfile => {
+                daemon.hardware.release_powermode().await.into_response()
             }
+            Request::DockState => match daemon.hardware.get_dock_state().await {
+                Ok(state) => Response::Dock(state),
+                Err(e) => e.into(),
+            },
+            Request::SetBacklight {
+                device: None,
+                percent,
+            } => daemon
+                .hardware
+                .set_brightness(percent)
+                .await
+                .into_response(),
+            // DDC/CI is slow and detecting displays even more so, they answer in the background
+            Request::SetBacklight {
+                device: Some(device),
+                percent,
+            } => {
+                let ddc = daemon.hardware.ddc();
+                tokio::spawn(async move {
+                    if let Err(e) = ddc.set_brightness(&device, percent).await {
+                        broadcast_error("hardware", e);
+                    }
+                });
+                Response::Ok
+            }
+            Request::Displays => {
+                let ddc = daemon.hardware.ddc();
+                tokio::spawn(async move {
+                    let displays = ddc.brightness().await;
+                    let _result = DAEMON_TX
+                        .get()
+                        .map(|d| d.send(InternalMessage::Displays(displays)));
+                });
+                Response::Ok
+            }
+            Request::DeviceUse => Response::DeviceUse(daemon.hardware.privacy().current()),
             Request::SetVolume(perc) => daemon.hardware.set_volume(perc).await.into_response(),
-            Request::SystemState => match SystemStateBuilder::new(&mut daemon.hardware).await {
+            Request::SetNightLight(enabled) => {
+                daemon.hardware.set_night_light(enabled).into_response()
+            }
+            Request::SetNightLightIntensity(perc) => daemon
+                .hardware
+                .set_night_light_intensity(perc)
+                .into_response(),
+            Request::SystemState => match daemon.system_state().await {
                 Ok(state) => Response::SystemState(state),
-                Err(e) => Response::Error(e.message),
+                Err(e) => e.into(),
             },
-            Request::Command(cmd) => spawn_detached(&cmd).into_response(),
+            // Socket clients are served by handle_client, which knows the connection's token
+            Request::Command {
+                cmd,
+                capture: false,
+            } => daemon.software.commands.spawn(&cmd, None).into_response(),
+            Request::Command { cmd, capture: true } => {
+                command_response(daemon.software.commands.run(&cmd, None).await)
+            }
+            // Handled per connection
+            Request::Authenticate(_) | Request::TraceProtocol => Response::Ok,
+            Request::SetServiceEnabled { service, enabled } => {
+                daemon.set_service_enabled(service, enabled).into_response()
+            }
+            // Both may wait on a portal dialog, so they run without holding the daemon
+            Request::Screenshot | Request::ToggleRecording | Request::PickColor
+                if !daemon.hardware.capabilities().portal =>
+            {
+                watson_err!(
+                    WatsonErrorKind::DBusConnect,
+                    "Capturing the screen needs xdg-desktop-portal"
+                )
+                .into()
+            }
+            Request::Screenshot => {
+                let capture = Arc::clone(&daemon.software.capture);
+                let weak = daemon.services.daemon();
+                tokio::spawn(async move {
+                    if let Err(e) = capture.screenshot(weak).await {
+                        broadcast_error("capture", e);
+                    }
+                });
+                Response::Ok
+            }
+            Request::PickColor => {
+                let capture = Arc::clone(&daemon.software.capture);
+                tokio::spawn(async move {
+                    match capture.pick_color().await {
+                        Ok(color) => {
+                            let _result = DAEMON_TX
+                                .get()
+                                .map(|d| d.send(InternalMessage::ColorPicked(color)));
+                        }
+                        Err(e) => broadcast_error("capture", e),
+                    }
+                });
+                Response::Ok
+            }
+            Request::ToggleRecording => {
+                let capture = Arc::clone(&daemon.software.capture);
+                let weak = daemon.services.daemon();
+                tokio::spawn(async move {
+                    if let Err(e) = capture.toggle_recording(weak).await {
+                        broadcast_error("capture", e);
+                        // Clients flipped their button before the portal answered
+                        let active = capture.is_recording().await;
+                        let _result = DAEMON_TX
+                            .get()
+                            .map(|d| d.send(InternalMessage::RecordingState(active)));
+                    }
+                });
+                Response::Ok
+            }
             Request::Event(filter) => {
-                Response::Events(daemon.software.events.get_events_with_filter(filter))
+                let mut events = daemon.software.events.get_events_with_filter(filter);
+                daemon.software.contacts.enrich(&mut events);
+                Response::Events(events)
+            }
+            Request::KeyboardLayout => match daemon.software.keyboard.current().await {
+                Ok(layout) => Response::KeyboardLayout {
+                    name: layout.name,
+                    short: layout.short,
+                },
+                Err(e) => e.into(),
+            },
+            Request::NextKeyboardLayout => daemon.software.keyboard.next().await.into_response(),
+            Request::Mail => Response::Mail(daemon.software.mail.accounts()),
+            Request::Quotes => Response::Quotes(daemon.software.ticker.quotes()),
+            Request::HomeAssistant => {
+                Response::HomeAssistant(daemon.software.home_assistant.entities())
             }
+            // Like plugin calls, only reached by requests that didn't come over the socket
+            Request::SetHomeAssistantEntity { entity, on } => {
+                let hub = Arc::clone(&daemon.software.home_assistant);
+                hub.set(&entity, on).await.into_response()
+            }
+            Request::ScreenTime => Response::ScreenTime(daemon.software.screen_time.usage()),
+            Request::Focus => Response::Focus(daemon.focus.state()),
+            Request::SetFocus(name) => daemon.set_focus(name).await.into_response(),
+            Request::ForceRefresh(kind) => {
+                tokio::spawn(force_refresh(
+                    kind,
+                    Arc::clone(&daemon.scheduler),
+                    Arc::clone(&daemon.software.mail),
+                ));
+                Response::Ok
+            }
+            Request::ScheduledJobs => Response::ScheduledJobs(daemon.scheduler.jobs()),
+            Request::Plugins => Response::Plugins(daemon.plugins.list()),
+            // Socket clients are served by handle_client without holding the daemon
+            Request::Plugin {
+                plugin,
+                request,
+                payload,
+            } => {
+                let plugins = Arc::clone(&daemon.plugins);
+                plugin_response(&plugin, plugins.call(&plugin, &request, &payload).await)
+            }
+            Request::ScheduleJob { id, schedule } => {
+                daemon.scheduler.schedule(id, schedule).into_response()
+            }
+            Request::CancelJob(id) => daemon.scheduler.cancel(&id).into_response(),
+            Request::ShowSurface(surface) => relay_surface(surface, SurfaceAction::Show),
+            Request::HideSurface(surface) => relay_surface(surface, SurfaceAction::Hide),
+            Request::ToggleSurface(surface) => relay_surface(surface, SurfaceAction::Toggle),
+            Request::Tracked { id, request } => (*request).handle(daemon).await.into_ack(id),
         }
     }
 }
+
+fn command_response(result: Result<(String, String), WatsonError>) -> Response {
+    match result {
+        Ok((stdout, stderr)) => Response::CommandOutput { stdout, stderr },
+        Err(e) => e.into(),
+    }
+}
+
+fn plugin_response(plugin: &str, result: Result<String, WatsonError>) -> Response {
+    match result {
+        Ok(payload) => Response::Plugin {
+            plugin: plugin.to_string(),
+            payload,
+        },
+        Err(e) => e.into(),
+    }
+}
+
+fn unknown_notification(id: u32) -> Response {
+    watson_err!(
+        WatsonErrorKind::InvalidData,
+        "No notification with id {}",
+        id
+    )
+    .into()
+}
+
+/// Reports a failure of a request that was answered before it finished
+fn broadcast_error(service: &'static str, e: WatsonError) {
+    eprintln!("{:?}", e);
+    COUNTERS.record_error(service);
+    let _result = DAEMON_TX
+        .get()
+        .map(|d| d.send(InternalMessage::Error(e.into())));
+}
+
+fn relay_surface(surface: Surface, action: SurfaceAction) -> Response {
+    let _result = DAEMON_TX
+        .get()
+        .map(|d| d.send(InternalMessage::Surface { surface, action }));
+    Response::Ok
+}
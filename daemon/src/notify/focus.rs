@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use suite_223b::{
+    config::profile::load_config_file,
+    notification::{Notification, Urgency},
+    protocol::{FocusState, InternalMessage, PowerMode},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+use crate::{DAEMON_TX, notify::NotificationDaemon};
+
+/// Settings applied together while the profile is active. Anything left out stays as it is.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FocusProfile {
+    pub dnd: Option<bool>,
+    /// Applications whose notifications are held back until the profile ends, matched
+    /// against the app name ignoring case. Critical notifications always get through.
+    pub hold: Vec<String>,
+    pub power_mode: Option<PowerMode>,
+    pub night_light: Option<bool>,
+    /// Client layout, read from `$XDG_CONFIG_HOME/watson/<layout>.json`
+    pub layout: Option<String>,
+}
+
+/// State from before the first profile changed it
+#[derive(Debug, Default)]
+struct Restore {
+    silent: Option<bool>,
+    power_mode: Option<PowerMode>,
+    night_light: Option<bool>,
+}
+
+/// `$XDG_CONFIG_HOME/watson/focus.json`, profiles by name, e.g.
+/// `{ "work": { "dnd": true, "hold": ["discord"] },
+///    "gaming": { "dnd": true, "power_mode": "performance", "layout": "gaming" } }`
+#[derive(Debug, Default)]
+pub struct FocusProfiles {
+    profiles: BTreeMap<String, FocusProfile>,
+    active: Option<String>,
+    restore: Restore,
+    /// Notifications of held applications, delivered once no profile holds them
+    held: Vec<Notification>,
+}
+impl FocusProfiles {
+    pub fn new(profiles: BTreeMap<String, FocusProfile>) -> Self {
+        Self {
+            profiles,
+            ..Default::default()
+        }
+    }
+    pub fn load() -> Self {
+        match load_config_file("focus") {
+            Ok(profiles) => Self::new(profiles),
+            Err(e) => {
+                eprintln!("{:?}", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn state(&self) -> FocusState {
+        FocusState {
+            active: self.active.clone(),
+            profiles: self.profiles.keys().cloned().collect(),
+            layout: self.active_profile().and_then(|p| p.layout.clone()),
+        }
+    }
+
+    /// The profile after the active one, `None` after the last one
+    pub fn next(&self) -> Option<String> {
+        let mut names = self.profiles.keys();
+        match &self.active {
+            Some(active) => names.skip_while(|name| *name != active).nth(1),
+            None => names.next(),
+        }
+        .cloned()
+    }
+
+    fn active_profile(&self) -> Option<&FocusProfile> {
+        self.profiles.get(self.active.as_ref()?)
+    }
+
+    /// Whether the active profile holds back `notification`
+    pub(super) fn holds(&self, notification: &Notification) -> bool {
+        if matches!(notification.urgency, Urgency::Critical) {
+            return false;
+        }
+        self.active_profile().is_some_and(|profile| {
+            profile
+                .hold
+                .iter()
+                .any(|app| app.eq_ignore_ascii_case(&notification.app_name))
+        })
+    }
+
+    pub(super) fn hold(&mut self, notification: Notification) {
+        self.held.push(notification);
+    }
+}
+
+impl NotificationDaemon {
+    /// Applies the profile `name`, or ends the active one with `None`. Switching between
+    /// profiles restores what only the previous one changed. Nothing changes if the power mode
+    /// or the night light can't be switched.
+    pub async fn set_focus(&mut self, name: Option<String>) -> Result<(), WatsonError> {
+        let profile = match &name {
+            Some(name) => self.focus.profiles.get(name).cloned().ok_or_else(|| {
+                watson_err!(
+                    WatsonErrorKind::InvalidData,
+                    format!("No focus profile named {name}")
+                )
+            })?,
+            None => FocusProfile::default(),
+        };
+        let saved = &self.focus.restore;
+        let power_mode = profile.power_mode.or(saved.power_mode);
+        let night_light = profile.night_light.or(saved.night_light);
+        let silent = profile.dnd.or(saved.silent);
+        let mut restore = Restore::default();
+
+        let mut switched_from = None;
+        if let Some(mode) = power_mode {
+            let current = self.hardware.get_powermode().await?;
+            if profile.power_mode.is_some() {
+                restore.power_mode = saved.power_mode.or(Some(current));
+            }
+            if mode != current {
+                self.hardware.set_powermode(mode).await?;
+                switched_from = Some(current);
+            }
+        }
+        if let Some(enabled) = night_light {
+            let (current, _) = self.hardware.get_night_light();
+            if profile.night_light.is_some() {
+                restore.night_light = saved.night_light.or(Some(current));
+            }
+            if enabled != current
+                && let Err(e) = self.hardware.set_night_light(enabled)
+            {
+                if let Some(previous) = switched_from
+                    && let Err(e) = self.hardware.set_powermode(previous).await
+                {
+                    eprintln!("{:?}", e);
+                }
+                return Err(e);
+            }
+        }
+        if let Some(silent) = silent {
+            if profile.dnd.is_some() {
+                restore.silent = saved.silent.or(Some(self.settings.silent));
+            }
+            if silent != self.settings.silent {
+                self.settings.set_silent(silent);
+            }
+        }
+
+        self.focus.restore = restore;
+        self.focus.active = name;
+        self.release_held();
+
+        // Toggles follow in the clients
+        match self.system_state().await {
+            Ok(state) => {
+                let _result = DAEMON_TX
+                    .get()
+                    .map(|d| d.send(InternalMessage::SystemState(state)));
+            }
+            Err(e) => eprintln!("{:?}", e),
+        }
+        let _result = DAEMON_TX
+            .get()
+            .map(|d| d.send(InternalMessage::Focus(self.focus.state())));
+        Ok(())
+    }
+
+    /// Delivers the held notifications the active profile doesn't hold anymore. They keep
+    /// their ids and stay until dismissed.
+    fn release_held(&mut self) {
+        let held = std::mem::take(&mut self.focus.held);
+        for notification in held {
+            if self.focus.holds(&notification) {
+                self.focus.held.push(notification);
+                continue;
+            }
+            let id = notification.id;
+            self.buffer.insert(id, notification);
+            let _result = DAEMON_TX
+                .get()
+                .map(|d| d.send(InternalMessage::Notification(id)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_cycles_through_profiles() {
+        let profiles: BTreeMap<String, FocusProfile> = serde_json::from_str(
+            r#"{
+                "work": { "dnd": true, "hold": ["Discord"] },
+                "gaming": { "power_mode": "performance", "layout": "gaming" }
+            }"#,
+        )
+        .unwrap();
+        let mut focus = FocusProfiles::new(profiles);
+        assert_eq!(focus.next().as_deref(), Some("gaming"));
+
+        focus.active = Some("gaming".into());
+        assert_eq!(focus.state().layout.as_deref(), Some("gaming"));
+        assert_eq!(focus.next().as_deref(), Some("work"));
+
+        focus.active = Some("work".into());
+        assert_eq!(focus.next(), None);
+        let discord = Notification {
+            app_name: "discord".into(),
+            ..Default::default()
+        };
+        assert!(focus.holds(&discord));
+        assert!(!focus.holds(&Notification {
+            urgency: Urgency::Critical,
+            ..discord
+        }));
+    }
+}
@@ -1,22 +1,45 @@
+use std::cmp::Reverse;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
-use suite_223b::notification::{HintValue, Notification};
-use suite_223b::protocol::InternalMessage;
+use chrono::{DateTime, Utc};
+use suite_223b::notification::{CloseReason, HintValue, Notification, Urgency};
+use suite_223b::protocol::{InternalMessage, JobSchedule, NotificationServer, SystemStateRaw};
 use suite_223b::utils::errors::{WatsonError, WatsonErrorKind};
 use suite_223b::watson_err;
 use tokio::sync::{Notify, RwLock};
+use tokio::task::AbortHandle;
+use zbus::object_server::SignalEmitter;
 use zbus::zvariant::OwnedValue;
 use zbus::{Connection, interface};
 
 use crate::DAEMON_TX;
+use crate::core::metrics::COUNTERS;
+use crate::core::plugins::PluginHost;
 use crate::core::registry::ServiceRegistry;
-use crate::hardware::HardwareController;
+use crate::core::scheduler::Scheduler;
+use crate::core::services::Services;
+use crate::hardware::{Capabilities, HardwareController};
 use crate::software::SoftwareController;
 
+mod focus;
+mod relay;
+mod snapshot;
+mod snooze;
+
+pub use focus::FocusProfiles;
+pub use relay::relay_actions;
+pub use snapshot::load_snapshot;
+use snooze::{SnoozeStore, Snoozed};
+
 pub struct DaemonHandle {
     daemon: Arc<RwLock<NotificationDaemon>>,
 }
+impl DaemonHandle {
+    pub const PATH: &'static str = "/org/freedesktop/Notifications";
+}
 impl DaemonHandle {
     pub fn new(daemon: Arc<RwLock<NotificationDaemon>>) -> Self {
         Self { daemon }
@@ -25,42 +48,418 @@ impl DaemonHandle {
 
 pub struct DaemonSettings {
     pub silent: bool,
+    /// Follow the compositor's fullscreen/screencast state
+    pub auto_dnd: bool,
+    /// Silent state from before do-not-disturb was enabled automatically
+    auto_restore: Option<bool>,
+    /// Notifications received while silent, reset when do-not-disturb ends
+    missed: u32,
+}
+impl DaemonSettings {
+    pub fn new() -> Self {
+        Self {
+            silent: false,
+            auto_dnd: true,
+            auto_restore: None,
+            missed: 0,
+        }
+    }
+
+    pub fn missed(&self) -> u32 {
+        self.missed
+    }
+
+    /// Counts a notification that arrived while silent and tells the clients
+    pub fn count_missed(&mut self) {
+        if !self.silent {
+            return;
+        }
+        self.missed += 1;
+        let _result = DAEMON_TX
+            .get()
+            .map(|d| d.send(InternalMessage::MissedNotifications(self.missed)));
+    }
+
+    /// Sends the summary of what was missed once do-not-disturb ends
+    fn apply_silent(&mut self, silent: bool) {
+        let ended = self.silent && !silent;
+        self.silent = silent;
+        if ended && self.missed > 0 {
+            let missed = std::mem::take(&mut self.missed);
+            let _result = DAEMON_TX
+                .get()
+                .map(|d| d.send(InternalMessage::DndEnded { missed }));
+        }
+    }
+
+    /// Explicitly set by the user. Takes precedence over the automatic state.
+    pub fn set_silent(&mut self, silent: bool) {
+        self.apply_silent(silent);
+        self.auto_restore = None;
+    }
+
+    pub fn set_auto_dnd(&mut self, active: bool) {
+        if !self.auto_dnd {
+            return;
+        }
+        match (active, self.auto_restore) {
+            (true, None) => {
+                self.auto_restore = Some(self.silent);
+                self.apply_silent(true);
+            }
+            (false, Some(previous)) => {
+                self.apply_silent(previous);
+                self.auto_restore = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Override toggle. Disabling restores the state from before automatic DND kicked in.
+    pub fn set_auto_dnd_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.set_auto_dnd(false);
+        }
+        self.auto_dnd = enabled;
+    }
 }
 
+/// Follows a notification of the daemon itself: called with each action the user invokes, then
+/// with `None` once the notification is closed. Runs under the daemon's lock, so anything
+/// longer has to be spawned.
+pub type ActionHandler = Box<dyn FnMut(Option<&str>) + Send + Sync>;
+
 pub struct NotificationDaemon {
     id: u32,
     buffer: HashMap<u32, Notification>,
+    /// Pending expiry timers by notification id
+    timers: HashMap<u32, AbortHandle>,
+    /// Handlers of our own notifications by id, see `insert_with_action`
+    actions: HashMap<u32, ActionHandler>,
+    /// Our ids of notifications forwarded to the daemon owning the name, by its ids
+    relayed: HashMap<u32, u32>,
+    /// Session bus connection, serving `org.freedesktop.Notifications` unless the name is
+    /// taken or the daemon was started with `--no-notifications`
+    pub session: Option<Connection>,
+    /// Whether that connection owns the name, reported by `Request::GetStatus`
+    pub server: NotificationServer,
     pub wake_signal: Arc<Notify>,
     pub hardware: HardwareController,
     pub software: SoftwareController,
     pub settings: DaemonSettings,
+    pub focus: FocusProfiles,
     pub register: Arc<ServiceRegistry>,
+    pub scheduler: Arc<Scheduler>,
+    pub services: Services,
+    /// Connected plugins, see `core::plugins`
+    pub plugins: Arc<PluginHost>,
+    snoozed: SnoozeStore,
+    /// Answered to `Request::SystemState` after `--load-state`
+    restored_system: Option<SystemStateRaw>,
 }
 impl NotificationDaemon {
     pub async fn new() -> Result<Self, WatsonError> {
         let conn = Connection::system()
             .await
             .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))?;
+        let capabilities = Capabilities::detect(&conn).await;
         Ok(Self {
+            scheduler: Arc::new(Scheduler::load()),
+            services: Services::load(),
+            plugins: Arc::new(PluginHost::load()),
+            snoozed: SnoozeStore::load(),
+            focus: FocusProfiles::load(),
+            ..Self::with_controllers(
+                HardwareController::new(conn, capabilities),
+                SoftwareController::new().await,
+            )
+        })
+    }
+
+    /// A daemon on the given backends, e.g. a private bus and a fake audio actor in tests
+    pub fn with_controllers(hardware: HardwareController, software: SoftwareController) -> Self {
+        Self {
             id: 0,
             buffer: HashMap::new(),
+            timers: HashMap::new(),
+            actions: HashMap::new(),
+            relayed: HashMap::new(),
+            session: None,
+            server: NotificationServer::default(),
             wake_signal: Arc::new(Notify::new()),
-            hardware: HardwareController::new(conn),
-            software: SoftwareController::new().await,
-            settings: DaemonSettings { silent: false },
+            hardware,
+            software,
+            settings: DaemonSettings::new(),
+            focus: FocusProfiles::default(),
             register: Arc::new(ServiceRegistry::new()),
-        })
+            scheduler: Arc::new(Scheduler::default()),
+            services: Services::default(),
+            plugins: Arc::new(PluginHost::default()),
+            snoozed: SnoozeStore::default(),
+            restored_system: None,
+        }
+    }
+
+    /// Stores a notification from D-Bus or `Request::Notify` and tells connected clients.
+    /// Returns its id, which is `replaces_id` when that notification still exists. Held back
+    /// while a focus profile holds its application.
+    pub fn insert(
+        &mut self,
+        mut notification: Notification,
+        daemon: Weak<RwLock<NotificationDaemon>>,
+    ) -> u32 {
+        // Updating a notification keeps its id, unknown ids get a fresh one
+        let replaces_id = notification.replaces_id;
+        let id = if replaces_id != 0 && self.buffer.contains_key(&replaces_id) {
+            if let Some(timer) = self.timers.remove(&replaces_id) {
+                timer.abort();
+            }
+            replaces_id
+        } else {
+            self.id += 1;
+            self.id
+        };
+        notification.id = id;
+        if self.focus.holds(&notification) {
+            self.focus.hold(notification);
+            return id;
+        }
+        if matches!(
+            self.server,
+            NotificationServer::Taken { .. } | NotificationServer::Disabled
+        ) {
+            self.forward(&notification, daemon.clone());
+        }
+
+        // -1 and 0 keep the notification in the centre until it is dismissed. Critical
+        // notifications never expire on their own.
+        let expire_timeout = notification.expire_timeout;
+        if expire_timeout > 0 && !matches!(notification.urgency, Urgency::Critical) {
+            let timer = tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(expire_timeout as u64)).await;
+                let Some(daemon) = daemon.upgrade() else {
+                    return;
+                };
+                let mut daemon = daemon.write().await;
+                // Forget our own handle first so close() doesn't abort the running task
+                daemon.timers.remove(&id);
+                daemon.close(id, CloseReason::Expired).await;
+            });
+            self.timers.insert(id, timer.abort_handle());
+        }
+
+        self.buffer.insert(id, notification);
+        self.settings.count_missed();
+
+        // Notify that a new notification has been added
+        let _result = DAEMON_TX
+            .get()
+            .map(|d| d.send(InternalMessage::Notification(id)));
+
+        id
+    }
+
+    /// `insert` for notifications the daemon sends itself. `on_action` takes the place of the
+    /// `ActionInvoked` and `NotificationClosed` signals other applications listen for.
+    pub fn insert_with_action(
+        &mut self,
+        notification: Notification,
+        daemon: Weak<RwLock<NotificationDaemon>>,
+        on_action: ActionHandler,
+    ) -> u32 {
+        let id = self.insert(notification, daemon);
+        if let Some(mut replaced) = self.actions.insert(id, on_action) {
+            replaced(None);
+        }
+        id
     }
 
     pub fn get_by_id(&self, id: u32) -> Option<&Notification> {
         self.buffer.get(&id)
     }
 
-    pub fn pending_notifications(&self) -> Vec<Notification> {
-        self.buffer.values().cloned().collect()
+    pub fn stored(&self) -> impl Iterator<Item = &Notification> {
+        self.buffer.values()
+    }
+
+    /// A page of the stored notifications, newest first. Clients page by how many they have,
+    /// which stays valid as new notifications only ever come first.
+    pub fn pending_notifications(&self, offset: usize, limit: usize) -> Vec<Notification> {
+        let mut notifications: Vec<_> = self.buffer.values().collect();
+        notifications.sort_unstable_by_key(|n| Reverse(n.id));
+        notifications
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Removes a notification, tells connected clients and emits `NotificationClosed`.
+    /// Returns false if there was no notification with this id.
+    pub async fn close(&mut self, id: u32, reason: CloseReason) -> bool {
+        if let Some(timer) = self.timers.remove(&id) {
+            timer.abort();
+        }
+        if self.buffer.remove(&id).is_none() {
+            return false;
+        }
+        if let Some(mut handler) = self.actions.remove(&id) {
+            handler(None);
+        }
+        self.relayed.retain(|_, local| *local != id);
+
+        let _result = DAEMON_TX
+            .get()
+            .map(|d| d.send(InternalMessage::NotificationClosed { id, reason }));
+
+        if let Some(emitter) = self.emitter()
+            && let Err(e) = DaemonHandle::notification_closed(&emitter, id, reason as u32).await
+        {
+            eprintln!("{:?}", e);
+        }
+        true
+    }
+
+    /// Emits `ActionInvoked` and closes the notification unless it is resident
+    pub async fn invoke_action(&mut self, id: u32, action: String) -> bool {
+        let Some(notification) = self.buffer.get(&id) else {
+            return false;
+        };
+        let resident = matches!(
+            notification.hints.get("resident"),
+            Some(HintValue::Bool(true))
+        );
+
+        if let Some(emitter) = self.emitter()
+            && let Err(e) = DaemonHandle::action_invoked(&emitter, id, &action).await
+        {
+            eprintln!("{:?}", e);
+        }
+        if let Some(handler) = self.actions.get_mut(&id) {
+            handler(Some(&action));
+        }
+
+        if !resident {
+            self.close(id, CloseReason::Dismissed).await;
+        }
+        true
+    }
+
+    /// Hides a notification and delivers it again `seconds` from now. The sending application
+    /// isn't told, its actions still work once the notification is back.
+    pub fn snooze(&mut self, id: u32, seconds: u64) -> Result<(), WatsonError> {
+        let Some(notification) = self.buffer.remove(&id) else {
+            return Err(watson_err!(
+                WatsonErrorKind::InvalidData,
+                "No notification with id {}",
+                id
+            ));
+        };
+        if let Some(timer) = self.timers.remove(&id) {
+            timer.abort();
+        }
+
+        let wake = Utc::now() + chrono::Duration::seconds(seconds as i64);
+        if let Err(e) = self.snoozed.insert(Snoozed { notification, wake }) {
+            // Still delivered again, unless the daemon restarts first
+            eprintln!("{:?}", e);
+        }
+        self.schedule_wake(id, wake)?;
+
+        let _result = DAEMON_TX.get().map(|d| {
+            d.send(InternalMessage::NotificationClosed {
+                id,
+                reason: CloseReason::Snoozed,
+            })
+        });
+        Ok(())
+    }
+
+    /// Schedules the notifications snoozed before the last restart. Overdue ones come back
+    /// right away.
+    pub fn restore_snoozed(&mut self) {
+        let wakes: Vec<(u32, DateTime<Utc>)> = self
+            .snoozed
+            .iter()
+            .map(|s| (s.notification.id, s.wake))
+            .collect();
+        for (id, wake) in wakes {
+            // Ids start over on restart, new notifications must not take these
+            self.id = self.id.max(id);
+            if let Err(e) = self.schedule_wake(id, wake) {
+                eprintln!("{:?}", e);
+            }
+        }
+    }
+
+    /// Delivers a snoozed notification again
+    pub fn wake(&mut self, id: u32) {
+        let Some(Snoozed { notification, .. }) = self.snoozed.take(id) else {
+            return;
+        };
+        self.buffer.insert(id, notification);
+        let _result = DAEMON_TX
+            .get()
+            .map(|d| d.send(InternalMessage::Notification(id)));
+    }
+
+    fn schedule_wake(&self, id: u32, wake: DateTime<Utc>) -> Result<(), WatsonError> {
+        let daemon = self.services.daemon();
+        self.scheduler
+            .register(&snooze_job(id), JobSchedule::At(wake), move || {
+                let daemon = daemon.clone();
+                async move {
+                    if let Some(daemon) = daemon.upgrade() {
+                        daemon.write().await.wake(id);
+                    }
+                }
+            })
+    }
+
+    /// Shows a notification through the daemon owning the name, nobody would see it otherwise
+    fn forward(&self, notification: &Notification, daemon: Weak<RwLock<NotificationDaemon>>) {
+        let id = notification.id;
+        let Some(conn) = self.session.clone() else {
+            eprintln!("Notification {id} can't be delivered: no session bus connection");
+            return;
+        };
+        let replaces_id = self
+            .relayed
+            .iter()
+            .find(|(_, local)| **local == id)
+            .map_or(0, |(remote, _)| *remote);
+        let notification = notification.clone();
+        tokio::spawn(async move {
+            match relay::forward(&conn, &notification, replaces_id).await {
+                Ok(remote) => {
+                    if let Some(daemon) = daemon.upgrade() {
+                        daemon.write().await.relayed.insert(remote, id);
+                    }
+                }
+                Err(e) => eprintln!("Notification {id} can't be delivered: {:?}", e),
+            }
+        });
+    }
+
+    /// Signals are only ours to send while we own the name
+    fn emitter(&self) -> Option<SignalEmitter<'static>> {
+        if matches!(
+            self.server,
+            NotificationServer::Taken { .. } | NotificationServer::Disabled
+        ) {
+            return None;
+        }
+        let conn = self.session.as_ref()?;
+        SignalEmitter::new(conn, DaemonHandle::PATH).ok()
     }
 }
 
+/// Scheduler id of the job that brings back a snoozed notification
+pub fn snooze_job(id: u32) -> String {
+    format!("snooze-{id}")
+}
+
 #[interface(name = "org.freedesktop.Notifications")]
 impl DaemonHandle {
     async fn notify(
@@ -74,10 +473,9 @@ impl DaemonHandle {
         hints: HashMap<String, OwnedValue>,
         expire_timeout: i32,
     ) -> u32 {
-        // log!("Notification received");
-        let mut daemon = self.daemon.write().await;
-        daemon.id += 1;
-        let id = daemon.id;
+        COUNTERS
+            .notifications_received
+            .fetch_add(1, Ordering::Relaxed);
 
         let urgency = hints
             .get("urgency")
@@ -90,7 +488,7 @@ impl DaemonHandle {
             .collect();
 
         let notification = Notification {
-            id,
+            id: 0,
             app_name,
             replaces_id,
             app_icon,
@@ -101,14 +499,19 @@ impl DaemonHandle {
             expire_timeout,
             urgency: urgency.into(),
         };
-        daemon.buffer.insert(id, notification);
-
-        // Notify that a new notification has been added
-        let _result = DAEMON_TX
-            .get()
-            .map(|d| d.send(InternalMessage::Notification(id)));
+        self.daemon
+            .write()
+            .await
+            .insert(notification, Arc::downgrade(&self.daemon))
+    }
 
-        id
+    async fn close_notification(&self, id: u32) {
+        // Closing an unknown notification is not an error
+        self.daemon
+            .write()
+            .await
+            .close(id, CloseReason::ClosedByCall)
+            .await;
     }
 
     fn get_server_information(&self) -> (String, String, String, String) {
@@ -121,6 +524,25 @@ impl DaemonHandle {
     }
 
     fn get_capabilities(&self) -> Vec<String> {
-        vec!["body".into(), "actions".into()]
+        vec![
+            "actions".into(),
+            "body".into(),
+            "icon-static".into(),
+            "persistence".into(),
+        ]
     }
+
+    #[zbus(signal)]
+    async fn notification_closed(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        reason: u32,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn action_invoked(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        action_key: &str,
+    ) -> zbus::Result<()>;
 }
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use suite_223b::notification::{HintValue, Notification, Urgency};
+use suite_223b::utils::errors::{WatsonError, WatsonErrorKind};
+use suite_223b::watson_err;
+use tokio::sync::RwLock;
+use zbus::zvariant::Value;
+use zbus::{Connection, Proxy};
+
+use super::{DaemonHandle, NotificationDaemon};
+
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+
+async fn proxy(conn: &Connection) -> Result<Proxy<'static>, WatsonError> {
+    Proxy::new(
+        conn,
+        NOTIFICATIONS_NAME,
+        DaemonHandle::PATH,
+        NOTIFICATIONS_NAME,
+    )
+    .await
+    .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))
+}
+
+/// Hands one of our notifications to whoever owns `org.freedesktop.Notifications`. Returns the
+/// id the other daemon gave it.
+pub async fn forward(
+    conn: &Connection,
+    notification: &Notification,
+    replaces_id: u32,
+) -> Result<u32, WatsonError> {
+    let mut hints: HashMap<&str, Value> = notification
+        .hints
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                HintValue::String(s) => Value::from(s.as_str()),
+                HintValue::Int(i) => Value::from(*i),
+                HintValue::Uint(u) => Value::from(*u),
+                HintValue::Bool(b) => Value::from(*b),
+                HintValue::Byte(b) => Value::from(*b),
+                HintValue::None => return None,
+            };
+            Some((key.as_str(), value))
+        })
+        .collect();
+    let urgency: u8 = match notification.urgency {
+        Urgency::Low => 0,
+        Urgency::Normal => 1,
+        Urgency::Critical => 2,
+    };
+    hints.insert("urgency", Value::from(urgency));
+
+    proxy(conn)
+        .await?
+        .call(
+            "Notify",
+            &(
+                &notification.app_name,
+                replaces_id,
+                &notification.app_icon,
+                &notification.summary,
+                &notification.body,
+                &notification.actions,
+                hints,
+                notification.expire_timeout,
+            ),
+        )
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))
+}
+
+/// Runs the actions the user picks on forwarded notifications. Ends with the connection.
+pub async fn relay_actions(
+    daemon: Arc<RwLock<NotificationDaemon>>,
+    conn: Connection,
+) -> Result<(), WatsonError> {
+    let proxy = proxy(&conn).await?;
+    let mut invoked = proxy
+        .receive_signal("ActionInvoked")
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))?;
+    let mut closed = proxy
+        .receive_signal("NotificationClosed")
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))?;
+
+    loop {
+        tokio::select! {
+            Some(msg) = invoked.next() => {
+                let Ok((remote, action)) = msg.body().deserialize::<(u32, String)>() else {
+                    continue;
+                };
+                let mut daemon = daemon.write().await;
+                if let Some(id) = daemon.relayed.get(&remote).copied() {
+                    daemon.invoke_action(id, action).await;
+                }
+            }
+            Some(msg) = closed.next() => {
+                // The notification stays in our own centre
+                let Ok((remote, _)) = msg.body().deserialize::<(u32, u32)>() else {
+                    continue;
+                };
+                daemon.write().await.relayed.remove(&remote);
+            }
+            else => return Ok(()),
+        }
+    }
+}
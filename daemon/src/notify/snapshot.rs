@@ -0,0 +1,83 @@
+use std::{fs, path::Path, sync::Arc};
+
+use chrono::Utc;
+use suite_223b::{
+    notification::Notification,
+    protocol::{StateSnapshot, SystemStateRaw},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+use super::{NotificationDaemon, snooze::SnoozeStore};
+use crate::{
+    core::{scheduler::Scheduler, services::Services},
+    hardware::SystemStateBuilder,
+};
+
+impl NotificationDaemon {
+    /// The state of the hardware, or the one from the snapshot after `--load-state`
+    pub async fn system_state(&mut self) -> Result<SystemStateRaw, WatsonError> {
+        match &self.restored_system {
+            Some(state) => Ok(state.clone()),
+            None => SystemStateBuilder::new(&mut self.hardware).await,
+        }
+    }
+
+    /// Answers `Request::DumpState`
+    pub async fn snapshot(&mut self) -> Result<StateSnapshot, WatsonError> {
+        let system = self.system_state().await?;
+        let mut notifications: Vec<Notification> = self
+            .buffer
+            .values()
+            .map(|n| Notification {
+                body: String::new(),
+                ..n.clone()
+            })
+            .collect();
+        notifications.sort_unstable_by_key(|n| n.id);
+
+        Ok(StateSnapshot {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            taken: Utc::now(),
+            system,
+            silent: self.settings.silent,
+            auto_dnd: self.settings.auto_dnd,
+            notifications,
+            registered_services: self.register.registered(),
+            unavailable_services: self.register.unavailable(),
+            disabled_services: self.services.disabled().to_vec(),
+            jobs: self.scheduler.jobs(),
+        })
+    }
+
+    /// Takes over a snapshot in place of what was saved on this system. Must run before the
+    /// services start, nothing restored is written back to disk.
+    pub fn restore(&mut self, snapshot: StateSnapshot) {
+        // Without a path the scheduler doesn't save client jobs
+        self.scheduler = Arc::new(Scheduler::default());
+        for job in snapshot.jobs.into_iter().filter(|j| j.persistent) {
+            if let Err(e) = self.scheduler.schedule(job.id, job.schedule) {
+                eprintln!("{:?}", e);
+            }
+        }
+        self.services = Services::restored(snapshot.disabled_services);
+        self.snoozed = SnoozeStore::default();
+
+        self.settings.set_silent(snapshot.silent);
+        self.settings.set_auto_dnd_enabled(snapshot.auto_dnd);
+        self.register
+            .set_registered_services(snapshot.registered_services);
+        for notification in snapshot.notifications {
+            self.id = self.id.max(notification.id);
+            self.buffer.insert(notification.id, notification);
+        }
+        self.restored_system = Some(snapshot.system);
+    }
+}
+
+/// Reads a snapshot written by `watson dump-state`
+pub fn load_snapshot(path: &Path) -> Result<StateSnapshot, WatsonError> {
+    let data = fs::read(path).map_err(|e| watson_err!(WatsonErrorKind::FileRead, e.to_string()))?;
+    serde_json::from_slice(&data)
+        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))
+}
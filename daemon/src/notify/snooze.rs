@@ -0,0 +1,82 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use suite_223b::{
+    notification::Notification,
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::get_data_dir,
+    },
+    watson_err,
+};
+
+const SNOOZE_FILE: &str = "snoozed_notifications.json";
+
+/// A notification put away by `Request::SnoozeNotification`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snoozed {
+    pub notification: Notification,
+    pub wake: DateTime<Utc>,
+}
+
+/// Snoozed notifications by id, kept across restarts
+#[derive(Default)]
+pub struct SnoozeStore {
+    items: HashMap<u32, Snoozed>,
+    /// Snoozed notifications are lost on restart without one
+    path: Option<PathBuf>,
+}
+impl SnoozeStore {
+    /// The notifications snoozed before the last restart
+    pub fn load() -> Self {
+        let path = match get_data_dir() {
+            Ok(dir) => dir.join(SNOOZE_FILE),
+            Err(e) => {
+                eprintln!("{:?}", e);
+                return Self::default();
+            }
+        };
+        let saved: Vec<Snoozed> = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                eprintln!(
+                    "{:?}",
+                    watson_err!(WatsonErrorKind::Deserialize, e.to_string())
+                );
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            items: saved.into_iter().map(|s| (s.notification.id, s)).collect(),
+            path: Some(path),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Snoozed> {
+        self.items.values()
+    }
+
+    pub fn insert(&mut self, snoozed: Snoozed) -> Result<(), WatsonError> {
+        self.items.insert(snoozed.notification.id, snoozed);
+        self.save()
+    }
+
+    pub fn take(&mut self, id: u32) -> Option<Snoozed> {
+        let snoozed = self.items.remove(&id)?;
+        if let Err(e) = self.save() {
+            eprintln!("{:?}", e);
+        }
+        Some(snoozed)
+    }
+
+    fn save(&self) -> Result<(), WatsonError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let saved: Vec<&Snoozed> = self.items.values().collect();
+        let data = serde_json::to_vec_pretty(&saved)
+            .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+        fs::write(path, data).map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))
+    }
+}
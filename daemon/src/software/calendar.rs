@@ -3,7 +3,14 @@ use std::{collections::HashSet, sync::Mutex};
 use chrono::{Local, Utc};
 use suite_223b::{
     auth::CredentialManager,
-    calendar::utils::{CalDavEvent, CalEventType, structs::EventFilter},
+    calendar::{
+        protocol::CalendarProvider,
+        subscription::SubscriptionClient,
+        utils::{
+            CalDavEvent, CalEventType, CalendarInfo, CalendarRegistry, OccurrenceCache,
+            structs::EventFilter,
+        },
+    },
 };
 
 pub struct EventCache {
@@ -19,14 +26,64 @@ impl EventCache {
     }
 }
 
+/// An initialized provider together with its calendars. Kept alive between fetches so
+/// providers can remember sync tokens / ctags.
+pub struct CalendarSource {
+    provider: Box<dyn CalendarProvider>,
+    calendars: Vec<CalendarInfo>,
+}
+
 pub struct CalendarBackend {
     pub cache: Mutex<EventCache>,
+    sources: tokio::sync::Mutex<Vec<CalendarSource>>,
 }
 impl CalendarBackend {
     pub fn new() -> Self {
         Self {
             cache: Mutex::new(EventCache::new()),
+            sources: tokio::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    async fn load_sources() -> Vec<CalendarSource> {
+        let mut credential_manager = match CredentialManager::new() {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                return Vec::new();
+            }
+        };
+        if let Err(e) = credential_manager.unlock() {
+            eprintln!("{:?}", e);
+            return Vec::new();
+        }
+
+        let mut sources = Vec::new();
+        let providers = credential_manager
+            .credentials
+            .into_iter()
+            .filter_map(|account| account.provider())
+            .chain(SubscriptionClient::load());
+        for mut provider in providers {
+            if let Err(e) = provider.init().await {
+                eprintln!("{:?}", e);
+                continue;
+            }
+
+            let calendars = match provider.get_calendars().await {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("{:?}", e);
+                    continue;
+                }
+            };
+
+            sources.push(CalendarSource {
+                provider,
+                calendars,
+            });
         }
+        sources
     }
 
     pub fn get_events_with_filter(&self, filter: EventFilter) -> Vec<CalDavEvent> {
@@ -61,9 +118,11 @@ impl CalendarBackend {
                     + chrono::Duration::from_std(look_ahead)
                         .unwrap_or_else(|_| chrono::Duration::zero());
 
+                // Nearby events drive reminders, which silent calendars opt out of
                 cache
                     .timed
                     .iter()
+                    .filter(|event| !event.calendar_info.silent)
                     .filter(|event| {
                         if let Some(start_time) = event.start_utc() {
                             start_time >= past_limit && start_time <= future_limit
@@ -77,20 +136,14 @@ impl CalendarBackend {
         }
     }
 
-    pub async fn fetch_for_today(&mut self) -> usize {
-        let mut credential_manager = match CredentialManager::new() {
-            Ok(m) => m,
-            Err(e) => {
-                eprintln!("{:?}", e);
-                return 0;
-            }
-        };
-        if let Err(e) = credential_manager.unlock() {
-            eprintln!("{:?}", e);
-            return 0;
+    pub async fn fetch_for_today(&self) -> usize {
+        let mut sources = self.sources.lock().await;
+        if sources.is_empty() {
+            *sources = Self::load_sources().await;
         }
 
         let today = Local::now().date_naive();
+        OccurrenceCache::global().evict_before(&today);
         let mut new_timed = Vec::new();
         let mut new_allday = Vec::new();
         let seen_ids: HashSet<String> = {
@@ -105,47 +158,26 @@ impl CalendarBackend {
             ids
         };
 
-        for account in credential_manager.credentials {
-            let Some(mut provider) = account.provider() else {
-                continue;
-            };
-
-            if let Err(e) = provider.init().await {
-                // TODO: Log err
-                eprintln!("{:?}", e);
-                continue;
-            }
-
-            let calendars = match provider.get_calendars().await {
+        for source in sources.iter_mut() {
+            let mut events = match source.provider.get_events(source.calendars.clone()).await {
                 Ok(v) => v,
                 Err(e) => {
-                    // TODO: Log err
-                    eprintln!("{:?}", e);
-                    continue;
-                }
-            };
-
-            let mut events = match provider.get_events(calendars).await {
-                Ok(v) => v,
-                Err(e) => {
-                    // TODO: Log err
                     eprintln!("{:?}", e);
                     continue;
                 }
             };
 
             // Filter events
-            events.retain(|e| e.occurs_on_day(&today));
+            events.retain(|e| e.occurs_on_day(&today) && !seen_ids.contains(&e.uid));
+            CalendarRegistry::global().intern_events(&mut events);
 
             // Extend the Events
             {
                 for item in events {
-                    if !seen_ids.contains(&item.uid) {
-                        item.seen.set(false);
-                        match item.event_type {
-                            CalEventType::Timed => new_timed.push(item),
-                            CalEventType::AllDay => new_allday.push(item),
-                        }
+                    item.seen.set(false);
+                    match item.event_type {
+                        CalEventType::Timed => new_timed.push(item),
+                        CalEventType::AllDay => new_allday.push(item),
                     }
                 }
             }
@@ -162,6 +194,66 @@ impl CalendarBackend {
         }
         num_changes
     }
+
+    /// Refetches calendars whose contents changed remotely and replaces their cached events.
+    /// Returns the hrefs of the changed calendars.
+    pub async fn refresh_changed(&self) -> Vec<String> {
+        let mut sources = self.sources.lock().await;
+        let today = Local::now().date_naive();
+        let mut changed_all = Vec::new();
+
+        for source in sources.iter_mut() {
+            let changed = match source.provider.changed_calendars(&source.calendars).await {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("{:?}", e);
+                    continue;
+                }
+            };
+            if changed.is_empty() {
+                continue;
+            }
+
+            let calendars: Vec<CalendarInfo> = source
+                .calendars
+                .iter()
+                .filter(|c| changed.contains(&c.href))
+                .cloned()
+                .collect();
+            let mut events = match source.provider.get_events(calendars).await {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("{:?}", e);
+                    continue;
+                }
+            };
+            let occurrences = OccurrenceCache::global();
+            events.retain(|e| {
+                occurrences.invalidate(&e.uid);
+                e.occurs_on_day(&today)
+            });
+            CalendarRegistry::global().intern_events(&mut events);
+
+            {
+                let mut cache = self.cache.lock().expect("Failed to lock mutex");
+                cache
+                    .timed
+                    .retain(|e| !changed.contains(&e.calendar_info.href));
+                cache
+                    .allday
+                    .retain(|e| !changed.contains(&e.calendar_info.href));
+                for item in events {
+                    match item.event_type {
+                        CalEventType::Timed => cache.timed.push(item),
+                        CalEventType::AllDay => cache.allday.push(item),
+                    }
+                }
+            }
+
+            changed_all.extend(changed);
+        }
+        changed_all
+    }
 }
 
 #[cfg(test)]
@@ -169,6 +261,7 @@ mod tests {
     use super::*;
     use chrono::Utc;
     use std::cell::Cell;
+    use std::sync::Arc;
     use std::time::Duration;
     use suite_223b::calendar::utils::structs::DateTimeSpec;
 
@@ -252,6 +345,37 @@ mod tests {
         assert!(uids.contains(&"future".into()));
     }
 
+    #[test]
+    fn test_nearby_skips_silent_calendars() {
+        let backend = CalendarBackend::new();
+        let mut holiday = create_test_event("holiday", "Public Holiday", false, 5);
+        holiday.calendar_info = Arc::new(CalendarInfo {
+            subscription: true,
+            silent: true,
+            ..Default::default()
+        });
+        {
+            let mut cache = backend.cache.lock().unwrap();
+            cache.timed.push(holiday);
+            cache
+                .timed
+                .push(create_test_event("meeting", "Meeting", false, 5));
+        }
+
+        let results = backend.get_events_with_filter(EventFilter::Nearby {
+            look_back: Duration::from_secs(20 * 60),
+            look_ahead: Duration::from_secs(20 * 60),
+        });
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].uid, "meeting");
+
+        // Silent calendars still show up in the day view
+        let results = backend.get_events_with_filter(EventFilter::Today {
+            include_allday: true,
+        });
+        assert_eq!(results.len(), 2);
+    }
+
     #[test]
     fn test_cache_deduplication_logic() {
         let backend = CalendarBackend::new();
@@ -317,4 +441,29 @@ mod tests {
         let final_cache = backend.cache.lock().unwrap();
         assert_eq!(final_cache.timed.len(), 2);
     }
+
+    #[test]
+    fn test_occurrence_cache_reevaluates_updated_event() {
+        use suite_223b::calendar::utils::structs::RecurrenceRule;
+
+        let today = Local::now().date_naive();
+        let tomorrow = today.succ_opt().unwrap();
+
+        // Daily event, so it occurs on both days
+        let mut event = create_test_event("recurring-789", "Daily Sync", false, 0);
+        event.recurrence = Some(RecurrenceRule::new("FREQ=DAILY".into()));
+        assert!(event.occurs_on_day(&tomorrow));
+        assert!(event.occurs_on_day(&tomorrow));
+
+        // Remote update ends the series today
+        event.recurrence = Some(RecurrenceRule::new(format!(
+            "FREQ=DAILY;UNTIL={}",
+            today.format("%Y%m%d")
+        )));
+        event.sequence = Some(1);
+        assert!(!event.occurs_on_day(&tomorrow));
+
+        OccurrenceCache::global().invalidate("recurring-789");
+        assert!(!event.occurs_on_day(&tomorrow));
+    }
 }
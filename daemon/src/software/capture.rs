@@ -0,0 +1,559 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::process::CommandExt,
+    },
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{
+        Arc, Weak,
+        atomic::{AtomicU32, Ordering},
+    },
+};
+
+use futures_util::StreamExt;
+use reqwest::Url;
+use suite_223b::{
+    notification::{HintValue, Notification},
+    protocol::{InternalMessage, PickedColor},
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::home_dir,
+    },
+    watson_err,
+};
+use tokio::sync::{Mutex, OnceCell, RwLock};
+use zbus::{
+    Connection, Proxy,
+    zvariant::{DynamicType, ObjectPath, OwnedObjectPath, OwnedValue, Value},
+};
+
+use crate::{DAEMON_TX, notify::NotificationDaemon, utils::command::detach};
+
+pub(crate) const PORTAL_DEST: &str = "org.freedesktop.portal.Desktop";
+pub(crate) const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SCREENSHOT_IFACE: &str = "org.freedesktop.portal.Screenshot";
+const SCREENCAST_IFACE: &str = "org.freedesktop.portal.ScreenCast";
+
+/// File descriptor number of the PipeWire remote inside the recorder process
+const REMOTE_FD: i32 = 3;
+
+struct Recording {
+    session: OwnedObjectPath,
+    recorder: Child,
+    path: PathBuf,
+}
+
+/// Screenshots and screen recordings through xdg-desktop-portal.
+///
+/// Recordings are encoded by `gst-launch-1.0` (`pipewiresrc`, `vp8enc`, `webmmux`) from the
+/// PipeWire stream the ScreenCast portal hands out.
+pub struct ScreenCapture {
+    session: OnceCell<Connection>,
+    recording: Mutex<Option<Recording>>,
+}
+impl ScreenCapture {
+    pub fn new() -> Self {
+        Self {
+            session: OnceCell::new(),
+            recording: Mutex::new(None),
+        }
+    }
+
+    async fn conn(&self) -> Result<&Connection, WatsonError> {
+        self.session
+            .get_or_try_init(|| async {
+                Connection::session()
+                    .await
+                    .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))
+            })
+            .await
+    }
+
+    /// Takes a screenshot and shows a notification for it once it was saved
+    pub async fn screenshot(
+        &self,
+        daemon: Weak<RwLock<NotificationDaemon>>,
+    ) -> Result<(), WatsonError> {
+        let conn = self.conn().await?;
+        let token = handle_token();
+        let options = HashMap::from([
+            ("handle_token", Value::from(token.as_str())),
+            ("interactive", Value::from(false)),
+        ]);
+
+        let results =
+            portal_request(conn, SCREENSHOT_IFACE, "Screenshot", &("", options), &token).await?;
+        let uri = result_str(&results, "uri")?;
+
+        let notification = CaptureNotification {
+            summary: "Screenshot taken",
+            body: uri_to_path(&uri).display().to_string(),
+            icon: "camera-photo-symbolic",
+            uri,
+            mime: "image/png",
+        };
+        notification.show(&daemon).await;
+        Ok(())
+    }
+
+    /// Lets the user pick a color on screen
+    pub async fn pick_color(&self) -> Result<PickedColor, WatsonError> {
+        let conn = self.conn().await?;
+        let token = handle_token();
+        let options = HashMap::from([("handle_token", Value::from(token.as_str()))]);
+
+        let results =
+            portal_request(conn, SCREENSHOT_IFACE, "PickColor", &("", options), &token).await?;
+        result_color(&results).ok_or_else(|| {
+            watson_err!(
+                WatsonErrorKind::InvalidData,
+                "Portal response is missing `color`"
+            )
+        })
+    }
+
+    pub async fn is_recording(&self) -> bool {
+        self.recording.lock().await.is_some()
+    }
+
+    /// Starts a recording, or stops and saves the running one. Returns if a recording is running
+    /// afterwards.
+    pub async fn toggle_recording(
+        &self,
+        daemon: Weak<RwLock<NotificationDaemon>>,
+    ) -> Result<bool, WatsonError> {
+        let conn = self.conn().await?;
+        let mut slot = self.recording.lock().await;
+
+        let active = match slot.take() {
+            Some(recording) => {
+                let path = recording.path.clone();
+                stop_recording(conn, recording).await?;
+                broadcast_recording(false);
+
+                let notification = CaptureNotification {
+                    summary: "Recording saved",
+                    body: path.display().to_string(),
+                    icon: "media-record-symbolic",
+                    uri: file_uri(&path),
+                    mime: "text/uri-list",
+                };
+                // Showing it takes the daemon's lock, don't hold ours meanwhile
+                tokio::spawn(async move { notification.show(&daemon).await });
+                false
+            }
+            None => {
+                slot.replace(start_recording(conn).await?);
+                broadcast_recording(true);
+                true
+            }
+        };
+        Ok(active)
+    }
+}
+
+fn broadcast_recording(active: bool) {
+    let _result = DAEMON_TX
+        .get()
+        .map(|d| d.send(InternalMessage::RecordingState(active)));
+}
+
+async fn start_recording(conn: &Connection) -> Result<Recording, WatsonError> {
+    // CreateSession
+    let token = handle_token();
+    let options = HashMap::from([
+        ("handle_token", Value::from(token.as_str())),
+        ("session_handle_token", Value::from(handle_token())),
+    ]);
+    let results =
+        portal_request(conn, SCREENCAST_IFACE, "CreateSession", &(options,), &token).await?;
+    let session = ObjectPath::try_from(result_str(&results, "session_handle")?)
+        .map(OwnedObjectPath::from)
+        .map_err(|e| watson_err!(WatsonErrorKind::InvalidData, e.to_string()))?;
+
+    // SelectSources: monitors and windows, cursor embedded into the stream
+    let token = handle_token();
+    let options = HashMap::from([
+        ("handle_token", Value::from(token.as_str())),
+        ("types", Value::from(1u32 | 2u32)),
+        ("cursor_mode", Value::from(2u32)),
+        ("multiple", Value::from(false)),
+    ]);
+    portal_request(
+        conn,
+        SCREENCAST_IFACE,
+        "SelectSources",
+        &(&session, options),
+        &token,
+    )
+    .await?;
+
+    // Start shows the source picker
+    let token = handle_token();
+    let options = HashMap::from([("handle_token", Value::from(token.as_str()))]);
+    let results = portal_request(
+        conn,
+        SCREENCAST_IFACE,
+        "Start",
+        &(&session, "", options),
+        &token,
+    )
+    .await?;
+    let node = stream_node(&results).ok_or_else(|| {
+        watson_err!(
+            WatsonErrorKind::InvalidData,
+            "ScreenCast portal returned no stream"
+        )
+    })?;
+
+    let proxy = Proxy::new(conn, PORTAL_DEST, PORTAL_PATH, SCREENCAST_IFACE)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+    let remote: zbus::zvariant::OwnedFd = proxy
+        .call(
+            "OpenPipeWireRemote",
+            &(&session, HashMap::<&str, Value>::new()),
+        )
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
+    let remote = OwnedFd::from(remote);
+
+    let path = recordings_dir()?.join(format!(
+        "watson-{}.webm",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let recorder = spawn_recorder(&remote, node, &path)?;
+
+    Ok(Recording {
+        session,
+        recorder,
+        path,
+    })
+}
+
+async fn stop_recording(conn: &Connection, recording: Recording) -> Result<(), WatsonError> {
+    let Recording {
+        session,
+        mut recorder,
+        ..
+    } = recording;
+
+    // `-e` makes gst-launch send EOS on SIGINT, so the container is finalized
+    unsafe {
+        libc::kill(recorder.id() as i32, libc::SIGINT);
+    }
+    tokio::task::spawn_blocking(move || recorder.wait())
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::TaskJoin, e.to_string()))?
+        .map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, e.to_string()))?;
+
+    let proxy = Proxy::new(
+        conn,
+        PORTAL_DEST,
+        &session,
+        "org.freedesktop.portal.Session",
+    )
+    .await
+    .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+    proxy
+        .call::<_, _, ()>("Close", &())
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))
+}
+
+fn spawn_recorder(remote: &OwnedFd, node: u32, path: &Path) -> Result<Child, WatsonError> {
+    let raw = remote.as_raw_fd();
+    let mut command = Command::new("gst-launch-1.0");
+    command
+        .arg("-e")
+        .args([
+            "pipewiresrc",
+            &format!("fd={REMOTE_FD}"),
+            &format!("path={node}"),
+        ])
+        .args([
+            "do-timestamp=true",
+            "keepalive-time=1000",
+            "!",
+            "videoconvert",
+            "!",
+        ])
+        .args(["queue", "!", "vp8enc", "deadline=1", "!", "webmmux", "!"])
+        .args(["filesink", &format!("location={}", path.display())])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    // Only a single syscall between fork and exec, dup2 also clears CLOEXEC on the copy
+    unsafe {
+        command.pre_exec(move || {
+            if libc::dup2(raw, REMOTE_FD) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    command
+        .spawn()
+        .map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, e.to_string()))
+}
+
+/// Notification with "Copy" and "Open" actions for a captured file
+struct CaptureNotification {
+    summary: &'static str,
+    body: String,
+    icon: &'static str,
+    uri: String,
+    mime: &'static str,
+}
+impl CaptureNotification {
+    async fn show(self, daemon: &Weak<RwLock<NotificationDaemon>>) {
+        let Some(strong) = daemon.upgrade() else {
+            return;
+        };
+        let notification = Notification {
+            app_name: "Watson".into(),
+            app_icon: self.icon.into(),
+            summary: self.summary.into(),
+            body: self.body.clone(),
+            actions: ["copy", "Copy", "open", "Open"].map(Into::into).to_vec(),
+            hints: HashMap::from([("image-path".into(), HintValue::String(self.uri.clone()))]),
+            expire_timeout: -1,
+            ..Default::default()
+        };
+        let capture = Arc::new(self);
+        let on_action = Box::new(move |action: Option<&str>| {
+            let Some(action) = action.map(str::to_string) else {
+                return;
+            };
+            let capture = Arc::clone(&capture);
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = capture.invoke(&action) {
+                    eprintln!("{:?}", e);
+                }
+            });
+        });
+        strong
+            .write()
+            .await
+            .insert_with_action(notification, daemon.clone(), on_action);
+    }
+
+    fn invoke(&self, action: &str) -> Result<(), WatsonError> {
+        match action {
+            "open" => detach(Command::new("xdg-open").arg(&self.uri)),
+            "copy" => {
+                let mut command = Command::new("wl-copy");
+                command.args(["--type", self.mime]);
+                if self.mime == "text/uri-list" {
+                    command.arg(&self.uri).stdin(Stdio::null());
+                } else {
+                    let file = File::open(uri_to_path(&self.uri))
+                        .map_err(|e| watson_err!(WatsonErrorKind::FileOpen, e.to_string()))?;
+                    command.stdin(file);
+                }
+                // wl-copy forks into the background to serve the selection
+                command
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .map(|_| ())
+                    .map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, e.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Calls a portal method and waits for the `Response` signal on its request object
+pub(crate) async fn portal_request<B>(
+    conn: &Connection,
+    interface: &str,
+    method: &str,
+    body: &B,
+    token: &str,
+) -> Result<HashMap<String, OwnedValue>, WatsonError>
+where
+    B: serde::Serialize + DynamicType,
+{
+    let sender = conn
+        .unique_name()
+        .map(|n| n.trim_start_matches(':').replace('.', "_"))
+        .ok_or_else(|| watson_err!(WatsonErrorKind::DBusConnect, "No unique bus name"))?;
+    let request_path = format!("{PORTAL_PATH}/request/{sender}/{token}");
+
+    // Subscribe before calling, the response may arrive before the call returns
+    let request = Proxy::new(
+        conn,
+        PORTAL_DEST,
+        request_path,
+        "org.freedesktop.portal.Request",
+    )
+    .await
+    .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+    let mut responses = request
+        .receive_signal("Response")
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
+
+    let proxy = Proxy::new(conn, PORTAL_DEST, PORTAL_PATH, interface)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+    proxy
+        .call::<_, _, OwnedObjectPath>(method, body)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
+
+    let msg = responses.next().await.ok_or_else(|| {
+        watson_err!(
+            WatsonErrorKind::DBusProxyCall,
+            "Portal request closed without a response"
+        )
+    })?;
+    let (code, results) = msg
+        .body()
+        .deserialize::<(u32, HashMap<String, OwnedValue>)>()
+        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+
+    match code {
+        0 => Ok(results),
+        1 => Err(watson_err!(
+            WatsonErrorKind::DBusProxyCall,
+            "{}.{} was cancelled",
+            interface,
+            method
+        )),
+        _ => Err(watson_err!(
+            WatsonErrorKind::DBusProxyCall,
+            "{}.{} failed",
+            interface,
+            method
+        )),
+    }
+}
+
+pub(crate) fn result_str(
+    results: &HashMap<String, OwnedValue>,
+    key: &str,
+) -> Result<String, WatsonError> {
+    match results.get(key).map(|v| &**v) {
+        Some(Value::Str(s)) => Ok(s.to_string()),
+        Some(Value::ObjectPath(p)) => Ok(p.to_string()),
+        _ => Err(watson_err!(
+            WatsonErrorKind::InvalidData,
+            "Portal response is missing `{}`",
+            key
+        )),
+    }
+}
+
+/// PipeWire node of the first stream in a `Start` response, `streams` is `a(ua{sv})`
+fn stream_node(results: &HashMap<String, OwnedValue>) -> Option<u32> {
+    let Value::Array(streams) = &**results.get("streams")? else {
+        return None;
+    };
+    streams.iter().find_map(|stream| match stream {
+        Value::Structure(s) => s.fields().first().and_then(|f| u32::try_from(f).ok()),
+        _ => None,
+    })
+}
+
+/// `color` of a `PickColor` response, `(ddd)` with channels from 0 to 1
+fn result_color(results: &HashMap<String, OwnedValue>) -> Option<PickedColor> {
+    let Value::Structure(color) = &**results.get("color")? else {
+        return None;
+    };
+    let channel = |i: usize| -> Option<u8> {
+        let value = f64::try_from(color.fields().get(i)?).ok()?;
+        Some((value.clamp(0.0, 1.0) * 255.0).round() as u8)
+    };
+    Some(PickedColor {
+        red: channel(0)?,
+        green: channel(1)?,
+        blue: channel(2)?,
+    })
+}
+
+pub(crate) fn handle_token() -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    format!(
+        "watson{}_{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// `$XDG_VIDEOS_DIR`, falling back to `~/Videos`
+fn recordings_dir() -> Result<PathBuf, WatsonError> {
+    let dir = match std::env::var("XDG_VIDEOS_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => home_dir()?.join("Videos"),
+    };
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| watson_err!(WatsonErrorKind::DirCreate, e.to_string()))?;
+    Ok(dir)
+}
+
+/// `file://` uri of an absolute path, percent encoded
+fn file_uri(path: &Path) -> String {
+    Url::from_file_path(path)
+        .map(String::from)
+        .unwrap_or_else(|()| format!("file://{}", path.display()))
+}
+
+/// Local path of a `file://` uri, percent escapes decoded
+fn uri_to_path(uri: &str) -> PathBuf {
+    let raw = uri.strip_prefix("file://").unwrap_or(uri).as_bytes();
+    let mut out = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        let escaped = (raw[i] == b'%')
+            .then(|| raw.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(raw[i]);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&out).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_color() {
+        let color = OwnedValue::try_from(Value::from((1.0f64, 0.5f64, 0.0f64))).unwrap();
+        let results = HashMap::from([("color".to_string(), color)]);
+        assert_eq!(
+            result_color(&results),
+            Some(PickedColor {
+                red: 255,
+                green: 128,
+                blue: 0,
+            })
+        );
+        assert_eq!(result_color(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_file_uri_escapes_path() {
+        let path = Path::new("/home/me/Videos/Recording #1 100%.mp4");
+        let uri = file_uri(path);
+        assert_eq!(uri, "file:///home/me/Videos/Recording%20%231%20100%25.mp4");
+        assert_eq!(uri_to_path(&uri), path);
+    }
+}
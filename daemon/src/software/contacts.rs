@@ -0,0 +1,80 @@
+use std::sync::{Arc, RwLock};
+
+use suite_223b::{
+    auth::CredentialManager, calendar::utils::CalDavEvent, contacts::ContactBook,
+    protocol::JobSchedule, utils::errors::WatsonError,
+};
+
+use crate::core::scheduler::Scheduler;
+
+/// Scheduler id of `schedule_contacts_sync`
+pub const CONTACTS_SYNC_JOB: &str = "contacts-sync";
+
+/// Address books change rarely, a sync every few hours is plenty
+const SYNC_INTERVAL: u64 = 6 * 3600;
+
+/// Names and avatars for event attendees, from the CardDAV address books of the stored accounts
+pub struct AddressBook {
+    book: RwLock<ContactBook>,
+}
+impl AddressBook {
+    /// Starts with the cache of the last sync
+    pub fn new() -> Self {
+        Self {
+            book: RwLock::new(ContactBook::load()),
+        }
+    }
+
+    pub fn enrich(&self, events: &mut [CalDavEvent]) {
+        let book = self.book.read().expect("Poisoned");
+        if book.is_empty() {
+            return;
+        }
+        for attendee in events.iter_mut().flat_map(|e| e.attendees.iter_mut()) {
+            book.enrich(attendee);
+        }
+    }
+
+    pub async fn sync(&self) {
+        let mut credential_manager = match CredentialManager::new() {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                return;
+            }
+        };
+        if let Err(e) = credential_manager.unlock() {
+            eprintln!("{:?}", e);
+            return;
+        }
+
+        let book = ContactBook::sync(credential_manager.credentials).await;
+        // An unreachable server shouldn't wipe the names until the next sync
+        if book.is_empty() {
+            return;
+        }
+        if let Err(e) = book.save() {
+            eprintln!("{:?}", e);
+        }
+        *self.book.write().expect("Poisoned") = book;
+    }
+}
+
+/// Syncs the address books now and every `SYNC_INTERVAL` after
+pub fn schedule_contacts_sync(
+    scheduler: &Scheduler,
+    contacts: Arc<AddressBook>,
+) -> Result<(), WatsonError> {
+    tokio::spawn({
+        let contacts = Arc::clone(&contacts);
+        async move { contacts.sync().await }
+    });
+    scheduler.register(
+        CONTACTS_SYNC_JOB,
+        JobSchedule::Every(SYNC_INTERVAL),
+        move || {
+            let contacts = Arc::clone(&contacts);
+            async move { contacts.sync().await }
+        },
+    )
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use suite_223b::{
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::UnixStream,
+    sync::RwLock,
+};
+
+use crate::notify::NotificationDaemon;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct CompositorState {
+    fullscreen: bool,
+    screencast: bool,
+}
+impl CompositorState {
+    fn wants_dnd(&self) -> bool {
+        self.fullscreen || self.screencast
+    }
+}
+
+/// Hyprland's event socket (`.socket2.sock`). Other compositors share neither fullscreen nor
+/// screencast state, and the ScreenCast portal only reports the sessions the daemon starts.
+fn event_socket() -> Result<String, WatsonError> {
+    let Ok(signature) = std::env::var("HYPRLAND_INSTANCE_SIGNATURE") else {
+        let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_else(|_| "unknown".into());
+        return Err(watson_err!(
+            WatsonErrorKind::UnsupportedCompositor,
+            "Automatic do-not-disturb needs Hyprland, the compositor ({}) is unsupported",
+            desktop
+        ));
+    };
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR")
+        .map_err(|e| watson_err!(WatsonErrorKind::EnvVar, e.to_string()))?;
+    Ok(format!("{}/hypr/{}/.socket2.sock", runtime_dir, signature))
+}
+
+/// Whether automatic do-not-disturb can follow the running compositor
+pub fn compositor_supported() -> Result<(), WatsonError> {
+    event_socket().map(|_| ())
+}
+
+/// Enables do-not-disturb while a fullscreen window or a screencast is active.
+///
+/// Listens on Hyprland's event socket. The previous silent state is restored once neither is
+/// active anymore. Fails with `UnsupportedCompositor` when not running under Hyprland.
+pub async fn compositor_dnd_listener(
+    daemon: Arc<RwLock<NotificationDaemon>>,
+) -> Result<(), WatsonError> {
+    let path = event_socket()?;
+
+    let stream = UnixStream::connect(&path)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
+    let mut lines = BufReader::new(stream).lines();
+
+    let mut state = CompositorState::default();
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?
+    {
+        let Some((event, data)) = line.split_once(">>") else {
+            continue;
+        };
+
+        let before = state.wants_dnd();
+        match event {
+            // fullscreen>>0|1
+            "fullscreen" => state.fullscreen = data == "1",
+            // screencast>>STATE,OWNER
+            "screencast" => state.screencast = data.starts_with('1'),
+            _ => continue,
+        }
+
+        if before != state.wants_dnd() {
+            daemon
+                .write()
+                .await
+                .settings
+                .set_auto_dnd(state.wants_dnd());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use suite_223b::{
+    config::profile::load_config_file,
+    finance::{FinanceConfig, QuoteProvider, RateLimiter},
+    protocol::{InternalMessage, JobSchedule, Quote},
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::get_cache_dir,
+    },
+    watson_err,
+};
+
+use crate::{DAEMON_TX, core::scheduler::Scheduler};
+
+/// Scheduler id of `schedule_quotes`
+pub const FINANCE_JOB: &str = "finance-quotes";
+
+const QUOTES_CACHE: &str = "quotes.json";
+/// Between two symbols of the same poll
+const SPACING: Duration = Duration::from_secs(2);
+const BACKOFF: Duration = Duration::from_secs(60);
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// Quotes of the symbols in `finance.json`, cached so sparklines survive restarts
+pub struct Ticker {
+    quotes: Mutex<Vec<Quote>>,
+    limiter: tokio::sync::Mutex<RateLimiter>,
+}
+impl Ticker {
+    pub fn new() -> Self {
+        let quotes = Self::path()
+            .ok()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        Self {
+            quotes: Mutex::new(quotes),
+            limiter: tokio::sync::Mutex::new(RateLimiter::new(SPACING, BACKOFF, MAX_BACKOFF)),
+        }
+    }
+
+    fn path() -> Result<PathBuf, WatsonError> {
+        Ok(get_cache_dir()?.join(QUOTES_CACHE))
+    }
+
+    pub fn quotes(&self) -> Vec<Quote> {
+        self.quotes.lock().expect("Poisoned").clone()
+    }
+
+    /// Fetches every symbol once, unless the provider asked to back off
+    async fn poll(&self, symbols: &[String], provider: &dyn QuoteProvider) {
+        // A slow poll isn't overtaken by the next one
+        let Ok(mut limiter) = self.limiter.try_lock() else {
+            return;
+        };
+        let mut fetched = Vec::new();
+        for symbol in symbols {
+            let delay = limiter.delay(Instant::now());
+            if delay > SPACING {
+                break;
+            }
+            tokio::time::sleep(delay).await;
+
+            let result = provider.quote(symbol).await;
+            let limited = matches!(&result, Err(e) if e.kind == WatsonErrorKind::RateLimited);
+            limiter.record(Instant::now(), limited);
+            match result {
+                Ok(quote) => fetched.push((symbol.clone(), quote)),
+                Err(e) => eprintln!("{:?}", e),
+            }
+        }
+        if fetched.is_empty() {
+            return;
+        }
+
+        let quotes = {
+            let mut quotes = self.quotes.lock().expect("Poisoned");
+            *quotes = merge_quotes(&quotes, fetched, symbols);
+            quotes.clone()
+        };
+        if let Err(e) = self.save(&quotes) {
+            eprintln!("{:?}", e);
+        }
+        let _result = DAEMON_TX
+            .get()
+            .map(|d| d.send(InternalMessage::Quotes(quotes)));
+    }
+
+    fn save(&self, quotes: &[Quote]) -> Result<(), WatsonError> {
+        let bytes = serde_json::to_vec(quotes)
+            .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+        fs::write(Self::path()?, bytes)
+            .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))
+    }
+}
+
+/// Quotes in the configured order, older ones kept for symbols that failed this time
+fn merge_quotes(old: &[Quote], fetched: Vec<(String, Quote)>, symbols: &[String]) -> Vec<Quote> {
+    symbols
+        .iter()
+        .filter_map(|symbol| {
+            fetched
+                .iter()
+                .find(|(s, _)| s == symbol)
+                .map(|(_, q)| q.clone())
+                .or_else(|| {
+                    old.iter()
+                        .find(|q| q.symbol.eq_ignore_ascii_case(symbol))
+                        .cloned()
+                })
+        })
+        .collect()
+}
+
+/// Polls the symbols in `finance.json` now and every `interval` seconds after. Nothing is
+/// scheduled without symbols.
+pub fn schedule_quotes(scheduler: &Scheduler, ticker: Arc<Ticker>) -> Result<(), WatsonError> {
+    let config: FinanceConfig = load_config_file("finance")?;
+    if config.symbols.is_empty() {
+        return Ok(());
+    }
+    let provider: Arc<dyn QuoteProvider> = Arc::from(config.provider());
+    let symbols = Arc::new(config.symbols.clone());
+
+    let poll = move || {
+        let ticker = Arc::clone(&ticker);
+        let provider = Arc::clone(&provider);
+        let symbols = Arc::clone(&symbols);
+        async move { ticker.poll(&symbols, provider.as_ref()).await }
+    };
+    tokio::spawn(poll());
+    scheduler.register(FINANCE_JOB, JobSchedule::Every(config.interval()), poll)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(symbol: &str, price: f64) -> Quote {
+        Quote {
+            symbol: symbol.into(),
+            price,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_failed_symbols_keep_their_quote() {
+        let symbols = vec![
+            "AAPL".to_string(),
+            "btc-usd".to_string(),
+            "MSFT".to_string(),
+        ];
+        let old = vec![quote("BTC-USD", 1.0), quote("TSLA", 2.0)];
+        let fetched = vec![
+            ("MSFT".to_string(), quote("MSFT", 3.0)),
+            ("AAPL".to_string(), quote("AAPL", 4.0)),
+        ];
+        let merged = merge_quotes(&old, fetched, &symbols);
+        let prices: Vec<(&str, f64)> = merged
+            .iter()
+            .map(|q| (q.symbol.as_str(), q.price))
+            .collect();
+        // Configured order, symbols no longer configured are dropped
+        assert_eq!(prices, [("AAPL", 4.0), ("BTC-USD", 1.0), ("MSFT", 3.0)]);
+    }
+}
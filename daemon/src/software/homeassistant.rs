@@ -0,0 +1,186 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use suite_223b::{
+    auth::{CredentialManager, CredentialService},
+    config::profile::load_config_file,
+    integrations::homeassistant::{HomeAssistantClient, HomeAssistantConfig},
+    protocol::{HomeAssistantEntity, InternalMessage},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+use crate::DAEMON_TX;
+
+/// Wait after losing the connection, doubled up to `MAX_BACKOFF`
+const BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// Entities of the Home Assistant instance, kept up to date by `watch_home_assistant`
+#[derive(Default)]
+pub struct HomeAssistantHub {
+    entities: Mutex<Vec<HomeAssistantEntity>>,
+    /// Set once the credential was read
+    client: Mutex<Option<Arc<HomeAssistantClient>>>,
+}
+impl HomeAssistantHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entities(&self) -> Vec<HomeAssistantEntity> {
+        self.entities.lock().expect("Poisoned").clone()
+    }
+
+    fn replace(&self, entities: Vec<HomeAssistantEntity>) {
+        *self.entities.lock().expect("Poisoned") = entities;
+    }
+
+    /// Stores `entity`, returns whether anything widgets show changed. Most events only touch
+    /// attributes such as a light's brightness.
+    fn update(&self, entity: HomeAssistantEntity) -> bool {
+        let mut entities = self.entities.lock().expect("Poisoned");
+        match entities.iter_mut().find(|e| e.id == entity.id) {
+            Some(slot) if *slot == entity => false,
+            Some(slot) => {
+                *slot = entity;
+                true
+            }
+            None => {
+                entities.push(entity);
+                true
+            }
+        }
+    }
+
+    /// Turns `entity` on or off, clients learn about the new state from the broadcast
+    pub async fn set(&self, entity: &str, on: bool) -> Result<(), WatsonError> {
+        let client = self.client.lock().expect("Poisoned").clone();
+        let result = match client {
+            Some(client) => client.set(entity, on).await,
+            None => Err(watson_err!(
+                WatsonErrorKind::HomeAssistant,
+                "Not connected to Home Assistant"
+            )),
+        };
+        // Clients flipped their switch before Home Assistant answered
+        if result.is_err()
+            && let Some(current) = self.entities().into_iter().find(|e| e.id == entity)
+        {
+            let _result = DAEMON_TX
+                .get()
+                .map(|d| d.send(InternalMessage::HomeAssistantEntity(current)));
+        }
+        result
+    }
+
+    pub fn clear(&self) {
+        self.entities.lock().expect("Poisoned").clear();
+        *self.client.lock().expect("Poisoned") = None;
+    }
+}
+
+/// Follows the instance from `homeassistant.json` over its WebSocket API, reconnecting with
+/// growing delays. Does nothing without a Home Assistant credential.
+pub async fn watch_home_assistant(hub: Arc<HomeAssistantHub>) {
+    let config: HomeAssistantConfig = match load_config_file("homeassistant") {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return;
+        }
+    };
+    let mut credential_manager = match CredentialManager::new() {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return;
+        }
+    };
+    // Most users have no instance, they shouldn't be asked to unlock for nothing
+    if config.credential.is_none()
+        && !credential_manager
+            .credentials
+            .iter()
+            .any(|c| matches!(c.service, CredentialService::HomeAssistant))
+    {
+        return;
+    }
+    if let Err(e) = credential_manager.unlock() {
+        eprintln!("{:?}", e);
+        return;
+    }
+    let client = match config
+        .credential(credential_manager.credentials)
+        .and_then(|c| HomeAssistantClient::new(&c))
+    {
+        Ok(client) => Arc::new(client),
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return;
+        }
+    };
+    *hub.client.lock().expect("Poisoned") = Some(Arc::clone(&client));
+
+    let mut backoff = BACKOFF;
+    loop {
+        if let Err(e) = follow(&client, &hub, &mut backoff).await {
+            eprintln!("{:?}", e);
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Runs until the connection is lost
+async fn follow(
+    client: &HomeAssistantClient,
+    hub: &HomeAssistantHub,
+    backoff: &mut Duration,
+) -> Result<(), WatsonError> {
+    // Subscribed before fetching, so no change falls in between
+    let mut changes = client.subscribe().await?;
+    let entities = client.states().await?;
+    *backoff = BACKOFF;
+    hub.replace(entities.clone());
+    let _result = DAEMON_TX
+        .get()
+        .map(|d| d.send(InternalMessage::HomeAssistant(entities)));
+    loop {
+        let entity = changes.next().await?;
+        if hub.update(entity.clone()) {
+            let _result = DAEMON_TX
+                .get()
+                .map(|d| d.send(InternalMessage::HomeAssistantEntity(entity)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(id: &str, state: &str) -> HomeAssistantEntity {
+        HomeAssistantEntity {
+            id: id.into(),
+            name: "Kitchen".into(),
+            state: state.into(),
+            unit: None,
+        }
+    }
+
+    #[test]
+    fn test_only_visible_changes_are_sent() {
+        let hub = HomeAssistantHub::new();
+        hub.replace(vec![entity("light.kitchen", "off")]);
+
+        // Brightness changes arrive with the same state
+        assert!(!hub.update(entity("light.kitchen", "off")));
+        assert!(hub.update(entity("light.kitchen", "on")));
+        assert!(hub.update(entity("switch.fan", "on")));
+        assert_eq!(hub.entities().len(), 2);
+        assert!(hub.entities()[0].is_on());
+    }
+}
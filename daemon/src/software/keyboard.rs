@@ -0,0 +1,282 @@
+use std::{sync::Mutex, time::Duration};
+
+use suite_223b::{
+    protocol::InternalMessage,
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::UnixStream,
+    process::Command,
+};
+
+use crate::DAEMON_TX;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyboardLayout {
+    /// Full xkb description, e.g. `English (US)`
+    pub name: String,
+    /// Two letter label, e.g. `US`
+    pub short: String,
+}
+impl KeyboardLayout {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            short: short_name(name),
+        }
+    }
+}
+
+/// Country code for an xkb layout description or layout code
+fn short_name(name: &str) -> String {
+    const LANGUAGES: &[(&str, &str)] = &[
+        ("Chinese", "CN"),
+        ("Czech", "CZ"),
+        ("Danish", "DK"),
+        ("Dutch", "NL"),
+        ("Finnish", "FI"),
+        ("French", "FR"),
+        ("German", "DE"),
+        ("Greek", "GR"),
+        ("Hungarian", "HU"),
+        ("Italian", "IT"),
+        ("Japanese", "JP"),
+        ("Korean", "KR"),
+        ("Norwegian", "NO"),
+        ("Polish", "PL"),
+        ("Portuguese", "PT"),
+        ("Russian", "RU"),
+        ("Spanish", "ES"),
+        ("Swedish", "SE"),
+        ("Turkish", "TR"),
+        ("Ukrainian", "UA"),
+    ];
+
+    // `English (US)`, `French (Switzerland)` -> prefer a two letter qualifier
+    if let Some((_, qualifier)) = name.split_once('(') {
+        let qualifier = qualifier.trim_end_matches(')').trim();
+        if qualifier.len() == 2 && qualifier.chars().all(|c| c.is_ascii_uppercase()) {
+            return qualifier.to_string();
+        }
+    }
+    if let Some((_, code)) = LANGUAGES.iter().find(|(lang, _)| name.starts_with(lang)) {
+        return code.to_string();
+    }
+    // Raw layout codes from setxkbmap, e.g. `us`
+    name.chars()
+        .filter(|c| c.is_alphabetic())
+        .take(2)
+        .collect::<String>()
+        .to_uppercase()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum LayoutBackend {
+    Hyprland,
+    Sway,
+    /// X11 or unknown compositors. Cannot tell the active group, so switching rotates the
+    /// configured layouts and the first one is the active one.
+    Setxkbmap,
+}
+impl LayoutBackend {
+    fn detect() -> Self {
+        if std::env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
+            Self::Hyprland
+        } else if std::env::var("SWAYSOCK").is_ok() {
+            Self::Sway
+        } else {
+            Self::Setxkbmap
+        }
+    }
+
+    async fn current(&self) -> Result<KeyboardLayout, WatsonError> {
+        let name = match self {
+            Self::Hyprland => {
+                let devices: serde_json::Value =
+                    serde_json::from_slice(&run("hyprctl", &["devices", "-j"]).await?)
+                        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+                let keyboards = devices["keyboards"].as_array().cloned().unwrap_or_default();
+                keyboards
+                    .iter()
+                    .find(|k| k["main"].as_bool() == Some(true))
+                    .or_else(|| keyboards.first())
+                    .and_then(|k| k["active_keymap"].as_str())
+                    .map(str::to_string)
+            }
+            Self::Sway => {
+                let inputs: serde_json::Value =
+                    serde_json::from_slice(&run("swaymsg", &["-t", "get_inputs", "-r"]).await?)
+                        .map_err(|e| watson_err!(WatsonErrorKind::Deserialize, e.to_string()))?;
+                inputs
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|i| i["type"] == "keyboard")
+                    .find_map(|i| i["xkb_active_layout_name"].as_str())
+                    .map(str::to_string)
+            }
+            Self::Setxkbmap => Self::setxkbmap_layouts().await?.into_iter().next(),
+        };
+
+        name.map(|n| KeyboardLayout::new(&n)).ok_or_else(|| {
+            watson_err!(
+                WatsonErrorKind::UndefinedAttribute,
+                "No active keyboard layout"
+            )
+        })
+    }
+
+    async fn next(&self) -> Result<(), WatsonError> {
+        match self {
+            Self::Hyprland => run("hyprctl", &["switchxkblayout", "all", "next"])
+                .await
+                .map(|_| ()),
+            Self::Sway => run(
+                "swaymsg",
+                &["input", "type:keyboard", "xkb_switch_layout", "next"],
+            )
+            .await
+            .map(|_| ()),
+            Self::Setxkbmap => {
+                let mut layouts = Self::setxkbmap_layouts().await?;
+                if layouts.len() > 1 {
+                    layouts.rotate_left(1);
+                    run("setxkbmap", &["-layout", &layouts.join(",")]).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn setxkbmap_layouts() -> Result<Vec<String>, WatsonError> {
+        let output = String::from_utf8_lossy(&run("setxkbmap", &["-query"]).await?).into_owned();
+        Ok(output
+            .lines()
+            .find_map(|l| l.strip_prefix("layout:"))
+            .map(|l| l.trim().split(',').map(str::to_string).collect())
+            .unwrap_or_default())
+    }
+}
+
+async fn run(program: &str, args: &[&str]) -> Result<Vec<u8>, WatsonError> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, e.to_string()))?;
+    if !output.status.success() {
+        return Err(watson_err!(
+            WatsonErrorKind::CommandExecute,
+            "{} exited with {}",
+            program,
+            output.status
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Tracks the active keyboard layout and tells clients when it changes
+pub struct KeyboardLayouts {
+    backend: LayoutBackend,
+    current: Mutex<Option<KeyboardLayout>>,
+}
+impl KeyboardLayouts {
+    pub fn new() -> Self {
+        Self {
+            backend: LayoutBackend::detect(),
+            current: Mutex::new(None),
+        }
+    }
+
+    pub async fn current(&self) -> Result<KeyboardLayout, WatsonError> {
+        if let Some(layout) = self.current.lock().ok().and_then(|c| c.clone()) {
+            return Ok(layout);
+        }
+        self.refresh().await
+    }
+
+    pub async fn next(&self) -> Result<(), WatsonError> {
+        self.backend.next().await?;
+        self.refresh().await.map(|_| ())
+    }
+
+    /// Queries the compositor and broadcasts the layout if it changed
+    async fn refresh(&self) -> Result<KeyboardLayout, WatsonError> {
+        let layout = self.backend.current().await?;
+        let changed = match self.current.lock() {
+            Ok(mut current) if current.as_ref() != Some(&layout) => {
+                current.replace(layout.clone());
+                true
+            }
+            _ => false,
+        };
+        if changed {
+            let _result = DAEMON_TX.get().map(|d| {
+                d.send(InternalMessage::KeyboardLayout {
+                    name: layout.name.clone(),
+                    short: layout.short.clone(),
+                })
+            });
+        }
+        Ok(layout)
+    }
+}
+
+/// Follows layout changes through Hyprland's `activelayout` event, other backends are polled.
+pub async fn keyboard_layout_listener(
+    keyboard: std::sync::Arc<KeyboardLayouts>,
+) -> Result<(), WatsonError> {
+    const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+    let LayoutBackend::Hyprland = keyboard.backend else {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = keyboard.refresh().await {
+                eprintln!("{:?}", e);
+                return Ok(());
+            }
+        }
+    };
+
+    let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE")
+        .map_err(|e| watson_err!(WatsonErrorKind::EnvVar, e.to_string()))?;
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR")
+        .map_err(|e| watson_err!(WatsonErrorKind::EnvVar, e.to_string()))?;
+    let path = format!("{}/hypr/{}/.socket2.sock", runtime_dir, signature);
+
+    let stream = UnixStream::connect(&path)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
+    let mut lines = BufReader::new(stream).lines();
+
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?
+    {
+        // activelayout>>KEYBOARD,LAYOUT
+        if line.starts_with("activelayout>>")
+            && let Err(e) = keyboard.refresh().await
+        {
+            eprintln!("{:?}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_name() {
+        assert_eq!(short_name("German"), "DE");
+        assert_eq!(short_name("English (US)"), "US");
+        assert_eq!(short_name("English (UK)"), "UK");
+        assert_eq!(short_name("German (no dead keys)"), "DE");
+        assert_eq!(short_name("us"), "US");
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use suite_223b::{
+    auth::CredentialManager,
+    config::profile::load_config_file,
+    mail::{MailConfig, MailProvider},
+    notification::{HintValue, Notification},
+    protocol::{InternalMessage, MailAccount, MailSummary},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::{
+    sync::{Notify, RwLock},
+    task::JoinSet,
+};
+
+use crate::{DAEMON_TX, notify::NotificationDaemon};
+
+/// Wait after a failed check, doubled up to `MAX_BACKOFF`
+const BACKOFF: Duration = Duration::from_secs(30);
+const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+/// Longest `MailInbox::refresh` waits for an account to answer
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Unread counts of the accounts in `mail.json`, kept up to date by `watch_mail`
+#[derive(Default)]
+pub struct MailInbox {
+    accounts: Mutex<Vec<MailAccount>>,
+    /// Interrupts the accounts waiting for changes so they check right away
+    wake: Notify,
+    /// Notified after every check
+    checked: Notify,
+}
+impl MailInbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn accounts(&self) -> Vec<MailAccount> {
+        self.accounts.lock().expect("Poisoned").clone()
+    }
+
+    /// Stores `account` and returns what arrived since its last check
+    fn update(&self, account: MailAccount) -> Vec<MailSummary> {
+        let mut accounts = self.accounts.lock().expect("Poisoned");
+        let slot = accounts.iter_mut().find(|a| a.label == account.label);
+        let arrived = new_messages(slot.as_deref(), &account);
+        match slot {
+            Some(slot) => *slot = account,
+            None => accounts.push(account),
+        }
+        self.checked.notify_waiters();
+        arrived
+    }
+
+    /// Checks every account now, returning once the first one answered
+    pub async fn refresh(&self) {
+        if self.accounts.lock().expect("Poisoned").is_empty() {
+            return;
+        }
+        let checked = self.checked.notified();
+        self.wake.notify_waiters();
+        let _ = tokio::time::timeout(REFRESH_TIMEOUT, checked).await;
+    }
+
+    pub fn clear(&self) {
+        self.accounts.lock().expect("Poisoned").clear();
+    }
+}
+
+/// Messages of `new` that weren't unread in `old`. Nothing is new on the first check, so starting
+/// the daemon doesn't announce the whole inbox.
+fn new_messages(old: Option<&MailAccount>, new: &MailAccount) -> Vec<MailSummary> {
+    let Some(old) = old else {
+        return Vec::new();
+    };
+    new.latest
+        .iter()
+        .filter(|m| old.latest.iter().all(|o| o.id != m.id))
+        .cloned()
+        .collect()
+}
+
+/// Checks every configured account and waits for changes (IMAP IDLE, Gmail polling). Aborting
+/// the returned task stops all accounts.
+pub async fn watch_mail(inbox: Arc<MailInbox>, daemon: Weak<RwLock<NotificationDaemon>>) {
+    let config: MailConfig = match load_config_file("mail") {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return;
+        }
+    };
+    if config.accounts.is_empty() {
+        return;
+    }
+
+    let mut credential_manager = match CredentialManager::new() {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return;
+        }
+    };
+    if let Err(e) = credential_manager.unlock() {
+        eprintln!("{:?}", e);
+        return;
+    }
+    let mut credentials: HashMap<_, _> = credential_manager
+        .credentials
+        .into_iter()
+        .map(|c| (c.label.clone(), c))
+        .collect();
+
+    let mut accounts = JoinSet::new();
+    for account in &config.accounts {
+        let provider = credentials
+            .remove(&account.credential)
+            .ok_or_else(|| {
+                watson_err!(
+                    WatsonErrorKind::CredentialEntry,
+                    "No credential labeled {}",
+                    account.credential
+                )
+            })
+            .and_then(|credential| account.provider(credential));
+        match provider {
+            Ok(provider) => {
+                accounts.spawn(watch_account(
+                    provider,
+                    config.latest,
+                    Arc::clone(&inbox),
+                    daemon.clone(),
+                ));
+            }
+            Err(e) => eprintln!("{:?}", e),
+        }
+    }
+    // Dropping the set aborts the accounts along with this task
+    while accounts.join_next().await.is_some() {}
+}
+
+async fn watch_account(
+    mut provider: Box<dyn MailProvider>,
+    latest: usize,
+    inbox: Arc<MailInbox>,
+    daemon: Weak<RwLock<NotificationDaemon>>,
+) {
+    let mut backoff = BACKOFF;
+    loop {
+        match provider.check(latest).await {
+            Ok(account) => {
+                backoff = BACKOFF;
+                let label = account.label.clone();
+                let arrived = inbox.update(account);
+                let _result = DAEMON_TX
+                    .get()
+                    .map(|d| d.send(InternalMessage::Mail(inbox.accounts())));
+                if !arrived.is_empty() {
+                    notify_new_mail(&daemon, &label, &arrived).await;
+                }
+            }
+            Err(e) => {
+                eprintln!("{:?}", e);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        }
+        tokio::select! {
+            result = provider.wait() => {
+                if let Err(e) = result {
+                    eprintln!("{:?}", e);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+            _ = inbox.wake.notified() => provider.interrupted(),
+        }
+    }
+}
+
+/// Shown under the account's label like any other application's notification
+async fn notify_new_mail(
+    daemon: &Weak<RwLock<NotificationDaemon>>,
+    label: &str,
+    arrived: &[MailSummary],
+) {
+    let Some(strong) = daemon.upgrade() else {
+        return;
+    };
+    let (summary, body) = match arrived {
+        [mail] => (mail.from.clone(), mail.subject.clone()),
+        _ => (
+            format!("{} new messages", arrived.len()),
+            arrived
+                .iter()
+                .map(|m| format!("{}: {}", m.from, m.subject))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+    };
+    let notification = Notification {
+        app_name: label.into(),
+        app_icon: "mail-unread-symbolic".into(),
+        summary,
+        body,
+        hints: HashMap::from([("category".into(), HintValue::String("email.arrived".into()))]),
+        expire_timeout: -1,
+        ..Default::default()
+    };
+    strong.write().await.insert(notification, daemon.clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mail(id: &str) -> MailSummary {
+        MailSummary {
+            id: id.into(),
+            from: "Jane Doe".into(),
+            subject: "Lunch?".into(),
+        }
+    }
+
+    #[test]
+    fn test_only_unseen_ids_are_new() {
+        let inbox = MailInbox::new();
+        let account = |latest: Vec<MailSummary>| MailAccount {
+            label: "Work".into(),
+            unread: latest.len() as u32,
+            latest,
+        };
+
+        // The first check only fills the inbox
+        assert!(inbox.update(account(vec![mail("1"), mail("2")])).is_empty());
+        let arrived = inbox.update(account(vec![mail("3"), mail("1")]));
+        assert_eq!(arrived.len(), 1);
+        assert_eq!(arrived[0].id, "3");
+        assert_eq!(inbox.accounts()[0].unread, 2);
+
+        // Reading mail doesn't announce anything
+        assert!(inbox.update(account(vec![mail("3")])).is_empty());
+    }
+}
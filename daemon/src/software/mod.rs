@@ -1,15 +1,126 @@
-use crate::software::calendar::CalendarBackend;
+use std::sync::Arc;
+
+use suite_223b::{
+    protocol::{DataKind, InternalMessage, JobSchedule},
+    utils::errors::WatsonError,
+};
+
+use crate::{
+    DAEMON_TX,
+    core::scheduler::Scheduler,
+    software::{
+        calendar::CalendarBackend,
+        capture::ScreenCapture,
+        contacts::AddressBook,
+        finance::{FINANCE_JOB, Ticker},
+        homeassistant::HomeAssistantHub,
+        keyboard::KeyboardLayouts,
+        mail::MailInbox,
+        screen_time::ScreenTime,
+    },
+    utils::command::CommandExecutor,
+};
 
 mod calendar;
+pub mod capture;
+pub mod contacts;
+pub mod dnd;
+pub mod finance;
+pub mod homeassistant;
+pub mod keyboard;
+pub mod mail;
+pub mod screen_time;
+pub mod shortcuts;
+pub mod travel;
 
 pub struct SoftwareController {
-    pub events: CalendarBackend,
+    pub events: Arc<CalendarBackend>,
+    pub contacts: Arc<AddressBook>,
+    pub capture: Arc<ScreenCapture>,
+    pub keyboard: Arc<KeyboardLayouts>,
+    pub mail: Arc<MailInbox>,
+    pub ticker: Arc<Ticker>,
+    pub home_assistant: Arc<HomeAssistantHub>,
+    pub commands: Arc<CommandExecutor>,
+    pub screen_time: Arc<ScreenTime>,
 }
 
 impl SoftwareController {
     pub async fn new() -> Self {
-        let mut events = CalendarBackend::new();
-        let _ = events.fetch_for_today().await;
-        Self { events }
+        let software = Self::without_calendars();
+        let _ = software.events.fetch_for_today().await;
+        software
+    }
+
+    /// Calendars stay empty until the next refresh, which may ask to unlock the credentials
+    pub fn without_calendars() -> Self {
+        Self {
+            events: Arc::new(CalendarBackend::new()),
+            contacts: Arc::new(AddressBook::new()),
+            capture: Arc::new(ScreenCapture::new()),
+            keyboard: Arc::new(KeyboardLayouts::new()),
+            mail: Arc::new(MailInbox::new()),
+            ticker: Arc::new(Ticker::new()),
+            home_assistant: Arc::new(HomeAssistantHub::new()),
+            commands: Arc::new(CommandExecutor::new()),
+            screen_time: Arc::new(ScreenTime::new()),
+        }
     }
 }
+
+/// Scheduler id of `schedule_calendar_refresh`
+pub const CALENDAR_REFRESH_JOB: &str = "calendar-refresh";
+
+/// Polls the calendar providers for changes (Google syncToken / CalDAV ctag) and notifies all
+/// clients about each changed calendar.
+pub fn schedule_calendar_refresh(
+    scheduler: &Scheduler,
+    events: Arc<CalendarBackend>,
+) -> Result<(), WatsonError> {
+    // The initial fetch already happened, the first run is a minute in
+    scheduler.register(CALENDAR_REFRESH_JOB, JobSchedule::Every(60), move || {
+        let events = Arc::clone(&events);
+        async move {
+            for calendar in events.refresh_changed().await {
+                let _result = DAEMON_TX
+                    .get()
+                    .map(|d| d.send(InternalMessage::CalendarChanged { calendar }));
+            }
+        }
+    })
+}
+
+/// Fetches `kind` now instead of on the next poll. The clients are told when it starts and when
+/// it's done, disabled services are skipped.
+pub async fn force_refresh(kind: DataKind, scheduler: Arc<Scheduler>, mail: Arc<MailInbox>) {
+    let refreshing = |active| {
+        let _result = DAEMON_TX
+            .get()
+            .map(|d| d.send(InternalMessage::Refreshing { kind, active }));
+    };
+    refreshing(true);
+
+    let run = |id: &'static str, included: bool| {
+        let scheduler = Arc::clone(&scheduler);
+        async move {
+            if included
+                && scheduler.jobs().iter().any(|j| j.id == id)
+                && let Err(e) = scheduler.run_now(id).await
+            {
+                eprintln!("{:?}", e);
+            }
+        }
+    };
+    let mail = async {
+        if kind.includes(DataKind::Mail) {
+            mail.refresh().await;
+        }
+    };
+    tokio::join!(
+        run(CALENDAR_REFRESH_JOB, kind.includes(DataKind::Calendar)),
+        run(FINANCE_JOB, kind.includes(DataKind::Quotes)),
+        mail,
+    );
+
+    refreshing(false);
+}
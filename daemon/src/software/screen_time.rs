@@ -0,0 +1,392 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    process::Command,
+    sync::{Arc, Mutex, Weak},
+};
+
+use chrono::{DateTime, Local, NaiveDate, TimeDelta};
+use serde::{Deserialize, Serialize};
+use suite_223b::{
+    config::profile::load_config_file,
+    notification::Notification,
+    protocol::{AppUsage, InternalMessage, JobSchedule, duration_label},
+    utils::{
+        errors::{WatsonError, WatsonErrorKind},
+        paths::get_state_dir,
+    },
+    watson_err,
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::UnixStream,
+    sync::RwLock,
+};
+
+use crate::{DAEMON_TX, core::scheduler::Scheduler, notify::NotificationDaemon};
+
+/// Scheduler id of the job `start_screen_time` registers
+pub const SCREEN_TIME_JOB: &str = "screen-time";
+
+/// `$XDG_CONFIG_HOME/watson/screen_time.json`, daily limits in minutes by window class, e.g.
+/// `{ "limits": { "firefox": 120 } }`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ScreenTimeConfig {
+    limits: HashMap<String, u64>,
+}
+impl ScreenTimeConfig {
+    /// In seconds
+    fn limit(&self, app: &str) -> Option<u64> {
+        self.limits.get(app).map(|minutes| minutes * 60)
+    }
+}
+
+/// Focus time of one day, kept in `$XDG_STATE_HOME/watson/screen-time/<day>.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct DailyUsage {
+    day: NaiveDate,
+    /// Seconds by window class
+    apps: HashMap<String, u64>,
+    /// Apps the user was already told reached their limit
+    #[serde(default)]
+    notified: Vec<String>,
+}
+impl DailyUsage {
+    fn new(day: NaiveDate) -> Self {
+        Self {
+            day,
+            apps: HashMap::new(),
+            notified: Vec::new(),
+        }
+    }
+
+    fn path(day: NaiveDate) -> Result<PathBuf, WatsonError> {
+        let dir = get_state_dir()?.join("screen-time");
+        fs::create_dir_all(&dir)
+            .map_err(|e| watson_err!(WatsonErrorKind::DirCreate, e.to_string()))?;
+        Ok(dir.join(format!("{day}.json")))
+    }
+
+    /// What was counted earlier that day, e.g. before a restart
+    fn load(day: NaiveDate) -> Self {
+        Self::path(day)
+            .and_then(|path| {
+                fs::read(path).map_err(|e| watson_err!(WatsonErrorKind::FileRead, e.to_string()))
+            })
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_else(|| Self::new(day))
+    }
+
+    fn save(&self) -> Result<(), WatsonError> {
+        let data = serde_json::to_vec(self)
+            .map_err(|e| watson_err!(WatsonErrorKind::Serialize, e.to_string()))?;
+        fs::write(Self::path(self.day)?, data)
+            .map_err(|e| watson_err!(WatsonErrorKind::FileWrite, e.to_string()))
+    }
+}
+
+#[derive(Debug)]
+struct Tracker {
+    usage: DailyUsage,
+    /// Window class of the focused window
+    focused: Option<String>,
+    /// While the session is idle or asleep
+    paused: bool,
+    /// Time up to here is in `usage`
+    counted: DateTime<Local>,
+}
+impl Tracker {
+    fn new(usage: DailyUsage, now: DateTime<Local>) -> Self {
+        Self {
+            usage,
+            focused: None,
+            paused: false,
+            counted: now,
+        }
+    }
+
+    /// Adds the time since the last call to the focused app. Returns the previous day once it
+    /// ended, the time before midnight still counts towards it.
+    fn count(&mut self, now: DateTime<Local>) -> Option<DailyUsage> {
+        let today = now.date_naive();
+        if today == self.usage.day {
+            self.count_until(now);
+            return None;
+        }
+        let midnight = today
+            .and_hms_opt(0, 0, 0)
+            .and_then(|t| t.and_local_timezone(Local).earliest())
+            .unwrap_or(now);
+        self.count_until(midnight);
+        let ended = std::mem::replace(&mut self.usage, DailyUsage::new(today));
+        self.count_until(now);
+        Some(ended)
+    }
+
+    fn count_until(&mut self, until: DateTime<Local>) {
+        // Whole seconds only, the rest is counted with the next call
+        let seconds = (until - self.counted).num_seconds().max(0);
+        self.counted += TimeDelta::seconds(seconds);
+        if let Some(app) = self.focused.as_ref().filter(|_| !self.paused) {
+            *self.usage.apps.entry(app.clone()).or_default() += seconds as u64;
+        }
+    }
+
+    /// Apps that reached their limit since the last call
+    fn newly_over(&mut self, config: &ScreenTimeConfig) -> Vec<(String, u64)> {
+        let mut reached: Vec<(String, u64)> = self
+            .usage
+            .apps
+            .iter()
+            .filter(|(app, _)| !self.usage.notified.contains(app))
+            .filter_map(|(app, seconds)| {
+                let limit = config.limit(app)?;
+                (*seconds >= limit).then(|| (app.clone(), limit))
+            })
+            .collect();
+        reached.sort();
+        self.usage
+            .notified
+            .extend(reached.iter().map(|(app, _)| app.clone()));
+        reached
+    }
+
+    fn usage(&self, config: &ScreenTimeConfig) -> Vec<AppUsage> {
+        let mut usage: Vec<AppUsage> = self
+            .usage
+            .apps
+            .iter()
+            .map(|(app, seconds)| AppUsage {
+                app: app.clone(),
+                seconds: *seconds,
+                limit: config.limit(app),
+            })
+            .collect();
+        usage.sort_by(|a, b| b.seconds.cmp(&a.seconds).then_with(|| a.app.cmp(&b.app)));
+        usage
+    }
+}
+
+/// Focus time per application today, fed by `focus_listener` while the `ScreenTime` service
+/// runs
+pub struct ScreenTime {
+    config: Mutex<ScreenTimeConfig>,
+    tracker: Mutex<Tracker>,
+}
+impl ScreenTime {
+    pub fn new() -> Self {
+        let now = Local::now();
+        Self {
+            config: Mutex::new(ScreenTimeConfig::default()),
+            tracker: Mutex::new(Tracker::new(DailyUsage::new(now.date_naive()), now)),
+        }
+    }
+
+    /// Reads the limits and what was counted today before the service started
+    fn load(&self) {
+        let config = load_config_file("screen_time").unwrap_or_else(|e| {
+            eprintln!("{:?}", e);
+            ScreenTimeConfig::default()
+        });
+        *self.config.lock().expect("Poisoned") = config;
+        let now = Local::now();
+        *self.tracker.lock().expect("Poisoned") =
+            Tracker::new(DailyUsage::load(now.date_naive()), now);
+    }
+
+    pub fn usage(&self) -> Vec<AppUsage> {
+        let config = self.config.lock().expect("Poisoned");
+        self.tracker.lock().expect("Poisoned").usage(&config)
+    }
+
+    /// The focused window changed, `None` without one
+    fn focus(&self, app: Option<String>) {
+        let mut tracker = self.tracker.lock().expect("Poisoned");
+        save_ended(tracker.count(Local::now()));
+        tracker.focused = app;
+    }
+
+    /// Stops counting while the session is idle or asleep
+    pub fn set_paused(&self, paused: bool) {
+        let mut tracker = self.tracker.lock().expect("Poisoned");
+        save_ended(tracker.count(Local::now()));
+        tracker.paused = paused;
+    }
+
+    /// Counts up and saves, returning the apps that just reached their limit
+    fn tick(&self) -> Vec<(String, u64)> {
+        let config = self.config.lock().expect("Poisoned");
+        let mut tracker = self.tracker.lock().expect("Poisoned");
+        save_ended(tracker.count(Local::now()));
+        let reached = tracker.newly_over(&config);
+        if let Err(e) = tracker.usage.save() {
+            eprintln!("{:?}", e);
+        }
+        reached
+    }
+
+    /// Saves what was counted, nothing is counted until the service starts again
+    pub fn stop(&self) {
+        self.focus(None);
+        if let Err(e) = self.tracker.lock().expect("Poisoned").usage.save() {
+            eprintln!("{:?}", e);
+        }
+    }
+}
+
+fn save_ended(ended: Option<DailyUsage>) {
+    if let Some(Err(e)) = ended.map(|usage| usage.save()) {
+        eprintln!("{:?}", e);
+    }
+}
+
+/// Starts counting: follows the focused window and adds up, saves and announces the usage
+/// every minute
+pub fn start_screen_time(
+    scheduler: &Scheduler,
+    screen_time: Arc<ScreenTime>,
+    daemon: Weak<RwLock<NotificationDaemon>>,
+) -> Result<tokio::task::AbortHandle, WatsonError> {
+    screen_time.load();
+    scheduler.register(SCREEN_TIME_JOB, JobSchedule::Every(60), {
+        let screen_time = Arc::clone(&screen_time);
+        move || {
+            let screen_time = Arc::clone(&screen_time);
+            let daemon = daemon.clone();
+            async move {
+                let reached = screen_time.tick();
+                let usage = screen_time.usage();
+                let _result = DAEMON_TX
+                    .get()
+                    .map(|d| d.send(InternalMessage::ScreenTime(usage)));
+                if reached.is_empty() {
+                    return;
+                }
+                let Some(strong) = daemon.upgrade() else {
+                    return;
+                };
+                let mut strong = strong.write().await;
+                for (app, limit) in reached {
+                    let notification = Notification {
+                        app_name: "Watson".into(),
+                        app_icon: "alarm-symbolic".into(),
+                        summary: format!("{app} limit reached"),
+                        body: format!("{} used today", duration_label(limit)),
+                        expire_timeout: -1,
+                        ..Default::default()
+                    };
+                    strong.insert(notification, daemon.clone());
+                }
+            }
+        }
+    })?;
+
+    let task = tokio::spawn(async move {
+        if let Err(e) = focus_listener(screen_time).await {
+            eprintln!("{:?}", e);
+        }
+    });
+    Ok(task.abort_handle())
+}
+
+/// Follows the focused window through Hyprland's `activewindow` event. Returns immediately
+/// when not running under Hyprland.
+async fn focus_listener(screen_time: Arc<ScreenTime>) -> Result<(), WatsonError> {
+    let Ok(signature) = std::env::var("HYPRLAND_INSTANCE_SIGNATURE") else {
+        return Ok(());
+    };
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR")
+        .map_err(|e| watson_err!(WatsonErrorKind::EnvVar, e.to_string()))?;
+    let path = format!("{}/hypr/{}/.socket2.sock", runtime_dir, signature);
+
+    let stream = UnixStream::connect(&path)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
+    let mut lines = BufReader::new(stream).lines();
+
+    // Events only arrive on the next switch
+    screen_time.focus(active_window());
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamRead, e.to_string()))?
+    {
+        if let Some(data) = line.strip_prefix("activewindow>>") {
+            screen_time.focus(window_class(data));
+        }
+    }
+    Ok(())
+}
+
+/// `activewindow>>CLASS,TITLE`, both empty without a focused window
+fn window_class(data: &str) -> Option<String> {
+    let class = data.split_once(',').map_or(data, |(class, _)| class);
+    (!class.is_empty()).then(|| class.to_string())
+}
+
+fn active_window() -> Option<String> {
+    let output = Command::new("hyprctl")
+        .args(["activewindow", "-j"])
+        .output()
+        .ok()?;
+    let window: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+    window["class"]
+        .as_str()
+        .filter(|class| !class.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_tracker_counts_focus_and_splits_days() {
+        let at = |d, h, m| Local.with_ymd_and_hms(2026, 3, d, h, m, 0).unwrap();
+        let mut tracker = Tracker::new(DailyUsage::new(at(1, 0, 0).date_naive()), at(1, 22, 0));
+
+        tracker.focused = Some("firefox".into());
+        assert_eq!(tracker.count(at(1, 23, 0)), None);
+        tracker.paused = true;
+        tracker.count(at(1, 23, 30));
+        tracker.paused = false;
+
+        // Until midnight goes to the day that ended
+        let ended = tracker.count(at(2, 0, 10)).expect("Day didn't end");
+        assert_eq!(ended.apps["firefox"], 90 * 60);
+        assert_eq!(tracker.usage.apps["firefox"], 10 * 60);
+    }
+
+    #[test]
+    fn test_limit_is_announced_once() {
+        let config = ScreenTimeConfig {
+            limits: HashMap::from([("firefox".into(), 30)]),
+        };
+        let mut usage = DailyUsage::new(NaiveDate::from_ymd_opt(2026, 3, 1).unwrap());
+        usage.apps.insert("firefox".into(), 29 * 60);
+        usage.apps.insert("kitty".into(), 90 * 60);
+        let mut tracker = Tracker::new(usage, Local::now());
+        assert!(tracker.newly_over(&config).is_empty());
+
+        tracker.usage.apps.insert("firefox".into(), 30 * 60);
+        assert_eq!(tracker.newly_over(&config), [("firefox".into(), 30 * 60)]);
+        assert!(tracker.newly_over(&config).is_empty());
+
+        let usage = tracker.usage(&config);
+        assert_eq!(usage[0].app, "kitty");
+        assert!(usage[1].over_limit());
+    }
+
+    #[test]
+    fn test_window_class() {
+        assert_eq!(
+            window_class("firefox,Watson - Mozilla Firefox").as_deref(),
+            Some("firefox")
+        );
+        assert_eq!(window_class(","), None);
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::Arc,
+};
+
+use futures_util::StreamExt;
+use serde::Deserialize;
+use strum::{AsRefStr, EnumString};
+use suite_223b::{
+    config::profile::load_config_file,
+    protocol::{Request, Response, Surface},
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+use tokio::sync::RwLock;
+use zbus::{
+    Connection, Proxy,
+    fdo::DBusProxy,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+};
+
+use crate::{
+    RequestHandler,
+    notify::NotificationDaemon,
+    software::capture::{PORTAL_DEST, PORTAL_PATH, handle_token, portal_request, result_str},
+};
+
+const SHORTCUTS_IFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+/// What a shortcut does, the key in `shortcuts.json`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, EnumString, AsRefStr)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum ShortcutAction {
+    ToggleWindow,
+    ToggleNotificationCentre,
+    ToggleCalendar,
+    ToggleNotes,
+    ToggleDnd,
+    /// Cycles through the profiles of `focus.json`, ending focus after the last one
+    NextFocus,
+    Mute,
+    PlayPause,
+    NextTrack,
+    PreviousTrack,
+    Screenshot,
+}
+impl ShortcutAction {
+    /// Shown by the compositor when it asks the user to confirm the bindings
+    fn description(&self) -> &'static str {
+        match self {
+            Self::ToggleWindow => "Show or hide Watson",
+            Self::ToggleNotificationCentre => "Show or hide the notification centre",
+            Self::ToggleCalendar => "Show or hide the calendar",
+            Self::ToggleNotes => "Show or hide the notes",
+            Self::ToggleDnd => "Toggle do not disturb",
+            Self::NextFocus => "Switch to the next focus profile",
+            Self::Mute => "Mute or unmute",
+            Self::PlayPause => "Play or pause media",
+            Self::NextTrack => "Next track",
+            Self::PreviousTrack => "Previous track",
+            Self::Screenshot => "Take a screenshot",
+        }
+    }
+
+    /// The MPRIS `Player` method, media keys have no request of their own
+    fn player_method(&self) -> Option<&'static str> {
+        match self {
+            Self::PlayPause => Some("PlayPause"),
+            Self::NextTrack => Some("Next"),
+            Self::PreviousTrack => Some("Previous"),
+            _ => None,
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/watson/shortcuts.json`, preferred triggers in the
+/// [shortcuts spec](https://specifications.freedesktop.org/shortcuts-spec/latest/) format, e.g.
+/// `{ "toggle-notification-centre": "LOGO+n", "mute": "LOGO+m" }`. The compositor may let
+/// the user pick others.
+type ShortcutsConfig = BTreeMap<ShortcutAction, String>;
+
+#[derive(Debug, Default)]
+struct ShortcutState {
+    /// Volume from before `ShortcutAction::Mute`, so the next press restores it
+    unmuted: Option<u8>,
+}
+impl ShortcutState {
+    /// The request doing `action`, given whether notifications are silenced and the volume
+    fn request(&mut self, action: ShortcutAction, silent: bool, volume: u8) -> Option<Request> {
+        Some(match action {
+            ShortcutAction::ToggleWindow => Request::ToggleSurface(Surface::Window),
+            ShortcutAction::ToggleNotificationCentre => {
+                Request::ToggleSurface(Surface::NotificationCentre)
+            }
+            ShortcutAction::ToggleCalendar => Request::ToggleSurface(Surface::Calendar),
+            ShortcutAction::ToggleNotes => Request::ToggleSurface(Surface::Notes),
+            ShortcutAction::ToggleDnd => Request::Silence(!silent),
+            // Picked by `run`, which knows the profiles
+            ShortcutAction::NextFocus => return None,
+            ShortcutAction::Mute => match self.unmuted.take() {
+                Some(unmuted) if volume == 0 => Request::SetVolume(unmuted),
+                // Not muted, or raised since, e.g. with a slider
+                _ => {
+                    self.unmuted = Some(volume);
+                    Request::SetVolume(0)
+                }
+            },
+            ShortcutAction::Screenshot => Request::Screenshot,
+            ShortcutAction::PlayPause
+            | ShortcutAction::NextTrack
+            | ShortcutAction::PreviousTrack => {
+                return None;
+            }
+        })
+    }
+}
+
+/// Registers the shortcuts from `shortcuts.json` with the GlobalShortcuts portal and runs them
+/// as requests when they are pressed
+pub async fn global_shortcuts_listener(
+    daemon: Arc<RwLock<NotificationDaemon>>,
+) -> Result<(), WatsonError> {
+    let config: ShortcutsConfig = load_config_file("shortcuts")?;
+    if config.is_empty() {
+        return Ok(());
+    }
+    // Request paths are derived from the unique name, so the portal gets its own connection
+    let conn = Connection::session()
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusConnect, e.to_string()))?;
+
+    let token = handle_token();
+    let options = HashMap::from([
+        ("handle_token", Value::from(token.as_str())),
+        ("session_handle_token", Value::from(handle_token())),
+    ]);
+    let results =
+        portal_request(&conn, SHORTCUTS_IFACE, "CreateSession", &(options,), &token).await?;
+    let session = ObjectPath::try_from(result_str(&results, "session_handle")?)
+        .map(OwnedObjectPath::from)
+        .map_err(|e| watson_err!(WatsonErrorKind::InvalidData, e.to_string()))?;
+
+    let shortcuts: Vec<(&str, HashMap<&str, Value>)> = config
+        .iter()
+        .map(|(action, trigger)| {
+            let properties = HashMap::from([
+                ("description", Value::from(action.description())),
+                ("preferred_trigger", Value::from(trigger.as_str())),
+            ]);
+            (action.as_ref(), properties)
+        })
+        .collect();
+
+    // Subscribe before binding, a shortcut may be pressed right away
+    let portal = Proxy::new(&conn, PORTAL_DEST, PORTAL_PATH, SHORTCUTS_IFACE)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+    let mut activated = portal
+        .receive_signal("Activated")
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
+
+    let token = handle_token();
+    let options = HashMap::from([("handle_token", Value::from(token.as_str()))]);
+    portal_request(
+        &conn,
+        SHORTCUTS_IFACE,
+        "BindShortcuts",
+        &(&session, shortcuts, "", options),
+        &token,
+    )
+    .await?;
+
+    let mut state = ShortcutState::default();
+    while let Some(signal) = activated.next().await {
+        let Ok((from, id, _, _)) =
+            signal
+                .body()
+                .deserialize::<(OwnedObjectPath, String, u64, HashMap<String, OwnedValue>)>()
+        else {
+            continue;
+        };
+        let Ok(action) = ShortcutAction::from_str(&id) else {
+            continue;
+        };
+        if from != session {
+            continue;
+        }
+        if let Err(e) = run(&daemon, &conn, &mut state, action).await {
+            eprintln!("{:?}", e);
+        }
+    }
+    Ok(())
+}
+
+async fn run(
+    daemon: &Arc<RwLock<NotificationDaemon>>,
+    conn: &Connection,
+    state: &mut ShortcutState,
+    action: ShortcutAction,
+) -> Result<(), WatsonError> {
+    if let Some(method) = action.player_method() {
+        return media_player(conn, method).await;
+    }
+
+    let mut daemon = daemon.write().await;
+    let volume = match action {
+        ShortcutAction::Mute => daemon.hardware.get_volume().await?,
+        _ => 0,
+    };
+    let request = match action {
+        ShortcutAction::NextFocus => Request::SetFocus(daemon.focus.next()),
+        _ => match state.request(action, daemon.settings.silent, volume) {
+            Some(request) => request,
+            None => return Ok(()),
+        },
+    };
+    match request.handle(&mut daemon).await {
+        Response::Error(e) => Err(watson_err!(e.kind, e.message)),
+        _ => Ok(()),
+    }
+}
+
+/// Calls `method` on the first MPRIS player on the session bus
+async fn media_player(conn: &Connection, method: &str) -> Result<(), WatsonError> {
+    let bus = DBusProxy::new(conn)
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+    let names = bus
+        .list_names()
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))?;
+    let Some(player) = names.iter().find(|n| n.starts_with(MPRIS_PREFIX)) else {
+        return Ok(());
+    };
+    let proxy = Proxy::new(
+        conn,
+        player.to_string(),
+        "/org/mpris/MediaPlayer2",
+        "org.mpris.MediaPlayer2.Player",
+    )
+    .await
+    .map_err(|e| watson_err!(WatsonErrorKind::ProxyCreate, e.to_string()))?;
+    proxy
+        .call::<_, _, ()>(method, &())
+        .await
+        .map_err(|e| watson_err!(WatsonErrorKind::DBusProxyCall, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_keys_are_actions() {
+        let config: ShortcutsConfig =
+            serde_json::from_str(r#"{ "toggle-notification-centre": "LOGO+n", "mute": "LOGO+m" }"#)
+                .unwrap();
+        assert_eq!(config[&ShortcutAction::Mute], "LOGO+m");
+        assert_eq!(
+            ShortcutAction::from_str(ShortcutAction::ToggleNotificationCentre.as_ref()),
+            Ok(ShortcutAction::ToggleNotificationCentre)
+        );
+    }
+
+    #[test]
+    fn test_mute_restores_the_volume() {
+        let mut state = ShortcutState::default();
+        let volume = |r: Option<Request>| match r {
+            Some(Request::SetVolume(v)) => v,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(volume(state.request(ShortcutAction::Mute, false, 40)), 0);
+        assert_eq!(volume(state.request(ShortcutAction::Mute, false, 0)), 40);
+
+        // Raised while muted, the next press mutes again
+        assert_eq!(volume(state.request(ShortcutAction::Mute, false, 40)), 0);
+        assert_eq!(volume(state.request(ShortcutAction::Mute, false, 25)), 0);
+        assert_eq!(volume(state.request(ShortcutAction::Mute, false, 0)), 25);
+
+        assert!(matches!(
+            state.request(ShortcutAction::ToggleDnd, true, 0),
+            Some(Request::Silence(false))
+        ));
+        assert!(state.request(ShortcutAction::NextTrack, false, 0).is_none());
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, Utc};
+use suite_223b::{
+    calendar::{
+        travel::{TravelConfig, TravelRouter},
+        utils::{CalDavEvent, structs::EventFilter},
+    },
+    config::profile::load_config_file,
+    notification::Notification,
+    protocol::JobSchedule,
+    utils::errors::WatsonError,
+};
+use tokio::sync::RwLock;
+
+use crate::{
+    core::scheduler::Scheduler, notify::NotificationDaemon, software::calendar::CalendarBackend,
+};
+
+/// Scheduler id of `schedule_travel_reminders`
+pub const TRAVEL_REMINDER_JOB: &str = "travel-reminders";
+
+/// How far ahead events are looked at, routes further out than this aren't worth a reminder
+const LOOK_AHEAD: Duration = Duration::from_secs(6 * 3600);
+
+/// "Leave by" reminders for events with a `location`
+pub struct TravelReminders {
+    config: TravelConfig,
+    router: Box<dyn TravelRouter>,
+    /// By uid and location, so a moved event is routed again. Failed lookups are kept as `None`
+    /// instead of being retried every minute.
+    travel: Mutex<HashMap<(String, String), Option<Duration>>>,
+    /// Uids of events that were already reminded of
+    reminded: Mutex<HashSet<String>>,
+}
+impl TravelReminders {
+    pub fn new(config: TravelConfig, router: Box<dyn TravelRouter>) -> Self {
+        Self {
+            config,
+            router,
+            travel: Mutex::new(HashMap::new()),
+            reminded: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// `None` unless `travel.json` sets a home to start from
+    pub fn load() -> Option<Self> {
+        let config: TravelConfig = match load_config_file("travel") {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("{:?}", e);
+                return None;
+            }
+        };
+        config.home?;
+        let router = config.router();
+        Some(Self::new(config, router))
+    }
+
+    /// Events whose reminder is due at `now`, with the time to leave by
+    pub async fn due(
+        &self,
+        events: Vec<CalDavEvent>,
+        now: DateTime<Utc>,
+    ) -> Vec<(CalDavEvent, DateTime<Utc>)> {
+        let mut due = Vec::new();
+        for event in events {
+            let Some(start) = event.start_utc().filter(|s| *s > now) else {
+                continue;
+            };
+            // Meeting links end up in `location` too
+            let Some(location) = event
+                .location
+                .clone()
+                .filter(|l| !l.trim().is_empty() && !l.contains("://"))
+            else {
+                continue;
+            };
+            if self.reminded.lock().expect("Poisoned").contains(&event.uid) {
+                continue;
+            }
+            let Some(travel) = self.travel_time(&event.uid, &location, start).await else {
+                continue;
+            };
+
+            let leave_by = self.config.leave_by(start, travel);
+            if now >= leave_by - chrono::Duration::minutes(self.config.reminder_minutes) {
+                self.reminded
+                    .lock()
+                    .expect("Poisoned")
+                    .insert(event.uid.clone());
+                due.push((event, leave_by));
+            }
+        }
+        due
+    }
+
+    async fn travel_time(
+        &self,
+        uid: &str,
+        location: &str,
+        start: DateTime<Utc>,
+    ) -> Option<Duration> {
+        let key = (uid.to_string(), location.to_string());
+        if let Some(cached) = self.travel.lock().expect("Poisoned").get(&key) {
+            return *cached;
+        }
+        let origin = self.config.origin_at(start.with_timezone(&Local))?;
+        let travel = match self.router.travel_time(origin, location).await {
+            Ok(t) => Some(t),
+            Err(e) => {
+                eprintln!("{:?}", e);
+                None
+            }
+        };
+        self.travel.lock().expect("Poisoned").insert(key, travel);
+        travel
+    }
+}
+
+/// Checks upcoming events every minute and sends a "Leave by" notification once it is time to
+/// get going
+pub fn schedule_travel_reminders(
+    scheduler: &Scheduler,
+    events: Arc<CalendarBackend>,
+    reminders: Arc<TravelReminders>,
+    daemon: Weak<RwLock<NotificationDaemon>>,
+) -> Result<(), WatsonError> {
+    scheduler.register(TRAVEL_REMINDER_JOB, JobSchedule::Every(60), move || {
+        let events = Arc::clone(&events);
+        let reminders = Arc::clone(&reminders);
+        let daemon = daemon.clone();
+        async move {
+            let upcoming = events.get_events_with_filter(EventFilter::Nearby {
+                look_back: Duration::ZERO,
+                look_ahead: LOOK_AHEAD,
+            });
+            let due = reminders.due(upcoming, Utc::now()).await;
+            if due.is_empty() {
+                return;
+            }
+            let Some(strong) = daemon.upgrade() else {
+                return;
+            };
+            let mut strong = strong.write().await;
+            for (event, leave_by) in due {
+                strong.insert(
+                    leave_by_notification(&event.title, leave_by),
+                    daemon.clone(),
+                );
+            }
+        }
+    })
+}
+
+fn leave_by_notification(title: &str, leave_by: DateTime<Utc>) -> Notification {
+    Notification {
+        app_name: "Watson".into(),
+        app_icon: "x-office-calendar-symbolic".into(),
+        summary: title.into(),
+        body: format!(
+            "Leave by {}",
+            leave_by.with_timezone(&Local).format("%H:%M")
+        ),
+        expire_timeout: -1,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use suite_223b::{calendar::utils::structs::DateTimeSpec, utils::astronomy::Location};
+
+    /// Every place is 20 minutes away
+    struct FixedRouter(Arc<AtomicUsize>);
+    #[async_trait]
+    impl TravelRouter for FixedRouter {
+        async fn travel_time(&self, _: Location, _: &str) -> Result<Duration, WatsonError> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Ok(Duration::from_secs(20 * 60))
+        }
+    }
+
+    fn event(uid: &str, location: Option<&str>, start: DateTime<Utc>) -> CalDavEvent {
+        CalDavEvent {
+            uid: uid.into(),
+            title: "Dentist".into(),
+            location: location.map(Into::into),
+            start: Some(DateTimeSpec::DateTime { value: start }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reminder_comes_before_travel_time() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let config = TravelConfig {
+            home: Some(Location {
+                latitude: 52.52,
+                longitude: 13.40,
+            }),
+            ..Default::default()
+        };
+        let reminders = TravelReminders::new(config, Box::new(FixedRouter(Arc::clone(&lookups))));
+
+        let now = Utc::now();
+        let start = now + chrono::Duration::minutes(60);
+        let events = vec![
+            event("dentist", Some("Hauptstraße 1, Berlin"), start),
+            event("call", Some("https://meet.example/abc"), start),
+            event("lunch", None, start),
+        ];
+
+        // Leave 25 minutes before the start, the reminder comes 10 minutes earlier
+        assert!(reminders.due(events.clone(), now).await.is_empty());
+        let later = start - chrono::Duration::minutes(35);
+        let due = reminders.due(events.clone(), later).await;
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0.uid, "dentist");
+        assert_eq!(due[0].1, start - chrono::Duration::minutes(25));
+
+        // Reminded once, routed once
+        assert!(reminders.due(events, later).await.is_empty());
+        assert_eq!(lookups.load(Ordering::Relaxed), 1);
+    }
+}
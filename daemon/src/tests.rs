@@ -0,0 +1,784 @@
+//! End-to-end flows through `handle_client`. The daemon runs on in-memory hardware backends,
+//! with private peer-to-peer buses instead of the system and session bus.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use suite_223b::notification::Notification;
+use suite_223b::protocol::{
+    BluetoothDevice, ManagedService, PluginCall, PluginManifest, PluginMessage, PowerMode,
+    StateSnapshot,
+};
+use zbus::{Guid, Proxy};
+
+use super::*;
+use crate::core::plugins::serve_plugin;
+use crate::hardware::mock::{MockAudio, MockBacklight, MockNetwork, MockPower};
+use crate::hardware::{Backends, Capabilities, HardwareController};
+use crate::notify::{FocusProfiles, snooze_job};
+use crate::software::{CALENDAR_REFRESH_JOB, SoftwareController};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Harness {
+    daemon: Arc<RwLock<NotificationDaemon>>,
+    network: MockNetwork,
+    power: MockPower,
+    /// Where applications send notifications from
+    notifications: Connection,
+    /// Server side of the hardware bus, nothing is served on it
+    _system: Connection,
+}
+impl Harness {
+    async fn new() -> Self {
+        DAEMON_TX.get_or_init(ConnectionRegistry::new);
+
+        // Only listeners use the bus, and none run here
+        let (system, system_peer) = peer_connections(None).await;
+        let (network, power) = (MockNetwork::default(), MockPower::default());
+        let backends = Backends {
+            network: Box::new(network.clone()),
+            backlight: Box::new(MockBacklight::default()),
+            power: Box::new(power.clone()),
+        };
+        let capabilities = Capabilities {
+            backlight: true,
+            pulse: true,
+            network_manager: true,
+            bluez: true,
+            power_profiles: true,
+            ..Default::default()
+        };
+        let mut hardware = HardwareController::with_backends(system, capabilities, backends);
+        hardware.set_audio(Box::new(MockAudio::default()));
+        let daemon = Arc::new(RwLock::new(NotificationDaemon::with_controllers(
+            hardware,
+            SoftwareController::without_calendars(),
+        )));
+
+        let handle = DaemonHandle::new(Arc::clone(&daemon));
+        let (notifications, session) = peer_connections(Some(handle)).await;
+        daemon.write().await.session = Some(session);
+
+        Self {
+            daemon,
+            network,
+            power,
+            notifications,
+            _system: system_peer,
+        }
+    }
+
+    /// Connects a client the way the socket listener does
+    fn connect(&self) -> TestClient {
+        let (stream, server) = UnixStream::pair().expect("Failed to create socket pair");
+        let mut slot = DAEMON_TX.get().expect("No registry").register();
+        let daemon = Arc::clone(&self.daemon);
+        tokio::spawn(async move {
+            handle_client(server, daemon, slot.id, &mut slot.rx).await;
+        });
+        TestClient { stream }
+    }
+
+    async fn notify(&self, summary: &str) -> u32 {
+        let proxy = Proxy::new(
+            &self.notifications,
+            "org.freedesktop.Notifications",
+            DaemonHandle::PATH,
+            "org.freedesktop.Notifications",
+        )
+        .await
+        .expect("Failed to create proxy");
+        let hints: HashMap<&str, zbus::zvariant::Value> = HashMap::new();
+        let actions: Vec<&str> = Vec::new();
+        proxy
+            .call(
+                "Notify",
+                &("test", 0u32, "", summary, "", actions, hints, -1i32),
+            )
+            .await
+            .expect("Notify failed")
+    }
+}
+
+/// Client and server end of a bus without a broker, optionally serving the notification
+/// interface on the server end
+async fn peer_connections(handle: Option<DaemonHandle>) -> (Connection, Connection) {
+    let (client, server) = UnixStream::pair().expect("Failed to create socket pair");
+    let mut server = Builder::unix_stream(server)
+        .server(Guid::generate())
+        .expect("Invalid guid")
+        .p2p();
+    if let Some(handle) = handle {
+        server = server
+            .serve_at(DaemonHandle::PATH, handle)
+            .expect("Failed to serve");
+    }
+    let (client, server) =
+        tokio::try_join!(Builder::unix_stream(client).p2p().build(), server.build())
+            .expect("Failed to connect peers");
+    (client, server)
+}
+
+/// Ack ids only have to be unique among the tests running at once
+fn rand_id() -> u32 {
+    static NEXT: AtomicU32 = AtomicU32::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+struct TestClient {
+    stream: UnixStream,
+}
+impl TestClient {
+    /// Waits until the daemon handled `request`, it doesn't answer most changes otherwise
+    async fn call(&mut self, request: Request) {
+        let id = rand_id();
+        self.send(Request::Tracked {
+            id,
+            request: Box::new(request),
+        })
+        .await;
+        let error = self
+            .expect(|r| match r {
+                Response::Ack { id: acked, error } if acked == id => Some(error),
+                _ => None,
+            })
+            .await;
+        assert!(error.is_none(), "{:?}", error);
+    }
+
+    async fn send(&mut self, request: Request) {
+        let message = SizedMessageObj::from_struct(&request).expect("Failed to encode");
+        self.stream
+            .write_sized(message)
+            .await
+            .expect("Failed to send");
+    }
+
+    /// The first response `f` accepts. Every test shares the registry, so responses meant for
+    /// other tests are skipped.
+    async fn expect<T>(&mut self, f: impl Fn(Response) -> Option<T>) -> T {
+        let wait = async {
+            loop {
+                let buf = self.stream.read_sized().await.expect("Daemon hung up");
+                let response: Response = decode_sized(&buf).expect("Failed to decode");
+                if let Some(value) = f(response) {
+                    return value;
+                }
+            }
+        };
+        tokio::time::timeout(TIMEOUT, wait)
+            .await
+            .expect("No matching response")
+    }
+}
+
+#[tokio::test]
+async fn test_notification_lifecycle() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+
+    let id = harness.notify("lifecycle").await;
+    let notification: Notification = client
+        .expect(|r| match r {
+            Response::Notification(Some(n)) if n.summary == "lifecycle" => Some(n),
+            _ => None,
+        })
+        .await;
+    assert_eq!(notification.id, id);
+
+    client
+        .send(Request::PendingNotifications {
+            offset: 0,
+            limit: 10,
+        })
+        .await;
+    let pending = client
+        .expect(|r| match r {
+            Response::Notifications(n) => Some(n),
+            _ => None,
+        })
+        .await;
+    assert_eq!(pending.len(), 1);
+
+    client.send(Request::DismissNotification(id)).await;
+    let reason = client
+        .expect(|r| match r {
+            Response::NotificationClosed { id: closed, reason } if closed == id => Some(reason),
+            _ => None,
+        })
+        .await;
+    assert!(matches!(reason, CloseReason::Dismissed));
+    assert!(harness.daemon.read().await.get_by_id(id).is_none());
+}
+
+#[tokio::test]
+async fn test_volume_round_trip() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+
+    client.send(Request::SetVolume(37)).await;
+    client
+        .expect(|r| match r {
+            Response::VolumeState { percentage: 37 } => Some(()),
+            _ => None,
+        })
+        .await;
+
+    client.send(Request::SystemState).await;
+    let state = client
+        .expect(|r| match r {
+            Response::SystemState(state) => Some(state),
+            _ => None,
+        })
+        .await;
+    assert_eq!(state.volume, 37);
+}
+
+#[tokio::test]
+async fn test_toggles_reach_the_backends() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+
+    client.call(Request::SetWifi(true)).await;
+    client
+        .call(Request::SetPowerMode(PowerMode::Performace.into()))
+        .await;
+    assert!(harness.network.wifi.load(Ordering::Relaxed));
+
+    client.send(Request::SystemState).await;
+    let state = client
+        .expect(|r| match r {
+            Response::SystemState(state) => Some(state),
+            _ => None,
+        })
+        .await;
+    assert!(state.wifi);
+    assert!(!state.bluetooth);
+    assert_eq!(state.powermode, u8::from(PowerMode::Performace));
+}
+
+#[tokio::test]
+async fn test_bluetooth_devices_in_system_state() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+    let headset = BluetoothDevice {
+        name: "Headset".into(),
+        icon: Some("audio-headset".into()),
+        battery: Some(80),
+        codec: Some("AAC".into()),
+    };
+    harness
+        .network
+        .bluetooth_devices
+        .lock()
+        .unwrap()
+        .push(headset.clone());
+
+    // Nothing is reported while Bluetooth is off
+    for (enabled, expected) in [(false, vec![]), (true, vec![headset])] {
+        client.call(Request::SetBluetooth(enabled)).await;
+        client.send(Request::SystemState).await;
+        let state = client
+            .expect(|r| match r {
+                Response::SystemState(state) => Some(state),
+                _ => None,
+            })
+            .await;
+        assert_eq!(state.bluetooth_devices, expected);
+    }
+}
+
+#[tokio::test]
+async fn test_power_hold_replaces_the_previous_one() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+
+    for profile in [PowerMode::Performace, PowerMode::PowerSave] {
+        let request = Request::HoldPowerProfile {
+            profile: profile.into(),
+            reason: "test".into(),
+        };
+        client.call(request).await;
+    }
+    assert_eq!(harness.power.cookies.lock().unwrap().as_slice(), &[1]);
+
+    client.call(Request::ReleasePowerProfile).await;
+    assert!(harness.power.cookies.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_battery_broadcast_reaches_every_client() {
+    let harness = Harness::new().await;
+    let mut clients = [harness.connect(), harness.connect(), harness.connect()];
+
+    let percentage = 42;
+    let _result = DAEMON_TX.get().map(|d| {
+        d.send(InternalMessage::BatteryState {
+            state: BatteryState::Discharging,
+            percentage,
+        })
+    });
+    for client in &mut clients {
+        client
+            .expect(|r| match r {
+                Response::BatteryState {
+                    state: BatteryState::Discharging,
+                    percentage: p,
+                } if p == percentage => Some(()),
+                _ => None,
+            })
+            .await;
+    }
+}
+
+#[tokio::test]
+async fn test_calendar_sync_can_be_switched_off() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+
+    for enabled in [true, false] {
+        let request = Request::SetServiceEnabled {
+            service: ManagedService::CalendarSync,
+            enabled,
+        };
+        client.call(request).await;
+
+        client.send(Request::ScheduledJobs).await;
+        let jobs = client
+            .expect(|r| match r {
+                Response::ScheduledJobs(jobs) => Some(jobs),
+                _ => None,
+            })
+            .await;
+        let scheduled = jobs.iter().any(|j| j.id == CALENDAR_REFRESH_JOB);
+        assert_eq!(scheduled, enabled);
+    }
+}
+
+#[tokio::test]
+async fn test_snoozed_notification_comes_back() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+
+    let id = harness.notify("snooze").await;
+    client
+        .call(Request::SnoozeNotification { id, seconds: 3600 })
+        .await;
+    client
+        .expect(|r| match r {
+            Response::NotificationClosed {
+                reason: CloseReason::Snoozed,
+                ..
+            } => Some(()),
+            _ => None,
+        })
+        .await;
+    assert!(harness.daemon.read().await.get_by_id(id).is_none());
+
+    client.send(Request::ScheduledJobs).await;
+    let jobs = client
+        .expect(|r| match r {
+            Response::ScheduledJobs(jobs) => Some(jobs),
+            _ => None,
+        })
+        .await;
+    assert!(jobs.iter().any(|j| j.id == snooze_job(id)));
+
+    // What the job runs once the hour is up
+    harness.daemon.write().await.wake(id);
+    client
+        .expect(|r| match r {
+            Response::Notification(Some(n)) if n.summary == "snooze" => Some(()),
+            _ => None,
+        })
+        .await;
+    assert!(harness.daemon.read().await.get_by_id(id).is_some());
+}
+
+#[tokio::test]
+async fn test_state_snapshot_restores() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+
+    harness.notify("snapshot").await;
+    client.call(Request::Silence(true)).await;
+    let job = Request::ScheduleJob {
+        id: "snapshot-job".into(),
+        schedule: JobSchedule::Every(3600),
+    };
+    client.call(job).await;
+
+    client.send(Request::DumpState).await;
+    let snapshot = client
+        .expect(|r| match r {
+            Response::StateSnapshot(s) => Some(s),
+            _ => None,
+        })
+        .await;
+    assert!(snapshot.silent);
+    assert!(
+        snapshot
+            .notifications
+            .iter()
+            .any(|n| n.summary == "snapshot")
+    );
+
+    // The way `--load-state` reads it
+    let json = serde_json::to_vec(&snapshot).expect("Failed to serialize");
+    let mut snapshot: StateSnapshot = serde_json::from_slice(&json).expect("Failed to parse");
+    snapshot.system.volume = 17;
+
+    let restored = Harness::new().await;
+    restored.daemon.write().await.restore(snapshot);
+    let mut client = restored.connect();
+
+    client.send(Request::SystemState).await;
+    let system = client
+        .expect(|r| match r {
+            Response::SystemState(s) => Some(s),
+            _ => None,
+        })
+        .await;
+    assert_eq!(system.volume, 17);
+
+    client.send(Request::ScheduledJobs).await;
+    let jobs = client
+        .expect(|r| match r {
+            Response::ScheduledJobs(jobs) => Some(jobs),
+            _ => None,
+        })
+        .await;
+    assert!(jobs.iter().any(|j| j.id == "snapshot-job"));
+
+    let daemon = restored.daemon.read().await;
+    assert!(daemon.settings.silent);
+    assert!(daemon.stored().any(|n| n.summary == "snapshot"));
+}
+
+#[tokio::test]
+async fn test_missed_notifications_during_dnd() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+
+    client.call(Request::Silence(true)).await;
+    for summary in ["missed 1", "missed 2"] {
+        harness.notify(summary).await;
+    }
+    client
+        .expect(|r| match r {
+            Response::MissedNotifications(2) => Some(()),
+            _ => None,
+        })
+        .await;
+    assert_eq!(harness.daemon.read().await.settings.missed(), 2);
+
+    client.call(Request::Silence(false)).await;
+    let missed = client
+        .expect(|r| match r {
+            Response::DndEnded { missed } => Some(missed),
+            _ => None,
+        })
+        .await;
+    assert_eq!(missed, 2);
+    assert_eq!(harness.daemon.read().await.settings.missed(), 0);
+}
+
+#[tokio::test]
+async fn test_client_notification_joins_the_history() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+
+    client
+        .send(Request::Notify {
+            summary: "Timer finished".into(),
+            body: "Tea is ready".into(),
+            icon: "alarm-symbolic".into(),
+            timeout: -1,
+            actions: vec![("restart".into(), "Restart".into())],
+        })
+        .await;
+    let id = client
+        .expect(|r| match r {
+            Response::Notified(id) => Some(id),
+            _ => None,
+        })
+        .await;
+
+    // Following ids continue from it, as for applications on D-Bus
+    assert_eq!(harness.notify("after").await, id + 1);
+    let daemon = harness.daemon.read().await;
+    let notification = daemon.get_by_id(id).expect("Not stored");
+    assert_eq!(notification.app_name, "Watson");
+    assert_eq!(notification.actions, ["restart", "Restart"]);
+}
+
+#[tokio::test]
+async fn test_own_notification_hears_its_action() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let notification = Notification {
+        app_name: "Watson".into(),
+        summary: "Allow plugin?".into(),
+        actions: vec!["allow".into(), "Allow".into()],
+        ..Default::default()
+    };
+    let id = harness.daemon.write().await.insert_with_action(
+        notification,
+        Arc::downgrade(&harness.daemon),
+        Box::new(move |action| {
+            let _result = tx.send(action.map(str::to_string));
+        }),
+    );
+
+    client
+        .call(Request::InvokeAction {
+            id,
+            action: "allow".into(),
+        })
+        .await;
+    assert_eq!(rx.recv().await, Some(Some("allow".into())));
+    // Invoking closes it, which the handler hears last
+    assert_eq!(rx.recv().await, Some(None));
+    assert_eq!(rx.recv().await, None);
+}
+
+#[tokio::test]
+async fn test_focus_profiles_apply_and_restore() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+    harness.daemon.write().await.focus = FocusProfiles::new(
+        serde_json::from_str(
+            r#"{
+                "work": { "dnd": true, "hold": ["test"] },
+                "gaming": { "power_mode": "performance", "layout": "gaming" }
+            }"#,
+        )
+        .expect("Invalid profiles"),
+    );
+
+    client.call(Request::SetFocus(Some("work".into()))).await;
+    let held = harness.notify("held").await;
+    {
+        let daemon = harness.daemon.read().await;
+        assert!(daemon.settings.silent);
+        assert!(daemon.get_by_id(held).is_none());
+    }
+
+    // Switching restores what only the previous profile changed
+    client.call(Request::SetFocus(Some("gaming".into()))).await;
+    let state = client
+        .expect(|r| match r {
+            Response::Focus(state) if state.active.as_deref() == Some("gaming") => Some(state),
+            _ => None,
+        })
+        .await;
+    assert_eq!(state.layout.as_deref(), Some("gaming"));
+    {
+        let daemon = harness.daemon.read().await;
+        assert!(!daemon.settings.silent);
+        assert!(daemon.get_by_id(held).is_some());
+    }
+    let active = || harness.power.profiles.lock().unwrap().active;
+    assert_eq!(active(), PowerMode::Performace);
+
+    client.call(Request::SetFocus(None)).await;
+    assert_eq!(active(), PowerMode::Balanced);
+
+    client.send(Request::SetFocus(Some("sleep".into()))).await;
+    client
+        .expect(|r| match r {
+            Response::Error(e) if e.message.contains("sleep") => Some(()),
+            _ => None,
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn test_trace_mirrors_other_connections() {
+    let harness = Harness::new().await;
+    let mut debugger = harness.connect();
+    let mut client = harness.connect();
+
+    // Requests are handled in order, the pong means the subscription is in place
+    debugger.send(Request::TraceProtocol).await;
+    debugger.send(Request::Ping).await;
+    debugger
+        .expect(|r| matches!(r, Response::Pong).then_some(()))
+        .await;
+
+    client.send(Request::Ping).await;
+    let request = debugger
+        .expect(|r| match r {
+            Response::Trace(e) if e.direction == TraceDirection::Request && e.payload == "Ping" => {
+                Some(e)
+            }
+            _ => None,
+        })
+        .await;
+    let response = debugger
+        .expect(|r| match r {
+            Response::Trace(e) if e.client == request.client && e.payload == "Pong" => Some(e),
+            _ => None,
+        })
+        .await;
+    assert_eq!(response.direction, TraceDirection::Response);
+}
+
+/// The plugin end of `serve_plugin`, after the handshake
+async fn connect_plugin(harness: &Harness, manifest: &PluginManifest) -> (UnixStream, PluginCall) {
+    let (mut plugin, server) = UnixStream::pair().expect("Failed to create socket pair");
+    tokio::spawn({
+        let daemon = Arc::clone(&harness.daemon);
+        async move { serve_plugin(server, daemon).await }
+    });
+    let hello = SizedMessageObj::from_struct(&PluginMessage::Hello(manifest.clone()))
+        .expect("Failed to encode");
+    plugin.write_sized(hello).await.expect("Failed to send");
+    let buf = tokio::time::timeout(TIMEOUT, plugin.read_sized())
+        .await
+        .expect("No handshake")
+        .expect("Daemon hung up");
+    (plugin, decode_sized(&buf).expect("Failed to decode"))
+}
+
+#[tokio::test]
+async fn test_plugin_serves_requests_and_broadcasts() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+    let manifest = PluginManifest {
+        name: "test-weather".into(),
+        version: "1.0".into(),
+        requests: vec!["forecast".into()],
+        broadcasts: vec!["alert".into()],
+    };
+    // Approved before, so nobody is asked
+    harness
+        .daemon
+        .read()
+        .await
+        .plugins
+        .approve(manifest.clone())
+        .expect("Failed to approve");
+
+    let (mut plugin, welcome) = connect_plugin(&harness, &manifest).await;
+    assert!(matches!(welcome, PluginCall::Welcome));
+    let (_, duplicate) = connect_plugin(&harness, &manifest).await;
+    assert!(matches!(duplicate, PluginCall::Rejected(_)));
+
+    client
+        .send(Request::Plugin {
+            plugin: "test-weather".into(),
+            request: "forecast".into(),
+            payload: "{\"city\":\"Berlin\"}".into(),
+        })
+        .await;
+    let buf = plugin.read_sized().await.expect("Daemon hung up");
+    let PluginCall::Call { call, request, .. } = decode_sized(&buf).expect("Failed to decode")
+    else {
+        panic!("Expected a call");
+    };
+    assert_eq!(request, "forecast");
+    let reply = PluginMessage::Reply {
+        call,
+        payload: "sunny".into(),
+    };
+    plugin
+        .write_sized(SizedMessageObj::from_struct(&reply).expect("Failed to encode"))
+        .await
+        .expect("Failed to send");
+    let payload = client
+        .expect(|r| match r {
+            Response::Plugin { plugin, payload } if plugin == "test-weather" => Some(payload),
+            _ => None,
+        })
+        .await;
+    assert_eq!(payload, "sunny");
+
+    // Requests the manifest doesn't declare never reach the plugin
+    client
+        .send(Request::Plugin {
+            plugin: "test-weather".into(),
+            request: "unlock-door".into(),
+            payload: String::new(),
+        })
+        .await;
+    let error = client
+        .expect(|r| match r {
+            Response::Error(e) => Some(e),
+            _ => None,
+        })
+        .await;
+    assert_eq!(error.kind, WatsonErrorKind::Plugin);
+
+    let alert = PluginMessage::Broadcast {
+        kind: "alert".into(),
+        payload: "storm".into(),
+    };
+    plugin
+        .write_sized(SizedMessageObj::from_struct(&alert).expect("Failed to encode"))
+        .await
+        .expect("Failed to send");
+    client
+        .expect(|r| match r {
+            Response::PluginEvent {
+                plugin,
+                kind,
+                payload,
+            } if plugin == "test-weather" => {
+                assert_eq!((kind.as_str(), payload.as_str()), ("alert", "storm"));
+                Some(())
+            }
+            _ => None,
+        })
+        .await;
+
+    // Gone with its connection
+    drop(plugin);
+    tokio::time::timeout(TIMEOUT, async {
+        while !harness.daemon.read().await.plugins.list().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("Plugin still listed");
+}
+
+#[tokio::test]
+async fn test_unknown_plugin_waits_for_approval() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+    let manifest = PluginManifest {
+        name: "test-unknown".into(),
+        version: "1.0".into(),
+        requests: Vec::new(),
+        broadcasts: Vec::new(),
+    };
+
+    let deny = async {
+        let id = client
+            .expect(|r| match r {
+                Response::Notification(Some(n)) if n.body.starts_with("test-unknown") => Some(n.id),
+                _ => None,
+            })
+            .await;
+        client
+            .call(Request::InvokeAction {
+                id,
+                action: "deny".into(),
+            })
+            .await;
+    };
+    let ((_, answer), ()) = tokio::join!(connect_plugin(&harness, &manifest), deny);
+    assert!(matches!(answer, PluginCall::Rejected(_)));
+    assert!(harness.daemon.read().await.plugins.list().is_empty());
+}
+
+#[tokio::test]
+async fn test_tracked_authenticate_is_acked() {
+    let harness = Harness::new().await;
+    let mut client = harness.connect();
+    client.call(Request::Authenticate("token".into())).await;
+}
@@ -1,13 +1,38 @@
 use std::{
     os::unix::process::CommandExt,
     process::{Command, Stdio},
+    time::Duration,
 };
 
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
 use suite_223b::{
+    config::profile::load_config_file,
+    protocol::SocketData,
     utils::errors::{WatsonError, WatsonErrorKind},
     watson_err,
 };
 
+use crate::core::peer::create_token;
+
+/// Environment variables commands get to see, besides `CommandPolicy::env`
+const KEPT_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LANG",
+    "LC_ALL",
+    "XDG_RUNTIME_DIR",
+    "XDG_CONFIG_HOME",
+    "XDG_DATA_HOME",
+    "XDG_CURRENT_DESKTOP",
+    "WAYLAND_DISPLAY",
+    "DISPLAY",
+    "DBUS_SESSION_BUS_ADDRESS",
+];
+/// Captured output is cut to this many bytes per stream
+const MAX_OUTPUT: usize = 4096;
+
 /// Spawnes a command completely detatched from the current process.
 ///
 /// This function uses a "double-fork" strategy to ensure that the spawned process is adopted by
@@ -31,9 +56,12 @@ pub fn spawn_detached(cmd: &str) -> Result<(), WatsonError> {
     let program = parts[0];
     let args = &parts[1..];
 
-    let mut command = Command::new(program);
-    command.args(args);
+    detach(Command::new(program).args(args))
+}
 
+/// `spawn_detached` for a command built by the caller, for arguments that must not be split,
+/// e.g. urls and paths
+pub fn detach(command: &mut Command) -> Result<(), WatsonError> {
     command
         .stdin(Stdio::null())
         .stdout(Stdio::null())
@@ -65,3 +93,249 @@ pub fn spawn_detached(cmd: &str) -> Result<(), WatsonError> {
 
     Ok(())
 }
+
+/// `$XDG_CONFIG_HOME/watson/commands.json`, limits what `Request::Command` may run, e.g.
+/// `{ "allow": ["playerctl", "brightnessctl"], "timeout_secs": 5 }`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CommandPolicy {
+    /// Programs clients may run, matched exactly against the first word of the command. Any
+    /// program may run without a list.
+    pub allow: Option<Vec<String>>,
+    /// Captured commands still running after this are killed along with their children
+    pub timeout_secs: u64,
+    /// Further environment variables to pass on
+    pub env: Vec<String>,
+    /// Only run commands for connections that sent the token from `SocketData::token_path`
+    pub require_token: bool,
+}
+impl Default for CommandPolicy {
+    fn default() -> Self {
+        Self {
+            allow: None,
+            timeout_secs: 10,
+            env: Vec::new(),
+            require_token: false,
+        }
+    }
+}
+impl CommandPolicy {
+    fn load() -> Result<Self, WatsonError> {
+        load_config_file("commands")
+    }
+    fn allows(&self, program: &str) -> bool {
+        self.allow
+            .as_ref()
+            .is_none_or(|allow| allow.iter().any(|p| p == program))
+    }
+}
+
+/// Runs the commands clients send, one process group each, without a shell and with a scrubbed
+/// environment
+pub struct CommandExecutor {
+    policy: CommandPolicy,
+    /// Set if the policy requires a token
+    token: Option<String>,
+}
+impl CommandExecutor {
+    pub fn new() -> Self {
+        let policy = CommandPolicy::load().unwrap_or_else(|e| {
+            eprintln!("{:?}", e);
+            CommandPolicy::default()
+        });
+        let token = policy.require_token.then(create_token);
+        Self {
+            token: match token {
+                Some(Ok(token)) => Some(token),
+                Some(Err(e)) => {
+                    // Nobody can know a token that wasn't written, so no command runs
+                    eprintln!("{:?}", e);
+                    Some(String::new())
+                }
+                None => None,
+            },
+            policy,
+        }
+    }
+
+    /// `cmd` in its own process group with a scrubbed environment, if it may run
+    fn command<'a>(
+        &self,
+        cmd: &'a str,
+        token: Option<&str>,
+    ) -> Result<(tokio::process::Command, &'a str), WatsonError> {
+        // Compared in constant time so the token can't be guessed byte by byte
+        if let Some(expected) = &self.token
+            && (expected.is_empty()
+                || !bool::from(
+                    expected
+                        .as_bytes()
+                        .ct_eq(token.unwrap_or_default().as_bytes()),
+                ))
+        {
+            return Err(watson_err!(
+                WatsonErrorKind::CommandDenied,
+                "Commands require authentication"
+            )
+            .with_hint(format!(
+                "Send the token from {} first",
+                SocketData::token_path()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|_| "the runtime directory".into())
+            )));
+        }
+
+        let mut parts = cmd.split_whitespace();
+        let Some(program) = parts.next() else {
+            return Err(watson_err!(WatsonErrorKind::InvalidData, "Empty command"));
+        };
+        if !self.policy.allows(program) {
+            return Err(watson_err!(
+                WatsonErrorKind::CommandDenied,
+                format!("{program} is not allowed to run")
+            )
+            .with_hint("Add it to \"allow\" in commands.json"));
+        }
+
+        let env = KEPT_ENV
+            .iter()
+            .copied()
+            .chain(self.policy.env.iter().map(String::as_str))
+            .filter_map(|key| std::env::var_os(key).map(|value| (key, value)));
+        let mut command = tokio::process::Command::new(program);
+        command
+            .args(parts)
+            .env_clear()
+            .envs(env)
+            .stdin(Stdio::null())
+            .process_group(0);
+        Ok((command, program))
+    }
+
+    /// Starts `cmd` for a connection that authenticated with `token` and leaves it running.
+    /// Fails if it isn't allowed or can't start.
+    pub fn spawn(&self, cmd: &str, token: Option<&str>) -> Result<(), WatsonError> {
+        let (mut command, program) = self.command(cmd, token)?;
+        // Reaped by tokio once it exits
+        command
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, format!("{program}: {e}")))?;
+        Ok(())
+    }
+
+    /// Runs `cmd` to completion for a connection that authenticated with `token`. Fails if it
+    /// isn't allowed, can't start, times out or exits with a non-zero status, the latter with
+    /// the end of its stderr as message.
+    pub async fn run(
+        &self,
+        cmd: &str,
+        token: Option<&str>,
+    ) -> Result<(String, String), WatsonError> {
+        let (mut command, program) = self.command(cmd, token)?;
+        let child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| watson_err!(WatsonErrorKind::CommandExecute, format!("{program}: {e}")))?;
+        let pid = child.id();
+
+        let timeout = Duration::from_secs(self.policy.timeout_secs);
+        let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(output) => output.map_err(|e| {
+                watson_err!(WatsonErrorKind::CommandExecute, format!("{program}: {e}"))
+            })?,
+            Err(_) => {
+                // Dropping the child only kills the program itself
+                if let Some(pid) = pid {
+                    unsafe { libc::kill(-(pid as i32), libc::SIGKILL) };
+                }
+                return Err(watson_err!(
+                    WatsonErrorKind::Timeout,
+                    format!("{program} was killed after {}s", self.policy.timeout_secs)
+                ));
+            }
+        };
+
+        let stdout = truncate_output(&output.stdout);
+        let stderr = truncate_output(&output.stderr);
+        if !output.status.success() {
+            let status = match output.status.code() {
+                Some(code) => format!("status {code}"),
+                None => "a signal".to_string(),
+            };
+            let reason = stderr.lines().rev().find(|l| !l.trim().is_empty());
+            return Err(watson_err!(
+                WatsonErrorKind::CommandExecute,
+                match reason {
+                    Some(reason) => format!("{program} exited with {status}: {}", reason.trim()),
+                    None => format!("{program} exited with {status}"),
+                }
+            ));
+        }
+        Ok((stdout, stderr))
+    }
+}
+
+/// The last `MAX_OUTPUT` bytes of `bytes`, where failures usually explain themselves
+fn truncate_output(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let mut start = text.len().saturating_sub(MAX_OUTPUT);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    text[start..].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowlist_matches_programs_exactly() {
+        let open = CommandPolicy::default();
+        assert!(open.allows("rm"));
+
+        let policy = CommandPolicy {
+            allow: Some(vec!["playerctl".into()]),
+            ..Default::default()
+        };
+        assert!(policy.allows("playerctl"));
+        assert!(!policy.allows("/usr/bin/playerctl"));
+        assert!(!policy.allows("playerctl2"));
+    }
+
+    #[test]
+    fn test_commands_need_the_token() {
+        let executor = CommandExecutor {
+            policy: CommandPolicy::default(),
+            token: Some("secret".into()),
+        };
+        for token in [None, Some(""), Some("secreT"), Some("secret2")] {
+            let error = executor.command("true", token).unwrap_err();
+            assert_eq!(error.kind, WatsonErrorKind::CommandDenied);
+        }
+        assert!(executor.command("true", Some("secret")).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_only_captured_commands_are_waited_for() {
+        let executor = CommandExecutor {
+            policy: CommandPolicy {
+                timeout_secs: 1,
+                ..Default::default()
+            },
+            token: None,
+        };
+        let started = std::time::Instant::now();
+        executor.spawn("sleep 5", None).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let error = executor.run("sleep 5", None).await.unwrap_err();
+        assert_eq!(error.kind, WatsonErrorKind::Timeout);
+        let (stdout, _) = executor.run("echo captured", None).await.unwrap();
+        assert_eq!(stdout, "captured\n");
+    }
+}
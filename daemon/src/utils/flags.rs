@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+#[derive(Debug, Default, Clone)]
+pub struct DaemonFlags {
+    /// Take over `org.freedesktop.Notifications` from the current owner
+    pub replace: bool,
+    /// Leave `org.freedesktop.Notifications` to another daemon and only run the other services
+    pub no_notifications: bool,
+    /// Largest client message in bytes, see `suite_223b::tokio::DEFAULT_MAX_MESSAGE_SIZE`
+    pub max_message_size: Option<usize>,
+    /// Config profile to merge over the base config, see `suite_223b::config::profile`
+    pub profile: Option<String>,
+    /// Serve Prometheus metrics on this local port
+    pub metrics_port: Option<u16>,
+    /// Start the client and restart it when it crashes, for sessions without the systemd units
+    pub watchdog: bool,
+    /// Start from a snapshot of `watson dump-state` instead of the saved state, for debugging
+    pub load_state: Option<PathBuf>,
+    /// Listen here instead of `$XDG_RUNTIME_DIR/watson/<display>.sock`, for testing
+    pub socket: Option<PathBuf>,
+    /// Print every request, response and broadcast to stderr
+    pub trace_protocol: bool,
+}
+impl DaemonFlags {
+    pub fn parse(args: std::env::Args) -> Self {
+        let mut flags = Self::default();
+        let mut args = args.skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--replace" | "-r" => flags.replace = true,
+                "--no-notifications" => flags.no_notifications = true,
+                "--max-message-size" => {
+                    flags.max_message_size = args.next().and_then(|v| v.parse().ok());
+                }
+                "--profile" => flags.profile = args.next(),
+                "--metrics-port" => {
+                    flags.metrics_port = args.next().and_then(|v| v.parse().ok());
+                }
+                "--watchdog" => flags.watchdog = true,
+                "--load-state" => flags.load_state = args.next().map(PathBuf::from),
+                "--socket" => flags.socket = args.next().map(PathBuf::from),
+                "--trace-protocol" => flags.trace_protocol = true,
+                _ => {}
+            }
+        }
+        flags
+    }
+}
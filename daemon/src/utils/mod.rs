@@ -1 +1,3 @@
 pub mod command;
+pub mod flags;
+pub mod systemd;
@@ -0,0 +1,63 @@
+use std::os::{
+    fd::FromRawFd,
+    linux::net::SocketAddrExt,
+    unix::net::{SocketAddr, UnixDatagram, UnixListener},
+};
+
+use suite_223b::{
+    utils::errors::{WatsonError, WatsonErrorKind},
+    watson_err,
+};
+
+/// First file descriptor passed by systemd (`SD_LISTEN_FDS_START`).
+const LISTEN_FDS_START: i32 = 3;
+
+/// Takes over the listening socket passed by systemd socket activation.
+///
+/// Mirrors `sd_listen_fds(3)`: the socket is only accepted if `LISTEN_PID` matches the current
+/// process. Only the first passed descriptor is used. The environment is left alone, child
+/// processes inherit the variables but have a different pid, so they ignore them.
+pub fn listen_fds() -> Option<UnixListener> {
+    let pid: u32 = std::env::var("LISTEN_PID").ok()?.parse().ok()?;
+    if pid != std::process::id() {
+        return None;
+    }
+
+    let count: i32 = std::env::var("LISTEN_FDS").ok()?.parse().ok()?;
+    if count < 1 {
+        return None;
+    }
+
+    // Mark the descriptor close-on-exec like sd_listen_fds does
+    unsafe {
+        libc::fcntl(LISTEN_FDS_START, libc::F_SETFD, libc::FD_CLOEXEC);
+    }
+
+    let listener = unsafe { UnixListener::from_raw_fd(LISTEN_FDS_START) };
+    listener.set_nonblocking(true).ok()?;
+    Some(listener)
+}
+
+/// Sends a state update (e.g. `READY=1`) to the service manager, mirroring `sd_notify(3)`.
+///
+/// Does nothing if the daemon was not started by systemd with `Type=notify`.
+pub fn notify(state: &str) -> Result<(), WatsonError> {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+    let path = path.to_string_lossy();
+
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+        None => SocketAddr::from_pathname(path.as_ref()),
+    }
+    .map_err(|e| watson_err!(WatsonErrorKind::StreamConnect, e.to_string()))?;
+
+    let socket = UnixDatagram::unbound()
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamBind, e.to_string()))?;
+    socket
+        .send_to_addr(state.as_bytes(), &addr)
+        .map_err(|e| watson_err!(WatsonErrorKind::StreamWrite, e.to_string()))?;
+
+    Ok(())
+}